@@ -1,23 +1,53 @@
-use crate::modes::oneliner::OnelinerModeParamsBuilder;
-use crate::modes::{
+use polysock::modes::oneliner::OnelinerModeParamsBuilder;
+use polysock::modes::{
     Command,
+    control::{ControlAddr, CtlCommand, ReplCommand},
+    daemon::{DaemonMode, DaemonModeCommand},
+    echo::{EchoMode, EchoModeCommand, EchoModeParamsBuilder},
+    examples::ExamplesCommand,
+    expect::{ExpectMode, ExpectModeCommand, ExpectStep, Stimulus},
+    fan_in::{FanInMode, FanInModeCommand, FanInModeParamsBuilder, FanInSourceConfig},
+    fan_out::{FanOutMode, FanOutModeCommand, FanOutModeParamsBuilder, FanOutSinkConfig},
+    generate::{GenerateCommand, GenerateTarget},
     oneliner::{OnelinerMode, OnelinerModeCommand},
+    probe::ProbeCommand,
+    report::{ReportCommand, parse_span},
+    script::{ScriptConfig, ScriptMode, ScriptModeCommand},
+    validate::{Endpoint, ValidateCommand, ValidateTarget},
 };
-use crate::sock::{
-    SocketFactory, SocketParams, TraceCanonicalDecoratorFactory, TraceInfoDecoratorFactory,
-    TraceRawDecoratorFactory,
-};
-use crate::sockets::{
-    tcp_client::TcpClientFactory, tcp_server::TcpServerFactory, terminal::SimpleTerminalFactory,
-    testgen::TestGenFactory, udp::SocketFactoryUDP,
+#[cfg(feature = "tui")]
+use polysock::modes::tui::{LogBuffer, TuiMode, TuiModeCommand};
+use polysock::sock::{
+    BindingOptionsBuilder, BindingStats, ByteSwapConfig, CaptureDecoratorFactory, CaptureFile,
+    ChecksumConfig, ChecksumDecoratorFactory, ChunkConfig, ChunkDecoratorFactory, Coalesce,
+    CompressConfig, CompressDecoratorFactory, DEFAULT_GRACE_PERIOD, DecoratorStack, DelayConfig,
+    DelayDecoratorFactory, DemuxConfig, DemuxDecoratorFactory, EncryptConfig, ExitCondition,
+    FaultConfig, FaultDecoratorFactory, FilterConfig, FilterDecoratorFactory, Framing, HalfDuplex,
+    HashAlgo, HashDecoratorFactory, HistoryRecorder, LatencyConfig, LatencyDecoratorFactory,
+    MonitorConfig, MonitorDecoratorFactory, ObfuscateConfig, OverflowPolicy, Profiles,
+    RateLimitConfig, RateLimitDecoratorFactory, RecordDecoratorFactory, Sandbox, SandboxBuilder,
+    SequenceConfig, SequenceDecoratorFactory, SocketFactory, SocketParams, StatsDecoratorFactory,
+    SummaryDecoratorFactory, TeeConfig, TeeDecoratorFactory, TraceCanonicalDecoratorFactory,
+    TraceColor, TraceFileConfigBuilder, TraceInfoDecoratorFactory, TraceRawDecoratorFactory,
+    SummaryFormat, TraceSide, TraceTimestamp, TransformBuilder, TransformDecoratorFactory, XonXoff,
+    set_summary_format, set_trace_color, set_trace_file, set_trace_timestamp,
 };
+use polysock::sock::pattern::BytePattern;
+use polysock::sockets::{plugin, registry::SocketRegistry};
 
 use clap::builder::PossibleValuesParser;
 use clap::{Parser, Subcommand, ValueEnum};
+use serde::de::DeserializeOwned;
 
-use std::collections::HashMap;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
+use std::thread;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::filter::LevelFilter;
 
 #[derive(Copy, Clone, ValueEnum)]
 enum ExchangeMode {
@@ -25,6 +55,43 @@ enum ExchangeMode {
     Bidir,
 }
 
+#[derive(Copy, Clone, ValueEnum)]
+enum Engine {
+    /// Thread per binding direction
+    Thread,
+    /// Tokio tasks (requires `async` feature)
+    Async,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// JSON object per line with the span context (e.g. for log shippers)
+    Json,
+}
+
 #[derive(clap::Args, Clone)]
 struct OnelinerArgs {
     /// Exchange mode
@@ -33,11 +100,14 @@ struct OnelinerArgs {
     /// Blocking input
     #[arg(short, long, default_value_t = false)]
     blocking: bool,
-    /// The first socket to bind
-    #[arg(short, long, value_parser = PossibleValuesParser::new(FACTORY_MAP.keys()))]
+    /// Binding engine
+    #[arg(value_enum, long, default_value_t = Engine::Thread)]
+    engine: Engine,
+    /// The first socket to bind (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
     from_dev: String,
-    /// The second socket to bind
-    #[arg(short, long, value_parser = PossibleValuesParser::new(FACTORY_MAP.keys()))]
+    /// The second socket to bind (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
     to_dev: String,
     /// The first socket parameters (JSON format)
     #[arg(long)]
@@ -54,18 +124,495 @@ struct OnelinerArgs {
     /// Socket data tracing (in canonical format)
     #[arg(long, default_value_t = false)]
     trace_canon: bool,
+    /// Digest of all received and written data, printed on close
+    #[arg(value_enum, long)]
+    trace_hash: Option<HashAlgo>,
+    /// Print one aggregate traffic line per direction every N seconds
+    #[arg(long, value_name = "SECS")]
+    trace_summary: Option<u64>,
+    /// Print throughput (B/s, msg/s) per direction every N seconds and totals on close
+    #[arg(long, value_name = "SECS")]
+    trace_stats: Option<u64>,
+    /// Track sequence counter of the received frames (JSON format), gaps, duplicates and reordering are printed
+    #[arg(long, value_parser = parse_json::<SequenceConfig>)]
+    trace_seq: Option<SequenceConfig>,
+    /// Protocol state machine monitor (JSON format), violations are printed
+    #[arg(long, value_parser = parse_json::<MonitorConfig>)]
+    trace_monitor: Option<MonitorConfig>,
+    /// Write every read and written chunk to PCAPNG file (socket is an interface)
+    #[arg(long, value_name = "FILE")]
+    capture: Option<PathBuf>,
+    /// Record timestamped chunks read from the first socket to FILE (played by `replay` socket)
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+    /// Append binding metrics to CSV file every interval (see `polysock report`)
+    #[arg(long, value_name = "FILE")]
+    stats_db: Option<PathBuf>,
+    /// Interval of the metrics rows in seconds
+    #[arg(long, value_name = "SECS", default_value_t = 60, requires = "stats_db")]
+    stats_db_interval: u64,
+    /// Copy data read from and written to the first socket to a sink socket (JSON format)
+    #[arg(long, value_parser = parse_json::<TeeConfig>)]
+    tee_from: Option<TeeConfig>,
+    /// Copy data read from and written to the second socket to a sink socket (JSON format)
+    #[arg(long, value_parser = parse_json::<TeeConfig>)]
+    tee_to: Option<TeeConfig>,
+    /// Split writes to the first socket into chunks of max size, coalesce small reads (JSON format)
+    #[arg(long, value_parser = parse_json::<ChunkConfig>)]
+    chunk_from: Option<ChunkConfig>,
+    /// Split writes to the second socket into chunks of max size, coalesce small reads (JSON format)
+    #[arg(long, value_parser = parse_json::<ChunkConfig>)]
+    chunk_to: Option<ChunkConfig>,
+    /// Append CRC to frames written to and verify it on frames read from the first socket (JSON format)
+    #[arg(long, value_parser = parse_json::<ChecksumConfig>)]
+    checksum_from: Option<ChecksumConfig>,
+    /// Append CRC to frames written to and verify it on frames read from the second socket (JSON format)
+    #[arg(long, value_parser = parse_json::<ChecksumConfig>)]
+    checksum_to: Option<ChecksumConfig>,
+    /// Shape traffic of the first socket with token bucket (JSON format)
+    #[arg(long, value_parser = parse_json::<RateLimitConfig>)]
+    rate_limit_from: Option<RateLimitConfig>,
+    /// Shape traffic of the second socket with token bucket (JSON format)
+    #[arg(long, value_parser = parse_json::<RateLimitConfig>)]
+    rate_limit_to: Option<RateLimitConfig>,
+    /// Hold chunks read from the first socket for latency with jitter (JSON format)
+    #[arg(long, value_parser = parse_json::<DelayConfig>)]
+    delay_from: Option<DelayConfig>,
+    /// Hold chunks read from the second socket for latency with jitter (JSON format)
+    #[arg(long, value_parser = parse_json::<DelayConfig>)]
+    delay_to: Option<DelayConfig>,
+    /// Duplicate and reorder chunks read from the first socket (JSON format)
+    #[arg(long, value_parser = parse_json::<FaultConfig>)]
+    fault_from: Option<FaultConfig>,
+    /// Duplicate and reorder chunks read from the second socket (JSON format)
+    #[arg(long, value_parser = parse_json::<FaultConfig>)]
+    fault_to: Option<FaultConfig>,
+    /// Measure round-trip latency of the first socket link: tag writes or reflect tags (JSON format)
+    #[arg(long, value_parser = parse_json::<LatencyConfig>)]
+    latency_from: Option<LatencyConfig>,
+    /// Measure round-trip latency of the second socket link: tag writes or reflect tags (JSON format)
+    #[arg(long, value_parser = parse_json::<LatencyConfig>)]
+    latency_to: Option<LatencyConfig>,
+    /// Deobfuscate data read from and obfuscate data written to the first socket (JSON format)
+    #[arg(long, value_parser = parse_json::<ObfuscateConfig>)]
+    obfuscate_from: Option<ObfuscateConfig>,
+    /// Deobfuscate data read from and obfuscate data written to the second socket (JSON format)
+    #[arg(long, value_parser = parse_json::<ObfuscateConfig>)]
+    obfuscate_to: Option<ObfuscateConfig>,
+    /// Encrypt data written to and decrypt data read from the first socket with PSK (JSON format)
+    #[arg(long, value_parser = parse_json::<EncryptConfig>)]
+    encrypt_from: Option<EncryptConfig>,
+    /// Encrypt data written to and decrypt data read from the second socket with PSK (JSON format)
+    #[arg(long, value_parser = parse_json::<EncryptConfig>)]
+    encrypt_to: Option<EncryptConfig>,
+    /// Compress data written to and decompress data read from the first socket (JSON format)
+    #[arg(long, value_parser = parse_json::<CompressConfig>)]
+    compress_from: Option<CompressConfig>,
+    /// Compress data written to and decompress data read from the second socket (JSON format)
+    #[arg(long, value_parser = parse_json::<CompressConfig>)]
+    compress_to: Option<CompressConfig>,
+    /// Drop chunks of the first socket, which don't pass byte pattern or regex rules (JSON format)
+    #[arg(long, value_parser = parse_json::<FilterConfig>)]
+    filter_from: Option<FilterConfig>,
+    /// Drop chunks of the second socket, which don't pass byte pattern or regex rules (JSON format)
+    #[arg(long, value_parser = parse_json::<FilterConfig>)]
+    filter_to: Option<FilterConfig>,
+    /// Swap byte order of fixed-width words read from and written to the first socket (JSON format)
+    #[arg(long, value_parser = parse_json::<ByteSwapConfig>)]
+    byte_swap_from: Option<ByteSwapConfig>,
+    /// Swap byte order of fixed-width words read from and written to the second socket (JSON format)
+    #[arg(long, value_parser = parse_json::<ByteSwapConfig>)]
+    byte_swap_to: Option<ByteSwapConfig>,
+    /// Route frames read from the first socket to sink sockets by header field (JSON format)
+    #[arg(long, value_parser = parse_json::<DemuxConfig>)]
+    demux: Option<DemuxConfig>,
+    /// Write tracing output to FILE instead of STDOUT (e.g. to use it with stdio socket)
+    #[arg(long, value_name = "FILE")]
+    trace_file: Option<PathBuf>,
+    /// Rotate the trace file when it would exceed the size
+    #[arg(long, value_name = "BYTES", requires = "trace_file")]
+    trace_file_max_size: Option<u64>,
+    /// Rotate the trace file every N seconds
+    #[arg(long, value_name = "SECS", requires = "trace_file")]
+    trace_file_max_age: Option<u64>,
+    /// Number of rotated trace files to keep (FILE.1 is the newest one)
+    #[arg(long, value_name = "N", default_value_t = 5, requires = "trace_file")]
+    trace_file_keep: usize,
+    /// Prefix trace lines with the timestamp, binding direction (from→to) and socket
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    trace_ts: Option<TraceTimestamp>,
+    /// Trace colors: received data is green, written data is cyan, errors are red
+    #[arg(value_enum, long, default_value_t = TraceColor::Auto)]
+    color: TraceColor,
     /// From device tracing off
     #[arg(long, default_value_t = false)]
     trace_from_off: bool,
     /// To device tracing off
     #[arg(long, default_value_t = false)]
     trace_to_off: bool,
+    /// Decorators of the first socket applied in order instead of the tracing flags:
+    /// short forms (e.g. stats:5,rate:960,trace-canon) or JSON array
+    #[arg(long, value_name = "LIST", value_parser = parse_stack, conflicts_with = "trace_from_off")]
+    from_decorators: Option<DecoratorStack>,
+    /// Decorators of the second socket applied in order instead of the tracing flags
+    #[arg(long, value_name = "LIST", value_parser = parse_stack, conflicts_with = "trace_to_off")]
+    to_decorators: Option<DecoratorStack>,
+    /// Capacity of the buffer between read and write sides of a binding direction
+    #[arg(long, value_name = "BYTES")]
+    buffer_size: Option<usize>,
+    /// Policy applied when the binding buffer is full
+    #[arg(value_enum, long, default_value_t = OverflowPolicy::Block)]
+    overflow_policy: OverflowPolicy,
+    /// Bytes per socket read of both binding directions, not more than the buffer size
+    /// [default: 1024]
+    #[arg(long, value_name = "BYTES")]
+    read_chunk: Option<usize>,
+    /// Max time in microseconds to wait for incoming data before the binding state is
+    /// rechecked, sockets without readiness polling sleep up to 1 ms [default: 50000]
+    #[arg(long, value_name = "US")]
+    poll_interval: Option<u64>,
+    /// --read-chunk of the tx direction (from the second socket back to the first one)
+    /// of the bidirectional binding
+    #[arg(long, value_name = "BYTES")]
+    read_chunk_tx: Option<usize>,
+    /// --poll-interval of the tx direction of the bidirectional binding
+    #[arg(long, value_name = "US")]
+    poll_interval_tx: Option<u64>,
+    /// Collect forwarded data and write it at once, when the size is reached or the timeout
+    /// expires (JSON format), e.g. '{ "size": 512, "timeout_ms": 10 }'
+    #[arg(long, value_parser = parse_json::<Coalesce>)]
+    coalesce: Option<Coalesce>,
+    /// Bridging of message sockets (e.g. udp) with stream ones (e.g. tcp-client)
+    #[arg(value_enum, long, default_value_t = Framing::Raw)]
+    framing: Framing,
+    /// Timeout of blocking socket reads in milliseconds
+    #[arg(long, value_name = "MS")]
+    read_timeout: Option<u64>,
+    /// Timeout of blocking socket writes in milliseconds, expired write stops the binding
+    #[arg(long, value_name = "MS")]
+    write_timeout: Option<u64>,
+    /// Time in milliseconds to flush queued data and close connections on stop (0 - abrupt close)
+    #[arg(long, value_name = "MS")]
+    grace_period: Option<u64>,
+    /// WASM module transforming data read from and written to both sockets (requires `wasm` feature)
+    #[arg(long, value_name = "PATH")]
+    transform_wasm: Option<PathBuf>,
+    /// Exit condition of the binding (JSON format), e.g. '{ "any": [ { "bytes": 1024 }, { "idle": 5 } ] }'
+    #[arg(long, value_parser = parse_json::<ExitCondition>)]
+    exit_when: Option<ExitCondition>,
+    /// Control socket for the ctl requests: path of the Unix socket or `ip:port` of the TCP one
+    #[arg(long, value_name = "ADDR")]
+    control: Option<ControlAddr>,
+    /// Only one direction of the bidir binding transmits at a time (JSON format),
+    /// e.g. '{ "turnaround_ms": 5, "terminator": "0x0a" }'
+    #[arg(long, value_parser = parse_json::<HalfDuplex>)]
+    half_duplex: Option<HalfDuplex>,
+    /// Strip XON/XOFF bytes read from the socket(s) and pause or resume writes to it
+    #[arg(value_enum, long, value_name = "SIDE")]
+    xon_xoff: Option<XonXoff>,
+    /// Evaluate untrusted configuration: cap runtime and traffic, deny privileged ports,
+    /// plugins and files outside the sandbox directory
+    #[arg(long, default_value_t = false)]
+    sandbox: bool,
+    /// Max runtime in sandbox
+    #[arg(long, value_name = "SECS", default_value_t = 60, requires = "sandbox")]
+    sandbox_max_time: u64,
+    /// Max transferred bytes in sandbox
+    #[arg(long, value_name = "BYTES", default_value_t = 10 * 1024 * 1024, requires = "sandbox")]
+    sandbox_max_bytes: u64,
+    /// The only directory, where files can be accessed in sandbox
+    #[arg(long, value_name = "DIR", default_value = ".", requires = "sandbox")]
+    sandbox_dir: PathBuf,
+}
+
+/// Endpoint presets of the user, loaded before the arguments are parsed,
+/// since `@name` of the profiles are possible values of `--from-dev`,
+/// `--to-dev` and `--dev`.
+static PROFILES: LazyLock<Profiles> = LazyLock::new(|| {
+    let Some(path) = Profiles::default_path() else {
+        return Profiles::default();
+    };
+    Profiles::load(&path).unwrap_or_else(|e| {
+        eprintln!("Profiles loading failed: {e}");
+        process::exit(1)
+    })
+});
+
+/// Socket types and `@name` of the profiles.
+fn dev_names() -> Vec<String> {
+    let profiles = PROFILES.names().map(|name| format!("@{name}"));
+    SocketRegistry::global()
+        .names()
+        .into_iter()
+        .chain(profiles)
+        .collect()
+}
+
+/// Parses JSON command line argument into the structure.
+fn parse_json<T: DeserializeOwned>(s: &str) -> Result<T, String> {
+    serde_json::from_str(s).map_err(|e| e.to_string())
+}
+
+/// Parses non-negative number of seconds.
+fn parse_secs(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(secs),
+        Ok(_) => Err("Seconds must be a non-negative number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses `LABEL=FILE` argument of the parameters file.
+fn parse_params_file(s: &str) -> Result<(String, PathBuf), String> {
+    let (label, path) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected LABEL=FILE, got {s}"))?;
+    Ok((label.to_string(), PathBuf::from(path)))
+}
+
+/// Parses `LABEL.FIELD=VALUE` override of the socket parameter.
+fn parse_override(s: &str) -> Result<(String, String, String), String> {
+    let err = || format!("Expected LABEL.FIELD=VALUE, got {s}");
+    let (path, value) = s.split_once('=').ok_or_else(err)?;
+    let (label, field) = path
+        .split_once('.')
+        .filter(|(_, f)| !f.is_empty())
+        .ok_or_else(err)?;
+    Ok((label.to_string(), field.to_string(), value.to_string()))
+}
+
+/// Parses decorator stack command line argument.
+fn parse_stack(s: &str) -> Result<DecoratorStack, String> {
+    DecoratorStack::parse(s).map_err(|e| e.to_string())
+}
+
+#[derive(clap::Args)]
+struct FanOutArgs {
+    /// Blocking input
+    #[arg(short, long, default_value_t = false)]
+    blocking: bool,
+    /// The socket mirrored to the sinks (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
+    from_dev: String,
+    /// The socket parameters (JSON format)
+    #[arg(long)]
+    from_params: Option<SocketParams>,
+    /// Decorators of the socket: short forms (e.g. stats:5,trace-raw) or JSON array
+    #[arg(long, value_name = "LIST", value_parser = parse_stack)]
+    from_decorators: Option<DecoratorStack>,
+    /// Sink with its own decorators (JSON format, can be repeated), e.g.
+    /// '{ "type": "tcp-client", "params": { "ip_dst": "127.0.0.1", "port_dst": 1234 }, "decorators": [ "trace_info" ] }'
+    #[arg(long = "to", value_name = "SINK", required = true, value_parser = parse_json::<FanOutSinkConfig>)]
+    sinks: Vec<FanOutSinkConfig>,
+    /// Exit condition of the binding (JSON format)
+    #[arg(long, value_parser = parse_json::<ExitCondition>)]
+    exit_when: Option<ExitCondition>,
+    /// Capacity of the buffer of every sink
+    #[arg(long, value_name = "BYTES")]
+    buffer_size: Option<usize>,
+    /// Policy applied when the buffer of a sink is full (`block` slows down all sinks)
+    #[arg(value_enum, long, default_value_t = OverflowPolicy::Block)]
+    overflow_policy: OverflowPolicy,
+    /// Bridging of message sockets (e.g. udp) with stream ones (e.g. tcp-client)
+    #[arg(value_enum, long, default_value_t = Framing::Raw)]
+    framing: Framing,
+    /// Timeout of blocking socket reads in milliseconds
+    #[arg(long, value_name = "MS")]
+    read_timeout: Option<u64>,
+    /// Timeout of blocking socket writes in milliseconds, expired write drops the sink
+    #[arg(long, value_name = "MS")]
+    write_timeout: Option<u64>,
+    /// Time in milliseconds to flush queued data and close connections on stop (0 - abrupt close)
+    #[arg(long, value_name = "MS")]
+    grace_period: Option<u64>,
+}
+
+#[derive(clap::Args)]
+struct FanInArgs {
+    /// Source with its own decorators and tag (JSON format, can be repeated), e.g.
+    /// '{ "type": "udp", "params": { "port_local": 5150 }, "tag": "gps: " }'
+    #[arg(long = "from", value_name = "SOURCE", required = true, value_parser = parse_json::<FanInSourceConfig>)]
+    sources: Vec<FanInSourceConfig>,
+    /// The socket merging the sources (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
+    to_dev: String,
+    /// The socket parameters (JSON format)
+    #[arg(long)]
+    to_params: Option<SocketParams>,
+    /// Decorators of the socket: short forms (e.g. stats:5,trace-raw) or JSON array
+    #[arg(long, value_name = "LIST", value_parser = parse_stack)]
+    to_decorators: Option<DecoratorStack>,
+    /// Exit condition of the binding (JSON format)
+    #[arg(long, value_parser = parse_json::<ExitCondition>)]
+    exit_when: Option<ExitCondition>,
+    /// Capacity of the buffer of the socket
+    #[arg(long, value_name = "BYTES")]
+    buffer_size: Option<usize>,
+    /// Policy applied when the buffer of the socket is full
+    #[arg(value_enum, long, default_value_t = OverflowPolicy::Block)]
+    overflow_policy: OverflowPolicy,
+    /// Bridging of message sockets (e.g. udp) with stream ones (e.g. tcp-client)
+    #[arg(value_enum, long, default_value_t = Framing::Raw)]
+    framing: Framing,
+    /// Timeout of blocking socket reads in milliseconds
+    #[arg(long, value_name = "MS")]
+    read_timeout: Option<u64>,
+    /// Timeout of blocking socket writes in milliseconds
+    #[arg(long, value_name = "MS")]
+    write_timeout: Option<u64>,
+    /// Time in milliseconds to flush queued data and close connections on stop (0 - abrupt close)
+    #[arg(long, value_name = "MS")]
+    grace_period: Option<u64>,
+}
+
+#[derive(clap::Args)]
+struct ChainArgs {
+    /// Exchange mode (data read from the sink passes the stages back in reverse order)
+    #[arg(value_enum, short, long, default_value_t = ExchangeMode::Unidir)]
+    exchange_mode: ExchangeMode,
+    /// Blocking input
+    #[arg(short, long, default_value_t = false)]
+    blocking: bool,
+    /// The source of the chain (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
+    from_dev: String,
+    /// The sink of the chain (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
+    to_dev: String,
+    /// The source parameters (JSON format)
+    #[arg(long)]
+    from_params: Option<SocketParams>,
+    /// The sink parameters (JSON format)
+    #[arg(long)]
+    to_params: Option<SocketParams>,
+    /// Decorators of the source: short forms (e.g. stats:5,trace-raw) or JSON array
+    #[arg(long, value_name = "LIST", value_parser = parse_stack)]
+    from_decorators: Option<DecoratorStack>,
+    /// Decorators of the sink, applied after all stages
+    #[arg(long, value_name = "LIST", value_parser = parse_stack)]
+    to_decorators: Option<DecoratorStack>,
+    /// Transform stages in the order data passes them: short forms or JSON array (can be repeated)
+    #[arg(long = "stage", value_name = "LIST", required = true, value_parser = parse_stack)]
+    stages: Vec<DecoratorStack>,
+    /// Exit condition of the binding (JSON format)
+    #[arg(long, value_parser = parse_json::<ExitCondition>)]
+    exit_when: Option<ExitCondition>,
+    /// Capacity of the buffer of every direction
+    #[arg(long, value_name = "BYTES")]
+    buffer_size: Option<usize>,
+    /// Policy applied when the buffer is full
+    #[arg(value_enum, long, default_value_t = OverflowPolicy::Block)]
+    overflow_policy: OverflowPolicy,
+    /// Bridging of message sockets (e.g. udp) with stream ones (e.g. tcp-client)
+    #[arg(value_enum, long, default_value_t = Framing::Raw)]
+    framing: Framing,
+    /// Time in milliseconds to flush queued data and close connections on stop (0 - abrupt close)
+    #[arg(long, value_name = "MS")]
+    grace_period: Option<u64>,
+}
+
+#[derive(clap::Args)]
+struct EchoArgs {
+    /// The socket reflecting received data (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
+    dev: String,
+    /// The socket parameters (JSON format)
+    #[arg(short, long)]
+    params: Option<SocketParams>,
+    /// Decorators of the socket: short forms (e.g. stats:5,trace-raw) or JSON array
+    #[arg(long, value_name = "LIST", value_parser = parse_stack)]
+    decorators: Option<DecoratorStack>,
+    /// Exit condition of the binding (JSON format)
+    #[arg(long, value_parser = parse_json::<ExitCondition>)]
+    exit_when: Option<ExitCondition>,
+    /// Time in milliseconds to flush queued data and close connections on stop (0 - abrupt close)
+    #[arg(long, value_name = "MS")]
+    grace_period: Option<u64>,
+}
+
+#[derive(clap::Args)]
+struct ExpectArgs {
+    /// The socket connected to the tested device (socket type or @profile)
+    #[arg(short, long, value_parser = PossibleValuesParser::new(dev_names()))]
+    dev: String,
+    /// The socket parameters (JSON format)
+    #[arg(short, long)]
+    params: Option<SocketParams>,
+    /// Decorators of the socket: short forms (e.g. stats:5,trace-raw) or JSON array
+    #[arg(long, value_name = "LIST", value_parser = parse_stack)]
+    decorators: Option<DecoratorStack>,
+    /// Step of the scenario (JSON format), steps run in the given order, e.g.
+    /// '{ "send": { "text": "AT\r\n" }, "expect": { "regex": "OK\\r\\n" }, "timeout_ms": 500 }'
+    #[arg(long = "step", value_name = "STEP", required = true, value_parser = parse_json::<ExpectStep>)]
+    steps: Vec<ExpectStep>,
+}
+
+#[derive(clap::Args)]
+struct ProbeArgs {
+    /// Targets in host:port format, e.g. 10.0.0.7:22 or [fe80::1]:80
+    #[arg(value_name = "TARGET", required = true)]
+    targets: Vec<String>,
+    /// Time in milliseconds given to connect and to read the banner
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    timeout: u64,
+    /// Read the first data sent by the target (e.g. SSH version)
+    #[arg(long, default_value_t = false)]
+    banner: bool,
+    /// Payload sent to the target before the banner is read (JSON format), e.g.
+    /// '{ "text": "HEAD / HTTP/1.0\r\n\r\n" }'
+    #[arg(long, value_name = "PAYLOAD", value_parser = parse_json::<Stimulus>)]
+    send: Option<Stimulus>,
+}
+
+#[derive(clap::Args)]
+struct ScriptArgs {
+    /// Script with the links (JSON format), e.g.
+    /// '{ "links": [ { "name": "gps", "from": { "type": "udp", "params": { "port_local": 5150 } }, "to": { "type": "stdio" } } ] }'
+    #[arg(value_name = "FILE")]
+    path: PathBuf,
+    /// Apply changes of the script without restarting the unchanged links
+    /// (the links are run until stopped, even if all of them are finished)
+    #[arg(long, default_value_t = false)]
+    watch: bool,
+    /// Binding engine of the links
+    #[arg(value_enum, long, default_value_t = Engine::Thread, conflicts_with = "watch")]
+    engine: Engine,
+}
+
+/// Default control socket of the daemon.
+const DEFAULT_CONTROL_SOCKET: &str = "/tmp/polysock.sock";
+
+#[derive(clap::Args)]
+struct DaemonArgs {
+    /// Control socket: path of the Unix socket or `ip:port` of the TCP one
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_CONTROL_SOCKET)]
+    control: ControlAddr,
+    /// Script with the links bound when the daemon starts (see the script mode)
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+    /// Apply changes of the script without restarting the unchanged links
+    #[arg(long, default_value_t = false, requires = "script")]
+    watch: bool,
+}
+
+#[derive(clap::Args)]
+struct TuiArgs {
+    /// Script with the links (JSON format), the same as of the script mode
+    #[arg(value_name = "FILE")]
+    path: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct ValidateArgs {
+    /// Script with the links (JSON format), the same as of the script mode
+    #[arg(value_name = "FILE")]
+    path: PathBuf,
 }
 
 #[derive(clap::Args)]
 struct InfoArgs {
     /// Socket type to print information
-    #[arg(short, long, value_parser = PossibleValuesParser::new(FACTORY_MAP.keys()))]
+    #[arg(short, long, value_parser = PossibleValuesParser::new(SocketRegistry::global().names()))]
     ty: String,
     /// Without JSON schema
     #[arg(long, default_value_t = false)]
@@ -75,16 +622,238 @@ struct InfoArgs {
     no_examples: bool,
 }
 
+#[derive(clap::Args)]
+struct CtlArgs {
+    /// Control socket of the running polysock: path of the Unix socket or `ip:port` of the TCP one
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_CONTROL_SOCKET)]
+    control: ControlAddr,
+    /// Request to the control socket
+    #[command(subcommand)]
+    request: CtlRequest,
+}
+
+#[derive(clap::Args)]
+struct ReplArgs {
+    /// Control socket of the running polysock: path of the Unix socket or `ip:port` of the TCP one
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_CONTROL_SOCKET)]
+    control: ControlAddr,
+}
+
+/// Line of the REPL: one request to the control socket.
+#[derive(Parser)]
+#[command(no_binary_name = true, disable_version_flag = true)]
+struct ReplLine {
+    #[command(subcommand)]
+    request: CtlRequest,
+}
+
+#[derive(Subcommand)]
+enum CtlRequest {
+    /// Bind the link, e.g. '{ "name": "gps", "from": { "type": "udp" }, "to": { "type": "stdio" } }'
+    Add {
+        /// Link in the format of the script link (JSON)
+        #[arg(value_parser = parse_json::<serde_json::Value>)]
+        link: serde_json::Value,
+    },
+    /// Stop the link and forget it
+    Remove {
+        /// Name of the link
+        name: String,
+    },
+    /// Print statistics of the link (of every link, if the name isn't set)
+    Stats {
+        /// Name of the link
+        name: Option<String>,
+    },
+    /// Print current digests of the sockets with the hash decorator
+    Digests,
+    /// Inject the labeled marker into the trace output and the captures
+    Mark {
+        /// Label of the marker, e.g. 'start test 7'
+        label: String,
+    },
+    /// Reopen the socket with the changed parameters, e.g. 'from { "port_local": 5151 }'
+    Reopen {
+        /// Socket of the binding
+        #[arg(value_parser = ["from", "to"])]
+        side: String,
+        /// Parameters merged over the current ones (JSON), the same ones if not set
+        #[arg(value_parser = parse_json::<serde_json::Value>)]
+        params: Option<serde_json::Value>,
+        /// Link of the daemon, which the socket belongs to
+        #[arg(long, value_name = "NAME")]
+        link: Option<String>,
+    },
+    /// Stop every link and the daemon
+    Shutdown,
+}
+
+impl CtlRequest {
+    /// Request of the control API.
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::Add { link } => serde_json::json!({ "cmd": "add", "link": link }),
+            Self::Remove { name } => serde_json::json!({ "cmd": "remove", "name": name }),
+            Self::Stats { name } => serde_json::json!({ "cmd": "stats", "name": name }),
+            Self::Digests => serde_json::json!({ "cmd": "digests" }),
+            Self::Mark { label } => serde_json::json!({ "cmd": "mark", "label": label }),
+            Self::Reopen { side, params, link } => {
+                let mut request =
+                    serde_json::json!({ "cmd": "reopen", "side": side, "params": params });
+                if let Some(link) = link {
+                    request["name"] = link.clone().into();
+                }
+                request
+            }
+            Self::Shutdown => serde_json::json!({ "cmd": "shutdown" }),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct GenerateArgs {
+    /// Manifest format
+    #[arg(value_enum)]
+    target: GenerateTarget,
+    /// Service name
+    #[arg(long, default_value = "polysock")]
+    name: String,
+    /// Container image with polysock installed (compose only)
+    #[arg(long, default_value = "polysock:latest")]
+    image: String,
+    /// Validated polysock command to run, e.g. `-- oneliner -f udp -t stdio`
+    #[arg(last = true, required = true)]
+    command: Vec<String>,
+}
+
+#[derive(clap::Args)]
+struct ExamplesArgs {
+    /// Example to run ("all" to run every example), examples are listed if it's not set
+    name: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct ReportArgs {
+    /// CSV file written with `oneliner --stats-db`
+    #[arg(long, value_name = "FILE")]
+    db: PathBuf,
+    /// Reported period, e.g. 90s, 30m, 24h or 7d
+    #[arg(long, default_value = "24h", value_parser = parse_span)]
+    since: Duration,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Oneliner mode (command line prameters management)
-    Oneliner(OnelinerArgs),
+    Oneliner(Box<OnelinerArgs>),
+    /// Fan-out mode (one socket mirrored to several sinks)
+    FanOut(Box<FanOutArgs>),
+    /// Fan-in mode (several sources merged into one socket)
+    FanIn(Box<FanInArgs>),
     /// Print configuration parameter's structures for sockets information
     Info(InfoArgs),
-    /// Not implemented yet
-    Script {},
-    /// Not implemented yet
-    Repl {},
+    /// Send the request to the control socket of the running polysock
+    Ctl(CtlArgs),
+    /// Generate systemd unit or docker-compose service running the command
+    Generate(GenerateArgs),
+    /// List and run self-contained demo scenarios (smoke tests on loopback)
+    Examples(ExamplesArgs),
+    /// Summarize throughput, uptime and errors recorded with `oneliner --stats-db`
+    Report(ReportArgs),
+    /// Echo mode (received data is sent back to its sender)
+    Echo(EchoArgs),
+    /// Expect mode (stimuli are sent and responses are checked, exit code 1 on mismatch)
+    Expect(ExpectArgs),
+    /// Probe mode (TCP reachability of the targets, exit code 1 if any isn't open)
+    Probe(ProbeArgs),
+    /// Chain mode (source → transform stages → sink pipeline)
+    Chain(Box<ChainArgs>),
+    /// Script mode (several named links bound in one process)
+    Script(ScriptArgs),
+    /// Daemon mode (links added and removed at runtime through the control socket)
+    Daemon(DaemonArgs),
+    /// Terminal UI monitoring the links of the script (live stats, hexdumps, pause and decorator toggles)
+    Tui(TuiArgs),
+    /// Check the script (socket types and parameters) without opening anything
+    Validate(ValidateArgs),
+    /// Interactive client of the control socket (every line is a `ctl` request, e.g. 'mark test')
+    Repl(ReplArgs),
+}
+
+impl Commands {
+    /// Exit condition of the mode binding the sockets, global exit options
+    /// are added to it.
+    fn exit_when_mut(&mut self) -> Option<&mut Option<ExitCondition>> {
+        match self {
+            Self::Oneliner(args) => Some(&mut args.exit_when),
+            Self::FanOut(args) => Some(&mut args.exit_when),
+            Self::FanIn(args) => Some(&mut args.exit_when),
+            Self::Chain(args) => Some(&mut args.exit_when),
+            Self::Echo(args) => Some(&mut args.exit_when),
+            _ => None,
+        }
+    }
+    /// Sockets set on the command line (type and parameters) with their
+    /// labels used by `--params-file`, `--set` and environment variables.
+    fn endpoints_mut(&mut self) -> Vec<(&'static str, &mut String, &mut Option<SocketParams>)> {
+        match self {
+            Self::Oneliner(args) => vec![
+                ("from", &mut args.from_dev, &mut args.from_params),
+                ("to", &mut args.to_dev, &mut args.to_params),
+            ],
+            Self::Chain(args) => vec![
+                ("from", &mut args.from_dev, &mut args.from_params),
+                ("to", &mut args.to_dev, &mut args.to_params),
+            ],
+            Self::FanOut(args) => vec![("from", &mut args.from_dev, &mut args.from_params)],
+            Self::FanIn(args) => vec![("to", &mut args.to_dev, &mut args.to_params)],
+            Self::Echo(args) => vec![("dev", &mut args.dev, &mut args.params)],
+            Self::Expect(args) => vec![("dev", &mut args.dev, &mut args.params)],
+            _ => Vec::new(),
+        }
+    }
+    /// Configuration checked instead of execution with `--dry-run`.
+    fn validate_target(&self) -> ValidateTarget {
+        let endpoint = |label: &str, ty: &str, params: &Option<SocketParams>| {
+            Endpoint::new(label, ty, params.clone().unwrap_or_default())
+        };
+        let endpoints = match self {
+            Self::Oneliner(args) => vec![
+                endpoint("from", &args.from_dev, &args.from_params),
+                endpoint("to", &args.to_dev, &args.to_params),
+            ],
+            Self::Chain(args) => vec![
+                endpoint("from", &args.from_dev, &args.from_params),
+                endpoint("to", &args.to_dev, &args.to_params),
+            ],
+            Self::FanOut(args) => {
+                let sinks = args.sinks.iter().enumerate().map(|(i, sink)| {
+                    Endpoint::new(&format!("to[{i}]"), sink.socket_type(), sink.sink_params())
+                });
+                std::iter::once(endpoint("from", &args.from_dev, &args.from_params))
+                    .chain(sinks)
+                    .collect()
+            }
+            Self::FanIn(args) => {
+                let sources = args.sources.iter().enumerate().map(|(i, src)| {
+                    Endpoint::new(&format!("from[{i}]"), src.socket_type(), src.source_params())
+                });
+                sources
+                    .chain([endpoint("to", &args.to_dev, &args.to_params)])
+                    .collect()
+            }
+            Self::Echo(args) => vec![endpoint("dev", &args.dev, &args.params)],
+            Self::Expect(args) => vec![endpoint("dev", &args.dev, &args.params)],
+            Self::Script(ScriptArgs { path, .. })
+            | Self::Tui(TuiArgs { path })
+            | Self::Validate(ValidateArgs { path })
+            | Self::Daemon(DaemonArgs {
+                script: Some(path), ..
+            }) => return ValidateTarget::Script(path.clone()),
+            _ => Vec::new(),
+        };
+        ValidateTarget::Endpoints(endpoints)
+    }
 }
 
 #[derive(Parser)]
@@ -93,51 +862,112 @@ pub struct PolySockArgs {
     /// Subcommand to execute
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Shared library with additional socket types (can be repeated)
+    #[arg(long, global = true, value_name = "PATH")]
+    #[allow(dead_code)] // Loaded before parsing, see `load_plugins`
+    plugin: Vec<PathBuf>,
+    /// Directory of plugins to load [env: POLYSOCK_PLUGIN_DIR] [default: /usr/lib/polysock/plugins]
+    #[arg(long, global = true, value_name = "DIR")]
+    #[allow(dead_code)]
+    plugin_dir: Option<PathBuf>,
+    /// Level of the log messages written to STDERR [default: RUST_LOG or info]
+    #[arg(value_enum, long, global = true, value_name = "LEVEL")]
+    log_level: Option<LogLevel>,
+    /// Format of the log messages
+    #[arg(value_enum, long, global = true, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Format of the summary of every binding reported on exit
+    #[arg(value_enum, long, global = true, default_value_t = SummaryFormat::Text)]
+    summary: SummaryFormat,
+    /// Stop the binding when the bytes are transferred (all directions)
+    #[arg(long, global = true, value_name = "BYTES")]
+    exit_after_bytes: Option<u64>,
+    /// Stop the binding when the chunks are transferred (all directions)
+    #[arg(long, global = true, value_name = "COUNT")]
+    exit_after_messages: Option<u64>,
+    /// Stop the binding when the seconds elapsed since its start
+    #[arg(long, global = true, value_name = "SECS", value_parser = parse_secs)]
+    exit_after_time: Option<f64>,
+    /// Close the binding when no data was transferred in any direction for the seconds
+    #[arg(long, global = true, value_name = "SECS", value_parser = parse_secs)]
+    idle_timeout: Option<f64>,
+    /// Stop the binding when the pattern is read (JSON format), e.g. '{ "text": "BYE" }'
+    #[arg(long, global = true, value_name = "PATTERN", value_parser = parse_json::<BytePattern>)]
+    exit_on_pattern: Option<BytePattern>,
+    /// Parse the sockets parameters and report errors without opening anything
+    #[arg(long, global = true, default_value_t = false)]
+    dry_run: bool,
+    /// Socket parameters loaded from JSON file (LABEL is from, to or dev), merged over --*-params
+    #[arg(long, global = true, value_name = "LABEL=FILE", value_parser = parse_params_file)]
+    params_file: Vec<(String, PathBuf)>,
+    /// Socket parameter override, e.g. from.pat.size=200 (value is JSON or a string), applied last
+    #[arg(long = "set", global = true, value_name = "LABEL.FIELD=VALUE", value_parser = parse_override)]
+    overrides: Vec<(String, String, String)>,
 }
 
-type FactoryCallback = Box<dyn Fn() -> Box<dyn SocketFactory> + Send + Sync>;
-macro_rules! factory_callback_create {
-    ($factory: expr) => {
-        Box::new(|| Box::new($factory) as Box<dyn SocketFactory>) as FactoryCallback
-    };
-}
+/// Prefix of environment variables with socket parameters, followed by
+/// the label of the socket, e.g. `POLYSOCK_FROM_PORT_LOCAL=5150`.
+const PARAMS_ENV_PREFIX: &str = "POLYSOCK_";
 
-static FACTORY_MAP: LazyLock<HashMap<&'static str, FactoryCallback>> = LazyLock::new(|| {
-    let mut m = HashMap::new();
-    m.insert("udp", factory_callback_create!(SocketFactoryUDP::new()));
-    m.insert(
-        "stdio",
-        factory_callback_create!(SimpleTerminalFactory::new()),
-    );
-    m.insert(
-        "tcp-client",
-        factory_callback_create!(TcpClientFactory::new()),
-    );
-    m.insert(
-        "tcp-server",
-        factory_callback_create!(TcpServerFactory::new()),
-    );
-    m.insert("test-gen", factory_callback_create!(TestGenFactory::new()));
-    m
-});
+/// Environment variable with the plugins directory.
+const PLUGIN_DIR_ENV: &str = "POLYSOCK_PLUGIN_DIR";
+/// Plugins directory used if it isn't set explicitly (skipped, if missing).
+const DEFAULT_PLUGIN_DIR: &str = "/usr/lib/polysock/plugins";
+/// Read & write timeout in sandbox, if it isn't set explicitly.
+const SANDBOX_IO_TIMEOUT: Duration = Duration::from_secs(1);
+/// Time after the sandbox runtime cap and grace period, when the process
+/// is terminated even if the binding isn't stopped.
+const SANDBOX_WATCHDOG_SLACK: Duration = Duration::from_secs(1);
 
 impl PolySockArgs {
     pub fn get_scenario() -> Box<dyn Command> {
-        let args = Self::parse();
-        let command = match &args.command.unwrap_or_else(|| {
+        Self::load_plugins();
+        let mut args = Self::parse();
+        args.init_logging();
+        set_summary_format(args.summary);
+        let mut command = args.command.take().unwrap_or_else(|| {
             eprintln!("Default command line parameters or subcommands are not provided!");
             process::exit(1)
-        }) {
+        });
+        if let Some(exit_when) = command.exit_when_mut() {
+            *exit_when = args.exit_condition(exit_when.take());
+        }
+        Self::resolve_profiles(&mut command);
+        args.layer_params(&mut command);
+        if args.dry_run {
+            return Box::new(ValidateCommand::new(command.validate_target()));
+        }
+        let command = match &command {
             Commands::Oneliner(args) => Self::get_oneliner_command(args),
+            Commands::FanOut(args) => Self::get_fan_out_command(args),
+            Commands::FanIn(args) => Self::get_fan_in_command(args),
             Commands::Info(args) => {
                 Self::print_info(args);
                 process::exit(0);
             }
-            Commands::Repl {} => {
-                panic!("Repl mode is not implemented yet!");
+            Commands::Ctl(args) => Self::get_ctl_command(args),
+            Commands::Repl(args) => Self::get_repl_command(args),
+            Commands::Generate(args) => Self::get_generate_command(args),
+            Commands::Examples(args) => {
+                Some(Box::new(ExamplesCommand::new(args.name.clone())) as Box<dyn Command>)
+            }
+            Commands::Report(args) => {
+                Some(Box::new(ReportCommand::new(args.db.clone(), args.since)) as Box<dyn Command>)
             }
-            Commands::Script {} => {
-                panic!("Script mode is not implemented yet!");
+            Commands::Echo(args) => Self::get_echo_command(args),
+            Commands::Expect(args) => Self::get_expect_command(args),
+            Commands::Probe(args) => Some(Box::new(ProbeCommand::new(
+                args.targets.clone(),
+                Duration::from_millis(args.timeout),
+                args.banner,
+                args.send.clone(),
+            )) as Box<dyn Command>),
+            Commands::Chain(args) => Self::get_chain_command(args),
+            Commands::Script(args) => Self::get_script_command(args),
+            Commands::Daemon(args) => Self::get_daemon_command(args),
+            Commands::Tui(args) => Self::get_tui_command(args),
+            Commands::Validate(_) => {
+                Some(Box::new(ValidateCommand::new(command.validate_target())) as Box<dyn Command>)
             }
         };
 
@@ -146,8 +976,139 @@ impl PolySockArgs {
             process::exit(1)
         })
     }
+    /// Combines the exit condition of the mode with the global exit
+    /// options, the binding is stopped when any of them is met.
+    fn exit_condition(&self, exit_when: Option<ExitCondition>) -> Option<ExitCondition> {
+        let mut conds: Vec<_> = exit_when
+            .into_iter()
+            .chain(self.exit_after_bytes.map(ExitCondition::Bytes))
+            .chain(self.exit_after_messages.map(ExitCondition::Messages))
+            .chain(self.exit_after_time.map(ExitCondition::Elapsed))
+            .chain(self.idle_timeout.map(ExitCondition::Idle))
+            .chain(self.exit_on_pattern.clone().map(ExitCondition::Pattern))
+            .collect();
+        match conds.len() {
+            0 | 1 => conds.pop(),
+            _ => Some(ExitCondition::Any(conds)),
+        }
+    }
+    /// Replaces `@name` of the profile with its socket type, parameters of
+    /// the profile are the base of the inline ones.
+    fn resolve_profiles(command: &mut Commands) {
+        for (_, dev, params) in command.endpoints_mut() {
+            let Some(name) = dev.strip_prefix('@') else {
+                continue;
+            };
+            // Name is checked by the parser
+            let profile = PROFILES.get(name).expect("Profile is loaded");
+            let mut resolved = profile.socket_params();
+            resolved.merge(params.take().unwrap_or_default());
+            *dev = profile.socket_type().to_string();
+            *params = Some(resolved).filter(|p| !p.is_empty());
+        }
+    }
+    /// Merges the socket parameters from their sources in order: inline
+    /// JSON, `--params-file`, environment variables and `--set` overrides.
+    fn layer_params(&self, command: &mut Commands) {
+        let mut sockets = command.endpoints_mut();
+        let labels = self
+            .params_file
+            .iter()
+            .map(|(label, _)| label)
+            .chain(self.overrides.iter().map(|(label, _, _)| label));
+        for label in labels {
+            if !sockets.iter().any(|(l, _, _)| l == label) {
+                eprintln!("Socket {label} has no parameters in this command");
+                process::exit(1);
+            }
+        }
+        for (label, _, params) in &mut sockets {
+            let mut layered = params.take().unwrap_or_default();
+            for (_, path) in self.params_file.iter().filter(|(l, _)| l == label) {
+                match SocketParams::load(path) {
+                    Ok(file) => layered.merge(file),
+                    Err(e) => {
+                        eprintln!("Socket parameters file loading failed: {e}");
+                        process::exit(1);
+                    }
+                }
+            }
+            let prefix = format!("{PARAMS_ENV_PREFIX}{}_", label.to_uppercase());
+            layered.merge(SocketParams::from_env(&prefix, env::vars()));
+            for (_, field, value) in self.overrides.iter().filter(|(l, _, _)| l == label) {
+                layered.set(field, value);
+            }
+            **params = Some(layered).filter(|p| !p.is_empty());
+        }
+    }
+    /// Logs are written to STDERR (to the log pane of the TUI mode),
+    /// `--log-level` overrides the `RUST_LOG` filter.
+    fn init_logging(&self) {
+        let filter = match self.log_level {
+            Some(level) => EnvFilter::default().add_directive(LevelFilter::from(level).into()),
+            None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        };
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_ansi(io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none())
+            .with_writer(io::stderr);
+        #[cfg(feature = "tui")]
+        if matches!(self.command, Some(Commands::Tui(_))) {
+            return subscriber.with_ansi(false).with_writer(LogBuffer::writer).init();
+        }
+        match self.log_format {
+            LogFormat::Text => subscriber.init(),
+            LogFormat::Json => subscriber.json().init(),
+        }
+    }
+    /// Plugins are loaded before the arguments are parsed, since socket
+    /// types registered by them are possible values of `--from-dev`,
+    /// `--to-dev` and `--ty`.
+    fn load_plugins() {
+        let mut plugins = Vec::new();
+        let mut plugin_dir = env::var_os(PLUGIN_DIR_ENV).map(PathBuf::from);
+        let mut sandbox = false;
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            match name.as_str() {
+                "--plugin" => plugins.extend(value.or_else(|| args.next()).map(PathBuf::from)),
+                "--plugin-dir" => plugin_dir = value.or_else(|| args.next()).map(PathBuf::from),
+                "--sandbox" => sandbox = true,
+                _ => {}
+            }
+        }
+        // Plugin is a native code, which can't be confined
+        if sandbox {
+            if !plugins.is_empty() {
+                eprintln!("Plugins aren't allowed in sandbox");
+                process::exit(1);
+            }
+            return;
+        }
+        let registry = SocketRegistry::global();
+        let res = match plugin_dir {
+            Some(dir) => plugin::load_plugin_dir(&dir, registry).map(|_| ()),
+            None if Path::new(DEFAULT_PLUGIN_DIR).is_dir() => {
+                plugin::load_plugin_dir(Path::new(DEFAULT_PLUGIN_DIR), registry).map(|_| ())
+            }
+            None => Ok(()),
+        }
+        .and_then(|_| {
+            plugins
+                .iter()
+                .try_for_each(|path| plugin::load_plugin(path, registry))
+        });
+        if let Err(e) = res {
+            eprintln!("Plugin loading failed: {e}");
+            process::exit(1);
+        }
+    }
     fn print_info(args: &InfoArgs) {
-        let viewer = FACTORY_MAP.get(args.ty.as_str()).unwrap()().create_doc_viewer();
+        let viewer = SocketRegistry::global().create(&args.ty).unwrap().create_doc_viewer();
         println!(
             "This is information about socket parameters, which passed to --to-params & --from-params:"
         );
@@ -160,53 +1121,613 @@ impl PolySockArgs {
             println!("{}", viewer.get_examples());
         }
     }
+    fn get_generate_command(args: &GenerateArgs) -> Option<Box<dyn Command>> {
+        // The command is checked as if it was run, so the manifest is valid
+        let mut parsed = Self::try_parse_from(
+            std::iter::once("polysock".to_string()).chain(args.command.iter().cloned()),
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Invalid command to generate {:?} manifest:", args.target);
+            e.exit()
+        });
+        let mut command = parsed.command.take();
+        if let Some(command) = command.as_mut() {
+            Self::resolve_profiles(command);
+            parsed.layer_params(command);
+        }
+        match &command {
+            // Socket parameters are fully checked on socket creation, which
+            // would open it, so only JSON syntax is checked (on parsing)
+            Some(Commands::Oneliner(oneliner)) => {
+                Self::get_oneliner_command(oneliner)?;
+            }
+            _ => {
+                eprintln!("Only oneliner command can be deployed!");
+                process::exit(1);
+            }
+        }
+        Some(Box::new(GenerateCommand::new(
+            args.target,
+            args.name.clone(),
+            args.image.clone(),
+            args.command.clone(),
+        )))
+    }
+    #[cfg(feature = "wasm")]
+    fn wasm_transform(path: &Path) -> TransformBuilder {
+        polysock::sock::WasmTransform::builder(path.to_path_buf()).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1)
+        })
+    }
+    #[cfg(not(feature = "wasm"))]
+    fn wasm_transform(_: &Path) -> TransformBuilder {
+        eprintln!("WASM transform requires polysock built with the `wasm` feature");
+        process::exit(1)
+    }
+    fn transform(builder: io::Result<TransformBuilder>) -> TransformBuilder {
+        builder.unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1)
+        })
+    }
+    fn sandbox(args: &OnelinerArgs, f_params: &SocketParams, to_params: &SocketParams) -> Sandbox {
+        let sandbox = SandboxBuilder::default()
+            .max_runtime(Duration::from_secs(args.sandbox_max_time))
+            .max_bytes(args.sandbox_max_bytes)
+            .dir(args.sandbox_dir.clone())
+            .build()
+            .unwrap_or_else(|e| {
+                eprintln!("Sandbox building failed: {e}");
+                process::exit(1)
+            });
+        let stacks = args.from_decorators.iter().chain(&args.to_decorators);
+        let res = sandbox
+            .check_params(f_params)
+            .and_then(|_| sandbox.check_params(to_params))
+            .and_then(|_| {
+                [
+                    &args.transform_wasm,
+                    &args.capture,
+                    &args.record,
+                    &args.stats_db,
+                    &args.trace_file,
+                ]
+                .into_iter()
+                .flatten()
+                .map(PathBuf::as_path)
+                .chain(
+                    args.encrypt_from
+                        .iter()
+                        .chain(&args.encrypt_to)
+                        .filter_map(EncryptConfig::key_path),
+                )
+                .chain(stacks.clone().flat_map(DecoratorStack::paths))
+                .try_for_each(|path| sandbox.check_path(path))
+            })
+            .and_then(|_| {
+                args.demux
+                    .iter()
+                    .flat_map(DemuxConfig::sink_params)
+                    .chain(
+                        args.tee_from
+                            .iter()
+                            .chain(&args.tee_to)
+                            .map(TeeConfig::sink_params),
+                    )
+                    .chain(stacks.clone().flat_map(DecoratorStack::sink_params))
+                    .try_for_each(|params| sandbox.check_params(&params))
+            });
+        if let Err(e) = res {
+            eprintln!("Sandbox: {e}");
+            process::exit(1);
+        }
+        sandbox
+    }
+    /// Hard runtime cap: exits even if the binding doesn't stop in time.
+    fn start_sandbox_watchdog(limit: Duration) {
+        thread::spawn(move || {
+            thread::sleep(limit + SANDBOX_WATCHDOG_SLACK);
+            eprintln!("Sandbox: runtime limit is exceeded, exiting");
+            process::exit(1);
+        });
+    }
+    fn set_trace_file(args: &OnelinerArgs, path: &Path) {
+        let res = TraceFileConfigBuilder::default()
+            .path(path.to_path_buf())
+            .max_size(args.trace_file_max_size)
+            .max_age(args.trace_file_max_age.map(Duration::from_secs))
+            .keep(args.trace_file_keep)
+            .build()
+            .map_err(io::Error::other)
+            .and_then(set_trace_file);
+        if let Err(e) = res {
+            eprintln!("Trace file {} opening failed: {e}", path.display());
+            process::exit(1);
+        }
+    }
+    fn get_fan_out_command(args: &FanOutArgs) -> Option<Box<dyn Command>> {
+        let exit = |e: io::Error| -> ! {
+            eprintln!("{e}");
+            process::exit(1)
+        };
+        let mut in_factory = SocketRegistry::global().create(&args.from_dev)?;
+        if let Some(stack) = &args.from_decorators {
+            in_factory = stack
+                .build(in_factory, TraceSide::From)
+                .unwrap_or_else(|e| exit(e));
+        }
+        let sinks = args
+            .sinks
+            .iter()
+            .map(|sink| {
+                (
+                    sink.create_factory().unwrap_or_else(|e| exit(e)),
+                    sink.sink_params(),
+                )
+            })
+            .collect();
+        let mut binding = BindingOptionsBuilder::default();
+        binding
+            .exit_condition(args.exit_when.clone())
+            .overflow_policy(args.overflow_policy)
+            .framing(args.framing)
+            .read_timeout(args.read_timeout.map(Duration::from_millis))
+            .write_timeout(args.write_timeout.map(Duration::from_millis));
+        if let Some(size) = args.buffer_size {
+            binding.buffer_size(size);
+        }
+        if let Some(ms) = args.grace_period {
+            binding.grace_period(Duration::from_millis(ms));
+        }
+        let binding = binding.build().unwrap_or_else(|e| {
+            eprintln!("Binding options building failed: {e}");
+            process::exit(1)
+        });
+        let params = FanOutModeParamsBuilder::default()
+            .in_params(args.from_params.clone().unwrap_or_default())
+            .blocking(args.blocking)
+            .binding(binding)
+            .build()
+            .unwrap_or_else(|e| {
+                eprintln!("Fan-out command parameters building failed: {e}");
+                process::exit(1)
+            });
+        Some(Box::new(FanOutModeCommand::new(FanOutMode::new(
+            in_factory, sinks, params,
+        ))))
+    }
+    fn get_fan_in_command(args: &FanInArgs) -> Option<Box<dyn Command>> {
+        let exit = |e: io::Error| -> ! {
+            eprintln!("{e}");
+            process::exit(1)
+        };
+        let mut out_factory = SocketRegistry::global().create(&args.to_dev)?;
+        if let Some(stack) = &args.to_decorators {
+            out_factory = stack
+                .build(out_factory, TraceSide::To)
+                .unwrap_or_else(|e| exit(e));
+        }
+        let sources = args
+            .sources
+            .iter()
+            .map(|source| {
+                (
+                    source.create_factory().unwrap_or_else(|e| exit(e)),
+                    source.source_params(),
+                )
+            })
+            .collect();
+        let mut binding = BindingOptionsBuilder::default();
+        binding
+            .exit_condition(args.exit_when.clone())
+            .overflow_policy(args.overflow_policy)
+            .framing(args.framing)
+            .read_timeout(args.read_timeout.map(Duration::from_millis))
+            .write_timeout(args.write_timeout.map(Duration::from_millis));
+        if let Some(size) = args.buffer_size {
+            binding.buffer_size(size);
+        }
+        if let Some(ms) = args.grace_period {
+            binding.grace_period(Duration::from_millis(ms));
+        }
+        let binding = binding.build().unwrap_or_else(|e| {
+            eprintln!("Binding options building failed: {e}");
+            process::exit(1)
+        });
+        let params = FanInModeParamsBuilder::default()
+            .out_params(args.to_params.clone().unwrap_or_default())
+            .binding(binding)
+            .build()
+            .unwrap_or_else(|e| {
+                eprintln!("Fan-in command parameters building failed: {e}");
+                process::exit(1)
+            });
+        Some(Box::new(FanInModeCommand::new(FanInMode::new(
+            sources,
+            out_factory,
+            params,
+        ))))
+    }
+    fn get_echo_command(args: &EchoArgs) -> Option<Box<dyn Command>> {
+        let mut factory = SocketRegistry::global().create(&args.dev)?;
+        if let Some(stack) = &args.decorators {
+            factory = stack.build(factory, TraceSide::From).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                process::exit(1)
+            });
+        }
+        let mut binding = BindingOptionsBuilder::default();
+        binding.exit_condition(args.exit_when.clone());
+        if let Some(ms) = args.grace_period {
+            binding.grace_period(Duration::from_millis(ms));
+        }
+        let binding = binding.build().unwrap_or_else(|e| {
+            eprintln!("Binding options building failed: {e}");
+            process::exit(1)
+        });
+        let params = EchoModeParamsBuilder::default()
+            .params(args.params.clone().unwrap_or_default())
+            .binding(binding)
+            .build()
+            .unwrap_or_else(|e| {
+                eprintln!("Echo command parameters building failed: {e}");
+                process::exit(1)
+            });
+        Some(Box::new(EchoModeCommand::new(EchoMode::new(factory, params))))
+    }
+    fn get_expect_command(args: &ExpectArgs) -> Option<Box<dyn Command>> {
+        let mut factory = SocketRegistry::global().create(&args.dev)?;
+        if let Some(stack) = &args.decorators {
+            factory = stack.build(factory, TraceSide::To).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                process::exit(1)
+            });
+        }
+        let params = args.params.clone().unwrap_or_default();
+        let mode = ExpectMode::new(factory, params, args.steps.clone());
+        Some(Box::new(ExpectModeCommand::new(mode)))
+    }
+    fn get_chain_command(args: &ChainArgs) -> Option<Box<dyn Command>> {
+        let exit = |e: io::Error| -> ! {
+            eprintln!("{e}");
+            process::exit(1)
+        };
+        let mut f_factory = SocketRegistry::global().create(&args.from_dev)?;
+        if let Some(stack) = &args.from_decorators {
+            f_factory = stack
+                .build(f_factory, TraceSide::From)
+                .unwrap_or_else(|e| exit(e));
+        }
+        let mut t_factory = SocketRegistry::global().create(&args.to_dev)?;
+        if let Some(stack) = &args.to_decorators {
+            t_factory = stack
+                .build(t_factory, TraceSide::To)
+                .unwrap_or_else(|e| exit(e));
+        }
+        // Stages decorate the sink, so the last one is built first
+        for stages in args.stages.iter().rev() {
+            t_factory = stages
+                .build_pipeline(t_factory, TraceSide::To)
+                .unwrap_or_else(|e| exit(e));
+        }
+        let mut binding = BindingOptionsBuilder::default();
+        binding
+            .exit_condition(args.exit_when.clone())
+            .overflow_policy(args.overflow_policy)
+            .framing(args.framing);
+        if let Some(size) = args.buffer_size {
+            binding.buffer_size(size);
+        }
+        if let Some(ms) = args.grace_period {
+            binding.grace_period(Duration::from_millis(ms));
+        }
+        let binding = binding.build().unwrap_or_else(|e| {
+            eprintln!("Binding options building failed: {e}");
+            process::exit(1)
+        });
+        let params = OnelinerModeParamsBuilder::default()
+            .f_params(args.from_params.clone().unwrap_or_default())
+            .to_params(args.to_params.clone().unwrap_or_default())
+            .bidir(matches!(args.exchange_mode, ExchangeMode::Bidir))
+            .blocking(args.blocking)
+            .binding(binding)
+            .build()
+            .unwrap_or_else(|e| {
+                eprintln!("Chain command parameters building failed: {e}");
+                process::exit(1)
+            });
+        Some(Box::new(OnelinerModeCommand::new(OnelinerMode::new(
+            f_factory, t_factory, params,
+        ))))
+    }
+    fn get_script_command(args: &ScriptArgs) -> Option<Box<dyn Command>> {
+        if args.watch {
+            let mode = DaemonMode::new(None, None).watch(args.path.clone());
+            return Some(Box::new(DaemonModeCommand::new(mode)));
+        }
+        let mode = ScriptConfig::load(&args.path)
+            .and_then(|config| ScriptMode::new(&config))
+            .map(|mode| mode.async_engine(matches!(args.engine, Engine::Async)))
+            .unwrap_or_else(|e| {
+                eprintln!("{e}");
+                process::exit(1)
+            });
+        Some(Box::new(ScriptModeCommand::new(mode)))
+    }
+    fn get_daemon_command(args: &DaemonArgs) -> Option<Box<dyn Command>> {
+        let control = Some(args.control.clone());
+        if let (Some(path), true) = (&args.script, args.watch) {
+            let mode = DaemonMode::new(control, None).watch(path.clone());
+            return Some(Box::new(DaemonModeCommand::new(mode)));
+        }
+        let script = args.script.as_deref().map(|path| {
+            ScriptConfig::load(path).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                process::exit(1)
+            })
+        });
+        let mode = DaemonMode::new(control, script);
+        Some(Box::new(DaemonModeCommand::new(mode)))
+    }
+    #[cfg(feature = "tui")]
+    fn get_tui_command(args: &TuiArgs) -> Option<Box<dyn Command>> {
+        let mode = ScriptConfig::load(&args.path)
+            .and_then(|config| TuiMode::new(&config))
+            .unwrap_or_else(|e| {
+                eprintln!("{e}");
+                process::exit(1)
+            });
+        Some(Box::new(TuiModeCommand::new(mode)))
+    }
+    #[cfg(not(feature = "tui"))]
+    fn get_tui_command(_: &TuiArgs) -> Option<Box<dyn Command>> {
+        eprintln!("TUI mode requires polysock built with the `tui` feature");
+        process::exit(1)
+    }
     fn get_oneliner_command(args: &OnelinerArgs) -> Option<Box<dyn Command>> {
-        let set_decorators =
-            |mut f: Box<dyn SocketFactory>, args: &OnelinerArgs| -> Box<dyn SocketFactory> {
+        let f_params = args.from_params.clone().unwrap_or_default();
+        let to_params = args.to_params.clone().unwrap_or_default();
+        // Files are checked before they are created
+        let sandbox = args
+            .sandbox
+            .then(|| Self::sandbox(args, &f_params, &to_params));
+        if let Some(path) = &args.trace_file {
+            Self::set_trace_file(args, path);
+        }
+        if let Some(ts) = args.trace_ts {
+            set_trace_timestamp(ts);
+        }
+        set_trace_color(args.color);
+        let capture = args.capture.as_ref().map(|path| {
+            CaptureFile::create(path).unwrap_or_else(|e| {
+                eprintln!("Capture file {} creation failed: {e}", path.display());
+                process::exit(1)
+            })
+        });
+        let set_decorators = |mut f: Box<dyn SocketFactory>,
+                              side: TraceSide,
+                              stack: &Option<DecoratorStack>|
+         -> Box<dyn SocketFactory> {
+            if let Some(stack) = stack {
+                f = stack.build(f, side).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    process::exit(1)
+                });
+            } else {
                 // Socket info must be printed firstly
                 if args.trace_info {
-                    f = TraceInfoDecoratorFactory::new(f);
+                    f = TraceInfoDecoratorFactory::new(f, side);
                 }
                 // Raw data should be printed after socket info
                 if args.trace_raw {
-                    f = TraceRawDecoratorFactory::new(f);
+                    f = TraceRawDecoratorFactory::new(f, side);
                 }
                 // Canonical data is the last
                 if args.trace_canon {
-                    f = TraceCanonicalDecoratorFactory::new(f);
+                    f = TraceCanonicalDecoratorFactory::new(f, side);
                 }
-                f
-            };
-        let mut f_factory = if let Some(cb) = FACTORY_MAP.get(args.from_dev.as_str()) {
-            cb()
+                if let Some(algo) = args.trace_hash {
+                    f = HashDecoratorFactory::new(f, algo);
+                }
+                if let Some(secs) = args.trace_summary {
+                    f = SummaryDecoratorFactory::new(f, Duration::from_secs(secs));
+                }
+                if let Some(secs) = args.trace_stats {
+                    f = StatsDecoratorFactory::new(f, Duration::from_secs(secs));
+                }
+            }
+            if let Some(cfg) = &args.trace_seq {
+                f = SequenceDecoratorFactory::new(f, cfg.clone());
+            }
+            if let Some(cfg) = &args.trace_monitor {
+                f = MonitorDecoratorFactory::new(f, cfg.clone());
+            }
+            if let Some(file) = &capture {
+                f = CaptureDecoratorFactory::new(f, file.clone());
+            }
+            f
+        };
+        let mut f_factory = if let Some(f) = SocketRegistry::global().create(&args.from_dev) {
+            f
         } else {
             eprintln!("Socket type {} not found! Exiting...", args.from_dev);
             process::exit(1);
         };
-        let mut t_factory = if let Some(cb) = FACTORY_MAP.get(args.to_dev.as_str()) {
-            cb()
+        let mut t_factory = if let Some(f) = SocketRegistry::global().create(&args.to_dev) {
+            f
         } else {
             eprintln!("Socket type {} not found! Exiting...", args.to_dev);
             process::exit(1);
         };
 
+        // Recorded chunks are the raw data of the socket
+        if let Some(path) = &args.record {
+            f_factory = RecordDecoratorFactory::new(f_factory, path.clone());
+        }
+        // Sink gets the raw data of the socket too
+        if let Some(cfg) = &args.tee_from {
+            f_factory = TeeDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.tee_to {
+            t_factory = TeeDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        // Chunk size limits the writes of the socket, so it's the closest one
+        if let Some(cfg) = &args.chunk_from {
+            f_factory = ChunkDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.chunk_to {
+            t_factory = ChunkDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        // Checksum covers the frame on the wire
+        if let Some(cfg) = &args.checksum_from {
+            f_factory = ChecksumDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.checksum_to {
+            t_factory = ChecksumDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        // Tracing shows the data as it's passed by the shaped link
+        if let Some(cfg) = &args.rate_limit_from {
+            f_factory = RateLimitDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.rate_limit_to {
+            t_factory = RateLimitDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.delay_from {
+            f_factory = DelayDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.delay_to {
+            t_factory = DelayDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.fault_from {
+            f_factory = FaultDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.fault_to {
+            t_factory = FaultDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        // Latency includes the emulated delay and faults of the link
+        if let Some(cfg) = &args.latency_from {
+            f_factory = LatencyDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.latency_to {
+            t_factory = LatencyDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.obfuscate_from {
+            f_factory = TransformDecoratorFactory::new(f_factory, Self::transform(cfg.builder()));
+        }
+        if let Some(cfg) = &args.obfuscate_to {
+            t_factory = TransformDecoratorFactory::new(t_factory, Self::transform(cfg.builder()));
+        }
+        // The link is shaped with the encrypted data, tracing shows the
+        // plain one
+        if let Some(cfg) = &args.encrypt_from {
+            f_factory = TransformDecoratorFactory::new(f_factory, Self::transform(cfg.builder()));
+        }
+        if let Some(cfg) = &args.encrypt_to {
+            t_factory = TransformDecoratorFactory::new(t_factory, Self::transform(cfg.builder()));
+        }
+        // Data is compressed before encryption, as ciphertext doesn't compress
+        if let Some(cfg) = &args.compress_from {
+            f_factory = CompressDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.compress_to {
+            t_factory = CompressDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        // Filter matches the plain data, tracing shows only the passed one
+        if let Some(cfg) = &args.filter_from {
+            f_factory = FilterDecoratorFactory::new(f_factory, cfg.clone());
+        }
+        if let Some(cfg) = &args.filter_to {
+            t_factory = FilterDecoratorFactory::new(t_factory, cfg.clone());
+        }
+        // Tracing shows the words in the byte order of the other socket
+        if let Some(cfg) = &args.byte_swap_from {
+            f_factory = TransformDecoratorFactory::new(f_factory, Self::transform(cfg.builder()));
+        }
+        if let Some(cfg) = &args.byte_swap_to {
+            t_factory = TransformDecoratorFactory::new(t_factory, Self::transform(cfg.builder()));
+        }
+
         // Set decorators, if it is not disabled for
         // this direction
         if !args.trace_from_off {
-            f_factory = set_decorators(f_factory, args);
+            f_factory = set_decorators(f_factory, TraceSide::From, &args.from_decorators);
         }
         if !args.trace_to_off {
-            t_factory = set_decorators(t_factory, args);
+            t_factory = set_decorators(t_factory, TraceSide::To, &args.to_decorators);
         }
 
-        let f_params = args.from_params.clone().unwrap_or_default();
-        let to_params = args.to_params.clone().unwrap_or_default();
+        // Transforms change the data, so they aren't disabled with tracing
+        if let Some(path) = &args.transform_wasm {
+            let builder = Self::wasm_transform(path);
+            f_factory = TransformDecoratorFactory::new(f_factory, builder.clone());
+            t_factory = TransformDecoratorFactory::new(t_factory, builder);
+        }
+        // Demux sinks are created once, so only the source is decorated
+        if let Some(cfg) = &args.demux {
+            f_factory = DemuxDecoratorFactory::new(f_factory, cfg.clone());
+        }
 
+        let mut binding = BindingOptionsBuilder::default();
+        binding
+            .exit_condition(args.exit_when.clone())
+            .overflow_policy(args.overflow_policy)
+            .framing(args.framing)
+            .read_timeout(args.read_timeout.map(Duration::from_millis))
+            .write_timeout(args.write_timeout.map(Duration::from_millis))
+            .half_duplex(args.half_duplex.clone())
+            .xon_xoff(args.xon_xoff)
+            .coalesce(args.coalesce);
+        if let Some(sandbox) = sandbox {
+            binding.exit_condition(Some(sandbox.exit_condition(args.exit_when.clone())));
+            // Blocked operations must not outlive the runtime cap
+            let timeout = |ms: Option<u64>| ms.map_or(SANDBOX_IO_TIMEOUT, Duration::from_millis);
+            binding
+                .read_timeout(Some(timeout(args.read_timeout)))
+                .write_timeout(Some(timeout(args.write_timeout)));
+            let grace = args
+                .grace_period
+                .map_or(DEFAULT_GRACE_PERIOD, Duration::from_millis);
+            Self::start_sandbox_watchdog(sandbox.max_runtime() + grace);
+        }
+        // Metrics are recorded from the binding counters
+        let history = args.stats_db.as_ref().map(|path| {
+            let stats = Arc::new(BindingStats::new());
+            binding.stats(Some(stats.clone()));
+            let interval = Duration::from_secs(args.stats_db_interval.max(1));
+            let recorder = HistoryRecorder::start(path, interval, stats).unwrap_or_else(|e| {
+                eprintln!("Stats file {} opening failed: {e}", path.display());
+                process::exit(1)
+            });
+            Arc::new(recorder)
+        });
+        if let Some(size) = args.buffer_size {
+            binding.buffer_size(size);
+        }
+        if let Some(size) = args.read_chunk {
+            binding.read_chunk(size);
+        }
+        if let Some(us) = args.poll_interval {
+            binding.poll_interval(Duration::from_micros(us));
+        }
+        binding.read_chunk_tx(args.read_chunk_tx);
+        binding.poll_interval_tx(args.poll_interval_tx.map(Duration::from_micros));
+        if let Some(ms) = args.grace_period {
+            binding.grace_period(Duration::from_millis(ms));
+        }
+        let binding = binding.build().unwrap_or_else(|e| {
+            eprintln!("Binding options building failed: {e}");
+            process::exit(1)
+        });
         let oneliner_params = OnelinerModeParamsBuilder::default()
             .f_params(f_params)
             .to_params(to_params)
+            .control(args.control.clone())
             .bidir(matches!(args.exchange_mode, ExchangeMode::Bidir))
             .blocking(args.blocking)
+            .binding(binding)
+            .async_engine(matches!(args.engine, Engine::Async))
+            .history(history)
             .build()
             .unwrap_or_else(|e| {
                 eprintln!("Oneliner command parameters building failed: {e}");
@@ -218,4 +1739,16 @@ impl PolySockArgs {
             oneliner_params,
         ))))
     }
+    fn get_ctl_command(args: &CtlArgs) -> Option<Box<dyn Command>> {
+        let request = args.request.to_json();
+        Some(Box::new(CtlCommand::new(args.control.clone(), request)))
+    }
+    fn get_repl_command(args: &ReplArgs) -> Option<Box<dyn Command>> {
+        let parser = |words| {
+            ReplLine::try_parse_from(words)
+                .map(|line| line.request.to_json())
+                .map_err(|e| e.to_string())
+        };
+        Some(Box::new(ReplCommand::new(args.control.clone(), parser)))
+    }
 }
@@ -0,0 +1,164 @@
+//! Errors with the context of the failed socket.
+//!
+//! The socket traits return [`std::io::Result`], so [`Error`] is carried
+//! inside [`io::Error`] with the same kind: callers still match on
+//! [`io::ErrorKind`], while the CLI prints the socket, the operation and
+//! the cause.
+
+use std::fmt;
+use std::io;
+
+/// Operation of the socket, which failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Open,
+    Read,
+    Write,
+    Flush,
+    Shutdown,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Self::Open => "open",
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Flush => "flush",
+            Self::Shutdown => "shutdown",
+        };
+        f.write_str(op)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Socket parameters don't match the configuration of the socket type
+    #[error("Invalid {sock} configuration: {}{source}", field_prefix(.field))]
+    Config {
+        sock: String,
+        /// Path of the field, e.g. `links[0].port_dst`
+        field: Option<String>,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// Parameters have fields, which the socket type doesn't know
+    #[error(
+        "Invalid {sock} configuration: unknown field {}, expected one of: {}",
+        .fields.join(", "),
+        .expected.join(", ")
+    )]
+    UnknownField {
+        sock: String,
+        fields: Vec<String>,
+        expected: Vec<String>,
+    },
+    /// Parameters are parsed, but their value can't be used
+    #[error("Invalid {sock} configuration: {field}: {message}")]
+    InvalidValue {
+        sock: String,
+        field: String,
+        message: String,
+    },
+    /// Socket type isn't in the registry
+    #[error("Socket type {0} is not registered")]
+    UnknownType(String),
+    /// Socket has no more data (e.g. test pattern is generated the given
+    /// number of times), the binding is finished without error
+    #[error("End of data")]
+    Eof,
+    /// Operation of the opened socket failed
+    #[error("{sock}: {op} failed: {source}")]
+    Io {
+        /// Description of the socket, e.g. `udp0`
+        sock: String,
+        op: Op,
+        #[source]
+        source: io::Error,
+    },
+}
+
+fn field_prefix(field: &Option<String>) -> String {
+    field.as_ref().map(|f| format!("{f}: ")).unwrap_or_default()
+}
+
+impl Error {
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Self::Config { .. } | Self::UnknownField { .. } | Self::InvalidValue { .. } => {
+                io::ErrorKind::InvalidInput
+            }
+            Self::UnknownType(_) => io::ErrorKind::NotFound,
+            Self::Eof => io::ErrorKind::UnexpectedEof,
+            Self::Io { source, .. } => source.kind(),
+        }
+    }
+    /// Adds the socket and the operation to the error of the socket.
+    /// Errors, which aren't failures (nonblocking socket without data),
+    /// and errors with the context already are returned as they are.
+    pub fn io(sock: impl FnOnce() -> String, op: Op, e: io::Error) -> io::Error {
+        let has_context = e.get_ref().is_some_and(|inner| inner.is::<Self>());
+        if has_context
+            || matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+            )
+        {
+            return e;
+        }
+        Self::Io {
+            sock: sock(),
+            op,
+            source: e,
+        }
+        .into()
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+/// Returns `true`, if the socket has no more data.
+pub fn is_eof(e: &io::Error) -> bool {
+    matches!(context(e), Some(Error::Eof))
+}
+
+/// Returns the context of the error, if it has one.
+pub fn context(e: &io::Error) -> Option<&Error> {
+    e.get_ref().and_then(|inner| inner.downcast_ref())
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn io_context() {
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        let e = Error::io(|| "udp0".to_string(), Op::Read, refused);
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(e.to_string(), "udp0: read failed: connection refused");
+        assert!(matches!(context(&e), Some(Error::Io { op: Op::Read, .. })));
+        // Context of the inner socket is kept
+        let e = Error::io(|| "tcp-client0".to_string(), Op::Write, e);
+        assert!(e.to_string().starts_with("udp0: "));
+        let e = Error::io(
+            || unreachable!(),
+            Op::Read,
+            io::ErrorKind::WouldBlock.into(),
+        );
+        assert!(context(&e).is_none());
+
+        let e: io::Error = Error::UnknownType("serial".to_string()).into();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert_eq!(e.to_string(), "Socket type serial is not registered");
+        // End of data isn't a failure of the socket
+        let eof = Error::io(|| unreachable!(), Op::Read, Error::Eof.into());
+        assert!(is_eof(&eof));
+        assert!(!is_eof(&io::ErrorKind::UnexpectedEof.into()));
+    }
+}
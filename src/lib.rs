@@ -0,0 +1,54 @@
+/*
+ * Copyright (c) 2026 Ilya Shishov
+ * Licensed under the MIT License.
+ * See the LICENSE file in the project root for full license information.
+ */
+
+//! Socket bridging engine of `polysock`.
+//!
+//! The crate can be embedded (e.g. into a test harness) instead of running
+//! the `polysock` binary:
+//! - [`sockets`] contains socket types, their factories and
+//!   [`sockets::registry::SocketRegistry`] for adding custom socket types;
+//! - [`sock`] contains socket traits, decorators (tracing, hashing, protocol
+//!   monitor) and [`sock::SocketManager`], which binds two sockets;
+//! - [`modes`] contains binding scenarios of the binary, e.g.
+//!   [`modes::oneliner::OnelinerMode`].
+//! - [`error`] contains [`error::Error`], which is carried by the returned
+//!   `io::Error` with the failed socket, operation or configuration field.
+//!
+//! Forward UDP datagrams to the TCP server until 1 KB is transferred:
+//! ```no_run
+//! use polysock::modes::oneliner::{OnelinerMode, OnelinerModeParamsBuilder};
+//! use polysock::sock::BindingOptionsBuilder;
+//! use polysock::sockets::{tcp_client::TcpClientFactory, udp::SocketFactoryUDP};
+//! use serde_json::json;
+//!
+//! let params = OnelinerModeParamsBuilder::default()
+//!     .f_params(json!({ "port_local": 5150 }).into())
+//!     .to_params(json!({ "ip_dst": "127.0.0.1", "port_dst": 1234 }).into())
+//!     .binding(
+//!         BindingOptionsBuilder::default()
+//!             .exit_condition(Some(serde_json::from_str(r#"{ "bytes": 1024 }"#).unwrap()))
+//!             .build()
+//!             .unwrap(),
+//!     )
+//!     .build()
+//!     .unwrap();
+//! let mut mode = OnelinerMode::new(
+//!     Box::new(SocketFactoryUDP::new()),
+//!     Box::new(TcpClientFactory::new()),
+//!     params,
+//! );
+//! mode.start().unwrap();
+//! // `mode.stop()` stops the binding from another place of the harness
+//! mode.wait().unwrap();
+//! ```
+
+pub mod error;
+pub mod modes;
+pub mod sock;
+pub mod sockets;
+
+mod serde_helpers;
+mod test_helpers;
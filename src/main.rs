@@ -6,17 +6,11 @@
 
 use std::io;
 
-mod sockets;
-mod sock;
-mod serde_helpers;
-mod test_helpers;
 mod args;
-mod modes;
 
 use crate::args::PolySockArgs;
 
 fn main() -> io::Result<()> {
-    env_logger::init();
     let mut command = PolySockArgs::get_scenario();
     command.execute();
     Ok(())
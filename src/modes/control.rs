@@ -0,0 +1,399 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, ErrorKind, IsTerminal, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Interval of checking for new clients and whether the listener is stopped.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Address of the control socket: `ip:port` for TCP, path of the Unix
+/// socket otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ControlAddr {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse()
+            .map(Self::Tcp)
+            .unwrap_or_else(|_| Self::Unix(PathBuf::from(s))))
+    }
+}
+
+impl fmt::Display for ControlAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+trait ControlStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> ControlStream for T {}
+
+impl ControlAddr {
+    /// Connects the client to the control socket.
+    fn connect(&self) -> io::Result<Box<dyn ControlStream>> {
+        match self {
+            Self::Tcp(addr) => Ok(Box::new(TcpStream::connect(addr)?)),
+            #[cfg(unix)]
+            Self::Unix(path) => Ok(Box::new(UnixStream::connect(path)?)),
+            #[cfg(not(unix))]
+            Self::Unix(_) => Err(io::Error::from(ErrorKind::Unsupported)),
+        }
+    }
+}
+
+/// Request line of the client with the channel of the reply.
+pub type Request = (String, mpsc::Sender<String>);
+
+/// Listening control socket, the file of the Unix socket is removed when
+/// it is dropped.
+pub enum ControlListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl ControlListener {
+    pub fn bind(addr: &ControlAddr) -> io::Result<Self> {
+        let listener = match addr {
+            ControlAddr::Tcp(addr) => Self::Tcp(TcpListener::bind(addr)?),
+            #[cfg(unix)]
+            ControlAddr::Unix(path) => {
+                // Socket file is left behind, if the previous process was killed
+                if UnixStream::connect(path).is_err() && path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                Self::Unix(UnixListener::bind(path)?, path.clone())
+            }
+            #[cfg(not(unix))]
+            ControlAddr::Unix(_) => return Err(io::Error::from(ErrorKind::Unsupported)),
+        };
+        match &listener {
+            Self::Tcp(l) => l.set_nonblocking(true)?,
+            #[cfg(unix)]
+            Self::Unix(l, _) => l.set_nonblocking(true)?,
+        }
+        Ok(listener)
+    }
+    fn accept(&self) -> io::Result<Box<dyn ControlStream>> {
+        match self {
+            Self::Tcp(l) => {
+                let (stream, _) = l.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Self::Unix(l, _) => {
+                let (stream, _) = l.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+    /// Accepts clients until the flag is cleared, every client is served
+    /// in its own thread. Request lines are passed to the channel.
+    pub fn serve(&self, requests: mpsc::Sender<Request>, run_ctl: &AtomicBool) -> io::Result<()> {
+        while run_ctl.load(Ordering::Relaxed) {
+            match self.accept() {
+                Ok(stream) => {
+                    let requests = requests.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = Self::serve_client(stream, requests) {
+                            tracing::warn!("Control client is dropped: {e}");
+                        }
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+    /// Passes request lines of the client to the channel and writes the
+    /// replies back.
+    fn serve_client(
+        stream: Box<dyn ControlStream>,
+        requests: mpsc::Sender<Request>,
+    ) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            let request = line.trim();
+            if !request.is_empty() {
+                let (tx, rx) = mpsc::channel();
+                let stopped = || io::Error::new(ErrorKind::BrokenPipe, "Control is stopped");
+                requests
+                    .send((request.to_string(), tx))
+                    .map_err(|_| stopped())?;
+                let reply = rx.recv().map_err(|_| stopped())?;
+                writeln!(reader.get_mut(), "{reply}")?;
+            }
+            line.clear();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ControlListener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Self::Unix(_, path) = self {
+            drop(std::fs::remove_file(path));
+        }
+    }
+}
+
+/// Reply of the control API: `{ "ok": true, "result": ... }` or
+/// `{ "ok": false, "error": "..." }`.
+pub fn reply(res: io::Result<serde_json::Value>) -> serde_json::Value {
+    match res {
+        Ok(result) => serde_json::json!({ "ok": true, "result": result }),
+        Err(e) => serde_json::json!({ "ok": false, "error": e.to_string() }),
+    }
+}
+
+/// Client sending one request to the control socket and printing the
+/// result. Exit code is 1, if the request failed.
+pub struct CtlCommand {
+    addr: ControlAddr,
+    request: serde_json::Value,
+}
+
+impl CtlCommand {
+    pub fn new(addr: ControlAddr, request: serde_json::Value) -> Self {
+        Self { addr, request }
+    }
+    fn send(&self) -> io::Result<serde_json::Value> {
+        let mut stream = BufReader::new(self.addr.connect()?);
+        writeln!(stream.get_mut(), "{}", self.request)?;
+        let mut reply = String::new();
+        stream.read_line(&mut reply)?;
+        serde_json::from_str(&reply).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+impl super::Command for CtlCommand {
+    fn execute(&mut self) {
+        let reply = self.send().unwrap_or_else(|e| {
+            tracing::error!("Control socket {}: {e}", self.addr);
+            process::exit(1)
+        });
+        if reply["ok"] != true {
+            tracing::error!("{}", reply["error"].as_str().unwrap_or("Unexpected reply"));
+            process::exit(1);
+        }
+        if !reply["result"].is_null() {
+            println!("{:#}", reply["result"]);
+        }
+    }
+}
+
+/// Splits the line into words: whitespace separates them, except inside
+/// single or double quotes, e.g. `reopen from '{ "port_local": 5151 }'`.
+fn split_words(line: &str) -> io::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_default().push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "Quote isn't closed",
+        ));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parser of the REPL line words into the request of the control API.
+pub type ReplParser = fn(Vec<String>) -> Result<serde_json::Value, String>;
+
+/// Interactive client of the control socket: every line is a request (the
+/// same as the `ctl` subcommands), the results are printed until the input
+/// is closed or `exit` is entered.
+pub struct ReplCommand {
+    addr: ControlAddr,
+    parser: ReplParser,
+}
+
+impl ReplCommand {
+    pub fn new(addr: ControlAddr, parser: ReplParser) -> Self {
+        Self { addr, parser }
+    }
+    fn run(&self, input: impl BufRead, output: &mut impl Write, prompt: bool) -> io::Result<()> {
+        let mut stream = BufReader::new(self.addr.connect()?);
+        let mut lines = input.lines();
+        loop {
+            if prompt {
+                write!(output, "polysock> ")?;
+                output.flush()?;
+            }
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(());
+            };
+            let line = line.trim();
+            if line == "exit" {
+                return Ok(());
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let request = split_words(line)
+                .map_err(|e| e.to_string())
+                .and_then(self.parser);
+            let request = match request {
+                Ok(request) => request,
+                Err(e) => {
+                    // Help of the requests is the error of the parser too
+                    writeln!(output, "{}", e.trim_end())?;
+                    continue;
+                }
+            };
+            writeln!(stream.get_mut(), "{request}")?;
+            let mut reply = String::new();
+            if stream.read_line(&mut reply)? == 0 {
+                return Err(io::Error::new(ErrorKind::BrokenPipe, "Control is stopped"));
+            }
+            let reply: serde_json::Value = serde_json::from_str(&reply)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+            if reply["ok"] != true {
+                let error = reply["error"].as_str().unwrap_or("Unexpected reply");
+                writeln!(output, "Error: {error}")?;
+            } else if !reply["result"].is_null() {
+                writeln!(output, "{:#}", reply["result"])?;
+            }
+        }
+    }
+}
+
+impl super::Command for ReplCommand {
+    fn execute(&mut self) {
+        let stdin = io::stdin();
+        let prompt = stdin.is_terminal();
+        if let Err(e) = self.run(stdin.lock(), &mut io::stdout(), prompt) {
+            tracing::error!("Control socket {}: {e}", self.addr);
+            process::exit(1);
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn request_reply() {
+        let addr: ControlAddr = "127.0.0.1:8120".parse().unwrap();
+        let listener = ControlListener::bind(&addr).unwrap();
+        let run_ctl = Arc::new(AtomicBool::new(true));
+        let (tx, rx) = mpsc::channel::<Request>();
+        let acceptor = {
+            let run_ctl = run_ctl.clone();
+            thread::spawn(move || listener.serve(tx, &run_ctl))
+        };
+        let handler = thread::spawn(move || {
+            let (request, reply_tx) = rx.recv().unwrap();
+            let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+            reply_tx
+                .send(reply(Ok(request["label"].clone())).to_string())
+                .unwrap();
+        });
+
+        let ctl = CtlCommand::new(addr, serde_json::json!({ "cmd": "mark", "label": "phase 1" }));
+        let reply = ctl.send().unwrap();
+        assert_eq!(reply, serde_json::json!({ "ok": true, "result": "phase 1" }));
+        handler.join().unwrap();
+        run_ctl.store(false, Ordering::Relaxed);
+        acceptor.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn repl_session() {
+        let addr: ControlAddr = "127.0.0.1:8130".parse().unwrap();
+        let listener = ControlListener::bind(&addr).unwrap();
+        let run_ctl = Arc::new(AtomicBool::new(true));
+        let (tx, rx) = mpsc::channel::<Request>();
+        let acceptor = {
+            let run_ctl = run_ctl.clone();
+            thread::spawn(move || listener.serve(tx, &run_ctl))
+        };
+        // Labels are reported back, the empty one is rejected
+        let handler = thread::spawn(move || {
+            for (request, reply_tx) in rx {
+                let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+                let res = match request["label"].as_str() {
+                    Some("") => Err(io::Error::new(ErrorKind::InvalidInput, "Empty label")),
+                    _ => Ok(request["label"].clone()),
+                };
+                reply_tx.send(reply(res).to_string()).unwrap();
+            }
+        });
+
+        // Requests are quoted JSON words
+        let parser: ReplParser =
+            |words| serde_json::from_str(&words.join(" ")).map_err(|e| e.to_string());
+        let input = r#"
+            '{ "cmd": "mark", "label": "phase 1" }'
+
+            '{ "cmd": "mark", "label": "" }'
+            mark
+            exit
+            '{ "cmd": "mark", "label": "phase 2" }'
+        "#;
+        let mut output = Vec::new();
+        ReplCommand::new(addr, parser)
+            .run(input.as_bytes(), &mut output, false)
+            .unwrap();
+        run_ctl.store(false, Ordering::Relaxed);
+        acceptor.join().unwrap().unwrap();
+        handler.join().unwrap();
+        let lines = String::from_utf8(output).unwrap();
+        let output = lines.lines().collect::<Vec<_>>();
+        assert_eq!(output[0], "\"phase 1\"");
+        assert_eq!(output[1], "Error: Empty label");
+        assert!(output[2].starts_with("expected value"), "{lines}");
+        assert_eq!(output.len(), 3);
+    }
+
+    #[test]
+    fn repl_words() {
+        assert_eq!(
+            split_words(r#" reopen  from '{ "baud": 9600 }'"#).unwrap(),
+            ["reopen", "from", r#"{ "baud": 9600 }"#]
+        );
+        assert_eq!(
+            split_words(r#"mark "" x"y z""#).unwrap(),
+            ["mark", "", "xy z"]
+        );
+        assert!(split_words("mark 'test").is_err());
+    }
+}
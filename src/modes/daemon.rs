@@ -0,0 +1,533 @@
+use serde::Deserialize;
+
+use crate::modes::control::{self, ControlAddr, ControlListener};
+use crate::modes::script::{LinkConfig, LinkMonitor, ScriptConfig, ScriptMode};
+use crate::sock::{POLL_TIMEOUT, TraceSide, inject_marker, stream_digests};
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Interval of checking whether the watched script is modified.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Request of the control API, one JSON object per line, e.g.
+/// `{ "cmd": "remove", "name": "gps" }`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum ControlRequest {
+    /// Binds the link (the same as the link of the script)
+    Add { link: Box<LinkConfig> },
+    /// Stops the link and forgets it
+    Remove { name: String },
+    /// Statistics of the link, or of every link if the name isn't set
+    Stats {
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// Injects the labeled marker into the trace output and the captures
+    Mark { label: String },
+    /// Reopens the socket of the link with the parameters merged over the
+    /// current ones, the link keeps running
+    Reopen {
+        name: String,
+        side: TraceSide,
+        #[serde(default)]
+        params: serde_json::Value,
+    },
+    /// Current digests of the sockets with the hash decorator
+    Digests,
+    /// Stops every link and the daemon
+    Shutdown,
+}
+
+/// Script with the links kept as the source JSON, so changed links can
+/// be told apart from unchanged ones.
+#[derive(Deserialize)]
+struct ScriptSource {
+    links: Vec<serde_json::Value>,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Script file watched by the daemon. When the file is modified, removed
+/// links are stopped, new ones are added and changed ones are rebound; the
+/// other links keep running.
+struct ScriptWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+    /// Sources of the applied links by names
+    applied: BTreeMap<String, serde_json::Value>,
+}
+
+impl ScriptWatcher {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            modified: modified_time(&path),
+            path,
+            last_check: Instant::now(),
+            applied: BTreeMap::new(),
+        }
+    }
+    /// Reads the links of the script with their sources.
+    fn load(&self) -> io::Result<Vec<(LinkConfig, serde_json::Value)>> {
+        let invalid = |e: serde_json::Error| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Script {} parsing failed: {e}", self.path.display()),
+            )
+        };
+        let script = std::fs::read_to_string(&self.path)?;
+        let source: ScriptSource = serde_json::from_str(&script).map_err(invalid)?;
+        let links = source
+            .links
+            .into_iter()
+            .map(|link| Ok((serde_json::from_value(link.clone()).map_err(invalid)?, link)))
+            .collect::<io::Result<Vec<(LinkConfig, _)>>>()?;
+        let mut names = std::collections::BTreeSet::new();
+        if let Some((link, _)) = links.iter().find(|(link, _)| !names.insert(link.name())) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Script {}: link {} is duplicated",
+                    self.path.display(),
+                    link.name()
+                ),
+            ));
+        }
+        Ok(links)
+    }
+    /// Returns the links of the script, if the file is modified since the
+    /// previous check.
+    fn poll(&mut self) -> Option<io::Result<Vec<(LinkConfig, serde_json::Value)>>> {
+        if self.last_check.elapsed() < RELOAD_CHECK_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(self.load())
+    }
+}
+
+/// Link bound by the daemon.
+struct DaemonLink {
+    mode: ScriptMode,
+    monitor: LinkMonitor,
+}
+
+impl DaemonLink {
+    fn stats(&self) -> serde_json::Value {
+        let stats = &self.monitor.stats;
+        let status = if self.mode.finished().iter().all(|&f| f) {
+            "finished"
+        } else {
+            "running"
+        };
+        serde_json::json!({
+            "name": self.monitor.name,
+            "status": status,
+            "bytes": stats.bytes(),
+            "messages": stats.messages(),
+            "uptime_s": stats.elapsed().as_secs(),
+            "idle_s": stats.idle().as_secs(),
+        })
+    }
+    fn stop(mut self) {
+        if self.mode.stop().is_ok() {
+            // Failure of the link is logged by `wait`
+            drop(self.mode.wait());
+        }
+        self.mode.report_summary();
+    }
+}
+
+/// Links added and removed at runtime through the control socket. Replies
+/// are `{ "ok": true, "result": ... }` or `{ "ok": false, "error": "..." }`.
+pub struct DaemonMode {
+    addr: Option<ControlAddr>,
+    script: Option<ScriptConfig>,
+    watcher: Option<ScriptWatcher>,
+    links: BTreeMap<String, DaemonLink>,
+    run_ctl: Arc<AtomicBool>,
+}
+
+impl DaemonMode {
+    /// Links of the `script` are added when the daemon starts. Without the
+    /// control socket the links are managed by the watched script only.
+    pub fn new(addr: Option<ControlAddr>, script: Option<ScriptConfig>) -> Self {
+        Self {
+            addr,
+            script,
+            watcher: None,
+            links: BTreeMap::new(),
+            run_ctl: Arc::new(AtomicBool::new(true)),
+        }
+    }
+    /// Links of the script file are added when the daemon starts and are
+    /// applied again every time the file is modified.
+    pub fn watch(mut self, path: PathBuf) -> Self {
+        self.watcher = Some(ScriptWatcher::new(path));
+        self
+    }
+    /// Returns flag, which stops the daemon when it is cleared.
+    pub fn run_ctl(&self) -> Arc<AtomicBool> {
+        self.run_ctl.clone()
+    }
+    /// Serves the control socket until the daemon is shut down, then stops
+    /// every link.
+    pub fn run(&mut self) -> io::Result<()> {
+        let listener = self.addr.as_ref().map(ControlListener::bind).transpose()?;
+        let res = self.serve(listener.as_ref());
+        self.run_ctl.store(false, Ordering::Relaxed);
+        for (_, link) in std::mem::take(&mut self.links) {
+            link.stop();
+        }
+        res
+    }
+    fn serve(&mut self, listener: Option<&ControlListener>) -> io::Result<()> {
+        if let Some(script) = self.script.take() {
+            for link in script.links() {
+                self.add(link.clone())?;
+            }
+        }
+        if let Some(mut watcher) = self.watcher.take() {
+            let links = watcher.load()?;
+            self.apply(&mut watcher, links)?;
+            tracing::info!("Script {} is watched", watcher.path.display());
+            self.watcher = Some(watcher);
+        }
+        if let Some(addr) = &self.addr {
+            tracing::info!("Control socket is listening on {addr}");
+        }
+        let (tx, rx) = mpsc::channel();
+        let run_ctl = self.run_ctl.clone();
+        thread::scope(|s| {
+            let acceptor = listener.map(|listener| s.spawn(|| listener.serve(tx, &run_ctl)));
+            while self.run_ctl.load(Ordering::Relaxed) {
+                match rx.recv_timeout(POLL_TIMEOUT) {
+                    // Client may be gone, the request is done anyway
+                    Ok((request, reply)) => drop(reply.send(self.handle(&request).to_string())),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(mpsc::RecvTimeoutError::Disconnected) if acceptor.is_some() => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => thread::sleep(POLL_TIMEOUT),
+                }
+                self.reload();
+            }
+            self.run_ctl.store(false, Ordering::Relaxed);
+            acceptor.map_or(Ok(()), |acceptor| {
+                acceptor.join().unwrap_or_else(|_| {
+                    tracing::error!("Unexpected error while joining thread!");
+                    process::exit(1)
+                })
+            })
+        })
+    }
+    /// Applies the watched script, if it is modified. Broken script is
+    /// reported and the running links are kept.
+    fn reload(&mut self) {
+        let Some(mut watcher) = self.watcher.take() else {
+            return;
+        };
+        match watcher.poll() {
+            Some(Ok(links)) => {
+                tracing::info!("Script {} is reloaded", watcher.path.display());
+                // Failed links are reported, the other ones are applied
+                drop(self.apply(&mut watcher, links));
+            }
+            Some(Err(e)) => tracing::warn!(
+                "Script {} reloading failed, running links are kept: {e}",
+                watcher.path.display()
+            ),
+            None => {}
+        }
+        self.watcher = Some(watcher);
+    }
+    /// Stops the links removed from the script, rebinds the changed ones and
+    /// adds the new ones. Returns the first error, but applies every link.
+    fn apply(
+        &mut self,
+        watcher: &mut ScriptWatcher,
+        links: Vec<(LinkConfig, serde_json::Value)>,
+    ) -> io::Result<()> {
+        let previous = std::mem::take(&mut watcher.applied);
+        let mut res = Ok(());
+        for name in previous.keys() {
+            if !links.iter().any(|(link, _)| link.name() == name)
+                && let Some(link) = self.links.remove(name)
+            {
+                link.stop();
+                tracing::info!("Link {name} is removed from the script");
+            }
+        }
+        for (link, source) in links {
+            let name = link.name().to_string();
+            if previous.get(&name) == Some(&source) {
+                watcher.applied.insert(name, source);
+                continue;
+            }
+            if previous.contains_key(&name)
+                && let Some(running) = self.links.remove(&name)
+            {
+                running.stop();
+            }
+            match self.add(link) {
+                Ok(()) => {
+                    watcher.applied.insert(name, source);
+                }
+                Err(e) => {
+                    tracing::error!("Link {name} isn't applied: {e}");
+                    if res.is_ok() {
+                        res = Err(e);
+                    }
+                }
+            }
+        }
+        res
+    }
+    fn handle(&mut self, request: &str) -> serde_json::Value {
+        let res = serde_json::from_str(request)
+            .map_err(|e| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Request parsing failed: {e}"),
+                )
+            })
+            .and_then(|request| self.execute(request));
+        control::reply(res)
+    }
+    fn execute(&mut self, request: ControlRequest) -> io::Result<serde_json::Value> {
+        match request {
+            ControlRequest::Add { link } => self.add(*link)?,
+            ControlRequest::Remove { name } => {
+                let link = self
+                    .links
+                    .remove(&name)
+                    .ok_or_else(|| Self::not_found(&name))?;
+                link.stop();
+                tracing::info!("Link {name} is removed");
+            }
+            ControlRequest::Stats { name: Some(name) } => {
+                let link = self
+                    .links
+                    .get(&name)
+                    .ok_or_else(|| Self::not_found(&name))?;
+                return Ok(link.stats());
+            }
+            ControlRequest::Stats { name: None } => {
+                return Ok(self.links.values().map(DaemonLink::stats).collect());
+            }
+            ControlRequest::Mark { label } => {
+                let captures = inject_marker(&label);
+                tracing::info!("Marker {label} is injected into {captures} captures");
+                return Ok(serde_json::json!({ "captures": captures }));
+            }
+            ControlRequest::Reopen { name, side, params } => {
+                let link = self
+                    .links
+                    .get(&name)
+                    .ok_or_else(|| Self::not_found(&name))?;
+                let reopener = match side {
+                    TraceSide::From => &link.monitor.from_reopener,
+                    TraceSide::To => &link.monitor.to_reopener,
+                };
+                let params = reopener.reopen(params.into())?;
+                return Ok(serde_json::json!({ "params": params.value() }));
+            }
+            ControlRequest::Digests => {
+                return serde_json::to_value(stream_digests()).map_err(io::Error::other);
+            }
+            ControlRequest::Shutdown => {
+                tracing::info!("Daemon is shut down by the control request");
+                self.run_ctl.store(false, Ordering::Relaxed);
+            }
+        }
+        Ok(serde_json::Value::Null)
+    }
+    fn add(&mut self, link: LinkConfig) -> io::Result<()> {
+        let name = link.name().to_string();
+        if self.links.contains_key(&name) {
+            return Err(io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Link {name} already exists"),
+            ));
+        }
+        let (mut mode, monitors) = ScriptMode::new_monitored(&ScriptConfig::from(vec![link]))?;
+        mode.start()?;
+        let monitor = monitors.into_iter().next().expect("monitor of the link");
+        self.links
+            .insert(name.clone(), DaemonLink { mode, monitor });
+        tracing::info!("Link {name} is added");
+        Ok(())
+    }
+    fn not_found(name: &str) -> io::Error {
+        io::Error::new(ErrorKind::NotFound, format!("Link {name} isn't found"))
+    }
+}
+
+pub struct DaemonModeCommand {
+    mode: DaemonMode,
+}
+
+impl DaemonModeCommand {
+    pub fn new(mode: DaemonMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl super::Command for DaemonModeCommand {
+    fn execute(&mut self) {
+        super::oneliner::set_stop_handler(self.mode.run_ctl());
+        if let Err(e) = self.mode.run() {
+            tracing::error!("{e}");
+            process::exit(1);
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn control_requests() {
+        let mut daemon = DaemonMode::new(Some("/tmp/polysock-test.sock".parse().unwrap()), None);
+        let add = r#"{ "cmd": "add", "link": { "name": "gps",
+            "from": { "type": "udp", "params": { "ip_local": "127.0.0.1", "port_local": 8093 } },
+            "to": { "type": "udp", "params": { "ip_local": "127.0.0.1", "port_local": 8094 } } } }"#;
+        assert_eq!(
+            daemon.handle(add),
+            serde_json::json!({ "ok": true, "result": null })
+        );
+        assert_eq!(
+            daemon.handle(add)["error"],
+            "Link gps already exists",
+            "name of the link must be unique"
+        );
+        let stats = daemon.handle(r#"{ "cmd": "stats" }"#);
+        assert_eq!(stats["result"][0]["name"], "gps");
+        assert_eq!(stats["result"][0]["status"], "running");
+        assert_eq!(
+            daemon.handle(r#"{ "cmd": "remove", "name": "gps" }"#)["ok"],
+            true
+        );
+        let digests = daemon.handle(r#"{ "cmd": "digests" }"#);
+        assert!(digests["result"].is_array());
+        let missing = daemon.handle(r#"{ "cmd": "stats", "name": "gps" }"#);
+        assert_eq!(missing["error"], "Link gps isn't found");
+        assert_eq!(daemon.handle("stop")["ok"], false);
+        daemon.handle(r#"{ "cmd": "shutdown" }"#);
+        assert!(!daemon.run_ctl.load(Ordering::Relaxed));
+        assert_eq!(
+            "127.0.0.1:7070".parse::<ControlAddr>().unwrap(),
+            ControlAddr::Tcp("127.0.0.1:7070".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn reopen_socket() {
+        use std::net::UdpSocket;
+
+        let mut daemon = DaemonMode::new(None, None);
+        let add = r#"{ "cmd": "add", "link": { "name": "plc",
+            "from": { "type": "udp", "params": { "ip_local": "127.0.0.1", "port_local": 8111 } },
+            "to": { "type": "udp", "params": { "ip_local": "127.0.0.1", "port_local": 8112 } } } }"#;
+        assert_eq!(daemon.handle(add)["ok"], true);
+        let stats = daemon.links["plc"].monitor.stats.clone();
+        let reopen = r#"{ "cmd": "reopen", "name": "plc", "side": "from",
+            "params": { "port_local": 8113 } }"#;
+        let reply = daemon.handle(reopen);
+        assert_eq!(reply["result"]["params"]["port_local"], 8113);
+        assert_eq!(reply["result"]["params"]["ip_local"], "127.0.0.1");
+        // Binding polling the previous socket releases it, when it's woken
+        let start = std::time::Instant::now();
+        while UdpSocket::bind("127.0.0.1:8111").is_err() {
+            assert!(
+                start.elapsed() < std::time::Duration::from_secs(1),
+                "previous socket must be closed"
+            );
+            thread::sleep(POLL_TIMEOUT / 10);
+        }
+        assert!(UdpSocket::bind("127.0.0.1:8113").is_err());
+        assert!(
+            Arc::ptr_eq(&stats, &daemon.links["plc"].monitor.stats),
+            "link must keep running"
+        );
+
+        // Socket, which can't be opened, is replaced with the previous one
+        let wrong = r#"{ "cmd": "reopen", "name": "plc", "side": "from",
+            "params": { "port_local": "x" } }"#;
+        assert_eq!(daemon.handle(wrong)["ok"], false);
+        assert!(UdpSocket::bind("127.0.0.1:8113").is_err());
+        let missing = daemon.handle(r#"{ "cmd": "reopen", "name": "gps", "side": "to" }"#);
+        assert_eq!(missing["error"], "Link gps isn't found");
+
+        for (_, link) in std::mem::take(&mut daemon.links) {
+            link.stop();
+        }
+    }
+
+    #[test]
+    fn script_reload() {
+        use serde_json::json;
+
+        let path = std::env::temp_dir().join(format!("daemon-reload-{}.json", process::id()));
+        let udp = |port: u16| json!({ "type": "udp", "params": { "ip_local": "127.0.0.1", "port_local": port } });
+        let link =
+            |name: &str, port: u16| json!({ "name": name, "from": udp(port), "to": udp(port + 1) });
+        let write = |links: serde_json::Value| {
+            std::fs::write(&path, json!({ "links": links }).to_string()).unwrap()
+        };
+        let mut daemon = DaemonMode::new(None, None);
+        let mut watcher = ScriptWatcher::new(path.clone());
+        let mut reload = |daemon: &mut DaemonMode, links| {
+            write(links);
+            let links = watcher.load().unwrap();
+            daemon.apply(&mut watcher, links).unwrap();
+            daemon.links.keys().cloned().collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            reload(&mut daemon, json!([link("a", 8095), link("b", 8097)])),
+            ["a", "b"]
+        );
+        let a_stats = daemon.links["a"].monitor.stats.clone();
+        let b_stats = daemon.links["b"].monitor.stats.clone();
+        let mut b_bidir = link("b", 8097);
+        b_bidir["bidir"] = json!(true);
+        assert_eq!(
+            reload(
+                &mut daemon,
+                json!([link("a", 8095), b_bidir, link("c", 8099)])
+            ),
+            ["a", "b", "c"]
+        );
+        assert!(
+            Arc::ptr_eq(&a_stats, &daemon.links["a"].monitor.stats),
+            "unchanged link must keep running"
+        );
+        assert!(!Arc::ptr_eq(&b_stats, &daemon.links["b"].monitor.stats));
+        assert_eq!(reload(&mut daemon, json!([link("a", 8095)])), ["a"]);
+        assert!(Arc::ptr_eq(&a_stats, &daemon.links["a"].monitor.stats));
+
+        write(json!([link("a", 8095), link("a", 8097)]));
+        let err = ScriptWatcher::new(path.clone()).load().err().unwrap();
+        assert!(err.to_string().ends_with("link a is duplicated"));
+
+        for (_, link) in std::mem::take(&mut daemon.links) {
+            link.stop();
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use derive_builder::Builder;
+
+use crate::sock::{BindingOptions, SocketFactory, SocketManager, SocketParams, report_summary};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{io, thread::JoinHandle};
+
+#[derive(Builder)]
+pub struct EchoModeParams {
+    #[builder(default)]
+    params: SocketParams,
+    #[builder(default)]
+    binding: BindingOptions,
+}
+
+/// Socket reflecting everything it receives back to the sender.
+pub struct EchoMode {
+    factory: Box<dyn SocketFactory>,
+    params: EchoModeParams,
+    handle: Option<JoinHandle<io::Result<()>>>,
+    run_ctl: Option<Arc<AtomicBool>>,
+}
+
+#[allow(unused)]
+impl EchoMode {
+    pub fn new(factory: Box<dyn SocketFactory>, params: EchoModeParams) -> Self {
+        Self {
+            factory,
+            params,
+            handle: None,
+            run_ctl: None,
+        }
+    }
+    pub fn start(&mut self) -> io::Result<()> {
+        let manager = SocketManager::new(self.factory.as_ref(), self.factory.as_ref());
+        let (h, r) = manager.bind_echo(&self.params.params, &self.params.binding)?;
+        self.handle = Some(h);
+        self.run_ctl = Some(r);
+        Ok(())
+    }
+    pub fn wait(&mut self) -> io::Result<()> {
+        let handle = self
+            .handle
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        handle.join().unwrap_or_else(|_| {
+            tracing::error!("Unexpected error while joining thread!");
+            process::exit(1)
+        })
+    }
+    /// Returns flag, which stops the running binding when it is cleared.
+    pub fn run_ctl(&self) -> Option<Arc<AtomicBool>> {
+        self.run_ctl.clone()
+    }
+    pub fn stop(&mut self) -> io::Result<()> {
+        let run_ctl = self
+            .run_ctl
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        run_ctl.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Reports totals of the finished binding.
+    pub fn report_summary(&self) {
+        if let Some(stats) = self.params.binding.stats() {
+            report_summary(None, stats);
+        }
+    }
+}
+
+pub struct EchoModeCommand {
+    mode: EchoMode,
+}
+
+impl EchoModeCommand {
+    pub fn new(mode: EchoMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl super::Command for EchoModeCommand {
+    fn execute(&mut self) {
+        if let Err(err) = self.mode.start() {
+            tracing::error!("Error during start echo task: {err}");
+            process::exit(1);
+        }
+        if let Some(run_ctl) = self.mode.run_ctl() {
+            super::oneliner::set_stop_handler(run_ctl);
+        }
+        let res = self.mode.wait();
+        self.mode.report_summary();
+        if let Err(e) = res {
+            tracing::error!("Thread finished with error: {e}");
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::oneliner::{OnelinerMode, OnelinerModeParamsBuilder};
+use crate::error;
+use crate::sock::{BindingOptions, BindingOptionsBuilder, SocketParams};
+use crate::sockets::registry::SocketRegistry;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::process;
+use std::time::Duration;
+
+/// Max time to wait for the data of an example.
+const EXAMPLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Self-contained scenario working on the loopback interface.
+struct Example {
+    name: &'static str,
+    description: &'static str,
+    /// Equivalent command line (ports are chosen when the example is run)
+    command: &'static str,
+    run: fn() -> io::Result<()>,
+}
+
+const EXAMPLES: &[Example] = &[
+    Example {
+        name: "test-gen-stdio",
+        description: "Print 5 text test patterns to STDOUT",
+        command: "polysock oneliner -f test-gen -t stdio \\\n    \
+                  --from-params '{ \"pat\": { \"type\": \"text_str\", \"data\": \"Hello from test-gen\\n\" }, \
+                  \"cycle\": 100000, \"iter_num\": 5 }' \\\n    \
+                  --exit-when '{ \"bytes\": 100 }'",
+        run: run_testgen_stdio,
+    },
+    Example {
+        name: "tcp-echo",
+        description: "TCP server echoing data back through UDP socket sending to itself",
+        command: "polysock oneliner -e bidir -f tcp-server --from-params '{ \"port_local\": 5150 }' \\\n    \
+                  -t udp --to-params '{ \"port_local\": 5151, \"ip_dst\": \"127.0.0.1\", \"port_dst\": 5151 }'",
+        run: run_tcp_echo,
+    },
+    Example {
+        name: "udp-tcp-bridge",
+        description: "Forward UDP datagrams to TCP connection",
+        command: "polysock oneliner -f udp --from-params '{ \"port_local\": 5150 }' \\\n    \
+                  -t tcp-client --to-params '{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 5151 }'",
+        run: run_udp_tcp_bridge,
+    },
+];
+
+/// Lists built-in examples or runs them as smoke tests.
+pub struct ExamplesCommand {
+    name: Option<String>,
+}
+
+impl ExamplesCommand {
+    /// Runs the example with the name (or all of them with "all"), lists
+    /// the examples without the name.
+    pub fn new(name: Option<String>) -> Self {
+        Self { name }
+    }
+    fn list() {
+        println!("Built-in examples (run with `polysock examples <NAME>` or `all`):");
+        for ex in EXAMPLES {
+            println!("  {:<16} {}", ex.name, ex.description);
+        }
+    }
+    /// Runs the examples and returns number of failed ones.
+    fn run(name: &str) -> io::Result<usize> {
+        let selected: Vec<&Example> = EXAMPLES
+            .iter()
+            .filter(|ex| name == "all" || ex.name == name)
+            .collect();
+        if selected.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Example {name} not found"),
+            ));
+        }
+        let mut failed = 0;
+        for ex in selected {
+            println!("=== {}: {}\n{}", ex.name, ex.description, ex.command);
+            match (ex.run)() {
+                Ok(()) => println!("=== {}: OK", ex.name),
+                Err(e) => {
+                    println!("=== {}: FAILED ({e})", ex.name);
+                    failed += 1;
+                }
+            }
+        }
+        Ok(failed)
+    }
+}
+
+impl super::Command for ExamplesCommand {
+    fn execute(&mut self) {
+        let Some(name) = &self.name else {
+            Self::list();
+            return;
+        };
+        match Self::run(name) {
+            Ok(0) => {}
+            Ok(failed) => {
+                eprintln!("{failed} example(s) failed");
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn start_binding(
+    from: (&str, String),
+    to: (&str, String),
+    bidir: bool,
+    binding: BindingOptions,
+) -> io::Result<OnelinerMode> {
+    let factory = |name| {
+        SocketRegistry::global()
+            .create(name)
+            .ok_or_else(|| error::Error::UnknownType(name.to_string()))
+    };
+    let params = OnelinerModeParamsBuilder::default()
+        .f_params(SocketParams::parse(&from.1).map_err(Error::other)?)
+        .to_params(SocketParams::parse(&to.1).map_err(Error::other)?)
+        .bidir(bidir)
+        .blocking(false)
+        .binding(binding)
+        .build()
+        .map_err(Error::other)?;
+    let mut mode = OnelinerMode::new(factory(from.0)?, factory(to.0)?, params);
+    mode.start()?;
+    Ok(mode)
+}
+
+/// Returns port, which is free on the loopback interface now.
+fn free_port() -> io::Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+fn check(cond: bool, msg: &str) -> io::Result<()> {
+    if cond {
+        Ok(())
+    } else {
+        Err(Error::other(msg.to_string()))
+    }
+}
+
+fn run_testgen_stdio() -> io::Result<()> {
+    let binding = BindingOptionsBuilder::default()
+        .exit_condition(Some(
+            serde_json::from_str("{ \"bytes\": 100 }").map_err(Error::other)?,
+        ))
+        .build()
+        .map_err(Error::other)?;
+    let params = "{ \"pat\": { \"type\": \"text_str\", \"data\": \"Hello from test-gen\\n\" }, \
+                  \"cycle\": 100000, \"iter_num\": 5 }";
+    let mut mode = start_binding(
+        ("test-gen", params.to_string()),
+        ("stdio", "{}".to_string()),
+        false,
+        binding,
+    )?;
+    mode.wait()?;
+    Ok(())
+}
+
+fn run_tcp_echo() -> io::Result<()> {
+    let (tcp_port, udp_port) = (free_port()?, free_port()?);
+    let mut mode = start_binding(
+        (
+            "tcp-server",
+            format!("{{ \"ip_local\": \"127.0.0.1\", \"port_local\": {tcp_port} }}"),
+        ),
+        (
+            "udp",
+            format!(
+                "{{ \"ip_local\": \"127.0.0.1\", \"port_local\": {udp_port}, \
+                 \"ip_dst\": \"127.0.0.1\", \"port_dst\": {udp_port} }}"
+            ),
+        ),
+        true,
+        BindingOptions::default(),
+    )?;
+    let res = (|| {
+        let mut client = TcpStream::connect(("127.0.0.1", tcp_port))?;
+        client.set_read_timeout(Some(EXAMPLE_TIMEOUT))?;
+        client.write_all(b"ping")?;
+        let mut echo = [0u8; 4];
+        client.read_exact(&mut echo)?;
+        println!("Echo received: {}", String::from_utf8_lossy(&echo));
+        check(&echo == b"ping", "echo differs from the sent data")
+    })();
+    mode.stop()?;
+    mode.wait()?;
+    res
+}
+
+fn run_udp_tcp_bridge() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let (tcp_port, udp_port) = (listener.local_addr()?.port(), free_port()?);
+    let mut mode = start_binding(
+        (
+            "udp",
+            format!("{{ \"ip_local\": \"127.0.0.1\", \"port_local\": {udp_port} }}"),
+        ),
+        (
+            "tcp-client",
+            format!("{{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": {tcp_port} }}"),
+        ),
+        false,
+        BindingOptions::default(),
+    )?;
+    let res = (|| {
+        let (mut peer, _) = listener.accept()?;
+        peer.set_read_timeout(Some(EXAMPLE_TIMEOUT))?;
+        let sender = UdpSocket::bind("127.0.0.1:0")?;
+        let datagrams: [&[u8]; 3] = [b"one ", b"two ", b"three"];
+        for data in datagrams {
+            sender.send_to(data, ("127.0.0.1", udp_port))?;
+        }
+        let expected = datagrams.concat();
+        let mut received = vec![0u8; expected.len()];
+        peer.read_exact(&mut received)?;
+        println!("TCP peer received: {}", String::from_utf8_lossy(&received));
+        check(received == expected, "TCP data differs from datagrams")
+    })();
+    mode.stop()?;
+    mode.wait()?;
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn loopback_examples() {
+        assert_eq!(ExamplesCommand::run("tcp-echo").unwrap(), 0);
+        assert_eq!(ExamplesCommand::run("udp-tcp-bridge").unwrap(), 0);
+        assert!(ExamplesCommand::run("unknown").is_err());
+    }
+}
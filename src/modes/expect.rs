@@ -0,0 +1,195 @@
+use serde::Deserialize;
+
+use crate::sock::pattern::BytePattern;
+use crate::sock::{SocketFactory, SocketParams, SocketWrapper, poll};
+use std::io;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Max time to wait for data before the deadline of the step is rechecked.
+const POLL_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Data sent by the step.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Stimulus {
+    /// Data in text format (for example "AT\r\n")
+    Text(String),
+    /// Data in hex string format (for example "55ff67")
+    Hex(
+        #[serde(with = "hex::serde")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+}
+
+impl Stimulus {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Text(s) => s.as_bytes(),
+            Self::Hex(v) => v,
+        }
+    }
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+/// Step of the scenario, e.g. `{ "send": { "text": "AT\r\n" }, "expect":
+/// { "regex": "OK\\r\\n" }, "timeout_ms": 500 }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct ExpectStep {
+    /// Data written to the socket when the step starts
+    #[serde(default)]
+    send: Option<Stimulus>,
+    /// Pattern, which must be read before the timeout (the step passes
+    /// once the data is sent, if it is not set)
+    #[serde(default)]
+    expect: Option<BytePattern>,
+    /// Time given to send the data and receive the expected one
+    #[serde(default = "default_timeout_ms")]
+    timeout_ms: u64,
+}
+
+/// Sends the stimuli of the steps in order and checks the responses. Data
+/// read after the matched pattern is kept for the next step.
+pub struct ExpectMode {
+    factory: Box<dyn SocketFactory>,
+    params: SocketParams,
+    steps: Vec<ExpectStep>,
+}
+
+impl ExpectMode {
+    pub fn new(
+        factory: Box<dyn SocketFactory>,
+        params: SocketParams,
+        steps: Vec<ExpectStep>,
+    ) -> Self {
+        Self {
+            factory,
+            params,
+            steps,
+        }
+    }
+    /// Runs the steps, the first failed one is reported as error.
+    pub fn run(&self) -> io::Result<()> {
+        let sock = self
+            .factory
+            .create_sock_blockctl(self.params.clone(), false)?;
+        let sock = SocketWrapper::new(sock).open()?;
+        let mut rx = Vec::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            let deadline = Instant::now() + Duration::from_millis(step.timeout_ms);
+            Self::run_step(&sock, step, &mut rx, deadline)
+                .map_err(|e| io::Error::new(e.kind(), format!("Step {}: {e}", i + 1)))?;
+            tracing::info!("Step {} passed", i + 1);
+        }
+        Ok(())
+    }
+    fn run_step(
+        sock: &SocketWrapper,
+        step: &ExpectStep,
+        rx: &mut Vec<u8>,
+        deadline: Instant,
+    ) -> io::Result<()> {
+        if let Some(send) = &step.send {
+            let mut data = send.as_bytes();
+            while !data.is_empty() {
+                let written = sock.write_bytes(data)?;
+                data = &data[written..];
+                if written == 0 {
+                    if Instant::now() >= deadline {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("{} bytes aren't sent", data.len()),
+                        ));
+                    }
+                    thread::sleep(poll::FALLBACK_SLEEP);
+                }
+            }
+            sock.flush_message()?;
+        }
+        let Some(expect) = &step.expect else {
+            return Ok(());
+        };
+        loop {
+            if let Some(range) = expect.find_range(rx) {
+                rx.drain(..range.end);
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("{expect} isn't received, got \"{}\"", rx.escape_ascii()),
+                ));
+            }
+            if sock.read_bytes_into(rx, usize::MAX)? == 0 {
+                poll::wait_or_sleep(sock.poll_handles(), POLL_TIMEOUT.min(deadline - now))?;
+            }
+        }
+    }
+}
+
+pub struct ExpectModeCommand {
+    mode: ExpectMode,
+}
+
+impl ExpectModeCommand {
+    pub fn new(mode: ExpectMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl super::Command for ExpectModeCommand {
+    fn execute(&mut self) {
+        if let Err(e) = self.mode.run() {
+            tracing::error!("{e}");
+            process::exit(1);
+        }
+        tracing::info!("All steps passed");
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sockets::registry::SocketRegistry;
+    use std::net::UdpSocket;
+
+    fn udp_mode(port: u16, steps: &str) -> ExpectMode {
+        let factory = SocketRegistry::global().create("udp").unwrap();
+        let params = format!(
+            "{{ \"ip_local\": \"127.0.0.1\", \"port_local\": {port}, \"ip_dst\": \"127.0.0.1\", \"port_dst\": {} }}",
+            port + 1
+        );
+        ExpectMode::new(
+            factory,
+            SocketParams::parse(&params).unwrap(),
+            serde_json::from_str(steps).unwrap(),
+        )
+    }
+
+    #[test]
+    fn steps_pass_and_fail() {
+        let peer = UdpSocket::bind("127.0.0.1:8091").unwrap();
+        let steps = r#"[
+            { "send": { "text": "AT\r\n" }, "expect": { "text": "OK" } },
+            { "expect": { "regex": "^\r\n\\+CSQ: \\d+" } },
+            { "expect": { "hex": "ff" }, "timeout_ms": 50 }
+        ]"#;
+        let responder = thread::spawn(move || {
+            let mut buf = [0u8; 16];
+            let (sz, addr) = peer.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..sz], b"AT\r\n");
+            peer.send_to(b"OK\r\n+CSQ: 21", addr).unwrap();
+        });
+        let err = udp_mode(8090, steps).run().unwrap_err();
+        responder.join().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.to_string().starts_with("Step 3: 0xff isn't received"));
+    }
+}
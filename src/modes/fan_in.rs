@@ -0,0 +1,166 @@
+use derive_builder::Builder;
+use serde::Deserialize;
+
+use crate::error;
+use crate::sock::{
+    BindingOptions, DecoratorStack, FanInSource, SocketFactory, SocketManager, SocketParams,
+    TraceSide, TransformDecoratorFactory, report_summary, tag_builder,
+};
+use crate::sockets::registry::SocketRegistry;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{io, thread::JoinHandle};
+
+/// Source of the fan-in mode with its own decorators and tag, e.g.
+/// `{ "type": "udp", "params": { "port_local": 5150 }, "tag": "gps: ",
+/// "decorators": [ "trace_info" ] }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct FanInSourceConfig {
+    /// Socket type of the source
+    #[serde(rename = "type")]
+    ty: String,
+    /// Source socket parameters
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    params: serde_json::Value,
+    /// Decorators of the source, the first one is the closest to the socket
+    #[serde(default)]
+    decorators: DecoratorStack,
+    /// Prefix of every chunk read from the source
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+impl FanInSourceConfig {
+    /// Socket type of the source.
+    pub fn socket_type(&self) -> &str {
+        &self.ty
+    }
+    /// Parameters of the source socket.
+    pub fn source_params(&self) -> SocketParams {
+        SocketParams::new(self.params.clone())
+    }
+    pub fn decorators(&self) -> &DecoratorStack {
+        &self.decorators
+    }
+    /// Creates the decorated factory of the source. The tag is added after
+    /// the decorators, so they see the data as it's read.
+    pub fn create_factory(&self) -> io::Result<Box<dyn SocketFactory>> {
+        let factory = SocketRegistry::global()
+            .create(&self.ty)
+            .ok_or_else(|| error::Error::UnknownType(self.ty.to_string()))?;
+        let factory = self.decorators.build(factory, TraceSide::From)?;
+        Ok(match &self.tag {
+            Some(tag) => TransformDecoratorFactory::new(factory, tag_builder(tag.as_bytes())),
+            None => factory,
+        })
+    }
+}
+
+#[derive(Builder)]
+pub struct FanInModeParams {
+    #[builder(default)]
+    out_params: SocketParams,
+    #[builder(default)]
+    binding: BindingOptions,
+}
+
+/// Several sources merged into one output socket.
+pub struct FanInMode {
+    sources: Vec<(Box<dyn SocketFactory>, SocketParams)>,
+    out_factory: Box<dyn SocketFactory>,
+    params: FanInModeParams,
+    handle: Option<JoinHandle<io::Result<()>>>,
+    run_ctl: Option<Arc<AtomicBool>>,
+}
+
+#[allow(unused)]
+impl FanInMode {
+    pub fn new(
+        sources: Vec<(Box<dyn SocketFactory>, SocketParams)>,
+        out_factory: Box<dyn SocketFactory>,
+        params: FanInModeParams,
+    ) -> Self {
+        Self {
+            sources,
+            out_factory,
+            params,
+            handle: None,
+            run_ctl: None,
+        }
+    }
+    pub fn start(&mut self) -> io::Result<()> {
+        // Input factory of the manager isn't used by the fan-in binding
+        let manager = SocketManager::new(self.out_factory.as_ref(), self.out_factory.as_ref());
+        let sources: Vec<_> = self
+            .sources
+            .iter()
+            .map(|(factory, params)| FanInSource {
+                factory: factory.as_ref(),
+                params: params.clone(),
+            })
+            .collect();
+        let params = &self.params;
+        let (h, r) = manager.bind_fan_in(&sources, &params.out_params, &params.binding)?;
+        self.handle = Some(h);
+        self.run_ctl = Some(r);
+        Ok(())
+    }
+    pub fn wait(&mut self) -> io::Result<()> {
+        let handle = self
+            .handle
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        handle.join().unwrap_or_else(|_| {
+            tracing::error!("Unexpected error while joining thread!");
+            process::exit(1)
+        })
+    }
+    /// Returns flag, which stops the running binding when it is cleared.
+    pub fn run_ctl(&self) -> Option<Arc<AtomicBool>> {
+        self.run_ctl.clone()
+    }
+    pub fn stop(&mut self) -> io::Result<()> {
+        let run_ctl = self
+            .run_ctl
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        run_ctl.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Reports totals of the finished binding.
+    pub fn report_summary(&self) {
+        if let Some(stats) = self.params.binding.stats() {
+            report_summary(None, stats);
+        }
+    }
+}
+
+pub struct FanInModeCommand {
+    mode: FanInMode,
+}
+
+impl FanInModeCommand {
+    pub fn new(mode: FanInMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl super::Command for FanInModeCommand {
+    fn execute(&mut self) {
+        if let Err(err) = self.mode.start() {
+            tracing::error!("Error during start fan-in task: {err}");
+            process::exit(1);
+        }
+        if let Some(run_ctl) = self.mode.run_ctl() {
+            super::oneliner::set_stop_handler(run_ctl);
+        }
+        let res = self.mode.wait();
+        self.mode.report_summary();
+        if let Err(e) = res {
+            tracing::error!("Thread finished with error: {e}");
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use derive_builder::Builder;
+use serde::Deserialize;
+
+use crate::error;
+use crate::sock::{
+    BindingOptions, DecoratorStack, FanOutSink, SocketFactory, SocketManager, SocketParams,
+    TraceSide, report_summary,
+};
+use crate::sockets::registry::SocketRegistry;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{io, thread::JoinHandle};
+
+/// Sink of the fan-out mode with its own decorators, e.g.
+/// `{ "type": "tcp-client", "params": { "ip_dst": "127.0.0.1", "port_dst": 1234 },
+/// "decorators": [ "trace_info", { "stats": 10 } ] }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct FanOutSinkConfig {
+    /// Socket type of the sink
+    #[serde(rename = "type")]
+    ty: String,
+    /// Sink socket parameters
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    params: serde_json::Value,
+    /// Decorators of the sink, the first one is the closest to the socket
+    #[serde(default)]
+    decorators: DecoratorStack,
+}
+
+impl FanOutSinkConfig {
+    /// Socket type of the sink.
+    pub fn socket_type(&self) -> &str {
+        &self.ty
+    }
+    /// Parameters of the sink socket.
+    pub fn sink_params(&self) -> SocketParams {
+        SocketParams::new(self.params.clone())
+    }
+    pub fn decorators(&self) -> &DecoratorStack {
+        &self.decorators
+    }
+    /// Creates the decorated factory of the sink.
+    pub fn create_factory(&self) -> io::Result<Box<dyn SocketFactory>> {
+        let factory = SocketRegistry::global()
+            .create(&self.ty)
+            .ok_or_else(|| error::Error::UnknownType(self.ty.to_string()))?;
+        self.decorators.build(factory, TraceSide::To)
+    }
+}
+
+#[derive(Builder)]
+pub struct FanOutModeParams {
+    #[builder(default)]
+    in_params: SocketParams,
+    #[builder(default = true)]
+    blocking: bool,
+    #[builder(default)]
+    binding: BindingOptions,
+}
+
+/// One input socket mirrored to several sinks.
+pub struct FanOutMode {
+    in_factory: Box<dyn SocketFactory>,
+    sinks: Vec<(Box<dyn SocketFactory>, SocketParams)>,
+    params: FanOutModeParams,
+    handle: Option<JoinHandle<io::Result<()>>>,
+    run_ctl: Option<Arc<AtomicBool>>,
+}
+
+#[allow(unused)]
+impl FanOutMode {
+    pub fn new(
+        in_factory: Box<dyn SocketFactory>,
+        sinks: Vec<(Box<dyn SocketFactory>, SocketParams)>,
+        params: FanOutModeParams,
+    ) -> Self {
+        Self {
+            in_factory,
+            sinks,
+            params,
+            handle: None,
+            run_ctl: None,
+        }
+    }
+    pub fn start(&mut self) -> io::Result<()> {
+        // Output factory of the manager isn't used by the fan-out binding
+        let manager = SocketManager::new(self.in_factory.as_ref(), self.in_factory.as_ref());
+        let sinks: Vec<_> = self
+            .sinks
+            .iter()
+            .map(|(factory, params)| FanOutSink {
+                factory: factory.as_ref(),
+                params: params.clone(),
+            })
+            .collect();
+        let params = &self.params;
+        let (h, r) =
+            manager.bind_fan_out(&params.in_params, &sinks, params.blocking, &params.binding)?;
+        self.handle = Some(h);
+        self.run_ctl = Some(r);
+        Ok(())
+    }
+    pub fn wait(&mut self) -> io::Result<()> {
+        let handle = self
+            .handle
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        handle.join().unwrap_or_else(|_| {
+            tracing::error!("Unexpected error while joining thread!");
+            process::exit(1)
+        })
+    }
+    /// Returns flag, which stops the running binding when it is cleared.
+    pub fn run_ctl(&self) -> Option<Arc<AtomicBool>> {
+        self.run_ctl.clone()
+    }
+    pub fn stop(&mut self) -> io::Result<()> {
+        let run_ctl = self
+            .run_ctl
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        run_ctl.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Reports totals of the finished binding.
+    pub fn report_summary(&self) {
+        if let Some(stats) = self.params.binding.stats() {
+            report_summary(None, stats);
+        }
+    }
+}
+
+pub struct FanOutModeCommand {
+    mode: FanOutMode,
+}
+
+impl FanOutModeCommand {
+    pub fn new(mode: FanOutMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl super::Command for FanOutModeCommand {
+    fn execute(&mut self) {
+        if let Err(err) = self.mode.start() {
+            tracing::error!("Error during start fan-out task: {err}");
+            process::exit(1);
+        }
+        if let Some(run_ctl) = self.mode.run_ctl() {
+            super::oneliner::set_stop_handler(run_ctl);
+        }
+        let res = self.mode.wait();
+        self.mode.report_summary();
+        if let Err(e) = res {
+            tracing::error!("Thread finished with error: {e}");
+            process::exit(1);
+        }
+    }
+}
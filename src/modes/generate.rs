@@ -0,0 +1,152 @@
+use clap::ValueEnum;
+
+/// Path of the binary installed by the package.
+const INSTALLED_BINARY: &str = "/usr/bin/polysock";
+
+/// Deployment manifest format.
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum GenerateTarget {
+    /// systemd service unit
+    Systemd,
+    /// docker-compose service
+    Compose,
+}
+
+/// Prints deployment manifest running the validated polysock command.
+pub struct GenerateCommand {
+    target: GenerateTarget,
+    name: String,
+    image: String,
+    args: Vec<String>,
+}
+
+impl GenerateCommand {
+    /// `args` are polysock arguments without the binary name, e.g.
+    /// `["oneliner", "-f", "udp", ...]`.
+    pub fn new(target: GenerateTarget, name: String, image: String, args: Vec<String>) -> Self {
+        Self {
+            target,
+            name,
+            image,
+            args,
+        }
+    }
+    pub fn render(&self) -> String {
+        match self.target {
+            GenerateTarget::Systemd => self.render_systemd(),
+            GenerateTarget::Compose => self.render_compose(),
+        }
+    }
+    fn render_systemd(&self) -> String {
+        let exec: Vec<String> = std::iter::once(INSTALLED_BINARY.to_string())
+            .chain(self.args.iter().map(|a| systemd_quote(a)))
+            .collect();
+        format!(
+            "[Unit]\n\
+             Description=polysock binding {name}\n\
+             After=network-online.target\n\
+             Wants=network-online.target\n\
+             \n\
+             [Service]\n\
+             ExecStart={exec}\n\
+             Restart=on-failure\n\
+             RestartSec=1\n\
+             DynamicUser=yes\n\
+             \n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            name = self.name,
+            exec = exec.join(" "),
+        )
+    }
+    fn render_compose(&self) -> String {
+        // JSON array of strings is a valid YAML flow sequence
+        let command: Vec<&str> = std::iter::once("polysock")
+            .chain(self.args.iter().map(String::as_str))
+            .collect();
+        format!(
+            "services:\n  \
+               {name}:\n    \
+                 image: {image}\n    \
+                 restart: unless-stopped\n    \
+                 network_mode: host\n    \
+                 command: {command}\n",
+            name = self.name,
+            image = self.image,
+            command = serde_json::to_string(&command).unwrap(),
+        )
+    }
+}
+
+impl super::Command for GenerateCommand {
+    fn execute(&mut self) {
+        print!("{}", self.render());
+    }
+}
+
+/// Quotes the argument of systemd `ExecStart`: specifiers (`%`) and
+/// variables (`$`) are escaped, arguments with spaces or quotes are
+/// enclosed in double quotes.
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return escaped;
+    }
+    let mut quoted = String::from("\"");
+    for c in escaped.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn render_manifests() {
+        let args: Vec<String> = [
+            "oneliner",
+            "-f",
+            "udp",
+            "--from-params",
+            r#"{ "port_local": 5150 }"#,
+        ]
+        .map(String::from)
+        .to_vec();
+        let unit = GenerateCommand::new(
+            GenerateTarget::Systemd,
+            "gw".to_string(),
+            String::new(),
+            args.clone(),
+        )
+        .render();
+        assert!(unit.contains(
+            r#"ExecStart=/usr/bin/polysock oneliner -f udp --from-params "{ \"port_local\": 5150 }""#
+        ));
+        let compose = GenerateCommand::new(
+            GenerateTarget::Compose,
+            "gw".to_string(),
+            "polysock:latest".to_string(),
+            args,
+        )
+        .render();
+        assert!(compose.contains(
+            r#"command: ["polysock","oneliner","-f","udp","--from-params","{ \"port_local\": 5150 }"]"#
+        ));
+        assert_eq!(systemd_quote("100%"), "100%%");
+    }
+}
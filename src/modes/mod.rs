@@ -1,4 +1,18 @@
+pub mod control;
+pub mod daemon;
+pub mod echo;
+pub mod expect;
+pub mod examples;
+pub mod fan_in;
+pub mod fan_out;
+pub mod generate;
 pub mod oneliner;
+pub mod probe;
+pub mod report;
+pub mod script;
+pub mod validate;
+#[cfg(feature = "tui")]
+pub mod tui;
 
 pub trait Command {
     fn execute(&mut self);
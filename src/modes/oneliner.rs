@@ -1,9 +1,18 @@
 use derive_builder::Builder;
 
-use crate::sock::{SocketFactory, SocketManager, SocketParams};
+use serde::Deserialize;
+
+use super::control::{self, ControlAddr, ControlListener};
+#[cfg(feature = "async")]
+use crate::sock::AsyncSocketManager;
+use crate::sock::{
+    BindingOptions, HistoryRecorder, POLL_TIMEOUT, ReopenFactory, SocketFactory, SocketManager,
+    SocketParams, SocketReopener, TraceSide, inject_marker, report_summary, stream_digests,
+};
 use std::process;
-use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::sync::{Arc, mpsc};
+use std::thread;
 use std::{io, sync::atomic::AtomicBool, thread::JoinHandle};
 
 pub struct OnelinerMode {
@@ -12,7 +21,16 @@ pub struct OnelinerMode {
     params: OnelinerModeParams,
     handle1: Option<JoinHandle<io::Result<()>>>,
     handle2: Option<JoinHandle<io::Result<()>>>,
+    control: Option<JoinHandle<io::Result<()>>>,
+    /// Requests of the control socket, they are executed by `wait`
+    requests: Option<mpsc::Receiver<control::Request>>,
+    from_reopener: SocketReopener,
+    to_reopener: SocketReopener,
     run_ctl: Option<Arc<AtomicBool>>,
+    #[cfg(feature = "async")]
+    runtime: Option<tokio::runtime::Runtime>,
+    #[cfg(feature = "async")]
+    tasks: Vec<tokio::task::JoinHandle<io::Result<()>>>,
 }
 
 #[derive(Builder)]
@@ -21,10 +39,20 @@ pub struct OnelinerModeParams {
     f_params: SocketParams,
     #[builder(default)]
     to_params: SocketParams,
+    #[builder(default)]
+    control: Option<ControlAddr>,
     #[builder(default = false)]
     bidir: bool,
     #[builder(default = true)]
     blocking: bool,
+    #[builder(default)]
+    binding: BindingOptions,
+    /// Run binding directions as tokio tasks (requires `async` feature)
+    #[builder(default = false)]
+    async_engine: bool,
+    /// Recorder of the binding metrics, finished with the binding result
+    #[builder(default)]
+    history: Option<Arc<HistoryRecorder>>,
 }
 
 #[allow(unused)]
@@ -34,48 +62,144 @@ impl OnelinerMode {
         todev: Box<dyn SocketFactory>,
         params: OnelinerModeParams,
     ) -> Self {
+        let (f_factory, from_reopener) = ReopenFactory::new(fdev);
+        let (to_factory, to_reopener) = ReopenFactory::new(todev);
         Self {
-            f_factory: fdev,
-            to_factory: todev,
+            f_factory,
+            to_factory,
             params,
             handle1: None,
             handle2: None,
+            control: None,
+            requests: None,
+            from_reopener,
+            to_reopener,
             run_ctl: None,
+            #[cfg(feature = "async")]
+            runtime: None,
+            #[cfg(feature = "async")]
+            tasks: Vec::new(),
         }
     }
     pub fn start(&mut self) -> io::Result<()> {
+        // Control socket is bound first, so the binding isn't started if
+        // the address is busy
+        let listener = self
+            .params
+            .control
+            .as_ref()
+            .map(ControlListener::bind)
+            .transpose()?;
+        self.bind()?;
+        if let (Some(listener), Some(run_ctl)) = (listener, self.run_ctl.clone()) {
+            let (tx, rx) = mpsc::channel::<control::Request>();
+            self.control = Some(thread::spawn(move || listener.serve(tx, &run_ctl)));
+            self.requests = Some(rx);
+        }
+        Ok(())
+    }
+    fn bind(&mut self) -> io::Result<()> {
         let manager = SocketManager::new(self.f_factory.as_ref(), self.to_factory.as_ref());
         let params = &self.params;
+        if params.async_engine {
+            #[cfg(feature = "async")]
+            return self.start_async();
+            #[cfg(not(feature = "async"))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Async engine requires polysock built with the `async` feature",
+            ));
+        }
         if !params.bidir {
             let (h, r) = manager.bind_unidirectional(
                 &params.f_params,
                 &params.to_params,
                 params.blocking,
+                &params.binding,
             )?;
             self.handle1 = Some(h);
             self.run_ctl = Some(r);
         } else {
-            let (h1, h2, r) = manager.bind_bidirectional(&params.f_params, &params.to_params)?;
+            let (h1, h2, r) =
+                manager.bind_bidirectional(&params.f_params, &params.to_params, &params.binding)?;
             self.handle1 = Some(h1);
             self.handle2 = Some(h2);
             self.run_ctl = Some(r);
         }
         Ok(())
     }
+    #[cfg(feature = "async")]
+    fn start_async(&mut self) -> io::Result<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        let manager = AsyncSocketManager::new(
+            SocketManager::new(self.f_factory.as_ref(), self.to_factory.as_ref()),
+            runtime.handle().clone(),
+        );
+        let params = &self.params;
+        if !params.bidir {
+            let (h, r) = manager.bind_unidirectional(
+                &params.f_params,
+                &params.to_params,
+                &params.binding,
+            )?;
+            self.tasks.push(h);
+            self.run_ctl = Some(r);
+        } else {
+            let (h1, h2, r) =
+                manager.bind_bidirectional(&params.f_params, &params.to_params, &params.binding)?;
+            self.tasks.push(h1);
+            self.tasks.push(h2);
+            self.run_ctl = Some(r);
+        }
+        self.runtime = Some(runtime);
+        Ok(())
+    }
+    #[cfg(feature = "async")]
+    fn wait_async(&mut self) -> io::Result<()> {
+        let runtime = self
+            .runtime
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        let tasks = std::mem::take(&mut self.tasks);
+        runtime.block_on(async {
+            for task in tasks {
+                task.await.unwrap_or_else(|_| {
+                    tracing::error!("Unexpected error while joining task!");
+                    process::exit(1)
+                })?;
+            }
+            Ok(())
+        })
+    }
     pub fn wait(&mut self) -> io::Result<()> {
+        self.serve_requests();
+        #[cfg(feature = "async")]
+        if self.runtime.is_some() {
+            return self.wait_async();
+        }
         if let Some(handle1) = self.handle1.take() {
-            handle1.join().unwrap_or_else(|_| {eprintln!("Unexpected error while joining thread!"); process::exit(1)})
+            handle1.join().unwrap_or_else(|_| {tracing::error!("Unexpected error while joining thread!"); process::exit(1)})
         } else {
             return Err(io::Error::from(io::ErrorKind::InvalidData));
         }?;
         if let Some(handle2) = self.handle2.take() {
-            handle2.join().unwrap_or_else(|_| {eprintln!("Unexpected error while joining thread!"); process::exit(1)})
+            handle2.join().unwrap_or_else(|_| {tracing::error!("Unexpected error while joining thread!"); process::exit(1)})
         } else {
             return Ok(());
         }?;
 
         Ok(())
     }
+    /// Writes the final history row, if metrics are recorded.
+    pub fn finish_history(&self, error: Option<&io::Error>) {
+        if let Some(history) = &self.params.history {
+            history.finish(error);
+        }
+    }
+    /// Returns flag, which stops the running binding when it is cleared.
+    pub fn run_ctl(&self) -> Option<Arc<AtomicBool>> {
+        self.run_ctl.clone()
+    }
     pub fn stop(&mut self) -> io::Result<()> {
         if let Some(run_ctl) = self.run_ctl.take() {
             run_ctl.store(false, Ordering::Relaxed);
@@ -84,6 +208,100 @@ impl OnelinerMode {
         }
         Ok(())
     }
+    /// Executes the requests of the control socket until the binding is
+    /// finished. Sockets are reopened by the thread, which created them.
+    fn serve_requests(&self) {
+        let Some(requests) = &self.requests else {
+            return;
+        };
+        while !self.is_finished() {
+            match requests.recv_timeout(POLL_TIMEOUT) {
+                Ok((request, reply)) => {
+                    let res = serde_json::from_str(&request)
+                        .map_err(|e| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("Request parsing failed: {e}"),
+                            )
+                        })
+                        .and_then(|request| self.execute(request));
+                    drop(reply.send(control::reply(res).to_string()));
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                // Control socket is stopped
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+    /// Checks whether every direction of the binding is finished.
+    fn is_finished(&self) -> bool {
+        let threads = [&self.handle1, &self.handle2]
+            .into_iter()
+            .flatten()
+            .all(|h| h.is_finished());
+        #[cfg(feature = "async")]
+        let threads = threads && self.tasks.iter().all(|t| t.is_finished());
+        threads
+    }
+    fn execute(&self, request: OnelinerRequest) -> io::Result<serde_json::Value> {
+        match request {
+            OnelinerRequest::Mark { label } => {
+                let captures = inject_marker(&label);
+                return Ok(serde_json::json!({ "captures": captures }));
+            }
+            OnelinerRequest::Reopen { side, params } => {
+                let reopener = match side {
+                    TraceSide::From => &self.from_reopener,
+                    TraceSide::To => &self.to_reopener,
+                };
+                let params = reopener.reopen(params.into())?;
+                return Ok(serde_json::json!({ "params": params.value() }));
+            }
+            OnelinerRequest::Digests => {
+                return serde_json::to_value(stream_digests()).map_err(io::Error::other);
+            }
+        }
+        Ok(serde_json::Value::Null)
+    }
+    /// Stops the control socket, when the binding is finished.
+    fn stop_control(&mut self) -> io::Result<()> {
+        if let Some(run_ctl) = &self.run_ctl {
+            run_ctl.store(false, Ordering::Relaxed);
+        }
+        // Clients waiting for the reply are disconnected
+        self.requests = None;
+        if let Some(control) = self.control.take() {
+            control.join().unwrap_or_else(|_| {
+                tracing::error!("Unexpected error while joining thread!");
+                process::exit(1)
+            })?;
+        }
+        Ok(())
+    }
+    /// Reports totals of the finished binding.
+    pub fn report_summary(&self) {
+        if let Some(stats) = self.params.binding.stats() {
+            report_summary(None, stats);
+        }
+    }
+}
+
+/// Request of the control socket of the oneliner, one JSON object per
+/// line, e.g. `{ "cmd": "mark", "label": "start test 7" }`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum OnelinerRequest {
+    /// Injects the labeled marker into the trace output and the captures
+    Mark { label: String },
+    /// Reopens the socket with the parameters merged over the current
+    /// ones, the binding keeps running
+    Reopen {
+        side: TraceSide,
+        #[serde(default)]
+        params: serde_json::Value,
+    },
+    /// Current digests of the sockets with the hash decorator
+    Digests,
 }
 
 pub struct OnelinerModeCommand {
@@ -100,15 +318,40 @@ impl super::Command for OnelinerModeCommand {
     fn execute(&mut self) {
         match self.mode.start() {
             Err(err) => {
-                eprintln!("Error during start oneliner task: {err}");
+                self.mode.finish_history(Some(&err));
+                tracing::error!("Error during start oneliner task: {err}");
                 process::exit(1);
             }
             Ok(_) => {
-                if let Err(e) = self.mode.wait() {
-                    eprintln!("Thread finished with error: {e}");
+                if let Some(run_ctl) = self.mode.run_ctl() {
+                    set_stop_handler(run_ctl);
+                }
+                let res = self.mode.wait();
+                if let Err(e) = self.mode.stop_control() {
+                    tracing::error!("Control socket failed: {e}");
+                }
+                self.mode.finish_history(res.as_ref().err());
+                self.mode.report_summary();
+                if let Err(e) = res {
+                    tracing::error!("Thread finished with error: {e}");
                     process::exit(1);
                 }
             }
         }
     }
 }
+
+/// Ctrl-C (or SIGTERM) stops the binding gracefully, the second one exits
+/// immediately.
+pub(super) fn set_stop_handler(run_ctl: Arc<AtomicBool>) {
+    let res = ctrlc::set_handler(move || {
+        if run_ctl.swap(false, Ordering::Relaxed) {
+            tracing::info!("Stopping the binding, repeat to exit immediately");
+        } else {
+            process::exit(130);
+        }
+    });
+    if let Err(e) = res {
+        tracing::warn!("Stop signal handler isn't set: {e}");
+    }
+}
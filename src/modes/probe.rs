@@ -0,0 +1,167 @@
+use crate::modes::expect::Stimulus;
+use crate::sock::SocketWrapper;
+use crate::sockets::registry::SocketRegistry;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+/// Max length of the printed banner.
+const BANNER_LEN: usize = 256;
+
+/// Reachability of the probed target.
+#[derive(Debug, PartialEq)]
+enum ProbeStatus {
+    /// Connection is established, with the banner read (if requested)
+    Open(Vec<u8>),
+    /// Connection is refused
+    Closed,
+    /// Connection isn't established in time (e.g. packets are dropped)
+    Timeout,
+    /// Address resolution or connection failed for other reason
+    Error(String),
+}
+
+impl std::fmt::Display for ProbeStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open(banner) if banner.is_empty() => write!(f, "open"),
+            Self::Open(banner) => write!(f, "open \"{}\"", banner.escape_ascii()),
+            Self::Closed => write!(f, "closed"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::Error(e) => write!(f, "error: {e}"),
+        }
+    }
+}
+
+/// Connects tcp-client sockets to the `host:port` targets concurrently and
+/// prints their reachability. Exit code is 1, if any target isn't open.
+pub struct ProbeCommand {
+    targets: Vec<String>,
+    timeout: Duration,
+    banner: bool,
+    payload: Option<Stimulus>,
+}
+
+impl ProbeCommand {
+    /// With `banner` the first data sent by the target (or its response
+    /// to the `payload`) is read within the timeout.
+    pub fn new(
+        targets: Vec<String>,
+        timeout: Duration,
+        banner: bool,
+        payload: Option<Stimulus>,
+    ) -> Self {
+        Self {
+            targets,
+            timeout,
+            banner: banner || payload.is_some(),
+            payload,
+        }
+    }
+    fn resolve(target: &str) -> io::Result<SocketAddr> {
+        target
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("Can't resolve {target}")))
+    }
+    fn probe(&self, target: &str) -> ProbeStatus {
+        let factory = SocketRegistry::global()
+            .create("tcp-client")
+            .expect("tcp-client is a built-in socket type");
+        let connected = Self::resolve(target).and_then(|addr| {
+            let params = serde_json::json!({
+                "ip_dst": addr.ip(),
+                "port_dst": addr.port(),
+                "connect_timeout_ms": self.timeout.as_millis() as u64,
+            });
+            let mut sock = factory.create_sock_blockctl(params.into(), true)?;
+            sock.set_timeout(Some(self.timeout), Some(self.timeout))?;
+            SocketWrapper::new(sock).open()
+        });
+        match connected {
+            Ok(sock) => ProbeStatus::Open(self.grab_banner(&sock).unwrap_or_default()),
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => ProbeStatus::Closed,
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {
+                ProbeStatus::Timeout
+            }
+            Err(e) => ProbeStatus::Error(e.to_string()),
+        }
+    }
+    /// Target may send nothing, so failed read means no banner.
+    fn grab_banner(&self, sock: &SocketWrapper) -> io::Result<Vec<u8>> {
+        if !self.banner {
+            return Ok(Vec::new());
+        }
+        if let Some(payload) = &self.payload {
+            sock.write_bytes(payload.as_bytes())?;
+            sock.flush_message()?;
+        }
+        let mut banner = vec![0u8; BANNER_LEN];
+        let len = sock.get_simple_sock().read(&mut banner, BANNER_LEN)?;
+        banner.truncate(len);
+        Ok(banner)
+    }
+    fn run(&self) -> Vec<ProbeStatus> {
+        thread::scope(|s| {
+            let handles: Vec<_> = self
+                .targets
+                .iter()
+                .map(|target| s.spawn(|| self.probe(target)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .unwrap_or_else(|_| ProbeStatus::Error("Probe panicked".to_string()))
+                })
+                .collect()
+        })
+    }
+}
+
+impl super::Command for ProbeCommand {
+    fn execute(&mut self) {
+        let statuses = self.run();
+        let width = self.targets.iter().map(String::len).max().unwrap_or(0);
+        for (target, status) in self.targets.iter().zip(&statuses) {
+            println!("{target:<width$} {status}");
+        }
+        if statuses.iter().any(|s| !matches!(s, ProbeStatus::Open(_))) {
+            process::exit(1);
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    #[test]
+    fn open_closed_and_banner() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"SSH-2.0-test\r\n").unwrap();
+        });
+        // Port of the dropped listener is closed
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let targets = vec![open, closed, "no.such.host.invalid:80".to_string()];
+        let probe = ProbeCommand::new(targets, Duration::from_secs(1), true, None);
+        let statuses = probe.run();
+        server.join().unwrap();
+        assert_eq!(statuses[0], ProbeStatus::Open(b"SSH-2.0-test\r\n".to_vec()));
+        assert_eq!(statuses[1], ProbeStatus::Closed);
+        assert!(matches!(statuses[2], ProbeStatus::Error(_)));
+        assert_eq!(statuses[0].to_string(), "open \"SSH-2.0-test\\r\\n\"");
+    }
+}
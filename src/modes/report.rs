@@ -0,0 +1,166 @@
+use crate::sock::history::{HistoryEvent, HistoryRow, read_history, unix_now};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+/// Parses time span like `90s`, `30m`, `24h` or `7d`.
+pub fn parse_span(s: &str) -> Result<Duration, String> {
+    let unit_pos = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(unit_pos);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("Invalid time span {s}, expected e.g. 24h"))?;
+    let unit_secs = match unit {
+        "s" | "" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Unknown time unit {unit}, expected s, m, h or d")),
+    };
+    Ok(Duration::from_secs(value * unit_secs))
+}
+
+/// Formats seconds as e.g. `1d 2h 3m 4s`, zero units are skipped.
+fn format_secs(secs: u64) -> String {
+    let units = [(24 * 60 * 60, "d"), (60 * 60, "h"), (60, "m"), (1, "s")];
+    let mut rest = secs;
+    let parts: Vec<String> = units
+        .into_iter()
+        .filter_map(|(unit_secs, name)| {
+            let value = rest / unit_secs;
+            rest %= unit_secs;
+            (value > 0).then(|| format!("{value}{name}"))
+        })
+        .collect();
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Summary of the history rows in the reported period.
+#[derive(Debug, Default, PartialEq)]
+struct Report {
+    runs: usize,
+    failed: usize,
+    uptime: f64,
+    bytes: u64,
+    messages: u64,
+    /// Max throughput of one row (B/s)
+    peak_rate: f64,
+    /// Time and message of errors
+    errors: Vec<(u64, String)>,
+}
+
+impl Report {
+    fn new(rows: &[HistoryRow], since: u64) -> Self {
+        let mut report = Self::default();
+        let mut runs = BTreeSet::new();
+        for row in rows.iter().filter(|r| r.time >= since) {
+            runs.insert(row.run.as_str());
+            report.uptime += row.secs;
+            report.bytes += row.bytes;
+            report.messages += row.messages;
+            if row.secs > 0.0 {
+                report.peak_rate = report.peak_rate.max(row.bytes as f64 / row.secs);
+            }
+            if row.event == HistoryEvent::Error {
+                report.failed += 1;
+                report.errors.push((row.time, row.detail.clone()));
+            }
+        }
+        report.runs = runs.len();
+        report
+    }
+}
+
+/// Prints throughput, uptime and error history of the binding metrics file.
+pub struct ReportCommand {
+    db: PathBuf,
+    since: Duration,
+}
+
+impl ReportCommand {
+    pub fn new(db: PathBuf, since: Duration) -> Self {
+        Self { db, since }
+    }
+}
+
+impl super::Command for ReportCommand {
+    fn execute(&mut self) {
+        let rows = read_history(&self.db).unwrap_or_else(|e| {
+            eprintln!("History file {} reading failed: {e}", self.db.display());
+            process::exit(1)
+        });
+        let now = unix_now();
+        let window = self.since.as_secs().max(1);
+        let report = Report::new(&rows, now.saturating_sub(window));
+        println!(
+            "History of {} for the last {}:",
+            self.db.display(),
+            format_secs(window)
+        );
+        println!("Runs: {}, failed: {}", report.runs, report.failed);
+        println!(
+            "Uptime: {} ({:.1}%)",
+            format_secs(report.uptime as u64),
+            report.uptime * 100.0 / window as f64
+        );
+        println!(
+            "Traffic: {} bytes, {} messages ({:.1} B/s average, {:.1} B/s peak)",
+            report.bytes,
+            report.messages,
+            report.bytes as f64 / report.uptime.max(1.0),
+            report.peak_rate
+        );
+        println!("Errors: {}", report.errors.len());
+        for (time, detail) in &report.errors {
+            println!("  {} ago: {detail}", format_secs(now.saturating_sub(*time)));
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn report_summary() {
+        assert_eq!(parse_span("24h"), Ok(Duration::from_secs(86400)));
+        assert_eq!(parse_span("90"), Ok(Duration::from_secs(90)));
+        assert!(parse_span("5w").is_err());
+        assert_eq!(format_secs(90061), "1d 1h 1m 1s");
+        assert_eq!(format_secs(3600), "1h");
+        assert_eq!(format_secs(0), "0s");
+
+        let row = |time: u64, run: &str, event, secs: f64, bytes: u64| HistoryRow {
+            time,
+            run: run.to_string(),
+            event,
+            secs,
+            bytes,
+            messages: bytes / 10,
+            detail: String::new(),
+        };
+        let rows = [
+            // Old run is out of the reported period
+            row(100, "a", HistoryEvent::Stop, 60.0, 5000),
+            row(1000, "b", HistoryEvent::Start, 0.0, 0),
+            row(1060, "b", HistoryEvent::Interval, 60.0, 600),
+            row(1090, "b", HistoryEvent::Error, 30.0, 3000),
+            row(2000, "c", HistoryEvent::Start, 0.0, 0),
+            row(2060, "c", HistoryEvent::Interval, 60.0, 1200),
+        ];
+        let report = Report::new(&rows, 500);
+        assert_eq!(report.runs, 2);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.uptime, 150.0);
+        assert_eq!(report.bytes, 4800);
+        assert_eq!(report.messages, 480);
+        assert_eq!(report.peak_rate, 100.0);
+        assert_eq!(report.errors, [(1090, String::new())]);
+    }
+}
@@ -0,0 +1,548 @@
+use serde::Deserialize;
+
+use crate::error;
+#[cfg(feature = "async")]
+use crate::sock::AsyncSocketManager;
+use crate::sock::{
+    BindingOptions, BindingOptionsBuilder, BindingStats, Coalesce, DecoratorStack, DecoratorSwitch,
+    ExitCondition, Framing, HalfDuplex, Link, OverflowPolicy, ReopenFactory, SocketFactory,
+    SocketManager, SocketParams, SocketReopener, TapDecoratorFactory, TraceSide, TrafficTap,
+    XonXoff, report_summary,
+};
+use crate::sockets::registry::SocketRegistry;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::{fs, io, thread::JoinHandle};
+
+/// Socket of the link with its own decorators, e.g.
+/// `{ "type": "udp", "params": { "port_local": 5150 }, "decorators": [ "trace_info" ] }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct EndpointConfig {
+    /// Socket type
+    #[serde(rename = "type")]
+    ty: String,
+    /// Socket parameters
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    params: serde_json::Value,
+    /// Decorators of the socket, the first one is the closest to the socket
+    #[serde(default)]
+    decorators: DecoratorStack,
+}
+
+impl EndpointConfig {
+    pub fn socket_type(&self) -> &str {
+        &self.ty
+    }
+    pub fn socket_params(&self) -> SocketParams {
+        SocketParams::new(self.params.clone())
+    }
+    /// Creates the decorated factory of the socket.
+    fn create_factory(&self, side: TraceSide) -> io::Result<Box<dyn SocketFactory>> {
+        let factory = SocketRegistry::global()
+            .create(&self.ty)
+            .ok_or_else(|| error::Error::UnknownType(self.ty.to_string()))?;
+        self.decorators.build(factory, side)
+    }
+    /// Creates the decorated factory, decorators of which can be switched
+    /// and the socket can be reopened with other parameters at runtime.
+    fn create_switchable_factory(
+        &self,
+        side: TraceSide,
+    ) -> io::Result<(Box<dyn SocketFactory>, Vec<DecoratorSwitch>, SocketReopener)> {
+        let factory = SocketRegistry::global()
+            .create(&self.ty)
+            .ok_or_else(|| error::Error::UnknownType(self.ty.to_string()))?;
+        let (factory, reopener) = ReopenFactory::new(factory);
+        let (factory, switches) = self.decorators.build_switchable(factory, side)?;
+        Ok((factory, switches, reopener))
+    }
+}
+
+/// Named binding of the script.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct LinkConfig {
+    /// Unique name of the link in log events
+    name: String,
+    /// The first socket to bind
+    from: EndpointConfig,
+    /// The second socket to bind
+    to: EndpointConfig,
+    /// Data is passed in both directions
+    #[serde(default)]
+    bidir: bool,
+    /// Blocking input (unidirectional link only)
+    #[serde(default)]
+    blocking: bool,
+    /// Condition which stops the link
+    #[serde(default)]
+    exit_when: Option<ExitCondition>,
+    /// Seconds without data in any direction, after which the link is closed
+    #[serde(default)]
+    idle_timeout: Option<f64>,
+    /// Bridging of message sockets with stream ones
+    #[serde(default)]
+    framing: Framing,
+    /// Capacity of the buffer of every direction
+    #[serde(default)]
+    buffer_size: Option<usize>,
+    /// Bytes per socket read of every direction
+    #[serde(default)]
+    read_chunk: Option<usize>,
+    /// Max time in microseconds to wait for incoming data before the link
+    /// state is rechecked
+    #[serde(default)]
+    poll_interval_us: Option<u64>,
+    /// `read_chunk` of the tx direction (from the second socket back to
+    /// the first one) of the bidirectional link
+    #[serde(default)]
+    read_chunk_tx: Option<usize>,
+    /// `poll_interval_us` of the tx direction of the bidirectional link
+    #[serde(default)]
+    poll_interval_tx_us: Option<u64>,
+    /// Forwarded data is collected and written at once
+    #[serde(default)]
+    coalesce: Option<Coalesce>,
+    /// Policy applied when the buffer is full
+    #[serde(default)]
+    overflow_policy: OverflowPolicy,
+    /// Only one direction of the bidirectional link transmits at a time
+    #[serde(default)]
+    half_duplex: Option<HalfDuplex>,
+    /// Socket(s) of the link whose XON/XOFF bytes pause and resume writes to it
+    #[serde(default)]
+    xon_xoff: Option<XonXoff>,
+}
+
+impl LinkConfig {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// The first and the second sockets of the link.
+    pub fn endpoints(&self) -> [(&'static str, &EndpointConfig); 2] {
+        [("from", &self.from), ("to", &self.to)]
+    }
+    fn invalid(&self, e: io::Error) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Link {}: {e}", self.name),
+        )
+    }
+    /// Exit condition of the link, the inactivity timeout is added to it.
+    fn exit_condition(&self) -> Option<ExitCondition> {
+        match (self.exit_when.clone(), self.idle_timeout) {
+            (cond, None) => cond,
+            (None, Some(secs)) => Some(ExitCondition::Idle(secs)),
+            (Some(cond), Some(secs)) => {
+                Some(ExitCondition::Any(vec![cond, ExitCondition::Idle(secs)]))
+            }
+        }
+    }
+    fn options(&self) -> BindingOptionsBuilder {
+        let mut opts = BindingOptionsBuilder::default();
+        opts.exit_condition(self.exit_condition())
+            .framing(self.framing)
+            .overflow_policy(self.overflow_policy)
+            .half_duplex(self.half_duplex.clone())
+            .xon_xoff(self.xon_xoff)
+            .coalesce(self.coalesce);
+        if let Some(size) = self.buffer_size {
+            opts.buffer_size(size);
+        }
+        if let Some(size) = self.read_chunk {
+            opts.read_chunk(size);
+        }
+        if let Some(us) = self.poll_interval_us {
+            opts.poll_interval(Duration::from_micros(us));
+        }
+        opts.read_chunk_tx(self.read_chunk_tx)
+            .poll_interval_tx(self.poll_interval_tx_us.map(Duration::from_micros));
+        opts
+    }
+}
+
+/// Several links bound in one process, e.g.
+/// `{ "links": [ { "name": "gps", "from": { "type": "udp", "params": { "port_local": 5150 } },
+/// "to": { "type": "stdio" } } ] }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct ScriptConfig {
+    links: Vec<LinkConfig>,
+}
+
+impl From<Vec<LinkConfig>> for ScriptConfig {
+    fn from(links: Vec<LinkConfig>) -> Self {
+        Self { links }
+    }
+}
+
+impl ScriptConfig {
+    pub fn links(&self) -> &[LinkConfig] {
+        &self.links
+    }
+    /// Reads the script from JSON file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let script = fs::read_to_string(path)?;
+        serde_json::from_str(&script).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Script {} parsing failed: {e}", path.display()),
+            )
+        })
+    }
+}
+
+/// Link of the script with the created factories.
+struct LinkSetup {
+    name: String,
+    in_factory: Box<dyn SocketFactory>,
+    out_factory: Box<dyn SocketFactory>,
+    in_params: SocketParams,
+    out_params: SocketParams,
+    bidir: bool,
+    blocking: bool,
+    opts: BindingOptions,
+}
+
+/// Bytes of each direction kept for the monitor of the link.
+const TAP_CAPACITY: usize = 4096;
+
+/// Live state of the link for the monitor (e.g. TUI).
+pub struct LinkMonitor {
+    pub name: String,
+    pub stats: Arc<BindingStats>,
+    /// The link is paused while the flag is set
+    pub paused: Arc<AtomicBool>,
+    /// Traffic of the `from` socket: read data is sent to the `to` one,
+    /// written data is received from it
+    pub tap: Arc<TrafficTap>,
+    pub from_switches: Vec<DecoratorSwitch>,
+    pub to_switches: Vec<DecoratorSwitch>,
+    /// Reopen the sockets with other parameters, the link keeps running
+    pub from_reopener: SocketReopener,
+    pub to_reopener: SocketReopener,
+}
+
+/// Running direction of the link: thread or tokio task of the async engine.
+enum LinkHandle {
+    Thread(JoinHandle<io::Result<()>>),
+    #[cfg(feature = "async")]
+    Task(tokio::task::JoinHandle<io::Result<()>>, tokio::runtime::Handle),
+}
+
+impl LinkHandle {
+    fn is_finished(&self) -> bool {
+        match self {
+            Self::Thread(h) => h.is_finished(),
+            #[cfg(feature = "async")]
+            Self::Task(h, _) => h.is_finished(),
+        }
+    }
+    fn join(self) -> io::Result<()> {
+        match self {
+            Self::Thread(h) => h.join().unwrap_or_else(|_| {
+                tracing::error!("Unexpected error while joining thread!");
+                process::exit(1)
+            }),
+            #[cfg(feature = "async")]
+            Self::Task(h, runtime) => runtime.block_on(h).unwrap_or_else(|_| {
+                tracing::error!("Unexpected error while joining task!");
+                process::exit(1)
+            }),
+        }
+    }
+}
+
+/// Named links of the script running concurrently with shared stop.
+pub struct ScriptMode {
+    links: Vec<LinkSetup>,
+    handles: Vec<(String, Vec<LinkHandle>)>,
+    run_ctl: Option<Arc<AtomicBool>>,
+    /// Run the links as tokio tasks (requires `async` feature)
+    async_engine: bool,
+    #[cfg(feature = "async")]
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+#[allow(unused)]
+impl ScriptMode {
+    /// Creates factories and binding options of every link, so errors of
+    /// the script are reported before any socket is opened.
+    pub fn new(config: &ScriptConfig) -> io::Result<Self> {
+        let links = config
+            .links
+            .iter()
+            .map(|link| Self::setup_plain(link).map_err(|e| link.invalid(e)))
+            .collect::<io::Result<_>>()?;
+        Ok(Self::with_links(links))
+    }
+    /// Same as `new`, but every link is monitored: it can be paused, its
+    /// decorators can be switched and its traffic is kept in the tap.
+    pub fn new_monitored(config: &ScriptConfig) -> io::Result<(Self, Vec<LinkMonitor>)> {
+        let mut monitors = Vec::new();
+        let links = config
+            .links
+            .iter()
+            .map(|link| {
+                let (link_setup, monitor) =
+                    Self::setup_monitored(link).map_err(|e| link.invalid(e))?;
+                monitors.push(monitor);
+                Ok(link_setup)
+            })
+            .collect::<io::Result<_>>()?;
+        Ok((Self::with_links(links), monitors))
+    }
+    fn with_links(links: Vec<LinkSetup>) -> Self {
+        Self {
+            links,
+            handles: Vec::new(),
+            run_ctl: None,
+            async_engine: false,
+            #[cfg(feature = "async")]
+            runtime: None,
+        }
+    }
+    /// Runs the links as tokio tasks instead of threads.
+    pub fn async_engine(mut self, enabled: bool) -> Self {
+        self.async_engine = enabled;
+        self
+    }
+    fn setup(
+        link: &LinkConfig,
+        in_factory: Box<dyn SocketFactory>,
+        out_factory: Box<dyn SocketFactory>,
+        opts: BindingOptionsBuilder,
+    ) -> io::Result<LinkSetup> {
+        Ok(LinkSetup {
+            name: link.name.clone(),
+            in_factory,
+            out_factory,
+            in_params: link.from.socket_params(),
+            out_params: link.to.socket_params(),
+            bidir: link.bidir,
+            blocking: link.blocking,
+            opts: opts.build().map_err(io::Error::other)?,
+        })
+    }
+    fn setup_plain(link: &LinkConfig) -> io::Result<LinkSetup> {
+        let in_factory = link.from.create_factory(TraceSide::From)?;
+        let out_factory = link.to.create_factory(TraceSide::To)?;
+        Self::setup(link, in_factory, out_factory, link.options())
+    }
+    fn setup_monitored(link: &LinkConfig) -> io::Result<(LinkSetup, LinkMonitor)> {
+        let (in_factory, from_switches, from_reopener) =
+            link.from.create_switchable_factory(TraceSide::From)?;
+        let (out_factory, to_switches, to_reopener) =
+            link.to.create_switchable_factory(TraceSide::To)?;
+        let monitor = LinkMonitor {
+            name: link.name.clone(),
+            stats: Arc::new(BindingStats::new()),
+            paused: Arc::new(AtomicBool::new(false)),
+            tap: Arc::new(TrafficTap::new(TAP_CAPACITY)),
+            from_switches,
+            to_switches,
+            from_reopener,
+            to_reopener,
+        };
+        let in_factory = TapDecoratorFactory::new(in_factory, monitor.tap.clone());
+        let mut opts = link.options();
+        opts.stats(Some(monitor.stats.clone()))
+            .paused(Some(monitor.paused.clone()));
+        let link_setup = Self::setup(link, in_factory, out_factory, opts)?;
+        Ok((link_setup, monitor))
+    }
+    fn bound_links(&self) -> Vec<Link<'_>> {
+        self.links
+            .iter()
+            .map(|link| Link {
+                name: link.name.clone(),
+                in_factory: link.in_factory.as_ref(),
+                out_factory: link.out_factory.as_ref(),
+                in_params: link.in_params.clone(),
+                out_params: link.out_params.clone(),
+                bidir: link.bidir,
+                blocking: link.blocking,
+                opts: link.opts.clone(),
+            })
+            .collect()
+    }
+    pub fn start(&mut self) -> io::Result<()> {
+        if self.async_engine {
+            #[cfg(feature = "async")]
+            return self.start_async();
+            #[cfg(not(feature = "async"))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Async engine requires polysock built with the `async` feature",
+            ));
+        }
+        let (handles, run_ctl) = SocketManager::bind_links(&self.bound_links())?;
+        self.handles = handles
+            .into_iter()
+            .map(|(name, handles)| (name, handles.into_iter().map(LinkHandle::Thread).collect()))
+            .collect();
+        self.run_ctl = Some(run_ctl);
+        Ok(())
+    }
+    #[cfg(feature = "async")]
+    fn start_async(&mut self) -> io::Result<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        let (handles, run_ctl) =
+            AsyncSocketManager::bind_links(&self.bound_links(), runtime.handle())?;
+        let task = |h| LinkHandle::Task(h, runtime.handle().clone());
+        self.handles = handles
+            .into_iter()
+            .map(|(name, handles)| (name, handles.into_iter().map(task).collect()))
+            .collect();
+        self.run_ctl = Some(run_ctl);
+        self.runtime = Some(runtime);
+        Ok(())
+    }
+    /// Waits for every link. Failed link is reported, but doesn't stop the
+    /// other ones; the error is returned, if any link failed.
+    pub fn wait(&mut self) -> io::Result<()> {
+        if self.handles.is_empty() {
+            return Err(io::Error::from(io::ErrorKind::InvalidData));
+        }
+        let total = self.handles.len();
+        let mut failed = 0;
+        for (name, handles) in self.handles.drain(..) {
+            let res = handles.into_iter().try_for_each(LinkHandle::join);
+            match res {
+                Ok(()) => tracing::info!("Link {name} is finished"),
+                Err(e) => {
+                    tracing::error!("Link {name} finished with error: {e}");
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(io::Error::other(format!(
+                "{failed} of {total} links failed"
+            )));
+        }
+        Ok(())
+    }
+    /// Returns `true` for every started link, all threads (or tasks) of
+    /// which are finished.
+    pub fn finished(&self) -> Vec<bool> {
+        self.handles
+            .iter()
+            .map(|(_, handles)| handles.iter().all(|h| h.is_finished()))
+            .collect()
+    }
+    /// Returns flag, which stops all links when it is cleared.
+    pub fn run_ctl(&self) -> Option<Arc<AtomicBool>> {
+        self.run_ctl.clone()
+    }
+    pub fn stop(&mut self) -> io::Result<()> {
+        let run_ctl = self
+            .run_ctl
+            .take()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+        run_ctl.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Reports totals of every link.
+    pub fn report_summary(&self) {
+        for link in &self.links {
+            if let Some(stats) = link.opts.stats() {
+                report_summary(Some(&link.name), stats);
+            }
+        }
+    }
+}
+
+pub struct ScriptModeCommand {
+    mode: ScriptMode,
+}
+
+impl ScriptModeCommand {
+    pub fn new(mode: ScriptMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl super::Command for ScriptModeCommand {
+    fn execute(&mut self) {
+        if let Err(err) = self.mode.start() {
+            tracing::error!("Error during start script links: {err}");
+            process::exit(1);
+        }
+        if let Some(run_ctl) = self.mode.run_ctl() {
+            super::oneliner::set_stop_handler(run_ctl);
+        }
+        let res = self.mode.wait();
+        self.mode.report_summary();
+        if let Err(e) = res {
+            tracing::error!("{e}");
+            process::exit(1);
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn script_links() {
+        let config: ScriptConfig = serde_json::from_str(
+            r#"{ "links": [
+                { "name": "gps", "from": { "type": "udp", "params": { "port_local": 5150 } },
+                  "to": { "type": "stdio" }, "framing": "length_prefix" },
+                { "name": "plc", "from": { "type": "udp", "decorators": [ "trace_info" ] },
+                  "to": { "type": "tcp-client" }, "bidir": true, "exit_when": { "bytes": 100 },
+                  "idle_timeout": 5 } ] }"#,
+        )
+        .unwrap();
+        assert!(config.links[0].exit_condition().is_none());
+        let cond = config.links[1].exit_condition().unwrap();
+        assert!(matches!(cond, ExitCondition::Any(ref v) if v.len() == 2));
+        let mode = ScriptMode::new(&config).unwrap();
+        assert_eq!(mode.links.len(), 2);
+        assert_eq!(mode.links[0].in_params.to_string(), r#"{"port_local":5150}"#);
+        assert!(mode.links[1].bidir);
+
+        let config: ScriptConfig = serde_json::from_str(
+            r#"{ "links": [ { "name": "bad", "from": { "type": "nope" }, "to": { "type": "stdio" } } ] }"#,
+        )
+        .unwrap();
+        let err = ScriptMode::new(&config).err().unwrap();
+        assert!(err.to_string().starts_with("Link bad:"));
+        let config: ScriptConfig = serde_json::from_str(
+            r#"{ "links": [ { "name": "idle", "from": { "type": "udp" }, "to": { "type": "stdio" },
+                "idle_timeout": -1 } ] }"#,
+        )
+        .unwrap();
+        let err = ScriptMode::new(&config).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_engine_links() {
+        let gen_check = |name: &str, checked: &str| {
+            format!(
+                r#"{{ "name": "{name}",
+                    "from": {{ "type": "test-gen", "params": {{ "pat": {{ "type": "text_str",
+                        "data": "ping" }}, "cycle": 0, "iter_num": 3 }} }},
+                    "to": {{ "type": "test-check", "params": {{ "pat": {{ "type": "text_str",
+                        "data": "{checked}" }}, "iter_num": 3 }} }} }}"#
+            )
+        };
+        let config = format!(
+            r#"{{ "links": [ {}, {} ] }}"#,
+            gen_check("good", "ping"),
+            gen_check("bad", "pong")
+        );
+        let config: ScriptConfig = serde_json::from_str(&config).unwrap();
+        let mut mode = ScriptMode::new(&config).unwrap().async_engine(true);
+        mode.start().unwrap();
+        // Failure of one link doesn't stop the other one
+        let err = mode.wait().unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 links failed");
+    }
+}
@@ -0,0 +1,404 @@
+use crate::modes::script::{LinkMonitor, ScriptConfig, ScriptMode};
+use crate::sock::{DecoratorSwitch, Direction};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Period of the screen refresh and rate update.
+const REFRESH_PERIOD: Duration = Duration::from_millis(250);
+/// Log lines kept for the log pane.
+const LOG_LINES: usize = 200;
+/// Bytes in one line of the hexdump pane.
+const HEX_LINE: usize = 16;
+
+/// Log of the process shown in the log pane, since log lines written to
+/// STDERR would break the screen.
+pub struct LogBuffer {
+    lines: Mutex<VecDeque<String>>,
+    partial: Mutex<Vec<u8>>,
+    passthrough: AtomicBool,
+}
+
+impl LogBuffer {
+    pub fn global() -> &'static LogBuffer {
+        static LOG: OnceLock<LogBuffer> = OnceLock::new();
+        LOG.get_or_init(|| LogBuffer {
+            lines: Mutex::new(VecDeque::with_capacity(LOG_LINES)),
+            partial: Mutex::new(Vec::new()),
+            passthrough: AtomicBool::new(false),
+        })
+    }
+    /// Writer of the log subscriber.
+    pub fn writer() -> LogWriter {
+        LogWriter(Self::global())
+    }
+    /// Log is written to STDERR again, e.g. when the screen is restored.
+    fn set_passthrough(&self, passthrough: bool) {
+        self.passthrough.store(passthrough, Ordering::Relaxed);
+    }
+    fn push(&self, data: &[u8]) {
+        let mut partial = self.partial.lock().unwrap();
+        partial.extend_from_slice(data);
+        let mut lines = self.lines.lock().unwrap();
+        while let Some(pos) = partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = partial.drain(..=pos).collect();
+            if lines.len() == LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(String::from_utf8_lossy(&line[..pos]).into_owned());
+        }
+    }
+    /// Returns the last `n` lines.
+    fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+pub struct LogWriter(&'static LogBuffer);
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0.passthrough.load(Ordering::Relaxed) {
+            return io::stderr().write(buf);
+        }
+        self.0.push(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Formats data as hexdump lines with offsets and printable characters.
+fn hexdump(data: &[u8]) -> Vec<String> {
+    data.chunks(HEX_LINE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let text: String = chunk
+                .iter()
+                .map(|&b| match b {
+                    0x20..=0x7e => b as char,
+                    _ => '.',
+                })
+                .collect();
+            format!(
+                "{:04x}  {:<width$}  {text}",
+                i * HEX_LINE,
+                hex.join(" "),
+                width = HEX_LINE * 3 - 1
+            )
+        })
+        .collect()
+}
+
+/// Monitored link with the state of its view.
+struct LinkView {
+    monitor: LinkMonitor,
+    finished: bool,
+    /// Bytes at the previous refresh
+    last_bytes: u64,
+    /// Throughput since the previous refresh (B/s)
+    rate: f64,
+}
+
+impl LinkView {
+    fn switches(&self) -> impl Iterator<Item = (&'static str, &DecoratorSwitch)> {
+        let from = self.monitor.from_switches.iter().map(|s| ("from", s));
+        let to = self.monitor.to_switches.iter().map(|s| ("to", s));
+        from.chain(to)
+    }
+    fn status(&self) -> &'static str {
+        if self.finished {
+            "finished"
+        } else if self.monitor.paused.load(Ordering::Relaxed) {
+            "paused"
+        } else {
+            "running"
+        }
+    }
+}
+
+/// Links of the script shown in the terminal UI: statistics of every link,
+/// decorators and traffic of the selected one, and the log.
+pub struct TuiMode {
+    script: ScriptMode,
+    links: Vec<LinkView>,
+    selected: usize,
+}
+
+impl TuiMode {
+    pub fn new(config: &ScriptConfig) -> io::Result<Self> {
+        let (script, monitors) = ScriptMode::new_monitored(config)?;
+        let links = monitors
+            .into_iter()
+            .map(|monitor| LinkView {
+                monitor,
+                finished: false,
+                last_bytes: 0,
+                rate: 0.0,
+            })
+            .collect();
+        Ok(Self {
+            script,
+            links,
+            selected: 0,
+        })
+    }
+    pub fn start(&mut self) -> io::Result<()> {
+        self.script.start()
+    }
+    /// Stops the links and waits for them, see [`ScriptMode::wait`].
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.script.stop()?;
+        self.script.wait()
+    }
+    /// Shows the screen until the user quits.
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut last_refresh = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let timeout = REFRESH_PERIOD.saturating_sub(last_refresh.elapsed());
+            if event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && !self.handle_key(key)
+            {
+                return Ok(());
+            }
+            if last_refresh.elapsed() >= REFRESH_PERIOD {
+                self.refresh(last_refresh.elapsed());
+                last_refresh = Instant::now();
+            }
+        }
+    }
+    fn refresh(&mut self, elapsed: Duration) {
+        let finished = self.script.finished();
+        for (i, link) in self.links.iter_mut().enumerate() {
+            let bytes = link.monitor.stats.bytes();
+            link.rate = (bytes - link.last_bytes) as f64 / elapsed.as_secs_f64();
+            link.last_bytes = bytes;
+            link.finished = finished.get(i).copied().unwrap_or(false);
+        }
+    }
+    /// Returns `false` if the user quits.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let Some(link) = self.links.get(self.selected) else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.links.len() - 1)
+            }
+            KeyCode::Char('p') | KeyCode::Char(' ') => {
+                let paused = !link.monitor.paused.fetch_xor(true, Ordering::Relaxed);
+                let state = if paused { "paused" } else { "resumed" };
+                tracing::info!("Link {} is {state}", link.monitor.name);
+            }
+            KeyCode::Char('c') => link.monitor.tap.clear(),
+            KeyCode::Char(c @ '1'..='9') => {
+                let n = c as usize - '1' as usize;
+                if let Some((side, switch)) = link.switches().nth(n) {
+                    let state = if switch.toggle() { "on" } else { "off" };
+                    tracing::info!(
+                        "Decorator {} of {} socket of link {} is {state}",
+                        switch.name(),
+                        side,
+                        link.monitor.name
+                    );
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+    fn draw(&self, frame: &mut Frame) {
+        let [
+            links_area,
+            decorators_area,
+            traffic_area,
+            log_area,
+            help_area,
+        ] = Layout::vertical([
+            Constraint::Length(self.links.len() as u16 + 2),
+            Constraint::Length(3),
+            Constraint::Min(6),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        self.draw_links(frame, links_area);
+        let Some(link) = self.links.get(self.selected) else {
+            return;
+        };
+        let switches: Vec<String> = link
+            .switches()
+            .enumerate()
+            .map(|(i, (side, switch))| {
+                let state = if switch.is_enabled() { "on" } else { "off" };
+                format!("{}:{} ({side}) {state}", i + 1, switch.name())
+            })
+            .collect();
+        let decorators = Paragraph::new(switches.join("  "))
+            .block(Block::bordered().title(format!("Decorators of {}", link.monitor.name)));
+        frame.render_widget(decorators, decorators_area);
+        let [rx_area, tx_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(traffic_area);
+        Self::draw_traffic(frame, rx_area, link, Direction::Rx, "from → to");
+        Self::draw_traffic(frame, tx_area, link, Direction::Tx, "to → from");
+        let log: Vec<Line> = LogBuffer::global()
+            .tail(log_area.height.saturating_sub(2) as usize)
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        frame.render_widget(
+            Paragraph::new(log).block(Block::bordered().title("Log")),
+            log_area,
+        );
+        let help = "q: quit  ↑/↓: select link  p: pause/resume  1-9: toggle decorator  c: clear";
+        frame.render_widget(Paragraph::new(help).dim(), help_area);
+    }
+    fn draw_links(&self, frame: &mut Frame, area: Rect) {
+        let width = self.links.iter().map(|l| l.monitor.name.len()).max();
+        let lines: Vec<Line> = self
+            .links
+            .iter()
+            .enumerate()
+            .map(|(i, link)| {
+                let stats = &link.monitor.stats;
+                let text = format!(
+                    "{:<width$}  {:<8}  {} bytes, {} msgs, {:.0} B/s, up {} s, idle {} s",
+                    link.monitor.name,
+                    link.status(),
+                    stats.bytes(),
+                    stats.messages(),
+                    link.rate,
+                    stats.elapsed().as_secs(),
+                    stats.idle().as_secs(),
+                    width = width.unwrap_or(0)
+                );
+                let line = Line::from(text);
+                if i == self.selected {
+                    line.style(Style::new().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Links")),
+            area,
+        );
+    }
+    fn draw_traffic(frame: &mut Frame, area: Rect, link: &LinkView, dir: Direction, title: &str) {
+        let rows = area.height.saturating_sub(2) as usize;
+        let data = link.monitor.tap.snapshot(dir);
+        // Only the latest data fits the pane
+        let data = &data[data.len().saturating_sub(rows * HEX_LINE)..];
+        let lines: Vec<Line> = hexdump(data).into_iter().map(Line::from).collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}
+
+pub struct TuiModeCommand {
+    mode: TuiMode,
+}
+
+impl TuiModeCommand {
+    pub fn new(mode: TuiMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl super::Command for TuiModeCommand {
+    fn execute(&mut self) {
+        if let Err(err) = self.mode.start() {
+            LogBuffer::global().set_passthrough(true);
+            tracing::error!("Error during start script links: {err}");
+            process::exit(1);
+        }
+        let mut terminal = ratatui::init();
+        let res = self.mode.run(&mut terminal);
+        ratatui::restore();
+        LogBuffer::global().set_passthrough(true);
+        if let Err(e) = res.and_then(|_| self.mode.stop()) {
+            tracing::error!("{e}");
+            process::exit(1);
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn hexdump_lines() {
+        let lines = hexdump(b"0123456789abcdef\x00\xffAB");
+        assert_eq!(
+            lines[0],
+            "0000  30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  0123456789abcdef"
+        );
+        assert_eq!(
+            lines[1],
+            format!("0010  00 ff 41 42{}  ..AB", " ".repeat(36))
+        );
+    }
+
+    #[test]
+    fn keys_pause_and_toggle() {
+        let config: ScriptConfig = serde_json::from_str(
+            r#"{ "links": [
+                { "name": "gps", "from": { "type": "udp", "decorators": [ "trace_info" ] },
+                  "to": { "type": "udp", "decorators": [ { "stats": 5 } ] } },
+                { "name": "plc", "from": { "type": "udp" }, "to": { "type": "udp" } } ] }"#,
+        )
+        .unwrap();
+        let mut mode = TuiMode::new(&config).unwrap();
+        let press = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert!(mode.handle_key(press('p')));
+        assert!(mode.handle_key(press('2')));
+        assert!(mode.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)));
+        let gps = &mode.links[0].monitor;
+        assert!(gps.paused.load(Ordering::Relaxed));
+        assert!(gps.from_switches[0].is_enabled() && !gps.to_switches[0].is_enabled());
+        assert_eq!(mode.selected, 1);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        mode.selected = 0;
+        terminal.draw(|frame| mode.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("gps  paused"));
+        assert!(screen.contains("1:trace_info (from) on  2:stats (to) off"));
+        assert!(!mode.handle_key(press('q')));
+    }
+}
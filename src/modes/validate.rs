@@ -0,0 +1,188 @@
+use crate::error::Error;
+use crate::modes::script::{LinkConfig, ScriptConfig};
+use crate::sock::SocketParams;
+use crate::sock::params::{parse_params, unknown_fields};
+use crate::sockets::registry::SocketRegistry;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+/// Socket of the command line checked without opening it.
+pub struct Endpoint {
+    /// Location of the socket in error messages, e.g. `from` or `to[1]`
+    pub label: String,
+    pub ty: String,
+    pub params: SocketParams,
+}
+
+impl Endpoint {
+    pub fn new(label: &str, ty: &str, params: SocketParams) -> Self {
+        Self {
+            label: label.to_string(),
+            ty: ty.to_string(),
+            params,
+        }
+    }
+}
+
+/// Configuration checked by the command.
+pub enum ValidateTarget {
+    /// Sockets of the mode (dry run)
+    Endpoints(Vec<Endpoint>),
+    /// Script file of the script, daemon or TUI modes
+    Script(PathBuf),
+}
+
+/// Parses the socket parameters and reports errors with the fields, which
+/// caused them. Nothing is opened, exit code is 1 if any error is found.
+pub struct ValidateCommand {
+    target: ValidateTarget,
+}
+
+impl ValidateCommand {
+    pub fn new(target: ValidateTarget) -> Self {
+        Self { target }
+    }
+    /// Returns the found errors, each one is prefixed with its location.
+    pub fn check(&self) -> Vec<String> {
+        match &self.target {
+            ValidateTarget::Endpoints(endpoints) => endpoints
+                .iter()
+                .filter_map(|ep| check_endpoint(&ep.label, &ep.ty, &ep.params))
+                .collect(),
+            ValidateTarget::Script(path) => {
+                let at = path.display();
+                let script = match fs::read_to_string(path) {
+                    Ok(script) => script,
+                    Err(e) => return vec![format!("{at}: {e}")],
+                };
+                let source = match SocketParams::parse(&script) {
+                    Ok(source) => source,
+                    Err(e) => return vec![format!("{at}: {e}")],
+                };
+                let config: ScriptConfig = match parse_params("script", &source) {
+                    Ok(config) => config,
+                    Err(e) => return vec![format!("{at}: {e}")],
+                };
+                let mut names = BTreeSet::new();
+                let mut errors = Vec::new();
+                for (i, link) in config.links().iter().enumerate() {
+                    let label = format!("{at}: links[{i}]");
+                    errors.extend(
+                        unknown_fields::<LinkConfig>(&source.value()["links"][i].clone().into())
+                            .into_iter()
+                            .map(|field| format!("{label}.{field}: unknown field")),
+                    );
+                    if !names.insert(link.name()) {
+                        errors.push(format!("{label}.name: link {} is duplicated", link.name()));
+                    }
+                    errors.extend(link.endpoints().into_iter().filter_map(|(side, ep)| {
+                        let label = format!("{label}.{side}");
+                        check_endpoint(&label, ep.socket_type(), &ep.socket_params())
+                    }));
+                }
+                errors
+            }
+        }
+    }
+}
+
+/// Returns the error of the socket, if its type isn't registered or its
+/// parameters are invalid.
+fn check_endpoint(label: &str, ty: &str, params: &SocketParams) -> Option<String> {
+    let Some(factory) = SocketRegistry::global().create(ty) else {
+        return Some(format!("{label}: {}", Error::UnknownType(ty.to_string())));
+    };
+    factory
+        .validate_params(params)
+        .err()
+        .map(|e| format!("{label}: {e}"))
+}
+
+impl super::Command for ValidateCommand {
+    fn execute(&mut self) {
+        let errors = self.check();
+        if errors.is_empty() {
+            println!("Configuration is valid");
+            return;
+        }
+        for e in &errors {
+            eprintln!("{e}");
+        }
+        process::exit(1);
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+
+    fn params(json: &str) -> SocketParams {
+        SocketParams::parse(json).unwrap()
+    }
+
+    #[test]
+    fn endpoint_errors() {
+        let cmd = ValidateCommand::new(ValidateTarget::Endpoints(vec![
+            Endpoint::new("from", "udp", params(r#"{ "port_local": 5150 }"#)),
+            Endpoint::new("to[0]", "serial", SocketParams::default()),
+            Endpoint::new(
+                "to[1]",
+                "tcp-client",
+                params(r#"{ "ip_dst": "localhost" }"#),
+            ),
+        ]));
+        let errors = cmd.check();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0], "to[0]: Socket type serial is not registered");
+        assert!(
+            errors[1].starts_with("to[1]: Invalid tcp-client configuration: ip_dst: invalid IP"),
+            "{}",
+            errors[1]
+        );
+    }
+
+    #[test]
+    fn script_errors() {
+        let path = std::env::temp_dir().join(format!("validate-{}.json", process::id()));
+        let link = |name: &str, params: &str| {
+            format!(
+                r#"{{ "name": "{name}", "from": {{ "type": "udp", "params": {params} }}, "to": {{ "type": "stdio" }}, "bidi": true }}"#
+            )
+        };
+        let script = format!(
+            r#"{{ "links": [ {}, {} ] }}"#,
+            link("gps", r#"{ "port_local": 5150 }"#),
+            link("gps", r#"{ "port_lcal": 5150 }"#)
+        );
+        fs::write(&path, script).unwrap();
+        let errors = ValidateCommand::new(ValidateTarget::Script(path.clone())).check();
+        let at = path.display();
+        assert_eq!(
+            errors,
+            [
+                format!("{at}: links[0].bidi: unknown field"),
+                format!("{at}: links[1].bidi: unknown field"),
+                format!("{at}: links[1].name: link gps is duplicated"),
+                format!(
+                    "{at}: links[1].from: Invalid udp configuration: unknown field port_lcal, \
+                     expected one of: interface, ip_dst, ip_local, port_dst, port_local, rx_timestamps"
+                ),
+            ]
+        );
+
+        fs::write(
+            &path,
+            r#"{ "links": [ { "name": "gps", "from": { "type": 1 } } ] }"#,
+        )
+        .unwrap();
+        let errors = ValidateCommand::new(ValidateTarget::Script(path.clone())).check();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with(&format!(
+            "{at}: Invalid script configuration: links[0].from.type: invalid type"
+        )));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,493 @@
+use super::buffer::RingBuffer;
+use super::capacity::check_capacity;
+use super::{
+    BindingCtl, BindingOptions, Link, RawHandle, SocketManager, SocketParams, SocketWrapper,
+    poll,
+};
+use std::io::{self, Result};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+type AsyncDoubleRet = (
+    JoinHandle<Result<()>>,
+    JoinHandle<Result<()>>,
+    Arc<AtomicBool>,
+);
+type AsyncSingleRet = (JoinHandle<Result<()>>, Arc<AtomicBool>);
+type AsyncMultiLinkRet = (Vec<(String, Vec<JoinHandle<Result<()>>>)>, Arc<AtomicBool>);
+
+/// Asynchronous counterpart of [`super::SimpleSock`] used by the tasks of
+/// the async engine: nothing blocks the async workers of the runtime.
+pub trait AsyncSimpleSock: Send + Sync {
+    /// Reads up to `limit` bytes (by `chunk` bytes) appended to `buf`.
+    fn read_chunked(
+        &self,
+        buf: &mut Vec<u8>,
+        limit: usize,
+        chunk: usize,
+    ) -> impl Future<Output = Result<usize>> + Send;
+    /// Waits until the socket may have data to read, but not longer than
+    /// `timeout`.
+    fn readable(&mut self, timeout: Duration) -> impl Future<Output = ()> + Send;
+    /// Writes the buffer, as much as the socket takes, returns the written
+    /// length.
+    fn flush(&self, buffer: &mut RingBuffer) -> impl Future<Output = Result<usize>> + Send;
+}
+
+/// Socket of the async engine shared by the directions of the binding.
+///
+/// The task only waits for readiness of the socket handles. Operations of
+/// the socket may block (e.g. sleeping decorators, test-gen cycle, write
+/// timeout or the lock held by the opposite direction), so they run on the
+/// blocking pool of the runtime.
+pub(crate) struct AsyncSocket {
+    sock: Arc<Mutex<SocketWrapper>>,
+    readiness: Readiness,
+}
+
+impl Clone for AsyncSocket {
+    // Handles are registered by the direction reading the socket, the
+    // registration isn't shared
+    fn clone(&self) -> Self {
+        Self {
+            sock: self.sock.clone(),
+            readiness: Readiness::default(),
+        }
+    }
+}
+
+impl AsyncSocket {
+    fn new(sock: SocketWrapper) -> Self {
+        Self {
+            sock: Arc::new(Mutex::new(sock)),
+            readiness: Readiness::default(),
+        }
+    }
+    /// Runs the blocking operation with the socket on the blocking pool.
+    async fn blocking<T, F>(&self, op: F) -> Result<T>
+    where
+        F: FnOnce(&Mutex<SocketWrapper>) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let sock = self.sock.clone();
+        tokio::task::spawn_blocking(move || op(&sock))
+            .await
+            .map_err(io::Error::other)
+    }
+}
+
+impl AsyncSimpleSock for AsyncSocket {
+    async fn read_chunked(&self, buf: &mut Vec<u8>, limit: usize, chunk: usize) -> Result<usize> {
+        // The read buffer is moved to the blocking task and back
+        let mut moved = mem::take(buf);
+        let (res, moved) = self
+            .blocking(move |sock| {
+                let res = sock.lock().unwrap().read_bytes_chunked(&mut moved, limit, chunk);
+                (res, moved)
+            })
+            .await?;
+        *buf = moved;
+        res
+    }
+    async fn readable(&mut self, timeout: Duration) {
+        // Handles are taken without waiting for the lock of the opposite
+        // direction, the socket is just rechecked a bit later then
+        let handles = self.sock.try_lock().ok().and_then(|sock| sock.poll_handles());
+        match handles {
+            Some(handles) => self.readiness.wait(handles, timeout).await,
+            None => tokio::time::sleep(poll::FALLBACK_SLEEP.min(timeout)).await,
+        }
+    }
+    async fn flush(&self, buffer: &mut RingBuffer) -> Result<usize> {
+        let mut moved = mem::take(buffer);
+        let (res, moved) = self
+            .blocking(move |sock| {
+                let res = moved.flush_to(sock);
+                (res, moved)
+            })
+            .await?;
+        *buffer = moved;
+        res
+    }
+}
+
+/// Binding engine running every direction as a tokio task instead of a
+/// dedicated thread, so many bindings can share a small pool of workers.
+///
+/// Sockets are used in nonblocking mode through [`AsyncSimpleSock`]: a task
+/// waits for readiness of the socket handles (see [`super::SockPoll`]), the
+/// socket operations run on the blocking pool of the runtime. So the
+/// runtime of any flavor can be used, the current-thread one too.
+pub struct AsyncSocketManager<'a> {
+    manager: SocketManager<'a>,
+    runtime: Handle,
+}
+
+#[allow(unused)]
+impl<'a> AsyncSocketManager<'a> {
+    pub fn new(manager: SocketManager<'a>, runtime: Handle) -> Self {
+        Self { manager, runtime }
+    }
+    pub fn bind_unidirectional(
+        &self,
+        in_params: &SocketParams,
+        out_params: &SocketParams,
+        opts: &BindingOptions,
+    ) -> io::Result<AsyncSingleRet> {
+        let (input, output) = self.manager.open_pair(in_params, out_params, false, None, opts)?;
+        check_capacity(input.get_sock_info(), output.get_sock_info(), opts);
+        let running = Arc::new(AtomicBool::new(true));
+        let ctl = BindingCtl::new(running.clone(), opts);
+
+        let h = self.spawn_binding(AsyncSocket::new(input), AsyncSocket::new(output), ctl);
+        Ok((h, running))
+    }
+    pub fn bind_bidirectional(
+        &self,
+        from_params: &SocketParams,
+        to_params: &SocketParams,
+        opts: &BindingOptions,
+    ) -> io::Result<AsyncDoubleRet> {
+        let (from, to) = self
+            .manager
+            .open_pair(from_params, to_params, false, Some(false), opts)?;
+        check_capacity(from.get_sock_info(), to.get_sock_info(), opts);
+        check_capacity(to.get_sock_info(), from.get_sock_info(), opts);
+        let running = Arc::new(AtomicBool::new(true));
+        let ctl = BindingCtl::new(running.clone(), opts);
+        let from = AsyncSocket::new(from);
+        let to = AsyncSocket::new(to);
+
+        let h_1_2 = self.spawn_binding(from.clone(), to.clone(), ctl.clone());
+        let h_2_1 = self.spawn_binding(to, from, ctl.reversed());
+        Ok((h_1_2, h_2_1, running))
+    }
+    /// Binds every link in its own tasks, see [`SocketManager::bind_links`].
+    /// If some link can't be bound, the links bound before it are stopped
+    /// (their tasks are finished by the runtime).
+    pub fn bind_links(links: &[Link], runtime: &Handle) -> io::Result<AsyncMultiLinkRet> {
+        let shutdown = Arc::new(AtomicBool::new(true));
+        let mut bound: Vec<(String, Vec<JoinHandle<Result<()>>>)> = Vec::new();
+        for link in links {
+            let res = if bound.iter().any(|(name, _)| *name == link.name) {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Link name is duplicated",
+                ))
+            } else {
+                Self::bind_link(link, &shutdown, runtime)
+            };
+            match res {
+                Ok(handles) => bound.push((link.name.clone(), handles)),
+                Err(e) => {
+                    shutdown.store(false, Ordering::Relaxed);
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("Link {} binding failed: {e}", link.name),
+                    ));
+                }
+            }
+        }
+        Ok((bound, shutdown))
+    }
+    fn bind_link(
+        link: &Link,
+        shutdown: &Arc<AtomicBool>,
+        runtime: &Handle,
+    ) -> io::Result<Vec<JoinHandle<Result<()>>>> {
+        let manager = AsyncSocketManager::new(
+            SocketManager::new(link.in_factory, link.out_factory),
+            runtime.clone(),
+        );
+        let mut opts = link.opts.clone();
+        opts.shutdown = Some(shutdown.clone());
+        opts.name = Some(link.name.clone());
+        if link.bidir {
+            let (h1, h2, _) =
+                manager.bind_bidirectional(&link.in_params, &link.out_params, &opts)?;
+            Ok(vec![h1, h2])
+        } else {
+            let (h, _) = manager.bind_unidirectional(&link.in_params, &link.out_params, &opts)?;
+            Ok(vec![h])
+        }
+    }
+    fn spawn_binding(
+        &self,
+        from: AsyncSocket,
+        to: AsyncSocket,
+        ctl: BindingCtl,
+    ) -> JoinHandle<Result<()>> {
+        let span = ctl.span(&from.sock, &to.sock);
+        let task = async move {
+            let res = binding_task(from, to, ctl.clone()).await;
+            ctl.settle(res)
+        };
+        self.runtime.spawn(task.instrument(span))
+    }
+}
+
+async fn binding_task(mut from: AsyncSocket, to: AsyncSocket, ctl: BindingCtl) -> Result<()> {
+    let mut buffer = ctl.new_buffer();
+    let mut buf: Vec<u8> = Vec::with_capacity(ctl.buffer_size);
+    // Last byte pushed to the buffer
+    let mut last = None;
+    let mut tail = Vec::new();
+    while ctl.is_running() {
+        if ctl.is_paused() {
+            tokio::time::sleep(ctl.poll_interval).await;
+            continue;
+        }
+        buf.clear();
+        let res = from.read_chunked(&mut buf, buffer.read_limit(), ctl.read_chunk).await;
+        if ctl.is_finished(res)? {
+            return finish(ctl, buffer, &to).await;
+        }
+        ctl.strip_flow_control(&mut buf);
+        ctl.inspect(&mut tail, &buf);
+        buffer.push(&buf);
+        last = buf.last().copied().or(last);
+        // More data is collected before the coalesced write
+        let coalesce_wait = ctl.coalesce_wait(&buffer);
+        if buffer.is_empty() || coalesce_wait.is_some() {
+            from.readable(coalesce_wait.unwrap_or(ctl.poll_interval)).await;
+        } else if !ctl.take_line() {
+            // Opposite direction transmits or the sink sent XOFF, data is
+            // kept in the buffer
+            tokio::time::sleep(poll::FALLBACK_SLEEP).await;
+        } else {
+            let Some(written) = ctl.check_flush(to.flush(&mut buffer).await)? else {
+                return Ok(());
+            };
+            ctl.record(written);
+            ctl.release_line(&buffer, last);
+            if written == 0 {
+                // Sink isn't ready, the remainder is retried later
+                tokio::time::sleep(poll::FALLBACK_SLEEP).await;
+            }
+        }
+        if ctl.check_exit() {
+            break;
+        }
+    }
+    // Draining sleeps between write attempts, so it runs on the blocking
+    // pool
+    to.blocking(move |to| ctl.drain(&mut buffer, to)).await?
+}
+
+/// Finishes the direction, whose source has no more data, on the blocking
+/// pool, see [`BindingCtl::finish`].
+async fn finish(ctl: BindingCtl, mut buffer: RingBuffer, to: &AsyncSocket) -> Result<()> {
+    to.blocking(move |to| ctl.finish(&mut buffer, to)).await?
+}
+
+/// Borrowed OS handle registered in the runtime reactor.
+#[cfg(unix)]
+struct BorrowedHandle(RawHandle);
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for BorrowedHandle {
+    fn as_raw_fd(&self) -> RawHandle {
+        self.0
+    }
+}
+
+/// Socket handles registered in the runtime reactor. They are registered
+/// once and again only when the socket reports other handles (e.g. after
+/// it's reopened).
+#[derive(Default)]
+struct Readiness {
+    /// Registered handles, `None` before the first wait
+    #[cfg(unix)]
+    handles: Option<Vec<RawHandle>>,
+    /// `None`, if the handles can't be registered (e.g. regular file), the
+    /// task sleeps shortly then
+    #[cfg(unix)]
+    fds: Option<Vec<tokio::io::unix::AsyncFd<BorrowedHandle>>>,
+}
+
+impl Readiness {
+    /// Waits until any of `handles` may be readable, but not longer than
+    /// `timeout`.
+    #[cfg(unix)]
+    async fn wait(&mut self, handles: Vec<RawHandle>, timeout: Duration) {
+        use std::task::Poll;
+        use tokio::io::{Interest, unix::AsyncFd};
+
+        if self.handles.as_ref() != Some(&handles) {
+            // Previous handles are deregistered first, the new socket may
+            // reuse them
+            self.fds = None;
+            self.fds = handles
+                .iter()
+                .map(|h| AsyncFd::with_interest(BorrowedHandle(*h), Interest::READABLE))
+                .collect::<Result<Vec<_>>>()
+                .inspect_err(|e| tracing::debug!("Readiness of the socket isn't polled: {e}"))
+                .ok();
+            self.handles = Some(handles);
+        }
+        let Some(fds) = &self.fds else {
+            tokio::time::sleep(poll::FALLBACK_SLEEP.min(timeout)).await;
+            return;
+        };
+        // Readiness of the reactor is kept until it's cleared, so the stale
+        // one is cleared, then the data, which has come before, is checked
+        std::future::poll_fn(|cx| {
+            for fd in fds {
+                if let Poll::Ready(Ok(mut guard)) = fd.poll_read_ready(cx) {
+                    guard.clear_ready();
+                }
+            }
+            Poll::Ready(())
+        })
+        .await;
+        let handles = self.handles.as_deref().unwrap_or_default();
+        if poll::wait_readable(handles, Duration::ZERO).unwrap_or(true) {
+            return;
+        }
+        let ready = std::future::poll_fn(|cx| {
+            if fds.iter().any(|fd| fd.poll_read_ready(cx).is_ready()) {
+                return Poll::Ready(());
+            }
+            Poll::Pending
+        });
+        // Timeout is not an error, the binding state is just rechecked
+        let _ = tokio::time::timeout(timeout, ready).await;
+    }
+    /// Readiness isn't polled on this platform, so the task just sleeps
+    /// shortly.
+    #[cfg(not(unix))]
+    async fn wait(&mut self, _handles: Vec<RawHandle>, timeout: Duration) {
+        tokio::time::sleep(poll::FALLBACK_SLEEP.min(timeout)).await;
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::{
+        BindingOptionsBuilder, ComplexSock, ExitCondition, SimpleSock, SockBlockCtl, SockPoll,
+        SocketFactory, make_simple_sock,
+    };
+    use crate::sockets::testgen::TestGenFactory;
+
+    make_simple_sock!(Pipe { input: Mutex<Vec<u8>>, output: Arc<Mutex<Vec<u8>>> }, "pipe");
+
+    impl SimpleSock for Pipe {
+        fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+            let mut input = self.input.lock().unwrap();
+            let len = input.len().min(sz);
+            data[..len].copy_from_slice(&input[..len]);
+            input.drain(..len);
+            Ok(len)
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            self.output.lock().unwrap().extend(&data[..sz]);
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Pipe {}
+    impl SockPoll for Pipe {}
+
+    type PipeOutput = Arc<Mutex<Vec<u8>>>;
+
+    /// Creates the pipe of the index in the params with its input and output.
+    struct PipeFactory(Vec<(&'static [u8], PipeOutput)>);
+
+    impl SocketFactory for PipeFactory {
+        fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+            let (input, output) = &self.0[params.value().as_u64().unwrap() as usize];
+            Ok(Box::new(Pipe::new(Mutex::new(input.to_vec()), output.clone())))
+        }
+    }
+
+    #[test]
+    fn unidirectional_current_thread() {
+        let output = PipeOutput::default();
+        let sink = PipeFactory(vec![(b"", output.clone())]);
+        let source = TestGenFactory::new();
+        // Blocking operations don't need the workers of multi-thread runtime
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let manager =
+            AsyncSocketManager::new(SocketManager::new(&source, &sink), runtime.handle().clone());
+        let params =
+            r#"{ "pat": { "type": "text_str", "data": "ping" }, "cycle": 0, "iter_num": 3 }"#;
+        let (h, running) = manager
+            .bind_unidirectional(
+                &params.parse().unwrap(),
+                &"0".parse().unwrap(),
+                &BindingOptions::default(),
+            )
+            .unwrap();
+        runtime.block_on(h).unwrap().unwrap();
+        assert_eq!(*output.lock().unwrap(), b"pingpingping");
+        assert!(!running.load(Ordering::Relaxed));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn readiness_follows_handles() {
+        use std::os::unix::net::UnixDatagram;
+        use std::time::Instant;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let (rx1, tx1) = UnixDatagram::pair().unwrap();
+        let (rx2, tx2) = UnixDatagram::pair().unwrap();
+        let long = Duration::from_secs(5);
+        runtime.block_on(async {
+            let mut readiness = Readiness::default();
+            // Data, which has come before the wait, isn't missed
+            tx1.send(b"a").unwrap();
+            let start = Instant::now();
+            readiness.wait(vec![poll::raw_handle(&rx1)], long).await;
+            assert!(start.elapsed() < Duration::from_secs(1));
+            rx1.recv(&mut [0; 8]).unwrap();
+
+            // Readiness of the read data is cleared
+            let start = Instant::now();
+            let timeout = Duration::from_millis(50);
+            readiness.wait(vec![poll::raw_handle(&rx1)], timeout).await;
+            assert!(start.elapsed() >= Duration::from_millis(40));
+
+            // Other handles of the socket are registered
+            tx2.send(b"b").unwrap();
+            let start = Instant::now();
+            readiness.wait(vec![poll::raw_handle(&rx2)], long).await;
+            assert!(start.elapsed() < Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn bidirectional() {
+        let outputs: Vec<PipeOutput> = (0..2).map(|_| Arc::default()).collect();
+        let factory = PipeFactory(vec![(b"abc", outputs[0].clone()), (b"xyz", outputs[1].clone())]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let manager = AsyncSocketManager::new(
+            SocketManager::new(&factory, &factory),
+            runtime.handle().clone(),
+        );
+        let opts = BindingOptionsBuilder::default()
+            .exit_condition(Some(ExitCondition::Bytes(6)))
+            .build()
+            .unwrap();
+        let (h1, h2, running) = manager
+            .bind_bidirectional(&"0".parse().unwrap(), &"1".parse().unwrap(), &opts)
+            .unwrap();
+        runtime.block_on(h1).unwrap().unwrap();
+        runtime.block_on(h2).unwrap().unwrap();
+        assert_eq!(*outputs[0].lock().unwrap(), b"xyz");
+        assert_eq!(*outputs[1].lock().unwrap(), b"abc");
+        assert!(!running.load(Ordering::Relaxed));
+    }
+}
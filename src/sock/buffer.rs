@@ -0,0 +1,305 @@
+use super::{Semantics, SocketWrapper};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::io::Result;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default capacity of the buffer between read and write sides of a binding.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Policy applied when the binding buffer is full.
+#[derive(Deserialize, Debug, Clone, Copy, Default, clap::ValueEnum, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Stop reading the source until the buffer is drained
+    #[default]
+    Block,
+    /// Drop the oldest buffered data to store new one
+    DropOldest,
+    /// Drop new data, which doesn't fit the buffer
+    DropNewest,
+}
+
+/// Write coalescing of the binding direction, read chunks are collected in
+/// the buffer and written at once: `{ "size": 512, "timeout_ms": 10 }`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, schemars::JsonSchema)]
+pub struct Coalesce {
+    /// Number of buffered bytes written without waiting
+    size: usize,
+    /// Max time in milliseconds the oldest buffered byte waits for the write
+    timeout_ms: u64,
+}
+
+impl Coalesce {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// Returns time to wait for more data before the buffered `len` bytes
+    /// are written, `None` if they are written now. `since` is the time the
+    /// oldest of them was read.
+    pub fn wait(&self, len: usize, since: Instant) -> Option<Duration> {
+        if len >= self.size {
+            return None;
+        }
+        let timeout = Duration::from_millis(self.timeout_ms);
+        timeout.checked_sub(since.elapsed()).filter(|wait| !wait.is_zero())
+    }
+}
+
+/// Bounded byte buffer between read and write sides of a binding direction.
+#[derive(Default)]
+pub struct RingBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: u64,
+    /// Time the oldest buffered data was pushed
+    since: Option<Instant>,
+    /// Lengths of the buffered chunks, they are written one by one to the
+    /// sink, which keeps message boundaries
+    chunks: VecDeque<usize>,
+}
+
+#[allow(unused)]
+impl RingBuffer {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            data: VecDeque::with_capacity(capacity),
+            capacity,
+            policy,
+            dropped: 0,
+            since: None,
+            chunks: VecDeque::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    /// Time the oldest buffered data was pushed, `None` if it's empty.
+    pub fn since(&self) -> Option<Instant> {
+        self.since
+    }
+    /// Total number of bytes dropped because of overflow.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+    /// Max number of bytes which should be read from the source now. The
+    /// blocking policy reads only into free space, so a slow sink throttles
+    /// the source.
+    pub fn read_limit(&self) -> usize {
+        match self.policy {
+            OverflowPolicy::Block => self.capacity - self.data.len(),
+            _ => self.capacity,
+        }
+    }
+    /// Stores data according to the overflow policy.
+    pub fn push(&mut self, data: &[u8]) {
+        let free = self.capacity - self.data.len();
+        let dropped = data.len().saturating_sub(free);
+        let data = match self.policy {
+            OverflowPolicy::Block | OverflowPolicy::DropNewest => &data[..data.len() - dropped],
+            OverflowPolicy::DropOldest => {
+                // Data larger than the whole buffer keeps only its tail
+                let data = &data[data.len().saturating_sub(self.capacity)..];
+                let evict = (self.data.len() + data.len()).saturating_sub(self.capacity);
+                self.consume(evict);
+                data
+            }
+        };
+        if !data.is_empty() {
+            self.data.extend(data);
+            self.chunks.push_back(data.len());
+        }
+        if self.data.is_empty() {
+            self.since = None;
+        } else if self.since.is_none() {
+            self.since = Some(Instant::now());
+        }
+        if dropped > 0 {
+            self.dropped += dropped as u64;
+            tracing::warn!(
+                "Binding buffer overflow ({:?}): {} bytes dropped",
+                self.policy,
+                dropped
+            );
+        }
+    }
+    /// Removes `len` bytes from the front of the buffer.
+    fn consume(&mut self, mut len: usize) {
+        self.data.drain(..len);
+        while len > 0 {
+            let Some(front) = self.chunks.front_mut() else {
+                break;
+            };
+            if *front > len {
+                *front -= len;
+                break;
+            }
+            len -= *front;
+            self.chunks.pop_front();
+        }
+    }
+    /// Writes buffered data to the socket. Returns number of written bytes,
+    /// the unwritten remainder stays in the buffer for the next attempt.
+    /// Chunks (e.g. collected by the coalescing) are written to the message
+    /// socket one by one, so the datagrams aren't merged.
+    pub fn flush_to(&mut self, to: &Mutex<SocketWrapper>) -> Result<usize> {
+        let sock = to.lock().unwrap();
+        let is_message = sock.get_sock_info().semantics() == Semantics::Message;
+        let mut total = 0;
+        while !self.data.is_empty() {
+            let len = match self.chunks.front() {
+                Some(len) if is_message => *len,
+                _ => self.data.len(),
+            };
+            let written = sock.write_bytes(&self.data.make_contiguous()[..len])?;
+            self.consume(written);
+            total += written;
+            // Read chunks are the messages, the partially written one isn't
+            // finished yet
+            if written < len {
+                break;
+            }
+            sock.flush_message()?;
+        }
+        if self.data.is_empty() {
+            self.since = None;
+        }
+        Ok(total)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::{RawHandle, SimpleSock, SockBlockCtl, SockPoll, make_simple_sock};
+    use std::sync::Arc;
+
+    #[test]
+    fn overflow_policies() {
+        let mut block = RingBuffer::new(4, OverflowPolicy::Block);
+        block.push(b"abc");
+        assert_eq!(block.read_limit(), 1);
+        block.push(b"de");
+        assert_eq!(block.data, b"abcd");
+        assert_eq!(block.dropped(), 1);
+
+        let mut newest = RingBuffer::new(4, OverflowPolicy::DropNewest);
+        newest.push(b"abc");
+        assert_eq!(newest.read_limit(), 4);
+        newest.push(b"def");
+        assert_eq!(newest.data, b"abcd");
+        assert_eq!(newest.dropped(), 2);
+
+        let mut oldest = RingBuffer::new(4, OverflowPolicy::DropOldest);
+        oldest.push(b"abc");
+        oldest.push(b"de");
+        assert_eq!(oldest.data, b"bcde");
+        oldest.push(b"fghijk");
+        assert_eq!(oldest.data, b"hijk");
+        assert_eq!(oldest.dropped(), 7);
+    }
+
+    make_simple_sock!(PartialSink {
+        limit: usize,
+        semantics: Semantics,
+        written: Arc<Mutex<Vec<u8>>>,
+        flushed: Arc<Mutex<Vec<usize>>>,
+    }, "partial"; info: {
+        fn semantics(&self) -> Semantics {
+            self.semantics
+        }
+    });
+
+    impl SimpleSock for PartialSink {
+        fn read(&self, _: &mut [u8], _: usize) -> Result<usize> {
+            Ok(0)
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            let sz = sz.min(self.limit);
+            self.written.lock().unwrap().extend(&data[..sz]);
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for PartialSink {
+        fn flush_message(&self) -> Result<()> {
+            let len = self.written.lock().unwrap().len();
+            self.flushed.lock().unwrap().push(len);
+            Ok(())
+        }
+    }
+    impl SockPoll for PartialSink {}
+
+    #[test]
+    fn partial_flush() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+        let sink = Mutex::new(SocketWrapper::new(Box::new(PartialSink::new(
+            3,
+            Semantics::Stream,
+            written.clone(),
+            flushed.clone(),
+        ))));
+        let mut buffer = RingBuffer::new(16, OverflowPolicy::Block);
+        buffer.push(b"abcdefgh");
+        assert_eq!(buffer.flush_to(&sink).unwrap(), 3);
+        // Unwritten remainder is kept for the next attempt
+        assert_eq!(buffer.data, b"defgh");
+        while !buffer.is_empty() {
+            buffer.flush_to(&sink).unwrap();
+        }
+        assert_eq!(*written.lock().unwrap(), b"abcdefgh");
+        // Message is flushed once, when it's written completely
+        assert_eq!(*flushed.lock().unwrap(), [8]);
+    }
+
+    #[test]
+    fn message_boundaries() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+        let sink = Mutex::new(SocketWrapper::new(Box::new(PartialSink::new(
+            16,
+            Semantics::Message,
+            written.clone(),
+            flushed.clone(),
+        ))));
+        // Coalesced datagrams are written one by one
+        let mut buffer = RingBuffer::new(16, OverflowPolicy::Block);
+        buffer.push(b"ab");
+        buffer.push(b"cde");
+        assert_eq!(buffer.flush_to(&sink).unwrap(), 5);
+        assert_eq!(*flushed.lock().unwrap(), [2, 5]);
+
+        // Datagram, which is partially dropped, keeps its rest
+        let mut buffer = RingBuffer::new(4, OverflowPolicy::DropOldest);
+        buffer.push(b"ab");
+        buffer.push(b"cde");
+        assert_eq!(buffer.flush_to(&sink).unwrap(), 4);
+        assert_eq!(*written.lock().unwrap(), b"abcdebcde");
+        assert_eq!(*flushed.lock().unwrap(), [2, 5, 6, 9]);
+    }
+
+    #[test]
+    fn coalesce_wait() {
+        let config = r#"{ "size": 4, "timeout_ms": 50 }"#;
+        let coalesce: Coalesce = serde_json::from_str(config).unwrap();
+        let mut buffer = RingBuffer::new(16, OverflowPolicy::Block);
+        assert!(buffer.since().is_none());
+        buffer.push(b"ab");
+        let since = buffer.since().unwrap();
+        assert!(coalesce.wait(buffer.len(), since).is_some());
+        // Time of the oldest data is kept
+        buffer.push(b"c");
+        assert_eq!(buffer.since(), Some(since));
+        buffer.push(b"d");
+        assert!(coalesce.wait(buffer.len(), since).is_none());
+        // Data waits no longer than the timeout
+        let expired = since - Duration::from_millis(60);
+        assert!(coalesce.wait(1, expired).is_none());
+    }
+}
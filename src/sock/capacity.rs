@@ -0,0 +1,91 @@
+use super::{BindingOptions, OverflowPolicy, SockInfo};
+use std::time::Duration;
+
+/// Throughput of one binding direction estimated from the socket
+/// configurations before any data is forwarded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CapacityEstimate {
+    /// Rate the source produces data with (B/s)
+    pub source: Option<f64>,
+    /// Rate the sink accepts data with (B/s)
+    pub sink: Option<f64>,
+}
+
+impl CapacityEstimate {
+    pub fn new(from: &dyn SockInfo, to: &dyn SockInfo) -> Self {
+        Self {
+            source: from.source_rate(),
+            sink: to.sink_rate(),
+        }
+    }
+    /// Achievable throughput, `None` if neither side is limited.
+    pub fn throughput(&self) -> Option<f64> {
+        match (self.source, self.sink) {
+            (Some(source), Some(sink)) => Some(source.min(sink)),
+            (rate, None) | (None, rate) => rate,
+        }
+    }
+    /// Rate the buffer grows with, if the source is faster than the sink.
+    pub fn buffer_growth(&self) -> Option<f64> {
+        let growth = self.source? - self.sink?;
+        (growth > 0.0).then_some(growth)
+    }
+    /// Time until the buffer of `size` bytes is full, if it grows.
+    pub fn time_to_fill(&self, size: usize) -> Option<Duration> {
+        self.buffer_growth()
+            .map(|growth| Duration::from_secs_f64(size as f64 / growth))
+    }
+}
+
+/// Logs the estimated throughput of the binding direction and warns, if
+/// the source outpaces the sink, so buffer overflow is predicted before it
+/// happens.
+pub(crate) fn check_capacity(from: &dyn SockInfo, to: &dyn SockInfo, opts: &BindingOptions) {
+    let estimate = CapacityEstimate::new(from, to);
+    let (from, to) = (from.get_description(), to.get_description());
+    if let Some(rate) = estimate.throughput() {
+        tracing::info!("Estimated throughput {from}→{to}: {rate:.1} B/s");
+    }
+    let (Some(source), Some(sink), Some(fill)) = (
+        estimate.source,
+        estimate.sink,
+        estimate.time_to_fill(opts.buffer_size),
+    ) else {
+        return;
+    };
+    let outcome = match opts.overflow_policy {
+        OverflowPolicy::Block => "then the source is slowed down",
+        OverflowPolicy::DropOldest | OverflowPolicy::DropNewest => "then data is dropped",
+    };
+    tracing::warn!(
+        "Source {from} produces {source:.1} B/s, but sink {to} accepts {sink:.1} B/s: \
+         buffer of {} bytes is full in {:.1} s, {outcome}",
+        opts.buffer_size,
+        fill.as_secs_f64()
+    );
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn buffer_growth() {
+        let estimate = |source, sink| CapacityEstimate { source, sink };
+        let slow_sink = estimate(Some(3000.0), Some(1000.0));
+        assert_eq!(slow_sink.throughput(), Some(1000.0));
+        assert_eq!(slow_sink.buffer_growth(), Some(2000.0));
+        assert_eq!(
+            slow_sink.time_to_fill(4096),
+            Some(Duration::from_millis(2048))
+        );
+        let fast_sink = estimate(Some(1000.0), Some(3000.0));
+        assert_eq!(fast_sink.throughput(), Some(1000.0));
+        assert_eq!(fast_sink.time_to_fill(4096), None);
+        // Unknown rate of one side gives no prediction
+        assert_eq!(estimate(None, Some(1000.0)).throughput(), Some(1000.0));
+        assert_eq!(estimate(Some(3000.0), None).buffer_growth(), None);
+        assert_eq!(estimate(None, None).throughput(), None);
+    }
+}
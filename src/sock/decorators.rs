@@ -1,23 +1,90 @@
-use super::{ComplexSock, SimpleSock, SockBlockCtl, SockInfo, SocketFactory, SocketParams};
+use super::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
 use pretty_hex::{self, PrettyHex};
-use std::io::Result;
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use trace_file::TraceStyle;
 
-macro_rules! socket_decorator {
+pub use byte_swap::ByteSwapConfig;
+pub use capture::{CaptureDecoratorFactory, CaptureFile, inject_marker};
+pub use checksum::{ChecksumAlgo, ChecksumConfig, ChecksumDecoratorFactory};
+pub use chunk::{ChunkConfig, ChunkDecoratorFactory};
+pub use compress::{CompressAlgo, CompressConfig, CompressDecoratorFactory};
+pub use delay::{DelayConfig, DelayDecoratorFactory, JitterDistribution};
+pub use demux::{DemuxConfig, DemuxDecoratorFactory};
+pub use encrypt::{CipherAlgo, EncryptConfig};
+pub use fault::{FaultConfig, FaultDecoratorFactory};
+pub use filter::{FilterConfig, FilterDecoratorFactory};
+pub use framing::{
+    Framing, LengthPrefixDecorator, adapt_fan_in_framing, adapt_fan_out_framing, adapt_framing,
+};
+pub use hash::{HashAlgo, HashDecoratorFactory, StreamDigest, stream_digests};
+pub use latency::{LatencyConfig, LatencyDecoratorFactory, LatencyRole};
+pub(crate) use latency::LatencyHistogram;
+pub use monitor::{Direction, MonitorConfig, MonitorDecoratorFactory};
+pub use obfuscate::ObfuscateConfig;
+pub use rate_limit::{RateLimitConfig, RateLimitDecoratorFactory};
+pub use record::{RecordDecoratorFactory, RecordReader, RecordWriter};
+pub use reopen::{ReopenFactory, SocketReopener};
+pub use sequence::{SequenceConfig, SequenceDecoratorFactory};
+pub use stats::StatsDecoratorFactory;
+pub use summary::SummaryDecoratorFactory;
+pub use switch::{DecoratorSwitch, SwitchDecoratorFactory};
+pub use tag::tag_builder;
+pub use tap::{TapDecoratorFactory, TrafficTap};
+pub use tee::{TeeConfig, TeeDecoratorFactory};
+pub use trace_file::{
+    TraceColor, TraceFileConfig, TraceFileConfigBuilder, TraceTimestamp, set_trace_color,
+    set_trace_file, set_trace_timestamp,
+};
+pub use transform::{Transform, TransformBuilder, TransformDecoratorFactory};
+#[cfg(feature = "wasm")]
+pub use wasm::WasmTransform;
+
+// Forwards control & information traits to the decorated socket
+macro_rules! decorator_forward {
+    ($name: ident) => {
+        decorator_forward_blockctl!($name);
+        decorator_forward_info!($name);
+    };
+}
+
+macro_rules! decorator_forward_blockctl {
     ($name: ident) => {
-        pub struct $name {
-            sock: Box<dyn ComplexSock>,
-        }
-        impl $name {
-            #[allow(clippy::new_ret_no_self)]
-            pub fn new(sock: Box<dyn ComplexSock>) -> Box<dyn ComplexSock> {
-                Box::new(Self { sock })
-            }
-        }
         impl SockBlockCtl for $name {
             fn set_block(&mut self, is_blocking: bool) -> Result<()> {
                 self.sock.set_block(is_blocking)
             }
+            fn set_timeout(
+                &mut self,
+                read: Option<std::time::Duration>,
+                write: Option<std::time::Duration>,
+            ) -> Result<()> {
+                self.sock.set_timeout(read, write)
+            }
+            fn set_grace_period(&mut self, grace: std::time::Duration) -> Result<()> {
+                self.sock.set_grace_period(grace)
+            }
+            fn set_echo(&mut self, echo: bool) -> Result<()> {
+                self.sock.set_echo(echo)
+            }
+            fn flush_message(&self) -> Result<()> {
+                self.sock.flush_message()
+            }
+            fn shutdown_write(&self) -> Result<()> {
+                self.sock.shutdown_write()
+            }
         }
+    };
+}
+
+// Forwards information & polling traits to the decorated socket
+macro_rules! decorator_forward_info {
+    ($name: ident) => {
         impl SockInfo for $name {
             fn get_type_name(&self) -> &str {
                 self.sock.get_type_name()
@@ -28,7 +95,39 @@ macro_rules! socket_decorator {
             fn get_description(&self) -> String {
                 self.sock.get_description()
             }
+            fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+                self.sock.rx_timestamp()
+            }
+            fn source_rate(&self) -> Option<f64> {
+                self.sock.source_rate()
+            }
+            fn sink_rate(&self) -> Option<f64> {
+                self.sock.sink_rate()
+            }
+            fn semantics(&self) -> $crate::sock::Semantics {
+                self.sock.semantics()
+            }
+        }
+        impl SockPoll for $name {
+            fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+                self.sock.poll_handles()
+            }
+        }
+    };
+}
+
+macro_rules! socket_decorator {
+    ($name: ident) => {
+        pub struct $name {
+            sock: Box<dyn ComplexSock>,
+        }
+        impl $name {
+            #[allow(clippy::new_ret_no_self)]
+            pub fn new(sock: Box<dyn ComplexSock>) -> Box<dyn ComplexSock> {
+                Box::new(Self { sock })
+            }
         }
+        decorator_forward!($name);
         paste::paste! {
             pub struct [< $name Factory >] {
                 factory: Box<dyn SocketFactory>,
@@ -50,6 +149,56 @@ macro_rules! socket_decorator {
             }
         }
     };
+    // Decorator with configuration: the structure must contain `sock` field
+    // and implement `new(sock, &config) -> Box<dyn ComplexSock>`
+    ($name: ident, $cfg: ty) => {
+        decorator_forward!($name);
+        paste::paste! {
+            pub struct [< $name Factory >] {
+                factory: Box<dyn SocketFactory>,
+                config: $cfg,
+            }
+            impl [< $name Factory >] {
+                #[allow(clippy::new_ret_no_self)]
+                pub fn new(factory: Box<dyn SocketFactory>, config: $cfg) -> Box<dyn SocketFactory> {
+                    Box::new(Self { factory, config })
+                }
+            }
+            impl SocketFactory for [< $name Factory >] {
+                fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+                    self.factory
+                        .create_sock(params)
+                        .map(|sock| $name::new(sock, &self.config))
+                }
+            }
+        }
+    };
+}
+
+// Prints the line of tracing decorators (to the trace file, if it's set),
+// the line is colored with the optional style
+macro_rules! trace_println {
+    (style = $style: expr; $($arg: tt)*) => {
+        $crate::sock::decorators::trace_file::trace_line($style, format_args!($($arg)*))
+    };
+    ($($arg: tt)*) => {
+        trace_println!(
+            style = $crate::sock::decorators::trace_file::TraceStyle::Plain;
+            $($arg)*
+        )
+    };
+}
+
+// Prints the trace line of the socket event in the direction
+macro_rules! trace_event {
+    ($sock: expr, $direction: expr, $style: expr, $($arg: tt)*) => {
+        $crate::sock::decorators::trace_file::trace_event(
+            $style,
+            $direction,
+            &$sock.get_description(),
+            format_args!($($arg)*),
+        )
+    };
 }
 
 macro_rules! decorator_openclose_default {
@@ -63,40 +212,170 @@ macro_rules! decorator_openclose_default {
     };
 }
 
-socket_decorator!(TraceInfoDecorator);
+mod byte_swap;
+mod capture;
+mod checksum;
+mod chunk;
+mod compress;
+mod delay;
+mod demux;
+mod encrypt;
+mod fault;
+mod filter;
+mod framing;
+mod hash;
+mod latency;
+mod monitor;
+mod obfuscate;
+mod rate_limit;
+mod record;
+mod reopen;
+mod sequence;
+mod stats;
+mod summary;
+mod switch;
+mod tag;
+mod tap;
+mod tee;
+mod trace_file;
+mod transform;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+/// Side of the binding, which the traced socket belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceSide {
+    From,
+    To,
+}
+
+impl TraceSide {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::From => "from",
+            Self::To => "to",
+        }
+    }
+    /// Binding direction of the data read from (`rx`) or written to the socket
+    fn direction(self, rx: bool) -> &'static str {
+        match (self, rx) {
+            (Self::From, true) | (Self::To, false) => "from→to",
+            _ => "to→from",
+        }
+    }
+}
+
+macro_rules! trace_decorator {
+    ($name: ident) => {
+        pub struct $name {
+            sock: Box<dyn ComplexSock>,
+            side: TraceSide,
+        }
+        impl $name {
+            #[allow(clippy::new_ret_no_self)]
+            pub fn new(sock: Box<dyn ComplexSock>, side: &TraceSide) -> Box<dyn ComplexSock> {
+                Box::new(Self { sock, side: *side })
+            }
+        }
+        socket_decorator!($name, TraceSide);
+    };
+}
+
+/// No data or interrupted call isn't an error of the socket.
+fn is_traced_error(e: &Error) -> bool {
+    !matches!(
+        e.kind(),
+        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+    )
+}
+
+trace_decorator!(TraceInfoDecorator);
 
 impl SimpleSock for TraceInfoDecorator {
     fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
         let sock = self.sock.as_ref();
         let res = sock.read(data, sz);
-        if let Ok(sz) = res
-            && sz > 0
-        {
-            println!("Data is received from: {}", sock.get_description());
+        let direction = self.side.direction(true);
+        match &res {
+            Ok(0) => {}
+            Ok(_) => match sock.rx_timestamp() {
+                Some(ts) => trace_event!(
+                    sock,
+                    direction,
+                    TraceStyle::Rx,
+                    "Data is received from: {}, kernel rx time: {:?} ago",
+                    sock.get_description(),
+                    ts.elapsed().unwrap_or_default()
+                ),
+                None => trace_event!(
+                    sock,
+                    direction,
+                    TraceStyle::Rx,
+                    "Data is received from: {}",
+                    sock.get_description()
+                ),
+            },
+            Err(e) if is_traced_error(e) => trace_event!(
+                sock,
+                direction,
+                TraceStyle::Error,
+                "Data receiving from {} failed: {e}",
+                sock.get_description()
+            ),
+            Err(_) => {}
         }
         res
     }
-    fn write(&self, data: &[u8], sz: usize) -> Result<()> {
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
         let sock = self.sock.as_ref();
         let res = sock.write(data, sz);
-        if sz > 0 {
-            println!("Data is transered to: {}", sock.get_description());
+        let direction = self.side.direction(false);
+        match &res {
+            Ok(0) => {}
+            Ok(_) => trace_event!(
+                sock,
+                direction,
+                TraceStyle::Tx,
+                "Data is transered to: {}",
+                sock.get_description()
+            ),
+            Err(e) if is_traced_error(e) => trace_event!(
+                sock,
+                direction,
+                TraceStyle::Error,
+                "Data transfer to {} failed: {e}",
+                sock.get_description()
+            ),
+            Err(_) => {}
         }
         res
     }
     fn open(&mut self) -> Result<()> {
         let sock = self.sock.as_mut();
-        println!("Socket is opened: {}", sock.get_description());
+        trace_event!(
+            sock,
+            self.side.as_str(),
+            TraceStyle::Plain,
+            "Socket is opened: {}",
+            sock.get_description()
+        );
         sock.open()
     }
     fn close(&mut self) {
         let sock = self.sock.as_mut();
-        println!("Socket is closed: {}", sock.get_description());
+        trace_event!(
+            sock,
+            self.side.as_str(),
+            TraceStyle::Plain,
+            "Socket is closed: {}",
+            sock.get_description()
+        );
         sock.close()
     }
 }
 
-socket_decorator!(TraceRawDecorator);
+trace_decorator!(TraceRawDecorator);
 
 impl SimpleSock for TraceRawDecorator {
     fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
@@ -104,22 +383,36 @@ impl SimpleSock for TraceRawDecorator {
         if let Ok(sz) = res
             && sz > 0
         {
-            println!("Data is received: {:?}", data[..sz].as_ref());
+            trace_event!(
+                self.sock,
+                self.side.direction(true),
+                TraceStyle::Rx,
+                "Data is received: {:?}",
+                &data[..sz]
+            );
         }
         res
     }
-    fn write(&self, data: &[u8], sz: usize) -> Result<()> {
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
         let sock = self.sock.as_ref();
         let res = sock.write(data, sz);
-        if sz > 0 {
-            println!("Data is written: {:?}", data[..sz].as_ref());
+        if let Ok(sz) = res
+            && sz > 0
+        {
+            trace_event!(
+                sock,
+                self.side.direction(false),
+                TraceStyle::Tx,
+                "Data is written: {:?}",
+                &data[..sz]
+            );
         }
         res
     }
     decorator_openclose_default!();
 }
 
-socket_decorator!(TraceCanonicalDecorator);
+trace_decorator!(TraceCanonicalDecorator);
 
 impl SimpleSock for TraceCanonicalDecorator {
     fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
@@ -127,18 +420,26 @@ impl SimpleSock for TraceCanonicalDecorator {
         if let Ok(sz) = res
             && sz > 0
         {
-            println!(
+            trace_event!(
+                self.sock,
+                self.side.direction(true),
+                TraceStyle::Rx,
                 "Received data (canonical format):\n {:?}",
                 data[..sz].hex_dump()
             );
         }
         res
     }
-    fn write(&self, data: &[u8], sz: usize) -> Result<()> {
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
         let sock = self.sock.as_ref();
         let res = sock.write(data, sz);
-        if sz > 0 {
-            println!(
+        if let Ok(sz) = res
+            && sz > 0
+        {
+            trace_event!(
+                sock,
+                self.side.direction(false),
+                TraceStyle::Tx,
                 "Written data (canonical format):\n{:?}",
                 data[..sz].hex_dump()
             );
@@ -147,3 +448,289 @@ impl SimpleSock for TraceCanonicalDecorator {
     }
     decorator_openclose_default!();
 }
+
+/// Decorator of the socket with its parameters. Decorators without
+/// parameters are strings, the other ones are objects with a single key,
+/// e.g. `"trace_info"`, `{ "stats": 5 }` or `{ "rate_limit": { "rate": 960 } }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DecoratorSpec {
+    TraceInfo,
+    TraceRaw,
+    TraceCanon,
+    Hash(HashAlgo),
+    /// Aggregate traffic line every N seconds
+    Summary(u64),
+    /// Throughput every N seconds
+    Stats(u64),
+    Sequence(SequenceConfig),
+    Monitor(MonitorConfig),
+    Tee(TeeConfig),
+    Chunk(ChunkConfig),
+    Checksum(ChecksumConfig),
+    RateLimit(RateLimitConfig),
+    Delay(DelayConfig),
+    Fault(FaultConfig),
+    Latency(LatencyConfig),
+    Obfuscate(ObfuscateConfig),
+    Encrypt(EncryptConfig),
+    Compress(CompressConfig),
+    Filter(FilterConfig),
+    ByteSwap(ByteSwapConfig),
+    /// PCAPNG capture file, shared by the sockets with the same path
+    Capture(PathBuf),
+}
+
+impl DecoratorSpec {
+    /// Parses the short form `name[:arg]` of the decorator, the argument is
+    /// the only (or the main) parameter, e.g. `stats:5`, `rate:960` or
+    /// `byte-swap:4`.
+    fn parse_short(s: &str) -> Result<Self> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let name = match name.replace('-', "_").as_str() {
+            "rate" => "rate_limit".to_string(),
+            name => name.to_string(),
+        };
+        let invalid =
+            |e: String| Error::new(ErrorKind::InvalidInput, format!("Decorator {s}: {e}"));
+        let value = match (name.as_str(), arg) {
+            ("hash", None) => serde_json::json!({ "hash": "sha256" }),
+            (_, None) => serde_json::Value::String(name.clone()),
+            ("hash", Some(algo)) => serde_json::json!({ "hash": algo }),
+            ("capture", Some(path)) => serde_json::json!({ "capture": path }),
+            (key, Some(arg)) => {
+                let number: u64 = arg.parse().map_err(|e| invalid(format!("{e}")))?;
+                let param = match key {
+                    "rate_limit" => serde_json::json!({ "rate": number }),
+                    "delay" => serde_json::json!({ "latency_ms": number }),
+                    "chunk" => serde_json::json!({ "max": number }),
+                    "byte_swap" => serde_json::json!({ "word": number }),
+                    _ => serde_json::json!(number),
+                };
+                serde_json::json!({ key: param })
+            }
+        };
+        serde_json::from_value(value).map_err(|e| match arg {
+            Some(_) => invalid(e.to_string()),
+            None => invalid(format!("{e} (parameter is set as {name}:ARG)")),
+        })
+    }
+    /// Name of the decorator in the configuration.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TraceInfo => "trace_info",
+            Self::TraceRaw => "trace_raw",
+            Self::TraceCanon => "trace_canon",
+            Self::Hash(_) => "hash",
+            Self::Summary(_) => "summary",
+            Self::Stats(_) => "stats",
+            Self::Sequence(_) => "sequence",
+            Self::Monitor(_) => "monitor",
+            Self::Tee(_) => "tee",
+            Self::Chunk(_) => "chunk",
+            Self::Checksum(_) => "checksum",
+            Self::RateLimit(_) => "rate_limit",
+            Self::Delay(_) => "delay",
+            Self::Fault(_) => "fault",
+            Self::Latency(_) => "latency",
+            Self::Obfuscate(_) => "obfuscate",
+            Self::Encrypt(_) => "encrypt",
+            Self::Compress(_) => "compress",
+            Self::Filter(_) => "filter",
+            Self::ByteSwap(_) => "byte_swap",
+            Self::Capture(_) => "capture",
+        }
+    }
+    /// Wraps the factory with the decorator. Configuration is checked here,
+    /// so a wrong one is reported before sockets are opened.
+    fn build(&self, f: Box<dyn SocketFactory>, side: TraceSide) -> Result<Box<dyn SocketFactory>> {
+        Ok(match self {
+            Self::TraceInfo => TraceInfoDecoratorFactory::new(f, side),
+            Self::TraceRaw => TraceRawDecoratorFactory::new(f, side),
+            Self::TraceCanon => TraceCanonicalDecoratorFactory::new(f, side),
+            Self::Hash(algo) => HashDecoratorFactory::new(f, *algo),
+            Self::Summary(secs) => SummaryDecoratorFactory::new(f, Duration::from_secs(*secs)),
+            Self::Stats(secs) => StatsDecoratorFactory::new(f, Duration::from_secs(*secs)),
+            Self::Sequence(cfg) => SequenceDecoratorFactory::new(f, cfg.clone()),
+            Self::Monitor(cfg) => MonitorDecoratorFactory::new(f, cfg.clone()),
+            Self::Tee(cfg) => TeeDecoratorFactory::new(f, cfg.clone()),
+            Self::Chunk(cfg) => ChunkDecoratorFactory::new(f, cfg.clone()),
+            Self::Checksum(cfg) => ChecksumDecoratorFactory::new(f, cfg.clone()),
+            Self::RateLimit(cfg) => RateLimitDecoratorFactory::new(f, cfg.clone()),
+            Self::Delay(cfg) => DelayDecoratorFactory::new(f, cfg.clone()),
+            Self::Fault(cfg) => FaultDecoratorFactory::new(f, cfg.clone()),
+            Self::Latency(cfg) => LatencyDecoratorFactory::new(f, cfg.clone()),
+            Self::Obfuscate(cfg) => TransformDecoratorFactory::new(f, cfg.builder()?),
+            Self::Encrypt(cfg) => TransformDecoratorFactory::new(f, cfg.builder()?),
+            Self::Compress(cfg) => CompressDecoratorFactory::new(f, cfg.clone()),
+            Self::Filter(cfg) => FilterDecoratorFactory::new(f, cfg.clone()),
+            Self::ByteSwap(cfg) => TransformDecoratorFactory::new(f, cfg.builder()?),
+            Self::Capture(path) => CaptureDecoratorFactory::new(f, CaptureFile::shared(path)?),
+        })
+    }
+}
+
+/// Ordered decorators of the socket, the first one is the closest to the
+/// socket. It's a JSON array of `DecoratorSpec` in the configuration or
+/// comma-separated short forms on the command line.
+#[derive(Deserialize, Debug, Clone, Default, schemars::JsonSchema)]
+#[serde(transparent)]
+pub struct DecoratorStack(Vec<DecoratorSpec>);
+
+impl DecoratorStack {
+    /// Parses JSON array of the specs (e.g. `[ "trace_raw", { "stats": 5 } ]`)
+    /// or short forms (e.g. `stats:5,rate:960,trace-canon`).
+    pub fn parse(s: &str) -> Result<Self> {
+        if s.trim_start().starts_with('[') {
+            return serde_json::from_str(s)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()));
+        }
+        s.split(',')
+            .map(|item| DecoratorSpec::parse_short(item.trim()))
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+    pub fn specs(&self) -> &[DecoratorSpec] {
+        &self.0
+    }
+    /// Builds the decorated factory.
+    pub fn build(
+        &self,
+        factory: Box<dyn SocketFactory>,
+        side: TraceSide,
+    ) -> Result<Box<dyn SocketFactory>> {
+        self.0
+            .iter()
+            .try_fold(factory, |f, spec| spec.build(f, side))
+    }
+    /// Builds the decorated factory with the stack as the pipeline stages:
+    /// written data passes them in their order, so the last one is the
+    /// closest to the socket.
+    pub fn build_pipeline(
+        &self,
+        factory: Box<dyn SocketFactory>,
+        side: TraceSide,
+    ) -> Result<Box<dyn SocketFactory>> {
+        self.0
+            .iter()
+            .rev()
+            .try_fold(factory, |f, spec| spec.build(f, side))
+    }
+    /// Builds the decorated factory, every decorator of which can be
+    /// bypassed at runtime with its switch (e.g. from the monitor UI).
+    pub fn build_switchable(
+        &self,
+        factory: Box<dyn SocketFactory>,
+        side: TraceSide,
+    ) -> Result<(Box<dyn SocketFactory>, Vec<DecoratorSwitch>)> {
+        let mut switches = Vec::new();
+        let factory = self.0.iter().try_fold(factory, |f, spec| {
+            let (f, switch) = SwitchDecoratorFactory::new(f, spec, side)?;
+            switches.push(switch);
+            Ok::<_, Error>(f)
+        })?;
+        Ok((factory, switches))
+    }
+    /// Parameters of the sink sockets created by the decorators.
+    pub fn sink_params(&self) -> impl Iterator<Item = SocketParams> + '_ {
+        self.0.iter().filter_map(|spec| match spec {
+            DecoratorSpec::Tee(cfg) => Some(cfg.sink_params()),
+            _ => None,
+        })
+    }
+    /// Files read or written by the decorators.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.0.iter().filter_map(|spec| match spec {
+            DecoratorSpec::Encrypt(cfg) => cfg.key_path(),
+            DecoratorSpec::Capture(path) => Some(path.as_path()),
+            _ => None,
+        })
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+    use crate::sockets::registry::SocketRegistry;
+    use std::sync::{Arc, Mutex};
+
+    make_simple_sock!(Sink { tx: Arc<Mutex<Vec<u8>>> }, "sink");
+
+    impl SimpleSock for Sink {
+        fn read(&self, _: &mut [u8], _: usize) -> Result<usize> {
+            Ok(0)
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            self.tx.lock().unwrap().extend(&data[..sz]);
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Sink {}
+    impl SockPoll for Sink {}
+
+    struct SinkFactory(Arc<Mutex<Vec<u8>>>);
+
+    impl SocketFactory for SinkFactory {
+        fn create_sock(&self, _: SocketParams) -> Result<Box<dyn ComplexSock>> {
+            Ok(Box::new(Sink::new(self.0.clone())))
+        }
+    }
+
+    #[test]
+    fn parse_decorator_stack() {
+        let stack = DecoratorStack::parse(
+            "trace-raw, stats:5,rate:960,hash:crc32,byte-swap:4,capture:dump.pcapng",
+        )
+        .unwrap();
+        assert!(matches!(
+            stack.specs(),
+            [
+                DecoratorSpec::TraceRaw,
+                DecoratorSpec::Stats(5),
+                DecoratorSpec::RateLimit(_),
+                DecoratorSpec::Hash(HashAlgo::Crc32),
+                DecoratorSpec::ByteSwap(_),
+                DecoratorSpec::Capture(_),
+            ]
+        ));
+        assert_eq!(stack.paths().collect::<Vec<_>>(), [Path::new("dump.pcapng")]);
+        let stack = DecoratorStack::parse(
+            r#"[ "trace_canon", { "tee": { "type": "udp", "params": { "port_dst": 9000 } } },
+                 { "encrypt": { "key_path": "psk" } } ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            stack.sink_params().map(|p| p.to_string()).collect::<Vec<_>>(),
+            [r#"{"port_dst":9000}"#]
+        );
+        assert_eq!(stack.paths().collect::<Vec<_>>(), [Path::new("psk")]);
+
+        for wrong in ["foo", "trace-raw:1", "stats:x", "hash:md5", r#"[ "foo" ]"#] {
+            assert!(DecoratorStack::parse(wrong).is_err(), "{wrong}");
+        }
+        // Configuration is checked, when the stack is built
+        let stack = DecoratorStack::parse("byte-swap:3").unwrap();
+        let factory = SocketRegistry::global().create("udp").unwrap();
+        assert!(stack.build(factory, TraceSide::From).is_err());
+    }
+    #[test]
+    fn pipeline_stage_order() {
+        let stages = DecoratorStack::parse(
+            r#"[ { "obfuscate": { "xor": "ff00" } }, { "byte_swap": { "word": 2 } } ]"#,
+        )
+        .unwrap();
+        let tx = Arc::new(Mutex::new(Vec::new()));
+        let factory = stages
+            .build_pipeline(Box::new(SinkFactory(tx.clone())), TraceSide::To)
+            .unwrap();
+        let sock = factory.create_sock(SocketParams::default()).unwrap();
+        sock.write(&[0x01, 0x02], 2).unwrap();
+        // XORed by the first stage, then swapped by the second one
+        assert_eq!(*tx.lock().unwrap(), [0x02, 0xfe]);
+    }
+}
@@ -0,0 +1,87 @@
+use super::transform::{Transform, TransformBuilder};
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
+
+/// Byte order swap of the fixed-width words, e.g. `{ "word": 4 }` converts
+/// big-endian 32-bit samples to little-endian ones (and back).
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct ByteSwapConfig {
+    /// Word size in bytes: 2, 4 or 8
+    word: usize,
+}
+
+impl ByteSwapConfig {
+    /// Creates transform builder. The word size is checked once here, so a
+    /// wrong size is reported before sockets are opened.
+    pub fn builder(&self) -> Result<TransformBuilder> {
+        if ![2, 4, 8].contains(&self.word) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Word size must be 2, 4 or 8 bytes, but it's {}", self.word),
+            ));
+        }
+        let word = self.word;
+        Ok(Arc::new(move || {
+            Ok(Box::new(ByteSwap::new(word)) as Box<dyn Transform>)
+        }))
+    }
+}
+
+/// Reverses bytes of every word in both directions. Words split between
+/// the chunks are reassembled, incomplete word is held until the rest
+/// comes (and dropped when the socket is closed).
+struct ByteSwap {
+    word: usize,
+    /// Incomplete words of the read and written streams
+    rx_partial: Vec<u8>,
+    tx_partial: Vec<u8>,
+}
+
+impl ByteSwap {
+    fn new(word: usize) -> Self {
+        Self {
+            word,
+            rx_partial: Vec::new(),
+            tx_partial: Vec::new(),
+        }
+    }
+    fn swap(word: usize, partial: &mut Vec<u8>, data: &[u8]) -> Vec<u8> {
+        partial.extend(data);
+        let whole = partial.len() - partial.len() % word;
+        let mut out: Vec<u8> = partial.drain(..whole).collect();
+        out.chunks_exact_mut(word).for_each(|w| w.reverse());
+        out
+    }
+}
+
+impl Transform for ByteSwap {
+    fn on_read(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(Self::swap(self.word, &mut self.rx_partial, data))
+    }
+    fn on_write(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(Self::swap(self.word, &mut self.tx_partial, data))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn swap_words() {
+        let mut swap = ByteSwap::new(2);
+        assert_eq!(swap.on_read(b"\x12\x34\x56").unwrap(), b"\x34\x12");
+        assert_eq!(swap.on_read(b"\x78").unwrap(), b"\x78\x56");
+        // Directions are independent
+        assert_eq!(swap.on_write(b"\x01").unwrap(), b"");
+        assert_eq!(swap.rx_partial, b"");
+
+        let mut swap = ByteSwap::new(4);
+        let sample = 0x11223344u32.to_be_bytes();
+        assert_eq!(swap.on_write(&sample).unwrap(), 0x11223344u32.to_le_bytes());
+        assert!(ByteSwapConfig { word: 3 }.builder().is_err());
+        assert!(ByteSwapConfig { word: 8 }.builder().is_ok());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SHB_TYPE: u32 = 0x0A0D_0D0A;
+const IDB_TYPE: u32 = 0x0000_0001;
+const EPB_TYPE: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+/// Link type for private use, the payload is shown as raw data
+const LINKTYPE_USER0: u16 = 147;
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_EPB_FLAGS: u16 = 2;
+const EPB_FLAGS_INBOUND: u32 = 0b01;
+const EPB_FLAGS_OUTBOUND: u32 = 0b10;
+
+/// Direction of the captured chunk relative to the socket.
+#[derive(Clone, Copy)]
+enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// Appends option with the value padded to 32 bits.
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+/// Frames block body with its type and total length.
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let len = (body.len() + 12) as u32;
+    let mut block = Vec::with_capacity(len as usize);
+    block.extend(block_type.to_le_bytes());
+    block.extend(len.to_le_bytes());
+    block.extend(body);
+    block.extend(len.to_le_bytes());
+    block
+}
+
+/// Body of the enhanced packet block without options.
+fn packet_body(interface: u32, time: SystemTime, data: &[u8]) -> Vec<u8> {
+    // Default timestamp resolution is microseconds
+    let ts = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    let mut body = Vec::with_capacity(data.len() + 40);
+    body.extend(interface.to_le_bytes());
+    body.extend(((ts >> 32) as u32).to_le_bytes());
+    body.extend((ts as u32).to_le_bytes());
+    body.extend((data.len() as u32).to_le_bytes());
+    body.extend((data.len() as u32).to_le_bytes());
+    body.extend(data);
+    body.resize(body.len().next_multiple_of(4), 0);
+    body
+}
+
+/// Capture files of the process, which get the markers.
+static OPEN_CAPTURES: Mutex<Vec<Weak<Mutex<CaptureWriter>>>> = Mutex::new(Vec::new());
+
+/// PCAPNG file shared by all captured sockets, every socket is the
+/// separate interface.
+#[derive(Clone)]
+pub struct CaptureFile {
+    inner: Arc<Mutex<CaptureWriter>>,
+}
+
+struct CaptureWriter {
+    out: Box<dyn Write + Send>,
+    interfaces: u32,
+    /// Path of the file, the capture is shared by the sockets with the
+    /// same path
+    path: Option<PathBuf>,
+    /// Interface of the markers, it's added with the first marker
+    markers: Option<u32>,
+}
+
+impl CaptureWriter {
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<()> {
+        self.out.write_all(&block(block_type, body))?;
+        // Capture is readable while the binding is running
+        self.out.flush()
+    }
+    /// Writes interface description and returns its id.
+    fn add_interface(&mut self, name: &str, description: &str) -> Result<u32> {
+        let mut body = Vec::new();
+        body.extend(LINKTYPE_USER0.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        // No snapshot length limit
+        body.extend(0u32.to_le_bytes());
+        push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+        push_option(&mut body, OPT_IF_DESCRIPTION, description.as_bytes());
+        push_option(&mut body, OPT_END, &[]);
+        self.write_block(IDB_TYPE, &body)?;
+        self.interfaces += 1;
+        Ok(self.interfaces - 1)
+    }
+}
+
+impl CaptureFile {
+    /// Creates (truncates) the file and writes the section header.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = Self::from_writer(Box::new(BufWriter::new(File::create(path)?)))?;
+        file.inner.lock().unwrap().path = Some(path.to_path_buf());
+        let mut open = OPEN_CAPTURES.lock().unwrap();
+        open.retain(|writer| writer.strong_count() > 0);
+        open.push(Arc::downgrade(&file.inner));
+        Ok(file)
+    }
+    /// Returns the capture of the path, which is open in the process, or
+    /// creates it, so the sockets of several links share one file.
+    pub fn shared(path: &Path) -> Result<Self> {
+        let open = OPEN_CAPTURES.lock().unwrap().iter().filter_map(Weak::upgrade).find(
+            |writer| writer.lock().unwrap().path.as_deref() == Some(path),
+        );
+        match open {
+            Some(inner) => Ok(Self { inner }),
+            None => Self::create(path),
+        }
+    }
+    fn from_writer(out: Box<dyn Write + Send>) -> Result<Self> {
+        let mut writer = CaptureWriter {
+            out,
+            interfaces: 0,
+            path: None,
+            markers: None,
+        };
+        let mut body = Vec::new();
+        body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend(1u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        // Section length is unknown
+        body.extend((-1i64).to_le_bytes());
+        writer.write_block(SHB_TYPE, &body)?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(writer)),
+        })
+    }
+    /// Writes interface description and returns its id.
+    fn add_interface(&self, name: &str, description: &str) -> Result<u32> {
+        self.inner.lock().unwrap().add_interface(name, description)
+    }
+    fn write_packet(
+        &self,
+        interface: u32,
+        dir: Direction,
+        time: SystemTime,
+        data: &[u8],
+    ) -> Result<()> {
+        let mut body = packet_body(interface, time, data);
+        let flags = match dir {
+            Direction::Inbound => EPB_FLAGS_INBOUND,
+            Direction::Outbound => EPB_FLAGS_OUTBOUND,
+        };
+        push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+        push_option(&mut body, OPT_END, &[]);
+        self.inner.lock().unwrap().write_block(EPB_TYPE, &body)
+    }
+    /// Writes the marker as the packet of the `markers` interface, the
+    /// label is its data and comment.
+    fn write_marker(&self, label: &str, time: SystemTime) -> Result<()> {
+        let mut writer = self.inner.lock().unwrap();
+        let interface = match writer.markers {
+            Some(interface) => interface,
+            None => {
+                let interface = writer.add_interface("markers", "polysock markers")?;
+                *writer.markers.insert(interface)
+            }
+        };
+        let mut body = packet_body(interface, time, label.as_bytes());
+        push_option(&mut body, OPT_COMMENT, label.as_bytes());
+        push_option(&mut body, OPT_END, &[]);
+        writer.write_block(EPB_TYPE, &body)
+    }
+}
+
+/// Injects the labeled marker (e.g. "start test 7") into the trace output
+/// and every open capture file, so they can be sliced by the test phases.
+/// Returns the number of the marked captures.
+pub fn inject_marker(label: &str) -> usize {
+    trace_println!("=== Marker: {label}");
+    let captures: Vec<_> = OPEN_CAPTURES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|inner| CaptureFile { inner })
+        .collect();
+    let time = SystemTime::now();
+    captures
+        .iter()
+        .filter(|file| match file.write_marker(label, time) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Marker {label} isn't written to the capture: {e}");
+                false
+            }
+        })
+        .count()
+}
+
+/// Writes every read and written chunk to the PCAPNG capture.
+pub struct CaptureDecorator {
+    sock: Box<dyn ComplexSock>,
+    file: CaptureFile,
+    interface: u32,
+}
+
+impl CaptureDecorator {
+    fn capture(&self, dir: Direction, time: SystemTime, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if let Err(e) = self.file.write_packet(self.interface, dir, time, data) {
+            tracing::warn!(
+                "Capture of {} data failed: {e}",
+                self.sock.get_description()
+            );
+        }
+    }
+}
+
+decorator_forward!(CaptureDecorator);
+
+impl SimpleSock for CaptureDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let res = self.sock.read(data, sz);
+        if let Ok(sz) = res {
+            // Kernel receive time is more precise, if the socket has it
+            let time = self.sock.rx_timestamp().unwrap_or_else(SystemTime::now);
+            self.capture(Direction::Inbound, time, &data[..sz]);
+        }
+        res
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let res = self.sock.write(data, sz);
+        if let Ok(sz) = res {
+            self.capture(Direction::Outbound, SystemTime::now(), &data[..sz]);
+        }
+        res
+    }
+    decorator_openclose_default!();
+}
+
+pub struct CaptureDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    file: CaptureFile,
+}
+
+impl CaptureDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, file: CaptureFile) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, file })
+    }
+}
+
+impl SocketFactory for CaptureDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.factory.create_sock(params)?;
+        let interface = self
+            .file
+            .add_interface(&sock.get_description(), sock.get_type_name())?;
+        Ok(Box::new(CaptureDecorator {
+            sock,
+            file: self.file.clone(),
+            interface,
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+
+    /// Writer keeping data in the shared buffer.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pcapng_blocks() {
+        let buf = SharedBuf::default();
+        let file = CaptureFile::from_writer(Box::new(buf.clone())).unwrap();
+        assert_eq!(file.add_interface("udp0", "udp").unwrap(), 0);
+        assert_eq!(file.add_interface("stdio0", "stdio").unwrap(), 1);
+        file.write_packet(1, Direction::Outbound, UNIX_EPOCH, b"hello")
+            .unwrap();
+        let data = buf.0.lock().unwrap();
+        // Every block starts with type and length, which is repeated at the end
+        let mut offset = 0;
+        let mut blocks = Vec::new();
+        while offset < data.len() {
+            let word = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+            let len = word(offset + 4) as usize;
+            assert_eq!(len % 4, 0);
+            assert_eq!(word(offset + len - 4) as usize, len);
+            blocks.push((word(offset), offset));
+            offset += len;
+        }
+        let types: Vec<u32> = blocks.iter().map(|(t, _)| *t).collect();
+        assert_eq!(types, [SHB_TYPE, IDB_TYPE, IDB_TYPE, EPB_TYPE]);
+        // Packet data follows 28 bytes of the packet block header
+        let epb = blocks[3].1;
+        assert_eq!(&data[epb + 8..epb + 12], &1u32.to_le_bytes());
+        assert_eq!(&data[epb + 28..epb + 33], b"hello");
+    }
+    #[test]
+    fn shared_capture_markers() {
+        let path = std::env::temp_dir().join(format!("markers-{}.pcapng", std::process::id()));
+        let file = CaptureFile::shared(&path).unwrap();
+        file.add_interface("udp0", "udp").unwrap();
+        // Sockets of the same path share the file
+        let other = CaptureFile::shared(&path).unwrap();
+        assert!(Arc::ptr_eq(&file.inner, &other.inner));
+        assert!(inject_marker("start test 7") >= 1);
+        inject_marker("stop test 7");
+        let interface = file.inner.lock().unwrap().markers.unwrap();
+        drop((file, other));
+        assert!(OPEN_CAPTURES.lock().unwrap().iter().all(|w| {
+            w.upgrade().is_none_or(|writer| writer.lock().unwrap().path.as_ref() != Some(&path))
+        }));
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let word = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let mut offset = 0;
+        let mut markers = Vec::new();
+        while offset < data.len() {
+            let len = word(offset + 4) as usize;
+            // Markers are the only packets, they have their own interface
+            if word(offset) == EPB_TYPE {
+                assert_eq!(word(offset + 8), interface);
+                let captured = word(offset + 20) as usize;
+                let label = &data[offset + 28..offset + 28 + captured];
+                // Comment is the first option after the padded data
+                let opt = offset + 28 + captured.next_multiple_of(4);
+                assert_eq!(&data[opt..opt + 2], &OPT_COMMENT.to_le_bytes());
+                assert_eq!(&data[opt + 4..opt + 4 + captured], label);
+                markers.push(String::from_utf8(label.to_vec()).unwrap());
+            }
+            offset += len;
+        }
+        // Markers of the other tests may be injected too
+        markers.retain(|label| label.ends_with("test 7"));
+        assert_eq!(markers, ["start test 7", "stop test 7"]);
+    }
+}
@@ -0,0 +1,184 @@
+use super::framing::write_frame;
+use super::trace_file::TraceStyle;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use pretty_hex::PrettyHex;
+use serde::Deserialize;
+use std::cell::Cell;
+use std::io::Result;
+
+static CRC16_CCITT: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_IBM_3740);
+static CRC16_MODBUS: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_MODBUS);
+static CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumAlgo {
+    /// CRC-16/CCITT-FALSE (0x1021, init 0xffff)
+    Crc16Ccitt,
+    /// CRC-16/MODBUS (0x8005 reflected, init 0xffff)
+    Crc16Modbus,
+    /// CRC-32 (ISO-HDLC, same as zlib/gzip)
+    Crc32,
+}
+
+impl ChecksumAlgo {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Crc16Ccitt => "CRC-16/CCITT",
+            Self::Crc16Modbus => "CRC-16/MODBUS",
+            Self::Crc32 => "CRC-32",
+        }
+    }
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Crc16Ccitt | Self::Crc16Modbus => 2,
+            Self::Crc32 => 4,
+        }
+    }
+    /// Returns the checksum bytes in the byte order.
+    pub(crate) fn checksum(&self, data: &[u8], little_endian: bool) -> Vec<u8> {
+        let value = match self {
+            Self::Crc16Ccitt => CRC16_CCITT.checksum(data) as u32,
+            Self::Crc16Modbus => CRC16_MODBUS.checksum(data) as u32,
+            Self::Crc32 => CRC32.checksum(data),
+        };
+        if little_endian {
+            value.to_le_bytes()[..self.len()].to_vec()
+        } else {
+            value.to_be_bytes()[4 - self.len()..].to_vec()
+        }
+    }
+}
+
+/// Checksum of every frame (chunk), e.g. `{ "algo": "crc16_modbus",
+/// "little_endian": true, "drop": true }` for Modbus RTU. Frame is a
+/// datagram of a message socket, stream sockets don't keep the frames.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct ChecksumConfig {
+    algo: ChecksumAlgo,
+    /// Checksum byte order (big-endian by default)
+    #[serde(default)]
+    little_endian: bool,
+    /// Corrupt frames are dropped instead of being passed without checksum
+    #[serde(default)]
+    drop: bool,
+}
+
+impl ChecksumConfig {
+    fn append(&self, data: &[u8]) -> Vec<u8> {
+        [data, &self.algo.checksum(data, self.little_endian)].concat()
+    }
+    /// Returns length of the frame without checksum, or `None` if the
+    /// frame is corrupt.
+    fn verify(&self, frame: &[u8]) -> Option<usize> {
+        let len = frame.len().checked_sub(self.algo.len())?;
+        (frame[len..] == self.algo.checksum(&frame[..len], self.little_endian)).then_some(len)
+    }
+}
+
+/// Appends checksum to the written frames, verifies and strips it from
+/// the read ones. Corrupt frames are reported to the trace output.
+pub struct ChecksumDecorator {
+    sock: Box<dyn ComplexSock>,
+    config: ChecksumConfig,
+    frames: Cell<u64>,
+    corrupt: Cell<u64>,
+}
+
+impl ChecksumDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, config: &ChecksumConfig) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            config: config.clone(),
+            frames: Cell::new(0),
+            corrupt: Cell::new(0),
+        })
+    }
+}
+
+socket_decorator!(ChecksumDecorator, ChecksumConfig);
+
+impl SimpleSock for ChecksumDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let read = self.sock.read(data, sz)?;
+        if read == 0 {
+            return Ok(0);
+        }
+        self.frames.set(self.frames.get() + 1);
+        if let Some(len) = self.config.verify(&data[..read]) {
+            return Ok(len);
+        }
+        self.corrupt.set(self.corrupt.get() + 1);
+        trace_println!(
+            style = TraceStyle::Error;
+            "{} mismatch of frame from {}{}:\n{:?}",
+            self.config.algo.name(),
+            self.sock.get_description(),
+            if self.config.drop { ", dropped" } else { "" },
+            data[..read].hex_dump()
+        );
+        if self.config.drop {
+            return Ok(0);
+        }
+        Ok(read.saturating_sub(self.config.algo.len()))
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let frame = self.config.append(&data[..sz]);
+        let written = write_frame(self.sock.as_ref(), &frame)?;
+        Ok(if written { sz } else { 0 })
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        trace_println!(
+            "{} of {}: {} frames read, {} corrupt",
+            self.config.algo.name(),
+            self.sock.get_description(),
+            self.frames.get(),
+            self.corrupt.get()
+        );
+        self.sock.close();
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn checksum_append_verify() {
+        // Check values of the algorithms for "123456789"
+        let data = b"123456789";
+        assert_eq!(ChecksumAlgo::Crc16Ccitt.checksum(data, false), [0x29, 0xb1]);
+        assert_eq!(ChecksumAlgo::Crc16Modbus.checksum(data, true), [0x37, 0x4b]);
+        assert_eq!(
+            ChecksumAlgo::Crc32.checksum(data, false),
+            [0xcb, 0xf4, 0x39, 0x26]
+        );
+        assert_eq!(
+            ChecksumAlgo::Crc32.checksum(data, true),
+            [0x26, 0x39, 0xf4, 0xcb]
+        );
+
+        let config = ChecksumConfig {
+            algo: ChecksumAlgo::Crc16Modbus,
+            little_endian: true,
+            drop: false,
+        };
+        // Modbus RTU request: read holding register 0 of slave 1
+        let frame = config.append(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(frame[6..], [0x84, 0x0a]);
+        assert_eq!(config.verify(&frame), Some(6));
+        let mut corrupt = frame.clone();
+        corrupt[2] ^= 0x10;
+        assert_eq!(config.verify(&corrupt), None);
+        assert_eq!(config.verify(&frame[..1]), None);
+        assert_eq!(config.verify(&frame[6..]), None);
+    }
+}
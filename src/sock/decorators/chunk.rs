@@ -0,0 +1,274 @@
+use super::framing::MESSAGE_READ_SIZE;
+use crate::sock::poll;
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Chunk size of the socket data, e.g. `{ "max": 240, "coalesce_ms": 50 }`
+/// for radio modem with 240 bytes frames.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct ChunkConfig {
+    /// Max size of the chunk written to the socket (larger writes are split)
+    max: usize,
+    /// Small chunks read from the socket are coalesced up to the max size,
+    /// incomplete chunk is passed after the timeout in milliseconds
+    coalesce_ms: Option<u64>,
+}
+
+impl ChunkConfig {
+    fn validate(&self) -> Result<()> {
+        if self.max == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Max chunk size must be positive",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Read data coalesced into the chunks of max size.
+struct Coalescer {
+    max: usize,
+    timeout: Duration,
+    data: Vec<u8>,
+    /// Time of the oldest coalesced data
+    since: Option<Instant>,
+    /// Not yet read length of the completed chunk
+    flush: usize,
+}
+
+impl Coalescer {
+    fn new(max: usize, timeout: Duration) -> Self {
+        Self {
+            max,
+            timeout,
+            data: Vec::new(),
+            since: None,
+            flush: 0,
+        }
+    }
+    fn push(&mut self, chunk: &[u8], now: Instant) {
+        self.data.extend(chunk);
+        self.since.get_or_insert(now);
+    }
+    /// Time left until the incomplete chunk is passed (`None`, if nothing
+    /// is coalesced).
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.since
+            .map(|since| (since + self.timeout).saturating_duration_since(now))
+    }
+    /// Copies up to `sz` bytes of the completed chunk to `data`.
+    fn pop(&mut self, now: Instant, data: &mut [u8], sz: usize) -> Option<usize> {
+        if self.flush == 0 {
+            if self.data.len() < self.max && self.remaining(now) != Some(Duration::ZERO) {
+                return None;
+            }
+            self.flush = self.data.len().min(self.max);
+        }
+        let len = self.flush.min(sz);
+        data[..len].copy_from_slice(&self.data[..len]);
+        self.data.drain(..len);
+        self.flush -= len;
+        if self.data.is_empty() {
+            self.since = None;
+        } else if self.flush == 0 {
+            // The rest is coalesced into the next chunk
+            self.since = Some(now);
+        }
+        Some(len)
+    }
+}
+
+/// Splits chunks written to the socket into the chunks of max size (every
+/// one is written separately) and optionally coalesces the read ones.
+/// Coalesced data is dropped when the socket is closed.
+pub struct ChunkDecorator {
+    sock: Box<dyn ComplexSock>,
+    max: usize,
+    rx: Option<Mutex<Coalescer>>,
+    blocking: bool,
+}
+
+impl ChunkDecorator {
+    /// Reads the socket once and coalesces the read chunk.
+    fn receive(&self, rx: &mut Coalescer) -> Result<usize> {
+        let mut chunk = vec![0u8; MESSAGE_READ_SIZE];
+        let len = self.sock.read(&mut chunk, MESSAGE_READ_SIZE)?;
+        if len > 0 {
+            rx.push(&chunk[..len], Instant::now());
+        }
+        Ok(len)
+    }
+}
+
+impl SimpleSock for ChunkDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let Some(rx) = &self.rx else {
+            return self.sock.read(data, sz);
+        };
+        let mut rx = rx.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            if let Some(len) = rx.pop(now, data, sz) {
+                return Ok(len);
+            }
+            match rx.remaining(now) {
+                // Nothing is coalesced, so the read may block in the socket
+                None => {
+                    if self.receive(&mut rx)? == 0 {
+                        return Ok(0);
+                    }
+                }
+                Some(_) if !self.blocking => {
+                    self.receive(&mut rx)?;
+                    return Ok(rx.pop(Instant::now(), data, sz).unwrap_or(0));
+                }
+                Some(left) => match self.sock.poll_handles() {
+                    Some(handles) => {
+                        if poll::wait_readable(&handles, left)? {
+                            self.receive(&mut rx)?;
+                        }
+                    }
+                    None => thread::sleep(left),
+                },
+            }
+        }
+    }
+    /// Returns the written length of the split chunks, so the caller
+    /// retries the rest of the partially written data.
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let mut written = 0;
+        for chunk in data[..sz].chunks(self.max) {
+            let len = self.sock.write(chunk, chunk.len())?;
+            written += len;
+            if len < chunk.len() {
+                break;
+            }
+        }
+        Ok(written)
+    }
+    decorator_openclose_default!();
+}
+
+impl SockBlockCtl for ChunkDecorator {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.blocking = is_blocking;
+        self.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        self.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.sock.flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.sock.shutdown_write()
+    }
+}
+
+impl SockInfo for ChunkDecorator {
+    fn get_type_name(&self) -> &str {
+        self.sock.get_type_name()
+    }
+    fn get_id(&self) -> u32 {
+        self.sock.get_id()
+    }
+    fn get_description(&self) -> String {
+        self.sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.sock.source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.sock.sink_rate()
+    }
+    fn semantics(&self) -> Semantics {
+        self.sock.semantics()
+    }
+}
+
+impl SockPoll for ChunkDecorator {
+    /// Coalesced data isn't signaled by the socket handles, so the caller
+    /// has to retry reading after a short sleep.
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        match &self.rx {
+            Some(rx) if !rx.lock().unwrap().data.is_empty() => None,
+            _ => self.sock.poll_handles(),
+        }
+    }
+}
+
+pub struct ChunkDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: ChunkConfig,
+}
+
+impl ChunkDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: ChunkConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for ChunkDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        self.config.validate()?;
+        let sock = self.factory.create_sock(params)?;
+        let rx = self
+            .config
+            .coalesce_ms
+            .map(|ms| Mutex::new(Coalescer::new(self.config.max, Duration::from_millis(ms))));
+        Ok(Box::new(ChunkDecorator {
+            sock,
+            max: self.config.max,
+            rx,
+            blocking: false,
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn coalesce_chunks() {
+        let timeout = Duration::from_millis(50);
+        let mut rx = Coalescer::new(4, timeout);
+        let mut buf = [0u8; 8];
+        let start = Instant::now();
+        assert_eq!(rx.remaining(start), None);
+        rx.push(b"ab", start);
+        assert_eq!(rx.pop(start, &mut buf, 8), None);
+        rx.push(b"cdefghij", start + timeout / 2);
+        // Full chunks are passed at once, even if they are read partially
+        assert_eq!(rx.pop(start, &mut buf, 3), Some(3));
+        assert_eq!(rx.pop(start, &mut buf[3..], 8), Some(1));
+        assert_eq!(&buf[..4], b"abcd");
+        assert_eq!(rx.pop(start, &mut buf, 8), Some(4));
+        assert_eq!(&buf[..4], b"efgh");
+        // The rest waits for the timeout
+        assert_eq!(rx.remaining(start), Some(timeout));
+        assert_eq!(rx.pop(start + timeout / 2, &mut buf, 8), None);
+        assert_eq!(rx.pop(start + timeout, &mut buf, 8), Some(2));
+        assert_eq!(&buf[..2], b"ij");
+        assert_eq!(rx.remaining(start + timeout), None);
+    }
+}
@@ -0,0 +1,247 @@
+use super::framing::{
+    LENGTH_PREFIX_LEN, MAX_MESSAGE_LEN, MESSAGE_READ_SIZE, message_len, write_frame,
+};
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::sync::Mutex;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressAlgo {
+    Gzip,
+    Zstd,
+    /// Fast compression without levels
+    Lz4,
+}
+
+/// Compression of the data written to the socket and decompression of the
+/// read data, e.g. `{ "algo": "zstd", "level": 19 }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct CompressConfig {
+    algo: CompressAlgo,
+    /// Compression level (default of the algorithm, if it is not set)
+    level: Option<i32>,
+}
+
+impl CompressConfig {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self.algo {
+            CompressAlgo::Gzip => {
+                let level = self.level.map_or(flate2::Compression::default(), |level| {
+                    flate2::Compression::new(level.clamp(0, 9) as u32)
+                });
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            CompressAlgo::Zstd => {
+                zstd::bulk::compress(data, self.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL))
+            }
+            CompressAlgo::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        }
+    }
+    /// Decompressed data larger than the max message is an error, so a
+    /// corrupted (or malicious) frame can't exhaust the memory.
+    fn decompress(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        let too_large = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Decompressed chunk exceeds {MAX_MESSAGE_LEN} bytes"),
+            )
+        };
+        let mut data = Vec::new();
+        match self.algo {
+            CompressAlgo::Gzip => {
+                flate2::read::GzDecoder::new(frame)
+                    .take(MAX_MESSAGE_LEN as u64 + 1)
+                    .read_to_end(&mut data)?;
+            }
+            CompressAlgo::Zstd => {
+                zstd::stream::read::Decoder::new(frame)?
+                    .take(MAX_MESSAGE_LEN as u64 + 1)
+                    .read_to_end(&mut data)?;
+            }
+            CompressAlgo::Lz4 => {
+                let Some((size, block)) = frame.split_first_chunk::<4>() else {
+                    return Err(Error::new(ErrorKind::InvalidData, "Truncated lz4 chunk"));
+                };
+                let size = u32::from_le_bytes(*size) as usize;
+                if size > MAX_MESSAGE_LEN {
+                    return Err(too_large());
+                }
+                data = lz4_flex::decompress(block, size)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            }
+        }
+        if data.len() > MAX_MESSAGE_LEN {
+            return Err(too_large());
+        }
+        Ok(data)
+    }
+}
+
+/// Compresses every written chunk separately and decompresses the read
+/// ones. Chunk is a datagram of the message socket or a length-prefixed
+/// frame (4 bytes big-endian) in the stream, so the peer must use the
+/// same algorithm.
+pub struct CompressDecorator {
+    sock: Box<dyn ComplexSock>,
+    config: CompressConfig,
+    /// Received bytes of the incomplete frame (stream only)
+    rx_frame: Mutex<Vec<u8>>,
+    /// Decompressed data, which didn't fit the read buffer
+    rx_pending: Mutex<Vec<u8>>,
+}
+
+impl CompressDecorator {
+    /// Reads the socket once and decompresses the received frames.
+    fn receive(&self, pending: &mut Vec<u8>) -> Result<usize> {
+        let mut raw = vec![0u8; MESSAGE_READ_SIZE];
+        let len = self.sock.read(&mut raw, MESSAGE_READ_SIZE)?;
+        if len == 0 {
+            return Ok(0);
+        }
+        if self.sock.semantics() == Semantics::Message {
+            pending.extend(self.config.decompress(&raw[..len])?);
+            return Ok(len);
+        }
+        let mut frame = self.rx_frame.lock().unwrap();
+        frame.extend(&raw[..len]);
+        while let Some(frame_len) = message_len(&frame)? {
+            let end = LENGTH_PREFIX_LEN + frame_len;
+            pending.extend(self.config.decompress(&frame[LENGTH_PREFIX_LEN..end])?);
+            frame.drain(..end);
+        }
+        Ok(len)
+    }
+}
+
+impl SimpleSock for CompressDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let mut pending = self.rx_pending.lock().unwrap();
+        if pending.is_empty() && self.receive(&mut pending)? == 0 {
+            return Ok(0);
+        }
+        let len = pending.len().min(sz);
+        data[..len].copy_from_slice(&pending[..len]);
+        pending.drain(..len);
+        Ok(len)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let mut frame = self.config.compress(&data[..sz])?;
+        if self.sock.semantics() == Semantics::Message {
+            return match self.sock.write(&frame, frame.len())? {
+                0 => Ok(0),
+                len if len == frame.len() => Ok(sz),
+                _ => Err(Error::new(
+                    ErrorKind::WriteZero,
+                    format!(
+                        "Compressed chunk to {} is written partially",
+                        self.sock.get_description()
+                    ),
+                )),
+            };
+        }
+        frame.splice(0..0, (frame.len() as u32).to_be_bytes());
+        let written = write_frame(self.sock.as_ref(), &frame)?;
+        Ok(if written { sz } else { 0 })
+    }
+    decorator_openclose_default!();
+}
+
+decorator_forward_blockctl!(CompressDecorator);
+
+impl SockInfo for CompressDecorator {
+    fn get_type_name(&self) -> &str {
+        self.sock.get_type_name()
+    }
+    fn get_id(&self) -> u32 {
+        self.sock.get_id()
+    }
+    fn get_description(&self) -> String {
+        self.sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.sock.source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.sock.sink_rate()
+    }
+    fn semantics(&self) -> Semantics {
+        self.sock.semantics()
+    }
+}
+
+impl SockPoll for CompressDecorator {
+    /// Decompressed data, which didn't fit the read buffer, isn't signaled
+    /// by the socket handles, so the caller has to retry reading after a
+    /// short sleep.
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        if self.rx_pending.lock().unwrap().is_empty() {
+            self.sock.poll_handles()
+        } else {
+            None
+        }
+    }
+}
+
+pub struct CompressDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: CompressConfig,
+}
+
+impl CompressDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: CompressConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for CompressDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.factory.create_sock(params)?;
+        Ok(Box::new(CompressDecorator {
+            sock,
+            config: self.config.clone(),
+            rx_frame: Mutex::new(Vec::new()),
+            rx_pending: Mutex::new(Vec::new()),
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn compress_roundtrip() {
+        let data = b"polysock ".repeat(100);
+        for algo in [CompressAlgo::Gzip, CompressAlgo::Zstd, CompressAlgo::Lz4] {
+            for level in [None, Some(1), Some(9)] {
+                let config = CompressConfig { algo, level };
+                let frame = config.compress(&data).unwrap();
+                assert!(frame.len() < data.len() / 4, "{algo:?} {level:?}");
+                assert_eq!(config.decompress(&frame).unwrap(), data);
+                assert!(config.decompress(&frame[..frame.len() / 2]).is_err());
+            }
+            // Empty chunk is passed too
+            let config = CompressConfig { algo, level: None };
+            let frame = config.compress(&[]).unwrap();
+            assert_eq!(config.decompress(&frame).unwrap(), b"");
+        }
+        let bomb = vec![0u8; MAX_MESSAGE_LEN + 1];
+        for algo in [CompressAlgo::Gzip, CompressAlgo::Zstd, CompressAlgo::Lz4] {
+            let config = CompressConfig { algo, level: None };
+            let err = config.decompress(&config.compress(&bomb).unwrap());
+            assert_eq!(err.unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::sock::poll;
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::io::Result;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Distribution of the latency jitter.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JitterDistribution {
+    /// Uniform in the range of latency ± jitter
+    #[default]
+    Uniform,
+    /// Normal with the jitter as standard deviation
+    #[serde(alias = "gaussian")]
+    Normal,
+}
+
+impl JitterDistribution {
+    /// Random deviation of the distribution with the jitter.
+    pub(crate) fn sample(&self, rng: &mut StdRng, jitter: f64) -> f64 {
+        match self {
+            _ if jitter == 0.0 => 0.0,
+            Self::Uniform => rng.random_range(-jitter..=jitter),
+            Self::Normal => {
+                // Box-Muller transform, 1 - u excludes ln(0)
+                let u1: f64 = 1.0 - rng.random::<f64>();
+                let u2: f64 = rng.random();
+                jitter * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+            }
+        }
+    }
+}
+
+/// Latency of the chunks read from the socket, e.g.
+/// `{ "latency_ms": 80, "jitter_ms": 20 }` emulates WAN link.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct DelayConfig {
+    /// Base latency in milliseconds
+    latency_ms: u64,
+    /// Max (uniform) or standard (normal) deviation of the latency in milliseconds
+    #[serde(default)]
+    jitter_ms: u64,
+    #[serde(default)]
+    distribution: JitterDistribution,
+    /// Seed of the jitter generator, the same seed gives the same delays
+    /// (random, if it is not set)
+    seed: Option<u64>,
+}
+
+/// Chunks held until their due time. Chunks are released in the read
+/// order, so jitter doesn't reorder the stream.
+struct DelayQueue {
+    config: DelayConfig,
+    rng: StdRng,
+    /// Due time, data and already read length of the held chunks
+    chunks: VecDeque<(Instant, Vec<u8>, usize)>,
+}
+
+impl DelayQueue {
+    fn new(config: DelayConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => rand::make_rng(),
+        };
+        Self {
+            config,
+            rng,
+            chunks: VecDeque::new(),
+        }
+    }
+    /// Latency of the next chunk, negative jitter is cut at zero latency.
+    fn next_delay(&mut self) -> Duration {
+        let latency = self.config.latency_ms as f64;
+        let jitter = self.config.jitter_ms as f64;
+        let deviation = self.config.distribution.sample(&mut self.rng, jitter);
+        Duration::from_secs_f64((latency + deviation).max(0.0) / 1000.0)
+    }
+    fn push(&mut self, chunk: Vec<u8>, now: Instant) {
+        let mut due = now + self.next_delay();
+        if let Some((last, _, _)) = self.chunks.back() {
+            due = due.max(*last);
+        }
+        self.chunks.push_back((due, chunk, 0));
+    }
+    fn next_due(&self) -> Option<Instant> {
+        self.chunks.front().map(|(due, _, _)| *due)
+    }
+    /// Copies up to `sz` bytes of the due chunk to `data`.
+    fn pop_due(&mut self, now: Instant, data: &mut [u8], sz: usize) -> Option<usize> {
+        let (due, chunk, offset) = self.chunks.front_mut()?;
+        if *due > now {
+            return None;
+        }
+        let len = (chunk.len() - *offset).min(sz);
+        data[..len].copy_from_slice(&chunk[*offset..*offset + len]);
+        *offset += len;
+        if *offset == chunk.len() {
+            self.chunks.pop_front();
+        }
+        Some(len)
+    }
+}
+
+/// Holds every chunk read from the socket for the latency with jitter.
+/// Held chunks are dropped when the socket is closed.
+pub struct DelayDecorator {
+    sock: Box<dyn ComplexSock>,
+    queue: Mutex<DelayQueue>,
+    blocking: bool,
+}
+
+impl DelayDecorator {
+    /// Reads the socket once and holds the read chunk.
+    fn receive(&self, queue: &mut DelayQueue, sz: usize) -> Result<usize> {
+        let mut chunk = vec![0u8; sz];
+        let len = self.sock.read(&mut chunk, sz)?;
+        if len > 0 {
+            chunk.truncate(len);
+            queue.push(chunk, Instant::now());
+        }
+        Ok(len)
+    }
+}
+
+impl SimpleSock for DelayDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            if let Some(len) = queue.pop_due(now, data, sz) {
+                return Ok(len);
+            }
+            match queue.next_due() {
+                // Nothing is held, so the read may block in the socket
+                None => {
+                    if self.receive(&mut queue, sz)? == 0 {
+                        return Ok(0);
+                    }
+                }
+                Some(_) if !self.blocking => {
+                    self.receive(&mut queue, sz)?;
+                    return Ok(queue.pop_due(Instant::now(), data, sz).unwrap_or(0));
+                }
+                // Data arriving before the held chunk is due is held too
+                Some(due) => match self.sock.poll_handles() {
+                    Some(handles) => {
+                        if poll::wait_readable(&handles, due - now)? {
+                            self.receive(&mut queue, sz)?;
+                        }
+                    }
+                    None => thread::sleep(due - now),
+                },
+            }
+        }
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.sock.write(data, sz)
+    }
+    decorator_openclose_default!();
+}
+
+impl SockBlockCtl for DelayDecorator {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.blocking = is_blocking;
+        self.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        self.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.sock.flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.sock.shutdown_write()
+    }
+}
+
+impl SockInfo for DelayDecorator {
+    fn get_type_name(&self) -> &str {
+        self.sock.get_type_name()
+    }
+    fn get_id(&self) -> u32 {
+        self.sock.get_id()
+    }
+    fn get_description(&self) -> String {
+        self.sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.sock.source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.sock.sink_rate()
+    }
+    fn semantics(&self) -> Semantics {
+        self.sock.semantics()
+    }
+}
+
+impl SockPoll for DelayDecorator {
+    /// Held chunks aren't signaled by the socket handles, so the caller
+    /// has to retry reading after a short sleep.
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        if self.queue.lock().unwrap().chunks.is_empty() {
+            self.sock.poll_handles()
+        } else {
+            None
+        }
+    }
+}
+
+pub struct DelayDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: DelayConfig,
+}
+
+impl DelayDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: DelayConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for DelayDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.factory.create_sock(params)?;
+        Ok(Box::new(DelayDecorator {
+            sock,
+            queue: Mutex::new(DelayQueue::new(self.config.clone())),
+            blocking: false,
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn delayed_chunks() {
+        let config = |distribution| DelayConfig {
+            latency_ms: 100,
+            jitter_ms: 20,
+            distribution,
+            seed: Some(42),
+        };
+        for distribution in [JitterDistribution::Uniform, JitterDistribution::Normal] {
+            let delays = |mut queue: DelayQueue| -> Vec<Duration> {
+                (0..100).map(|_| queue.next_delay()).collect()
+            };
+            let first = delays(DelayQueue::new(config(distribution)));
+            // The same seed gives the same delays
+            assert_eq!(first, delays(DelayQueue::new(config(distribution))));
+            assert!(first.iter().any(|d| *d != first[0]));
+            if distribution == JitterDistribution::Uniform {
+                let range = Duration::from_millis(80)..=Duration::from_millis(120);
+                assert!(first.iter().all(|d| range.contains(d)));
+            }
+        }
+
+        let mut queue = DelayQueue::new(config(JitterDistribution::Uniform));
+        let start = Instant::now();
+        queue.push(b"first".to_vec(), start);
+        queue.push(b"second".to_vec(), start);
+        let mut buf = [0u8; 16];
+        assert_eq!(queue.pop_due(start, &mut buf, 16), None);
+        let late = start + Duration::from_millis(200);
+        assert_eq!(queue.pop_due(late, &mut buf, 3), Some(3));
+        assert_eq!(queue.pop_due(late, &mut buf[3..], 16), Some(2));
+        assert_eq!(&buf[..5], b"first");
+        // Chunks aren't reordered by jitter
+        assert_eq!(queue.pop_due(late, &mut buf, 16), Some(6));
+        assert_eq!(&buf[..6], b"second");
+        assert_eq!(queue.next_due(), None);
+    }
+}
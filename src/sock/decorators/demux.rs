@@ -0,0 +1,268 @@
+use crate::error;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use crate::sockets::registry::SocketRegistry;
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
+
+fn default_field_size() -> usize {
+    1
+}
+
+/// Sink socket receiving frames with the routing field value.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct DemuxRoute {
+    /// Value of the routing field
+    value: u64,
+    /// Socket type of the sink
+    #[serde(rename = "type")]
+    ty: String,
+    /// Sink socket parameters
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    params: serde_json::Value,
+}
+
+/// Routing of the read frames to sink sockets by the value of the header
+/// field. Frames matching no route are passed as is.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct DemuxConfig {
+    /// Offset of the routing field in the frame
+    #[serde(default)]
+    offset: usize,
+    /// Size of the routing field in bytes (big-endian, 1..8)
+    #[serde(default = "default_field_size")]
+    size: usize,
+    /// Remove the routing field from frames written to the sinks
+    #[serde(default)]
+    strip: bool,
+    routes: Vec<DemuxRoute>,
+}
+
+impl DemuxConfig {
+    /// Returns the routing field value, if the frame contains it.
+    fn field(&self, frame: &[u8]) -> Option<u64> {
+        let field = frame.get(self.offset..self.offset + self.size)?;
+        Some(field.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+    }
+    /// Parameters of the sink sockets.
+    pub fn sink_params(&self) -> Vec<SocketParams> {
+        self.routes.iter().map(|r| SocketParams::new(r.params.clone())).collect()
+    }
+    /// Creates sink sockets of all routes.
+    fn create_sinks(&self) -> Result<Vec<(u64, Box<dyn ComplexSock>)>> {
+        if !(1..=8).contains(&self.size) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Demux field size {} is out of 1..8", self.size),
+            ));
+        }
+        self.routes
+            .iter()
+            .map(|route| {
+                let factory = SocketRegistry::global()
+                    .create(&route.ty)
+                    .ok_or_else(|| error::Error::UnknownType(route.ty.to_string()))?;
+                // Sinks are written in place of the read, so they are blocking
+                let sink = factory.create_sock_blockctl(SocketParams::new(route.params.clone()), true)?;
+                Ok((route.value, sink))
+            })
+            .collect()
+    }
+}
+
+/// Writes frames read from the socket to the sink of the matching route.
+pub struct DemuxDecorator {
+    sock: Box<dyn ComplexSock>,
+    config: DemuxConfig,
+    sinks: Vec<(u64, Box<dyn ComplexSock>)>,
+}
+
+impl DemuxDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        sock: Box<dyn ComplexSock>,
+        config: DemuxConfig,
+        sinks: Vec<(u64, Box<dyn ComplexSock>)>,
+    ) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            config,
+            sinks,
+        })
+    }
+    fn sink(&self, frame: &[u8]) -> Option<&dyn ComplexSock> {
+        let value = self.config.field(frame)?;
+        self.sinks
+            .iter()
+            .find(|(v, _)| *v == value)
+            .map(|(_, sink)| sink.as_ref())
+    }
+}
+
+impl SimpleSock for DemuxDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let read = self.sock.read(data, sz)?;
+        let Some(sink) = self.sink(&data[..read]) else {
+            return Ok(read);
+        };
+        let frame = &data[..read];
+        if self.config.strip {
+            // Header before the field is kept
+            let field_end = self.config.offset + self.config.size;
+            write_frame(
+                sink,
+                &[&frame[..self.config.offset], &frame[field_end..]].concat(),
+            );
+        } else {
+            write_frame(sink, frame);
+        }
+        // Routed frame isn't passed to the binding
+        Ok(0)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.sock.write(data, sz)
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()?;
+        self.sinks.iter_mut().try_for_each(|(_, sink)| sink.open())
+    }
+    fn close(&mut self) {
+        for (_, sink) in self.sinks.iter_mut() {
+            sink.close();
+        }
+        self.sock.close();
+    }
+}
+
+/// Writes the whole frame to the sink. Failed sink doesn't stop the
+/// binding, the frame is dropped.
+fn write_frame(sink: &dyn ComplexSock, frame: &[u8]) {
+    let mut written = 0;
+    while written < frame.len() {
+        match sink.write(&frame[written..], frame.len() - written) {
+            Ok(0) => break,
+            Ok(sz) => written += sz,
+            Err(e) => {
+                tracing::warn!("Demux sink {} write failed: {e}", sink.get_description());
+                return;
+            }
+        }
+    }
+    if let Err(e) = sink.flush_message() {
+        tracing::warn!("Demux sink {} flush failed: {e}", sink.get_description());
+    }
+}
+
+impl SockBlockCtl for DemuxDecorator {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        for (_, sink) in self.sinks.iter_mut() {
+            sink.set_timeout(read, write)?;
+        }
+        self.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        for (_, sink) in self.sinks.iter_mut() {
+            sink.set_grace_period(grace)?;
+        }
+        self.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.sock.flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.sock.shutdown_write()
+    }
+}
+
+decorator_forward_info!(DemuxDecorator);
+
+pub struct DemuxDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: DemuxConfig,
+}
+
+impl DemuxDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: DemuxConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for DemuxDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sinks = self.config.create_sinks()?;
+        let sock = self.factory.create_sock(params)?;
+        Ok(DemuxDecorator::new(sock, self.config.clone(), sinks))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+    use std::sync::{Arc, Mutex};
+
+    make_simple_sock!(Queue {
+        frames: Arc<Mutex<Vec<Vec<u8>>>>,
+    }, "queue");
+
+    impl SimpleSock for Queue {
+        fn read(&self, data: &mut [u8], _: usize) -> Result<usize> {
+            let mut frames = self.frames.lock().unwrap();
+            if frames.is_empty() {
+                return Ok(0);
+            }
+            let frame = frames.remove(0);
+            data[..frame.len()].copy_from_slice(&frame);
+            Ok(frame.len())
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            self.frames.lock().unwrap().push(data[..sz].to_vec());
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Queue {}
+    impl SockPoll for Queue {}
+
+    #[test]
+    fn route_by_channel() {
+        let config: DemuxConfig =
+            serde_json::from_str("{ \"offset\": 1, \"size\": 2, \"strip\": true, \"routes\": [] }")
+                .unwrap();
+        let source = Arc::new(Mutex::new(vec![
+            b"$\x00\x01one".to_vec(),
+            b"$\x00\x02two".to_vec(),
+            b"$\x00\x03three".to_vec(),
+        ]));
+        let (ch1, ch2) = (Arc::default(), Arc::default());
+        let demux = DemuxDecorator::new(
+            Box::new(Queue::new(source)),
+            config,
+            vec![
+                (
+                    1,
+                    Box::new(Queue::new(Arc::clone(&ch1))) as Box<dyn ComplexSock>,
+                ),
+                (2, Box::new(Queue::new(Arc::clone(&ch2)))),
+            ],
+        );
+        let mut buf = [0u8; 16];
+        assert_eq!(demux.read(&mut buf, 16).unwrap(), 0);
+        assert_eq!(demux.read(&mut buf, 16).unwrap(), 0);
+        // Frame of unknown channel is passed to the binding
+        assert_eq!(demux.read(&mut buf, 16).unwrap(), 8);
+        assert_eq!(*ch1.lock().unwrap(), [b"$one".to_vec()]);
+        assert_eq!(*ch2.lock().unwrap(), [b"$two".to_vec()]);
+    }
+}
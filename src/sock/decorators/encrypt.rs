@@ -0,0 +1,200 @@
+use super::framing::{LENGTH_PREFIX_LEN, message_len};
+use super::transform::{Transform, TransformBuilder};
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, KeyInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use rand::RngExt;
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, schemars::JsonSchema)]
+pub enum CipherAlgo {
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    /// Faster than AES without hardware acceleration
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+}
+
+/// Encryption of the socket data with pre-shared key, e.g.
+/// `{ "cipher": "chacha20-poly1305", "key_path": "/etc/polysock/psk" }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct EncryptConfig {
+    #[serde(default)]
+    cipher: CipherAlgo,
+    /// 256-bit key in hex format (visible in the process list, prefer
+    /// `key_path`)
+    key: Option<String>,
+    /// File with 256-bit key in hex format
+    key_path: Option<PathBuf>,
+}
+
+impl EncryptConfig {
+    pub fn key_path(&self) -> Option<&Path> {
+        self.key_path.as_deref()
+    }
+    fn load_key(&self) -> Result<Vec<u8>> {
+        let hex_key = match (&self.key, &self.key_path) {
+            (Some(key), None) => key.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| Error::new(e.kind(), format!("Key file {}: {e}", path.display())))?,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Exactly one of key and key_path must be set",
+                ));
+            }
+        };
+        let key = hex::decode(hex_key.trim())
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid key: {e}")))?;
+        if key.len() != KEY_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Key must be {KEY_LEN} bytes, but it's {}", key.len()),
+            ));
+        }
+        Ok(key)
+    }
+    /// Creates transform builder. The key is loaded once here, so a wrong
+    /// key is reported before sockets are opened.
+    pub fn builder(&self) -> Result<TransformBuilder> {
+        let key = self.load_key()?;
+        let algo = self.cipher;
+        Ok(Arc::new(move || {
+            Ok(Box::new(EncryptTransform::new(algo, &key)) as Box<dyn Transform>)
+        }))
+    }
+}
+
+enum Cipher {
+    Aes256Gcm(Box<Aes256Gcm>),
+    ChaCha20Poly1305(Box<ChaCha20Poly1305>),
+}
+
+impl Cipher {
+    fn new(algo: CipherAlgo, key: &[u8]) -> Self {
+        match algo {
+            CipherAlgo::Aes256Gcm => {
+                Self::Aes256Gcm(Box::new(Aes256Gcm::new_from_slice(key).unwrap()))
+            }
+            CipherAlgo::ChaCha20Poly1305 => {
+                Self::ChaCha20Poly1305(Box::new(ChaCha20Poly1305::new_from_slice(key).unwrap()))
+            }
+        }
+    }
+    fn seal(&self, nonce: &[u8], data: &[u8]) -> std::result::Result<Vec<u8>, aes_gcm::Error> {
+        match self {
+            Self::Aes256Gcm(c) => c.encrypt(aes_gcm::Nonce::from_slice(nonce), data),
+            Self::ChaCha20Poly1305(c) => {
+                c.encrypt(chacha20poly1305::Nonce::from_slice(nonce), data)
+            }
+        }
+    }
+    fn open(&self, nonce: &[u8], data: &[u8]) -> std::result::Result<Vec<u8>, aes_gcm::Error> {
+        match self {
+            Self::Aes256Gcm(c) => c.decrypt(aes_gcm::Nonce::from_slice(nonce), data),
+            Self::ChaCha20Poly1305(c) => {
+                c.decrypt(chacha20poly1305::Nonce::from_slice(nonce), data)
+            }
+        }
+    }
+}
+
+/// Encrypts every written chunk with random nonce into the frame of
+/// 4 bytes length (big-endian), nonce and ciphertext with tag. Read frames
+/// are decrypted, tampered frame or frame encrypted with other key stops
+/// the binding. Replayed frames aren't detected.
+struct EncryptTransform {
+    cipher: Cipher,
+    /// Received bytes of the incomplete frame
+    rx_frame: Vec<u8>,
+}
+
+impl EncryptTransform {
+    fn new(algo: CipherAlgo, key: &[u8]) -> Self {
+        Self {
+            cipher: Cipher::new(algo, key),
+            rx_frame: Vec::new(),
+        }
+    }
+}
+
+impl Transform for EncryptTransform {
+    fn on_read(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.rx_frame.extend(data);
+        let mut out = Vec::new();
+        while let Some(len) = message_len(&self.rx_frame)? {
+            let frame = &self.rx_frame[LENGTH_PREFIX_LEN..LENGTH_PREFIX_LEN + len];
+            let Some((nonce, sealed)) = frame.split_at_checked(NONCE_LEN) else {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Truncated encrypted frame",
+                ));
+            };
+            let plain = self.cipher.open(nonce, sealed).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    "Encrypted frame fails authentication (wrong key or tampered data)",
+                )
+            })?;
+            out.extend(plain);
+            self.rx_frame.drain(..LENGTH_PREFIX_LEN + len);
+        }
+        Ok(out)
+    }
+    fn on_write(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rng().fill(&mut nonce);
+        let sealed = self
+            .cipher
+            .seal(&nonce, data)
+            .map_err(|_| Error::other("Encryption failed"))?;
+        let len = (NONCE_LEN + sealed.len()) as u32;
+        Ok([&len.to_be_bytes()[..], &nonce, &sealed].concat())
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn encrypt_roundtrip() {
+        let key = [7u8; KEY_LEN];
+        for algo in [CipherAlgo::Aes256Gcm, CipherAlgo::ChaCha20Poly1305] {
+            let mut tx = EncryptTransform::new(algo, &key);
+            let mut rx = EncryptTransform::new(algo, &key);
+            let first = tx.on_write(b"hello").unwrap();
+            let second = tx.on_write(b"world").unwrap();
+            // Nonce is random, so equal chunks are encrypted differently
+            assert_ne!(tx.on_write(b"hello").unwrap(), first);
+            assert!(!first.windows(5).any(|w| w == b"hello"));
+            // Frames split by the stream are reassembled
+            let stream = [first.clone(), second].concat();
+            assert_eq!(rx.on_read(&stream[..7]).unwrap(), b"");
+            assert_eq!(rx.on_read(&stream[7..]).unwrap(), b"helloworld");
+
+            let mut tampered = first.clone();
+            *tampered.last_mut().unwrap() ^= 1;
+            let mut same_key = EncryptTransform::new(algo, &key);
+            assert!(same_key.on_read(&tampered).is_err());
+            let mut other_key = EncryptTransform::new(algo, &[8u8; KEY_LEN]);
+            assert!(other_key.on_read(&first).is_err());
+        }
+        let config = |key: &str| EncryptConfig {
+            cipher: CipherAlgo::default(),
+            key: Some(key.to_string()),
+            key_path: None,
+        };
+        assert!(config(&"ab".repeat(KEY_LEN)).builder().is_ok());
+        assert!(config(&"ab".repeat(16)).builder().is_err());
+        assert!(config("not hex").builder().is_err());
+    }
+}
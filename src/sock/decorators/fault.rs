@@ -0,0 +1,281 @@
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Mutex;
+use std::time::Duration;
+
+fn default_window() -> usize {
+    3
+}
+
+/// Duplication and reordering of the chunks read from the socket, e.g.
+/// `{ "duplicate": 0.01, "reorder": 0.05, "window": 4 }` emulates
+/// unreliable UDP path.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct FaultConfig {
+    /// Probability of the chunk to be passed twice
+    #[serde(default)]
+    duplicate: f64,
+    /// Probability of the chunk to be passed after the next ones
+    #[serde(default)]
+    reorder: f64,
+    /// Max number of the next chunks passed before the reordered one
+    #[serde(default = "default_window")]
+    window: usize,
+    /// Seed of the fault generator, the same seed gives the same faults
+    /// (random, if it is not set)
+    seed: Option<u64>,
+}
+
+impl FaultConfig {
+    fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.duplicate) || !(0.0..1.0).contains(&self.reorder) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Duplicate probability must be in 0..=1 and reorder one in 0..1",
+            ));
+        }
+        if self.window == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Reorder window must be positive",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Chunks passed to the reader and chunks held back by reordering.
+struct FaultQueue {
+    config: FaultConfig,
+    rng: StdRng,
+    /// Data and already read length of the passed chunks
+    ready: VecDeque<(Vec<u8>, usize)>,
+    /// Number of the next chunks to pass before release and data of the
+    /// held chunks
+    held: Vec<(usize, Vec<u8>)>,
+}
+
+impl FaultQueue {
+    fn new(config: FaultConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => rand::make_rng(),
+        };
+        Self {
+            config,
+            rng,
+            ready: VecDeque::new(),
+            held: Vec::new(),
+        }
+    }
+    fn push(&mut self, chunk: Vec<u8>) {
+        let copies = if self.rng.random_bool(self.config.duplicate) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            if self.rng.random_bool(self.config.reorder) {
+                let after = self.rng.random_range(1..=self.config.window);
+                self.held.push((after, chunk.clone()));
+                continue;
+            }
+            self.ready.push_back((chunk.clone(), 0));
+            // Passed chunk moves the held ones closer to release
+            for (after, _) in self.held.iter_mut() {
+                *after -= 1;
+            }
+            for (_, released) in self.held.extract_if(.., |(after, _)| *after == 0) {
+                self.ready.push_back((released, 0));
+            }
+        }
+    }
+    /// Copies up to `sz` bytes of the first passed chunk to `data`.
+    fn pop(&mut self, data: &mut [u8], sz: usize) -> Option<usize> {
+        let (chunk, offset) = self.ready.front_mut()?;
+        let len = (chunk.len() - *offset).min(sz);
+        data[..len].copy_from_slice(&chunk[*offset..*offset + len]);
+        *offset += len;
+        if *offset == chunk.len() {
+            self.ready.pop_front();
+        }
+        Some(len)
+    }
+}
+
+/// Duplicates and reorders chunks read from the socket to test robustness
+/// of the peers. Reordered chunk is held until the next chunks come, so
+/// it's dropped if the socket is closed before.
+pub struct FaultDecorator {
+    sock: Box<dyn ComplexSock>,
+    queue: Mutex<FaultQueue>,
+    blocking: bool,
+}
+
+impl SimpleSock for FaultDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if let Some(len) = queue.pop(data, sz) {
+                return Ok(len);
+            }
+            let mut chunk = vec![0u8; sz];
+            let len = self.sock.read(&mut chunk, sz)?;
+            if len == 0 {
+                return Ok(0);
+            }
+            chunk.truncate(len);
+            queue.push(chunk);
+            // Held chunk doesn't block the reader
+            if !self.blocking {
+                return Ok(queue.pop(data, sz).unwrap_or(0));
+            }
+        }
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.sock.write(data, sz)
+    }
+    decorator_openclose_default!();
+}
+
+impl SockBlockCtl for FaultDecorator {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.blocking = is_blocking;
+        self.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        self.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.sock.flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.sock.shutdown_write()
+    }
+}
+
+impl SockInfo for FaultDecorator {
+    fn get_type_name(&self) -> &str {
+        self.sock.get_type_name()
+    }
+    fn get_id(&self) -> u32 {
+        self.sock.get_id()
+    }
+    fn get_description(&self) -> String {
+        self.sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.sock.source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.sock.sink_rate()
+    }
+    fn semantics(&self) -> Semantics {
+        self.sock.semantics()
+    }
+}
+
+impl SockPoll for FaultDecorator {
+    /// Duplicated and released chunks aren't signaled by the socket
+    /// handles, so the caller has to retry reading after a short sleep.
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        if self.queue.lock().unwrap().ready.is_empty() {
+            self.sock.poll_handles()
+        } else {
+            None
+        }
+    }
+}
+
+pub struct FaultDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: FaultConfig,
+}
+
+impl FaultDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: FaultConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for FaultDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        self.config.validate()?;
+        let sock = self.factory.create_sock(params)?;
+        Ok(Box::new(FaultDecorator {
+            sock,
+            queue: Mutex::new(FaultQueue::new(self.config.clone())),
+            blocking: false,
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn duplicate_reorder() {
+        let config = |duplicate, reorder| FaultConfig {
+            duplicate,
+            reorder,
+            window: 2,
+            seed: Some(42),
+        };
+        let passed = |mut queue: FaultQueue| -> (Vec<u8>, Vec<u8>) {
+            for i in 0..100 {
+                queue.push(vec![i]);
+            }
+            let mut out = Vec::new();
+            let mut buf = [0u8; 1];
+            while queue.pop(&mut buf, 1).is_some() {
+                out.push(buf[0]);
+            }
+            let held = queue.held.iter().map(|(_, chunk)| chunk[0]).collect();
+            (out, held)
+        };
+
+        let (out, held) = passed(FaultQueue::new(config(1.0, 0.0)));
+        assert!(held.is_empty());
+        assert_eq!(out, (0..100).flat_map(|i| [i, i]).collect::<Vec<_>>());
+
+        let (out, held) = passed(FaultQueue::new(config(0.0, 0.3)));
+        // The same seed gives the same faults
+        assert_eq!(
+            (out.clone(), held.clone()),
+            passed(FaultQueue::new(config(0.0, 0.3)))
+        );
+        assert!(!out.is_sorted());
+        // Reordered chunks aren't lost, the last ones wait for release
+        let mut all = [out.clone(), held.clone()].concat();
+        all.sort();
+        assert_eq!(all, (0..100).collect::<Vec<_>>());
+
+        let mut queue = FaultQueue::new(config(0.0, 0.0));
+        queue.push(b"chunk".to_vec());
+        let mut buf = [0u8; 8];
+        assert_eq!(queue.pop(&mut buf, 3), Some(3));
+        assert_eq!(queue.pop(&mut buf[3..], 8), Some(2));
+        assert_eq!(&buf[..5], b"chunk");
+        assert_eq!(queue.pop(&mut buf, 8), None);
+        assert!(config(0.0, 1.0).validate().is_err());
+    }
+}
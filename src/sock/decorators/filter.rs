@@ -0,0 +1,250 @@
+use super::framing::{MAX_MESSAGE_LEN, MESSAGE_READ_SIZE, write_frame};
+use super::monitor::Direction;
+use crate::sock::pattern::BytePattern;
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use serde::Deserialize;
+use std::io::Result;
+use std::sync::Mutex;
+
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct FilterRule {
+    /// Pattern matched against the chunk
+    on: BytePattern,
+    /// Direction of the data (both directions, if it is not set)
+    dir: Option<Direction>,
+}
+
+/// Content filter of the socket data, e.g. `{ "delimiter": "\n", "pass":
+/// [ { "on": { "regex": "^\\$GPRMC," }, "dir": "rx" } ] }` passes only
+/// RMC sentences of NMEA feed.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct FilterConfig {
+    /// Allowlist: chunk must match one of the rules of its direction (all
+    /// chunks pass, if there are no such rules)
+    #[serde(default)]
+    pass: Vec<FilterRule>,
+    /// Denylist: chunk matching one of the rules of its direction is dropped
+    #[serde(default)]
+    drop: Vec<FilterRule>,
+    /// Data is split into records ending with the delimiter (e.g. "\r\n"),
+    /// which are filtered instead of the chunks. Records longer than the
+    /// max message are split.
+    delimiter: Option<String>,
+}
+
+impl FilterConfig {
+    fn passes(&self, dir: Direction, data: &[u8]) -> bool {
+        let applies = |r: &&FilterRule| r.dir.is_none_or(|d| d == dir);
+        let mut pass = self.pass.iter().filter(applies).peekable();
+        let allowed = pass.peek().is_none() || pass.any(|r| r.on.matches(data));
+        allowed && !self.drop.iter().filter(applies).any(|r| r.on.matches(data))
+    }
+}
+
+/// Filter state of one direction.
+#[derive(Clone, Default)]
+struct FilterState {
+    /// Incomplete record (delimiter is set only)
+    partial: Vec<u8>,
+    /// Passed data, which isn't delivered yet
+    pending: Vec<u8>,
+    passed: u64,
+    dropped: u64,
+}
+
+impl FilterState {
+    fn push(&mut self, config: &FilterConfig, dir: Direction, record: &[u8]) {
+        if config.passes(dir, record) {
+            self.passed += 1;
+            self.pending.extend(record);
+        } else {
+            self.dropped += 1;
+        }
+    }
+    /// Filters the chunk, passed data is appended to the pending one.
+    fn feed(&mut self, config: &FilterConfig, dir: Direction, data: &[u8]) {
+        let delimiter = match &config.delimiter {
+            Some(delimiter) if !delimiter.is_empty() => delimiter.as_bytes(),
+            _ => return self.push(config, dir, data),
+        };
+        self.partial.extend(data);
+        let mut start = 0;
+        while let Some(pos) = self.partial[start..]
+            .windows(delimiter.len())
+            .position(|w| w == delimiter)
+        {
+            let end = start + pos + delimiter.len();
+            let record = self.partial[start..end].to_vec();
+            self.push(config, dir, &record);
+            start = end;
+        }
+        self.partial.drain(..start);
+        if self.partial.len() > MAX_MESSAGE_LEN {
+            let record = std::mem::take(&mut self.partial);
+            self.push(config, dir, &record);
+        }
+    }
+}
+
+/// Drops chunks (or records) read from and written to the socket, which
+/// don't pass the rules. Dropped chunk read from the socket looks like no
+/// data, dropped written chunk is reported as written.
+pub struct FilterDecorator {
+    sock: Box<dyn ComplexSock>,
+    config: FilterConfig,
+    rx: Mutex<FilterState>,
+    tx: Mutex<FilterState>,
+}
+
+impl SimpleSock for FilterDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let mut rx = self.rx.lock().unwrap();
+        if rx.pending.is_empty() {
+            let mut raw = vec![0u8; MESSAGE_READ_SIZE];
+            let len = self.sock.read(&mut raw, MESSAGE_READ_SIZE)?;
+            if len == 0 {
+                return Ok(0);
+            }
+            rx.feed(&self.config, Direction::Rx, &raw[..len]);
+        }
+        let len = rx.pending.len().min(sz);
+        data[..len].copy_from_slice(&rx.pending[..len]);
+        rx.pending.drain(..len);
+        Ok(len)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let mut tx = self.tx.lock().unwrap();
+        // State is updated only if the passed data is written, as the
+        // caller retries the unwritten chunk
+        let mut next = tx.clone();
+        next.feed(&self.config, Direction::Tx, &data[..sz]);
+        if !next.pending.is_empty() && !write_frame(self.sock.as_ref(), &next.pending)? {
+            return Ok(0);
+        }
+        next.pending.clear();
+        *tx = next;
+        Ok(sz)
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        let (rx, tx) = (self.rx.get_mut().unwrap(), self.tx.get_mut().unwrap());
+        trace_println!(
+            "Filter of {}: {} chunks passed, {} dropped",
+            self.sock.get_description(),
+            rx.passed + tx.passed,
+            rx.dropped + tx.dropped
+        );
+        self.sock.close();
+    }
+}
+
+decorator_forward_blockctl!(FilterDecorator);
+
+impl SockInfo for FilterDecorator {
+    fn get_type_name(&self) -> &str {
+        self.sock.get_type_name()
+    }
+    fn get_id(&self) -> u32 {
+        self.sock.get_id()
+    }
+    fn get_description(&self) -> String {
+        self.sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.sock.source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.sock.sink_rate()
+    }
+    fn semantics(&self) -> Semantics {
+        self.sock.semantics()
+    }
+}
+
+impl SockPoll for FilterDecorator {
+    /// Passed data, which didn't fit the read buffer, isn't signaled by the
+    /// socket handles, so the caller has to retry reading after a short
+    /// sleep.
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        if self.rx.lock().unwrap().pending.is_empty() {
+            self.sock.poll_handles()
+        } else {
+            None
+        }
+    }
+}
+
+pub struct FilterDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: FilterConfig,
+}
+
+impl FilterDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: FilterConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for FilterDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.factory.create_sock(params)?;
+        Ok(Box::new(FilterDecorator {
+            sock,
+            config: self.config.clone(),
+            rx: Mutex::new(FilterState::default()),
+            tx: Mutex::new(FilterState::default()),
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn filter_records() {
+        let config: FilterConfig = serde_json::from_str(
+            r#"{ "delimiter": "\r\n",
+                 "pass": [ { "on": { "regex": "^\\$GP(RMC|GGA)," }, "dir": "rx" } ],
+                 "drop": [ { "on": { "text": ",V," } } ] }"#,
+        )
+        .unwrap();
+        let mut rx = FilterState::default();
+        // Records split between the chunks are reassembled
+        rx.feed(&config, Direction::Rx, b"$GPGSV,3,1*7A\r\n$GPRMC,12");
+        assert!(rx.pending.is_empty());
+        rx.feed(
+            &config,
+            Direction::Rx,
+            b"3519,A,4807*6A\r\n$GPRMC,1,V,*00\r\n$GPGGA",
+        );
+        assert_eq!(rx.pending, b"$GPRMC,123519,A,4807*6A\r\n");
+        assert_eq!((rx.passed, rx.dropped), (1, 2));
+        assert_eq!(rx.partial, b"$GPGGA");
+
+        // Allowlist is for rx only, denylist for both directions
+        let mut tx = FilterState::default();
+        tx.feed(&config, Direction::Tx, b"AT\r\n$GPRMC,1,V,*00\r\n");
+        assert_eq!(tx.pending, b"AT\r\n");
+
+        // Without delimiter every chunk is filtered
+        let config = FilterConfig {
+            delimiter: None,
+            ..config
+        };
+        let mut rx = FilterState::default();
+        rx.feed(&config, Direction::Rx, b"noise $GPRMC,");
+        rx.feed(&config, Direction::Rx, b"$GPRMC,1\r\nnoise");
+        assert_eq!(rx.pending, b"$GPRMC,1\r\nnoise");
+    }
+}
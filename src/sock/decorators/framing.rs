@@ -0,0 +1,380 @@
+use crate::sock::poll;
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll,
+};
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Mutex;
+use std::thread;
+
+/// Length of the message length prefix (big-endian).
+pub(super) const LENGTH_PREFIX_LEN: usize = 4;
+/// Messages with larger length are considered garbage.
+pub(super) const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+pub(super) const MESSAGE_READ_SIZE: usize = 65536;
+
+/// Bridging of a message socket (e.g. UDP) with a stream one (e.g. TCP).
+#[derive(
+    Deserialize, Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Framing {
+    /// Bytes are passed as is, message boundaries are lost in the stream
+    #[default]
+    Raw,
+    /// Every message is prefixed with 4 bytes length (big-endian) in the stream
+    LengthPrefix,
+    /// Bridging is refused with error
+    Strict,
+}
+
+/// Presents a message socket as a stream of length-prefixed messages:
+/// read messages are encoded, messages decoded from the written stream are
+/// written one by one.
+pub struct LengthPrefixDecorator {
+    sock: Box<dyn ComplexSock>,
+    /// Encoded message, which didn't fit the read buffer
+    rx_pending: Mutex<Vec<u8>>,
+    /// Written bytes of the incomplete message
+    tx_pending: Mutex<Vec<u8>>,
+}
+
+impl LengthPrefixDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            rx_pending: Mutex::new(Vec::new()),
+            tx_pending: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+/// Returns length of the first complete message in the stream.
+pub(super) fn message_len(stream: &[u8]) -> Result<Option<usize>> {
+    let Some(prefix) = stream.first_chunk::<LENGTH_PREFIX_LEN>() else {
+        return Ok(None);
+    };
+    let len = u32::from_be_bytes(*prefix) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Message length {len} is too large, the stream isn't length-prefixed"),
+        ));
+    }
+    Ok((stream.len() >= LENGTH_PREFIX_LEN + len).then_some(len))
+}
+
+/// Writes the whole frame, waiting for the socket to accept the rest of
+/// it, once a part is written. Returns `false`, if nothing is written.
+pub(super) fn write_frame(sock: &dyn ComplexSock, frame: &[u8]) -> Result<bool> {
+    let mut written = 0;
+    while written < frame.len() {
+        match sock.write(&frame[written..], frame.len() - written)? {
+            0 if written == 0 => return Ok(false),
+            0 => thread::sleep(poll::FALLBACK_SLEEP),
+            len => written += len,
+        }
+    }
+    Ok(true)
+}
+
+impl SimpleSock for LengthPrefixDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let mut pending = self.rx_pending.lock().unwrap();
+        if pending.is_empty() {
+            let mut message = vec![0u8; MESSAGE_READ_SIZE];
+            let len = self.sock.read(&mut message, MESSAGE_READ_SIZE)?;
+            if len == 0 {
+                return Ok(0);
+            }
+            pending.extend((len as u32).to_be_bytes());
+            pending.extend(&message[..len]);
+        }
+        let len = pending.len().min(sz);
+        data[..len].copy_from_slice(&pending[..len]);
+        pending.drain(..len);
+        Ok(len)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let mut pending = self.tx_pending.lock().unwrap();
+        // Only the incomplete message is kept pending between the writes
+        let kept = pending.len();
+        pending.extend(&data[..sz]);
+        let mut written = 0;
+        while let Some(len) = message_len(&pending)? {
+            let message = &pending[LENGTH_PREFIX_LEN..LENGTH_PREFIX_LEN + len];
+            if !write_frame(&*self.sock, message)? {
+                // Socket is full: the rest of the data is written again by
+                // the caller, starting from the unwritten message
+                pending.truncate(kept.saturating_sub(written));
+                return Ok(written.saturating_sub(kept));
+            }
+            pending.drain(..LENGTH_PREFIX_LEN + len);
+            written += LENGTH_PREFIX_LEN + len;
+        }
+        Ok(sz)
+    }
+    decorator_openclose_default!();
+}
+
+decorator_forward_blockctl!(LengthPrefixDecorator);
+
+impl SockInfo for LengthPrefixDecorator {
+    fn get_type_name(&self) -> &str {
+        self.sock.get_type_name()
+    }
+    fn get_id(&self) -> u32 {
+        self.sock.get_id()
+    }
+    fn get_description(&self) -> String {
+        self.sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.sock.source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.sock.sink_rate()
+    }
+    fn semantics(&self) -> Semantics {
+        Semantics::Stream
+    }
+}
+
+impl SockPoll for LengthPrefixDecorator {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        self.sock.poll_handles()
+    }
+}
+
+/// Checks semantics of the bound sockets. Message socket bridged with
+/// stream one is adapted, refused or passed as is (with warning) according
+/// to the framing.
+pub fn adapt_framing(
+    input: Box<dyn ComplexSock>,
+    output: Box<dyn ComplexSock>,
+    framing: Framing,
+) -> Result<(Box<dyn ComplexSock>, Box<dyn ComplexSock>)> {
+    if input.semantics() == output.semantics() {
+        return Ok((input, output));
+    }
+    let input_is_message = input.semantics() == Semantics::Message;
+    let (message, stream) = if input_is_message {
+        (input.get_description(), output.get_description())
+    } else {
+        (output.get_description(), input.get_description())
+    };
+    match framing {
+        Framing::Raw => {
+            tracing::warn!(
+                "Message boundaries of {message} are lost in stream {stream}, \
+                 use length-prefix framing to keep them"
+            );
+            Ok((input, output))
+        }
+        Framing::Strict => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Message socket {message} can't be bound with stream socket {stream}: \
+                 message boundaries would be lost (use length-prefix or raw framing)"
+            ),
+        )),
+        Framing::LengthPrefix if input_is_message => {
+            Ok((LengthPrefixDecorator::new(input), output))
+        }
+        Framing::LengthPrefix => Ok((input, LengthPrefixDecorator::new(output))),
+    }
+}
+
+/// Source socket of the fan-out binding with its sinks.
+pub type FanOutSocks = (Box<dyn ComplexSock>, Vec<Box<dyn ComplexSock>>);
+
+/// Checks semantics of the fan-out source and its sinks. Length-prefixed
+/// source is the stream shared by all sinks, so message sinks decode it
+/// back, otherwise every sink is adapted as if it was bound alone.
+pub fn adapt_fan_out_framing(
+    input: Box<dyn ComplexSock>,
+    sinks: Vec<Box<dyn ComplexSock>>,
+    framing: Framing,
+) -> Result<FanOutSocks> {
+    let is_stream = |sock: &dyn ComplexSock| sock.semantics() == Semantics::Stream;
+    if framing == Framing::LengthPrefix
+        && !is_stream(input.as_ref())
+        && sinks.iter().any(|sink| is_stream(sink.as_ref()))
+    {
+        let sinks = sinks
+            .into_iter()
+            .map(|sink| {
+                if is_stream(sink.as_ref()) {
+                    sink
+                } else {
+                    LengthPrefixDecorator::new(sink)
+                }
+            })
+            .collect();
+        return Ok((LengthPrefixDecorator::new(input), sinks));
+    }
+    let mut input = input;
+    let mut adapted = Vec::with_capacity(sinks.len());
+    for sink in sinks {
+        let (source, sink) = adapt_framing(input, sink, framing)?;
+        input = source;
+        adapted.push(sink);
+    }
+    Ok((input, adapted))
+}
+
+/// Source sockets of the fan-in binding with their sink.
+pub type FanInSocks = (Vec<Box<dyn ComplexSock>>, Box<dyn ComplexSock>);
+
+/// Checks semantics of the fan-in sources and their sink. Length-prefixed
+/// sink is the stream shared by all sources, so message sources are
+/// encoded, otherwise every source is adapted as if it was bound alone.
+pub fn adapt_fan_in_framing(
+    sources: Vec<Box<dyn ComplexSock>>,
+    output: Box<dyn ComplexSock>,
+    framing: Framing,
+) -> Result<FanInSocks> {
+    let is_stream = |sock: &dyn ComplexSock| sock.semantics() == Semantics::Stream;
+    if framing == Framing::LengthPrefix
+        && !is_stream(output.as_ref())
+        && sources.iter().any(|source| is_stream(source.as_ref()))
+    {
+        let sources = sources
+            .into_iter()
+            .map(|source| {
+                if is_stream(source.as_ref()) {
+                    source
+                } else {
+                    LengthPrefixDecorator::new(source)
+                }
+            })
+            .collect();
+        return Ok((sources, LengthPrefixDecorator::new(output)));
+    }
+    let mut output = output;
+    let mut adapted = Vec::with_capacity(sources.len());
+    for source in sources {
+        let (source, sink) = adapt_framing(source, output, framing)?;
+        output = sink;
+        adapted.push(source);
+    }
+    Ok((adapted, output))
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    type Written = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    make_simple_sock!(Chunks {
+        semantics: Semantics,
+        rx: Mutex<VecDeque<Vec<u8>>>,
+        tx: Written,
+        full: Arc<AtomicBool>,
+    }, "chunks"; info: {
+        fn semantics(&self) -> Semantics {
+            self.semantics
+        }
+    });
+
+    impl SimpleSock for Chunks {
+        fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+            let Some(chunk) = self.rx.lock().unwrap().pop_front() else {
+                return Ok(0);
+            };
+            let len = chunk.len().min(sz);
+            data[..len].copy_from_slice(&chunk[..len]);
+            Ok(len)
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            if self.full.load(Ordering::Relaxed) {
+                return Ok(0);
+            }
+            self.tx.lock().unwrap().push(data[..sz].to_vec());
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Chunks {}
+    impl SockPoll for Chunks {}
+
+    fn chunks(semantics: Semantics, rx: &[&[u8]], tx: &Written) -> Box<dyn ComplexSock> {
+        let rx = rx.iter().map(|chunk| chunk.to_vec()).collect();
+        Box::new(Chunks::new(semantics, Mutex::new(rx), tx.clone(), Default::default()))
+    }
+
+    #[test]
+    fn length_prefix() {
+        let tx = Written::default();
+        let message = || chunks(Semantics::Message, &[], &tx);
+        let stream = || chunks(Semantics::Stream, &[], &tx);
+        let res = adapt_framing(message(), stream(), Framing::Strict);
+        let err = res.map(|_| ()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(adapt_framing(stream(), stream(), Framing::Strict).is_ok());
+        assert!(adapt_framing(message(), stream(), Framing::Raw).is_ok());
+
+        // Message side is adapted, so both sockets are streams
+        let input = chunks(Semantics::Message, &[b"ab", b"cde"], &tx);
+        let (sock, output) = adapt_framing(input, stream(), Framing::LengthPrefix).unwrap();
+        assert_eq!(sock.semantics(), Semantics::Stream);
+        assert_eq!(output.semantics(), Semantics::Stream);
+        let mut buf = [0u8; 16];
+        assert_eq!(sock.read(&mut buf, 4).unwrap(), 4);
+        assert_eq!(sock.read(&mut buf[4..], 12).unwrap(), 2);
+        assert_eq!(sock.read(&mut buf[6..], 10).unwrap(), 7);
+        assert_eq!(&buf[..13], b"\0\0\0\x02ab\0\0\0\x03cde");
+        assert_eq!(sock.read(&mut buf, 16).unwrap(), 0);
+
+        // Messages split between writes are reassembled
+        let written = b"\0\0\0\x02ab\0\0\0\x03cde";
+        assert_eq!(sock.write(&written[..3], 3).unwrap(), 3);
+        assert_eq!(sock.write(&written[3..], 10).unwrap(), 10);
+        assert_eq!(*tx.lock().unwrap(), [b"ab".to_vec(), b"cde".to_vec()]);
+        assert!(sock.write(&[0xff; 4], 4).is_err());
+
+        // Messages aren't lost, while the message socket is full
+        let full = Arc::new(AtomicBool::new(true));
+        let input = Chunks::new(Semantics::Message, Default::default(), tx.clone(), full.clone());
+        let sock = LengthPrefixDecorator::new(Box::new(input));
+        tx.lock().unwrap().clear();
+        assert_eq!(sock.write(&written[..3], 3).unwrap(), 3);
+        assert_eq!(sock.write(&written[3..], 10).unwrap(), 0);
+        full.store(false, Ordering::Relaxed);
+        assert_eq!(sock.write(&written[3..], 10).unwrap(), 10);
+        assert_eq!(*tx.lock().unwrap(), [b"ab".to_vec(), b"cde".to_vec()]);
+
+        // Fan-out source is prefixed once, message sinks decode it back
+        let (source, sinks) =
+            adapt_fan_out_framing(message(), vec![message(), stream()], Framing::LengthPrefix)
+                .unwrap();
+        assert_eq!(source.semantics(), Semantics::Stream);
+        assert!(sinks.iter().all(|s| s.semantics() == Semantics::Stream));
+        let (source, _) =
+            adapt_fan_out_framing(message(), vec![message()], Framing::LengthPrefix).unwrap();
+        assert_eq!(source.semantics(), Semantics::Message);
+        let res = adapt_fan_out_framing(stream(), vec![stream(), message()], Framing::Strict);
+        assert!(res.is_err());
+
+        // Fan-in sink is decoded once, message sources are encoded
+        let (sources, sink) =
+            adapt_fan_in_framing(vec![message(), stream()], message(), Framing::LengthPrefix)
+                .unwrap();
+        assert!(sources.iter().all(|s| s.semantics() == Semantics::Stream));
+        assert_eq!(sink.semantics(), Semantics::Stream);
+        let (sources, _) =
+            adapt_fan_in_framing(vec![message()], stream(), Framing::LengthPrefix).unwrap();
+        assert_eq!(sources[0].semantics(), Semantics::Stream);
+        let res = adapt_fan_in_framing(vec![stream(), message()], stream(), Framing::Strict);
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use super::trace_file::TraceStyle;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Result;
+use std::sync::{Arc, Mutex, Weak};
+
+static CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+/// Hash algorithm of the stream hash decorator.
+#[derive(Deserialize, Debug, Clone, Copy, clap::ValueEnum, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgo {
+    /// SHA-256 digest
+    Sha256,
+    /// CRC-32 (ISO-HDLC, same as zlib/gzip)
+    Crc32,
+}
+
+impl HashAlgo {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "SHA-256",
+            Self::Crc32 => "CRC-32",
+        }
+    }
+}
+
+enum StreamHasher {
+    Sha256(Sha256),
+    Crc32(crc::Digest<'static, u32>),
+}
+
+impl StreamHasher {
+    fn new(algo: HashAlgo) -> Self {
+        match algo {
+            HashAlgo::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgo::Crc32 => Self::Crc32(CRC32.digest()),
+        }
+    }
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(data),
+            Self::Crc32(h) => h.update(data),
+        }
+    }
+    /// Returns the digest of all data passed so far in hex format.
+    fn digest(&self) -> String {
+        match self {
+            Self::Sha256(h) => hex::encode(h.clone().finalize()),
+            Self::Crc32(h) => format!("{:08x}", h.clone().finalize()),
+        }
+    }
+}
+
+struct DirectionHash {
+    hasher: StreamHasher,
+    bytes: u64,
+}
+
+impl DirectionHash {
+    fn new(algo: HashAlgo) -> Self {
+        Self {
+            hasher: StreamHasher::new(algo),
+            bytes: 0,
+        }
+    }
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.bytes += data.len() as u64;
+    }
+}
+
+/// Current digests of the socket with the hash decorator.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StreamDigest {
+    /// Description of the socket
+    pub socket: String,
+    pub algo: &'static str,
+    /// Digest of the received data in hex format
+    pub rx: String,
+    pub rx_bytes: u64,
+    /// Digest of the written data in hex format
+    pub tx: String,
+    pub tx_bytes: u64,
+}
+
+/// Hashes of both directions shared with the registry of open sockets.
+struct HashState {
+    descr: Mutex<String>,
+    algo: HashAlgo,
+    rx: Mutex<DirectionHash>,
+    tx: Mutex<DirectionHash>,
+}
+
+impl HashState {
+    fn digest(&self) -> StreamDigest {
+        let rx = self.rx.lock().unwrap();
+        let tx = self.tx.lock().unwrap();
+        StreamDigest {
+            socket: self.descr.lock().unwrap().clone(),
+            algo: self.algo.name(),
+            rx: rx.hasher.digest(),
+            rx_bytes: rx.bytes,
+            tx: tx.hasher.digest(),
+            tx_bytes: tx.bytes,
+        }
+    }
+}
+
+/// Hashed sockets, which aren't closed yet.
+static OPEN_HASHES: Mutex<Vec<Weak<HashState>>> = Mutex::new(Vec::new());
+
+/// Returns current digests of every open socket with the hash decorator,
+/// so a transfer can be verified on demand while the binding is running
+/// (e.g. by the `digests` request of the daemon control socket).
+pub fn stream_digests() -> Vec<StreamDigest> {
+    let mut open = OPEN_HASHES.lock().unwrap();
+    open.retain(|state| state.strong_count() > 0);
+    open.iter()
+        .filter_map(Weak::upgrade)
+        .map(|state| state.digest())
+        .collect()
+}
+
+/// Computes rolling digest of received and written data, which are
+/// reported when the socket is closed and on demand, see [`stream_digests`].
+pub struct HashDecorator {
+    sock: Box<dyn ComplexSock>,
+    state: Arc<HashState>,
+}
+
+impl HashDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, algo: &HashAlgo) -> Box<dyn ComplexSock> {
+        let state = Arc::new(HashState {
+            descr: Mutex::new(sock.get_description()),
+            algo: *algo,
+            rx: Mutex::new(DirectionHash::new(*algo)),
+            tx: Mutex::new(DirectionHash::new(*algo)),
+        });
+        OPEN_HASHES.lock().unwrap().push(Arc::downgrade(&state));
+        Box::new(Self { sock, state })
+    }
+    fn report(&self) {
+        let digest = self.state.digest();
+        trace_println!(
+            style = TraceStyle::Rx;
+            "{} of data received from {}: {} ({} bytes)",
+            digest.algo,
+            digest.socket,
+            digest.rx,
+            digest.rx_bytes
+        );
+        trace_println!(
+            style = TraceStyle::Tx;
+            "{} of data written to {}: {} ({} bytes)",
+            digest.algo,
+            digest.socket,
+            digest.tx,
+            digest.tx_bytes
+        );
+    }
+}
+
+socket_decorator!(HashDecorator, HashAlgo);
+
+impl SimpleSock for HashDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let res = self.sock.read(data, sz);
+        if let Ok(sz) = res {
+            self.state.rx.lock().unwrap().update(&data[..sz]);
+        }
+        res
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let res = self.sock.write(data, sz);
+        if let Ok(sz) = res {
+            self.state.tx.lock().unwrap().update(&data[..sz]);
+        }
+        res
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()?;
+        // Description may be known only when the socket is open
+        *self.state.descr.lock().unwrap() = self.sock.get_description();
+        Ok(())
+    }
+    fn close(&mut self) {
+        self.report();
+        let state = Arc::downgrade(&self.state);
+        OPEN_HASHES.lock().unwrap().retain(|open| !open.ptr_eq(&state));
+        self.sock.close();
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+
+    make_simple_sock!(Sink { tag: String }, "sink", self, { format!("sink-{}", self.tag) });
+
+    impl SimpleSock for Sink {
+        fn read(&self, _: &mut [u8], _: usize) -> Result<usize> {
+            Ok(0)
+        }
+        fn write(&self, _: &[u8], sz: usize) -> Result<usize> {
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Sink {}
+    impl SockPoll for Sink {}
+
+    #[test]
+    fn digests_on_demand() {
+        let tag = format!("digest-{}", std::process::id());
+        let mut sock = HashDecorator::new(Box::new(Sink::new(tag.clone())), &HashAlgo::Crc32);
+        let current = || {
+            stream_digests()
+                .into_iter()
+                .find(|digest| digest.socket == format!("sink-{tag}"))
+        };
+        sock.write(b"1234", 4).unwrap();
+        sock.write(b"56789", 5).unwrap();
+        // Digest is read before the socket is closed
+        let digest = current().unwrap();
+        assert_eq!((digest.tx.as_str(), digest.tx_bytes), ("cbf43926", 9));
+        assert_eq!((digest.rx.as_str(), digest.rx_bytes), ("00000000", 0));
+        sock.close();
+        assert!(current().is_none());
+    }
+
+    #[test]
+    fn known_digests() {
+        let mut sha = StreamHasher::new(HashAlgo::Sha256);
+        let mut crc = StreamHasher::new(HashAlgo::Crc32);
+        // Chunked update must be equal to the digest of the whole stream
+        for chunk in ["1234", "56789"] {
+            sha.update(chunk.as_bytes());
+            crc.update(chunk.as_bytes());
+        }
+        assert_eq!(crc.digest(), "cbf43926");
+        assert_eq!(
+            sha.digest(),
+            "15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225"
+        );
+    }
+}
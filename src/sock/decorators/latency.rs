@@ -0,0 +1,280 @@
+use super::framing::write_frame;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Result;
+use std::time::{Duration, Instant};
+
+/// Magic of the chunk tagged by the measuring side and of the reflected
+/// tag.
+const TAG_MAGIC: &[u8; 3] = b"PLT";
+const REFLECT_MAGIC: &[u8; 3] = b"PLR";
+/// Magic and sequence number (big-endian)
+const HEADER_LEN: usize = 7;
+/// Max number of the tags waiting for reflection, older ones are lost.
+const MAX_PENDING: usize = 4096;
+/// Upper bounds of the histogram buckets in microseconds.
+const BUCKETS_US: [u64; 13] = [
+    100, 200, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000, 200_000, 500_000,
+    1_000_000,
+];
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LatencyRole {
+    /// Tags written chunks and measures round trip of the reflected tags
+    Tag,
+    /// Writes tags of the read chunks back to the socket
+    Reflect,
+}
+
+/// Round-trip latency measurement between two polysock instances, e.g.
+/// `{ "role": "tag" }` on one end of the link and `{ "role": "reflect" }`
+/// on the other one.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct LatencyConfig {
+    role: LatencyRole,
+}
+
+fn header(magic: &[u8; 3], seq: u32) -> [u8; HEADER_LEN] {
+    let mut header = [0u8; HEADER_LEN];
+    header[..3].copy_from_slice(magic);
+    header[3..].copy_from_slice(&seq.to_be_bytes());
+    header
+}
+
+/// Returns sequence number of the chunk starting with the magic.
+fn parse(magic: &[u8; 3], chunk: &[u8]) -> Option<u32> {
+    let header = chunk.get(..HEADER_LEN)?;
+    (header[..3] == magic[..]).then(|| u32::from_be_bytes(header[3..].try_into().unwrap()))
+}
+
+/// Round-trip times with the histogram of the log scale buckets.
+#[derive(Default)]
+pub(crate) struct LatencyHistogram {
+    count: u64,
+    sum: Duration,
+    min: Duration,
+    max: Duration,
+    /// Sample counts of `BUCKETS_US` and of the larger samples
+    buckets: [u64; BUCKETS_US.len() + 1],
+}
+
+impl LatencyHistogram {
+    pub(crate) fn add(&mut self, rtt: Duration) {
+        self.min = if self.count == 0 {
+            rtt
+        } else {
+            self.min.min(rtt)
+        };
+        self.max = self.max.max(rtt);
+        self.count += 1;
+        self.sum += rtt;
+        let us = rtt.as_micros() as u64;
+        let bucket = BUCKETS_US.iter().position(|b| us < *b);
+        self.buckets[bucket.unwrap_or(BUCKETS_US.len())] += 1;
+    }
+}
+
+fn format_us(us: u64) -> String {
+    if us < 1_000 {
+        format!("{us} µs")
+    } else if us < 1_000_000 {
+        format!("{} ms", us / 1_000)
+    } else {
+        format!("{} s", us / 1_000_000)
+    }
+}
+
+impl fmt::Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count == 0 {
+            return write!(f, "no samples");
+        }
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{} samples, min/avg/max {:.3}/{:.3}/{:.3} ms",
+            self.count,
+            ms(self.min),
+            ms(self.sum) / self.count as f64,
+            ms(self.max)
+        )?;
+        for (i, count) in self.buckets.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            let range = match BUCKETS_US.get(i) {
+                Some(bound) => format!("< {}", format_us(*bound)),
+                None => format!(">= {}", format_us(BUCKETS_US[i - 1])),
+            };
+            let bar = (count * 40).div_ceil(self.count) as usize;
+            write!(f, "\n{range:>9}: {count:>8} {}", "#".repeat(bar))?;
+        }
+        Ok(())
+    }
+}
+
+struct LatencyTracker {
+    next_seq: u32,
+    /// Sequence numbers and send times of the tags waiting for reflection
+    pending: VecDeque<(u32, Instant)>,
+    histogram: LatencyHistogram,
+    lost: u64,
+}
+
+impl LatencyTracker {
+    fn new() -> Self {
+        Self {
+            next_seq: 0,
+            pending: VecDeque::new(),
+            histogram: LatencyHistogram::default(),
+            lost: 0,
+        }
+    }
+    /// Header of the next tagged chunk.
+    fn next_tag(&self) -> [u8; HEADER_LEN] {
+        header(TAG_MAGIC, self.next_seq)
+    }
+    /// Next tag is sent.
+    fn sent(&mut self, now: Instant) {
+        if self.pending.len() == MAX_PENDING {
+            self.pending.pop_front();
+            self.lost += 1;
+        }
+        self.pending.push_back((self.next_seq, now));
+        self.next_seq = self.next_seq.wrapping_add(1);
+    }
+    /// Reflected tag gives the round trip sample. Tags sent before it are
+    /// lost, as the reflection keeps the order.
+    fn reflected(&mut self, seq: u32, now: Instant) {
+        let Some(pos) = self.pending.iter().position(|(s, _)| *s == seq) else {
+            return;
+        };
+        self.lost += pos as u64;
+        let (_, sent) = self.pending.drain(..=pos).next_back().unwrap();
+        self.histogram.add(now - sent);
+    }
+}
+
+/// Measures round-trip latency of the link. Tagging side prefixes every
+/// written chunk with 7 bytes header (magic and sequence number), the
+/// reflecting side strips it from the read chunk and writes it back.
+/// Histogram is printed on close. Tags are recognized at the beginning
+/// of the read chunk, so it works with message sockets (e.g. UDP).
+pub struct LatencyDecorator {
+    sock: Box<dyn ComplexSock>,
+    role: LatencyRole,
+    tracker: RefCell<LatencyTracker>,
+}
+
+impl LatencyDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, config: &LatencyConfig) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            role: config.role,
+            tracker: RefCell::new(LatencyTracker::new()),
+        })
+    }
+    /// Removes the header from the read chunk.
+    fn strip(data: &mut [u8], read: usize) -> usize {
+        data.copy_within(HEADER_LEN..read, 0);
+        read - HEADER_LEN
+    }
+}
+
+socket_decorator!(LatencyDecorator, LatencyConfig);
+
+impl SimpleSock for LatencyDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let read = self.sock.read(data, sz)?;
+        match self.role {
+            LatencyRole::Tag => {
+                if let Some(seq) = parse(REFLECT_MAGIC, &data[..read]) {
+                    self.tracker.borrow_mut().reflected(seq, Instant::now());
+                    return Ok(Self::strip(data, read));
+                }
+            }
+            LatencyRole::Reflect => {
+                if let Some(seq) = parse(TAG_MAGIC, &data[..read]) {
+                    let reflection = header(REFLECT_MAGIC, seq);
+                    // Reflection isn't retried, the tag is lost if the
+                    // socket isn't ready
+                    self.sock.write(&reflection, HEADER_LEN)?;
+                    return Ok(Self::strip(data, read));
+                }
+            }
+        }
+        Ok(read)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        if self.role == LatencyRole::Reflect {
+            return self.sock.write(data, sz);
+        }
+        let mut tracker = self.tracker.borrow_mut();
+        let frame = [&tracker.next_tag()[..], &data[..sz]].concat();
+        if !write_frame(self.sock.as_ref(), &frame)? {
+            return Ok(0);
+        }
+        tracker.sent(Instant::now());
+        Ok(sz)
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        if self.role == LatencyRole::Tag {
+            let tracker = self.tracker.borrow();
+            trace_println!(
+                "Round-trip latency of {}: {} lost, {}",
+                self.sock.get_description(),
+                tracker.lost + tracker.pending.len() as u64,
+                tracker.histogram
+            );
+        }
+        self.sock.close();
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn tag_reflect_histogram() {
+        let start = Instant::now();
+        let mut tracker = LatencyTracker::new();
+        let tags: Vec<_> = (0..4)
+            .map(|_| {
+                let tag = tracker.next_tag();
+                tracker.sent(start);
+                tag
+            })
+            .collect();
+        assert_eq!(tags[1], *b"PLT\0\0\0\x01");
+        assert_eq!(parse(TAG_MAGIC, &tags[2]), Some(2));
+        assert_eq!(parse(REFLECT_MAGIC, &tags[2]), None);
+        assert_eq!(parse(TAG_MAGIC, b"PLT"), None);
+
+        // Tag 1 isn't reflected, 3 waits for reflection
+        tracker.reflected(0, start + Duration::from_micros(150));
+        tracker.reflected(2, start + Duration::from_millis(3));
+        tracker.reflected(7, start + Duration::from_millis(3));
+        assert_eq!(tracker.lost, 1);
+        assert_eq!(tracker.pending.len(), 1);
+        assert_eq!(
+            tracker.histogram.to_string(),
+            "2 samples, min/avg/max 0.150/1.575/3.000 ms\
+             \n < 200 µs:        1 ####################\
+             \n   < 5 ms:        1 ####################"
+        );
+        assert_eq!(LatencyHistogram::default().to_string(), "no samples");
+    }
+}
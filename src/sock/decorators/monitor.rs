@@ -0,0 +1,235 @@
+use super::trace_file::TraceStyle;
+use crate::sock::pattern::BytePattern;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use pretty_hex::PrettyHex;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Result;
+
+/// State name matching any state in transitions and illegal patterns.
+const ANY_STATE: &str = "*";
+
+/// Data direction relative to the monitored socket.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Data received from the socket
+    Rx,
+    /// Data written to the socket
+    Tx,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rx => write!(f, "rx"),
+            Self::Tx => write!(f, "tx"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct Transition {
+    /// Source state ("*" for any state)
+    from: String,
+    /// Destination state
+    to: String,
+    /// Pattern triggering the transition
+    on: BytePattern,
+    /// Direction of the data (both directions, if it is not set)
+    dir: Option<Direction>,
+}
+
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct IllegalPattern {
+    /// State where the pattern is illegal ("*" for any state)
+    state: String,
+    /// Pattern which is not allowed
+    on: BytePattern,
+    /// Direction of the data (both directions, if it is not set)
+    dir: Option<Direction>,
+}
+
+/// User-defined protocol state machine. Patterns are matched against
+/// every chunk separately, so a pattern split between two reads is not
+/// detected.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct MonitorConfig {
+    /// Initial state name
+    initial: String,
+    /// Allowed transitions (the first matching one is taken)
+    transitions: Vec<Transition>,
+    /// Patterns which are protocol violations in the given state
+    #[serde(default)]
+    illegal: Vec<IllegalPattern>,
+    /// Data matching no transition of the current state is a violation
+    #[serde(default)]
+    strict: bool,
+    /// Dump the offending chunk in canonical hex format on violation
+    #[serde(default)]
+    dump: bool,
+}
+
+fn state_matches(pattern: &str, state: &str) -> bool {
+    pattern == ANY_STATE || pattern == state
+}
+
+fn dir_matches(dir: Option<Direction>, actual: Direction) -> bool {
+    dir.is_none_or(|d| d == actual)
+}
+
+struct StateMachine {
+    state: String,
+    transitions: u64,
+    violations: u64,
+}
+
+impl StateMachine {
+    fn new(cfg: &MonitorConfig) -> Self {
+        Self {
+            state: cfg.initial.clone(),
+            transitions: 0,
+            violations: 0,
+        }
+    }
+    /// Feeds one chunk to the state machine. Returns violation description,
+    /// if the chunk breaks the protocol.
+    fn feed(&mut self, cfg: &MonitorConfig, dir: Direction, data: &[u8]) -> Option<String> {
+        if data.is_empty() {
+            return None;
+        }
+        if let Some(ill) = cfg.illegal.iter().find(|ill| {
+            state_matches(&ill.state, &self.state)
+                && dir_matches(ill.dir, dir)
+                && ill.on.matches(data)
+        }) {
+            self.violations += 1;
+            return Some(format!(
+                "pattern {} is illegal in state {}",
+                ill.on, self.state
+            ));
+        }
+        match cfg.transitions.iter().find(|t| {
+            state_matches(&t.from, &self.state) && dir_matches(t.dir, dir) && t.on.matches(data)
+        }) {
+            Some(t) => {
+                tracing::debug!("Protocol state {} -> {} ({})", self.state, t.to, dir);
+                self.state = t.to.clone();
+                self.transitions += 1;
+                None
+            }
+            None if cfg.strict => {
+                self.violations += 1;
+                Some(format!("unexpected data in state {}", self.state))
+            }
+            None => None,
+        }
+    }
+}
+
+/// Evaluates the protocol state machine against the stream and reports
+/// violations.
+pub struct MonitorDecorator {
+    sock: Box<dyn ComplexSock>,
+    config: MonitorConfig,
+    machine: RefCell<StateMachine>,
+}
+
+impl MonitorDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, config: &MonitorConfig) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            config: config.clone(),
+            machine: RefCell::new(StateMachine::new(config)),
+        })
+    }
+    fn check(&self, dir: Direction, data: &[u8]) {
+        let violation = self.machine.borrow_mut().feed(&self.config, dir, data);
+        if let Some(violation) = violation {
+            trace_println!(
+                style = TraceStyle::Error;
+                "Protocol violation on {} ({}): {}",
+                self.sock.get_description(),
+                dir,
+                violation
+            );
+            if self.config.dump {
+                trace_println!(style = TraceStyle::Error; "{:?}", data.hex_dump());
+            }
+        }
+    }
+}
+
+socket_decorator!(MonitorDecorator, MonitorConfig);
+
+impl SimpleSock for MonitorDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let res = self.sock.read(data, sz);
+        if let Ok(sz) = res {
+            self.check(Direction::Rx, &data[..sz]);
+        }
+        res
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let res = self.sock.write(data, sz);
+        if let Ok(sz) = res {
+            self.check(Direction::Tx, &data[..sz]);
+        }
+        res
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        let machine = self.machine.borrow();
+        trace_println!(
+            "Protocol monitor {}: state {}, {} transitions, {} violations",
+            self.sock.get_description(),
+            machine.state,
+            machine.transitions,
+            machine.violations
+        );
+        drop(machine);
+        self.sock.close();
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn transitions_and_violations() {
+        let cfg: MonitorConfig = serde_json::from_str(
+            r#"{
+                "initial": "idle",
+                "transitions": [
+                    { "from": "idle", "to": "connected", "on": { "text": "CONNECT" }, "dir": "tx" },
+                    { "from": "connected", "to": "idle", "on": { "text": "BYE" } },
+                    { "from": "connected", "to": "connected", "on": { "hex": "02" } }
+                ],
+                "illegal": [ { "state": "idle", "on": { "hex": "02" } } ],
+                "strict": true
+            }"#,
+        )
+        .unwrap();
+        let mut sm = StateMachine::new(&cfg);
+        // Transition is bound to the tx direction
+        assert!(sm.feed(&cfg, Direction::Rx, b"CONNECT").is_some());
+        assert!(sm.feed(&cfg, Direction::Tx, b"CONNECT").is_none());
+        assert_eq!(sm.state, "connected");
+        assert!(sm.feed(&cfg, Direction::Rx, b"\x02data").is_none());
+        assert!(sm.feed(&cfg, Direction::Rx, b"BYE").is_none());
+        assert_eq!(
+            sm.feed(&cfg, Direction::Rx, b"\x02data").unwrap(),
+            "pattern 0x02 is illegal in state idle"
+        );
+        assert_eq!((sm.transitions, sm.violations), (3, 2));
+    }
+}
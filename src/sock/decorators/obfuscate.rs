@@ -0,0 +1,187 @@
+use super::transform::{Transform, TransformBuilder};
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
+
+/// Trivial obfuscation used by legacy devices, e.g.
+/// `{ "xor": "5a3c", "per_chunk": true }`. Written data is substituted and
+/// then XORed, read data is deobfuscated in the reverse order.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct ObfuscateConfig {
+    /// Repeating XOR key in hex format
+    xor: Option<String>,
+    /// XOR key restarts at every chunk (e.g. datagram) instead of running
+    /// through the stream
+    #[serde(default)]
+    per_chunk: bool,
+    /// Substitution table in hex format: 256 bytes, every byte value once
+    substitute: Option<String>,
+}
+
+fn decode_hex(name: &str, value: &str) -> Result<Vec<u8>> {
+    hex::decode(value.trim())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid {name}: {e}")))
+}
+
+impl ObfuscateConfig {
+    fn obfuscation(&self) -> Result<Obfuscation> {
+        let xor = match &self.xor {
+            Some(key) => decode_hex("XOR key", key)?,
+            None => Vec::new(),
+        };
+        if self.xor.is_some() && xor.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "XOR key is empty"));
+        }
+        let substitute = match &self.substitute {
+            Some(table) => Some(SubstTable::new(&decode_hex("substitution table", table)?)?),
+            None => None,
+        };
+        Ok(Obfuscation {
+            xor,
+            per_chunk: self.per_chunk,
+            substitute,
+            rx_pos: 0,
+            tx_pos: 0,
+        })
+    }
+    /// Creates transform builder. The keys are checked once here, so a
+    /// wrong key is reported before sockets are opened.
+    pub fn builder(&self) -> Result<TransformBuilder> {
+        self.obfuscation()?;
+        let config = self.clone();
+        Ok(Arc::new(move || {
+            Ok(Box::new(config.obfuscation()?) as Box<dyn Transform>)
+        }))
+    }
+}
+
+/// Byte substitution with its inverse.
+struct SubstTable {
+    forward: [u8; 256],
+    inverse: [u8; 256],
+}
+
+impl SubstTable {
+    fn new(table: &[u8]) -> Result<Self> {
+        let Ok(forward) = <[u8; 256]>::try_from(table) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Substitution table must be 256 bytes, but it's {}",
+                    table.len()
+                ),
+            ));
+        };
+        let mut inverse = [0u8; 256];
+        let mut seen = [false; 256];
+        for (byte, subst) in forward.iter().enumerate() {
+            if seen[*subst as usize] {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Substitution table isn't invertible: {subst:#04x} is repeated"),
+                ));
+            }
+            seen[*subst as usize] = true;
+            inverse[*subst as usize] = byte as u8;
+        }
+        Ok(Self { forward, inverse })
+    }
+}
+
+struct Obfuscation {
+    xor: Vec<u8>,
+    per_chunk: bool,
+    substitute: Option<SubstTable>,
+    /// XOR key positions of the read and written streams
+    rx_pos: usize,
+    tx_pos: usize,
+}
+
+/// XORs data with the key starting at `pos`, `pos` is moved to the key
+/// byte following the data.
+fn xor(key: &[u8], data: &mut [u8], pos: &mut usize) {
+    for byte in data {
+        *byte ^= key[*pos];
+        *pos = (*pos + 1) % key.len();
+    }
+}
+
+impl Obfuscation {
+    /// Key position of the next chunk.
+    fn key_pos(per_chunk: bool, pos: &mut usize) -> &mut usize {
+        if per_chunk {
+            *pos = 0;
+        }
+        pos
+    }
+}
+
+impl Transform for Obfuscation {
+    fn on_read(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = data.to_vec();
+        if !self.xor.is_empty() {
+            xor(
+                &self.xor,
+                &mut out,
+                Self::key_pos(self.per_chunk, &mut self.rx_pos),
+            );
+        }
+        if let Some(table) = &self.substitute {
+            out.iter_mut().for_each(|b| *b = table.inverse[*b as usize]);
+        }
+        Ok(out)
+    }
+    fn on_write(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = data.to_vec();
+        if let Some(table) = &self.substitute {
+            out.iter_mut().for_each(|b| *b = table.forward[*b as usize]);
+        }
+        if !self.xor.is_empty() {
+            xor(
+                &self.xor,
+                &mut out,
+                Self::key_pos(self.per_chunk, &mut self.tx_pos),
+            );
+        }
+        Ok(out)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn obfuscation_roundtrip() {
+        let config = |xor: Option<&str>, per_chunk, substitute: Option<String>| ObfuscateConfig {
+            xor: xor.map(str::to_string),
+            per_chunk,
+            substitute,
+        };
+        let mut xor = config(Some("0102"), false, None).obfuscation().unwrap();
+        assert_eq!(xor.on_write(b"\0\0\0").unwrap(), b"\x01\x02\x01");
+        // Key runs through the stream
+        assert_eq!(xor.on_write(b"\0").unwrap(), b"\x02");
+        assert_eq!(xor.on_read(b"\x01\x02\x01\x02").unwrap(), b"\0\0\0\0");
+        let mut xor = config(Some("0102"), true, None).obfuscation().unwrap();
+        assert_eq!(xor.on_write(b"\0\0\0").unwrap(), b"\x01\x02\x01");
+        assert_eq!(xor.on_write(b"\0").unwrap(), b"\x01");
+
+        // Table adding 1 to every byte
+        let table: Vec<u8> = (0..=255u8).map(|b| b.wrapping_add(1)).collect();
+        let mut both = config(Some("ff"), false, Some(hex::encode(&table)))
+            .obfuscation()
+            .unwrap();
+        let data: Vec<u8> = (0..=255u8).collect();
+        let written = both.on_write(&data).unwrap();
+        assert_eq!(written[..2], [0x01 ^ 0xff, 0x02 ^ 0xff]);
+        assert_eq!(both.on_read(&written).unwrap(), data);
+
+        let repeated = hex::encode([0u8; 256]);
+        assert!(config(None, false, Some(repeated)).builder().is_err());
+        assert!(config(None, false, Some("00".into())).builder().is_err());
+        assert!(config(Some(""), false, None).builder().is_err());
+        assert!(config(Some("xyz"), false, None).builder().is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use super::monitor::Direction;
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket shaping the socket traffic, e.g. `{ "rate": 960 }`
+/// emulates 9600 baud link (8N1) over a fast transport.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct RateLimitConfig {
+    /// Sustained rate in bytes per second
+    rate: u64,
+    /// Bucket size: max bytes passed at once after idle time (default is
+    /// 1/10 s of the rate)
+    burst: Option<u64>,
+    /// Limited direction (both directions, if it is not set)
+    dir: Option<Direction>,
+}
+
+impl RateLimitConfig {
+    fn burst(&self) -> u64 {
+        self.burst.unwrap_or(self.rate / 10).max(1)
+    }
+    fn validate(&self) -> Result<()> {
+        if self.rate == 0 || self.burst == Some(0) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Rate limit and burst must be positive",
+            ));
+        }
+        Ok(())
+    }
+    fn bucket(&self, dir: Direction) -> Option<Mutex<TokenBucket>> {
+        self.dir.is_none_or(|d| d == dir).then(|| {
+            Mutex::new(TokenBucket::new(
+                self.rate as f64,
+                self.burst() as f64,
+                Instant::now(),
+            ))
+        })
+    }
+}
+
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// Bucket is full initially, so the first burst passes at once.
+    fn new(rate: f64, burst: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last: now,
+        }
+    }
+    /// Returns number of bytes (up to `want`) allowed now, or time to wait
+    /// until the whole `want` (or the burst, if it's smaller) is allowed.
+    fn available(&mut self, want: usize, now: Instant) -> std::result::Result<usize, Duration> {
+        let refill = now.saturating_duration_since(self.last).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.burst);
+        self.last = now;
+        let need = want.min(self.burst as usize) as f64;
+        if self.tokens >= need {
+            Ok((self.tokens as usize).min(want))
+        } else {
+            Err(Duration::from_secs_f64((need - self.tokens) / self.rate))
+        }
+    }
+    fn consume(&mut self, len: usize) {
+        self.tokens -= len as f64;
+    }
+    /// Returns the tokens taken, but not used by the operation.
+    fn refund(&mut self, len: usize) {
+        self.tokens = (self.tokens + len as f64).min(self.burst);
+    }
+}
+
+/// Passes data of the limited directions not faster than the rate. The
+/// operation returns 0 until the tokens are refilled, the socket isn't
+/// polled meanwhile, so the binding sleeps instead of waiting for data.
+/// Messages aren't split, the bucket goes into debt for the rest of the
+/// message instead.
+pub struct RateLimitDecorator {
+    sock: Box<dyn ComplexSock>,
+    rate: f64,
+    rx: Option<Mutex<TokenBucket>>,
+    tx: Option<Mutex<TokenBucket>>,
+}
+
+fn limited(
+    bucket: &Option<Mutex<TokenBucket>>,
+    sz: usize,
+    message: bool,
+    op: impl FnOnce(usize) -> Result<usize>,
+) -> Result<usize> {
+    let Some(bucket) = bucket else {
+        return op(sz);
+    };
+    let reserved = {
+        let mut state = bucket.lock().unwrap();
+        // Nothing is done until the tokens are refilled: the caller waits
+        // without holding the socket, so the other direction isn't blocked
+        let Ok(allowed) = state.available(sz, Instant::now()) else {
+            return Ok(0);
+        };
+        let reserved = if message { sz } else { allowed };
+        // Tokens are taken in advance, so the operation is done without
+        // the lock
+        state.consume(reserved);
+        reserved
+    };
+    let res = op(reserved);
+    let used = *res.as_ref().unwrap_or(&0);
+    bucket.lock().unwrap().refund(reserved.saturating_sub(used));
+    res
+}
+
+impl SimpleSock for RateLimitDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let message = self.sock.semantics() == Semantics::Message;
+        limited(&self.rx, sz, message, |allowed| self.sock.read(data, allowed))
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let message = self.sock.semantics() == Semantics::Message;
+        limited(&self.tx, sz, message, |allowed| self.sock.write(data, allowed))
+    }
+    decorator_openclose_default!();
+}
+
+decorator_forward_blockctl!(RateLimitDecorator);
+
+impl SockInfo for RateLimitDecorator {
+    fn get_type_name(&self) -> &str {
+        self.sock.get_type_name()
+    }
+    fn get_id(&self) -> u32 {
+        self.sock.get_id()
+    }
+    fn get_description(&self) -> String {
+        self.sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        let inner = self.sock.source_rate();
+        match self.rx {
+            Some(_) => Some(inner.map_or(self.rate, |rate| rate.min(self.rate))),
+            None => inner,
+        }
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        let inner = self.sock.sink_rate();
+        match self.tx {
+            Some(_) => Some(inner.map_or(self.rate, |rate| rate.min(self.rate))),
+            None => inner,
+        }
+    }
+    fn semantics(&self) -> Semantics {
+        self.sock.semantics()
+    }
+}
+
+impl SockPoll for RateLimitDecorator {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        // Readable socket doesn't mean the data can be read
+        if let Some(bucket) = &self.rx
+            && bucket.lock().unwrap().available(1, Instant::now()).is_err()
+        {
+            return None;
+        }
+        self.sock.poll_handles()
+    }
+}
+
+pub struct RateLimitDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: RateLimitConfig,
+}
+
+impl RateLimitDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: RateLimitConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for RateLimitDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        self.config.validate()?;
+        let sock = self.factory.create_sock(params)?;
+        Ok(Box::new(RateLimitDecorator {
+            sock,
+            rate: self.config.rate as f64,
+            rx: self.config.bucket(Direction::Rx),
+            tx: self.config.bucket(Direction::Tx),
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn token_bucket() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        // 1000 B/s with 100 bytes burst
+        let mut bucket = TokenBucket::new(1000.0, 100.0, start);
+        assert_eq!(bucket.available(300, start), Ok(100));
+        bucket.consume(100);
+        // Empty bucket: 100 bytes are refilled in 100 ms
+        assert_eq!(
+            bucket.available(300, start),
+            Err(Duration::from_millis(100))
+        );
+        assert_eq!(bucket.available(30, at(20)), Err(Duration::from_millis(10)));
+        assert_eq!(bucket.available(30, at(50)), Ok(30));
+        bucket.consume(30);
+        // Idle time doesn't accumulate more than the burst
+        assert_eq!(bucket.available(300, at(10_000)), Ok(100));
+        assert_eq!(bucket.available(0, at(10_000)), Ok(0));
+        // Unused tokens are returned up to the burst
+        bucket.consume(100);
+        bucket.refund(150);
+        assert_eq!(bucket.available(300, at(10_000)), Ok(100));
+    }
+    #[test]
+    fn wait_without_lock() {
+        let mut empty = TokenBucket::new(1000.0, 100.0, Instant::now());
+        empty.consume(100);
+        let bucket = Some(Mutex::new(empty));
+        // Operation isn't done without tokens
+        assert_eq!(limited(&bucket, 50, false, |_| unreachable!()).unwrap(), 0);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(limited(&bucket, 50, false, |len| Ok(len / 2)).unwrap(), 25);
+        // Only the used tokens are consumed
+        let mut bucket = bucket.as_ref().unwrap().lock().unwrap();
+        assert!(bucket.available(25, Instant::now()).is_ok());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Magic and version of the record log.
+const RECORD_MAGIC: &[u8; 6] = b"PSREC\x01";
+/// Chunks with larger length are considered corrupted.
+const MAX_CHUNK_LEN: u64 = 16 * 1024 * 1024;
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads LEB128 value. Returns `None` on the clean end of the log.
+fn read_varint(input: &mut impl Read) -> Result<Option<u64>> {
+    let mut value = 0u64;
+    let mut byte = [0u8];
+    for shift in (0..64).step_by(7) {
+        if input.read(&mut byte)? == 0 {
+            return match shift {
+                0 => Ok(None),
+                _ => Err(ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(Error::new(
+        ErrorKind::InvalidData,
+        "Record varint is too long",
+    ))
+}
+
+/// Writes chunks with the time since the previous one (in microseconds)
+/// and the length, both as varints.
+pub struct RecordWriter<W: Write> {
+    out: W,
+    last: SystemTime,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Writes the log header, the first chunk time is relative to now.
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(RECORD_MAGIC)?;
+        Ok(Self {
+            out,
+            last: SystemTime::now(),
+        })
+    }
+    pub fn write_chunk(&mut self, time: SystemTime, data: &[u8]) -> Result<()> {
+        // Kernel timestamp may be older than the previous chunk
+        let delta = time.duration_since(self.last).unwrap_or_default();
+        self.last = self.last.max(time);
+        let mut chunk = Vec::with_capacity(data.len() + 16);
+        write_varint(&mut chunk, delta.as_micros() as u64);
+        write_varint(&mut chunk, data.len() as u64);
+        chunk.extend(data);
+        self.out.write_all(&chunk)?;
+        // Log is usable even if the binding is killed
+        self.out.flush()
+    }
+}
+
+/// Reads chunks of the record log.
+pub struct RecordReader<R: Read> {
+    input: R,
+    /// Time of the last read chunk since the recording start
+    at: Duration,
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Checks the log header.
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut magic = [0u8; RECORD_MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if &magic != RECORD_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a record log"));
+        }
+        Ok(Self {
+            input,
+            at: Duration::ZERO,
+        })
+    }
+    /// Returns the next chunk with its time since the recording start.
+    pub fn next_chunk(&mut self) -> Result<Option<(Duration, Vec<u8>)>> {
+        let Some(delta) = read_varint(&mut self.input)? else {
+            return Ok(None);
+        };
+        let len = read_varint(&mut self.input)?.ok_or(ErrorKind::UnexpectedEof)?;
+        if len > MAX_CHUNK_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Record chunk length {len} is too large"),
+            ));
+        }
+        let mut data = vec![0u8; len as usize];
+        self.input.read_exact(&mut data)?;
+        self.at += Duration::from_micros(delta);
+        Ok(Some((self.at, data)))
+    }
+}
+
+/// Writes every chunk read from the socket to the record log.
+pub struct RecordDecorator {
+    sock: Box<dyn ComplexSock>,
+    writer: Mutex<RecordWriter<BufWriter<File>>>,
+}
+
+decorator_forward!(RecordDecorator);
+
+impl SimpleSock for RecordDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let res = self.sock.read(data, sz);
+        if let Ok(sz @ 1..) = res {
+            let time = self.sock.rx_timestamp().unwrap_or_else(SystemTime::now);
+            if let Err(e) = self.writer.lock().unwrap().write_chunk(time, &data[..sz]) {
+                tracing::warn!(
+                    "Recording of {} data failed: {e}",
+                    self.sock.get_description()
+                );
+            }
+        }
+        res
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.sock.write(data, sz)
+    }
+    decorator_openclose_default!();
+}
+
+pub struct RecordDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    path: PathBuf,
+}
+
+impl RecordDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, path: PathBuf) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, path })
+    }
+}
+
+impl SocketFactory for RecordDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.factory.create_sock(params)?;
+        let writer = RecordWriter::new(BufWriter::new(File::create(&self.path)?))?;
+        Ok(Box::new(RecordDecorator {
+            sock,
+            writer: Mutex::new(writer),
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn record_roundtrip() {
+        let mut writer = RecordWriter::new(Vec::new()).unwrap();
+        let start = writer.last;
+        writer.write_chunk(start, b"hello").unwrap();
+        writer
+            .write_chunk(start + Duration::from_millis(1500), &[0u8; 300])
+            .unwrap();
+        // Late timestamp doesn't move the time backwards
+        writer.write_chunk(start, b"late").unwrap();
+        let mut reader = RecordReader::new(Cursor::new(writer.out)).unwrap();
+        let chunk = |at: u64, data: &[u8]| Some((Duration::from_millis(at), data.to_vec()));
+        assert_eq!(reader.next_chunk().unwrap(), chunk(0, b"hello"));
+        assert_eq!(reader.next_chunk().unwrap(), chunk(1500, &[0u8; 300]));
+        assert_eq!(reader.next_chunk().unwrap(), chunk(1500, b"late"));
+        assert_eq!(reader.next_chunk().unwrap(), None);
+        assert!(RecordReader::new(Cursor::new(b"garbage".to_vec())).is_err());
+    }
+}
@@ -0,0 +1,406 @@
+use crate::sock::{
+    ComplexSock, POLL_TIMEOUT, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockInfo, SockPoll,
+    SocketFactory, SocketParams, poll,
+};
+use std::io::{Error, ErrorKind, Result};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Settings applied to the socket by the binding, they are applied again
+/// to the reopened socket.
+#[derive(Default, Clone, Copy)]
+struct Settings {
+    blocking: Option<bool>,
+    timeout: Option<(Option<Duration>, Option<Duration>)>,
+    grace_period: Option<Duration>,
+    echo: Option<bool>,
+}
+
+/// Socket of the endpoint with the parameters it's created with.
+struct Endpoint {
+    sock: Box<dyn ComplexSock>,
+    params: SocketParams,
+    settings: Settings,
+    opened: bool,
+}
+
+impl Endpoint {
+    /// Creates the socket with the same settings and state as the current one.
+    fn create(
+        &self,
+        factory: &dyn SocketFactory,
+        params: &SocketParams,
+    ) -> Result<Box<dyn ComplexSock>> {
+        let mut sock = factory.create_sock(params.clone())?;
+        let settings = self.settings;
+        if let Some(blocking) = settings.blocking {
+            sock.set_block(blocking)?;
+        }
+        if let Some((read, write)) = settings.timeout {
+            sock.set_timeout(read, write)?;
+        }
+        if let Some(grace) = settings.grace_period {
+            sock.set_grace_period(grace)?;
+        }
+        if let Some(echo) = settings.echo {
+            sock.set_echo(echo)?;
+        }
+        if self.opened {
+            // Binding waiting for the readiness of the closed socket keeps
+            // its resources (e.g. port), until the wait is woken
+            let start = Instant::now();
+            while let Err(e) = sock.open() {
+                if e.kind() != ErrorKind::AddrInUse || start.elapsed() >= POLL_TIMEOUT {
+                    return Err(e);
+                }
+                thread::sleep(poll::FALLBACK_SLEEP);
+            }
+        }
+        Ok(sock)
+    }
+}
+
+/// Wakes the binding waiting for the readiness of the replaced socket, so
+/// it polls the handles of the new one.
+struct Wake {
+    #[cfg(unix)]
+    pair: Option<(UnixDatagram, UnixDatagram)>,
+}
+
+impl Wake {
+    fn new() -> Self {
+        #[cfg(unix)]
+        {
+            // Without the pair the binding notices the new socket after
+            // the poll timeout
+            let pair = UnixDatagram::pair().ok().filter(|(rx, tx)| {
+                rx.set_nonblocking(true).is_ok() && tx.set_nonblocking(true).is_ok()
+            });
+            Self { pair }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+    fn notify(&self) {
+        #[cfg(unix)]
+        if let Some((_, tx)) = &self.pair {
+            drop(tx.send(&[0]));
+        }
+    }
+    /// Handle readable after the notification, the notifications are
+    /// consumed by the call.
+    fn handle(&self) -> Option<RawHandle> {
+        #[cfg(unix)]
+        if let Some((rx, _)) = &self.pair {
+            while rx.recv(&mut [0; 8]).is_ok() {}
+            return Some(poll::raw_handle(rx));
+        }
+        None
+    }
+}
+
+/// Handle reopening the socket of the endpoint with other parameters at
+/// runtime (e.g. from the control socket), the binding keeps running.
+#[derive(Clone)]
+pub struct SocketReopener {
+    factory: Arc<dyn SocketFactory>,
+    endpoint: Arc<Mutex<Weak<Mutex<Endpoint>>>>,
+    wake: Arc<Wake>,
+}
+
+impl SocketReopener {
+    /// Parameters of the running socket.
+    pub fn params(&self) -> Option<SocketParams> {
+        let endpoint = self.endpoint.lock().unwrap().upgrade()?;
+        Some(endpoint.lock().unwrap().params.clone())
+    }
+    /// Closes the socket and opens it with the parameters merged over the
+    /// current ones. If the socket can't be created with them, it's opened
+    /// with the previous parameters again and the error is returned.
+    /// Returns the parameters of the reopened socket.
+    ///
+    /// The socket is replaced under its lock, so the binding doesn't read
+    /// or write the closed one, and the binding waiting for its readiness
+    /// is woken to release the closed one and to poll the new one. The
+    /// blocking socket is replaced after its pending read is finished.
+    pub fn reopen(&self, layer: SocketParams) -> Result<SocketParams> {
+        let endpoint = self.endpoint.lock().unwrap().upgrade().ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotConnected,
+                "Socket of the endpoint isn't created",
+            )
+        })?;
+        let mut endpoint = endpoint.lock().unwrap();
+        let mut params = endpoint.params.clone();
+        params.merge(layer);
+        let description = endpoint.sock.get_description();
+        // The new socket may need the same resources (e.g. port or device)
+        endpoint.sock.close();
+        self.wake.notify();
+        let res = match endpoint.create(self.factory.as_ref(), &params) {
+            Ok(sock) => {
+                endpoint.sock = sock;
+                endpoint.params = params.clone();
+                tracing::info!("Socket {description} is reopened with {params}");
+                Ok(params)
+            }
+            Err(e) => {
+                let previous = endpoint.params.clone();
+                match endpoint.create(self.factory.as_ref(), &previous) {
+                    Ok(sock) => {
+                        endpoint.sock = sock;
+                        Err(e)
+                    }
+                    Err(err) => Err(Error::new(
+                        err.kind(),
+                        format!("{e}, previous socket isn't restored: {err}"),
+                    )),
+                }
+            }
+        };
+        self.wake.notify();
+        res
+    }
+}
+
+/// Socket, which can be replaced by the reopener. Type name and id are
+/// kept, so the socket is the same one in traces.
+pub struct ReopenSock {
+    endpoint: Arc<Mutex<Endpoint>>,
+    wake: Arc<Wake>,
+    type_name: String,
+    id: u32,
+}
+
+impl SimpleSock for ReopenSock {
+    fn open(&mut self) -> Result<()> {
+        let mut endpoint = self.endpoint.lock().unwrap();
+        endpoint.sock.open()?;
+        endpoint.opened = true;
+        Ok(())
+    }
+    fn close(&mut self) {
+        let mut endpoint = self.endpoint.lock().unwrap();
+        endpoint.sock.close();
+        endpoint.opened = false;
+    }
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        self.endpoint.lock().unwrap().sock.read(data, sz)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.endpoint.lock().unwrap().sock.write(data, sz)
+    }
+}
+
+impl SockBlockCtl for ReopenSock {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        let mut endpoint = self.endpoint.lock().unwrap();
+        endpoint.settings.blocking = Some(is_blocking);
+        endpoint.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        let mut endpoint = self.endpoint.lock().unwrap();
+        endpoint.settings.timeout = Some((read, write));
+        endpoint.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        let mut endpoint = self.endpoint.lock().unwrap();
+        endpoint.settings.grace_period = Some(grace);
+        endpoint.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        let mut endpoint = self.endpoint.lock().unwrap();
+        endpoint.settings.echo = Some(echo);
+        endpoint.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.endpoint.lock().unwrap().sock.flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.endpoint.lock().unwrap().sock.shutdown_write()
+    }
+}
+
+impl SockInfo for ReopenSock {
+    fn get_type_name(&self) -> &str {
+        &self.type_name
+    }
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+    fn get_description(&self) -> String {
+        self.endpoint.lock().unwrap().sock.get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.endpoint.lock().unwrap().sock.rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.endpoint.lock().unwrap().sock.source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.endpoint.lock().unwrap().sock.sink_rate()
+    }
+    fn semantics(&self) -> Semantics {
+        self.endpoint.lock().unwrap().sock.semantics()
+    }
+}
+
+impl SockPoll for ReopenSock {
+    // Handles are taken again by the binding after every wait, the wake
+    // handle interrupts the wait for the replaced socket
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        let mut handles = self.endpoint.lock().unwrap().sock.poll_handles()?;
+        handles.extend(self.wake.handle());
+        Some(handles)
+    }
+}
+
+/// Wraps the factory of the socket type, so the last created socket can be
+/// reopened with other parameters by its reopener.
+pub struct ReopenFactory {
+    factory: Arc<dyn SocketFactory>,
+    endpoint: Arc<Mutex<Weak<Mutex<Endpoint>>>>,
+    wake: Arc<Wake>,
+}
+
+impl ReopenFactory {
+    /// Returns the factory and the reopener of its sockets.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        factory: Box<dyn SocketFactory>,
+    ) -> (Box<dyn SocketFactory>, SocketReopener) {
+        let factory: Arc<dyn SocketFactory> = Arc::from(factory);
+        let endpoint = Arc::new(Mutex::new(Weak::new()));
+        let wake = Arc::new(Wake::new());
+        let reopener = SocketReopener {
+            factory: factory.clone(),
+            endpoint: endpoint.clone(),
+            wake: wake.clone(),
+        };
+        let factory = Self {
+            factory,
+            endpoint,
+            wake,
+        };
+        (Box::new(factory), reopener)
+    }
+}
+
+impl SocketFactory for ReopenFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.factory.create_sock(params.clone())?;
+        let type_name = sock.get_type_name().to_string();
+        let id = sock.get_id();
+        let endpoint = Arc::new(Mutex::new(Endpoint {
+            sock,
+            params,
+            settings: Settings::default(),
+            opened: false,
+        }));
+        *self.endpoint.lock().unwrap() = Arc::downgrade(&endpoint);
+        Ok(Box::new(ReopenSock {
+            endpoint,
+            wake: self.wake.clone(),
+            type_name,
+            id,
+        }))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn crate::sock::SockDocViewer> {
+        self.factory.create_doc_viewer()
+    }
+    fn validate_params(&self, params: &SocketParams) -> Result<()> {
+        self.factory.validate_params(params)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+    use serde_json::json;
+
+    make_simple_sock!(
+        Port {
+            port: u64
+        },
+        "port",
+        self,
+        { format!("port{}", self.port) }
+    );
+
+    impl SimpleSock for Port {
+        fn open(&mut self) -> Result<()> {
+            if self.port == 0 {
+                return Err(Error::from(ErrorKind::InvalidInput));
+            }
+            Ok(())
+        }
+        fn read(&self, _: &mut [u8], _: usize) -> Result<usize> {
+            Ok(0)
+        }
+        fn write(&self, _: &[u8], sz: usize) -> Result<usize> {
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Port {}
+    impl SockPoll for Port {
+        fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+            Some(Vec::new())
+        }
+    }
+
+    struct PortFactory;
+
+    impl SocketFactory for PortFactory {
+        fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+            Ok(Box::new(Port::new(params.value()["port"].as_u64().unwrap())))
+        }
+    }
+
+    #[test]
+    fn reopen_with_params() {
+        let (factory, reopener) = ReopenFactory::new(Box::new(PortFactory));
+        assert!(reopener.reopen(SocketParams::default()).is_err());
+        let mut sock = factory
+            .create_sock(json!({ "port": 1, "baud": 9600 }).into())
+            .unwrap();
+        sock.open().unwrap();
+        assert_eq!(sock.get_description(), "port1");
+
+        let params = reopener.reopen(json!({ "port": 2 }).into()).unwrap();
+        assert_eq!(params.value(), &json!({ "port": 2, "baud": 9600 }));
+        assert_eq!(sock.get_description(), "port2");
+        assert_eq!(sock.write(b"ab", 2).unwrap(), 2);
+
+        // Previous socket is kept, if the new one can't be opened
+        assert!(reopener.reopen(json!({ "port": 0 }).into()).is_err());
+        assert_eq!(sock.get_description(), "port2");
+        assert_eq!(reopener.params().unwrap().value()["port"], 2);
+
+        drop(sock);
+        assert!(reopener.params().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reopen_wakes_binding() {
+        use crate::sock::poll::wait_readable;
+
+        let (factory, reopener) = ReopenFactory::new(Box::new(PortFactory));
+        let sock = factory
+            .create_sock(json!({ "port": 1 }).into())
+            .unwrap();
+        let handles = sock.poll_handles().unwrap();
+        assert!(!wait_readable(&handles, Duration::ZERO).unwrap());
+
+        // Binding waiting for the replaced socket polls the new one
+        reopener.reopen(json!({ "port": 2 }).into()).unwrap();
+        assert!(wait_readable(&handles, Duration::ZERO).unwrap());
+        let handles = sock.poll_handles().unwrap();
+        assert!(!wait_readable(&handles, Duration::ZERO).unwrap());
+    }
+}
@@ -0,0 +1,252 @@
+use super::trace_file::TraceStyle;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::time::{Duration, Instant};
+
+/// Max number of the missing counters remembered to detect reordering.
+const MISSING_WINDOW: usize = 1024;
+
+/// Sequence counter of the frames read from the socket, e.g.
+/// `{ "offset": 2, "width": 4, "interval": 10 }` for 32-bit big-endian
+/// counter after 2 bytes header.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct SequenceConfig {
+    /// Offset of the counter in the frame
+    offset: usize,
+    /// Counter width in bytes (1-8)
+    width: usize,
+    /// Counter byte order (big-endian by default)
+    #[serde(default)]
+    little_endian: bool,
+    /// Print the statistics every N seconds (only on close, if it is not set)
+    interval: Option<u64>,
+}
+
+impl SequenceConfig {
+    fn validate(&self) -> Result<()> {
+        if !(1..=8).contains(&self.width) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Sequence counter width must be 1-8 bytes",
+            ));
+        }
+        Ok(())
+    }
+    /// Returns the counter of the frame, `None` if the frame is too short.
+    fn counter(&self, frame: &[u8]) -> Option<u64> {
+        let field = frame.get(self.offset..self.offset + self.width)?;
+        let mut value = [0u8; 8];
+        if self.little_endian {
+            value[..self.width].copy_from_slice(field);
+            Some(u64::from_le_bytes(value))
+        } else {
+            value[8 - self.width..].copy_from_slice(field);
+            Some(u64::from_be_bytes(value))
+        }
+    }
+}
+
+/// Sequence statistics. Counter wraps around at its width. Late frame is
+/// reordered, if its counter is one of the last missing ones, otherwise
+/// it's a duplicate.
+#[derive(Debug, Default, PartialEq)]
+struct SequenceStats {
+    frames: u64,
+    /// Frames missing in the sequence (reordered ones are excluded)
+    lost: u64,
+    duplicates: u64,
+    reordered: u64,
+    /// Frames too short to contain the counter
+    short: u64,
+}
+
+impl fmt::Display for SequenceStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, {} lost, {} duplicates, {} reordered, {} short",
+            self.frames, self.lost, self.duplicates, self.reordered, self.short
+        )
+    }
+}
+
+struct SequenceTracker {
+    config: SequenceConfig,
+    stats: SequenceStats,
+    /// Expected counter of the next frame
+    next: Option<u64>,
+    missing: VecDeque<u64>,
+}
+
+impl SequenceTracker {
+    fn new(config: SequenceConfig) -> Self {
+        Self {
+            config,
+            stats: SequenceStats::default(),
+            next: None,
+            missing: VecDeque::new(),
+        }
+    }
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - 8 * self.config.width)
+    }
+    fn feed(&mut self, frame: &[u8]) {
+        self.stats.frames += 1;
+        let Some(seq) = self.config.counter(frame) else {
+            self.stats.short += 1;
+            return;
+        };
+        let mask = self.mask();
+        let next = self.next.unwrap_or(seq);
+        let ahead = seq.wrapping_sub(next) & mask;
+        if ahead <= mask / 2 {
+            // Counters skipped by the frame are missing
+            for i in 0..ahead.min(MISSING_WINDOW as u64) {
+                if self.missing.len() == MISSING_WINDOW {
+                    self.missing.pop_front();
+                }
+                self.missing.push_back(next.wrapping_add(i) & mask);
+            }
+            self.stats.lost += ahead;
+            self.next = Some(seq.wrapping_add(1) & mask);
+        } else if let Some(pos) = self.missing.iter().position(|m| *m == seq) {
+            self.missing.remove(pos);
+            self.stats.lost -= 1;
+            self.stats.reordered += 1;
+        } else {
+            self.stats.duplicates += 1;
+        }
+    }
+}
+
+/// Tracks sequence counter of the frames read from the socket: gaps,
+/// duplicates and reordering are printed every interval and on close.
+/// Frame is a datagram of a message socket, stream sockets don't keep
+/// the frames.
+pub struct SequenceDecorator {
+    sock: Box<dyn ComplexSock>,
+    interval: Option<Duration>,
+    tracker: RefCell<SequenceTracker>,
+    last_report: RefCell<Instant>,
+}
+
+impl SequenceDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, config: &SequenceConfig) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            interval: config.interval.map(Duration::from_secs),
+            tracker: RefCell::new(SequenceTracker::new(config.clone())),
+            last_report: RefCell::new(Instant::now()),
+        })
+    }
+    fn report_if_elapsed(&self) {
+        let mut last_report = self.last_report.borrow_mut();
+        if self.interval.is_none_or(|i| last_report.elapsed() < i) {
+            return;
+        }
+        trace_println!(
+            style = TraceStyle::Rx;
+            "Sequence {}: {}",
+            self.sock.get_description(),
+            self.tracker.borrow().stats
+        );
+        *last_report = Instant::now();
+    }
+}
+
+decorator_forward!(SequenceDecorator);
+
+impl SimpleSock for SequenceDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let read = self.sock.read(data, sz)?;
+        if read > 0 {
+            self.tracker.borrow_mut().feed(&data[..read]);
+        }
+        self.report_if_elapsed();
+        Ok(read)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.sock.write(data, sz)
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        trace_println!(
+            style = TraceStyle::Rx;
+            "Sequence {} total: {}",
+            self.sock.get_description(),
+            self.tracker.borrow().stats
+        );
+        self.sock.close();
+    }
+}
+
+pub struct SequenceDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: SequenceConfig,
+}
+
+impl SequenceDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: SequenceConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for SequenceDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        self.config.validate()?;
+        let sock = self.factory.create_sock(params)?;
+        Ok(SequenceDecorator::new(sock, &self.config))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn gaps_duplicates_reordering() {
+        let config = |width, little_endian| SequenceConfig {
+            offset: 1,
+            width,
+            little_endian,
+            interval: None,
+        };
+        assert_eq!(config(2, false).counter(&[0xff, 0x01, 0x02]), Some(0x0102));
+        assert_eq!(config(2, true).counter(&[0xff, 0x01, 0x02]), Some(0x0201));
+        assert_eq!(config(2, true).counter(&[0xff, 0x01]), None);
+        assert!(config(9, false).validate().is_err());
+
+        let mut tracker = SequenceTracker::new(config(1, false));
+        // 3 is late, 5 is a duplicate, 7 is lost, counter wraps at 255
+        for seq in [253u8, 254, 255, 0, 1, 2, 4, 5, 3, 5, 6, 8] {
+            tracker.feed(&[0xaa, seq]);
+        }
+        tracker.feed(&[0xaa]);
+        assert_eq!(
+            tracker.stats,
+            SequenceStats {
+                frames: 13,
+                lost: 1,
+                duplicates: 1,
+                reordered: 1,
+                short: 1,
+            }
+        );
+        assert_eq!(
+            tracker.stats.to_string(),
+            "13 frames, 1 lost, 1 duplicates, 1 reordered, 1 short"
+        );
+    }
+}
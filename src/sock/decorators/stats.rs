@@ -0,0 +1,177 @@
+use super::trace_file::TraceStyle;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use std::cell::RefCell;
+use std::io::{ErrorKind, Result};
+use std::time::{Duration, Instant};
+
+/// Traffic counters of one direction.
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    bytes: u64,
+    msgs: u64,
+    errors: u64,
+}
+
+impl Counters {
+    fn update(&mut self, res: &Result<usize>) {
+        match res {
+            Ok(0) => {}
+            Ok(sz) => {
+                self.bytes += *sz as u64;
+                self.msgs += 1;
+            }
+            // No data is not an error of the socket
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {}
+            Err(_) => self.errors += 1,
+        }
+    }
+    /// Returns rates since the `prev` counters.
+    fn rate_line(&self, prev: &Counters, elapsed: Duration) -> String {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        format!(
+            "{:.0} B/s, {:.1} msg/s, {} errors",
+            (self.bytes - prev.bytes) as f64 / secs,
+            (self.msgs - prev.msgs) as f64 / secs,
+            self.errors - prev.errors
+        )
+    }
+    fn total_line(&self, elapsed: Duration) -> String {
+        format!(
+            "{} bytes, {} msgs, {} errors in {:.1} s ({})",
+            self.bytes,
+            self.msgs,
+            self.errors,
+            elapsed.as_secs_f64(),
+            self.rate_line(&Counters::default(), elapsed)
+        )
+    }
+}
+
+struct StatsState {
+    rx: Counters,
+    tx: Counters,
+    /// Counters at the last periodic report
+    rx_reported: Counters,
+    tx_reported: Counters,
+    start: Instant,
+    last_report: Instant,
+}
+
+/// Counts bytes, messages and errors per direction, prints rolling
+/// throughput every interval and totals on close.
+pub struct StatsDecorator {
+    sock: Box<dyn ComplexSock>,
+    interval: Duration,
+    state: RefCell<StatsState>,
+}
+
+impl StatsDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, interval: &Duration) -> Box<dyn ComplexSock> {
+        let now = Instant::now();
+        Box::new(Self {
+            sock,
+            interval: *interval,
+            state: RefCell::new(StatsState {
+                rx: Counters::default(),
+                tx: Counters::default(),
+                rx_reported: Counters::default(),
+                tx_reported: Counters::default(),
+                start: now,
+                last_report: now,
+            }),
+        })
+    }
+    fn report_if_elapsed(&self) {
+        let mut state = self.state.borrow_mut();
+        let elapsed = state.last_report.elapsed();
+        if elapsed < self.interval {
+            return;
+        }
+        let descr = self.sock.get_description();
+        trace_println!(
+            style = TraceStyle::Rx;
+            "Stats {} rx: {}",
+            descr,
+            state.rx.rate_line(&state.rx_reported, elapsed)
+        );
+        trace_println!(
+            style = TraceStyle::Tx;
+            "Stats {} tx: {}",
+            descr,
+            state.tx.rate_line(&state.tx_reported, elapsed)
+        );
+        state.rx_reported = state.rx;
+        state.tx_reported = state.tx;
+        state.last_report = Instant::now();
+    }
+}
+
+socket_decorator!(StatsDecorator, Duration);
+
+impl SimpleSock for StatsDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let res = self.sock.read(data, sz);
+        self.state.borrow_mut().rx.update(&res);
+        self.report_if_elapsed();
+        res
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let res = self.sock.write(data, sz);
+        self.state.borrow_mut().tx.update(&res);
+        self.report_if_elapsed();
+        res
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        let state = self.state.borrow();
+        let elapsed = state.start.elapsed();
+        let descr = self.sock.get_description();
+        trace_println!(
+            style = TraceStyle::Rx;
+            "Stats {} rx total: {}",
+            descr,
+            state.rx.total_line(elapsed)
+        );
+        trace_println!(
+            style = TraceStyle::Tx;
+            "Stats {} tx total: {}",
+            descr,
+            state.tx.total_line(elapsed)
+        );
+        drop(state);
+        self.sock.close();
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::io::Error;
+
+    #[test]
+    fn counters_rates() {
+        let mut counters = Counters::default();
+        counters.update(&Ok(100));
+        counters.update(&Ok(0));
+        counters.update(&Err(Error::from(ErrorKind::WouldBlock)));
+        counters.update(&Err(Error::from(ErrorKind::ConnectionReset)));
+        let prev = counters;
+        counters.update(&Ok(300));
+        counters.update(&Ok(100));
+        assert_eq!(
+            counters.rate_line(&prev, Duration::from_secs(2)),
+            "200 B/s, 1.0 msg/s, 0 errors"
+        );
+        assert_eq!(
+            counters.total_line(Duration::from_secs(5)),
+            "500 bytes, 3 msgs, 1 errors in 5.0 s (100 B/s, 0.6 msg/s, 1 errors)"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use super::trace_file::TraceStyle;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Result;
+use std::time::{Duration, Instant};
+
+/// Number of first frame bytes used to find the most common prefix.
+const PREFIX_LEN: usize = 4;
+/// Max number of distinct prefixes counted per interval, frames of the next
+/// ones are counted as other.
+const MAX_PREFIXES: usize = 256;
+
+#[derive(Default)]
+struct Aggregate {
+    bytes: u64,
+    frames: u64,
+    min: usize,
+    max: usize,
+    prefixes: HashMap<Vec<u8>, u64>,
+    /// Frames with the prefixes over the limit
+    other: u64,
+}
+
+impl Aggregate {
+    fn update(&mut self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if self.frames == 0 || data.len() < self.min {
+            self.min = data.len();
+        }
+        self.max = self.max.max(data.len());
+        self.bytes += data.len() as u64;
+        self.frames += 1;
+        let prefix = &data[..data.len().min(PREFIX_LEN)];
+        if let Some(count) = self.prefixes.get_mut(prefix) {
+            *count += 1;
+        } else if self.prefixes.len() < MAX_PREFIXES {
+            self.prefixes.insert(prefix.to_vec(), 1);
+        } else {
+            self.other += 1;
+        }
+    }
+    /// Returns one line summary and resets the aggregate. Nothing is returned,
+    /// if there was no traffic.
+    fn take_line(&mut self) -> Option<String> {
+        if self.frames == 0 {
+            return None;
+        }
+        let (prefix, count) = self
+            .prefixes
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(prefix, count)| (hex::encode(prefix), *count))
+            .unwrap_or_default();
+        let mut line = format!(
+            "{} bytes, {} frames, size {}..{}, top prefix {} ({} frames)",
+            self.bytes, self.frames, self.min, self.max, prefix, count
+        );
+        if self.other > 0 {
+            line.push_str(&format!(", other prefixes ({} frames)", self.other));
+        }
+        *self = Self::default();
+        Some(line)
+    }
+}
+
+struct SummaryState {
+    rx: Aggregate,
+    tx: Aggregate,
+    last_report: Instant,
+}
+
+/// Prints one aggregate line per direction and interval instead of
+/// tracing every chunk.
+pub struct SummaryDecorator {
+    sock: Box<dyn ComplexSock>,
+    interval: Duration,
+    state: RefCell<SummaryState>,
+}
+
+impl SummaryDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, interval: &Duration) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            interval: *interval,
+            state: RefCell::new(SummaryState {
+                rx: Aggregate::default(),
+                tx: Aggregate::default(),
+                last_report: Instant::now(),
+            }),
+        })
+    }
+    fn report(&self, state: &mut SummaryState) {
+        let descr = self.sock.get_description();
+        if let Some(line) = state.rx.take_line() {
+            trace_println!(style = TraceStyle::Rx; "Summary {} rx: {}", descr, line);
+        }
+        if let Some(line) = state.tx.take_line() {
+            trace_println!(style = TraceStyle::Tx; "Summary {} tx: {}", descr, line);
+        }
+        state.last_report = Instant::now();
+    }
+    fn report_if_elapsed(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.last_report.elapsed() >= self.interval {
+            self.report(&mut state);
+        }
+    }
+}
+
+socket_decorator!(SummaryDecorator, Duration);
+
+impl SimpleSock for SummaryDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let res = self.sock.read(data, sz);
+        if let Ok(sz) = res {
+            self.state.borrow_mut().rx.update(&data[..sz]);
+        }
+        self.report_if_elapsed();
+        res
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let res = self.sock.write(data, sz);
+        if let Ok(sz) = res {
+            self.state.borrow_mut().tx.update(&data[..sz]);
+        }
+        self.report_if_elapsed();
+        res
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        // Flush the last incomplete interval
+        self.report(&mut self.state.borrow_mut());
+        self.sock.close();
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn aggregate_line() {
+        let mut agg = Aggregate::default();
+        assert!(agg.take_line().is_none());
+        agg.update(b"$GPRMC,1");
+        agg.update(b"$GPGGA,22");
+        agg.update(b"$GPRMC,333");
+        assert_eq!(
+            agg.take_line().unwrap(),
+            "27 bytes, 3 frames, size 8..10, top prefix 24475052 (2 frames)"
+        );
+        // Aggregate is reset after the report
+        assert!(agg.take_line().is_none());
+    }
+    #[test]
+    fn prefixes_limit() {
+        let mut agg = Aggregate::default();
+        for i in 0..MAX_PREFIXES as u32 + 10 {
+            agg.update(&i.to_be_bytes());
+        }
+        agg.update(&[0; 4]);
+        assert_eq!(agg.prefixes.len(), MAX_PREFIXES);
+        assert_eq!(agg.other, 10);
+        assert_eq!(
+            agg.take_line().unwrap(),
+            "1068 bytes, 267 frames, size 4..4, top prefix 00000000 (2 frames), \
+             other prefixes (10 frames)"
+        );
+    }
+}
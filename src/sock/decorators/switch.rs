@@ -0,0 +1,270 @@
+use super::{DecoratorSpec, TraceSide};
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Runtime switch of one decorator of the stack.
+#[derive(Clone)]
+pub struct DecoratorSwitch {
+    name: String,
+    enabled: Arc<AtomicBool>,
+}
+
+impl DecoratorSwitch {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+    /// Turns the decorator on or off, returns the new state.
+    pub fn toggle(&self) -> bool {
+        !self.enabled.fetch_xor(true, Ordering::Relaxed)
+    }
+}
+
+/// Socket shared by the decorator and its bypass. Type name and id are
+/// copied, since they can't be borrowed through the lock.
+#[derive(Clone)]
+struct SharedSock {
+    sock: Arc<Mutex<Box<dyn ComplexSock>>>,
+    type_name: String,
+    id: u32,
+}
+
+impl SharedSock {
+    fn new(sock: Box<dyn ComplexSock>) -> Self {
+        Self {
+            type_name: sock.get_type_name().to_string(),
+            id: sock.get_id(),
+            sock: Arc::new(Mutex::new(sock)),
+        }
+    }
+}
+
+impl SimpleSock for SharedSock {
+    fn open(&mut self) -> Result<()> {
+        self.sock.lock().unwrap().open()
+    }
+    fn close(&mut self) {
+        self.sock.lock().unwrap().close();
+    }
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        self.sock.lock().unwrap().read(data, sz)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.sock.lock().unwrap().write(data, sz)
+    }
+}
+
+impl SockBlockCtl for SharedSock {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.sock.lock().unwrap().set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.sock.lock().unwrap().set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        self.sock.lock().unwrap().set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.lock().unwrap().set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.sock.lock().unwrap().flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.sock.lock().unwrap().shutdown_write()
+    }
+}
+
+impl SockInfo for SharedSock {
+    fn get_type_name(&self) -> &str {
+        &self.type_name
+    }
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+    fn get_description(&self) -> String {
+        self.sock.lock().unwrap().get_description()
+    }
+    fn rx_timestamp(&self) -> Option<std::time::SystemTime> {
+        self.sock.lock().unwrap().rx_timestamp()
+    }
+    fn source_rate(&self) -> Option<f64> {
+        self.sock.lock().unwrap().source_rate()
+    }
+    fn sink_rate(&self) -> Option<f64> {
+        self.sock.lock().unwrap().sink_rate()
+    }
+    fn semantics(&self) -> crate::sock::Semantics {
+        self.sock.lock().unwrap().semantics()
+    }
+}
+
+impl SockPoll for SharedSock {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        self.sock.lock().unwrap().poll_handles()
+    }
+}
+
+/// Factory handing the prepared shared socket to the decorator factory.
+struct SlotFactory(Arc<Mutex<Option<SharedSock>>>);
+
+impl SocketFactory for SlotFactory {
+    fn create_sock(&self, _: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.0.lock().unwrap().take();
+        sock.map(|sock| Box::new(sock) as Box<dyn ComplexSock>)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Switched socket isn't prepared"))
+    }
+}
+
+/// Passes data through the decorator while it is enabled, and straight to
+/// the decorated socket otherwise. Data held by a stateful decorator (e.g.
+/// incomplete chunk) may be lost when it is switched.
+pub struct SwitchDecorator {
+    sock: Box<dyn ComplexSock>,
+    bypass: SharedSock,
+    enabled: Arc<AtomicBool>,
+}
+
+impl SwitchDecorator {
+    fn active(&self) -> &dyn ComplexSock {
+        if self.enabled.load(Ordering::Relaxed) {
+            self.sock.as_ref()
+        } else {
+            &self.bypass
+        }
+    }
+}
+
+impl SimpleSock for SwitchDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        self.active().read(data, sz)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        self.active().write(data, sz)
+    }
+    decorator_openclose_default!();
+}
+
+impl SockBlockCtl for SwitchDecorator {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        self.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.active().flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.active().shutdown_write()
+    }
+}
+
+decorator_forward_info!(SwitchDecorator);
+
+/// Wraps the factory with the decorator, which can be bypassed at runtime.
+pub struct SwitchDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    decorator: Box<dyn SocketFactory>,
+    slot: Arc<Mutex<Option<SharedSock>>>,
+    enabled: Arc<AtomicBool>,
+}
+
+impl SwitchDecoratorFactory {
+    /// Returns the factory and the switch of its decorator.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        factory: Box<dyn SocketFactory>,
+        spec: &DecoratorSpec,
+        side: TraceSide,
+    ) -> Result<(Box<dyn SocketFactory>, DecoratorSwitch)> {
+        let slot = Arc::new(Mutex::new(None));
+        let decorator = spec.build(Box::new(SlotFactory(slot.clone())), side)?;
+        let switch = DecoratorSwitch {
+            name: spec.name().to_string(),
+            enabled: Arc::new(AtomicBool::new(true)),
+        };
+        let factory = Box::new(Self {
+            factory,
+            decorator,
+            slot,
+            enabled: switch.enabled.clone(),
+        });
+        Ok((factory, switch))
+    }
+}
+
+impl SocketFactory for SwitchDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let bypass = SharedSock::new(self.factory.create_sock(params.clone())?);
+        *self.slot.lock().unwrap() = Some(bypass.clone());
+        let sock = self.decorator.create_sock(params)?;
+        Ok(Box::new(SwitchDecorator {
+            sock,
+            bypass,
+            enabled: self.enabled.clone(),
+        }))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+
+    make_simple_sock!(Sink { tx: Arc<Mutex<Vec<u8>>> }, "sink");
+
+    impl SimpleSock for Sink {
+        fn read(&self, _: &mut [u8], _: usize) -> Result<usize> {
+            Ok(0)
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            self.tx.lock().unwrap().extend(&data[..sz]);
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Sink {}
+    impl SockPoll for Sink {}
+
+    struct SinkFactory(Arc<Mutex<Vec<u8>>>);
+
+    impl SocketFactory for SinkFactory {
+        fn create_sock(&self, _: SocketParams) -> Result<Box<dyn ComplexSock>> {
+            Ok(Box::new(Sink::new(self.0.clone())))
+        }
+    }
+
+    #[test]
+    fn bypass_switched_off_decorator() {
+        let tx = Arc::<Mutex<Vec<u8>>>::default();
+        let spec = DecoratorSpec::parse_short("byte-swap:2").unwrap();
+        let (factory, switch) =
+            SwitchDecoratorFactory::new(Box::new(SinkFactory(tx.clone())), &spec, TraceSide::To)
+                .unwrap();
+        assert_eq!(switch.name(), "byte_swap");
+        let mut sock = factory.create_sock(SocketParams::default()).unwrap();
+        sock.open().unwrap();
+        sock.write(b"abcd", 4).unwrap();
+        assert!(!switch.toggle());
+        sock.write(b"abcd", 4).unwrap();
+        assert!(switch.toggle() && switch.is_enabled());
+        sock.write(b"ab", 2).unwrap();
+        assert_eq!(&tx.lock().unwrap()[..], b"badcabcdba");
+        assert_eq!(sock.get_type_name(), "sink");
+    }
+}
@@ -0,0 +1,38 @@
+use super::transform::{Transform, TransformBuilder};
+use std::io::Result;
+use std::sync::Arc;
+
+/// Prepends the tag to every chunk read from the socket, so the consumer
+/// of merged streams can tell the sources apart. Written data is passed
+/// as is.
+struct Tag {
+    tag: Arc<[u8]>,
+}
+
+impl Transform for Tag {
+    fn on_read(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok([&self.tag[..], data].concat())
+    }
+    fn on_write(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+}
+
+/// Creates transform builder tagging the read chunks.
+pub fn tag_builder(tag: &[u8]) -> TransformBuilder {
+    let tag: Arc<[u8]> = tag.into();
+    Arc::new(move || Ok(Box::new(Tag { tag: tag.clone() }) as Box<dyn Transform>))
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn tag_read_chunks() {
+        let mut tag = tag_builder(b"gps: ")().unwrap();
+        assert_eq!(tag.on_read(b"$GPRMC").unwrap(), b"gps: $GPRMC");
+        assert_eq!(tag.on_write(b"cmd").unwrap(), b"cmd");
+    }
+}
@@ -0,0 +1,98 @@
+use super::monitor::Direction;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use std::collections::VecDeque;
+use std::io::Result;
+use std::sync::{Arc, Mutex};
+
+/// Last bytes read from and written to the socket, shared with the viewer
+/// (e.g. hexdump panes of the monitor UI).
+pub struct TrafficTap {
+    capacity: usize,
+    rx: Mutex<VecDeque<u8>>,
+    tx: Mutex<VecDeque<u8>>,
+}
+
+impl TrafficTap {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            rx: Mutex::new(VecDeque::with_capacity(capacity)),
+            tx: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+    fn data(&self, dir: Direction) -> &Mutex<VecDeque<u8>> {
+        match dir {
+            Direction::Rx => &self.rx,
+            Direction::Tx => &self.tx,
+        }
+    }
+    /// Appends the chunk, the oldest bytes are dropped to fit the capacity.
+    fn push(&self, dir: Direction, chunk: &[u8]) {
+        let chunk = &chunk[chunk.len().saturating_sub(self.capacity)..];
+        let mut data = self.data(dir).lock().unwrap();
+        let excess = (data.len() + chunk.len()).saturating_sub(self.capacity);
+        data.drain(..excess);
+        data.extend(chunk);
+    }
+    /// Returns the kept bytes of the direction.
+    pub fn snapshot(&self, dir: Direction) -> Vec<u8> {
+        self.data(dir).lock().unwrap().iter().copied().collect()
+    }
+    pub fn clear(&self) {
+        self.rx.lock().unwrap().clear();
+        self.tx.lock().unwrap().clear();
+    }
+}
+
+/// Copies chunks read from and written to the socket to the tap.
+pub struct TapDecorator {
+    sock: Box<dyn ComplexSock>,
+    tap: Arc<TrafficTap>,
+}
+
+impl TapDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, tap: &Arc<TrafficTap>) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            tap: tap.clone(),
+        })
+    }
+}
+
+socket_decorator!(TapDecorator, Arc<TrafficTap>);
+
+impl SimpleSock for TapDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let read = self.sock.read(data, sz)?;
+        self.tap.push(Direction::Rx, &data[..read]);
+        Ok(read)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let written = self.sock.write(data, sz)?;
+        self.tap.push(Direction::Tx, &data[..written]);
+        Ok(written)
+    }
+    decorator_openclose_default!();
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn keep_last_bytes() {
+        let tap = TrafficTap::new(4);
+        tap.push(Direction::Rx, b"ab");
+        tap.push(Direction::Rx, b"cde");
+        tap.push(Direction::Tx, b"0123456");
+        assert_eq!(tap.snapshot(Direction::Rx), b"bcde");
+        assert_eq!(tap.snapshot(Direction::Tx), b"3456");
+        tap.clear();
+        assert!(tap.snapshot(Direction::Rx).is_empty());
+    }
+}
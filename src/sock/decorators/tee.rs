@@ -0,0 +1,218 @@
+use super::monitor::Direction;
+use crate::error;
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll, SocketFactory,
+    SocketParams,
+};
+use crate::sockets::registry::SocketRegistry;
+use serde::Deserialize;
+use std::cell::Cell;
+use std::io::Result;
+use std::time::Duration;
+
+/// Sink socket receiving a copy of the socket data, e.g.
+/// `{ "type": "udp", "params": { "ip_dst": "127.0.0.1", "port_dst": 9000 } }`.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct TeeConfig {
+    /// Socket type of the sink
+    #[serde(rename = "type")]
+    ty: String,
+    /// Sink socket parameters
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    params: serde_json::Value,
+    /// Direction of the copied data (both directions, if it is not set)
+    dir: Option<Direction>,
+}
+
+impl TeeConfig {
+    /// Parameters of the sink socket.
+    pub fn sink_params(&self) -> SocketParams {
+        SocketParams::new(self.params.clone())
+    }
+    fn create_sink(&self) -> Result<Box<dyn ComplexSock>> {
+        let factory = SocketRegistry::global()
+            .create(&self.ty)
+            .ok_or_else(|| error::Error::UnknownType(self.ty.to_string()))?;
+        // Slow sink must not hold the binding, so it isn't blocking
+        factory.create_sock_blockctl(self.sink_params(), false)
+    }
+}
+
+/// Copies chunks read from and written to the socket to the sink. Sink
+/// doesn't affect the binding: data it doesn't accept is dropped and
+/// counted, failed sink is reported once.
+pub struct TeeDecorator {
+    sock: Box<dyn ComplexSock>,
+    sink: Box<dyn ComplexSock>,
+    dir: Option<Direction>,
+    dropped: Cell<u64>,
+    failed: Cell<bool>,
+}
+
+impl TeeDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        sock: Box<dyn ComplexSock>,
+        sink: Box<dyn ComplexSock>,
+        dir: Option<Direction>,
+    ) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            sink,
+            dir,
+            dropped: Cell::new(0),
+            failed: Cell::new(false),
+        })
+    }
+    fn copy(&self, dir: Direction, chunk: &[u8]) {
+        if chunk.is_empty() || self.dir.is_some_and(|d| d != dir) {
+            return;
+        }
+        let res = self
+            .sink
+            .write(chunk, chunk.len())
+            .and_then(|written| self.sink.flush_message().map(|_| written));
+        let written = match res {
+            Ok(written) => written,
+            Err(e) => {
+                if !self.failed.replace(true) {
+                    tracing::warn!("Tee sink {} write failed: {e}", self.sink.get_description());
+                }
+                0
+            }
+        };
+        self.dropped
+            .set(self.dropped.get() + (chunk.len() - written) as u64);
+    }
+}
+
+impl SimpleSock for TeeDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let read = self.sock.read(data, sz)?;
+        self.copy(Direction::Rx, &data[..read]);
+        Ok(read)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        let written = self.sock.write(data, sz)?;
+        self.copy(Direction::Tx, &data[..written]);
+        Ok(written)
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()?;
+        self.sink.open()
+    }
+    fn close(&mut self) {
+        if self.dropped.get() > 0 {
+            tracing::warn!(
+                "Tee sink {} dropped {} bytes",
+                self.sink.get_description(),
+                self.dropped.get()
+            );
+        }
+        self.sink.close();
+        self.sock.close();
+    }
+}
+
+impl SockBlockCtl for TeeDecorator {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        self.sink.set_grace_period(grace)?;
+        self.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        self.sock.flush_message()
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.sock.shutdown_write()
+    }
+}
+
+decorator_forward_info!(TeeDecorator);
+
+pub struct TeeDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    config: TeeConfig,
+}
+
+impl TeeDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(factory: Box<dyn SocketFactory>, config: TeeConfig) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, config })
+    }
+}
+
+impl SocketFactory for TeeDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sink = self.config.create_sink()?;
+        let sock = self.factory.create_sock(params)?;
+        Ok(TeeDecorator::new(sock, sink, self.config.dir))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+    use std::sync::{Arc, Mutex};
+
+    make_simple_sock!(Queue {
+        frames: Arc<Mutex<Vec<Vec<u8>>>>,
+    }, "queue");
+
+    impl SimpleSock for Queue {
+        fn read(&self, data: &mut [u8], _: usize) -> Result<usize> {
+            let mut frames = self.frames.lock().unwrap();
+            if frames.is_empty() {
+                return Ok(0);
+            }
+            let frame = frames.remove(0);
+            data[..frame.len()].copy_from_slice(&frame);
+            Ok(frame.len())
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            // Queue accepts up to 4 bytes at once
+            let sz = sz.min(4);
+            self.frames.lock().unwrap().push(data[..sz].to_vec());
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Queue {}
+    impl SockPoll for Queue {}
+
+    #[test]
+    fn copy_to_sink() {
+        let source = Arc::new(Mutex::new(vec![b"rx".to_vec()]));
+        let copies = Arc::<Mutex<Vec<Vec<u8>>>>::default();
+        let tee = TeeDecorator::new(
+            Box::new(Queue::new(Arc::clone(&source))),
+            Box::new(Queue::new(Arc::clone(&copies))),
+            None,
+        );
+        let mut buf = [0u8; 16];
+        assert_eq!(tee.read(&mut buf, 16).unwrap(), 2);
+        assert_eq!(tee.read(&mut buf, 16).unwrap(), 0);
+        assert_eq!(tee.write(b"tx", 2).unwrap(), 2);
+        assert_eq!(*copies.lock().unwrap(), [b"rx".to_vec(), b"tx".to_vec()]);
+
+        let copies = Arc::<Mutex<Vec<Vec<u8>>>>::default();
+        let tee = TeeDecorator::new(
+            Box::new(Queue::new(Arc::clone(&source))),
+            Box::new(Queue::new(Arc::clone(&copies))),
+            Some(Direction::Tx),
+        );
+        // Written chunk is copied, but the sink accepts only a part of it
+        assert_eq!(tee.write(b"written", 7).unwrap(), 4);
+        assert_eq!(*copies.lock().unwrap(), [b"writ".to_vec()]);
+    }
+}
@@ -0,0 +1,285 @@
+use derive_builder::Builder;
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Trace file used instead of STDOUT, if it's set.
+static TRACE_FILE: Mutex<Option<TraceFile>> = Mutex::new(None);
+/// Timestamp format of the trace lines and the tracing start.
+static TRACE_TS: OnceLock<(TraceTimestamp, Instant)> = OnceLock::new();
+static TRACE_COLOR: OnceLock<TraceColor> = OnceLock::new();
+/// STDOUT is a terminal and colors aren't disabled with `NO_COLOR`.
+static STDOUT_COLORS: OnceLock<bool> = OnceLock::new();
+
+/// Timestamp of the trace line header.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum TraceTimestamp {
+    /// Seconds since the tracing start (monotonic clock)
+    Mono,
+    /// UTC time, e.g. 2024-05-01T12:30:00.000123Z
+    Iso8601,
+}
+
+/// Colors of the trace output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum TraceColor {
+    /// Colors if STDOUT is a terminal
+    #[default]
+    Auto,
+    /// Colors even in the trace file
+    Always,
+    Never,
+}
+
+/// Style of the trace line: received and written data have different
+/// colors, errors are red.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceStyle {
+    Plain,
+    Rx,
+    Tx,
+    Error,
+}
+
+impl TraceStyle {
+    fn ansi_color(self) -> Option<&'static str> {
+        match self {
+            Self::Plain => None,
+            Self::Rx => Some("\x1b[32m"),
+            Self::Tx => Some("\x1b[36m"),
+            Self::Error => Some("\x1b[31m"),
+        }
+    }
+    fn format(self, args: fmt::Arguments, colors: bool) -> String {
+        match self.ansi_color() {
+            Some(color) if colors => format!("{color}{args}\x1b[0m"),
+            _ => args.to_string(),
+        }
+    }
+}
+
+/// Trace file with size and time based rotation. Rotated files get the
+/// number suffix (`trace.log.1` is the newest one).
+#[derive(Builder, Clone, Debug)]
+pub struct TraceFileConfig {
+    path: PathBuf,
+    /// Rotate when the file would exceed the size (bytes)
+    #[builder(default)]
+    max_size: Option<u64>,
+    /// Rotate when the file is older than the interval
+    #[builder(default)]
+    max_age: Option<Duration>,
+    /// Number of rotated files to keep
+    #[builder(default = "5")]
+    keep: usize,
+}
+
+struct TraceFile {
+    config: TraceFileConfig,
+    file: File,
+    size: u64,
+    opened: Instant,
+}
+
+impl TraceFile {
+    /// Appends to the existing file, its size counts for rotation.
+    fn open(config: TraceFileConfig) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            config,
+            file,
+            size,
+            opened: Instant::now(),
+        })
+    }
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.config.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+    fn rotate(&mut self) -> io::Result<()> {
+        let ignore_missing = |res: io::Result<()>| match res {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            res => res,
+        };
+        if self.config.keep == 0 {
+            ignore_missing(fs::remove_file(&self.config.path))?;
+        } else {
+            for n in (1..self.config.keep).rev() {
+                ignore_missing(fs::rename(self.rotated_path(n), self.rotated_path(n + 1)))?;
+            }
+            fs::rename(&self.config.path, self.rotated_path(1))?;
+        }
+        self.file = File::create(&self.config.path)?;
+        self.size = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        let too_big = self
+            .config
+            .max_size
+            .is_some_and(|max| self.size > 0 && self.size + len > max);
+        let too_old = self
+            .config
+            .max_age
+            .is_some_and(|age| self.opened.elapsed() >= age);
+        if too_big || too_old {
+            self.rotate()?;
+        }
+        // Line is written at once, so it isn't split between the files
+        self.file.write_all(format!("{line}\n").as_bytes())?;
+        self.size += len;
+        Ok(())
+    }
+}
+
+/// Redirects output of the tracing decorators to the file.
+pub fn set_trace_file(config: TraceFileConfig) -> io::Result<()> {
+    *TRACE_FILE.lock().unwrap() = Some(TraceFile::open(config)?);
+    Ok(())
+}
+
+/// Overrides the automatic colors of the trace output.
+pub fn set_trace_color(color: TraceColor) {
+    let _ = TRACE_COLOR.set(color);
+}
+
+fn use_colors(to_file: bool) -> bool {
+    match TRACE_COLOR.get().copied().unwrap_or_default() {
+        TraceColor::Auto => {
+            !to_file
+                && *STDOUT_COLORS
+                    .get_or_init(|| io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none())
+        }
+        TraceColor::Always => true,
+        TraceColor::Never => false,
+    }
+}
+
+/// Prefixes trace lines with the timestamp, socket events also get the
+/// binding direction and the socket description.
+pub fn set_trace_timestamp(ts: TraceTimestamp) {
+    let _ = TRACE_TS.set((ts, Instant::now()));
+}
+
+fn format_iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let day_secs = secs % 86400;
+    // Civil date of the day number (proleptic Gregorian calendar)
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        day_secs / 3600,
+        day_secs / 60 % 60,
+        day_secs % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+fn timestamp() -> Option<String> {
+    let (ts, start) = TRACE_TS.get()?;
+    Some(match ts {
+        TraceTimestamp::Mono => {
+            let elapsed = start.elapsed();
+            format!("{}.{:06}", elapsed.as_secs(), elapsed.subsec_micros())
+        }
+        TraceTimestamp::Iso8601 => format_iso8601(SystemTime::now()),
+    })
+}
+
+/// Prints the trace line (with the timestamp, if it's set).
+pub fn trace_line(style: TraceStyle, args: fmt::Arguments) {
+    match timestamp() {
+        Some(ts) => output(style, format_args!("{ts} {args}")),
+        None => output(style, args),
+    }
+}
+
+/// Prints the trace line of the socket event. With the timestamp the line
+/// starts with the `<time> <direction> <socket>:` header.
+pub fn trace_event(style: TraceStyle, direction: &str, descr: &str, args: fmt::Arguments) {
+    match timestamp() {
+        Some(ts) => output(style, format_args!("{ts} {direction} {descr}: {args}")),
+        None => output(style, args),
+    }
+}
+
+/// Writes the line to the trace file or STDOUT.
+fn output(style: TraceStyle, args: fmt::Arguments) {
+    let mut trace_file = TRACE_FILE.lock().unwrap();
+    let line = style.format(args, use_colors(trace_file.is_some()));
+    match trace_file.as_mut() {
+        Some(file) => {
+            if let Err(e) = file.write_line(&line) {
+                tracing::warn!("Trace file writing failed: {e}");
+            }
+        }
+        None => println!("{line}"),
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn rotate_by_size() {
+        let dir = std::env::temp_dir().join(format!("trace-rotation-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = TraceFileConfigBuilder::default()
+            .path(dir.join("trace.log"))
+            .max_size(Some(10))
+            .keep(2)
+            .build()
+            .unwrap();
+        let mut file = TraceFile::open(config).unwrap();
+        for line in ["one", "two", "three", "four", "five", "six"] {
+            file.write_line(line).unwrap();
+        }
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("trace.log"), "six\n");
+        assert_eq!(read("trace.log.1"), "four\nfive\n");
+        // The oldest file ("one", "two") is overwritten
+        assert_eq!(read("trace.log.2"), "three\n");
+        assert!(!dir.join("trace.log.3").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn iso8601_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_042);
+        assert_eq!(format_iso8601(time), "2023-11-14T22:13:20.000042Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_868_799);
+        assert_eq!(format_iso8601(leap_day), "2000-02-29T23:59:59.000000Z");
+    }
+
+    #[test]
+    fn colored_lines() {
+        let line = |style: TraceStyle, colors| style.format(format_args!("data"), colors);
+        assert_eq!(line(TraceStyle::Rx, true), "\x1b[32mdata\x1b[0m");
+        assert_eq!(line(TraceStyle::Tx, true), "\x1b[36mdata\x1b[0m");
+        assert_eq!(line(TraceStyle::Error, true), "\x1b[31mdata\x1b[0m");
+        assert_eq!(line(TraceStyle::Plain, true), "data");
+        assert_eq!(line(TraceStyle::Error, false), "data");
+    }
+}
@@ -0,0 +1,225 @@
+use crate::sock::{
+    ComplexSock, DEFAULT_GRACE_PERIOD, RawHandle, SimpleSock, SockBlockCtl, SockInfo, SockPoll,
+    SocketFactory, SocketParams, poll,
+};
+use std::cell::{Cell, RefCell};
+use std::io::Result;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Transformation of the socket byte stream. Output may differ in size
+/// from input, e.g. be empty while the transform accumulates data.
+pub trait Transform: Send {
+    /// Transforms data received from the socket.
+    fn on_read(&mut self, data: &[u8]) -> Result<Vec<u8>>;
+    /// Transforms data before it is written to the socket.
+    fn on_write(&mut self, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Creates transform state for every decorated socket.
+pub type TransformBuilder = Arc<dyn Fn() -> Result<Box<dyn Transform>> + Send + Sync>;
+
+/// Applies the transform to all data passing through the socket.
+pub struct TransformDecorator {
+    sock: Box<dyn ComplexSock>,
+    transform: RefCell<Box<dyn Transform>>,
+    /// Transformed received data, which didn't fit the read buffer
+    rx_pending: RefCell<Vec<u8>>,
+    /// Transformed data, which wasn't accepted by the socket yet
+    tx_pending: RefCell<Vec<u8>>,
+    /// Message is finished, but its transformed data isn't written yet
+    flush_due: Cell<bool>,
+    grace_period: Duration,
+}
+
+impl TransformDecorator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(sock: Box<dyn ComplexSock>, transform: Box<dyn Transform>) -> Box<dyn ComplexSock> {
+        Box::new(Self {
+            sock,
+            transform: RefCell::new(transform),
+            rx_pending: RefCell::new(Vec::new()),
+            tx_pending: RefCell::new(Vec::new()),
+            flush_due: Cell::new(false),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        })
+    }
+    /// Writes pending transformed data. Returns `true` if all of it is
+    /// written.
+    fn flush_pending(&self) -> Result<bool> {
+        let mut pending = self.tx_pending.borrow_mut();
+        while !pending.is_empty() {
+            let written = self.sock.write(&pending, pending.len())?;
+            if written == 0 {
+                return Ok(false);
+            }
+            pending.drain(..written);
+        }
+        if self.flush_due.take() {
+            self.sock.flush_message()?;
+        }
+        Ok(true)
+    }
+}
+
+impl SimpleSock for TransformDecorator {
+    fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+        let mut pending = self.rx_pending.borrow_mut();
+        if pending.is_empty() {
+            let read = self.sock.read(data, sz)?;
+            if read == 0 {
+                return Ok(0);
+            }
+            *pending = self.transform.borrow_mut().on_read(&data[..read])?;
+        }
+        // Pending data is returned first, so the caller never waits for the
+        // socket readiness while it has data
+        let len = pending.len().min(sz);
+        data[..len].copy_from_slice(&pending[..len]);
+        pending.drain(..len);
+        Ok(len)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+        // New data isn't accepted until the previous one is written, so the
+        // pending data is bounded by one transformed chunk
+        if !self.flush_pending()? {
+            return Ok(0);
+        }
+        let out = self.transform.borrow_mut().on_write(&data[..sz])?;
+        *self.tx_pending.borrow_mut() = out;
+        self.flush_pending()?;
+        Ok(sz)
+    }
+    fn open(&mut self) -> Result<()> {
+        self.sock.open()
+    }
+    fn close(&mut self) {
+        let deadline = Instant::now() + self.grace_period;
+        loop {
+            match self.flush_pending() {
+                Ok(false) if Instant::now() < deadline => thread::sleep(poll::FALLBACK_SLEEP),
+                Ok(false) => {
+                    tracing::warn!(
+                        "Transformed data of {} is dropped on close",
+                        self.sock.get_description()
+                    );
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.sock.close();
+    }
+}
+
+impl SockBlockCtl for TransformDecorator {
+    fn set_block(&mut self, is_blocking: bool) -> Result<()> {
+        self.sock.set_block(is_blocking)
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.sock.set_timeout(read, write)
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> Result<()> {
+        self.grace_period = grace;
+        self.sock.set_grace_period(grace)
+    }
+    fn set_echo(&mut self, echo: bool) -> Result<()> {
+        self.sock.set_echo(echo)
+    }
+    fn flush_message(&self) -> Result<()> {
+        // Message is finished on the socket with its last transformed byte
+        if self.tx_pending.borrow().is_empty() {
+            return self.sock.flush_message();
+        }
+        self.flush_due.set(true);
+        Ok(())
+    }
+    fn shutdown_write(&self) -> Result<()> {
+        self.sock.shutdown_write()
+    }
+}
+
+decorator_forward_info!(TransformDecorator);
+
+pub struct TransformDecoratorFactory {
+    factory: Box<dyn SocketFactory>,
+    builder: TransformBuilder,
+}
+
+impl TransformDecoratorFactory {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        factory: Box<dyn SocketFactory>,
+        builder: TransformBuilder,
+    ) -> Box<dyn SocketFactory> {
+        Box::new(Self { factory, builder })
+    }
+}
+
+impl SocketFactory for TransformDecoratorFactory {
+    fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+        let sock = self.factory.create_sock(params)?;
+        Ok(TransformDecorator::new(sock, (self.builder)()?))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sock::make_simple_sock;
+    use std::sync::Mutex;
+
+    make_simple_sock!(Loopback {
+        data: Mutex<Vec<u8>>,
+        accept: Mutex<usize>,
+    }, "loopback");
+
+    impl SimpleSock for Loopback {
+        fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+            let mut stored = self.data.lock().unwrap();
+            let len = stored.len().min(sz);
+            data[..len].copy_from_slice(&stored[..len]);
+            stored.drain(..len);
+            Ok(len)
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            // Accepts limited amount of data to emulate a slow sink
+            let mut accept = self.accept.lock().unwrap();
+            let len = sz.min(*accept);
+            *accept -= len;
+            self.data.lock().unwrap().extend(&data[..len]);
+            Ok(len)
+        }
+    }
+    impl SockBlockCtl for Loopback {}
+    impl SockPoll for Loopback {}
+
+    /// Doubles every byte in both directions.
+    struct Doubler;
+    impl Transform for Doubler {
+        fn on_read(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+            self.on_write(data)
+        }
+        fn on_write(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(data.iter().flat_map(|b| [*b, *b]).collect())
+        }
+    }
+
+    #[test]
+    fn transform_with_slow_sink() {
+        let sock = Box::new(Loopback::new(Mutex::new(Vec::new()), Mutex::new(5)));
+        let dec = TransformDecorator::new(sock, Box::new(Doubler));
+        // Whole chunk is consumed, but only 5 of 6 transformed bytes are written
+        assert_eq!(dec.write(b"abc", 3).unwrap(), 3);
+        assert_eq!(dec.write(b"de", 2).unwrap(), 0);
+        // Transformed data, which doesn't fit the read buffer, is kept
+        let mut buf = [0u8; 6];
+        assert_eq!(dec.read(&mut buf, 6).unwrap(), 6);
+        assert_eq!(&buf, b"aaaabb");
+        assert_eq!(dec.read(&mut buf, 6).unwrap(), 4);
+        assert_eq!(&buf[..4], b"bbcc");
+        assert_eq!(dec.read(&mut buf, 6).unwrap(), 0);
+    }
+}
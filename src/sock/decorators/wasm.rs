@@ -0,0 +1,178 @@
+use super::transform::{Transform, TransformBuilder};
+use std::fs;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+
+/// Min interval between checks of the module file modification.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Transform function: takes input pointer and length, returns output
+/// pointer in the high half and length in the low half.
+type TransformFunc = TypedFunc<(u32, u32), u64>;
+
+fn wasm_err(path: &Path, e: wasmtime::Error) -> Error {
+    Error::other(format!("WASM module {}: {e:#}", path.display()))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Instantiated transform module.
+struct WasmInstance {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<u32, u32>,
+    on_read: Option<TransformFunc>,
+    on_write: Option<TransformFunc>,
+}
+
+impl WasmInstance {
+    fn load(engine: &Engine, path: &Path) -> wasmtime::Result<Self> {
+        let module = Module::from_file(engine, path)?;
+        let mut store = Store::new(engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("`memory` export is missing"))?;
+        let alloc = instance.get_typed_func(&mut store, "alloc")?;
+        // Missing transform function passes data of the direction as is
+        let mut optional = |name| {
+            instance
+                .get_func(&mut store, name)
+                .map(|f| f.typed(&store))
+                .transpose()
+        };
+        let on_read = optional("transform_read")?;
+        let on_write = optional("transform_write")?;
+        Ok(Self {
+            store,
+            memory,
+            alloc,
+            on_read,
+            on_write,
+        })
+    }
+    fn call(&mut self, func: Option<TransformFunc>, data: &[u8]) -> wasmtime::Result<Vec<u8>> {
+        let Some(func) = func else {
+            return Ok(data.to_vec());
+        };
+        let ptr = self.alloc.call(&mut self.store, data.len() as u32)?;
+        self.memory.write(&mut self.store, ptr as usize, data)?;
+        let ret = func.call(&mut self.store, (ptr, data.len() as u32))?;
+        let mut out = vec![0; (ret & 0xffff_ffff) as usize];
+        self.memory
+            .read(&self.store, (ret >> 32) as usize, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// Transform implemented by WASM module. The module exports `memory`,
+/// `alloc(len) -> ptr` for input data and optional `transform_read` and
+/// `transform_write` functions. Module file is reloaded when it's modified.
+pub struct WasmTransform {
+    engine: Engine,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+    instance: WasmInstance,
+}
+
+impl WasmTransform {
+    pub fn new(engine: Engine, path: PathBuf) -> Result<Self> {
+        let modified = modified_time(&path);
+        let instance = WasmInstance::load(&engine, &path).map_err(|e| wasm_err(&path, e))?;
+        Ok(Self {
+            engine,
+            path,
+            modified,
+            last_check: Instant::now(),
+            instance,
+        })
+    }
+    /// Creates transform builder sharing one engine. The module is checked
+    /// once here, so a broken module is reported before sockets are opened.
+    pub fn builder(path: PathBuf) -> Result<TransformBuilder> {
+        let engine = Engine::default();
+        Self::new(engine.clone(), path.clone())?;
+        Ok(Arc::new(move || {
+            Ok(Box::new(Self::new(engine.clone(), path.clone())?) as Box<dyn Transform>)
+        }))
+    }
+    /// Swaps the module, if its file is modified. Broken module is reported
+    /// and the current one is kept.
+    fn check_reload(&mut self) {
+        if self.last_check.elapsed() < RELOAD_CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        match WasmInstance::load(&self.engine, &self.path) {
+            Ok(instance) => {
+                tracing::info!("WASM module {} is reloaded", self.path.display());
+                self.instance = instance;
+            }
+            Err(e) => tracing::warn!(
+                "WASM module {} reloading failed, previous version is kept: {e:#}",
+                self.path.display()
+            ),
+        }
+    }
+}
+
+impl Transform for WasmTransform {
+    fn on_read(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.check_reload();
+        let func = self.instance.on_read.clone();
+        self.instance
+            .call(func, data)
+            .map_err(|e| wasm_err(&self.path, e))
+    }
+    fn on_write(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.check_reload();
+        let func = self.instance.on_write.clone();
+        self.instance
+            .call(func, data)
+            .map_err(|e| wasm_err(&self.path, e))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn uppercase_on_read() {
+        // Received text is uppercased in place, written data is passed as is
+        let wat = r#"(module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "transform_read") (param $ptr i32) (param $len i32) (result i64)
+                (local $i i32) (local $c i32)
+                (block $done (loop $next
+                    (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                    (local.set $c (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+                    (if (i32.and (i32.ge_u (local.get $c) (i32.const 97))
+                                 (i32.le_u (local.get $c) (i32.const 122)))
+                        (then (i32.store8 (i32.add (local.get $ptr) (local.get $i))
+                                          (i32.sub (local.get $c) (i32.const 32)))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $next)))
+                (i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                        (i64.extend_i32_u (local.get $len))))
+        )"#;
+        let path = std::env::temp_dir().join("polysock-upper.wat");
+        fs::write(&path, wat).unwrap();
+        let mut transform = WasmTransform::builder(path).unwrap()().unwrap();
+        assert_eq!(transform.on_read(b"hello, wasm").unwrap(), b"HELLO, WASM");
+        assert_eq!(transform.on_write(b"as is").unwrap(), b"as is");
+    }
+}
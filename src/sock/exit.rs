@@ -0,0 +1,143 @@
+use super::pattern::BytePattern;
+use super::stats::BindingStats;
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
+
+/// Condition which stops a binding when it becomes true.
+///
+/// Conditions can be combined with `all`, `any` and `not`, for example
+/// `{ "any": [ { "bytes": 10485760 }, { "all": [ { "elapsed": 60 }, { "idle": 5 } ] } ] }`
+/// stops when 10 MB are transferred or when 60 s elapsed and no data came for 5 s.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitCondition {
+    /// True if every nested condition is true
+    All(Vec<ExitCondition>),
+    /// True if at least one nested condition is true
+    Any(Vec<ExitCondition>),
+    /// Inverts the nested condition
+    Not(Box<ExitCondition>),
+    /// Total transferred bytes (all directions) reached the value
+    Bytes(u64),
+    /// Total transferred chunks (all directions) reached the value
+    Messages(u64),
+    /// Seconds elapsed since the binding start
+    Elapsed(f64),
+    /// Seconds passed without any transferred data
+    Idle(f64),
+    /// The pattern was found in the read data (any direction), e.g.
+    /// `{ "pattern": { "text": "BYE" } }`
+    Pattern(BytePattern),
+}
+
+impl ExitCondition {
+    /// Evaluates the condition against current binding counters.
+    pub fn is_met(&self, stats: &BindingStats) -> bool {
+        match self {
+            Self::All(conds) => conds.iter().all(|c| c.is_met(stats)),
+            Self::Any(conds) => conds.iter().any(|c| c.is_met(stats)),
+            Self::Not(cond) => !cond.is_met(stats),
+            Self::Bytes(limit) => stats.bytes() >= *limit,
+            Self::Messages(limit) => stats.messages() >= *limit,
+            Self::Elapsed(secs) => stats.elapsed() >= Duration::from_secs_f64(*secs),
+            Self::Idle(secs) => stats.idle() >= Duration::from_secs_f64(*secs),
+            Self::Pattern(pattern) => stats.is_matched(pattern),
+        }
+    }
+    /// Checks the values, which can't be evaluated (e.g. negative seconds).
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Self::All(conds) | Self::Any(conds) => conds.iter().try_for_each(Self::check),
+            Self::Not(cond) => cond.check(),
+            // Too large values don't fit into the duration compared with
+            Self::Elapsed(secs) | Self::Idle(secs) => Duration::try_from_secs_f64(*secs)
+                .map(|_| ())
+                .map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Exit condition seconds {secs} can't be evaluated: {e}"),
+                    )
+                }),
+            _ => Ok(()),
+        }
+    }
+    /// Returns the patterns searched in the read data.
+    pub fn patterns(&self) -> Vec<BytePattern> {
+        match self {
+            Self::All(conds) | Self::Any(conds) => conds.iter().flat_map(Self::patterns).collect(),
+            Self::Not(cond) => cond.patterns(),
+            Self::Pattern(pattern) => vec![pattern.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn parse_nested_condition() {
+        let cfg = "{ \"any\": [ { \"bytes\": 100 }, { \"all\": [ { \"elapsed\": 60 }, { \"idle\": 5 } ] } ] }";
+        let cond: ExitCondition = serde_json::from_str(cfg).unwrap();
+        let ExitCondition::Any(conds) = cond else {
+            panic!("Top condition must be `any`: {cond:?}");
+        };
+        assert!(matches!(conds[0], ExitCondition::Bytes(100)));
+        assert!(matches!(
+            &conds[1],
+            ExitCondition::All(all) if matches!(
+                all[..],
+                [ExitCondition::Elapsed(60.0), ExitCondition::Idle(5.0)]
+            )
+        ));
+        assert_eq!(conds.len(), 2);
+    }
+    #[test]
+    fn evaluate_combination() {
+        let stats = BindingStats::new();
+        let cond: ExitCondition =
+            serde_json::from_str("{ \"any\": [ { \"bytes\": 10 }, { \"idle\": 3600 } ] }").unwrap();
+        assert!(!cond.is_met(&stats));
+        stats.record(6);
+        assert!(!cond.is_met(&stats));
+        stats.record(4);
+        assert!(cond.is_met(&stats));
+
+        let cond: ExitCondition = serde_json::from_str(
+            "{ \"all\": [ { \"messages\": 2 }, { \"not\": { \"elapsed\": 3600 } } ] }",
+        )
+        .unwrap();
+        assert!(cond.is_met(&stats));
+    }
+    #[test]
+    fn pattern_condition() {
+        let stats = BindingStats::new();
+        let cond: ExitCondition = serde_json::from_str(
+            "{ \"any\": [ { \"pattern\": { \"text\": \"BYE\" } }, { \"not\": { \"pattern\": { \"hex\": \"ff\" } } } ] }",
+        )
+        .unwrap();
+        assert_eq!(cond.patterns().len(), 2);
+        let bye = &cond.patterns()[0];
+        assert!(!ExitCondition::Pattern(bye.clone()).is_met(&stats));
+        stats.record_match(bye);
+        assert!(ExitCondition::Pattern(bye.clone()).is_met(&stats));
+    }
+    #[test]
+    fn invalid_seconds() {
+        let cfg = "{ \"any\": [ { \"bytes\": 10 }, { \"elapsed\": -1 } ] }";
+        let cond: ExitCondition = serde_json::from_str(cfg).unwrap();
+        assert_eq!(cond.check().unwrap_err().kind(), ErrorKind::InvalidInput);
+        let cond = ExitCondition::Not(Box::new(ExitCondition::Idle(f64::NAN)));
+        assert_eq!(cond.check().unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(ExitCondition::Elapsed(1e30).check().is_err());
+        assert!(ExitCondition::Idle(0.5).check().is_ok());
+        // The binding isn't built with the condition
+        let opts = crate::sock::BindingOptionsBuilder::default()
+            .exit_condition(Some(cond))
+            .build();
+        assert!(opts.is_err());
+    }
+}
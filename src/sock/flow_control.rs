@@ -0,0 +1,107 @@
+use super::stats::Flow;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Software flow control bytes.
+pub const XON: u8 = 0x11;
+pub const XOFF: u8 = 0x13;
+
+/// Socket, whose data carries XON/XOFF bytes of its peer.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, clap::ValueEnum, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum XonXoff {
+    /// The first socket (e.g. serial line)
+    From,
+    /// The second socket
+    To,
+    /// Both sockets
+    Both,
+}
+
+/// XON/XOFF state of the bidirectional binding. XON/XOFF bytes are stripped
+/// from the data read from the socket, XOFF pauses writes to it (the
+/// opposite direction) until XON, unwritten data is kept in the buffer.
+pub(crate) struct FlowControl {
+    sides: XonXoff,
+    /// Writes of the direction are paused, indexed by `Flow`
+    paused: [AtomicBool; 2],
+}
+
+fn index(flow: Flow) -> usize {
+    match flow {
+        Flow::FromTo => 0,
+        Flow::ToFrom => 1,
+    }
+}
+
+fn opposite(flow: Flow) -> Flow {
+    match flow {
+        Flow::FromTo => Flow::ToFrom,
+        Flow::ToFrom => Flow::FromTo,
+    }
+}
+
+impl FlowControl {
+    pub(crate) fn new(sides: XonXoff) -> Self {
+        Self {
+            sides,
+            paused: Default::default(),
+        }
+    }
+    /// Strips XON/XOFF bytes from the data read by the direction and
+    /// pauses or resumes the opposite one. The last byte of the chunk wins.
+    pub(crate) fn inspect(&self, flow: Flow, data: &mut Vec<u8>) {
+        let interpreted = matches!(
+            (self.sides, flow),
+            (XonXoff::Both, _) | (XonXoff::From, Flow::FromTo) | (XonXoff::To, Flow::ToFrom)
+        );
+        if !interpreted {
+            return;
+        }
+        let mut state = None;
+        data.retain(|b| match *b {
+            XON | XOFF => {
+                state = Some(*b == XOFF);
+                false
+            }
+            _ => true,
+        });
+        if let Some(paused) = state {
+            let opposite = opposite(flow);
+            if self.paused[index(opposite)].swap(paused, Ordering::Relaxed) != paused {
+                let state = if paused { "paused (XOFF)" } else { "resumed (XON)" };
+                tracing::debug!("Opposite direction is {state}");
+            }
+        }
+    }
+    /// Returns `true`, if writes of the direction are paused by XOFF.
+    pub(crate) fn is_paused(&self, flow: Flow) -> bool {
+        self.paused[index(flow)].load(Ordering::Relaxed)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn xon_xoff_bytes() {
+        let control = FlowControl::new(XonXoff::From);
+        let mut data = vec![b'a', XOFF, b'b'];
+        control.inspect(Flow::FromTo, &mut data);
+        assert_eq!(data, b"ab");
+        assert!(control.is_paused(Flow::ToFrom));
+        assert!(!control.is_paused(Flow::FromTo));
+        let mut data = vec![XOFF, b'c', XON];
+        control.inspect(Flow::FromTo, &mut data);
+        assert_eq!(data, b"c");
+        assert!(!control.is_paused(Flow::ToFrom));
+
+        // Data of the other socket is forwarded as is
+        let mut data = vec![XOFF];
+        control.inspect(Flow::ToFrom, &mut data);
+        assert_eq!(data, [XOFF]);
+        assert!(!control.is_paused(Flow::FromTo));
+    }
+}
@@ -0,0 +1,101 @@
+use super::stats::Flow;
+use serde::Deserialize;
+use serde_hex::{SerHexOpt, StrictPfx};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Half-duplex line of the bidirectional binding (e.g. RS-485 bus bridged
+/// to TCP), only one direction transmits at a time:
+/// `{ "turnaround_ms": 5, "terminator": "0x0a" }`.
+#[derive(Deserialize, Debug, Clone, PartialEq, schemars::JsonSchema)]
+pub struct HalfDuplex {
+    /// Delay in milliseconds before the opposite direction may transmit
+    #[serde(default)]
+    turnaround_ms: u64,
+    /// The line is released after this byte (hex) is written, otherwise
+    /// when all read data is written
+    #[serde(with = "SerHexOpt::<StrictPfx>", default)]
+    #[schemars(with = "Option<String>")]
+    terminator: Option<u8>,
+}
+
+/// Direction holding the line and the last release of the line.
+#[derive(Default)]
+struct LineState {
+    owner: Option<Flow>,
+    released: Option<(Flow, Instant)>,
+}
+
+/// Line shared by both directions of the binding. Data of the direction
+/// waiting for the line is kept in its buffer.
+pub(crate) struct HalfDuplexLine {
+    turnaround: Duration,
+    terminator: Option<u8>,
+    state: Mutex<LineState>,
+}
+
+impl HalfDuplexLine {
+    pub(crate) fn new(config: &HalfDuplex) -> Self {
+        Self {
+            turnaround: Duration::from_millis(config.turnaround_ms),
+            terminator: config.terminator,
+            state: Mutex::default(),
+        }
+    }
+    /// Returns `true`, if the direction may transmit. The free line is
+    /// taken, the turnaround is waited only when the direction changes.
+    pub(crate) fn take(&self, flow: Flow) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.owner {
+            Some(owner) => owner == flow,
+            None => {
+                if let Some((by, at)) = state.released
+                    && by != flow
+                    && at.elapsed() < self.turnaround
+                {
+                    return false;
+                }
+                state.owner = Some(flow);
+                true
+            }
+        }
+    }
+    /// Releases the line after all read data is written, `last` is the last
+    /// written byte. With the terminator the line is kept until it's written.
+    pub(crate) fn release(&self, flow: Flow, last: Option<u8>) {
+        if self.terminator.is_some_and(|terminator| last != Some(terminator)) {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.owner == Some(flow) {
+            state.owner = None;
+            state.released = Some((flow, Instant::now()));
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn line_turnaround() {
+        let config = r#"{ "turnaround_ms": 30, "terminator": "0x0a" }"#;
+        let line = HalfDuplexLine::new(&serde_json::from_str(config).unwrap());
+        assert!(line.take(Flow::FromTo));
+        assert!(!line.take(Flow::ToFrom));
+        // The line is kept until the terminator is written
+        line.release(Flow::FromTo, Some(b'x'));
+        assert!(!line.take(Flow::ToFrom));
+        line.release(Flow::FromTo, Some(b'\n'));
+        assert!(!line.take(Flow::ToFrom));
+        // The same direction transmits again without turnaround
+        assert!(line.take(Flow::FromTo));
+        line.release(Flow::FromTo, Some(b'\n'));
+        thread::sleep(Duration::from_millis(40));
+        assert!(line.take(Flow::ToFrom));
+        assert!(!line.take(Flow::FromTo));
+    }
+}
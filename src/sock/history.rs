@@ -0,0 +1,247 @@
+use super::stats::BindingStats;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HISTORY_HEADER: &str = "time,run,event,secs,bytes,messages,detail";
+
+/// Seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Kind of the history row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HistoryEvent {
+    /// Binding is started
+    Start,
+    /// Periodic metrics of the running binding
+    Interval,
+    /// Binding is stopped
+    Stop,
+    /// Binding is finished with error
+    Error,
+}
+
+impl HistoryEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Interval => "interval",
+            Self::Stop => "stop",
+            Self::Error => "error",
+        }
+    }
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "start" => Some(Self::Start),
+            "interval" => Some(Self::Interval),
+            "stop" => Some(Self::Stop),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// One CSV row of the history. Time and counters are accounted since the
+/// previous row of the same run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryRow {
+    /// Unix time of the row
+    pub time: u64,
+    /// Binding run, which wrote the row
+    pub run: String,
+    pub event: HistoryEvent,
+    pub secs: f64,
+    pub bytes: u64,
+    pub messages: u64,
+    /// Error message of the error row
+    pub detail: String,
+}
+
+impl HistoryRow {
+    pub fn to_csv(&self) -> String {
+        let detail = if self.detail.contains([',', '"', '\n']) {
+            format!(
+                "\"{}\"",
+                self.detail.replace('"', "\"\"").replace('\n', " ")
+            )
+        } else {
+            self.detail.clone()
+        };
+        format!(
+            "{},{},{},{:.3},{},{},{}",
+            self.time,
+            self.run,
+            self.event.as_str(),
+            self.secs,
+            self.bytes,
+            self.messages,
+            detail
+        )
+    }
+    /// Parses the CSV row, the header and malformed rows give `None`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(7, ',');
+        let mut next = || fields.next();
+        let row = Self {
+            time: next()?.parse().ok()?,
+            run: next()?.to_string(),
+            event: HistoryEvent::parse(next()?)?,
+            secs: next()?.parse().ok()?,
+            bytes: next()?.parse().ok()?,
+            messages: next()?.parse().ok()?,
+            detail: next().unwrap_or_default().to_string(),
+        };
+        let detail = match row.detail.strip_prefix('"') {
+            Some(quoted) => quoted.strip_suffix('"')?.replace("\"\"", "\""),
+            None => row.detail,
+        };
+        Some(Self { detail, ..row })
+    }
+}
+
+/// Reads all rows of the history file.
+pub fn read_history(path: &Path) -> io::Result<Vec<HistoryRow>> {
+    let mut rows = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        match HistoryRow::parse(&line) {
+            Some(row) => rows.push(row),
+            None if line == HISTORY_HEADER => {}
+            None => tracing::warn!("Malformed history row is skipped: {line}"),
+        }
+    }
+    Ok(rows)
+}
+
+struct RecorderState {
+    file: File,
+    run: String,
+    stats: Arc<BindingStats>,
+    /// Elapsed time and counters of the previous row
+    last: (Duration, u64, u64),
+}
+
+impl RecorderState {
+    fn append(&mut self, event: HistoryEvent, detail: &str) {
+        let current = (
+            self.stats.elapsed(),
+            self.stats.bytes(),
+            self.stats.messages(),
+        );
+        let row = HistoryRow {
+            time: unix_now(),
+            run: self.run.clone(),
+            event,
+            secs: (current.0 - self.last.0).as_secs_f64(),
+            bytes: current.1 - self.last.1,
+            messages: current.2 - self.last.2,
+            detail: detail.to_string(),
+        };
+        self.last = current;
+        if let Err(e) = writeln!(self.file, "{}", row.to_csv()) {
+            tracing::warn!("History row writing failed: {e}");
+        }
+    }
+}
+
+/// Appends binding metrics to the CSV history every interval.
+pub struct HistoryRecorder {
+    state: Arc<Mutex<RecorderState>>,
+    stop: Mutex<Option<Sender<()>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl HistoryRecorder {
+    /// Opens (or creates) the history file and writes the start row.
+    pub fn start(path: &Path, interval: Duration, stats: Arc<BindingStats>) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{HISTORY_HEADER}")?;
+        }
+        let last = (stats.elapsed(), stats.bytes(), stats.messages());
+        let mut state = RecorderState {
+            file,
+            run: format!("{}-{}", unix_now(), std::process::id()),
+            stats,
+            last,
+        };
+        state.append(HistoryEvent::Start, "");
+        let state = Arc::new(Mutex::new(state));
+        let (tx, rx) = mpsc::channel();
+        let thread_state = state.clone();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                thread_state
+                    .lock()
+                    .unwrap()
+                    .append(HistoryEvent::Interval, "");
+            }
+        });
+        Ok(Self {
+            state,
+            stop: Mutex::new(Some(tx)),
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+    /// Stops periodic rows and writes the final row with the binding result.
+    pub fn finish(&self, error: Option<&io::Error>) {
+        drop(self.stop.lock().unwrap().take());
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+        let mut state = self.state.lock().unwrap();
+        match error {
+            Some(e) => state.append(HistoryEvent::Error, &e.to_string()),
+            None => state.append(HistoryEvent::Stop, ""),
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn history_rows() {
+        let row = HistoryRow {
+            time: 1700000000,
+            run: "1700000000-42".to_string(),
+            event: HistoryEvent::Error,
+            secs: 1.5,
+            bytes: 1024,
+            messages: 8,
+            detail: "Connection refused, \"os error 111\"".to_string(),
+        };
+        let csv = row.to_csv();
+        assert_eq!(
+            csv,
+            "1700000000,1700000000-42,error,1.500,1024,8,\"Connection refused, \"\"os error 111\"\"\""
+        );
+        assert_eq!(HistoryRow::parse(&csv), Some(row));
+        assert_eq!(HistoryRow::parse(HISTORY_HEADER), None);
+
+        let path = std::env::temp_dir().join(format!("history-{}.csv", std::process::id()));
+        let stats = Arc::new(BindingStats::new());
+        let recorder =
+            HistoryRecorder::start(&path, Duration::from_millis(20), stats.clone()).unwrap();
+        stats.record(100);
+        thread::sleep(Duration::from_millis(50));
+        recorder.finish(None);
+        let rows = read_history(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(rows.first().unwrap().event, HistoryEvent::Start);
+        assert_eq!(rows.last().unwrap().event, HistoryEvent::Stop);
+        assert!(rows.iter().any(|r| r.event == HistoryEvent::Interval));
+        assert_eq!(rows.iter().map(|r| r.bytes).sum::<u64>(), 100);
+    }
+}
@@ -1,14 +1,69 @@
+#[cfg(feature = "async")]
+pub mod async_manager;
+pub mod buffer;
+pub mod capacity;
 pub mod decorators;
+pub mod exit;
+pub mod flow_control;
+pub mod half_duplex;
+pub mod history;
+pub mod params;
+pub mod pattern;
+pub mod poll;
+pub mod profiles;
+pub mod sandbox;
+pub mod stats;
+pub use decorators::inject_marker;
 pub use decorators::{
-    TraceCanonicalDecoratorFactory, TraceInfoDecoratorFactory, TraceRawDecoratorFactory,
+    ByteSwapConfig, CaptureDecoratorFactory, CaptureFile, ChecksumConfig, ChecksumDecoratorFactory,
+    ChunkConfig, ChunkDecoratorFactory, CompressConfig, CompressDecoratorFactory, DecoratorSpec,
+    DecoratorStack, DecoratorSwitch, DelayConfig, DelayDecoratorFactory, DemuxConfig,
+    DemuxDecoratorFactory, Direction, EncryptConfig, FaultConfig, FaultDecoratorFactory,
+    FilterConfig, FilterDecoratorFactory, Framing, HashAlgo, HashDecoratorFactory, LatencyConfig,
+    LatencyDecoratorFactory, LengthPrefixDecorator, MonitorConfig, MonitorDecoratorFactory,
+    ObfuscateConfig, RateLimitConfig, RateLimitDecoratorFactory, RecordDecoratorFactory,
+    RecordReader, RecordWriter, ReopenFactory, SequenceConfig, SequenceDecoratorFactory,
+    SocketReopener, StatsDecoratorFactory, StreamDigest, SummaryDecoratorFactory,
+    TapDecoratorFactory, TeeConfig, TeeDecoratorFactory, TraceCanonicalDecoratorFactory, TraceColor,
+    TraceFileConfig, TraceFileConfigBuilder, TraceInfoDecoratorFactory, TraceRawDecoratorFactory,
+    TraceSide, TraceTimestamp, TrafficTap, Transform, TransformBuilder, TransformDecoratorFactory,
+    adapt_fan_in_framing, adapt_fan_out_framing, adapt_framing, set_trace_color, set_trace_file,
+    set_trace_timestamp, stream_digests, tag_builder,
 };
+#[cfg(feature = "wasm")]
+pub use decorators::WasmTransform;
+#[cfg(feature = "async")]
+pub use async_manager::AsyncSocketManager;
+pub use buffer::{Coalesce, OverflowPolicy};
+pub use exit::ExitCondition;
+pub use flow_control::XonXoff;
+pub use half_duplex::HalfDuplex;
+pub use history::HistoryRecorder;
+pub use params::SocketParams;
+pub use poll::RawHandle;
+pub use profiles::{Profile, Profiles};
+pub use sandbox::{Sandbox, SandboxBuilder};
+pub use stats::{
+    BindingStats, BindingSummary, Flow, FlowSummary, SummaryFormat, report_summary,
+    set_summary_format,
+};
+
+use crate::error::{self, Op};
+use buffer::RingBuffer;
+use capacity::check_capacity;
+use flow_control::FlowControl;
+use half_duplex::HalfDuplexLine;
+use pattern::BytePattern;
+use derive_builder::Builder;
 
+use std::cell::RefCell;
 use std::io;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use std::{io::Result, mem::size_of, thread};
+use tracing::Span;
 
 /// A simple socket trait providing basic read/write operations.
 #[allow(unused)]
@@ -24,8 +79,41 @@ pub trait SimpleSock: Send {
     /// Reads data into the provided buffer, up to `sz` bytes.
     fn read(&self, data: &mut [u8], sz: usize) -> Result<usize>;
 
-    /// Writes data from the provided buffer, up to `sz` bytes.
-    fn write(&self, data: &[u8], sz: usize) -> Result<()>;
+    /// Writes data from the provided buffer, up to `sz` bytes. Returns
+    /// number of written bytes, `Ok(0)` means that the socket can't
+    /// accept data now (e.g. nonblocking socket would block).
+    fn write(&self, data: &[u8], sz: usize) -> Result<usize>;
+}
+
+/// Checks that `sz` bytes requested by the caller fit the buffer.
+pub(crate) fn check_buffer_size(sz: usize, len: usize) -> Result<()> {
+    if sz > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Requested size {sz} exceeds buffer length {len}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Socket must not return more data than it was asked for.
+fn check_read_len(read: usize, requested: usize) -> Result<()> {
+    if read > requested {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Socket returned {read} bytes, but {requested} were requested"),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the socket keeps boundaries of the written data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Semantics {
+    /// Every read returns one whole message (e.g. UDP datagram)
+    Message,
+    /// Data is a byte stream, write boundaries aren't kept (e.g. TCP)
+    Stream,
 }
 
 pub trait SockInfo {
@@ -34,12 +122,68 @@ pub trait SockInfo {
     fn get_description(&self) -> String {
         format!("{}{}", self.get_type_name(), self.get_id())
     }
+    /// Kernel receive time of the last read data, if the socket captures it.
+    fn rx_timestamp(&self) -> Option<SystemTime> {
+        None
+    }
+    /// Rate the socket produces data with (B/s), if it's known from the
+    /// configuration.
+    fn source_rate(&self) -> Option<f64> {
+        None
+    }
+    /// Rate the socket accepts data with (B/s), if it's limited by the
+    /// configuration.
+    fn sink_rate(&self) -> Option<f64> {
+        None
+    }
+    /// Message sockets can't be bound with stream ones without framing,
+    /// see [`Framing`].
+    fn semantics(&self) -> Semantics {
+        Semantics::Stream
+    }
 }
 
 pub trait SockBlockCtl {
     fn set_block(&mut self, _: bool) -> Result<()> {
         Ok(())
     }
+    /// Sets timeouts of blocking read and write operations (`None` means
+    /// no timeout). Expired read returns no data, expired write fails with
+    /// `TimedOut` error, so a dead peer can't hang the binding forever.
+    fn set_timeout(&mut self, _read: Option<Duration>, _write: Option<Duration>) -> Result<()> {
+        Ok(())
+    }
+    /// Sets time given to the socket on close to deliver pending data and
+    /// finish the connection gracefully (zero means abrupt close).
+    fn set_grace_period(&mut self, _: Duration) -> Result<()> {
+        Ok(())
+    }
+    /// Sends written data back to the peers the read data came from (e.g.
+    /// every client of a server gets its own data) instead of the
+    /// destination. Sockets with a single peer reflect data as usual.
+    fn set_echo(&mut self, _: bool) -> Result<()> {
+        Ok(())
+    }
+    /// Called when the whole message is written, so data held back to fill
+    /// the segments (e.g. with TCP_CORK) is sent without delay.
+    fn flush_message(&self) -> Result<()> {
+        Ok(())
+    }
+    /// Half-closes the socket after the last write: the peer gets the end
+    /// of data, while the socket still can be read (e.g. TCP shutdown of
+    /// the write side).
+    fn shutdown_write(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub trait SockPoll {
+    /// Returns OS handles, which become readable when the socket has data.
+    /// `None` means that readiness can't be polled and the caller has to
+    /// retry reading after a short sleep.
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        None
+    }
 }
 
 pub trait SockDocViewer {
@@ -57,13 +201,12 @@ impl SockDocViewer for SocDocDefault {
     }
 }
 
-pub trait ComplexSock: SimpleSock + SockBlockCtl + SockInfo {}
+pub trait ComplexSock: SimpleSock + SockBlockCtl + SockInfo + SockPoll {}
 
-// Any type that impl SimpleSock & SockBlockCtl automatically
-// implements SimpleSockBlock
-impl<T: SimpleSock + SockBlockCtl + SockInfo> ComplexSock for T {}
+// Any type that impl SimpleSock, SockBlockCtl, SockInfo & SockPoll
+// automatically implements ComplexSock
+impl<T: SimpleSock + SockBlockCtl + SockInfo + SockPoll> ComplexSock for T {}
 
-pub type SocketParams = String;
 pub trait SocketFactory {
     /// Creates a new SimpleSock instance with the given parameters.
     fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>>;
@@ -79,6 +222,116 @@ pub trait SocketFactory {
     fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
         Box::new(SocDocDefault)
     }
+    /// Checks the parameters without opening the socket (dry run). The
+    /// parameters are parsed JSON already, so the default implementation
+    /// accepts any of them.
+    fn validate_params(&self, _params: &SocketParams) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Options shared by all threads of one binding.
+#[derive(Builder, Clone)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct BindingOptions {
+    /// Condition which stops the binding
+    #[builder(default)]
+    exit_condition: Option<ExitCondition>,
+    /// Capacity of the buffer between read and write sides of a direction
+    #[builder(default = "buffer::DEFAULT_BUFFER_SIZE")]
+    buffer_size: usize,
+    /// Policy applied when the buffer is full
+    #[builder(default)]
+    overflow_policy: OverflowPolicy,
+    /// Bridging of message and stream sockets
+    #[builder(default)]
+    framing: Framing,
+    /// Timeout of blocking read operations of both sockets
+    #[builder(default)]
+    read_timeout: Option<Duration>,
+    /// Timeout of blocking write operations of both sockets
+    #[builder(default)]
+    write_timeout: Option<Duration>,
+    /// Time to flush queued data and close connections gracefully on stop
+    #[builder(default = "DEFAULT_GRACE_PERIOD")]
+    grace_period: Duration,
+    /// Counters shared with the caller (e.g. for history records and the
+    /// summary on exit), the binding creates its own ones if not set
+    #[builder(default = "Some(Arc::default())")]
+    stats: Option<Arc<BindingStats>>,
+    /// Flag shared by several bindings (e.g. links of the script), clearing
+    /// it stops all of them
+    #[builder(default)]
+    shutdown: Option<Arc<AtomicBool>>,
+    /// Name of the binding in log events
+    #[builder(default)]
+    name: Option<String>,
+    /// Flag pausing the binding while it is set, unread data is kept in the
+    /// socket buffers
+    #[builder(default)]
+    paused: Option<Arc<AtomicBool>>,
+    /// Only one direction of the bidirectional binding transmits at a time
+    #[builder(default)]
+    half_duplex: Option<HalfDuplex>,
+    /// XON/XOFF bytes read from the socket are stripped and pause or resume
+    /// writes to it
+    #[builder(default)]
+    xon_xoff: Option<XonXoff>,
+    /// Size of one read operation of every direction: small chunks are
+    /// forwarded sooner, large ones take fewer reads for bulk transfers
+    #[builder(default = "READ_CHUNK_SIZE")]
+    read_chunk: usize,
+    /// Max time to wait for incoming data before the binding state (stop,
+    /// pause) is rechecked, sockets without readiness polling sleep at
+    /// most this time
+    #[builder(default = "POLL_TIMEOUT")]
+    poll_interval: Duration,
+    /// `read_chunk` of the tx direction of the bidirectional binding (from
+    /// the second socket back to the first one), if it differs
+    #[builder(default)]
+    read_chunk_tx: Option<usize>,
+    /// `poll_interval` of the tx direction of the bidirectional binding, if
+    /// it differs
+    #[builder(default)]
+    poll_interval_tx: Option<Duration>,
+    /// Read chunks are collected in the buffer and written at once, when
+    /// the size is reached or the oldest data waits too long
+    #[builder(default)]
+    coalesce: Option<Coalesce>,
+}
+
+/// Named binding of the multi-link set: factories (with their own
+/// decorators) and parameters of both sockets.
+pub struct Link<'a> {
+    pub name: String,
+    pub in_factory: &'a dyn SocketFactory,
+    pub out_factory: &'a dyn SocketFactory,
+    pub in_params: SocketParams,
+    pub out_params: SocketParams,
+    pub bidir: bool,
+    pub blocking: bool,
+    pub opts: BindingOptions,
+}
+
+/// Sink of the fan-out binding: factory (with its own decorators) and
+/// parameters of the output socket.
+pub struct FanOutSink<'a> {
+    pub factory: &'a dyn SocketFactory,
+    pub params: SocketParams,
+}
+
+/// Source of the fan-in binding: factory (with its own decorators and
+/// tag) and parameters of the input socket.
+pub struct FanInSource<'a> {
+    pub factory: &'a dyn SocketFactory,
+    pub params: SocketParams,
+}
+
+/// Output socket of the fan-out binding with its own buffer, so a slow
+/// sink doesn't hold data of the other ones.
+struct FanOutOutput {
+    sock: Mutex<SocketWrapper>,
+    buffer: RingBuffer,
 }
 
 pub struct SocketManager<'a> {
@@ -92,6 +345,18 @@ type DoubleThreadRet = (
     Arc<AtomicBool>,
 );
 type SingleThreadRet = (JoinHandle<Result<()>>, Arc<AtomicBool>);
+/// Threads of every link by its name and the flag stopping all of them.
+type MultiLinkRet = (Vec<(String, Vec<JoinHandle<Result<()>>>)>, Arc<AtomicBool>);
+
+/// Default size of one read operation (in elements).
+pub(crate) const READ_CHUNK_SIZE: usize = 1024; // Reasonable chunk size
+
+/// Default max time to wait for incoming data before the binding state is
+/// rechecked.
+pub(crate) const POLL_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Default time to drain the binding on stop.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(1);
 
 #[allow(unused)]
 impl<'a> SocketManager<'a> {
@@ -107,26 +372,51 @@ impl<'a> SocketManager<'a> {
     pub fn set_out_factory(&mut self, out_factory: &'a dyn SocketFactory) {
         self.out_factory = out_factory;
     }
+    /// Creates and opens sockets of the binding. Blocking mode of the output
+    /// socket is kept default, if `out_blocking` isn't set.
+    pub(crate) fn open_pair(
+        &self,
+        in_params: &SocketParams,
+        out_params: &SocketParams,
+        in_blocking: bool,
+        out_blocking: Option<bool>,
+        opts: &BindingOptions,
+    ) -> io::Result<(SocketWrapper, SocketWrapper)> {
+        let mut input = self
+            .in_factory
+            .create_sock_blockctl(in_params.clone(), in_blocking)?;
+        input.set_timeout(opts.read_timeout, opts.write_timeout)?;
+        input.set_grace_period(opts.grace_period)?;
+        let mut output = match out_blocking {
+            Some(blocking) => self
+                .out_factory
+                .create_sock_blockctl(out_params.clone(), blocking)?,
+            None => self.out_factory.create_sock(out_params.clone())?,
+        };
+        output.set_timeout(opts.read_timeout, opts.write_timeout)?;
+        output.set_grace_period(opts.grace_period)?;
+        // Semantics is checked before anything is opened
+        let (input, output) = adapt_framing(input, output, opts.framing)?;
+        let input = SocketWrapper::new(input).open()?;
+        let output = SocketWrapper::new(output).open()?;
+        Ok((input, output))
+    }
     pub fn bind_unidirectional(
         &self,
         in_params: &SocketParams,
         out_params: &SocketParams,
         blocking: bool,
+        opts: &BindingOptions,
     ) -> io::Result<SingleThreadRet> {
-        let input = SocketWrapper::new(
-            self.in_factory
-                .create_sock_blockctl(in_params.clone(), blocking)?,
-        )
-        .open()?;
-        let output =
-            SocketWrapper::new(self.out_factory.create_sock(out_params.clone())?).open()?;
+        let (input, output) = self.open_pair(in_params, out_params, blocking, None, opts)?;
+        check_capacity(input.get_sock_info(), output.get_sock_info(), opts);
         let running = Arc::new(AtomicBool::new(true));
-        let r = running.clone();
+        let ctl = BindingCtl::new(running.clone(), opts);
 
         let h = Self::create_binding_thread(
             Arc::new(Mutex::new(input)),
             Arc::new(Mutex::new(output)),
-            r,
+            ctl,
         );
         Ok((h, running))
     }
@@ -134,80 +424,849 @@ impl<'a> SocketManager<'a> {
         &self,
         from_params: &SocketParams,
         to_params: &SocketParams,
+        opts: &BindingOptions,
     ) -> io::Result<DoubleThreadRet> {
-        let from = SocketWrapper::new(
-            self.in_factory
-                .create_sock_blockctl(from_params.clone(), false)?,
-        )
-        .open()?;
-        let to = SocketWrapper::new(
-            self.out_factory
-                .create_sock_blockctl(to_params.clone(), false)?,
-        )
-        .open()?;
+        let (from, to) = self.open_pair(from_params, to_params, false, Some(false), opts)?;
+        check_capacity(from.get_sock_info(), to.get_sock_info(), opts);
+        check_capacity(to.get_sock_info(), from.get_sock_info(), opts);
         let running = Arc::new(AtomicBool::new(true));
-        let r_1_2 = running.clone();
-        let r_2_1 = running.clone();
+        // Both directions share running flag and are accounted together
+        let ctl = BindingCtl::new(running.clone(), opts);
         let from_1_2 = Arc::new(Mutex::new(from));
         let to_2_1 = from_1_2.clone();
         let to_1_2 = Arc::new(Mutex::new(to));
         let from_2_1 = to_1_2.clone();
 
-        let handle_1_2 = Self::create_binding_thread(from_1_2, to_1_2, r_1_2);
-        let handle_2_1 = Self::create_binding_thread(from_2_1, to_2_1, r_2_1);
+        let handle_1_2 = Self::create_binding_thread(from_1_2, to_1_2, ctl.clone());
+        let handle_2_1 = Self::create_binding_thread(from_2_1, to_2_1, ctl.reversed());
 
         Ok((handle_1_2, handle_2_1, running))
     }
+    /// Binds the input socket to itself: data read from it is written back
+    /// to the peers it came from.
+    pub fn bind_echo(
+        &self,
+        params: &SocketParams,
+        opts: &BindingOptions,
+    ) -> io::Result<SingleThreadRet> {
+        let mut sock = self.in_factory.create_sock_blockctl(params.clone(), false)?;
+        sock.set_echo(true)?;
+        sock.set_timeout(opts.read_timeout, opts.write_timeout)?;
+        sock.set_grace_period(opts.grace_period)?;
+        let sock = Arc::new(Mutex::new(SocketWrapper::new(sock).open()?));
+        let running = Arc::new(AtomicBool::new(true));
+        let ctl = BindingCtl::new(running.clone(), opts);
+        // Read and write locks of the socket are taken in turn
+        let h = Self::create_binding_thread(sock.clone(), sock, ctl);
+        Ok((h, running))
+    }
+    /// Binds the input socket to every sink: data read from the input is
+    /// written to all of them. Sinks aren't blocking, so a slow one doesn't
+    /// stall the others. Failed sink is closed and dropped, the binding
+    /// fails only when no sinks are left.
+    pub fn bind_fan_out(
+        &self,
+        in_params: &SocketParams,
+        sinks: &[FanOutSink],
+        blocking: bool,
+        opts: &BindingOptions,
+    ) -> io::Result<SingleThreadRet> {
+        let mut input = self
+            .in_factory
+            .create_sock_blockctl(in_params.clone(), blocking)?;
+        input.set_timeout(opts.read_timeout, opts.write_timeout)?;
+        input.set_grace_period(opts.grace_period)?;
+        let mut outputs = Vec::with_capacity(sinks.len());
+        for sink in sinks {
+            let mut output = sink
+                .factory
+                .create_sock_blockctl(sink.params.clone(), false)?;
+            output.set_timeout(opts.read_timeout, opts.write_timeout)?;
+            output.set_grace_period(opts.grace_period)?;
+            outputs.push(output);
+        }
+        let (input, outputs) = adapt_fan_out_framing(input, outputs, opts.framing)?;
+        let input = SocketWrapper::new(input).open()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let ctl = BindingCtl::new(running.clone(), opts);
+        let outputs: Vec<_> = outputs
+            .into_iter()
+            .filter_map(|output| {
+                let description = output.get_description();
+                match SocketWrapper::new(output).open() {
+                    Ok(sock) => Some(sock),
+                    Err(e) => {
+                        tracing::warn!("Sink {description} opening failed, it's skipped: {e}");
+                        None
+                    }
+                }
+            })
+            .inspect(|output| check_capacity(input.get_sock_info(), output.get_sock_info(), opts))
+            .map(|sock| FanOutOutput {
+                sock: Mutex::new(sock),
+                buffer: ctl.new_buffer(),
+            })
+            .collect();
+        if outputs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "No fan-out sink is opened",
+            ));
+        }
+        let h = Self::create_fan_out_thread(input, outputs, ctl);
+        Ok((h, running))
+    }
+    /// Binds every source to the output socket: data read from the sources
+    /// is merged into it. Sources aren't blocking, so an idle one doesn't
+    /// stall the others. Failed source is closed and dropped, the binding
+    /// fails only when no sources are left.
+    pub fn bind_fan_in(
+        &self,
+        sources: &[FanInSource],
+        out_params: &SocketParams,
+        opts: &BindingOptions,
+    ) -> io::Result<SingleThreadRet> {
+        let mut inputs = Vec::with_capacity(sources.len());
+        for source in sources {
+            let mut input = source
+                .factory
+                .create_sock_blockctl(source.params.clone(), false)?;
+            input.set_timeout(opts.read_timeout, opts.write_timeout)?;
+            input.set_grace_period(opts.grace_period)?;
+            inputs.push(input);
+        }
+        let mut output = self.out_factory.create_sock(out_params.clone())?;
+        output.set_timeout(opts.read_timeout, opts.write_timeout)?;
+        output.set_grace_period(opts.grace_period)?;
+        let (inputs, output) = adapt_fan_in_framing(inputs, output, opts.framing)?;
+        let output = SocketWrapper::new(output).open()?;
+        let inputs: Vec<_> = inputs
+            .into_iter()
+            .filter_map(|input| {
+                let description = input.get_description();
+                match SocketWrapper::new(input).open() {
+                    Ok(sock) => Some(sock),
+                    Err(e) => {
+                        tracing::warn!("Source {description} opening failed, it's skipped: {e}");
+                        None
+                    }
+                }
+            })
+            .inspect(|input| check_capacity(input.get_sock_info(), output.get_sock_info(), opts))
+            .collect();
+        if inputs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "No fan-in source is opened",
+            ));
+        }
+        let running = Arc::new(AtomicBool::new(true));
+        let ctl = BindingCtl::new(running.clone(), opts);
+        let h = Self::create_fan_in_thread(inputs, Mutex::new(output), ctl);
+        Ok((h, running))
+    }
+    /// Binds every link in its own threads. Links share the flag, which
+    /// stops all of them when it's cleared, but exit condition or failure
+    /// of a link stops only this link. If some link can't be bound, the
+    /// links bound before it are stopped.
+    pub fn bind_links(links: &[Link]) -> io::Result<MultiLinkRet> {
+        let shutdown = Arc::new(AtomicBool::new(true));
+        let mut bound: Vec<(String, Vec<JoinHandle<Result<()>>>)> = Vec::new();
+        for link in links {
+            let res = if bound.iter().any(|(name, _)| *name == link.name) {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Link name is duplicated",
+                ))
+            } else {
+                Self::bind_link(link, &shutdown)
+            };
+            match res {
+                Ok(handles) => bound.push((link.name.clone(), handles)),
+                Err(e) => {
+                    shutdown.store(false, Ordering::Relaxed);
+                    bound
+                        .into_iter()
+                        .flat_map(|(_, handles)| handles)
+                        .for_each(|h| drop(h.join()));
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("Link {} binding failed: {e}", link.name),
+                    ));
+                }
+            }
+        }
+        Ok((bound, shutdown))
+    }
+    fn bind_link(
+        link: &Link,
+        shutdown: &Arc<AtomicBool>,
+    ) -> io::Result<Vec<JoinHandle<Result<()>>>> {
+        let manager = SocketManager::new(link.in_factory, link.out_factory);
+        let mut opts = link.opts.clone();
+        opts.shutdown = Some(shutdown.clone());
+        opts.name = Some(link.name.clone());
+        if link.bidir {
+            let (h1, h2, _) =
+                manager.bind_bidirectional(&link.in_params, &link.out_params, &opts)?;
+            Ok(vec![h1, h2])
+        } else {
+            let (h, _) = manager.bind_unidirectional(
+                &link.in_params,
+                &link.out_params,
+                link.blocking,
+                &opts,
+            )?;
+            Ok(vec![h])
+        }
+    }
     fn create_binding_thread(
         from: Arc<Mutex<SocketWrapper>>,
         to: Arc<Mutex<SocketWrapper>>,
-        r: Arc<AtomicBool>,
+        ctl: BindingCtl,
     ) -> JoinHandle<Result<()>> {
-        thread::spawn(move || -> Result<()> {
-            while r.load(Ordering::Relaxed) {
-                {
-                    let buf: Vec<u8> = from.lock().unwrap().read_all()?;
-                    to.lock()
-                        .unwrap()
-                        .generic_write(buf.as_slice(), buf.len())?;
+        let span = ctl.span(&from, &to);
+        ctl.spawn(span, move |ctl| {
+            let mut buffer = ctl.new_buffer();
+            // Read buffer is reused, so steady-state forwarding doesn't allocate
+            let mut buf: Vec<u8> = Vec::with_capacity(ctl.buffer_size);
+            // Last byte pushed to the buffer
+            let mut last = None;
+            let mut tail = Vec::new();
+            while ctl.is_running() {
+                if ctl.is_paused() {
+                    thread::sleep(ctl.poll_interval);
+                    continue;
+                }
+                buf.clear();
+                let res = from
+                    .lock()
+                    .unwrap()
+                    .read_bytes_chunked(&mut buf, buffer.read_limit(), ctl.read_chunk);
+                if ctl.is_finished(res)? {
+                    return ctl.finish(&mut buffer, &to);
+                }
+                ctl.strip_flow_control(&mut buf);
+                ctl.inspect(&mut tail, &buf);
+                buffer.push(&buf);
+                last = buf.last().copied().or(last);
+                if buffer.is_empty() {
+                    // Handles are taken under the lock, but waiting is done
+                    // without it, so the opposite direction isn't blocked
+                    let handles = from.lock().unwrap().poll_handles();
+                    poll::wait_or_sleep(handles, ctl.poll_interval)?;
+                } else if let Some(wait) = ctl.coalesce_wait(&buffer) {
+                    // More data is collected before the write
+                    let handles = from.lock().unwrap().poll_handles();
+                    poll::wait_or_sleep(handles, wait)?;
+                } else if !ctl.take_line() {
+                    // Opposite direction transmits or the sink sent XOFF, data
+                    // is kept in the buffer
+                    thread::sleep(poll::FALLBACK_SLEEP);
+                } else {
+                    let Some(written) = ctl.flush(&mut buffer, &to)? else {
+                        return Ok(());
+                    };
+                    ctl.record(written);
+                    ctl.release_line(&buffer, last);
+                    if written == 0 {
+                        // Sink isn't ready, the remainder is retried later
+                        thread::sleep(poll::FALLBACK_SLEEP);
+                    }
+                }
+                if ctl.check_exit() {
+                    break;
+                }
+            }
+            ctl.drain(&mut buffer, &to)
+        })
+    }
+    fn create_fan_out_thread(
+        from: SocketWrapper,
+        mut outputs: Vec<FanOutOutput>,
+        ctl: BindingCtl,
+    ) -> JoinHandle<Result<()>> {
+        let sinks = outputs
+            .iter()
+            .map(|output| output.sock.lock().unwrap().get_description())
+            .collect::<Vec<_>>()
+            .join(",");
+        let span = tracing::info_span!(
+            "binding",
+            id = ctl.id,
+            from = %from.get_description(),
+            to = %sinks
+        );
+        ctl.spawn(span, move |ctl| {
+            let mut buf: Vec<u8> = Vec::with_capacity(ctl.buffer_size);
+            let mut tail = Vec::new();
+            let mut finished = false;
+            while ctl.is_running() {
+                if ctl.is_paused() {
+                    thread::sleep(ctl.poll_interval);
+                    continue;
+                }
+                buf.clear();
+                // The fullest buffer limits the read with the blocking policy
+                let limit = outputs.iter().map(|o| o.buffer.read_limit()).min();
+                let limit = limit.unwrap_or_default();
+                if ctl.is_finished(from.read_bytes_chunked(&mut buf, limit, ctl.read_chunk))? {
+                    finished = true;
+                    break;
+                }
+                ctl.inspect(&mut tail, &buf);
+                outputs.iter_mut().for_each(|o| o.buffer.push(&buf));
+                if outputs.iter().all(|o| o.buffer.is_empty()) {
+                    poll::wait_or_sleep(from.poll_handles(), ctl.poll_interval)?;
+                } else {
+                    let mut written = 0;
+                    outputs.retain_mut(|o| match o.buffer.flush_to(&o.sock) {
+                        Ok(len) => {
+                            // Every sink is accounted, as the other bindings
+                            // account the written data
+                            ctl.record(len);
+                            written += len;
+                            true
+                        }
+                        Err(e) => {
+                            let sink = o.sock.lock().unwrap().get_description();
+                            tracing::warn!("Sink {sink} failed, it's dropped: {e}");
+                            ctl.record_error();
+                            false
+                        }
+                    });
+                    if outputs.is_empty() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotConnected,
+                            "All fan-out sinks failed",
+                        ));
+                    }
+                    if written == 0 {
+                        // No sink is ready, the remainder is retried later
+                        thread::sleep(poll::FALLBACK_SLEEP);
+                    }
+                }
+                if ctl.check_exit() {
+                    break;
+                }
+            }
+            for output in &mut outputs {
+                if let Err(e) = ctl.drain(&mut output.buffer, &output.sock) {
+                    let sink = output.sock.lock().unwrap().get_description();
+                    tracing::warn!("Sink {sink} draining failed: {e}");
+                } else if finished {
+                    half_close(&output.sock);
                 }
-                // Yeld the thread
-                thread::sleep(Duration::from_micros(1));
             }
             Ok(())
         })
     }
+    fn create_fan_in_thread(
+        mut inputs: Vec<SocketWrapper>,
+        to: Mutex<SocketWrapper>,
+        ctl: BindingCtl,
+    ) -> JoinHandle<Result<()>> {
+        let sources = inputs
+            .iter()
+            .map(|input| input.get_description())
+            .collect::<Vec<_>>()
+            .join(",");
+        let span = tracing::info_span!(
+            "binding",
+            id = ctl.id,
+            from = %sources,
+            to = %to.lock().unwrap().get_description()
+        );
+        ctl.spawn(span, move |ctl| {
+            let mut buffer = ctl.new_buffer();
+            let mut buf: Vec<u8> = Vec::with_capacity(ctl.buffer_size);
+            // Patterns are searched in the data of every source separately
+            let mut tails = vec![Vec::new(); inputs.len()];
+            // Sources, which ran out of data, aren't failures
+            let mut failed = false;
+            while ctl.is_running() {
+                if ctl.is_paused() {
+                    thread::sleep(ctl.poll_interval);
+                    continue;
+                }
+                let mut written = 0;
+                // Every read chunk is flushed before the next source is read,
+                // so chunks of different sources aren't merged in the sink
+                let mut i = 0;
+                while i < inputs.len() {
+                    buf.clear();
+                    let limit = buffer.read_limit();
+                    if let Err(e) = inputs[i].read_bytes_chunked(&mut buf, limit, ctl.read_chunk) {
+                        let source = inputs.remove(i).get_description();
+                        tails.remove(i);
+                        if error::is_eof(&e) {
+                            tracing::info!("Source {source} has no more data, it's dropped");
+                        } else {
+                            tracing::warn!("Source {source} failed, it's dropped: {e}");
+                            ctl.record_error();
+                            failed = true;
+                        }
+                        continue;
+                    }
+                    ctl.inspect(&mut tails[i], &buf);
+                    buffer.push(&buf);
+                    let Some(flushed) = ctl.flush(&mut buffer, &to)? else {
+                        return Ok(());
+                    };
+                    written += flushed;
+                    i += 1;
+                }
+                ctl.record(written);
+                if inputs.is_empty() {
+                    if !failed {
+                        return ctl.finish(&mut buffer, &to);
+                    }
+                    ctl.drain(&mut buffer, &to)?;
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "All fan-in sources failed",
+                    ));
+                }
+                if buffer.is_empty() && written == 0 {
+                    let handles = inputs
+                        .iter()
+                        .map(|input| input.poll_handles())
+                        .collect::<Option<Vec<_>>>()
+                        .map(|handles| handles.concat());
+                    poll::wait_or_sleep(handles, ctl.poll_interval)?;
+                } else if written == 0 {
+                    // Sink isn't ready, the remainder is retried later
+                    thread::sleep(poll::FALLBACK_SLEEP);
+                }
+                if ctl.check_exit() {
+                    break;
+                }
+            }
+            ctl.drain(&mut buffer, &to)
+        })
+    }
+}
+
+impl BindingOptionsBuilder {
+    fn validate(&self) -> std::result::Result<(), String> {
+        if let Some(Some(cond)) = &self.exit_condition {
+            cond.check().map_err(|e| e.to_string())?;
+        }
+        let buffer_size = self.buffer_size.unwrap_or(buffer::DEFAULT_BUFFER_SIZE);
+        let read_chunk = self.read_chunk.unwrap_or(READ_CHUNK_SIZE);
+        for read_chunk in [Some(read_chunk), self.read_chunk_tx.flatten()].into_iter().flatten() {
+            if read_chunk == 0 {
+                return Err("Read chunk size must be non-zero".to_string());
+            }
+            if buffer_size < read_chunk {
+                return Err(format!(
+                    "Buffer size {buffer_size} is less than read chunk size {read_chunk}"
+                ));
+            }
+        }
+        if self.poll_interval == Some(Duration::ZERO)
+            || self.poll_interval_tx == Some(Some(Duration::ZERO))
+        {
+            return Err("Poll interval must be non-zero".to_string());
+        }
+        if let Some(Some(coalesce)) = self.coalesce
+            && !(1..=buffer_size).contains(&coalesce.size())
+        {
+            return Err(format!("Coalesced write size must be in 1..={buffer_size}"));
+        }
+        // Zero timeout is rejected by OS socket options
+        if let Some(Some(Duration::ZERO)) = self.read_timeout {
+            return Err("Read timeout must be non-zero".to_string());
+        }
+        if let Some(Some(Duration::ZERO)) = self.write_timeout {
+            return Err("Write timeout must be non-zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for BindingOptions {
+    fn default() -> Self {
+        BindingOptionsBuilder::default().build().unwrap()
+    }
+}
+
+impl BindingOptions {
+    /// Counters of the binding shared with the caller.
+    pub fn stats(&self) -> Option<&Arc<BindingStats>> {
+        self.stats.as_ref()
+    }
+}
+
+/// Id of the next binding, ids tell apart log events of the bindings.
+static NEXT_BINDING_ID: AtomicU32 = AtomicU32::new(1);
+
+/// State of one binding, shared by all its directions.
+#[derive(Clone)]
+pub(crate) struct BindingCtl {
+    id: u32,
+    running: Arc<AtomicBool>,
+    /// Set by the first failed direction of the binding
+    failed: Arc<AtomicBool>,
+    shutdown: Option<Arc<AtomicBool>>,
+    name: Option<String>,
+    paused: Option<Arc<AtomicBool>>,
+    stats: Arc<BindingStats>,
+    /// Direction accounted by the thread of the binding
+    flow: Flow,
+    exit_condition: Option<Arc<ExitCondition>>,
+    /// Patterns of the exit condition, searched in every read chunk
+    exit_patterns: Vec<BytePattern>,
+    buffer_size: usize,
+    overflow_policy: OverflowPolicy,
+    grace_period: Duration,
+    read_chunk: usize,
+    poll_interval: Duration,
+    /// Values of the tx direction, taken by `reversed`
+    read_chunk_tx: usize,
+    poll_interval_tx: Duration,
+    coalesce: Option<Coalesce>,
+    /// Line shared by both directions of the half-duplex binding
+    line: Option<Arc<HalfDuplexLine>>,
+    /// XON/XOFF state shared by both directions of the binding
+    flow_control: Option<Arc<FlowControl>>,
+}
+
+impl BindingCtl {
+    pub(crate) fn new(running: Arc<AtomicBool>, opts: &BindingOptions) -> Self {
+        Self {
+            id: NEXT_BINDING_ID.fetch_add(1, Ordering::Relaxed),
+            running,
+            failed: Arc::default(),
+            shutdown: opts.shutdown.clone(),
+            name: opts.name.clone(),
+            paused: opts.paused.clone(),
+            stats: opts.stats.clone().unwrap_or_default(),
+            flow: Flow::FromTo,
+            exit_condition: opts.exit_condition.clone().map(Arc::new),
+            exit_patterns: opts
+                .exit_condition
+                .as_ref()
+                .map(ExitCondition::patterns)
+                .unwrap_or_default(),
+            buffer_size: opts.buffer_size,
+            overflow_policy: opts.overflow_policy,
+            grace_period: opts.grace_period,
+            read_chunk: opts.read_chunk,
+            poll_interval: opts.poll_interval,
+            read_chunk_tx: opts.read_chunk_tx.unwrap_or(opts.read_chunk),
+            poll_interval_tx: opts.poll_interval_tx.unwrap_or(opts.poll_interval),
+            coalesce: opts.coalesce,
+            line: opts.half_duplex.as_ref().map(|cfg| Arc::new(HalfDuplexLine::new(cfg))),
+            flow_control: opts.xon_xoff.map(|sides| Arc::new(FlowControl::new(sides))),
+        }
+    }
+    /// Returns the state of the opposite direction of the binding.
+    pub(crate) fn reversed(&self) -> Self {
+        Self {
+            flow: Flow::ToFrom,
+            read_chunk: self.read_chunk_tx,
+            poll_interval: self.poll_interval_tx,
+            ..self.clone()
+        }
+    }
+    /// Runs the direction in its own thread inside the span, see
+    /// [`Self::settle`].
+    fn spawn<F>(self, span: Span, body: F) -> JoinHandle<Result<()>>
+    where
+        F: FnOnce(&Self) -> Result<()> + Send + 'static,
+    {
+        thread::spawn(move || {
+            let _span = span.enter();
+            self.settle(body(&self))
+        })
+    }
+    /// Accounts the result of the finished direction. Failure of the
+    /// direction stops the whole binding, so the opposite direction doesn't
+    /// run until Ctrl-C. Only the first failure is returned, the failures of
+    /// the directions stopped after it are logged.
+    pub(crate) fn settle(&self, res: Result<()>) -> Result<()> {
+        let Err(e) = res else {
+            return Ok(());
+        };
+        self.stats.record_error();
+        if self.failed.swap(true, Ordering::Relaxed) {
+            tracing::debug!("Binding is already failed, the error is dropped: {e}");
+            return Ok(());
+        }
+        if self.running.swap(false, Ordering::Relaxed) {
+            tracing::info!("Binding direction failed, binding is stopped");
+        }
+        Err(e)
+    }
+    /// Span of one binding direction, log events inside it get the binding
+    /// id and the socket descriptions.
+    pub(crate) fn span(&self, from: &Mutex<SocketWrapper>, to: &Mutex<SocketWrapper>) -> Span {
+        let from = from.lock().unwrap().get_description();
+        let to = to.lock().unwrap().get_description();
+        let span = tracing::info_span!(
+            "binding",
+            id = self.id,
+            link = tracing::field::Empty,
+            from = %from,
+            to = %to
+        );
+        if let Some(name) = &self.name {
+            span.record("link", name.as_str());
+        }
+        span
+    }
+    /// Creates buffer for one direction of the binding.
+    pub(crate) fn new_buffer(&self) -> RingBuffer {
+        RingBuffer::new(self.buffer_size, self.overflow_policy)
+    }
+    pub(crate) fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+            && self
+                .shutdown
+                .as_ref()
+                .is_none_or(|shutdown| shutdown.load(Ordering::Relaxed))
+    }
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+            .as_ref()
+            .is_some_and(|paused| paused.load(Ordering::Relaxed))
+    }
+    /// Returns `true`, if the direction may write to its sink: the sink
+    /// hasn't sent XOFF and the direction holds the half-duplex line or the
+    /// binding is full-duplex.
+    pub(crate) fn take_line(&self) -> bool {
+        if let Some(control) = &self.flow_control
+            && control.is_paused(self.flow)
+        {
+            return false;
+        }
+        self.line.as_ref().is_none_or(|line| line.take(self.flow))
+    }
+    /// Returns time to wait for more data before the buffer is written, if
+    /// writes are coalesced. The buffer, which can't take the next read
+    /// chunk, is written without waiting.
+    pub(crate) fn coalesce_wait(&self, buffer: &RingBuffer) -> Option<Duration> {
+        let coalesce = self.coalesce.as_ref()?;
+        if buffer.read_limit() < self.read_chunk {
+            return None;
+        }
+        coalesce
+            .wait(buffer.len(), buffer.since()?)
+            .map(|wait| wait.min(self.poll_interval))
+    }
+    /// Releases the half-duplex line, when the buffer is written to the
+    /// sink, `last` is the last written byte.
+    pub(crate) fn release_line(&self, buffer: &RingBuffer, last: Option<u8>) {
+        if let Some(line) = &self.line
+            && buffer.is_empty()
+        {
+            line.release(self.flow, last);
+        }
+    }
+    /// Registers forwarded chunk of `len` bytes.
+    pub(crate) fn record(&self, len: usize) {
+        self.stats.record_flow(self.flow, len);
+    }
+    /// Registers failure of the dropped peer (e.g. fan-out sink).
+    pub(crate) fn record_error(&self) {
+        self.stats.record_error();
+    }
+    /// Returns `true` if the read failed, because the source has no more
+    /// data: the direction is finished without error. Other errors are
+    /// returned.
+    pub(crate) fn is_finished(&self, res: Result<usize>) -> Result<bool> {
+        match res {
+            Ok(_) => Ok(false),
+            Err(e) if error::is_eof(&e) => {
+                tracing::info!("Source has no more data, binding is stopped");
+                Ok(true)
+            }
+            Err(e) => Err(e),
+        }
+    }
+    /// Finishes the direction, whose source has no more data: the rest of
+    /// the buffer is flushed and the sink is half-closed, so its peer gets
+    /// the end of data too. The opposite direction is stopped.
+    pub(crate) fn finish(&self, buffer: &mut RingBuffer, to: &Mutex<SocketWrapper>) -> Result<()> {
+        self.running.store(false, Ordering::Relaxed);
+        self.drain(buffer, to)?;
+        half_close(to);
+        Ok(())
+    }
+    /// Flushes the buffer to the sink. `None` is returned, if the sink
+    /// takes no more data (e.g. test-check verified all iterations): the
+    /// binding is stopped without error.
+    pub(crate) fn flush(
+        &self,
+        buffer: &mut RingBuffer,
+        to: &Mutex<SocketWrapper>,
+    ) -> Result<Option<usize>> {
+        self.check_flush(buffer.flush_to(to))
+    }
+    /// Checks the result of the buffer flush, see [`Self::flush`].
+    pub(crate) fn check_flush(&self, res: Result<usize>) -> Result<Option<usize>> {
+        match res {
+            Ok(written) => Ok(Some(written)),
+            Err(e) if error::is_eof(&e) => {
+                if self.running.swap(false, Ordering::Relaxed) {
+                    tracing::info!("Sink takes no more data, binding is stopped");
+                }
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+    /// Strips XON/XOFF bytes from the read chunk and pauses or resumes the
+    /// opposite direction.
+    pub(crate) fn strip_flow_control(&self, data: &mut Vec<u8>) {
+        if let Some(control) = &self.flow_control {
+            control.inspect(self.flow, data);
+        }
+    }
+    /// Searches patterns of the exit condition in the read chunk. The tail
+    /// of the previous chunks of the source is kept in `tail`, so the
+    /// pattern split between the reads is found too.
+    pub(crate) fn inspect(&self, tail: &mut Vec<u8>, data: &[u8]) {
+        if data.is_empty() || self.exit_patterns.is_empty() {
+            return;
+        }
+        tail.extend_from_slice(data);
+        for pattern in &self.exit_patterns {
+            if pattern.matches(tail) {
+                self.stats.record_match(pattern);
+            }
+        }
+        let keep = self.exit_patterns.iter().map(BytePattern::carry_over).max();
+        tail.drain(..tail.len().saturating_sub(keep.unwrap_or_default()));
+    }
+    /// Stops every direction of the binding, when exit condition is met.
+    /// Returns `true` if the binding is stopped.
+    pub(crate) fn check_exit(&self) -> bool {
+        if let Some(cond) = &self.exit_condition
+            && cond.is_met(&self.stats)
+        {
+            // Report only once for all directions of the binding
+            if self.running.swap(false, Ordering::Relaxed) {
+                tracing::info!("Exit condition is met, binding is stopped");
+            }
+            return true;
+        }
+        false
+    }
+    /// Flushes data left in the buffer after the binding is stopped. A slow
+    /// sink is waited for up to the grace period, then the rest is dropped.
+    pub(crate) fn drain(&self, buffer: &mut RingBuffer, to: &Mutex<SocketWrapper>) -> Result<()> {
+        let deadline = Instant::now() + self.grace_period;
+        while !buffer.is_empty() {
+            let Some(written) = self.flush(buffer, to)? else {
+                break;
+            };
+            self.record(written);
+            if written == 0 {
+                if Instant::now() >= deadline {
+                    tracing::warn!(
+                        "Grace period expired, {} buffered bytes are dropped",
+                        buffer.len()
+                    );
+                    break;
+                }
+                thread::sleep(poll::FALLBACK_SLEEP);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Half-closes the sink, whose source is finished. The sink, which can't
+/// be half-closed, is just left open.
+fn half_close(to: &Mutex<SocketWrapper>) {
+    let to = to.lock().unwrap();
+    if let Err(e) = to.shutdown_write() {
+        tracing::warn!("Sink {} isn't half-closed: {e}", to.get_description());
+    }
 }
 
 pub struct SocketWrapper {
     simple_sock: Box<dyn ComplexSock>,
+    /// Intermediate byte buffer reused by read/write operations
+    scratch: RefCell<Vec<u8>>,
 }
 
 impl SocketWrapper {
     pub fn new(simple_sock: Box<dyn ComplexSock>) -> Self {
-        Self { simple_sock }
+        Self {
+            simple_sock,
+            scratch: RefCell::new(Vec::new()),
+        }
     }
     pub fn open(mut self) -> io::Result<Self> {
-        self.simple_sock.open()?;
+        self.simple_sock
+            .open()
+            .map_err(|e| self.context(Op::Open, e))?;
         Ok(self)
     }
+    /// Adds description of the socket and the operation to its error.
+    fn context(&self, op: Op, e: io::Error) -> io::Error {
+        error::Error::io(|| self.get_description(), op, e)
+    }
     fn close(&mut self) {
         self.simple_sock.close();
     }
     pub fn get_simple_sock(&self) -> &dyn SimpleSock {
         &*self.simple_sock
     }
+    pub fn get_sock_info(&self) -> &dyn SockInfo {
+        &*self.simple_sock
+    }
+    pub fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        self.simple_sock.poll_handles()
+    }
+    pub fn get_description(&self) -> String {
+        self.simple_sock.get_description()
+    }
     /// Reads a vector of generic type T of size `sz`.
+    #[allow(unused)]
     pub fn generic_read<T>(&self, sz: usize) -> Result<Vec<T>> {
-        let bytes_needed = size_of::<T>() * sz;
-        let mut buffer = vec![0u8; bytes_needed];
+        let mut result = Vec::new();
+        self.generic_read_into(&mut result, sz)?;
+        Ok(result)
+    }
+
+    /// Reads up to `sz` elements of generic type T and appends them to
+    /// `out`. Returns number of appended elements.
+    pub fn generic_read_into<T>(&self, out: &mut Vec<T>, sz: usize) -> Result<usize> {
+        if size_of::<T>() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Zero-sized elements can't be read",
+            ));
+        }
+        if sz == 0 {
+            return Ok(0);
+        }
+        let bytes_needed = size_of::<T>().checked_mul(sz).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Requested size of {sz} elements is too large"),
+            )
+        })?;
+        let mut buffer = self.scratch.borrow_mut();
+        buffer.resize(bytes_needed, 0);
         let mut bytes_read = 0;
 
         while bytes_read < bytes_needed {
             let chunk_iter = bytes_needed - bytes_read;
             let chunk = self
                 .get_simple_sock()
-                .read(&mut buffer[bytes_read..], chunk_iter)?;
+                .read(&mut buffer[bytes_read..], chunk_iter)
+                .and_then(|chunk| check_read_len(chunk, chunk_iter).map(|_| chunk))
+                .map_err(|e| self.context(Op::Read, e));
+            let chunk = match chunk {
+                // End of data is reported by the next read
+                Err(e) if bytes_read > 0 && error::is_eof(&e) => break,
+                res => res?,
+            };
             bytes_read += chunk;
             if chunk < chunk_iter {
                 break;
@@ -216,7 +1275,7 @@ impl SocketWrapper {
 
         // Convert bytes to Vec<T> safely
         let num_elements = bytes_read / size_of::<T>();
-        let mut result = Vec::with_capacity(num_elements);
+        out.reserve(num_elements);
 
         for i in 0..num_elements {
             let start = i * size_of::<T>();
@@ -225,16 +1284,18 @@ impl SocketWrapper {
 
             // Use unsafe only for the necessary conversion
             let value = unsafe { std::ptr::read(bytes.as_ptr() as *const T) };
-            result.push(value);
+            out.push(value);
         }
 
-        Ok(result)
+        Ok(num_elements)
     }
 
-    /// Writes a slice of generic type T.
-    pub fn generic_write<T>(&self, data: &[T], sz: usize) -> Result<()> {
-        let bytes_needed = size_of::<T>() * sz;
-        let mut buffer = vec![0u8; bytes_needed];
+    /// Writes a slice of generic type T. Returns number of written bytes.
+    pub fn generic_write<T>(&self, data: &[T], sz: usize) -> Result<usize> {
+        check_buffer_size(sz, data.len())?;
+        let bytes_needed = size_of_val(&data[..sz]);
+        let mut buffer = self.scratch.borrow_mut();
+        buffer.resize(bytes_needed, 0);
 
         // Copy data to buffer safely
         unsafe {
@@ -245,24 +1306,111 @@ impl SocketWrapper {
             );
         }
 
-        self.get_simple_sock().write(&buffer, bytes_needed)
+        self.get_simple_sock()
+            .write(&buffer, bytes_needed)
+            .map_err(|e| self.context(Op::Write, e))
     }
 
     /// Reads all available data of type T in chunks.
     pub fn read_all<T>(&self) -> Result<Vec<T>> {
-        const CHUNK_SIZE: usize = 1024; // Reasonable chunk size
+        self.read_bounded(usize::MAX)
+    }
+
+    /// Reads available data of type T in chunks, until `max` elements
+    /// are read. Chunk is read only if it fits into the remaining space.
+    pub fn read_bounded<T>(&self, max: usize) -> Result<Vec<T>> {
         let mut result = Vec::new();
+        Self::read_chunks(max, READ_CHUNK_SIZE, |sz| self.generic_read_into(&mut result, sz))?;
+        Ok(result)
+    }
+
+    /// Byte version of `read_bounded`: data is read straight into the tail
+    /// of `out` without intermediate copies, so the caller can reuse one
+    /// vector between reads. Returns number of appended bytes.
+    pub fn read_bytes_into(&self, out: &mut Vec<u8>, max: usize) -> Result<usize> {
+        self.read_bytes_chunked(out, max, READ_CHUNK_SIZE)
+    }
+
+    /// `read_bytes_into` with `chunk` bytes per read operation.
+    pub fn read_bytes_chunked(&self, out: &mut Vec<u8>, max: usize, chunk: usize) -> Result<usize> {
+        Self::read_chunks(max, chunk, |sz| self.read_chunk_into(out, sz))
+    }
 
-        loop {
-            let chunk = self.generic_read::<T>(CHUNK_SIZE)?;
-            if chunk.len() < CHUNK_SIZE {
-                result.extend(chunk);
+    /// Writes bytes straight to the socket without intermediate copies.
+    /// Returns number of written bytes.
+    pub fn write_bytes(&self, data: &[u8]) -> Result<usize> {
+        self.get_simple_sock()
+            .write(data, data.len())
+            .map_err(|e| self.context(Op::Write, e))
+    }
+
+    /// Marks the end of the written message.
+    pub fn flush_message(&self) -> Result<()> {
+        self.simple_sock
+            .flush_message()
+            .map_err(|e| self.context(Op::Flush, e))
+    }
+
+    /// Reports the end of data to the peer, see
+    /// [`SockBlockCtl::shutdown_write`].
+    pub fn shutdown_write(&self) -> Result<()> {
+        self.simple_sock
+            .shutdown_write()
+            .map_err(|e| self.context(Op::Shutdown, e))
+    }
+
+    /// Calls `read_chunk` while whole chunks of `chunk_size` fit into `max`
+    /// and the socket returns full chunks. Returns total number of read
+    /// elements.
+    fn read_chunks(
+        max: usize,
+        chunk_size: usize,
+        mut read_chunk: impl FnMut(usize) -> Result<usize>,
+    ) -> Result<usize> {
+        let mut total = 0;
+
+        while max - total >= chunk_size {
+            let read = match read_chunk(chunk_size) {
+                // End of data is reported by the next read
+                Err(e) if total > 0 && error::is_eof(&e) => break,
+                res => res?,
+            };
+            total += read;
+            if read < chunk_size {
                 break;
             }
-            result.extend(chunk);
         }
 
-        Ok(result)
+        Ok(total)
+    }
+
+    /// Reads up to `sz` bytes into the tail of `out`.
+    fn read_chunk_into(&self, out: &mut Vec<u8>, sz: usize) -> Result<usize> {
+        let start = out.len();
+        out.resize(start + sz, 0);
+        let mut bytes_read = 0;
+
+        while bytes_read < sz {
+            let chunk_iter = sz - bytes_read;
+            let chunk = self
+                .get_simple_sock()
+                .read(&mut out[start + bytes_read..], chunk_iter)
+                .and_then(|chunk| check_read_len(chunk, chunk_iter).map(|_| chunk))
+                .map_err(|e| self.context(Op::Read, e))
+                .inspect_err(|_| out.truncate(start + bytes_read));
+            let chunk = match chunk {
+                // End of data is reported by the next read
+                Err(e) if bytes_read > 0 && error::is_eof(&e) => break,
+                res => res?,
+            };
+            bytes_read += chunk;
+            if chunk < chunk_iter {
+                break;
+            }
+        }
+
+        out.truncate(start + bytes_read);
+        Ok(bytes_read)
     }
 }
 
@@ -273,7 +1421,7 @@ impl Drop for SocketWrapper {
 }
 
 macro_rules! make_simple_sock {
-    ($name: ident { $($field:ident : $t:ty),* $(,)? }, $stype: expr $(, $self_ident: ident, $sock_descr: block)?) => {
+    ($name: ident { $($field:ident : $t:ty),* $(,)? }, $stype: expr $(, $self_ident: ident, $sock_descr: block)? $(; info: { $($info: item)* })?) => {
         paste::paste! {
             use crate::sock::SockInfo;
             use std::sync::atomic::AtomicU32 as IdAtomic;
@@ -286,6 +1434,7 @@ macro_rules! make_simple_sock {
                 $($field: $t),*
             }
             impl $name {
+                #[allow(clippy::too_many_arguments)]
                 pub fn new($($field: $t),*) -> Self {
                     Self {
                         id: [<$name _id>].fetch_add(1, IdOrdering::Relaxed),
@@ -306,8 +1455,480 @@ macro_rules! make_simple_sock {
                         $sock_descr
                     }
                 )?
+                // Other SockInfo methods overridden by the socket
+                $($($info)*)?
             }
         }
     };
 }
 pub(crate) use make_simple_sock;
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+
+    make_simple_sock!(Loopback { data: Mutex<Vec<u8>>, extra: usize }, "loopback");
+
+    impl SimpleSock for Loopback {
+        /// Returns `extra` bytes more than requested, if it's set
+        fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+            let mut pending = self.data.lock().unwrap();
+            let len = pending.len().min(sz);
+            data[..len].copy_from_slice(&pending[..len]);
+            pending.drain(..len);
+            Ok(len + self.extra)
+        }
+        fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+            self.data.lock().unwrap().extend(&data[..sz]);
+            Ok(sz)
+        }
+    }
+    impl SockBlockCtl for Loopback {}
+    impl SockPoll for Loopback {}
+
+    fn loopback(extra: usize) -> SocketWrapper {
+        SocketWrapper::new(Box::new(Loopback::new(Mutex::new(Vec::new()), extra)))
+    }
+
+    #[test]
+    fn invalid_read_sizes() {
+        let sock = loopback(0);
+        assert!(sock.generic_read::<u32>(0).unwrap().is_empty());
+        assert!(sock.generic_read::<()>(1).is_err());
+        assert!(sock.generic_read::<u64>(usize::MAX).is_err());
+        assert!(sock.generic_write(&[1u8, 2], 3).is_err());
+        // Socket returning more than requested is an error, not a panic
+        let sock = loopback(1);
+        sock.write_bytes(b"data").unwrap();
+        let err = sock.generic_read::<u8>(2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_chunk_size() {
+        let sock = loopback(0);
+        sock.write_bytes(b"0123456789").unwrap();
+        let mut out = Vec::new();
+        // Only whole chunks fit into the limit
+        assert_eq!(sock.read_bytes_chunked(&mut out, 3, 4).unwrap(), 0);
+        assert_eq!(sock.read_bytes_chunked(&mut out, 11, 4).unwrap(), 8);
+        assert_eq!(out, b"01234567");
+
+        let build = |chunk, buffer, poll| {
+            BindingOptionsBuilder::default()
+                .read_chunk(chunk)
+                .buffer_size(buffer)
+                .poll_interval(poll)
+                .build()
+        };
+        let poll = Duration::from_micros(100);
+        assert!(build(256, 512, poll).is_ok());
+        assert!(build(0, 512, poll).is_err());
+        assert!(build(1024, 512, poll).is_err());
+        assert!(build(256, 512, Duration::ZERO).is_err());
+
+        // Directions of the bidirectional binding are tuned separately
+        let opts = BindingOptionsBuilder::default()
+            .read_chunk(16)
+            .read_chunk_tx(Some(4096))
+            .poll_interval_tx(Some(poll))
+            .build()
+            .unwrap();
+        let rx = BindingCtl::new(Arc::new(AtomicBool::new(true)), &opts);
+        let tx = rx.reversed();
+        assert_eq!((rx.read_chunk, rx.poll_interval), (16, POLL_TIMEOUT));
+        assert_eq!((tx.read_chunk, tx.poll_interval), (4096, poll));
+        let tx_chunk = |chunk| BindingOptionsBuilder::default().read_chunk_tx(Some(chunk)).build();
+        assert!(tx_chunk(0).is_err());
+        assert!(tx_chunk(1 << 30).is_err());
+    }
+
+    #[test]
+    fn pattern_split_between_reads() {
+        let bye: BytePattern = serde_json::from_str("{ \"text\": \"BYE\" }").unwrap();
+        let stats = Arc::new(BindingStats::new());
+        let opts = BindingOptionsBuilder::default()
+            .stats(Some(stats.clone()))
+            .exit_condition(Some(ExitCondition::Pattern(bye.clone())))
+            .build()
+            .unwrap();
+        let ctl = BindingCtl::new(Arc::new(AtomicBool::new(true)), &opts);
+        let (mut tail, mut other) = (Vec::new(), Vec::new());
+        ctl.inspect(&mut tail, b"data B");
+        // Tail of the other source isn't joined with the chunk
+        ctl.inspect(&mut other, b"YE");
+        assert!(!stats.is_matched(&bye));
+        ctl.inspect(&mut tail, b"YE");
+        assert!(stats.is_matched(&bye));
+        assert_eq!(tail, b"YE");
+    }
+
+    mod multi {
+        use super::*;
+
+        make_simple_sock!(Shared { data: Arc<Mutex<Vec<u8>>>, broken: bool }, "shared");
+
+        impl SimpleSock for Shared {
+            fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+                if self.broken {
+                    return Err(io::Error::from(io::ErrorKind::ConnectionReset));
+                }
+                let mut pending = self.data.lock().unwrap();
+                let len = pending.len().min(sz);
+                data[..len].copy_from_slice(&pending[..len]);
+                pending.drain(..len);
+                Ok(len)
+            }
+            fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+                if self.broken {
+                    return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+                }
+                self.data.lock().unwrap().extend(&data[..sz]);
+                Ok(sz)
+            }
+        }
+        impl SockBlockCtl for Shared {}
+        impl SockPoll for Shared {}
+
+        /// Creates sockets over the buffer selected by index in the params,
+        /// the "broken" params create a socket failing every read and write.
+        #[derive(Clone)]
+        struct SharedFactory(Vec<Arc<Mutex<Vec<u8>>>>);
+
+        impl SocketFactory for SharedFactory {
+            fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+                let (data, broken) = match params.value().as_u64() {
+                    Some(i) => (self.0[i as usize].clone(), false),
+                    None => (Arc::default(), true),
+                };
+                Ok(Box::new(Shared::new(data, broken)))
+            }
+        }
+
+        /// Parameters of the shared socket, anything but the index is broken.
+        fn sock_params(params: &str) -> SocketParams {
+            SocketParams::parse(params).unwrap_or_default()
+        }
+
+        #[test]
+        fn fan_out_drops_failed_sink() {
+            let buffers: Vec<Arc<Mutex<Vec<u8>>>> = (0..3).map(|_| Arc::default()).collect();
+            buffers[0].lock().unwrap().extend(b"data");
+            let factory = SharedFactory(buffers.clone());
+            let manager = SocketManager::new(&factory, &factory);
+            let sinks: Vec<_> = ["1", "broken", "2"]
+                .into_iter()
+                .map(|params| FanOutSink {
+                    factory: &factory,
+                    params: sock_params(params),
+                })
+                .collect();
+            let build = |stats| {
+                BindingOptionsBuilder::default()
+                    .exit_condition(Some(ExitCondition::Bytes(4)))
+                    .stats(stats)
+                    .build()
+                    .unwrap()
+            };
+            let stats = Arc::new(BindingStats::new());
+            let (h, _) = manager
+                .bind_fan_out(&sock_params("0"), &sinks, false, &build(Some(stats.clone())))
+                .unwrap();
+            h.join().unwrap().unwrap();
+            assert_eq!(*buffers[1].lock().unwrap(), b"data");
+            assert_eq!(*buffers[2].lock().unwrap(), b"data");
+            // Data written to every live sink is accounted
+            assert_eq!((stats.bytes(), stats.messages()), (8, 2));
+            let opts = build(Some(Arc::default()));
+
+            // Binding fails, when every sink is dead
+            buffers[0].lock().unwrap().extend(b"data");
+            let sinks = [FanOutSink {
+                factory: &factory,
+                params: sock_params("broken"),
+            }];
+            let (h, _) = manager
+                .bind_fan_out(&sock_params("0"), &sinks, false, &opts)
+                .unwrap();
+            assert!(h.join().unwrap().is_err());
+        }
+
+        #[test]
+        fn fan_in_drops_failed_source() {
+            let buffers: Vec<Arc<Mutex<Vec<u8>>>> = (0..3).map(|_| Arc::default()).collect();
+            buffers[0].lock().unwrap().extend(b"ab");
+            buffers[1].lock().unwrap().extend(b"cd");
+            let factory = SharedFactory(buffers.clone());
+            let tagged =
+                TransformDecoratorFactory::new(Box::new(factory.clone()), tag_builder(b"1:"));
+            let manager = SocketManager::new(&factory, &factory);
+            let sources = [
+                FanInSource {
+                    factory: &factory,
+                    params: sock_params("0"),
+                },
+                FanInSource {
+                    factory: &factory,
+                    params: sock_params("broken"),
+                },
+                FanInSource {
+                    factory: tagged.as_ref(),
+                    params: sock_params("1"),
+                },
+            ];
+            let opts = BindingOptionsBuilder::default()
+                .exit_condition(Some(ExitCondition::Bytes(6)))
+                .build()
+                .unwrap();
+            let (h, _) = manager
+                .bind_fan_in(&sources, &sock_params("2"), &opts)
+                .unwrap();
+            h.join().unwrap().unwrap();
+            assert_eq!(*buffers[2].lock().unwrap(), b"ab1:cd");
+        }
+
+        #[test]
+        fn exit_on_pattern() {
+            let buffers: Vec<Arc<Mutex<Vec<u8>>>> = (0..2).map(|_| Arc::default()).collect();
+            buffers[0].lock().unwrap().extend(b"data BYE");
+            let factory = SharedFactory(buffers.clone());
+            let manager = SocketManager::new(&factory, &factory);
+            let bye = serde_json::from_str("{ \"text\": \"BYE\" }").unwrap();
+            let opts = BindingOptionsBuilder::default()
+                .exit_condition(Some(ExitCondition::Pattern(bye)))
+                .build()
+                .unwrap();
+            let (h, _) = manager
+                .bind_unidirectional(&sock_params("0"), &sock_params("1"), false, &opts)
+                .unwrap();
+            // Chunk with the pattern is still forwarded
+            h.join().unwrap().unwrap();
+            assert_eq!(*buffers[1].lock().unwrap(), b"data BYE");
+        }
+
+        #[test]
+        fn source_eof_finishes_binding() {
+            let buffers: Vec<Arc<Mutex<Vec<u8>>>> = vec![Arc::default()];
+            let sink = SharedFactory(buffers.clone());
+            let source = crate::sockets::testgen::TestGenFactory::new();
+            let manager = SocketManager::new(&source, &sink);
+            let params =
+                r#"{ "pat": { "type": "text_str", "data": "ping" }, "cycle": 0, "iter_num": 3 }"#;
+            // No exit condition, the binding is finished by the end of data
+            let (h, _) = manager
+                .bind_unidirectional(
+                    &sock_params(params),
+                    &sock_params("0"),
+                    false,
+                    &BindingOptions::default(),
+                )
+                .unwrap();
+            h.join().unwrap().unwrap();
+            assert_eq!(*buffers[0].lock().unwrap(), b"pingpingping");
+
+            // The opposite direction is stopped too
+            let (h1, h2, running) = manager
+                .bind_bidirectional(
+                    &sock_params(params),
+                    &sock_params("0"),
+                    &BindingOptions::default(),
+                )
+                .unwrap();
+            h1.join().unwrap().unwrap();
+            h2.join().unwrap().unwrap();
+            assert!(!running.load(Ordering::Relaxed));
+        }
+
+        #[test]
+        fn failure_stops_binding() {
+            let buffers: Vec<Arc<Mutex<Vec<u8>>>> = vec![Arc::default()];
+            let factory = SharedFactory(buffers);
+            let manager = SocketManager::new(&factory, &factory);
+            // Direction from the healthy socket has no data, it would wait
+            // forever without the failure of the opposite one
+            let (h1, h2, running) = manager
+                .bind_bidirectional(
+                    &sock_params("0"),
+                    &sock_params("broken"),
+                    &BindingOptions::default(),
+                )
+                .unwrap();
+            h1.join().unwrap().unwrap();
+            let err = h2.join().unwrap().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+            assert!(!running.load(Ordering::Relaxed));
+        }
+
+        mod ports {
+            use super::*;
+
+            /// Writes of every port: index of the port, byte and time (the same
+            /// for the bytes of one write)
+            type PortLog = Arc<Mutex<Vec<(u8, u8, Instant)>>>;
+
+            make_simple_sock!(Port { port: u8, input: Mutex<Vec<u8>>, log: PortLog }, "port");
+
+            impl SimpleSock for Port {
+                /// Input is read byte by byte
+                fn read(&self, data: &mut [u8], sz: usize) -> Result<usize> {
+                    let mut input = self.input.lock().unwrap();
+                    if input.is_empty() || sz == 0 {
+                        return Ok(0);
+                    }
+                    data[0] = input.remove(0);
+                    Ok(1)
+                }
+                fn write(&self, data: &[u8], sz: usize) -> Result<usize> {
+                    let mut log = self.log.lock().unwrap();
+                    let now = Instant::now();
+                    log.extend(data[..sz].iter().map(|b| (self.port, *b, now)));
+                    Ok(sz)
+                }
+            }
+            impl SockBlockCtl for Port {}
+            impl SockPoll for Port {}
+
+            /// Creates the port of the index in the params with its input.
+            struct PortFactory(Vec<&'static [u8]>, PortLog);
+
+            impl SocketFactory for PortFactory {
+                fn create_sock(&self, params: SocketParams) -> Result<Box<dyn ComplexSock>> {
+                    let port = params.value().as_u64().unwrap() as u8;
+                    let input = Mutex::new(self.0[port as usize].to_vec());
+                    Ok(Box::new(Port::new(port, input, self.1.clone())))
+                }
+            }
+
+            #[test]
+            fn half_duplex_turnaround() {
+                let log = PortLog::default();
+                let factory = PortFactory(vec![b"abc\n", b"xyz\n"], log.clone());
+                let manager = SocketManager::new(&factory, &factory);
+                let line = r#"{ "turnaround_ms": 30, "terminator": "0x0a" }"#;
+                let opts = BindingOptionsBuilder::default()
+                    .exit_condition(Some(ExitCondition::Bytes(8)))
+                    .half_duplex(Some(serde_json::from_str(line).unwrap()))
+                    .build()
+                    .unwrap();
+                let (h1, h2, _) = manager
+                    .bind_bidirectional(&sock_params("0"), &sock_params("1"), &opts)
+                    .unwrap();
+                h1.join().unwrap().unwrap();
+                h2.join().unwrap().unwrap();
+                let log = log.lock().unwrap();
+                // Messages of the directions aren't interleaved
+                let written: Vec<u8> = log.iter().map(|(_, b, _)| *b).collect();
+                assert!(written == b"abc\nxyz\n" || written == b"xyz\nabc\n", "{written:?}");
+                let switch = log.windows(2).find(|w| w[0].0 != w[1].0).unwrap();
+                assert!(switch[1].2 - switch[0].2 >= Duration::from_millis(30));
+            }
+
+            #[test]
+            fn xon_xoff_stripped() {
+                let log = PortLog::default();
+                const INPUT: [u8; 4] = [flow_control::XOFF, b'a', b'b', flow_control::XON];
+                let factory = PortFactory(vec![&INPUT, b"xyz"], log.clone());
+                let manager = SocketManager::new(&factory, &factory);
+                let opts = BindingOptionsBuilder::default()
+                    .exit_condition(Some(ExitCondition::Bytes(5)))
+                    .xon_xoff(Some(XonXoff::From))
+                    .build()
+                    .unwrap();
+                let (h1, h2, _) = manager
+                    .bind_bidirectional(&sock_params("0"), &sock_params("1"), &opts)
+                    .unwrap();
+                h1.join().unwrap().unwrap();
+                h2.join().unwrap().unwrap();
+                let log = log.lock().unwrap();
+                let written = |port| -> Vec<u8> {
+                    log.iter().filter(|w| w.0 == port).map(|w| w.1).collect()
+                };
+                // Data held by XOFF is written after XON
+                assert_eq!(written(0), b"xyz");
+                assert_eq!(written(1), b"ab");
+            }
+
+            #[test]
+            fn coalesced_writes() {
+                let log = PortLog::default();
+                let factory = PortFactory(vec![b"abcdefgh", b""], log.clone());
+                let manager = SocketManager::new(&factory, &factory);
+                let coalesce = r#"{ "size": 4, "timeout_ms": 1000 }"#;
+                let opts = BindingOptionsBuilder::default()
+                    .exit_condition(Some(ExitCondition::Bytes(8)))
+                    .coalesce(Some(serde_json::from_str(coalesce).unwrap()))
+                    .build()
+                    .unwrap();
+                let (h, _) = manager
+                    .bind_unidirectional(&sock_params("0"), &sock_params("1"), false, &opts)
+                    .unwrap();
+                h.join().unwrap().unwrap();
+                // Bytes read one by one are written by 4
+                let log = log.lock().unwrap();
+                let writes = log.chunk_by(|a, b| a.2 == b.2).map(|w| w.len()).collect::<Vec<_>>();
+                assert_eq!(writes, [4, 4]);
+            }
+        }
+
+        #[test]
+        fn links_share_shutdown() {
+            let buffers: Vec<Arc<Mutex<Vec<u8>>>> = (0..4).map(|_| Arc::default()).collect();
+            buffers[0].lock().unwrap().extend(b"ab");
+            let factory = SharedFactory(buffers.clone());
+            let link = |name: &str, from: &str, to: &str, exit_when| Link {
+                name: name.to_string(),
+                in_factory: &factory,
+                out_factory: &factory,
+                in_params: sock_params(from),
+                out_params: sock_params(to),
+                bidir: false,
+                blocking: false,
+                opts: BindingOptionsBuilder::default()
+                    .exit_condition(exit_when)
+                    .build()
+                    .unwrap(),
+            };
+            let links = [
+                link("once", "0", "1", Some(ExitCondition::Bytes(2))),
+                link("idle", "2", "3", None),
+            ];
+            let (mut handles, shutdown) = SocketManager::bind_links(&links).unwrap();
+            // Exit condition stops only its own link
+            let (name, once) = handles.remove(0);
+            assert_eq!(name, "once");
+            once.into_iter().for_each(|h| h.join().unwrap().unwrap());
+            assert_eq!(*buffers[1].lock().unwrap(), b"ab");
+            assert!(shutdown.load(Ordering::Relaxed));
+            shutdown.store(false, Ordering::Relaxed);
+            let (_, idle) = handles.remove(0);
+            idle.into_iter().for_each(|h| h.join().unwrap().unwrap());
+
+            let links = [link("dup", "0", "1", None), link("dup", "2", "3", None)];
+            let err = SocketManager::bind_links(&links).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[cfg(test)]
+    proptest::proptest! {
+        #[test]
+        fn generic_read_write_sizes(
+            data in proptest::collection::vec(proptest::num::u16::ANY, 0..64),
+            write_sz in 0..80usize,
+            read_sz in 0..80usize,
+        ) {
+            let sock = loopback(0);
+            match sock.generic_write(&data, write_sz) {
+                Ok(written) => assert_eq!(written, write_sz * 2),
+                Err(e) => {
+                    assert!(write_sz > data.len());
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                    return Ok(());
+                }
+            }
+            let read: Vec<u16> = sock.generic_read(read_sz).unwrap();
+            assert_eq!(read, data[..write_sz.min(read_sz)]);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use crate::error::Error;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Parameters of the socket, parsed once from their sources (inline JSON,
+/// file, environment variables and `key=value` overrides) and handed to
+/// the factory as JSON value. `Null` means that no parameters are set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SocketParams(Value);
+
+impl SocketParams {
+    pub fn new(value: Value) -> Self {
+        Self(value)
+    }
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_null()
+    }
+    /// Parses inline JSON, empty string means no parameters.
+    pub fn parse(json: &str) -> serde_json::Result<Self> {
+        if json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(json).map(Self)
+    }
+    /// Loads the parameters from the JSON file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let at =
+            |kind, e: &dyn fmt::Display| io::Error::new(kind, format!("{}: {e}", path.display()));
+        let json = fs::read_to_string(path).map_err(|e| at(e.kind(), &e))?;
+        Self::parse(&json).map_err(|e| at(io::ErrorKind::InvalidData, &e))
+    }
+    /// Collects the fields set by the environment variables with the
+    /// prefix, e.g. `POLYSOCK_FROM_PORT_LOCAL=5151` with the prefix
+    /// `POLYSOCK_FROM_` sets `port_local`. Double underscore separates the
+    /// nested fields (`POLYSOCK_FROM_PAT__SIZE` sets `pat.size`).
+    pub fn from_env(prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut params = Self::default();
+        for (name, value) in vars {
+            if let Some(field) = name.strip_prefix(prefix).filter(|f| !f.is_empty()) {
+                params.set(&field.to_lowercase().replace("__", "."), &value);
+            }
+        }
+        params
+    }
+    /// Adds the layer over the parameters: fields of the objects are merged
+    /// recursively, other values of the layer replace the current ones.
+    pub fn merge(&mut self, layer: SocketParams) {
+        merge(&mut self.0, layer.0);
+    }
+    /// Sets the field by its dotted path, e.g. `pat.size`. The value is
+    /// taken as JSON, if it can be parsed, otherwise as a string.
+    pub fn set(&mut self, path: &str, value: &str) {
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::from(value));
+        let mut field = &mut self.0;
+        for key in path.split('.') {
+            if !field.is_object() {
+                *field = Value::Object(Map::new());
+            }
+            field = field
+                .as_object_mut()
+                .unwrap()
+                .entry(key)
+                .or_insert(Value::Null);
+        }
+        *field = value;
+    }
+    /// Expands placeholders of the string values: `${NAME}` is replaced
+    /// with the environment variable, `${NAME:-default}` with the default,
+    /// if the variable isn't set, and `$$` with `$`. The string, which is
+    /// one placeholder, is taken as JSON (e.g. `"port_dst": "${PORT}"` gets
+    /// a number), if the value can be parsed.
+    pub fn expand(&self, sock: &str) -> Result<Self, Error> {
+        self.expand_with(sock, |name| env::var(name).ok())
+    }
+    /// Expands placeholders with the values of the lookup.
+    pub fn expand_with(
+        &self,
+        sock: &str,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Error> {
+        let mut value = self.0.clone();
+        expand(&mut value, "", &lookup).map_err(|(field, message)| Error::InvalidValue {
+            sock: sock.to_string(),
+            field,
+            message,
+        })?;
+        Ok(Self(value))
+    }
+}
+
+/// Expands the strings of the value, the error has the path of the field.
+fn expand(
+    value: &mut Value,
+    path: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<(), (String, String)> {
+    match value {
+        Value::Object(map) => map.iter_mut().try_for_each(|(key, value)| {
+            let path = match path {
+                "" => key.to_string(),
+                path => format!("{path}.{key}"),
+            };
+            expand(value, &path, lookup)
+        }),
+        Value::Array(items) => items
+            .iter_mut()
+            .enumerate()
+            .try_for_each(|(i, value)| expand(value, &format!("{path}[{i}]"), lookup)),
+        Value::String(s) if s.contains('$') => {
+            *value = substitute(s, lookup).map_err(|e| (path.to_string(), e))?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn substitute(s: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<Value, String> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(tail) = rest.strip_prefix("$$") {
+            out.push('$');
+            rest = tail;
+            continue;
+        }
+        let Some(tail) = rest.strip_prefix("${") else {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let end = tail
+            .find('}')
+            .ok_or_else(|| format!("placeholder {rest} isn't closed"))?;
+        let (name, default) = match tail[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&tail[..end], None),
+        };
+        let value = lookup(name)
+            .or_else(|| default.map(str::to_string))
+            .ok_or_else(|| format!("variable {name} is not set"))?;
+        rest = &tail[end + 1..];
+        if out.is_empty() && rest.is_empty() {
+            return Ok(serde_json::from_str(&value).unwrap_or(Value::String(value)));
+        }
+        out.push_str(&value);
+    }
+    out.push_str(rest);
+    Ok(Value::String(out))
+}
+
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (_, Value::Null) => {}
+        (base, layer) => *base = layer,
+    }
+}
+
+impl From<Value> for SocketParams {
+    fn from(value: Value) -> Self {
+        Self(value)
+    }
+}
+
+impl FromStr for SocketParams {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> serde_json::Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// Compact JSON, no parameters are written as an empty string.
+impl fmt::Display for SocketParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Value::Null => Ok(()),
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Parses parameters of the socket type `sock` into its configuration. The
+/// error names the field, e.g. `port_local: invalid type: string "x",
+/// expected u16`. No parameters are parsed as an empty object, so the
+/// configuration with all fields optional gets the defaults. Placeholders
+/// of the values are expanded before, see [`SocketParams::expand`].
+pub fn parse_params<T: DeserializeOwned>(sock: &str, params: &SocketParams) -> Result<T, Error> {
+    let params = params.expand(sock)?;
+    let empty = Value::Object(Map::new());
+    let value = if params.is_empty() {
+        &empty
+    } else {
+        params.value()
+    };
+    serde_path_to_error::deserialize(value).map_err(|e| Error::Config {
+        sock: sock.to_string(),
+        field: Some(e.path().to_string()).filter(|path| path != "."),
+        source: e.into_inner(),
+    })
+}
+
+/// Returns top-level fields of the parameters, which aren't in the schema
+/// of the configuration type (e.g. typos of optional fields, which are
+/// ignored on socket creation).
+pub fn unknown_fields<T: JsonSchema>(params: &SocketParams) -> Vec<String> {
+    let (known, fields) = fields::<T>(params);
+    fields
+        .into_iter()
+        .filter(|field| !known.contains(field))
+        .collect()
+}
+
+/// Known fields of the configuration type and the fields of the parameters.
+fn fields<T: JsonSchema>(params: &SocketParams) -> (Vec<String>, Vec<String>) {
+    let schema = schemars::schema_for!(T);
+    let (Some(known), Some(fields)) = (
+        schema.get("properties").and_then(|p| p.as_object()),
+        params.value().as_object(),
+    ) else {
+        return (Vec::new(), Vec::new());
+    };
+    (
+        known.keys().cloned().collect(),
+        fields.keys().cloned().collect(),
+    )
+}
+
+/// Checks the parameters of the socket type `sock` without creating the
+/// socket, unknown fields are reported as errors.
+pub fn validate_params<T: DeserializeOwned + JsonSchema>(
+    sock: &str,
+    params: &SocketParams,
+) -> Result<(), Error> {
+    parse_params::<T>(sock, params)?;
+    let unknown = unknown_fields::<T>(params);
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    Err(Error::UnknownField {
+        sock: sock.to_string(),
+        fields: unknown,
+        expected: fields::<T>(params).0,
+    })
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema, Debug)]
+    struct Config {
+        #[serde(default)]
+        port_local: u16,
+        #[serde(default)]
+        interface: Option<String>,
+    }
+
+    fn params(json: &str) -> SocketParams {
+        SocketParams::parse(json).unwrap()
+    }
+
+    #[test]
+    fn field_errors() {
+        let err = parse_params::<Config>("udp", &params(r#"{ "port_local": "x" }"#)).unwrap_err();
+        assert!(matches!(&err, Error::Config { field: Some(f), .. } if f == "port_local"));
+        assert!(
+            err.to_string()
+                .starts_with("Invalid udp configuration: port_local: invalid type: string")
+        );
+        let err = SocketParams::parse(r#"{ "port_local": 1 "#).unwrap_err();
+        assert!(err.to_string().contains("EOF while parsing"));
+        assert!(validate_params::<Config>("udp", &params(r#"{ "port_local": 5150 }"#)).is_ok());
+        // No parameters mean the default configuration
+        let config = parse_params::<Config>("udp", &params("")).unwrap();
+        assert_eq!(config.port_local, 0);
+
+        let typo = params(r#"{ "port_locl": 5150, "interface": "eth0" }"#);
+        assert!(parse_params::<Config>("udp", &typo).is_ok());
+        assert_eq!(unknown_fields::<Config>(&typo), ["port_locl"]);
+        let err = validate_params::<Config>("udp", &typo).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid udp configuration: unknown field port_locl, expected one of: interface, port_local"
+        );
+    }
+
+    #[test]
+    fn layers() {
+        let mut layered = params(r#"{ "pat": { "type": "inc", "size": 100 }, "cycle": 5000 }"#);
+        layered.merge(params(r#"{ "pat": { "size": 200 } }"#));
+        let env = [
+            ("POLYSOCK_FROM_CYCLE", "1000"),
+            ("POLYSOCK_FROM_PAT__DATA", "0x80"),
+            ("POLYSOCK_TO_CYCLE", "1"),
+        ];
+        layered.merge(SocketParams::from_env(
+            "POLYSOCK_FROM_",
+            env.map(|(name, value)| (name.to_string(), value.to_string())),
+        ));
+        layered.set("pat.type", "seq");
+        layered.set("iter_num", "5");
+        assert_eq!(
+            layered,
+            params(
+                r#"{ "pat": { "type": "seq", "size": 200, "data": "0x80" }, "cycle": 1000, "iter_num": 5 }"#
+            )
+        );
+        assert_eq!(params("").to_string(), "");
+        assert_eq!(params(r#"{ "cycle": 1 }"#).to_string(), r#"{"cycle":1}"#);
+    }
+
+    #[test]
+    fn placeholders() {
+        let lookup = |name: &str| match name {
+            "TARGET_IP" => Some("10.0.0.7".to_string()),
+            "PORT" => Some("5150".to_string()),
+            _ => None,
+        };
+        let expanded = params(
+            r#"{ "ip_dst": "${TARGET_IP}", "port_dst": "${PORT}", "port_local": "${LOCAL:-0}",
+                 "links": [ { "path": "/tmp/${TARGET_IP}-$${PORT}.log", "cost": "5$" } ] }"#,
+        )
+        .expand_with("udp", lookup)
+        .unwrap();
+        assert_eq!(
+            expanded,
+            params(
+                r#"{ "ip_dst": "10.0.0.7", "port_dst": 5150, "port_local": 0,
+                     "links": [ { "path": "/tmp/10.0.0.7-${PORT}.log", "cost": "5$" } ] }"#
+            )
+        );
+        let err = params(r#"{ "links": [ { "ip_dst": "${MISSING}" } ] }"#)
+            .expand_with("bond", lookup)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid bond configuration: links[0].ip_dst: variable MISSING is not set"
+        );
+        let err = params(r#"{ "ip_dst": "${TARGET_IP" }"#)
+            .expand_with("udp", lookup)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("placeholder ${TARGET_IP isn't closed")
+        );
+    }
+}
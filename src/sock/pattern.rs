@@ -0,0 +1,112 @@
+use serde::{Deserialize, Deserializer};
+use std::ops::Range;
+
+/// Regular expression matched against the raw bytes (not only UTF-8 text).
+#[derive(Debug, Clone)]
+pub struct ByteRegex(regex::bytes::Regex);
+
+impl ByteRegex {
+    pub fn new(re: &str) -> Result<Self, regex::Error> {
+        regex::bytes::Regex::new(re).map(Self)
+    }
+}
+
+impl PartialEq for ByteRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteRegex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let re = String::deserialize(deserializer)?;
+        Self::new(&re).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Byte sequence searched in the stream data.
+#[derive(Deserialize, Debug, Clone, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BytePattern {
+    /// Pattern in text format (for example "CONNECT")
+    Text(String),
+    /// Pattern in hex string format (for example "55ff67")
+    Hex(
+        #[serde(with = "hex::serde")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+    /// Regular expression (for example "^\\$GP(RMC|GGA),"), `(?-u)` allows
+    /// to match any byte with `\xff`
+    Regex(#[schemars(with = "String")] ByteRegex),
+}
+
+impl BytePattern {
+    /// Returns position of the first pattern occurrence in data. Empty
+    /// pattern matches at the beginning.
+    pub fn find(&self, data: &[u8]) -> Option<usize> {
+        self.find_range(data).map(|r| r.start)
+    }
+    /// Returns range of the first pattern occurrence in data.
+    pub fn find_range(&self, data: &[u8]) -> Option<Range<usize>> {
+        let pat: &[u8] = match self {
+            Self::Text(s) => s.as_bytes(),
+            Self::Hex(v) => v,
+            Self::Regex(re) => return re.0.find(data).map(|m| m.range()),
+        };
+        if pat.is_empty() {
+            return Some(0..0);
+        }
+        let pos = data.windows(pat.len()).position(|w| w == pat)?;
+        Some(pos..pos + pat.len())
+    }
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.find(data).is_some()
+    }
+    /// Returns length of the data tail, which is kept to find the pattern
+    /// split between the chunks. Regular expression is matched within a
+    /// chunk.
+    pub fn carry_over(&self) -> usize {
+        match self {
+            Self::Text(s) => s.len().saturating_sub(1),
+            Self::Hex(v) => v.len().saturating_sub(1),
+            Self::Regex(_) => 0,
+        }
+    }
+}
+
+impl std::fmt::Display for BytePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(s) => write!(f, "{:?}", s),
+            Self::Hex(v) => write!(f, "0x{}", hex::encode(v)),
+            Self::Regex(re) => write!(f, "/{}/", re.0.as_str()),
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn parse_and_find() {
+        let text: BytePattern = serde_json::from_str("{ \"text\": \"OK\" }").unwrap();
+        let hex: BytePattern = serde_json::from_str("{ \"hex\": \"0d0a\" }").unwrap();
+        assert_eq!(text.find(b"AT\r\nOK\r\n"), Some(4));
+        assert_eq!(hex.find(b"AT\r\nOK\r\n"), Some(2));
+        assert!(!text.matches(b"ERROR"));
+        assert_eq!(text.find_range(b"AT\r\nOK\r\n"), Some(4..6));
+        assert_eq!(hex.to_string(), "0x0d0a");
+
+        let re: BytePattern = serde_json::from_str(r#"{ "regex": "O[KN]\\r" }"#).unwrap();
+        assert_eq!(re.find(b"AT\r\nOK\r\n"), Some(4));
+        assert_eq!(re.to_string(), "/O[KN]\\r/");
+        assert_eq!(re.find_range(b"AT\r\nOK\r\n"), Some(4..7));
+        assert_eq!((text.carry_over(), re.carry_over()), (1, 0));
+        let binary: BytePattern = serde_json::from_str(r#"{ "regex": "(?-u)^\\xff+$" }"#).unwrap();
+        assert!(binary.matches(b"\xff\xff") && !binary.matches(b"\xff\x00"));
+        assert!(serde_json::from_str::<BytePattern>(r#"{ "regex": "(" }"#).is_err());
+    }
+}
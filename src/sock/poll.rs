@@ -0,0 +1,88 @@
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+/// OS handle of a socket, which can be polled for readiness.
+#[cfg(unix)]
+pub type RawHandle = std::os::fd::RawFd;
+/// OS handle of a socket, which can be polled for readiness.
+#[cfg(windows)]
+pub type RawHandle = std::os::windows::io::RawSocket;
+
+/// Returns the OS handle of a std socket object.
+#[cfg(unix)]
+pub fn raw_handle<T: std::os::fd::AsRawFd>(s: &T) -> RawHandle {
+    s.as_raw_fd()
+}
+/// Returns the OS handle of a std socket object.
+#[cfg(windows)]
+pub fn raw_handle<T: std::os::windows::io::AsRawSocket>(s: &T) -> RawHandle {
+    s.as_raw_socket()
+}
+
+/// Sleep used when readiness of a socket can't be polled.
+pub const FALLBACK_SLEEP: Duration = Duration::from_millis(1);
+
+/// Blocks until any of `handles` becomes readable or `timeout` expires.
+/// Returns `true` if some handle is ready (readable, closed or failed).
+#[cfg(unix)]
+pub fn wait_readable(handles: &[RawHandle], timeout: Duration) -> io::Result<bool> {
+    let mut fds: Vec<libc::pollfd> = handles
+        .iter()
+        .map(|fd| libc::pollfd {
+            fd: *fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    // Sub-millisecond timeout is rounded up, truncated to zero it would make
+    // the callers spin
+    let timeout_ms = timeout.as_micros().div_ceil(1000);
+    let timeout_ms = timeout_ms.min(libc::c_int::MAX as u128) as libc::c_int;
+    let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        // Signal interruption is the same as timeout for callers
+        if err.kind() == io::ErrorKind::Interrupted {
+            return Ok(false);
+        }
+        return Err(err);
+    }
+    Ok(ret > 0)
+}
+
+/// Blocks until any of `handles` becomes readable or `timeout` expires.
+/// Readiness polling isn't implemented on this platform, so it just sleeps.
+#[cfg(not(unix))]
+pub fn wait_readable(_handles: &[RawHandle], timeout: Duration) -> io::Result<bool> {
+    thread::sleep(FALLBACK_SLEEP.min(timeout));
+    Ok(false)
+}
+
+/// Waits for data on the socket handles, or sleeps shortly if they are unknown.
+pub fn wait_or_sleep(handles: Option<Vec<RawHandle>>, timeout: Duration) -> io::Result<()> {
+    match handles {
+        Some(handles) => wait_readable(&handles, timeout).map(|_| ()),
+        None => {
+            thread::sleep(FALLBACK_SLEEP.min(timeout));
+            Ok(())
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::time::Instant;
+
+    #[cfg(unix)]
+    #[test]
+    fn sub_millisecond_wait() {
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let start = Instant::now();
+        let ready = wait_readable(&[raw_handle(&sock)], Duration::from_micros(100)).unwrap();
+        assert!(!ready);
+        assert!(start.elapsed() >= Duration::from_millis(1));
+    }
+}
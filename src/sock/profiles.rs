@@ -0,0 +1,109 @@
+use super::SocketParams;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Profiles file in the user config directory.
+const PROFILES_FILE: &str = "polysock/profiles.toml";
+
+/// Endpoint preset of the profiles file, e.g.
+/// ```toml
+/// [bench-serial]
+/// type = "tcp-client"
+/// params = { ip_dst = "10.0.0.5", port_dst = 4001 }
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Socket type of the endpoint
+    #[serde(rename = "type")]
+    ty: String,
+    /// Socket parameters, inline ones are merged over them
+    #[serde(default)]
+    params: Value,
+}
+
+impl Profile {
+    pub fn socket_type(&self) -> &str {
+        &self.ty
+    }
+    pub fn socket_params(&self) -> SocketParams {
+        SocketParams::new(self.params.clone())
+    }
+}
+
+/// Named endpoint presets used as `@name` in place of the socket type.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Profiles(BTreeMap<String, Profile>);
+
+impl Profiles {
+    /// Parses the profiles from TOML.
+    pub fn parse(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+    /// Loads the profiles file, missing file has no profiles.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let at = |kind, e: &dyn std::fmt::Display| {
+            io::Error::new(kind, format!("{}: {e}", path.display()))
+        };
+        match fs::read_to_string(path) {
+            Ok(toml) => Self::parse(&toml).map_err(|e| at(ErrorKind::InvalidData, &e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(at(e.kind(), &e)),
+        }
+    }
+    /// Path of the profiles file: `$XDG_CONFIG_HOME/polysock/profiles.toml`
+    /// or `~/.config/polysock/profiles.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config.join(PROFILES_FILE))
+    }
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.0.get(name)
+    }
+    /// Names of the profiles in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn parse_profiles() {
+        let profiles = Profiles::parse(
+            r#"
+            [bench-serial]
+            type = "tcp-client"
+            params = { ip_dst = "10.0.0.5", port_dst = 4001 }
+
+            [console]
+            type = "stdio"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profiles.names().collect::<Vec<_>>(), ["bench-serial", "console"]);
+        let bench = profiles.get("bench-serial").unwrap();
+        assert_eq!(bench.socket_type(), "tcp-client");
+        assert_eq!(
+            bench.socket_params(),
+            SocketParams::parse(r#"{ "ip_dst": "10.0.0.5", "port_dst": 4001 }"#).unwrap()
+        );
+        assert!(profiles.get("console").unwrap().socket_params().is_empty());
+
+        let err = Profiles::parse("[gps]\nty = \"udp\"").unwrap_err();
+        assert!(err.to_string().contains("unknown field `ty`"), "{err}");
+        let missing = std::env::temp_dir().join("polysock-no-profiles.toml");
+        assert!(Profiles::load(&missing).unwrap().get("gps").is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use super::{ExitCondition, SocketParams};
+use derive_builder::Builder;
+use serde_json::Value;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Ports below this one require privileges to bind.
+const FIRST_UNPRIVILEGED_PORT: u64 = 1024;
+
+/// Hard caps for evaluation of untrusted configurations.
+#[derive(Builder, Clone, Debug)]
+pub struct Sandbox {
+    /// Max time of the binding
+    #[builder(default = "Duration::from_secs(60)")]
+    max_runtime: Duration,
+    /// Max transferred bytes (all directions)
+    #[builder(default = "10 * 1024 * 1024")]
+    max_bytes: u64,
+    /// The only directory where files can be accessed
+    #[builder(default = "PathBuf::from(\".\")")]
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    pub fn max_runtime(&self) -> Duration {
+        self.max_runtime
+    }
+    /// Extends the user exit condition with runtime and traffic caps.
+    pub fn exit_condition(&self, user: Option<ExitCondition>) -> ExitCondition {
+        let mut conds = vec![
+            ExitCondition::Elapsed(self.max_runtime.as_secs_f64()),
+            ExitCondition::Bytes(self.max_bytes),
+        ];
+        conds.extend(user);
+        ExitCondition::Any(conds)
+    }
+    /// Rejects socket parameters binding privileged ports or referring to
+    /// files outside the sandbox directory. Placeholders are expanded
+    /// first, so they can't hide the values from the checks.
+    pub fn check_params(&self, params: &SocketParams) -> Result<()> {
+        let params = params.expand("sandboxed socket")?;
+        self.check_value(None, params.value())
+    }
+    fn check_value(&self, key: Option<&str>, value: &Value) -> Result<()> {
+        match (key, value) {
+            (_, Value::Object(map)) => map
+                .iter()
+                .try_for_each(|(k, v)| self.check_value(Some(k), v)),
+            (_, Value::Array(items)) => items.iter().try_for_each(|v| self.check_value(key, v)),
+            (Some("port_local"), _) => {
+                let port = value
+                    .as_u64()
+                    .or_else(|| value.as_str().and_then(|s| s.parse().ok()));
+                match port {
+                    Some(port) if port != 0 && port < FIRST_UNPRIVILEGED_PORT => Err(Error::new(
+                        ErrorKind::PermissionDenied,
+                        format!("Privileged port {port} isn't allowed in sandbox"),
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            (Some(k), Value::String(path)) if k == "path" || k.ends_with("_path") => {
+                self.check_path(Path::new(path))
+            }
+            _ => Ok(()),
+        }
+    }
+    /// Checks that the file is inside the sandbox directory. The parent
+    /// directory of the file must exist.
+    pub fn check_path(&self, path: &Path) -> Result<()> {
+        let denied = || {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "File {} is outside of sandbox directory {}",
+                    path.display(),
+                    self.dir.display()
+                ),
+            )
+        };
+        let dir = self.dir.canonicalize()?;
+        let name = path.file_name().ok_or_else(denied)?;
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize()?,
+            _ => Path::new(".").canonicalize()?,
+        };
+        // Symlink file may point outside the directory
+        let file = parent.join(name);
+        let file = if file.is_symlink() {
+            file.canonicalize()?
+        } else {
+            file
+        };
+        if file.starts_with(&dir) {
+            Ok(())
+        } else {
+            Err(denied())
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn sandbox_checks() {
+        let dir = std::env::temp_dir();
+        let sandbox = SandboxBuilder::default().dir(dir.clone()).build().unwrap();
+        let params = |json: &str| SocketParams::parse(json).unwrap();
+        assert!(
+            sandbox
+                .check_params(&params("{ \"port_local\": 5150 }"))
+                .is_ok()
+        );
+        assert!(
+            sandbox
+                .check_params(&params("{ \"port_local\": \"80\" }"))
+                .is_err()
+        );
+        let inside = format!(
+            "{{ \"pat\": {{ \"type\": \"file\", \"path\": {:?} }} }}",
+            dir.join("pattern.bin")
+        );
+        assert!(sandbox.check_params(&params(&inside)).is_ok());
+        let outside = "{ \"pat\": { \"type\": \"file\", \"path\": \"/etc/passwd\" } }";
+        assert!(sandbox.check_params(&params(outside)).is_err());
+        let escape = format!("{{ \"path\": {:?} }}", dir.join("..").join("passwd"));
+        assert!(sandbox.check_params(&params(&escape)).is_err());
+        // Path is checked after the expansion
+        let hidden = "{ \"path\": \"${POLYSOCK_UNSET_PATH:-/etc/passwd}\" }";
+        assert!(sandbox.check_params(&params(hidden)).is_err());
+        assert!(matches!(
+            sandbox.exit_condition(None),
+            ExitCondition::Any(ref v) if v.len() == 2
+        ));
+    }
+}
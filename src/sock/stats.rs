@@ -0,0 +1,281 @@
+use super::pattern::BytePattern;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Length of the window, over which the peak rate is measured.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Direction of the data in the binding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    /// From the first socket to the second one
+    FromTo,
+    /// From the second socket back to the first one (bidirectional binding)
+    ToFrom,
+}
+
+/// Counters of one direction of the binding.
+#[derive(Default)]
+struct FlowCounters {
+    bytes: AtomicU64,
+    messages: AtomicU64,
+    /// Start of the current rate window, its bytes and the highest rate of
+    /// the finished windows (bytes per second)
+    window: Mutex<Option<(Instant, u64, f64)>>,
+}
+
+impl FlowCounters {
+    fn record(&self, len: usize, now: Instant) {
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        let mut window = self.window.lock().unwrap();
+        let (start, bytes, peak) = window.get_or_insert((now, 0, 0.0));
+        let elapsed = now.duration_since(*start);
+        if elapsed >= RATE_WINDOW {
+            *peak = peak.max(*bytes as f64 / elapsed.as_secs_f64());
+            *start = now;
+            *bytes = 0;
+        }
+        *bytes += len as u64;
+    }
+    fn summary(&self, secs: f64) -> FlowSummary {
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let avg_rate = if secs > 0.0 { bytes as f64 / secs } else { 0.0 };
+        let peak = self.window.lock().unwrap().map_or(0.0, |(_, _, peak)| peak);
+        FlowSummary {
+            bytes,
+            messages: self.messages.load(Ordering::Relaxed),
+            avg_rate,
+            // Binding shorter than the window has only the average rate
+            peak_rate: peak.max(avg_rate),
+        }
+    }
+}
+
+/// Transfer counters shared by all threads of one binding.
+pub struct BindingStats {
+    start: Instant,
+    bytes: AtomicU64,
+    messages: AtomicU64,
+    last_activity: Mutex<Instant>,
+    /// Patterns of the exit condition found in the read data
+    matched: Mutex<Vec<BytePattern>>,
+    from_to: FlowCounters,
+    to_from: FlowCounters,
+    /// Failures of the binding and of its dropped peers
+    errors: AtomicU64,
+}
+
+impl Default for BindingStats {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            bytes: AtomicU64::new(0),
+            messages: AtomicU64::new(0),
+            last_activity: Mutex::new(now),
+            matched: Mutex::new(Vec::new()),
+            from_to: FlowCounters::default(),
+            to_from: FlowCounters::default(),
+            errors: AtomicU64::new(0),
+        }
+    }
+}
+
+#[allow(unused)]
+impl BindingStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers one forwarded chunk of `len` bytes.
+    pub fn record(&self, len: usize) {
+        self.record_flow(Flow::FromTo, len);
+    }
+    /// Registers one chunk of `len` bytes forwarded in the direction.
+    pub fn record_flow(&self, flow: Flow, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let now = Instant::now();
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.counters(flow).record(len, now);
+        *self.last_activity.lock().unwrap() = now;
+    }
+    /// Registers failure of the binding (or of its dropped peer).
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Total bytes forwarded in all directions.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+    /// Total chunks forwarded in all directions.
+    pub fn messages(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+    /// Number of the registered failures.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+    /// Time since the binding was started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+    /// Time since the last forwarded chunk (or binding start).
+    pub fn idle(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
+    }
+    /// Registers the pattern found in the read data.
+    pub fn record_match(&self, pattern: &BytePattern) {
+        let mut matched = self.matched.lock().unwrap();
+        if !matched.contains(pattern) {
+            matched.push(pattern.clone());
+        }
+    }
+    /// Returns `true` if the pattern was found in the read data.
+    pub fn is_matched(&self, pattern: &BytePattern) -> bool {
+        self.matched.lock().unwrap().contains(pattern)
+    }
+    /// Returns snapshot of the counters for the report on exit.
+    pub fn summary(&self) -> BindingSummary {
+        let secs = self.elapsed().as_secs_f64();
+        BindingSummary {
+            secs,
+            from_to: self.from_to.summary(secs),
+            to_from: self.to_from.summary(secs),
+            errors: self.errors(),
+        }
+    }
+    fn counters(&self, flow: Flow) -> &FlowCounters {
+        match flow {
+            Flow::FromTo => &self.from_to,
+            Flow::ToFrom => &self.to_from,
+        }
+    }
+}
+
+/// Totals of one direction of the binding.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlowSummary {
+    pub bytes: u64,
+    pub messages: u64,
+    /// Bytes per second over the whole binding
+    pub avg_rate: f64,
+    /// The highest bytes per second over one second
+    pub peak_rate: f64,
+}
+
+impl FlowSummary {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "bytes": self.bytes,
+            "messages": self.messages,
+            "avg_rate": self.avg_rate,
+            "peak_rate": self.peak_rate,
+        })
+    }
+}
+
+impl fmt::Display for FlowSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes, {} messages, avg {:.1} B/s, peak {:.1} B/s",
+            self.bytes, self.messages, self.avg_rate, self.peak_rate
+        )
+    }
+}
+
+/// Totals of the binding reported when it is finished.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BindingSummary {
+    /// Duration of the binding
+    pub secs: f64,
+    pub from_to: FlowSummary,
+    pub to_from: FlowSummary,
+    pub errors: u64,
+}
+
+impl BindingSummary {
+    pub fn to_json(&self, name: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "secs": self.secs,
+            "from_to": self.from_to.to_json(),
+            "to_from": self.to_from.to_json(),
+            "errors": self.errors,
+        })
+    }
+}
+
+impl fmt::Display for BindingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3} s, from -> to: {}", self.secs, self.from_to)?;
+        if self.to_from.messages > 0 {
+            write!(f, ", to -> from: {}", self.to_from)?;
+        }
+        write!(f, ", {} errors", self.errors)
+    }
+}
+
+/// Format of the binding summary reported on exit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum SummaryFormat {
+    /// Log event with the totals
+    #[default]
+    Text,
+    /// One JSON object per binding written to STDERR
+    Json,
+    /// Summary isn't reported
+    Off,
+}
+
+static SUMMARY_FORMAT: OnceLock<SummaryFormat> = OnceLock::new();
+
+/// Sets format of the summaries reported by the modes on exit.
+pub fn set_summary_format(format: SummaryFormat) {
+    let _ = SUMMARY_FORMAT.set(format);
+}
+
+/// Reports summary of the finished binding (the link of the script has
+/// its name).
+pub fn report_summary(name: Option<&str>, stats: &BindingStats) {
+    let summary = stats.summary();
+    match SUMMARY_FORMAT.get().copied().unwrap_or_default() {
+        SummaryFormat::Text => match name {
+            Some(name) => tracing::info!("Link {name} summary: {summary}"),
+            None => tracing::info!("Binding summary: {summary}"),
+        },
+        SummaryFormat::Json => eprintln!("{}", summary.to_json(name)),
+        SummaryFormat::Off => {}
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn flow_summary() {
+        let stats = BindingStats::new();
+        stats.record(10);
+        stats.record_flow(Flow::ToFrom, 4);
+        stats.record_flow(Flow::ToFrom, 0);
+        stats.record_error();
+        assert_eq!(stats.bytes(), 14);
+        assert_eq!(stats.messages(), 2);
+        let summary = stats.summary();
+        assert_eq!(summary.from_to.bytes, 10);
+        assert_eq!(summary.to_from.messages, 1);
+        assert_eq!(summary.errors, 1);
+        assert!(summary.from_to.peak_rate >= summary.from_to.avg_rate);
+        let json = summary.to_json(Some("gps"));
+        assert_eq!(json["name"], "gps");
+        assert_eq!(json["to_from"]["bytes"], 4);
+        assert!(summary.to_string().contains("to -> from: 4 bytes, 1 messages"));
+    }
+}
@@ -0,0 +1,483 @@
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll,
+    SocketFactory, SocketParams, make_simple_sock, poll,
+};
+use crate::error;
+use crate::sock::params::{parse_params, validate_params};
+use crate::sockets::registry::SocketRegistry;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, Error};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Marker of the frame start, used to resynchronize stream links.
+const FRAME_MAGIC: [u8; 2] = *b"PB";
+/// Magic, session id, sequence number and payload length.
+const FRAME_HEADER_LEN: usize = 2 + 4 + 8 + 4;
+/// Frames with larger length are considered garbage.
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+/// Max number of frames waiting for the missing one.
+const MAX_REORDER_FRAMES: usize = 1024;
+/// Time when a failed link isn't used for writing.
+const LINK_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const LINK_READ_SIZE: usize = 65536;
+
+fn default_reorder_timeout() -> u64 {
+    100
+}
+
+/// Transport of the bond.
+#[derive(Deserialize, JsonSchema)]
+pub struct BondLinkConfig {
+    /// Socket type of the link
+    #[serde(rename = "type")]
+    ty: String,
+    /// Socket parameters of the link
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    params: serde_json::Value,
+}
+
+/// Configuration for bonding socket. The peer instance has the mirrored
+/// links, e.g. TCP server is paired with TCP client.
+#[derive(Deserialize, JsonSchema)]
+pub struct BondConfig {
+    /// Parallel transports, chunks are distributed between them in turn
+    links: Vec<BondLinkConfig>,
+    /// Time in milliseconds to wait for a missing chunk before it's skipped
+    #[serde(default = "default_reorder_timeout")]
+    reorder_timeout: u64,
+}
+
+struct Frame {
+    session: u32,
+    seq: u64,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(FRAME_HEADER_LEN + self.data.len());
+        buf.extend(FRAME_MAGIC);
+        buf.extend(self.session.to_be_bytes());
+        buf.extend(self.seq.to_be_bytes());
+        buf.extend((self.data.len() as u32).to_be_bytes());
+        buf.extend(&self.data);
+        buf
+    }
+    /// Takes the first complete frame from the received bytes. Bytes
+    /// before the frame magic are dropped.
+    fn decode(buf: &mut Vec<u8>) -> Option<Frame> {
+        loop {
+            let start = buf.windows(2).position(|w| w == FRAME_MAGIC);
+            buf.drain(..start.unwrap_or(buf.len().saturating_sub(1)));
+            if buf.len() < FRAME_HEADER_LEN {
+                return None;
+            }
+            let len = u32::from_be_bytes(buf[14..18].try_into().unwrap()) as usize;
+            if len > MAX_FRAME_LEN {
+                // False magic, search for the next one
+                buf.drain(..1);
+                continue;
+            }
+            if buf.len() < FRAME_HEADER_LEN + len {
+                return None;
+            }
+            let frame = Frame {
+                session: u32::from_be_bytes(buf[2..6].try_into().unwrap()),
+                seq: u64::from_be_bytes(buf[6..14].try_into().unwrap()),
+                data: buf[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len].to_vec(),
+            };
+            buf.drain(..FRAME_HEADER_LEN + len);
+            return Some(frame);
+        }
+    }
+}
+
+/// Restores the order of frames received from all links.
+struct Reorder {
+    session: Option<u32>,
+    next_seq: u64,
+    pending: BTreeMap<u64, Vec<u8>>,
+    /// Time since the missing frame is waited for
+    gap_since: Option<Instant>,
+    timeout: Duration,
+}
+
+impl Reorder {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            session: None,
+            next_seq: 0,
+            pending: BTreeMap::new(),
+            gap_since: None,
+            timeout,
+        }
+    }
+    fn push(&mut self, frame: Frame) {
+        // Restarted peer begins the sequence again
+        if self.session != Some(frame.session) {
+            *self = Self::new(self.timeout);
+            self.session = Some(frame.session);
+        }
+        // Late frames are already skipped
+        if frame.seq >= self.next_seq {
+            self.pending.insert(frame.seq, frame.data);
+        }
+    }
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        let (&first, _) = self.pending.first_key_value()?;
+        if first != self.next_seq {
+            let since = *self.gap_since.get_or_insert_with(Instant::now);
+            if since.elapsed() < self.timeout && self.pending.len() < MAX_REORDER_FRAMES {
+                return None;
+            }
+            tracing::debug!("Bond frames {}..{} are lost", self.next_seq, first);
+        }
+        self.gap_since = None;
+        self.next_seq = first + 1;
+        self.pending.remove(&first)
+    }
+}
+
+struct BondLink {
+    sock: Box<dyn ComplexSock>,
+    /// Received bytes, which don't make a complete frame yet
+    rx: Vec<u8>,
+    /// Frame bytes, which weren't accepted by the link yet
+    tx: Vec<u8>,
+    down_until: Option<Instant>,
+}
+
+impl BondLink {
+    fn is_up(&self) -> bool {
+        self.down_until.is_none_or(|t| Instant::now() >= t)
+    }
+    fn fail(&mut self, e: &Error) {
+        tracing::warn!("Bond link {} failed: {e}", self.sock.get_description());
+        // Receiver skips the frame after the reorder timeout
+        self.tx.clear();
+        self.down_until = Some(Instant::now() + LINK_RETRY_INTERVAL);
+    }
+    /// Sends the frame, which is written completely, without delay.
+    fn frame_written(&mut self) {
+        if let Err(e) = self.sock.flush_message() {
+            self.fail(&e);
+        }
+    }
+    /// Writes the pending frame bytes. Returns `true` if the link is free.
+    fn flush(&mut self) -> bool {
+        if self.tx.is_empty() {
+            return true;
+        }
+        while !self.tx.is_empty() {
+            match self.sock.write(&self.tx, self.tx.len()) {
+                Ok(0) => return false,
+                Ok(sz) => {
+                    self.tx.drain(..sz);
+                }
+                Err(e) => {
+                    self.fail(&e);
+                    return false;
+                }
+            }
+        }
+        self.frame_written();
+        true
+    }
+}
+
+pub struct BondState {
+    links: Vec<BondLink>,
+    /// Index of the link for the next frame
+    next_link: usize,
+    tx_seq: u64,
+    reorder: Reorder,
+    /// Received chunk, which didn't fit the read buffer
+    rx_pending: Vec<u8>,
+}
+
+make_simple_sock!(BondSock {
+    session: u32,
+    state: RefCell<BondState>,
+    blocking: bool,
+    read_timeout: Option<Duration>,
+}, "bond"; info: {
+    /// Every read returns one chunk written to the peer bond
+    fn semantics(&self) -> Semantics {
+        Semantics::Message
+    }
+});
+
+impl BondSock {
+    /// Reads all links and returns the next chunk in order.
+    fn read_links(&self, data: &mut [u8], sz: usize) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        let BondState {
+            links,
+            reorder,
+            rx_pending,
+            ..
+        } = &mut *state;
+        if rx_pending.is_empty() {
+            let mut buf = vec![0u8; LINK_READ_SIZE];
+            for link in links.iter_mut() {
+                match link.sock.read(&mut buf, LINK_READ_SIZE) {
+                    Ok(read) => link.rx.extend(&buf[..read]),
+                    Err(e) => tracing::debug!("Bond link {} read: {e}", link.sock.get_description()),
+                }
+                while let Some(frame) = Frame::decode(&mut link.rx) {
+                    reorder.push(frame);
+                }
+            }
+            match reorder.pop() {
+                Some(chunk) => *rx_pending = chunk,
+                None => return Ok(0),
+            }
+        }
+        let len = rx_pending.len().min(sz);
+        data[..len].copy_from_slice(&rx_pending[..len]);
+        rx_pending.drain(..len);
+        Ok(len)
+    }
+}
+
+impl SimpleSock for BondSock {
+    fn open(&mut self) -> io::Result<()> {
+        let state = self.state.get_mut();
+        state.links.iter_mut().try_for_each(|link| link.sock.open())
+    }
+    fn close(&mut self) {
+        for link in self.state.get_mut().links.iter_mut() {
+            link.sock.close();
+        }
+    }
+    fn read(&self, data: &mut [u8], sz: usize) -> io::Result<usize> {
+        let start = Instant::now();
+        loop {
+            let read = self.read_links(data, sz)?;
+            if read > 0 || !self.blocking {
+                return Ok(read);
+            }
+            let wait = match self.read_timeout {
+                Some(timeout) if start.elapsed() >= timeout => return Ok(0),
+                Some(timeout) => timeout - start.elapsed(),
+                None => Duration::MAX,
+            };
+            // Missing frame is skipped after the reorder timeout even
+            // without new data
+            let timeout = self.state.borrow().reorder.timeout;
+            poll::wait_or_sleep(self.poll_handles(), wait.min(timeout))?;
+        }
+    }
+    fn write(&self, data: &[u8], sz: usize) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        let count = state.links.len();
+        let mut free: Vec<usize> = Vec::new();
+        for (i, link) in state.links.iter_mut().enumerate() {
+            if link.flush() && link.is_up() {
+                free.push(i);
+            }
+        }
+        let frame = Frame {
+            session: self.session,
+            seq: state.tx_seq,
+            data: data[..sz].to_vec(),
+        }
+        .encode();
+        // Links are taken in turn starting from the next one
+        free.sort_by_key(|i| (i + count - state.next_link) % count);
+        for i in free {
+            let link = &mut state.links[i];
+            match link.sock.write(&frame, frame.len()) {
+                Ok(0) => continue,
+                Ok(written) => {
+                    link.tx = frame[written..].to_vec();
+                    if link.tx.is_empty() {
+                        link.frame_written();
+                    }
+                    state.next_link = (i + 1) % count;
+                    state.tx_seq += 1;
+                    return Ok(sz);
+                }
+                Err(e) => link.fail(&e),
+            }
+        }
+        // All links are busy or failed
+        Ok(0)
+    }
+}
+
+impl SockBlockCtl for BondSock {
+    fn set_block(&mut self, is_blocking: bool) -> io::Result<()> {
+        // Links are always polled without blocking
+        self.blocking = is_blocking;
+        Ok(())
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        self.read_timeout = read;
+        for link in self.state.get_mut().links.iter_mut() {
+            link.sock.set_timeout(read, write)?;
+        }
+        Ok(())
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> io::Result<()> {
+        for link in self.state.get_mut().links.iter_mut() {
+            link.sock.set_grace_period(grace)?;
+        }
+        Ok(())
+    }
+}
+
+impl SockPoll for BondSock {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        let state = self.state.borrow();
+        let mut handles = Vec::new();
+        for link in state.links.iter() {
+            handles.extend(link.sock.poll_handles()?);
+        }
+        Some(handles)
+    }
+}
+
+/// Bonding socket factory implementing the SocketFactory trait.
+#[derive(Default)]
+pub struct BondFactory;
+
+impl BondFactory {
+    /// Creates a new bonding socket factory.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+struct BondDoc;
+impl SockDocViewer for BondDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(BondConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let example_gw = "{ \"links\": [ \
+            { \"type\": \"udp\", \"params\": { \"port_local\": 7001, \"ip_dst\": \"10.0.1.2\", \"port_dst\": 7001 } }, \
+            { \"type\": \"tcp-client\", \"params\": { \"ip_dst\": \"10.0.2.2\", \"port_dst\": 7002 } } ] }";
+        let example_peer = "{ \"links\": [ \
+            { \"type\": \"udp\", \"params\": { \"port_local\": 7001, \"ip_dst\": \"10.0.1.1\", \"port_dst\": 7001 } }, \
+            { \"type\": \"tcp-server\", \"params\": { \"port_local\": 7002 } } ] }";
+        format!(
+            "{}: {}\n{}: {}",
+            "Gateway with UDP and TCP links", example_gw, "Paired instance", example_peer
+        )
+    }
+}
+
+impl BondConfig {
+    fn check(&self) -> Result<(), error::Error> {
+        if self.links.is_empty() {
+            return Err(error::Error::InvalidValue {
+                sock: "bond".to_string(),
+                field: "links".to_string(),
+                message: "bond has no links".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl SocketFactory for BondFactory {
+    fn create_sock(&self, params: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
+        let config: BondConfig = parse_params("bond", &params)?;
+        config.check()?;
+        let links = config
+            .links
+            .iter()
+            .map(|link| {
+                let factory = SocketRegistry::global()
+                    .create(&link.ty)
+                    .ok_or_else(|| error::Error::UnknownType(link.ty.clone()))?;
+                Ok(BondLink {
+                    sock: factory.create_sock_blockctl(SocketParams::new(link.params.clone()), false)?,
+                    rx: Vec::new(),
+                    tx: Vec::new(),
+                    down_until: None,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        // Session distinguishes restarted peer from the old one
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let session = now.subsec_nanos() ^ std::process::id().rotate_left(16);
+        Ok(Box::new(BondSock::new(
+            session,
+            RefCell::new(BondState {
+                links,
+                next_link: 0,
+                tx_seq: 0,
+                reorder: Reorder::new(Duration::from_millis(config.reorder_timeout)),
+                rx_pending: Vec::new(),
+            }),
+            false,
+            None,
+        )))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
+        Box::new(BondDoc)
+    }
+    fn validate_params(&self, params: &SocketParams) -> io::Result<()> {
+        validate_params::<BondConfig>("bond", params)?;
+        let config: BondConfig = parse_params("bond", params)?;
+        config.check()?;
+        for (i, link) in config.links.iter().enumerate() {
+            SocketRegistry::global()
+                .validate(&link.ty, &SocketParams::new(link.params.clone()))
+                .map_err(|e| error::Error::InvalidValue {
+                    sock: "bond".to_string(),
+                    field: format!("links[{i}]"),
+                    message: e.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn reassemble_frames() {
+        let frame = |seq: u64, data: &[u8]| Frame {
+            session: 7,
+            seq,
+            data: data.to_vec(),
+        };
+        // Stream with garbage and split frame is resynchronized
+        let mut stream = b"xx".to_vec();
+        stream.extend(frame(1, b"second").encode());
+        let first = frame(0, b"first").encode();
+        stream.extend(&first[..5]);
+        let decoded = Frame::decode(&mut stream).unwrap();
+        assert_eq!((decoded.seq, decoded.data.as_slice()), (1, &b"second"[..]));
+        assert!(Frame::decode(&mut stream).is_none());
+        stream.extend(&first[5..]);
+        assert_eq!(Frame::decode(&mut stream).unwrap().data, b"first");
+
+        let mut reorder = Reorder::new(Duration::from_secs(60));
+        reorder.push(frame(1, b"b"));
+        assert_eq!(reorder.pop(), None);
+        reorder.push(frame(0, b"a"));
+        assert_eq!(reorder.pop(), Some(b"a".to_vec()));
+        assert_eq!(reorder.pop(), Some(b"b".to_vec()));
+
+        // Missing frame is skipped after the timeout, the late one is dropped
+        let mut reorder = Reorder::new(Duration::ZERO);
+        reorder.push(frame(2, b"c"));
+        assert_eq!(reorder.pop(), Some(b"c".to_vec()));
+        reorder.push(frame(1, b"b"));
+        assert_eq!(reorder.pop(), None);
+    }
+}
@@ -0,0 +1,283 @@
+use super::testgen::TestPatternStrategy;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::any::Any;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+/// Column of the CSV record, index from 0 or name of the header column.
+#[derive(Deserialize, Debug, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum CsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+/// Encoding of the CSV record.
+#[derive(Deserialize, Debug, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CsvEncoding {
+    /// Fields as text
+    Text {
+        /// Separator of the fields
+        #[serde(default = "default_separator")]
+        separator: String,
+        /// Terminator of the record
+        #[serde(default = "default_terminator")]
+        terminator: String,
+    },
+    /// Fields as binary numbers
+    Binary {
+        /// Number types of the fields in order
+        fields: Vec<CsvFieldType>,
+        /// Byte order (big-endian by default)
+        #[serde(default)]
+        little_endian: bool,
+    },
+}
+
+impl Default for CsvEncoding {
+    fn default() -> Self {
+        Self::Text {
+            separator: default_separator(),
+            terminator: default_terminator(),
+        }
+    }
+}
+
+fn default_separator() -> String {
+    ",".to_string()
+}
+
+fn default_terminator() -> String {
+    "\n".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvFieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+impl CsvFieldType {
+    /// Encodes the text value of the field.
+    fn encode(&self, value: &str, little_endian: bool) -> Option<Vec<u8>> {
+        macro_rules! bytes {
+            ($ty: ty) => {{
+                let value: $ty = value.parse().ok()?;
+                if little_endian {
+                    value.to_le_bytes().to_vec()
+                } else {
+                    value.to_be_bytes().to_vec()
+                }
+            }};
+        }
+        Some(match self {
+            Self::U8 => bytes!(u8),
+            Self::I8 => bytes!(i8),
+            Self::U16 => bytes!(u16),
+            Self::I16 => bytes!(i16),
+            Self::U32 => bytes!(u32),
+            Self::I32 => bytes!(i32),
+            Self::U64 => bytes!(u64),
+            Self::I64 => bytes!(i64),
+            Self::F32 => bytes!(f32),
+            Self::F64 => bytes!(f64),
+        })
+    }
+}
+
+/// Reads the CSV file and encodes its records. Fields are separated by the
+/// delimiter and trimmed, quoting isn't supported. Empty lines are skipped.
+pub fn load_records(
+    path: &Path,
+    delimiter: char,
+    header: bool,
+    columns: Option<&[CsvColumn]>,
+    encoding: &CsvEncoding,
+) -> Result<Vec<Vec<u8>>> {
+    let invalid = |line: usize, msg: String| {
+        Error::new(ErrorKind::InvalidData, format!("{}:{line}: {msg}", path.display()))
+    };
+    let text = std::fs::read_to_string(path)?;
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, line.split(delimiter).map(str::trim).collect::<Vec<_>>()));
+    let names = if header { lines.next().map(|(_, names)| names) } else { None };
+    let indices = match columns {
+        Some(columns) => columns
+            .iter()
+            .map(|column| match column {
+                CsvColumn::Index(idx) => Ok(*idx),
+                CsvColumn::Name(name) => names
+                    .as_ref()
+                    .and_then(|names| names.iter().position(|n| n == name))
+                    .ok_or_else(|| {
+                        let msg = format!("CSV column {name} isn't found");
+                        Error::new(ErrorKind::InvalidInput, msg)
+                    }),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    if let CsvEncoding::Binary { fields, .. } = encoding
+        && columns.is_some()
+        && fields.len() != indices.len()
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "CSV binary encoding must have a type of every column",
+        ));
+    }
+
+    let mut records = Vec::new();
+    for (line, fields) in lines {
+        let values = if columns.is_some() {
+            indices
+                .iter()
+                .map(|idx| {
+                    let value = fields.get(*idx).copied();
+                    value.ok_or_else(|| invalid(line, format!("no column {idx}")))
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            fields
+        };
+        let record = match encoding {
+            CsvEncoding::Text { separator, terminator } => {
+                format!("{}{terminator}", values.join(separator)).into_bytes()
+            }
+            CsvEncoding::Binary { fields, little_endian } => {
+                if fields.len() != values.len() {
+                    let msg = format!("{} fields, not {}", values.len(), fields.len());
+                    return Err(invalid(line, msg));
+                }
+                let mut record = Vec::new();
+                for (ty, value) in fields.iter().zip(values) {
+                    let bytes = ty
+                        .encode(value, *little_endian)
+                        .ok_or_else(|| invalid(line, format!("{value} isn't {ty:?}")))?;
+                    record.extend(bytes);
+                }
+                record
+            }
+        };
+        records.push(record);
+    }
+    if records.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} has no CSV records", path.display()),
+        ));
+    }
+    Ok(records)
+}
+
+/// Encoded records and the record of the current iteration.
+pub struct CsvState {
+    records: Vec<Vec<u8>>,
+    current: usize,
+    next: usize,
+}
+
+impl CsvState {
+    pub fn new(records: Vec<Vec<u8>>) -> Self {
+        Self {
+            records,
+            current: 0,
+            next: 0,
+        }
+    }
+}
+
+pub struct CsvStrategy;
+impl TestPatternStrategy for CsvStrategy {
+    fn read(
+        &self,
+        _: &(dyn Any + Send),
+        p: &mut Option<Box<dyn Any + Send>>,
+        buf: &mut [u8],
+        real_size: usize,
+        pos: usize,
+    ) -> Result<usize> {
+        let Some(state) = p.as_ref().unwrap().downcast_ref::<CsvState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        buf[..real_size].copy_from_slice(&state.records[state.current][pos..pos + real_size]);
+        Ok(real_size)
+    }
+    fn start_iteration(&self, p: &mut Option<Box<dyn Any + Send>>) -> Result<Option<usize>> {
+        let Some(state) = p.as_mut().unwrap().downcast_mut::<CsvState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        state.current = state.next;
+        state.next = (state.next + 1) % state.records.len();
+        Ok(Some(state.records[state.current].len()))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use crate::error;
+    use crate::sock::SocketFactory;
+    use crate::sockets::testgen::TestGenFactory;
+
+    #[test]
+    fn csv_records() {
+        let path = std::env::temp_dir().join(format!("telemetry-{}.csv", std::process::id()));
+        std::fs::write(&path, "time; temp; id\n1.5; -20; 7\n\n2.0; 300; 8\n").unwrap();
+        let read_all = |pat: serde_json::Value| {
+            let cfg = serde_json::json!({ "pat": pat, "cycle": 0, "iter_num": 3 });
+            let sock = TestGenFactory::new().create_sock(cfg.into())?;
+            let mut buf = [0u8; 64];
+            let mut records = Vec::new();
+            loop {
+                match sock.read(&mut buf, 64) {
+                    Ok(read) => records.push(buf[..read].to_vec()),
+                    Err(e) if error::is_eof(&e) => return Ok(records),
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+        let text = read_all(serde_json::json!({
+            "type": "csv", "path": path, "delimiter": ";", "header": true,
+            "columns": ["id", 1], "encoding": { "type": "text", "separator": " " }
+        }))
+        .unwrap();
+        // Records are played in a loop
+        assert_eq!(text, [b"7 -20\n".to_vec(), b"8 300\n".to_vec(), b"7 -20\n".to_vec()]);
+
+        let binary = serde_json::json!({
+            "type": "csv", "path": path, "delimiter": ";", "header": true,
+            "columns": ["id", "temp"], "repeat": false,
+            "encoding": { "type": "binary", "fields": ["u8", "i16"], "little_endian": true }
+        });
+        assert_eq!(read_all(binary).unwrap(), [vec![7, 0xec, 0xff], vec![8, 0x2c, 0x01]]);
+
+        let overflow = serde_json::json!({
+            "type": "csv", "path": path, "delimiter": ";", "header": true,
+            "columns": ["temp"], "encoding": { "type": "binary", "fields": ["i8"] }
+        });
+        let err = read_all(overflow).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().ends_with(":4: 300 isn't I8"), "{err}");
+        let missing = serde_json::json!({
+            "type": "csv", "path": path, "delimiter": ";", "columns": ["temp"]
+        });
+        assert!(read_all(missing).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -3,3 +3,21 @@ pub mod terminal;
 pub mod tcp_client;
 pub mod tcp_server;
 pub mod testgen;
+pub mod testcheck;
+mod csv_pattern;
+mod waveform_pattern;
+#[cfg(feature = "rhai")]
+mod script_pattern;
+pub mod tls;
+pub mod tls_client;
+pub mod tls_server;
+pub mod ws;
+pub mod ws_client;
+pub mod ws_server;
+pub mod sockopt;
+pub mod ipnet;
+pub mod proxy;
+pub mod registry;
+pub mod plugin;
+pub mod bond;
+pub mod replay;
@@ -0,0 +1,84 @@
+use super::registry::SocketRegistry;
+use libloading::Library;
+use std::fs;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Name of the function exported by every plugin.
+pub const PLUGIN_ENTRY: &str = "register_sockets";
+
+/// Entry point of a plugin, which registers its socket types.
+///
+/// Plugin is a `cdylib` crate built with the same Rust compiler and
+/// `polysock` version as the binary, since socket factories are passed
+/// as Rust trait objects:
+/// ```ignore
+/// use polysock::sockets::registry::SocketRegistry;
+///
+/// #[unsafe(no_mangle)]
+/// pub extern "C" fn register_sockets(registry: &SocketRegistry) {
+///     registry
+///         .register("can-bus", || Box::new(CanBusFactory::new()))
+///         .unwrap();
+/// }
+/// ```
+pub type RegisterFn = unsafe extern "C" fn(registry: &SocketRegistry);
+
+/// Loads the shared library and registers its socket types.
+pub fn load_plugin(path: &Path, registry: &SocketRegistry) -> Result<()> {
+    let plugin_err = |e: libloading::Error| {
+        // Reason of the failure (e.g. dlerror message) is the error source
+        let reason = std::error::Error::source(&e).map(|s| format!(": {s}"));
+        Error::other(format!(
+            "Plugin {}: {e}{}",
+            path.display(),
+            reason.unwrap_or_default()
+        ))
+    };
+    // Plugins are trusted code: loading runs their initialization routines
+    let lib = unsafe { Library::new(path) }.map_err(plugin_err)?;
+    {
+        let register =
+            unsafe { lib.get::<RegisterFn>(PLUGIN_ENTRY.as_bytes()) }.map_err(plugin_err)?;
+        unsafe { register(registry) };
+    }
+    tracing::debug!("Plugin {} is loaded", path.display());
+    // Registered factories refer to the library code, so it's never unloaded
+    std::mem::forget(lib);
+    Ok(())
+}
+
+/// Loads all shared libraries of the directory (in alphabetical order).
+/// Returns number of loaded plugins.
+pub fn load_plugin_dir(dir: &Path, registry: &SocketRegistry) -> Result<usize> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION)
+        })
+        .collect();
+    paths.sort();
+    for path in &paths {
+        load_plugin(path, registry)?;
+    }
+    Ok(paths.len())
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn load_missing_plugin() {
+        let registry = SocketRegistry::new();
+        assert!(load_plugin(Path::new("/nonexistent/plugin.so"), &registry).is_err());
+        // Files other than shared libraries are skipped
+        let dir = std::env::temp_dir().join("polysock-plugins-test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("readme.txt"), "not a plugin").unwrap();
+        assert_eq!(load_plugin_dir(&dir, &registry).unwrap(), 0);
+        assert!(registry.names().is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use base64::Engine;
+use serde::Deserialize;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+
+/// Proxy server of the connection chain.
+#[derive(Deserialize, Debug, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProxyConfig {
+    /// SOCKS5 proxy (optional username/password authentication)
+    Socks5 {
+        /// Proxy host name or IP address
+        host: String,
+        /// Proxy port
+        port: u16,
+        /// User name for authentication
+        username: Option<String>,
+        /// Password for authentication
+        password: Option<String>,
+    },
+    /// HTTP proxy supporting CONNECT method
+    HttpConnect {
+        /// Proxy host name or IP address
+        host: String,
+        /// Proxy port
+        port: u16,
+        /// Basic authentication credentials in "user:password" format
+        auth: Option<String>,
+    },
+}
+
+impl ProxyConfig {
+    fn host(&self) -> (&str, u16) {
+        match self {
+            Self::Socks5 { host, port, .. } | Self::HttpConnect { host, port, .. } => {
+                (host.as_str(), *port)
+            }
+        }
+    }
+    /// Resolves address of the proxy to connect it directly.
+    pub fn resolve(&self) -> io::Result<SocketAddr> {
+        let (host, port) = self.host();
+        (host, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Can't resolve {host}")))
+    }
+    /// Asks the proxy connected by `stream` to open a tunnel to `host:port`.
+    fn handshake(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        match self {
+            Self::Socks5 {
+                username, password, ..
+            } => socks5_connect(stream, host, port, username.as_deref(), password.as_deref()),
+            Self::HttpConnect { auth, .. } => http_connect(stream, host, port, auth.as_deref()),
+        }
+    }
+}
+
+/// Tunnels `stream`, which is connected to the first proxy, through all
+/// proxies of the chain to the target.
+pub fn connect_chain(
+    stream: &mut TcpStream,
+    chain: &[ProxyConfig],
+    target: SocketAddr,
+) -> io::Result<()> {
+    for (i, proxy) in chain.iter().enumerate() {
+        // Every proxy connects to the next one, the last one to the target
+        let target_ip = target.ip().to_string();
+        let (host, port) = match chain.get(i + 1) {
+            Some(next) => next.host(),
+            None => (target_ip.as_str(), target.port()),
+        };
+        tracing::debug!("Proxy {:?}: connecting to {}:{}", proxy.host(), host, port);
+        proxy.handshake(stream, host, port)?;
+    }
+    Ok(())
+}
+
+fn proxy_error(msg: String) -> Error {
+    Error::new(ErrorKind::ConnectionRefused, msg)
+}
+
+fn socks5_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> io::Result<()> {
+    const VERSION: u8 = 5;
+    const NO_AUTH: u8 = 0x00;
+    const USER_PASS: u8 = 0x02;
+    const NO_METHODS: u8 = 0xff;
+
+    // Method negotiation
+    if username.is_some() {
+        stream.write_all(&[VERSION, 2, NO_AUTH, USER_PASS])?;
+    } else {
+        stream.write_all(&[VERSION, 1, NO_AUTH])?;
+    }
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply)?;
+    match reply {
+        [VERSION, NO_AUTH] => {}
+        [VERSION, USER_PASS] => {
+            let user = username.unwrap_or_default().as_bytes();
+            let pass = password.unwrap_or_default().as_bytes();
+            let mut req = vec![1, user.len() as u8];
+            req.extend(user);
+            req.push(pass.len() as u8);
+            req.extend(pass);
+            stream.write_all(&req)?;
+            stream.read_exact(&mut reply)?;
+            if reply[1] != 0 {
+                return Err(proxy_error("SOCKS5 authentication failed".to_string()));
+            }
+        }
+        [VERSION, NO_METHODS] => {
+            return Err(proxy_error(
+                "SOCKS5 proxy rejected authentication methods".to_string(),
+            ));
+        }
+        _ => return Err(proxy_error(format!("Invalid SOCKS5 reply {reply:?}"))),
+    }
+
+    // Connect request: IP addresses are passed as is, names are resolved
+    // by the proxy
+    let mut req = vec![VERSION, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            req.push(1);
+            req.extend(ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            req.push(4);
+            req.extend(ip.octets());
+        }
+        Err(_) => {
+            req.push(3);
+            req.push(host.len() as u8);
+            req.extend(host.as_bytes());
+        }
+    }
+    req.extend(port.to_be_bytes());
+    stream.write_all(&req)?;
+
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head)?;
+    if head[1] != 0 {
+        return Err(proxy_error(format!(
+            "SOCKS5 proxy failed to connect {host}:{port} (code {})",
+            head[1]
+        )));
+    }
+    // Skip bound address and port
+    let addr_len = match head[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        atyp => return Err(proxy_error(format!("Invalid SOCKS5 address type {atyp}"))),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound)
+}
+
+fn http_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    auth: Option<&str>,
+) -> io::Result<()> {
+    let authority = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => format!("[{host}]:{port}"),
+        _ => format!("{host}:{port}"),
+    };
+    let mut req = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    if let Some(auth) = auth {
+        let token = base64::engine::general_purpose::STANDARD.encode(auth);
+        req.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes())?;
+
+    // Response is read byte by byte, so the tunnel data isn't consumed
+    const MAX_HEADER_SIZE: usize = 8192;
+    let mut resp = Vec::new();
+    let mut byte = [0u8; 1];
+    while !resp.ends_with(b"\r\n\r\n") {
+        if resp.len() >= MAX_HEADER_SIZE {
+            return Err(proxy_error("HTTP proxy response is too long".to_string()));
+        }
+        stream.read_exact(&mut byte)?;
+        resp.push(byte[0]);
+    }
+    let resp = String::from_utf8_lossy(&resp);
+    let status = resp.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(proxy_error(format!(
+            "HTTP proxy failed to connect {authority}: {status}"
+        ))),
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn socks5_then_http_chain() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        // Both proxies are emulated on one connection: SOCKS5 handshake is
+        // followed by CONNECT request inside the tunnel, then data is echoed
+        let server = thread::spawn(move || {
+            let (mut cli, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 3];
+            cli.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, [5, 1, 0]);
+            cli.write_all(&[5, 0]).unwrap();
+            let mut req = [0u8; 4 + 1 + 10 + 2];
+            cli.read_exact(&mut req).unwrap();
+            assert_eq!(&req[..5], &[5, 1, 0, 3, 10]);
+            assert_eq!(&req[5..15], b"http.proxy");
+            assert_eq!(&req[15..], &8080u16.to_be_bytes());
+            cli.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+            let expected = "CONNECT 10.0.0.7:1234 HTTP/1.1\r\nHost: 10.0.0.7:1234\r\n\
+                Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n";
+            let mut req = vec![0u8; expected.len()];
+            cli.read_exact(&mut req).unwrap();
+            assert_eq!(String::from_utf8(req).unwrap(), expected);
+            cli.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .unwrap();
+
+            let mut data = [0u8; 5];
+            cli.read_exact(&mut data).unwrap();
+            cli.write_all(&data).unwrap();
+        });
+
+        let chain: Vec<ProxyConfig> = serde_json::from_str(&format!(
+            "[ {{ \"type\": \"socks5\", \"host\": \"{}\", \"port\": {} }}, \
+               {{ \"type\": \"http_connect\", \"host\": \"http.proxy\", \"port\": 8080, \"auth\": \"user:pass\" }} ]",
+            proxy_addr.ip(),
+            proxy_addr.port()
+        ))
+        .unwrap();
+        let mut stream = TcpStream::connect(chain[0].resolve().unwrap()).unwrap();
+        connect_chain(&mut stream, &chain, "10.0.0.7:1234".parse().unwrap()).unwrap();
+        stream.write_all(b"hello").unwrap();
+        let mut data = [0u8; 5];
+        stream.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello");
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use super::{
+    bond::BondFactory, replay::ReplayFactory, tcp_client::TcpClientFactory,
+    tcp_server::TcpServerFactory, terminal::SimpleTerminalFactory, testcheck::TestCheckFactory,
+    testgen::TestGenFactory, tls_client::TlsClientFactory, tls_server::TlsServerFactory,
+    udp::SocketFactoryUDP, ws_client::WsClientFactory, ws_server::WsServerFactory,
+};
+use crate::error;
+use crate::sock::{SocketFactory, SocketParams};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::{LazyLock, RwLock};
+
+/// Creates a new factory of the registered socket type.
+pub type FactoryCallback = Box<dyn Fn() -> Box<dyn SocketFactory> + Send + Sync>;
+
+/// Socket types available by name (e.g. for `--from-dev` and `--to-dev`).
+///
+/// The global registry is filled with built-in socket types, custom types
+/// are added at runtime:
+/// ```
+/// use polysock::sockets::{registry::SocketRegistry, udp::SocketFactoryUDP};
+///
+/// SocketRegistry::global()
+///     .register("my-udp", || Box::new(SocketFactoryUDP::new()))
+///     .unwrap();
+/// assert!(SocketRegistry::global().create("my-udp").is_some());
+/// ```
+pub struct SocketRegistry {
+    factories: RwLock<BTreeMap<String, FactoryCallback>>,
+}
+
+static GLOBAL_REGISTRY: LazyLock<SocketRegistry> = LazyLock::new(SocketRegistry::with_builtins);
+
+impl SocketRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self {
+            factories: RwLock::new(BTreeMap::new()),
+        }
+    }
+    /// Creates a registry with built-in socket types.
+    pub fn with_builtins() -> Self {
+        let registry = Self::new();
+        let builtins: [(&str, FactoryCallback); 12] = [
+            ("udp", Box::new(|| Box::new(SocketFactoryUDP::new()))),
+            ("stdio", Box::new(|| Box::new(SimpleTerminalFactory::new()))),
+            ("tcp-client", Box::new(|| Box::new(TcpClientFactory::new()))),
+            ("tcp-server", Box::new(|| Box::new(TcpServerFactory::new()))),
+            ("test-gen", Box::new(|| Box::new(TestGenFactory::new()))),
+            ("test-check", Box::new(|| Box::new(TestCheckFactory::new()))),
+            ("tls-client", Box::new(|| Box::new(TlsClientFactory::new()))),
+            // Backends of the routes are looked up in the global registry
+            ("tls-server", Box::new(|| Box::new(TlsServerFactory::new(global_factory)))),
+            ("ws-client", Box::new(|| Box::new(WsClientFactory::new()))),
+            ("ws-server", Box::new(|| Box::new(WsServerFactory::new()))),
+            ("bond", Box::new(|| Box::new(BondFactory::new()))),
+            ("replay", Box::new(|| Box::new(ReplayFactory::new()))),
+        ];
+        registry.factories.write().unwrap().extend(
+            builtins
+                .into_iter()
+                .map(|(name, factory)| (name.to_string(), factory)),
+        );
+        registry
+    }
+    /// Registry used by the command line front-end.
+    pub fn global() -> &'static SocketRegistry {
+        &GLOBAL_REGISTRY
+    }
+    /// Adds a new socket type. Registered names can't be overridden.
+    pub fn register<F>(&self, name: &str, factory: F) -> Result<()>
+    where
+        F: Fn() -> Box<dyn SocketFactory> + Send + Sync + 'static,
+    {
+        let mut factories = self.factories.write().unwrap();
+        if factories.contains_key(name) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Socket type {name} is already registered"),
+            ));
+        }
+        factories.insert(name.to_string(), Box::new(factory));
+        Ok(())
+    }
+    /// Creates a factory of the socket type, if it is registered.
+    pub fn create(&self, name: &str) -> Option<Box<dyn SocketFactory>> {
+        self.factories.read().unwrap().get(name).map(|cb| cb())
+    }
+    /// Checks parameters of the socket type without opening the socket.
+    pub fn validate(&self, name: &str, params: &SocketParams) -> Result<()> {
+        let factory = self
+            .create(name)
+            .ok_or_else(|| error::Error::UnknownType(name.to_string()))?;
+        factory.validate_params(params)
+    }
+    /// Names of the registered socket types in alphabetical order.
+    pub fn names(&self) -> Vec<String> {
+        self.factories.read().unwrap().keys().cloned().collect()
+    }
+}
+
+/// Creates the factory of the socket type registered globally.
+fn global_factory(name: &str) -> Option<Box<dyn SocketFactory>> {
+    SocketRegistry::global().create(name)
+}
+
+impl Default for SocketRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+
+    #[test]
+    fn register_custom_type() {
+        let registry = SocketRegistry::with_builtins();
+        registry
+            .register("udp-alias", || Box::new(SocketFactoryUDP::new()))
+            .unwrap();
+        assert!(registry.create("udp-alias").is_some());
+        assert!(registry.create("unknown").is_none());
+        // Built-in types can't be replaced
+        let err = registry
+            .register("udp", || Box::new(SocketFactoryUDP::new()))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(registry.names().len(), 13);
+    }
+
+    #[test]
+    fn validate_params() {
+        let registry = SocketRegistry::with_builtins();
+        let validate = |name, json: &str| registry.validate(name, &SocketParams::parse(json).unwrap());
+        assert!(validate("udp", r#"{ "port_local": 5150 }"#).is_ok());
+        assert!(validate("stdio", "").is_ok());
+        let err = validate("udp", r#"{ "port_dst": "x" }"#).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid udp configuration: port_dst: invalid type"));
+        let err = validate("udp", r#"{ "port_locl": 5150 }"#).unwrap_err();
+        assert!(err.to_string().contains("unknown field port_locl"));
+        let err = validate("serial", "{}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let bond = r#"{ "links": [{ "type": "tcp-client", "params": { "port": 0 } }] }"#;
+        let err = validate("bond", bond).unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                "Invalid bond configuration: links[0]: Invalid tcp-client configuration: missing field"
+            ),
+            "{err}"
+        );
+        let tls = r#"{ "port_local": 8443, "cert": "server.pem", "key": "server.key",
+            "routes": [{ "sni": "gps.local", "type": "udp", "params": { "port_dst": "x" } }] }"#;
+        let err = validate("tls-server", tls).unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                "Invalid tls-server configuration: routes[0]: Invalid udp configuration: port_dst"
+            ),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::sock::{
+    ComplexSock, RecordReader, Semantics, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll,
+    SocketFactory, SocketParams, make_simple_sock,
+};
+use crate::error;
+use crate::sock::params::{parse_params, validate_params};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+/// Configuration for replay socket.
+#[derive(Deserialize, JsonSchema, Clone)]
+pub struct ReplayConfig {
+    /// Record log written with --record
+    path: PathBuf,
+    /// Playback speed factor, e.g. 2.0 plays the log twice as fast
+    #[serde(default = "default_speed")]
+    speed: f64,
+    /// Start the log again when it's finished
+    #[serde(default)]
+    repeat: bool,
+}
+
+fn open_log(path: &Path) -> io::Result<RecordReader<BufReader<File>>> {
+    RecordReader::new(BufReader::new(File::open(path)?))
+}
+
+pub struct ReplayState {
+    reader: RecordReader<BufReader<File>>,
+    /// Playback start, it's set by the first read
+    start: Option<Instant>,
+    /// Chunk and its already read length
+    pending: Option<(Duration, Vec<u8>, usize)>,
+    finished: bool,
+}
+
+make_simple_sock!(SimpleReplay {
+    config: ReplayConfig,
+    state: RefCell<ReplayState>,
+    blocking: bool,
+    read_timeout: Option<Duration>,
+}, "replay"; info: {
+    /// Every read returns (a part of) one recorded chunk
+    fn semantics(&self) -> Semantics {
+        Semantics::Message
+    }
+});
+
+impl SimpleSock for SimpleReplay {
+    fn read(&self, data: &mut [u8], sz: usize) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        let call_start = Instant::now();
+        let start = *state.start.get_or_insert(call_start);
+        if state.pending.is_none() {
+            match state.reader.next_chunk()? {
+                Some((at, chunk)) => state.pending = Some((at, chunk, 0)),
+                None if self.config.repeat => {
+                    state.reader = open_log(&self.config.path)?;
+                    state.start = None;
+                    return Ok(0);
+                }
+                None => {
+                    if !state.finished {
+                        tracing::info!("Replay of {} is finished", self.config.path.display());
+                        state.finished = true;
+                    }
+                    return Err(error::Error::Eof.into());
+                }
+            }
+        }
+        let Some((at, chunk, offset)) = state.pending.as_mut() else {
+            return Ok(0);
+        };
+        let due = start + at.div_f64(self.config.speed);
+        let now = Instant::now();
+        if now < due {
+            if !self.blocking {
+                return Ok(0);
+            }
+            match self.read_timeout {
+                Some(timeout) if now + timeout < due => {
+                    thread::sleep(timeout);
+                    return Ok(0);
+                }
+                _ => thread::sleep(due - now),
+            }
+        }
+        let len = (chunk.len() - *offset).min(sz);
+        data[..len].copy_from_slice(&chunk[*offset..*offset + len]);
+        *offset += len;
+        if *offset == chunk.len() {
+            state.pending = None;
+        }
+        Ok(len)
+    }
+    fn write(&self, _: &[u8], sz: usize) -> io::Result<usize> {
+        tracing::debug!("Socket replay unsupports write operation! Skipping...");
+        Ok(sz)
+    }
+}
+
+impl SockBlockCtl for SimpleReplay {
+    fn set_block(&mut self, is_blocking: bool) -> io::Result<()> {
+        self.blocking = is_blocking;
+        Ok(())
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, _write: Option<Duration>) -> io::Result<()> {
+        self.read_timeout = read;
+        Ok(())
+    }
+}
+
+impl SockPoll for SimpleReplay {}
+
+struct ReplayDoc;
+impl SockDocViewer for ReplayDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(ReplayConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let original = "{ \"path\": \"session.rec\" }";
+        let fast_loop = "{ \"path\": \"session.rec\", \"speed\": 4.0, \"repeat\": true }";
+        format!(
+            "{}: {}\n{}: {}",
+            "Replay with original timing", original, "Replay 4 times faster in a loop", fast_loop
+        )
+    }
+}
+
+/// Replay socket factory implementing the SocketFactory trait.
+#[derive(Default)]
+pub struct ReplayFactory;
+
+impl ReplayFactory {
+    /// Creates a new replay socket factory.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ReplayConfig {
+    fn check(&self) -> Result<(), error::Error> {
+        if !(self.speed.is_finite() && self.speed > 0.0) {
+            return Err(error::Error::InvalidValue {
+                sock: "replay".to_string(),
+                field: "speed".to_string(),
+                message: "must be positive".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl SocketFactory for ReplayFactory {
+    fn create_sock(&self, params: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
+        let config: ReplayConfig = parse_params("replay", &params)?;
+        config.check()?;
+        let reader = open_log(&config.path)?;
+        Ok(Box::new(SimpleReplay::new(
+            config,
+            RefCell::new(ReplayState {
+                reader,
+                start: None,
+                pending: None,
+                finished: false,
+            }),
+            false,
+            None,
+        )))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
+        Box::new(ReplayDoc)
+    }
+    fn validate_params(&self, params: &SocketParams) -> io::Result<()> {
+        validate_params::<ReplayConfig>("replay", params)?;
+        let config: ReplayConfig = parse_params("replay", params)?;
+        Ok(config.check()?)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use crate::sock::RecordWriter;
+    use std::time::SystemTime;
+
+    #[test]
+    fn replay_timing() {
+        let path = std::env::temp_dir().join(format!("replay-{}.rec", std::process::id()));
+        let mut writer = RecordWriter::new(File::create(&path).unwrap()).unwrap();
+        let now = SystemTime::now();
+        writer.write_chunk(now, b"first").unwrap();
+        writer
+            .write_chunk(now + Duration::from_millis(400), b"second")
+            .unwrap();
+        let params = format!("{{ \"path\": {path:?}, \"speed\": 2.0 }}");
+        let mut sock = ReplayFactory::new().create_sock(params.parse().unwrap()).unwrap();
+        sock.set_block(true).unwrap();
+        let mut buf = [0u8; 16];
+        let start = Instant::now();
+        assert_eq!(sock.read(&mut buf, 3).unwrap(), 3);
+        assert_eq!(sock.read(&mut buf[3..], 13).unwrap(), 2);
+        assert_eq!(&buf[..5], b"first");
+        // Second chunk is due in 200 ms at double speed
+        assert_eq!(sock.read(&mut buf, 16).unwrap(), 6);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_millis(400));
+        assert!(error::is_eof(&sock.read(&mut buf, 16).unwrap_err()));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use super::testgen::TestPatternStrategy;
+use rhai::{AST, Dynamic, Engine, Scope};
+use std::any::Any;
+use std::io::{Error, ErrorKind, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Max number of script operations per iteration, so a runaway script
+/// fails instead of hanging the binding.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+/// Compiled script and the pattern of the current iteration.
+pub struct ScriptState {
+    engine: Engine,
+    ast: AST,
+    iter: u64,
+    frame: Vec<u8>,
+    /// The first iteration is evaluated on creation, but not produced yet
+    fresh: bool,
+}
+
+impl ScriptState {
+    /// Compiles the script and evaluates the first iteration, so errors of
+    /// the script are reported before the binding starts.
+    pub fn new(code: &str) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine
+            .compile(code)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Pattern script: {e}")))?;
+        let mut state = Self {
+            engine,
+            ast,
+            iter: 0,
+            frame: Vec::new(),
+            fresh: true,
+        };
+        state.frame = state.eval()?;
+        Ok(state)
+    }
+    pub fn frame_len(&self) -> usize {
+        self.frame.len()
+    }
+    fn eval(&self) -> Result<Vec<u8>> {
+        let invalid = |e: String| Error::new(ErrorKind::InvalidData, format!("Pattern script: {e}"));
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut scope = Scope::new();
+        scope.push("iter", self.iter as i64);
+        scope.push("time", time);
+        let result: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| invalid(e.to_string()))?;
+        if result.is_blob() {
+            return Ok(result.cast());
+        }
+        if result.is_string() {
+            return Ok(result.cast::<String>().into_bytes());
+        }
+        if result.is_array() {
+            return result
+                .cast::<rhai::Array>()
+                .into_iter()
+                .map(|el| {
+                    el.as_int()
+                        .ok()
+                        .and_then(|byte| u8::try_from(byte).ok())
+                        .ok_or_else(|| invalid(format!("{el} isn't a byte")))
+                })
+                .collect();
+        }
+        Err(invalid(format!(
+            "result must be blob, string or array of bytes, not {}",
+            result.type_name()
+        )))
+    }
+}
+
+pub struct ScriptStrategy;
+impl TestPatternStrategy for ScriptStrategy {
+    fn read(
+        &self,
+        _: &(dyn Any + Send),
+        p: &mut Option<Box<dyn Any + Send>>,
+        buf: &mut [u8],
+        real_size: usize,
+        pos: usize,
+    ) -> Result<usize> {
+        let Some(state) = p.as_ref().unwrap().downcast_ref::<ScriptState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        buf[..real_size].copy_from_slice(&state.frame[pos..pos + real_size]);
+        Ok(real_size)
+    }
+    fn start_iteration(&self, p: &mut Option<Box<dyn Any + Send>>) -> Result<Option<usize>> {
+        let Some(state) = p.as_mut().unwrap().downcast_mut::<ScriptState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        if !state.fresh {
+            state.iter += 1;
+            state.frame = state.eval()?;
+        }
+        state.fresh = false;
+        Ok(Some(state.frame.len()))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use crate::sock::SocketFactory;
+    use crate::sockets::testgen::TestGenFactory;
+
+    #[test]
+    fn script_frames() {
+        // Sequence numbered frame with XOR checksum, its length varies
+        let code = r#"
+            let frame = blob();
+            frame.push(0x7e);
+            frame.push(iter);
+            for i in 0..iter { frame.push(0xaa); }
+            let sum = 0;
+            for i in 0..frame.len() { sum = sum ^ frame[i]; }
+            frame.push(sum);
+            frame
+        "#;
+        let cfg = serde_json::json!({
+            "pat": { "type": "script", "code": code }, "cycle": 0, "iter_num": 3
+        });
+        let sock = TestGenFactory::new().create_sock(cfg.into()).unwrap();
+        let mut buf = [0u8; 16];
+        let mut frames = Vec::new();
+        while let Ok(read) = sock.read(&mut buf, 16) {
+            frames.push(buf[..read].to_vec());
+        }
+        assert_eq!(
+            frames,
+            [vec![0x7e, 0x00, 0x7e], vec![0x7e, 0x01, 0xaa, 0xd5], vec![0x7e, 0x02, 0xaa, 0xaa, 0x7c]]
+        );
+
+        let create = |code: &str| {
+            let cfg = serde_json::json!({ "pat": { "type": "script", "code": code }, "cycle": 0 });
+            TestGenFactory::new().create_sock(cfg.into())
+        };
+        assert!(create(r#""ping" + iter"#).is_ok());
+        assert!(create("[1, 256]").is_err());
+        assert!(create("let x = ;").is_err());
+        assert!(create("loop {}").is_err());
+    }
+}
@@ -0,0 +1,314 @@
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, Type};
+use std::io::{self, Read};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Binds the socket to the network interface (SO_BINDTODEVICE).
+fn bind_to_interface(socket: &Socket, interface: Option<&str>) -> io::Result<()> {
+    let Some(interface) = interface else {
+        return Ok(());
+    };
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    {
+        socket.bind_device(Some(interface.as_bytes()))
+    }
+    #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+    {
+        let _ = socket;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Binding to interface {interface} is not supported on this platform"),
+        ))
+    }
+}
+
+/// TCP level socket options, which are set on Linux only.
+#[derive(Clone, Copy)]
+enum TcpOption {
+    FastOpenConnect,
+    FastOpen,
+    DeferAccept,
+    Cork,
+}
+
+impl TcpOption {
+    fn name(self) -> &'static str {
+        match self {
+            Self::FastOpenConnect => "TCP_FASTOPEN_CONNECT",
+            Self::FastOpen => "TCP_FASTOPEN",
+            Self::DeferAccept => "TCP_DEFER_ACCEPT",
+            Self::Cork => "TCP_CORK",
+        }
+    }
+}
+
+/// Sets integer option of TCP level.
+fn set_tcp_option(socket: &Socket, option: TcpOption, value: i32) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let name = match option {
+            TcpOption::FastOpenConnect => libc::TCP_FASTOPEN_CONNECT,
+            TcpOption::FastOpen => libc::TCP_FASTOPEN,
+            TcpOption::DeferAccept => libc::TCP_DEFER_ACCEPT,
+            TcpOption::Cork => libc::TCP_CORK,
+        };
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                name,
+                &value as *const i32 as *const libc::c_void,
+                size_of::<i32>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            let e = io::Error::last_os_error();
+            return Err(io::Error::new(e.kind(), format!("{}: {e}", option.name())));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (socket, value);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} is not supported on this platform", option.name()),
+        ))
+    }
+}
+
+/// Creates UDP socket bound to the local address and optional interface.
+pub fn udp_bind(addr: SocketAddr, interface: Option<&str>) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    bind_to_interface(&socket, interface)?;
+    socket.bind(&SockAddr::from(addr))?;
+    Ok(socket.into())
+}
+
+/// Connects TCP stream to the remote address through the optional interface.
+/// With `fast_open` the SYN is sent with the first written data, if the
+/// server cookie is known (TCP_FASTOPEN_CONNECT). Without `timeout` the
+/// connection is established within the OS timeout.
+pub fn tcp_connect(
+    addr: SocketAddr,
+    interface: Option<&str>,
+    fast_open: bool,
+    timeout: Option<Duration>,
+) -> io::Result<TcpStream> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    bind_to_interface(&socket, interface)?;
+    if fast_open {
+        set_tcp_option(&socket, TcpOption::FastOpenConnect, 1)?;
+    }
+    match timeout {
+        Some(timeout) => socket.connect_timeout(&SockAddr::from(addr), timeout)?,
+        None => socket.connect(&SockAddr::from(addr))?,
+    }
+    Ok(socket.into())
+}
+
+/// Creates TCP listener on the local address and optional interface.
+/// `fast_open_queue` enables TCP Fast Open with the max number of pending
+/// requests (TCP_FASTOPEN). With `defer_accept` connections are accepted
+/// only when data arrives, but not later than the timeout (TCP_DEFER_ACCEPT).
+pub fn tcp_listen(
+    addr: SocketAddr,
+    interface: Option<&str>,
+    fast_open_queue: Option<u32>,
+    defer_accept: Option<Duration>,
+) -> io::Result<TcpListener> {
+    const BACKLOG: i32 = 128;
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    // Same behaviour as std TcpListener::bind on unix
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    bind_to_interface(&socket, interface)?;
+    if let Some(queue) = fast_open_queue {
+        let queue = queue.try_into().unwrap_or(i32::MAX);
+        set_tcp_option(&socket, TcpOption::FastOpen, queue)?;
+    }
+    if let Some(timeout) = defer_accept {
+        let secs = timeout.as_secs().try_into().unwrap_or(i32::MAX);
+        set_tcp_option(&socket, TcpOption::DeferAccept, secs)?;
+    }
+    socket.bind(&SockAddr::from(addr))?;
+    socket.listen(BACKLOG)?;
+    Ok(socket.into())
+}
+
+/// Sets Nagle's algorithm (TCP_NODELAY), if `nodelay` is given, and holds
+/// partial segments until the message is finished with `tcp_push` (TCP_CORK).
+pub fn tcp_set_delay(stream: &TcpStream, nodelay: Option<bool>, cork: bool) -> io::Result<()> {
+    if let Some(nodelay) = nodelay {
+        stream.set_nodelay(nodelay)?;
+    }
+    if cork {
+        set_tcp_option(&SockRef::from(stream), TcpOption::Cork, 1)?;
+    }
+    Ok(())
+}
+
+/// Sends data held back by TCP_CORK, the stream stays corked for the next
+/// message.
+pub fn tcp_push(stream: &TcpStream) -> io::Result<()> {
+    let socket = SockRef::from(stream);
+    set_tcp_option(&socket, TcpOption::Cork, 0)?;
+    set_tcp_option(&socket, TcpOption::Cork, 1)
+}
+
+/// Closes TCP stream in orderly way: FIN is sent after pending data and the
+/// peer is given up to `grace` to close its side. Closing with unread data
+/// makes OS reset the connection, so the peer could lose the transfer tail.
+/// Zero grace period shuts the connection down immediately.
+pub fn tcp_graceful_close(stream: &TcpStream, grace: Duration) {
+    if grace.is_zero() {
+        let _ = stream.shutdown(Shutdown::Both);
+        return;
+    }
+    let _ = stream.shutdown(Shutdown::Write);
+    let _ = stream.set_nonblocking(false);
+    let deadline = Instant::now() + grace;
+    let mut buf = [0u8; 1024];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || stream.set_read_timeout(Some(left)).is_err() {
+            break;
+        }
+        // Data is discarded until the peer closes the connection
+        match (&*stream).read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Enables kernel receive timestamps of datagrams (SO_TIMESTAMPING). Software
+/// timestamps are always requested, hardware ones are reported when the
+/// network interface is configured to generate them.
+pub fn enable_rx_timestamping(socket: &UdpSocket) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let flags: libc::c_uint = libc::SOF_TIMESTAMPING_RX_SOFTWARE
+            | libc::SOF_TIMESTAMPING_SOFTWARE
+            | libc::SOF_TIMESTAMPING_RX_HARDWARE
+            | libc::SOF_TIMESTAMPING_RAW_HARDWARE;
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_TIMESTAMPING,
+                &flags as *const libc::c_uint as *const libc::c_void,
+                size_of::<libc::c_uint>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = socket;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Kernel receive timestamps are not supported on this platform",
+        ))
+    }
+}
+
+/// Receives a datagram with its kernel receive timestamp (hardware one is
+/// preferred). Timestamping must be enabled by [`enable_rx_timestamping`].
+pub fn recv_with_timestamp(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+) -> io::Result<(usize, Option<SystemTime>)> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        // Control buffer is aligned for cmsghdr and fits scm_timestamping
+        let mut control = [0u64; 16];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = size_of_val(&control) as _;
+        let ret = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut stamp = None;
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+        while !cmsg.is_null() {
+            let hdr = unsafe { &*cmsg };
+            if hdr.cmsg_level == libc::SOL_SOCKET && hdr.cmsg_type == libc::SCM_TIMESTAMPING {
+                // scm_timestamping: software, deprecated and raw hardware stamps
+                let ts: [libc::timespec; 3] =
+                    unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const _) };
+                stamp = [ts[2], ts[0]]
+                    .into_iter()
+                    .find(|t| t.tv_sec != 0 || t.tv_nsec != 0)
+                    .map(|t| {
+                        SystemTime::UNIX_EPOCH + Duration::new(t.tv_sec as u64, t.tv_nsec as u32)
+                    });
+            }
+            cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+        }
+        Ok((ret as usize, stamp))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        socket.recv(buf).map(|sz| (sz, None))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn tcp_fast_open_and_defer_accept() {
+        let listener = tcp_listen(
+            "127.0.0.1:0".parse().unwrap(),
+            None,
+            Some(16),
+            Some(Duration::from_secs(1)),
+        )
+        .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = tcp_connect(addr, None, true, None).unwrap();
+        // Deferred connection is accepted when the first data arrives
+        client.write_all(b"hello").unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 5];
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn tcp_cork_push() {
+        let listener = tcp_listen("127.0.0.1:0".parse().unwrap(), None, None, None).unwrap();
+        let mut client = tcp_connect(listener.local_addr().unwrap(), None, false, None).unwrap();
+        tcp_set_delay(&client, Some(true), true).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        let mut buf = [0u8; 5];
+        // Partial segment is held back (up to 200 ms) until the push
+        client.write_all(b"hello").unwrap();
+        assert!(server.read(&mut buf).is_err());
+        tcp_push(&client).unwrap();
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+}
@@ -1,10 +1,16 @@
+use crate::error;
 use crate::serde_helpers;
+use crate::sockets::proxy::{self, ProxyConfig};
+use crate::sockets::sockopt;
 use crate::sock::make_simple_sock;
 use crate::sock::{ComplexSock, SimpleSock, SockBlockCtl, SocketFactory, SocketParams, SockDocViewer};
+use crate::sock::params::{parse_params, validate_params};
+use crate::sock::{poll, RawHandle, SockPoll};
 use serde::Deserialize;
 use std::cell::RefCell;
 use std::io::{Error, ErrorKind, Read, Write};
-use std::net::{IpAddr, Shutdown, TcpStream};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpStream};
+use std::time::Duration;
 
 /// Configuration for TCP client.
 #[derive(Deserialize, schemars::JsonSchema)]
@@ -16,6 +22,22 @@ pub struct TcpClientConfig {
     )]
     /// Destination port of host TCP server to connect
     port_dst: u16,
+    /// Network interface to bind socket (SO_BINDTODEVICE, Linux only)
+    interface: Option<String>,
+    /// Ordered chain of proxies to reach the destination through
+    #[serde(default)]
+    proxies: Vec<ProxyConfig>,
+    /// TCP Fast Open: the first data is sent in SYN (TCP_FASTOPEN_CONNECT, Linux only)
+    #[serde(default)]
+    fast_open: bool,
+    /// TCP_NODELAY: `true` sends small segments at once, `false` enables Nagle's
+    /// algorithm (OS default, if unset)
+    nodelay: Option<bool>,
+    /// Hold partial segments until the whole message is written (TCP_CORK, Linux only)
+    #[serde(default)]
+    cork: bool,
+    /// Max time to establish the connection in milliseconds (OS timeout, if unset)
+    connect_timeout_ms: Option<u64>,
 }
 
 type MaybeTcpStream = Option<TcpStream>;
@@ -24,24 +46,38 @@ make_simple_sock!(SimpleTcpClient {
     config: TcpClientConfig,
     stream: RefCell<MaybeTcpStream>,
     is_blocking: bool,
+    timeouts: (Option<Duration>, Option<Duration>),
+    grace_period: Duration,
 }, "tcp-client");
 
 impl SimpleSock for SimpleTcpClient {
     fn open(&mut self) -> std::io::Result<()> {
-        self.stream = RefCell::new(Some(TcpStream::connect(format!(
-            "{}:{}",
-            self.config.ip_dst, self.config.port_dst
-        ))?));
+        let cfg = &self.config;
+        let dst = SocketAddr::new(cfg.ip_dst, cfg.port_dst);
+        let timeout = cfg.connect_timeout_ms.map(Duration::from_millis);
+        // The first proxy is connected directly, if the chain is set
+        let stream = match cfg.proxies.first() {
+            Some(proxy) => {
+                let mut stream =
+                    sockopt::tcp_connect(proxy.resolve()?, cfg.interface.as_deref(), cfg.fast_open, timeout)?;
+                proxy::connect_chain(&mut stream, &cfg.proxies, dst)?;
+                stream
+            }
+            None => sockopt::tcp_connect(dst, cfg.interface.as_deref(), cfg.fast_open, timeout)?,
+        };
+        sockopt::tcp_set_delay(&stream, cfg.nodelay, cfg.cork)?;
+        self.stream = RefCell::new(Some(stream));
         if let Some(stream) = self.stream.borrow().as_ref() {
+            stream.set_read_timeout(self.timeouts.0)?;
+            stream.set_write_timeout(self.timeouts.1)?;
             return stream.set_nonblocking(!self.is_blocking);
         }
         Ok(())
     }
     fn close(&mut self) {
-        self.stream
-            .borrow()
-            .as_ref()
-            .map(|s| s.shutdown(Shutdown::Both));
+        if let Some(stream) = self.stream.borrow().as_ref() {
+            sockopt::tcp_graceful_close(stream, self.grace_period);
+        }
     }
     fn read(&self, data: &mut [u8], sz: usize) -> std::io::Result<usize> {
         if let Some(stream) = self.stream.borrow_mut().as_mut() {
@@ -52,14 +88,24 @@ impl SimpleSock for SimpleTcpClient {
                     }
                     return Err(e);
                 }
+                // Peer has closed its write side, no data will come
+                Ok(0) if sz > 0 => return Err(error::Error::Eof.into()),
                 count => return count,
             }
         }
         Err(Error::from(ErrorKind::NotConnected))
     }
-    fn write(&self, data: &[u8], sz: usize) -> std::io::Result<()> {
+    fn write(&self, data: &[u8], sz: usize) -> std::io::Result<usize> {
         if let Some(stream) = self.stream.borrow_mut().as_mut() {
-            return stream.write_all(data[..sz].as_ref());
+            // Partial write is possible, the caller retries the remainder
+            return match stream.write(data[..sz].as_ref()) {
+                // Blocking write would block only if the timeout is expired
+                Err(e) if e.kind() == ErrorKind::WouldBlock && self.is_blocking => {
+                    Err(Error::from(ErrorKind::TimedOut))
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(0),
+                count => count,
+            };
         }
         Err(Error::from(ErrorKind::NotConnected))
     }
@@ -70,6 +116,40 @@ impl SockBlockCtl for SimpleTcpClient {
         self.is_blocking = is_blocking;
         Ok(())
     }
+    // Applied to the stream when it is connected
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> std::io::Result<()> {
+        self.timeouts = (read, write);
+        if let Some(stream) = self.stream.borrow().as_ref() {
+            stream.set_read_timeout(read)?;
+            stream.set_write_timeout(write)?;
+        }
+        Ok(())
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> std::io::Result<()> {
+        self.grace_period = grace;
+        Ok(())
+    }
+    fn flush_message(&self) -> std::io::Result<()> {
+        match self.stream.borrow().as_ref() {
+            Some(stream) if self.config.cork => sockopt::tcp_push(stream),
+            _ => Ok(()),
+        }
+    }
+    fn shutdown_write(&self) -> std::io::Result<()> {
+        match self.stream.borrow().as_ref() {
+            Some(stream) => stream.shutdown(Shutdown::Write),
+            None => Ok(()),
+        }
+    }
+}
+
+impl SockPoll for SimpleTcpClient {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        self.stream
+            .borrow()
+            .as_ref()
+            .map(|s| vec![poll::raw_handle(s)])
+    }
 }
 
 struct TcpClientDoc;
@@ -80,13 +160,24 @@ impl SockDocViewer for TcpClientDoc {
     }
     fn get_examples(&self) -> String {
         let example = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 1234 }";
+        let example_iface = "{ \"ip_dst\": \"192.168.1.10\", \"port_dst\": 1234, \"interface\": \"eth1\" }";
+        let example_proxies = "{ \"ip_dst\": \"10.0.0.7\", \"port_dst\": 1234, \"proxies\": [ \
+            { \"type\": \"socks5\", \"host\": \"gw.example.com\", \"port\": 1080 }, \
+            { \"type\": \"http_connect\", \"host\": \"10.0.0.1\", \"port\": 3128, \"auth\": \"user:pass\" } ] }";
+        let example_tfo = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 1234, \"fast_open\": true }";
+        let example_cork = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 1234, \"nodelay\": true, \"cork\": true }";
         format!(
-            "{}: {}",
+            "{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}",
             "Server configuration with IP constrain", example,
+            "Connection through network interface", example_iface,
+            "Connection through SOCKS5 and HTTP CONNECT proxies", example_proxies,
+            "Connection with TCP Fast Open", example_tfo,
+            "Every message is sent in full segments without delay", example_cork,
         )
     }
 }
 
+#[derive(Default)]
 pub struct TcpClientFactory;
 
 impl TcpClientFactory {
@@ -98,19 +189,48 @@ impl TcpClientFactory {
 impl SocketFactory for TcpClientFactory {
     fn create_sock(&self, params: SocketParams) -> std::io::Result<Box<dyn ComplexSock>> {
         // Deserialize to TcpClientConfig
-        let tcp_config: TcpClientConfig = serde_json::from_str(params.as_str()).map_err(|e| {
-            eprintln!("{e}");
-            Error::new(ErrorKind::InvalidInput, "Invalid TCP configuration")
-        })?;
+        let tcp_config: TcpClientConfig = parse_params("tcp-client", &params)?;
 
         // Blocking by default
         Ok(Box::new(SimpleTcpClient::new(
             tcp_config,
             RefCell::new(None),
             true,
+            (None, None),
+            Duration::ZERO,
         )))
     }
     fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
         Box::new(TcpClientDoc)
     }
+    fn validate_params(&self, params: &SocketParams) -> std::io::Result<()> {
+        Ok(validate_params::<TcpClientConfig>("tcp-client", params)?)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn half_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let params = format!("{{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": {port} }}");
+        let mut client = TcpClientFactory::new().create_sock(params.parse().unwrap()).unwrap();
+        client.open().unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        // Peer gets the end of data, but the client still reads
+        client.shutdown_write().unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(peer.read(&mut buf).unwrap(), 0);
+        peer.write_all(b"bye").unwrap();
+        peer.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(client.read(&mut buf, 8).unwrap(), 3);
+        assert_eq!(&buf[..3], b"bye");
+        assert!(error::is_eof(&client.read(&mut buf, 8).unwrap_err()));
+    }
 }
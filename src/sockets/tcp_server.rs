@@ -1,23 +1,28 @@
+use crate::error;
 use crate::serde_helpers;
+use crate::sockets::ipnet::IpNet;
+use crate::sockets::sockopt;
 use crate::sock::make_simple_sock;
+use crate::sock::params::{parse_params, validate_params};
 use crate::sock::{
-    ComplexSock, SimpleSock, SockBlockCtl, SockDocViewer, SocketFactory, SocketParams,
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll, SocketFactory,
+    SocketParams, check_buffer_size, poll,
 };
 use pretty_hex::PrettyHex;
 use serde::Deserialize;
-use std::collections::LinkedList;
+use std::collections::{LinkedList, VecDeque};
 use std::io::Write;
-use std::io::{self, BufRead, BufReader};
-use std::io::{Error, ErrorKind};
+use std::io::{self, Read};
+use std::mem;
 use std::net::IpAddr;
-use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::net::{SocketAddr, TcpStream};
 use std::sync::Mutex;
 use std::sync::{
     Arc,
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Configuration for TCP server.
 #[derive(Deserialize, schemars::JsonSchema)]
@@ -27,23 +32,211 @@ pub struct TcpServerConfig {
     ip_local: IpAddr,
     /// Local port of TCP server
     port_local: u16,
+    /// Network interface to bind server (SO_BINDTODEVICE, Linux only)
+    interface: Option<String>,
+    /// TCP Fast Open with the max number of pending requests (TCP_FASTOPEN, Linux only)
+    fast_open: Option<u32>,
+    /// Seconds to wait for the first data of a connection before it's accepted
+    /// (TCP_DEFER_ACCEPT, Linux only)
+    defer_accept: Option<u64>,
+    /// TCP_NODELAY: `true` sends small segments at once, `false` enables Nagle's
+    /// algorithm (OS default, if unset)
+    nodelay: Option<bool>,
+    /// Hold partial segments until the whole message is written (TCP_CORK, Linux only)
+    #[serde(default)]
+    cork: bool,
+    /// Clients receiving the written data
+    #[serde(default)]
+    write_policy: WritePolicy,
+    /// Max number of connected clients, new connections over the limit are rejected
+    max_clients: Option<usize>,
+    /// Seconds without data received from the client, after which it's disconnected
+    client_idle_timeout: Option<f64>,
+    /// Prefix the data of every read with `[tag] ` of its client, written
+    /// data after such header (up to the next one) goes to that client only
+    client_tag: Option<ClientTag>,
+    /// Networks of the accepted clients in CIDR notation (any client, if unset)
+    #[schemars(with = "Option<Vec<String>>")]
+    allow: Option<Vec<IpNet>>,
+    /// Networks of the rejected clients in CIDR notation, checked before `allow`
+    #[serde(default)]
+    #[schemars(with = "Vec<String>")]
+    deny: Vec<IpNet>,
+}
+
+impl TcpServerConfig {
+    /// Checks the values, which can't be used by the server.
+    fn check(&self) -> Result<(), error::Error> {
+        if let Some(timeout) = self.client_idle_timeout
+            && !(timeout.is_finite() && timeout > 0.0)
+        {
+            return Err(error::Error::InvalidValue {
+                sock: "tcp-server".to_string(),
+                field: "client_idle_timeout".to_string(),
+                message: "must be a positive number of seconds".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Clients receiving the data written to the server.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WritePolicy {
+    /// Every connected client
+    #[default]
+    Broadcast,
+    /// One client per write, the clients take turns in the connection order
+    RoundRobin,
+    /// The most recently connected client
+    Newest,
+    /// The earliest connected client
+    First,
+}
+
+/// Client identification in the header of the tagged data.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientTag {
+    /// Number of the connection since the server is opened, e.g. `[3] `
+    Index,
+    /// Address of the client, e.g. `[127.0.0.1:40112] `
+    Addr,
+}
+
+impl ClientTag {
+    /// Header of the data read from the client.
+    fn header(self, cli: &Client) -> String {
+        match self {
+            ClientTag::Index => format!("[{}] ", cli.id),
+            ClientTag::Addr => format!("[{}] ", cli.addr),
+        }
+    }
+    /// Returns `true`, if the tag of the header identifies the client.
+    fn matches(self, tag: &str, cli: &Client) -> bool {
+        match self {
+            ClientTag::Index => tag.parse() == Ok(cli.id),
+            ClientTag::Addr => tag.parse() == Ok(cli.addr),
+        }
+    }
+    /// Returns `true`, if the byte can be a part of the tag.
+    fn is_tag_byte(self, byte: u8) -> bool {
+        match self {
+            ClientTag::Index => byte.is_ascii_digit(),
+            ClientTag::Addr => byte.is_ascii_hexdigit() || b".:[]%".contains(&byte),
+        }
+    }
+    /// Parses `[tag] ` header at the start of the data.
+    fn parse_header(self, data: &[u8]) -> Header<'_> {
+        let area = &data[..data.len().min(MAX_HEADER_LEN)];
+        let Some(end) = area.windows(2).position(|w| w == b"] ") else {
+            let is_prefix = area[1..].iter().all(|&b| self.is_tag_byte(b));
+            return if data.len() < MAX_HEADER_LEN && is_prefix {
+                Header::Incomplete
+            } else {
+                Header::None
+            };
+        };
+        let tag = std::str::from_utf8(&data[1..end]).unwrap_or_default();
+        let is_valid = match self {
+            ClientTag::Index => tag.parse::<usize>().is_ok(),
+            ClientTag::Addr => tag.parse::<SocketAddr>().is_ok(),
+        };
+        if is_valid { Header::Complete(tag, end + 2) } else { Header::None }
+    }
+}
+
+/// Max length of the client header, the longest one has IPv6 address with
+/// the scope.
+const MAX_HEADER_LEN: usize = 64;
+
+/// Header found at the start of the data.
+enum Header<'a> {
+    /// Tag of the client and the length of the header
+    Complete(&'a str, usize),
+    /// Header, which is completed by the next write
+    Incomplete,
+    /// Data isn't a header
+    None,
+}
+
+/// Data directed to the client by the header, the data before the first
+/// header has no tag.
+type TaggedPart<'a> = (Option<&'a str>, &'a [u8]);
+
+/// Splits the data by every `[tag] ` header. Returns the parts and the
+/// length of the incomplete header at the end of the data.
+fn split_headers(tag: ClientTag, data: &[u8]) -> (Vec<TaggedPart<'_>>, usize) {
+    let mut parts = Vec::new();
+    let mut current = None;
+    let mut start = 0;
+    let mut pos = 0;
+    while let Some(offset) = data[pos..].iter().position(|&b| b == b'[') {
+        let i = pos + offset;
+        match tag.parse_header(&data[i..]) {
+            Header::Complete(id, len) => {
+                parts.push((current, &data[start..i]));
+                current = Some(id);
+                start = i + len;
+                pos = start;
+            }
+            Header::Incomplete => {
+                parts.push((current, &data[start..i]));
+                return (parts, data.len() - i);
+            }
+            Header::None => pos = i + 1,
+        }
+    }
+    parts.push((current, &data[start..]));
+    (parts, 0)
+}
+
+/// Connection of the client.
+pub struct Client {
+    stream: TcpStream,
+    addr: SocketAddr,
+    /// Number of the connection since the server is opened
+    id: usize,
+    /// Time of the last data received from the client
+    last_read: Instant,
+    /// Written data, which the client isn't ready to receive yet
+    pending: Vec<u8>,
 }
 
 type ListenerHandle = JoinHandle<io::Result<()>>;
+/// Read and write timeouts applied to every client connection.
+type ClientTimeouts = (Option<Duration>, Option<Duration>);
+
+/// Max time to wait for a new connection before the running flag is rechecked.
+const ACCEPT_POLL_TIMEOUT: Duration = Duration::from_millis(100);
+/// Max size of the data pending for one client, the client is disconnected
+/// when it's exceeded.
+const MAX_CLIENT_PENDING: usize = 16 * 1024 * 1024;
 
 make_simple_sock!(TcpServer {
     config: TcpServerConfig,
-    clients: Arc<Mutex<LinkedList<(TcpStream, SocketAddr)>>>,
+    clients: Arc<Mutex<LinkedList<Client>>>,
     blocking: Arc<AtomicBool>,
+    timeouts: Arc<Mutex<ClientTimeouts>>,
+    grace_period: Duration,
     is_running: Arc<AtomicBool>,
     handle: Option<ListenerHandle>,
+    // Clients and lengths of the read data, recorded in echo mode only
+    rx_peers: Option<Mutex<VecDeque<(SocketAddr, usize)>>>,
+    // Turn of the client in round-robin write policy
+    next_client: AtomicUsize,
+    // Client read first by the next tagged read, so no one is starved
+    next_tagged: AtomicUsize,
+    // Incomplete header at the end of the tagged write
+    tx_header: Mutex<Vec<u8>>,
 }, "tcp-server", self, {
     let mut descr = format!("{}{}", self.get_type_name(), self.get_id());
     let clients = self.clients.lock().unwrap();
     if !clients.is_empty() {
         descr.push_str(", connected clients:");
-        for (_, addr) in clients.iter() {
-            descr.push_str(format!("\nClient {addr}").as_str());
+        for cli in clients.iter() {
+            descr.push_str(format!("\nClient {}", cli.addr).as_str());
         }
     }
     descr
@@ -52,26 +245,71 @@ make_simple_sock!(TcpServer {
 impl SimpleSock for TcpServer {
     fn open(&mut self) -> io::Result<()> {
         let cfg = &self.config;
-        let listener = TcpListener::bind(format!("{}:{}", cfg.ip_local, cfg.port_local))?;
+        let listener = sockopt::tcp_listen(
+            SocketAddr::new(cfg.ip_local, cfg.port_local),
+            cfg.interface.as_deref(),
+            cfg.fast_open,
+            cfg.defer_accept.map(Duration::from_secs),
+        )?;
         listener.set_nonblocking(true)?;
         self.is_running.store(true, Ordering::Relaxed);
         let r = self.is_running.clone();
         let clients = self.clients.clone();
         let b = self.blocking.clone();
+        let t = self.timeouts.clone();
+        let (nodelay, cork) = (cfg.nodelay, cfg.cork);
+        let max_clients = cfg.max_clients;
+        let (allow, deny) = (cfg.allow.clone(), cfg.deny.clone());
+        let idle_timeout = cfg.client_idle_timeout.map(Duration::from_secs_f64);
+        let listener_handle = poll::raw_handle(&listener);
+        let mut next_id = 0;
 
         self.handle = Some(thread::spawn(move || -> io::Result<()> {
             while r.load(Ordering::Relaxed) {
-                let cli = if let Ok(cli) = listener.accept() {
+                if let Some(timeout) = idle_timeout {
+                    evict_idle(&clients, timeout);
+                }
+                let (stream, addr) = if let Ok(cli) = listener.accept() {
                     cli
                 } else {
-                    // Check acception every 10 ms, it is
-                    // bad solution, but it is the easyiest way
-                    thread::sleep(Duration::from_millis(10));
+                    // Wait for the next connection, but wake up
+                    // periodically to check the running flag
+                    poll::wait_readable(&[listener_handle], ACCEPT_POLL_TIMEOUT)?;
                     continue;
                 };
-                cli.0.set_nonblocking(!b.load(Ordering::Relaxed))?;
+                let ip = addr.ip();
+                if deny.iter().any(|net| net.contains(ip))
+                    || allow.as_ref().is_some_and(|allow| !allow.iter().any(|net| net.contains(ip)))
+                {
+                    tracing::info!("Client {addr} is rejected, its address isn't allowed");
+                    continue;
+                }
+                if max_clients.is_some_and(|max| clients.lock().unwrap().len() >= max) {
+                    // Connection is closed when the stream is dropped
+                    tracing::info!("Client {addr} is rejected, max number of clients is connected");
+                    continue;
+                }
+                let (read, write) = *t.lock().unwrap();
+                let setup = stream
+                    .set_nonblocking(!b.load(Ordering::Relaxed))
+                    .and_then(|_| stream.set_read_timeout(read))
+                    .and_then(|_| stream.set_write_timeout(write))
+                    .and_then(|_| sockopt::tcp_set_delay(&stream, nodelay, cork));
+                if let Err(e) = setup {
+                    // Failure of one connection (e.g. reset by the client
+                    // right after accept) doesn't stop the listener
+                    tracing::warn!("Client {addr} is dropped, its connection isn't set up: {e}");
+                    continue;
+                }
                 // Pass new connection to client list
-                clients.lock().unwrap().push_back(cli);
+                clients.lock().unwrap().push_back(Client {
+                    stream,
+                    addr,
+                    id: next_id,
+                    last_read: Instant::now(),
+                    pending: Vec::new(),
+                });
+                next_id += 1;
             }
             Ok(())
         }));
@@ -89,54 +327,307 @@ impl SimpleSock for TcpServer {
             let _ = handle.join();
             let mut clients = self.clients.lock().unwrap();
             // Invoke shutdown for every connected client
-            for (cli, _) in clients.iter() {
-                let _ = cli.shutdown(Shutdown::Both);
+            for cli in clients.iter_mut() {
+                if flush_pending(cli).is_ok() && !cli.pending.is_empty() {
+                    let len = cli.pending.len();
+                    tracing::info!("{len} bytes aren't sent to client {}", cli.addr);
+                }
+                sockopt::tcp_graceful_close(&cli.stream, self.grace_period);
             }
             // Clear connection list
             clients.clear();
         }
     }
     fn read(&self, data: &mut [u8], sz: usize) -> io::Result<usize> {
+        check_buffer_size(sz, data.len())?;
+        if let Some(tag) = self.config.client_tag {
+            return self.read_tagged(tag, &mut data[..sz]);
+        }
         let mut clients = self.clients.lock().unwrap();
+        flush_clients(&mut clients);
         let mut total: usize = 0;
+        let mut lost = Vec::new();
 
-        for (cli, addr) in clients.iter_mut() {
-            let mut reader = BufReader::new(cli);
-            // Get current internal state of stream
-            let tmp = if let Ok(tmp) = reader.fill_buf() {
-                tmp
-            } else {
+        for cli in clients.iter_mut() {
+            if total == sz {
+                break;
+            }
+            // Data is read straight into the free space, so nothing taken
+            // from the stream is dropped when the buffer is full
+            let read = match cli.stream.read(&mut data[total..sz]) {
+                // End of stream, the client closed the connection
+                Ok(0) => {
+                    tracing::info!("Client {} is disconnected", cli.addr);
+                    lost.push(cli.addr);
+                    continue;
+                }
+                Ok(read) => read,
+                Err(e) if is_disconnect(&e) => {
+                    tracing::info!("Client {} is disconnected: {e}", cli.addr);
+                    lost.push(cli.addr);
+                    continue;
+                }
+                // Go to the next client if this empty
+                Err(_) => continue,
+            };
+            cli.last_read = Instant::now();
+            // Trace data with client address if trace level is trace
+            tracing::trace!(
+                "Data received from {}:\n{}",
+                cli.addr,
+                data[total..total + read].hex_dump()
+            );
+            if let Some(rx_peers) = &self.rx_peers {
+                rx_peers.lock().unwrap().push_back((cli.addr, read));
+            }
+            total += read;
+        }
+        drop_clients(&mut clients, |cli| lost.contains(&cli.addr));
+
+        Ok(total)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> io::Result<usize> {
+        if let Some(rx_peers) = &self.rx_peers {
+            return self.reply(rx_peers, &data[..sz]);
+        }
+        let mut clients = self.clients.lock().unwrap();
+        flush_clients(&mut clients);
+        let Some(tag) = self.config.client_tag else {
+            self.send_by_policy(&mut clients, &data[..sz]);
+            return Ok(sz);
+        };
+        let mut pending = self.tx_header.lock().unwrap();
+        pending.extend_from_slice(&data[..sz]);
+        let (parts, incomplete) = split_headers(tag, &pending);
+        for (id, part) in parts {
+            if part.is_empty() {
+                continue;
+            }
+            let Some(id) = id else {
+                self.send_by_policy(&mut clients, part);
                 continue;
             };
+            match clients.iter_mut().find(|cli| tag.matches(id, cli)) {
+                Some(cli) => {
+                    if send(cli, part).is_err() {
+                        let addr = cli.addr;
+                        drop_clients(&mut clients, |cli| cli.addr == addr);
+                    }
+                }
+                None => tracing::info!("Client [{id}] isn't connected, data is dropped"),
+            }
+        }
+        let len = pending.len();
+        pending.drain(..len - incomplete);
+        Ok(sz)
+    }
+}
 
-            let tmp_len = tmp.len();
-            // Go to the next client if this empty
-            if tmp_len == 0 {
+impl TcpServer {
+    /// Sends the data to the clients chosen by the write policy.
+    fn send_by_policy(&self, clients: &mut LinkedList<Client>, data: &[u8]) {
+        let len = clients.len();
+        if len == 0 {
+            return;
+        }
+        // Index of the only client receiving the data
+        let target = match self.config.write_policy {
+            WritePolicy::Broadcast => None,
+            WritePolicy::RoundRobin => {
+                Some(self.next_client.fetch_add(1, Ordering::Relaxed) % len)
+            }
+            WritePolicy::Newest => Some(len - 1),
+            WritePolicy::First => Some(0),
+        };
+
+        // Data is considered written even if some clients failed to
+        // receive it, the rest of the partial write is pending
+        let mut lost = Vec::new();
+        for (i, cli) in clients.iter_mut().enumerate() {
+            if target.is_some_and(|target| target != i) {
                 continue;
             }
-            if total + tmp_len > sz || total + tmp_len > data.len() {
+            if send(cli, data).is_err() {
+                lost.push(cli.addr);
+            }
+        }
+        drop_clients(clients, |cli| lost.contains(&cli.addr));
+    }
+    /// Reads the data of one client prefixed with its header. The clients
+    /// take turns to be read first.
+    fn read_tagged(&self, tag: ClientTag, data: &mut [u8]) -> io::Result<usize> {
+        let mut clients = self.clients.lock().unwrap();
+        flush_clients(&mut clients);
+        let start = self.next_tagged.load(Ordering::Relaxed) % clients.len().max(1);
+        let mut turn: Vec<_> = clients.iter_mut().enumerate().collect();
+        turn.rotate_left(start);
+        let mut result = Ok(0);
+        let mut lost = Vec::new();
+        for (i, cli) in turn {
+            let header = tag.header(cli);
+            if header.len() >= data.len() {
+                result = Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Buffer of {} bytes can't hold the client header", data.len()),
+                ));
                 break;
             }
-            // Trace data with client address if trace level is trace
-            log::trace!("Data received from {}:\n{}", addr, tmp.hex_dump());
-            data[total..total + tmp_len].copy_from_slice(tmp);
-            total += tmp_len;
-            // Now data is really dropped from stream queue
-            reader.consume(tmp_len);
+            let read = match cli.stream.read(&mut data[header.len()..]) {
+                Ok(0) => {
+                    tracing::info!("Client {} is disconnected", cli.addr);
+                    lost.push(cli.addr);
+                    continue;
+                }
+                Ok(read) => read,
+                Err(e) if is_disconnect(&e) => {
+                    tracing::info!("Client {} is disconnected: {e}", cli.addr);
+                    lost.push(cli.addr);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            cli.last_read = Instant::now();
+            data[..header.len()].copy_from_slice(header.as_bytes());
+            tracing::trace!(
+                "Data received from {}:\n{}",
+                cli.addr,
+                data[header.len()..header.len() + read].hex_dump()
+            );
+            self.next_tagged.store(i + 1, Ordering::Relaxed);
+            result = Ok(header.len() + read);
+            break;
         }
-
-        Ok(total)
+        drop_clients(&mut clients, |cli| lost.contains(&cli.addr));
+        result
     }
-    fn write(&self, data: &[u8], sz: usize) -> io::Result<()> {
+    /// Writes data back to the clients it was read from, in the order it
+    /// was read. Data of a disconnected client is dropped.
+    fn reply(&self, rx_peers: &Mutex<VecDeque<(SocketAddr, usize)>>, data: &[u8]) -> io::Result<usize> {
         let mut clients = self.clients.lock().unwrap();
+        flush_clients(&mut clients);
+        let mut rx_peers = rx_peers.lock().unwrap();
+        let mut lost = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let Some((addr, len)) = rx_peers.front_mut() else {
+                tracing::trace!("{} bytes aren't read from any client, dropped", rest.len());
+                break;
+            };
+            let part = (*len).min(rest.len());
+            if let Some(cli) = clients.iter_mut().find(|cli| cli.addr == *addr)
+                && send(cli, &rest[..part]).is_err()
+            {
+                lost.push(cli.addr);
+            }
+            *len -= part;
+            if *len == 0 {
+                rx_peers.pop_front();
+            }
+            rest = &rest[part..];
+        }
+        drop_clients(&mut clients, |cli| lost.contains(&cli.addr));
+        Ok(data.len())
+    }
+}
+
+/// Writes the data to the client after its pending data. The part, which
+/// the client isn't ready to receive, is kept pending. The error is returned
+/// if the client is disconnected.
+fn send(cli: &mut Client, data: &[u8]) -> io::Result<()> {
+    flush_pending(cli)?;
+    let written = if cli.pending.is_empty() {
+        write_some(cli, data)?
+    } else {
+        0
+    };
+    if written == data.len() {
+        tracing::trace!("Data sent to {}", cli.addr);
+        return Ok(());
+    }
+    if cli.pending.len() + data.len() - written > MAX_CLIENT_PENDING {
+        tracing::info!(
+            "Client {} is disconnected, it doesn't receive data and {} bytes are pending",
+            cli.addr,
+            cli.pending.len()
+        );
+        return Err(io::ErrorKind::WriteZero.into());
+    }
+    cli.pending.extend_from_slice(&data[written..]);
+    Ok(())
+}
+
+/// Writes the pending data of the client, as much as it's ready to receive.
+fn flush_pending(cli: &mut Client) -> io::Result<()> {
+    if cli.pending.is_empty() {
+        return Ok(());
+    }
+    let pending = mem::take(&mut cli.pending);
+    let written = write_some(cli, &pending);
+    cli.pending = pending;
+    cli.pending.drain(..written?);
+    Ok(())
+}
 
-        for (cli, addr) in clients.iter_mut() {
-            if cli.write_all(data[..sz].as_ref()).is_ok() {
-                log::trace!("Data sent to {}", addr);
+/// Writes the pending data of every client, the disconnected ones are removed.
+fn flush_clients(clients: &mut LinkedList<Client>) {
+    let mut lost = Vec::new();
+    for cli in clients.iter_mut() {
+        if flush_pending(cli).is_err() {
+            lost.push(cli.addr);
+        }
+    }
+    drop_clients(clients, |cli| lost.contains(&cli.addr));
+}
+
+/// Writes the data until the stream isn't ready (no space of nonblocking
+/// stream or the timeout of blocking one), returns the written length.
+fn write_some(cli: &mut Client, data: &[u8]) -> io::Result<usize> {
+    let mut written = 0;
+    while written < data.len() {
+        match cli.stream.write(&data[written..]) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(len) => written += len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if is_disconnect(&e) => {
+                tracing::info!("Client {} is disconnected: {e}", cli.addr);
+                return Err(e);
             }
+            Err(_) => break,
         }
-        Ok(())
     }
+    Ok(written)
+}
+
+/// Disconnects the clients, which sent nothing for the timeout.
+fn evict_idle(clients: &Mutex<LinkedList<Client>>, timeout: Duration) {
+    let mut clients = clients.lock().unwrap();
+    let now = Instant::now();
+    let idle = |cli: &Client| now.duration_since(cli.last_read) >= timeout;
+    for cli in clients.iter().filter(|cli| idle(cli)) {
+        tracing::info!("Client {} is idle for {timeout:?}, disconnected", cli.addr);
+    }
+    drop_clients(&mut clients, idle);
+}
+
+/// Removes the clients matching the predicate from the list, their
+/// connections are closed.
+fn drop_clients(clients: &mut LinkedList<Client>, pred: impl Fn(&Client) -> bool) {
+    if !clients.iter().any(&pred) {
+        return;
+    }
+    *clients = mem::take(clients)
+        .into_iter()
+        .filter(|cli| !pred(cli))
+        .collect();
+}
+
+/// Returns `true`, if the error means the lost connection (not a timeout
+/// of the blocking stream or no data of the nonblocking one).
+fn is_disconnect(e: &io::Error) -> bool {
+    !matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+    )
 }
 
 impl SockBlockCtl for TcpServer {
@@ -144,6 +635,38 @@ impl SockBlockCtl for TcpServer {
         self.blocking.store(is_blocking, Ordering::Relaxed);
         Ok(())
     }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        *self.timeouts.lock().unwrap() = (read, write);
+        for cli in self.clients.lock().unwrap().iter() {
+            cli.stream.set_read_timeout(read)?;
+            cli.stream.set_write_timeout(write)?;
+        }
+        Ok(())
+    }
+    fn set_grace_period(&mut self, grace: Duration) -> io::Result<()> {
+        self.grace_period = grace;
+        Ok(())
+    }
+    fn set_echo(&mut self, echo: bool) -> io::Result<()> {
+        self.rx_peers = echo.then(|| Mutex::new(VecDeque::new()));
+        Ok(())
+    }
+    fn flush_message(&self) -> io::Result<()> {
+        if self.config.cork {
+            // Message is broadcasted, so it's finished for every client
+            for cli in self.clients.lock().unwrap().iter() {
+                sockopt::tcp_push(&cli.stream)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SockPoll for TcpServer {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        let clients = self.clients.lock().unwrap();
+        Some(clients.iter().map(|cli| poll::raw_handle(&cli.stream)).collect())
+    }
 }
 
 struct TcpServerDoc;
@@ -155,14 +678,23 @@ impl SockDocViewer for TcpServerDoc {
     fn get_examples(&self) -> String {
         let example_ip = "{ \"ip_local\": \"127.0.0.1\", \"port_local\": 1234 }";
         let example_no_ip = "{ \"port_local\": 1234 }";
+        let example_tfo = "{ \"port_local\": 1234, \"fast_open\": 256, \"defer_accept\": 5 }";
+        let example_cork = "{ \"port_local\": 1234, \"nodelay\": true, \"cork\": true }";
+        let example_rr = "{ \"port_local\": 1234, \"write_policy\": \"round_robin\" }";
+        let example_tag = "{ \"port_local\": 1234, \"client_tag\": \"index\" }";
         format!(
-            "{}: {}\n{}: {}",
+            "{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}",
             "Server configuration with IP constrain", example_ip,
             "Server configuration without IP constrain", example_no_ip,
+            "Server with TCP Fast Open and deferred accept", example_tfo,
+            "Every message is sent in full segments without delay", example_cork,
+            "Every write goes to the next client in turn", example_rr,
+            "Data is tagged with the client, e.g. [0] data", example_tag,
         )
     }
 }
 
+#[derive(Default)]
 pub struct TcpServerFactory;
 
 impl TcpServerFactory {
@@ -174,21 +706,285 @@ impl TcpServerFactory {
 impl SocketFactory for TcpServerFactory {
     fn create_sock(&self, params: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
         // Deserialize to TcpServerConfig
-        let tcp_config: TcpServerConfig = serde_json::from_str(params.as_str()).map_err(|e| {
-            eprintln!("{e}");
-            Error::new(ErrorKind::InvalidInput, "Invalid TCP configuration")
-        })?;
+        let tcp_config: TcpServerConfig = parse_params("tcp-server", &params)?;
+        tcp_config.check()?;
 
         // Blocking by default
         Ok(Box::new(TcpServer::new(
             tcp_config,
             Arc::new(Mutex::new(LinkedList::new())),
             Arc::new(AtomicBool::new(true)),
+            Arc::new(Mutex::new((None, None))),
+            Duration::ZERO,
             Arc::new(AtomicBool::new(true)),
             None,
+            None,
+            AtomicUsize::new(0),
+            AtomicUsize::new(0),
+            Mutex::new(Vec::new()),
         )))
     }
     fn create_doc_viewer(&self) -> Box<dyn crate::sock::SockDocViewer> {
         Box::new(TcpServerDoc)
     }
+    fn validate_params(&self, params: &SocketParams) -> io::Result<()> {
+        validate_params::<TcpServerConfig>("tcp-server", params)?;
+        Ok(parse_params::<TcpServerConfig>("tcp-server", params)?.check()?)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use std::io::ErrorKind;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    /// Opens nonblocking server on the free port with the extra parameters.
+    fn open_server(extra: &str) -> (Box<dyn ComplexSock>, u16) {
+        // Free port is taken from the OS
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let params = format!("{{ \"ip_local\": \"127.0.0.1\", \"port_local\": {port} {extra} }}");
+        let mut server = TcpServerFactory::new().create_sock(params.parse().unwrap()).unwrap();
+        server.set_block(false).unwrap();
+        server.open().unwrap();
+        (server, port)
+    }
+
+    /// Connects the clients one by one, each one is accepted before the next.
+    fn connect(server: &dyn ComplexSock, port: u16, count: usize) -> Vec<TcpStream> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        (1..=count)
+            .map(|accepted| {
+                let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+                client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+                while server.poll_handles().unwrap().len() < accepted {
+                    assert!(Instant::now() < deadline);
+                    thread::sleep(Duration::from_millis(5));
+                }
+                client
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_reads_keep_data() {
+        let (mut server, port) = open_server("");
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let sent: Vec<u8> = (0..100).collect();
+        client.write_all(&sent).unwrap();
+
+        let mut buf = [0u8; 8];
+        assert_eq!(
+            server.read(&mut buf, 9).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        let mut received: Vec<u8> = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.len() < sent.len() && Instant::now() < deadline {
+            // Reads smaller than the received data don't lose the rest
+            let read = server.read(&mut buf, 7).unwrap();
+            received.extend(&buf[..read]);
+        }
+        assert_eq!(received, sent);
+        server.close();
+    }
+
+    #[test]
+    fn client_limits() {
+        let (server, port) = open_server(r#", "max_clients": 1"#);
+        let _first = connect(server.as_ref(), port, 1);
+        // Connection over the limit is closed by the server
+        let mut rejected = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        rejected.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(rejected.read(&mut [0u8; 1]).unwrap(), 0);
+        assert_eq!(server.poll_handles().unwrap().len(), 1);
+
+        let (server, port) = open_server(r#", "client_idle_timeout": 0.2"#);
+        let mut clients = connect(server.as_ref(), port, 2);
+        let mut buf = [0u8; 8];
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            clients[1].write_all(b"x").unwrap();
+            thread::sleep(Duration::from_millis(20));
+            server.read(&mut buf, 8).unwrap();
+        }
+        // Silent client is disconnected, active one is kept
+        assert_eq!(clients[0].read(&mut buf).unwrap(), 0);
+        assert_eq!(server.poll_handles().unwrap().len(), 1);
+        assert!(
+            TcpServerFactory::new()
+                .validate_params(&r#"{ "port_local": 1, "client_idle_timeout": 0 }"#.parse().unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn disconnected_clients() {
+        let (mut server, port) = open_server("");
+        let mut clients = connect(server.as_ref(), port, 3);
+        drop(clients.remove(0));
+        let mut buf = [0u8; 8];
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // Closed connection is found by the read
+        while server.poll_handles().unwrap().len() > 2 {
+            assert!(Instant::now() < deadline);
+            server.read(&mut buf, 8).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+        // Reset connection is found by the write
+        drop(clients.remove(0));
+        while server.poll_handles().unwrap().len() > 1 {
+            assert!(Instant::now() < deadline);
+            server.write(b"x", 1).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+        // Remaining client still gets the data
+        server.write(b"data", 4).unwrap();
+        let mut data = Vec::new();
+        while !data.ends_with(b"data") {
+            let mut byte = [0u8; 1];
+            clients[0].read_exact(&mut byte).unwrap();
+            data.push(byte[0]);
+        }
+        server.close();
+    }
+
+    #[test]
+    fn address_lists() {
+        let rejected = |extra: &str| {
+            let (server, port) = open_server(extra);
+            let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            client.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+            // Rejected connection is closed by the server, accepted one waits for data
+            let closed = client.read(&mut [0u8; 1]).is_ok_and(|read| read == 0);
+            assert_eq!(server.poll_handles().unwrap().len(), usize::from(!closed));
+            closed
+        };
+        assert!(rejected(r#", "deny": ["127.0.0.0/8"]"#));
+        assert!(rejected(r#", "allow": ["10.0.0.0/8", "::1"]"#));
+        assert!(rejected(r#", "allow": ["127.0.0.1"], "deny": ["127.0.0.1/32"]"#));
+        assert!(!rejected(r#", "allow": ["10.0.0.0/8", "127.0.0.1"]"#));
+        assert!(
+            TcpServerFactory::new()
+                .validate_params(&r#"{ "port_local": 1, "allow": ["10.0.0.0/40"] }"#.parse().unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn write_policies() {
+        let read = |client: &mut TcpStream, len: usize| {
+            let mut buf = vec![0u8; len];
+            client.read_exact(&mut buf).unwrap();
+            buf
+        };
+        let (mut server, port) = open_server(r#", "write_policy": "round_robin""#);
+        let mut clients = connect(server.as_ref(), port, 2);
+        for data in [b"a", b"b", b"c"] {
+            server.write(data, 1).unwrap();
+        }
+        assert_eq!(read(&mut clients[0], 2), b"ac");
+        assert_eq!(read(&mut clients[1], 1), b"b");
+        server.close();
+
+        for (policy, receiver) in [("newest", 1), ("first", 0)] {
+            let (mut server, port) = open_server(&format!(r#", "write_policy": "{policy}""#));
+            let mut clients = connect(server.as_ref(), port, 2);
+            server.write(b"data", 4).unwrap();
+            server.write(b"next", 4).unwrap();
+            assert_eq!(read(&mut clients[receiver], 8), b"datanext");
+            // Other client gets nothing
+            let other = &mut clients[1 - receiver];
+            other.set_nonblocking(true).unwrap();
+            let err = other.read(&mut [0u8; 4]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::WouldBlock);
+            server.close();
+        }
+    }
+
+    #[test]
+    fn client_tags() {
+        let (mut server, port) = open_server(r#", "client_tag": "index""#);
+        let mut clients = connect(server.as_ref(), port, 2);
+        let mut buf = [0u8; 16];
+        let mut read_tagged = |server: &dyn ComplexSock| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                let read = server.read(&mut buf, 16).unwrap();
+                if read > 0 {
+                    return buf[..read].to_vec();
+                }
+                assert!(Instant::now() < deadline);
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+        clients[1].write_all(b"one").unwrap();
+        assert_eq!(read_tagged(server.as_ref()), b"[1] one");
+        clients[0].write_all(b"zero").unwrap();
+        assert_eq!(read_tagged(server.as_ref()), b"[0] zero");
+
+        // Header directs the data to its client only
+        server.write(b"[1] to one", 10).unwrap();
+        server.write(b"all", 3).unwrap();
+        let mut data = [0u8; 9];
+        clients[1].read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"to oneall");
+        clients[0].read_exact(&mut data[..3]).unwrap();
+        assert_eq!(&data[..3], b"all");
+        // Every header of the write is parsed, the split one is completed
+        // by the next write
+        server.write(b"[0] zero[1", 10).unwrap();
+        server.write(b"] one", 5).unwrap();
+        clients[0].read_exact(&mut data[..4]).unwrap();
+        assert_eq!(&data[..4], b"zero");
+        clients[1].read_exact(&mut data[..3]).unwrap();
+        assert_eq!(&data[..3], b"one");
+        assert_eq!(
+            server.read(&mut buf, 4).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        server.close();
+
+        let (parts, incomplete) = split_headers(ClientTag::Addr, b"a[1.2.3.4:5] x[[::1]:7] y[1.2");
+        assert_eq!(parts, [(None, &b"a"[..]), (Some("1.2.3.4:5"), b"x"), (Some("[::1]:7"), b"y")]);
+        assert_eq!(incomplete, 4);
+        let (parts, incomplete) = split_headers(ClientTag::Index, b"[1]x[a] y");
+        assert_eq!((parts, incomplete), (vec![(None, &b"[1]x[a] y"[..])], 0));
+    }
+    #[test]
+    fn slow_client_gets_all_data() {
+        let (mut server, port) = open_server("");
+        let mut clients = connect(server.as_ref(), port, 1);
+        let mut client = clients.remove(0);
+        // Client doesn't read while the data is written, so the writes
+        // outgrow the socket buffers and the rest is kept pending
+        let sent: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        for chunk in sent.chunks(64 * 1024) {
+            server.write(chunk, chunk.len()).unwrap();
+        }
+        let reader = thread::spawn(move || {
+            let mut received = vec![0u8; 8 * 1024 * 1024];
+            client.read_exact(&mut received).unwrap();
+            // Client is kept connected till the end of the check
+            (client, received)
+        });
+        // Pending data is sent by the next reads and writes of the server
+        let mut buf = [0u8; 8];
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !reader.is_finished() {
+            assert!(Instant::now() < deadline);
+            server.read(&mut buf, 8).unwrap();
+            server.write(b"", 0).unwrap();
+        }
+        let (_client, received) = reader.join().unwrap();
+        assert!(received == sent);
+        assert_eq!(server.poll_handles().unwrap().len(), 1);
+        server.close();
+    }
 }
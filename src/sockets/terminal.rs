@@ -1,9 +1,11 @@
-use crate::sock::{ComplexSock, SimpleSock, SockBlockCtl, SocketFactory, SocketParams, make_simple_sock};
+use crate::error;
+use crate::sock::{ComplexSock, SimpleSock, SockBlockCtl, SockPoll, SocketFactory, SocketParams, make_simple_sock};
 use std::io::{self, ErrorKind, Read, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 fn spawn_stdin_channel() -> (Receiver<Vec<u8>>, JoinHandle<io::Result<()>>, Arc<AtomicBool>) {
     let (tx, rx) = mpsc::channel();
@@ -14,7 +16,11 @@ fn spawn_stdin_channel() -> (Receiver<Vec<u8>>, JoinHandle<io::Result<()>>, Arc<
         while r.load(Ordering::Relaxed) {
             const CHUNK_SIZE: usize = 1024;
             let mut chunk: Vec<u8> = vec![0; CHUNK_SIZE];
-            let sz = read_blocking(&term, &mut chunk, CHUNK_SIZE).unwrap();
+            let sz = match read_blocking(&term, &mut chunk, CHUNK_SIZE) {
+                // Dropped sender reports the end of data to the reader
+                Err(e) if error::is_eof(&e) => return Ok(()),
+                res => res?,
+            };
             chunk.truncate(sz);
             if tx.send(chunk).is_err() {
                 return Err(io::Error::from(ErrorKind::BrokenPipe));
@@ -36,11 +42,14 @@ type SimpleTermReadCb = fn(obj: &SimpleTerminal, data: &mut [u8], sz: usize) ->
 make_simple_sock!(SimpleTerminal {
     non_block_ctl: Option<SimpleTerminalNonblocking>,
     read: SimpleTermReadCb,
+    read_timeout: Option<Duration>,
+    // Leftover of the channel chunk, which didn't fit into the last read
+    pending: Mutex<Vec<u8>>,
 }, "stdio");
 
 impl Default for SimpleTerminal {
     fn default() -> Self {
-        Self::new(None, read_blocking)
+        Self::new(None, read_blocking, None, Mutex::default())
     }
 }
 
@@ -50,33 +59,75 @@ fn read_blocking(_: &SimpleTerminal, data: &mut [u8], sz: usize) -> io::Result<u
         print!("stdio# ");
         stdout.flush()?;
     }
-    io::stdin().lock().read(data[..sz].as_mut())
+    match io::stdin().lock().read(data[..sz].as_mut())? {
+        // Stdin is closed (e.g. Ctrl-D or the end of the piped input)
+        0 if sz > 0 => Err(error::Error::Eof.into()),
+        len => Ok(len),
+    }
 }
 
 fn read_nonblocking(obj: &SimpleTerminal, data: &mut [u8], sz: usize) -> io::Result<usize> {
     let ctl = obj.non_block_ctl.as_ref().expect("You can't use nonblocking method without initialization");
+    if let Some(len) = copy_pending(obj, data, sz) {
+        return Ok(len);
+    }
     let buf = match ctl.stdin.try_recv() {
         Err(TryRecvError::Empty) => return Ok(0),
-        Err(TryRecvError::Disconnected) => return Err(io::Error::from(ErrorKind::ResourceBusy)),
+        Err(TryRecvError::Disconnected) => return Err(error::Error::Eof.into()),
         Ok(buf) => buf,
     };
+    Ok(copy_chunk(obj, buf, data, sz))
+}
 
-    let len = if buf.len() < sz {
-        buf.len()
-    } else {
-        sz
+// Blocking read through the stdin channel, which is limited by the timeout
+fn read_timeout(obj: &SimpleTerminal, data: &mut [u8], sz: usize) -> io::Result<usize> {
+    let ctl = obj.non_block_ctl.as_ref().expect("You can't use timeout method without initialization");
+    if let Some(len) = copy_pending(obj, data, sz) {
+        return Ok(len);
+    }
+    let res = match obj.read_timeout {
+        Some(timeout) => ctl.stdin.recv_timeout(timeout),
+        None => ctl.stdin.recv().map_err(RecvTimeoutError::from),
     };
+    let buf = match res {
+        Err(RecvTimeoutError::Timeout) => return Ok(0),
+        Err(RecvTimeoutError::Disconnected) => return Err(error::Error::Eof.into()),
+        Ok(buf) => buf,
+    };
+    Ok(copy_chunk(obj, buf, data, sz))
+}
+
+// Copies the chunk of the channel, the part that doesn't fit into `sz` is
+// kept for the next reads
+fn copy_chunk(obj: &SimpleTerminal, mut buf: Vec<u8>, data: &mut [u8], sz: usize) -> usize {
+    let len = buf.len().min(sz);
 
     data[..len].copy_from_slice(buf[..len].as_ref());
-    Ok(len)
+    if len < buf.len() {
+        buf.drain(..len);
+        *obj.pending.lock().unwrap() = buf;
+    }
+    len
+}
+
+// Copies the leftover of the previous chunk, if any
+fn copy_pending(obj: &SimpleTerminal, data: &mut [u8], sz: usize) -> Option<usize> {
+    let mut pending = obj.pending.lock().unwrap();
+    if pending.is_empty() || sz == 0 {
+        return None;
+    }
+    let len = pending.len().min(sz);
+    data[..len].copy_from_slice(pending[..len].as_ref());
+    pending.drain(..len);
+    Some(len)
 }
 
 impl SimpleSock for SimpleTerminal {
-    fn write(&self, data: &[u8], sz: usize) -> io::Result<()> {
+    fn write(&self, data: &[u8], sz: usize) -> io::Result<usize> {
         let mut stdout = io::stdout().lock();
         stdout.write_all(data[..sz].as_ref())?;
         stdout.flush()?;
-        Ok(())
+        Ok(sz)
     }
     fn read(&self, data: &mut [u8], sz: usize) -> io::Result<usize> {
         (self.read)(self, data, sz)
@@ -105,17 +156,39 @@ impl SockBlockCtl for SimpleTerminal {
         }
         Ok(())
     }
+    fn set_timeout(&mut self, read: Option<Duration>, _: Option<Duration>) -> io::Result<()> {
+        self.read_timeout = read;
+        // Stdin can't be read with timeout directly, so the blocking
+        // mode switches to the helper thread channel
+        if read.is_some() && self.non_block_ctl.is_none() {
+            self.read = read_timeout;
+            let (receiver, handle, running) = spawn_stdin_channel();
+            self.non_block_ctl = Some(
+                SimpleTerminalNonblocking { running, handle: Some(handle), stdin: receiver }
+            );
+        }
+        Ok(())
+    }
 }
 
+// Stdin is read by the helper thread, so readiness can't be polled
+impl SockPoll for SimpleTerminal {}
+
 impl Drop for SimpleTerminal {
     fn drop(&mut self) {
         if let Some(ctl) = &mut self.non_block_ctl {
             ctl.running.store(false, Ordering::Relaxed);
-            let _ = ctl.handle.take().unwrap().join();
+            // The helper thread may be blocked on stdin read forever, so it
+            // is detached and exits after the next read
+            let handle = ctl.handle.take().unwrap();
+            if handle.is_finished() {
+                let _ = handle.join();
+            }
         }
     }
 }
 
+#[derive(Default)]
 pub struct SimpleTerminalFactory;
 
 impl SimpleTerminalFactory {
@@ -128,6 +201,10 @@ impl SocketFactory for SimpleTerminalFactory {
     fn create_sock(&self, _: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
         Ok(Box::new(SimpleTerminal::default()))
     }
+    fn validate_params(&self, _: &SocketParams) -> io::Result<()> {
+        // Parameters aren't used
+        Ok(())
+    }
 }
 
 mod tests {
@@ -135,13 +212,49 @@ mod tests {
 
     use std::collections::HashMap;
 
-    use crate::{sock::SocketFactory, sockets::terminal::SimpleTerminalFactory, sock::SocketWrapper};
+    use crate::{error, sock::SocketFactory, sock::SocketParams, sockets::terminal::SimpleTerminalFactory, sock::SocketWrapper};
+    use super::*;
 
     #[test]
     fn stdout_test() {
         let factory = SimpleTerminalFactory::new();
-        let sock = SocketWrapper::new(factory.create_sock(String::new()).unwrap());
-        let data: Vec<u8> = sock.read_all().unwrap();
+        let sock = SocketWrapper::new(factory.create_sock(SocketParams::default()).unwrap());
+        // Stdin of the test may be closed, it's the end of data
+        let data: Vec<u8> = match sock.read_all() {
+            Err(e) if error::is_eof(&e) => Vec::new(),
+            res => res.unwrap(),
+        };
         assert!(sock.generic_write(data.as_ref(), data.len()).is_ok());
     }
+
+    #[test]
+    fn small_read_chunk() {
+        let (tx, rx) = mpsc::channel();
+        let ctl = SimpleTerminalNonblocking {
+            running: Arc::new(AtomicBool::new(true)),
+            handle: Some(thread::spawn(|| Ok(()))),
+            stdin: rx,
+        };
+        let timeout = Some(Duration::from_millis(10));
+        let mut term = SimpleTerminal::new(Some(ctl), read_nonblocking, timeout, Mutex::default());
+        tx.send(b"0123456789".to_vec()).unwrap();
+        let mut data = [0; 4];
+        let mut received: Vec<u8> = Vec::new();
+        while received.len() < 10 {
+            let len = term.read(&mut data, 4).unwrap();
+            assert!((1..=4).contains(&len));
+            received.extend(&data[..len]);
+        }
+        assert_eq!(received, b"0123456789");
+        assert_eq!(term.read(&mut data, 4).unwrap(), 0);
+
+        // The leftover is read before the end of data is reported
+        tx.send(b"abcdef".to_vec()).unwrap();
+        drop(tx);
+        term.read = read_timeout;
+        assert_eq!(term.read(&mut data, 4).unwrap(), 4);
+        assert_eq!(term.read(&mut data, 4).unwrap(), 2);
+        assert_eq!(&data[..2], b"ef");
+        assert!(term.read(&mut data, 4).is_err_and(|e| error::is_eof(&e)));
+    }
 }
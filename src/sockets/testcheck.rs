@@ -0,0 +1,196 @@
+use crate::error;
+use crate::sock::params::{parse_params, validate_params};
+use crate::sock::{
+    ComplexSock, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll, SocketFactory, SocketParams,
+    check_buffer_size, make_simple_sock,
+};
+use crate::sockets::testgen::{SimpleTestGen, TestGenConfig, TestGenTypes};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::io::{self, ErrorKind};
+
+/// Configuration for test-check socket.
+#[derive(Deserialize, JsonSchema)]
+pub struct TestCheckConfig {
+    /// Expected test pattern, the same as of test-gen
+    pat: TestGenTypes,
+    /// Number of pattern iterations to check, the binding is finished after them (optional)
+    iter_num: Option<u64>,
+    /// Number of mismatched bytes, which fails the binding (optional)
+    max_errors: Option<u64>,
+}
+
+pub struct TestCheckState {
+    /// Generator of the expected data
+    expected: SimpleTestGen,
+    /// Buffer of the expected data, reused by every write
+    scratch: Vec<u8>,
+    /// Number of the checked bytes
+    checked: u64,
+    /// Number of the mismatched bytes and offset of the first one
+    errors: u64,
+    first_error: Option<u64>,
+}
+
+impl TestCheckState {
+    /// Fills the scratch buffer with `len` expected bytes.
+    fn expect(&mut self, len: usize) -> io::Result<&[u8]> {
+        self.scratch.resize(len, 0);
+        let mut filled = 0;
+        // Generator returns at most the rest of the pattern iteration
+        while filled < len {
+            filled += self.expected.read(&mut self.scratch[filled..], len - filled)?;
+        }
+        Ok(&self.scratch)
+    }
+    fn report(&self) -> String {
+        let first = self.first_error.map_or(String::new(), |at| format!(", first at offset {at}"));
+        format!("{} of {} bytes mismatched{first}", self.errors, self.checked)
+    }
+}
+
+make_simple_sock!(SimpleTestCheck {
+    max_errors: Option<u64>,
+    // Number of bytes of all checked iterations
+    total: Option<u64>,
+    state: RefCell<TestCheckState>,
+}, "test-check");
+
+impl SimpleSock for SimpleTestCheck {
+    fn read(&self, _: &mut [u8], _: usize) -> io::Result<usize> {
+        Ok(0)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> io::Result<usize> {
+        check_buffer_size(sz, data.len())?;
+        let mut state = self.state.borrow_mut();
+        let offset = state.checked;
+        // Data after the last iteration is unexpected
+        let len = self.total.map_or(sz, |total| sz.min((total - offset) as usize));
+        let expected = state.expect(len)?;
+        let mut mismatched = data[..len].iter().zip(expected).map(|(a, b)| a != b);
+        let first = mismatched.position(|m| m).map(|i| offset + i as u64);
+        let errors = mismatched.filter(|m| *m).count() + usize::from(first.is_some()) + sz - len;
+        if let Some(at) = first.or((sz > len).then_some(offset + len as u64)) {
+            tracing::warn!("{errors} mismatched bytes from offset {at}");
+            state.first_error.get_or_insert(at);
+        }
+        state.errors += errors as u64;
+        state.checked += sz as u64;
+
+        if self.max_errors.is_some_and(|max| state.errors >= max) {
+            return Err(io::Error::new(ErrorKind::InvalidData, state.report()));
+        }
+        if self.total.is_some_and(|total| state.checked >= total) {
+            if state.errors > 0 {
+                return Err(io::Error::new(ErrorKind::InvalidData, state.report()));
+            }
+            tracing::info!("Test data is verified: {}", state.report());
+            return Err(error::Error::Eof.into());
+        }
+        Ok(sz)
+    }
+}
+
+impl SockBlockCtl for SimpleTestCheck {}
+
+impl SockPoll for SimpleTestCheck {}
+
+struct TestCheckDoc;
+impl SockDocViewer for TestCheckDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(TestCheckConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let inc_cfg = "{ \"pat\": { \"type\": \"inc\", \"data\": \"0xf0\", \"size\": 100 }, \"max_errors\": 1 }";
+        let hex_str_cfg = "{ \"pat\": { \"type\": \"hex_str\", \"data\": \"1122334455aaddff\" }, \"iter_num\": 10 }";
+        format!(
+            "{}: {}\n{}: {}",
+            "Incremental traffic check, the first mismatch fails", inc_cfg,
+            "Hex string traffic check (only 10 iterations)", hex_str_cfg
+        )
+    }
+}
+
+/// Test-check socket factory implementing the SocketFactory trait.
+#[derive(Default)]
+pub struct TestCheckFactory;
+
+impl TestCheckFactory {
+    /// Creates a new test-check socket factory.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl SocketFactory for TestCheckFactory {
+    fn create_sock(&self, params: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
+        let config: TestCheckConfig = parse_params("test-check", &params)?;
+        let TestCheckConfig { pat, iter_num, max_errors } = config;
+        // Expected data is generated without delay
+        let expected = SimpleTestGen::from_config(TestGenConfig::new(pat, 0, iter_num))?;
+        let total = iter_num.map(|iter_num| iter_num * expected.pattern_size() as u64);
+        Ok(Box::new(SimpleTestCheck::new(
+            max_errors,
+            total,
+            RefCell::new(TestCheckState {
+                expected,
+                scratch: Vec::new(),
+                checked: 0,
+                errors: 0,
+                first_error: None,
+            }),
+        )))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
+        Box::new(TestCheckDoc)
+    }
+    fn validate_params(&self, params: &SocketParams) -> io::Result<()> {
+        Ok(validate_params::<TestCheckConfig>("test-check", params)?)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use crate::sockets::testgen::TestGenFactory;
+
+    #[test]
+    fn check_generated_data() {
+        let pat = r#"{ "type": "seq", "size": 10 }"#;
+        let gen_params = format!(r#"{{ "pat": {pat}, "cycle": 0, "iter_num": 3 }}"#);
+        let generator = TestGenFactory::new().create_sock(gen_params.parse().unwrap()).unwrap();
+        let check_params = format!(r#"{{ "pat": {pat}, "iter_num": 3 }}"#);
+        let check = TestCheckFactory::new().create_sock(check_params.parse().unwrap()).unwrap();
+        let mut buf = [0u8; 7];
+        let mut checked = 0;
+        // All 30 bytes are checked without errors
+        let err = loop {
+            let read = generator.read(&mut buf, 7).unwrap();
+            match check.write(&buf, read) {
+                Ok(written) => checked += written,
+                Err(e) => break e,
+            }
+        };
+        assert!(error::is_eof(&err));
+        assert_eq!(checked, 27);
+    }
+
+    #[test]
+    fn count_mismatches() {
+        let params = r#"{ "pat": { "type": "hex_str", "data": "00112233" }, "max_errors": 3 }"#;
+        let check = TestCheckFactory::new().create_sock(params.parse().unwrap()).unwrap();
+        assert_eq!(check.write(&[0x00, 0x11, 0xff, 0x33, 0x00], 5).unwrap(), 5);
+        let err = check.write(&[0x11, 0x00, 0x00, 0x00], 4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "3 of 9 bytes mismatched, first at offset 2");
+
+        // Data over the iterations is mismatched
+        let params = r#"{ "pat": { "type": "text_str", "data": "ab" }, "iter_num": 1 }"#;
+        let check = TestCheckFactory::new().create_sock(params.parse().unwrap()).unwrap();
+        let err = check.write(b"abc", 3).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 3 bytes mismatched, first at offset 2");
+    }
+}
@@ -1,16 +1,27 @@
-use crate::sock::{ComplexSock, SimpleSock, SockBlockCtl, SocketFactory, make_simple_sock, SockDocViewer};
+use crate::sock::{ComplexSock, SimpleSock, SockBlockCtl, SockPoll, SocketFactory, SocketParams, make_simple_sock, SockDocViewer};
+use crate::sock::check_buffer_size;
+use crate::sockets::csv_pattern::{CsvColumn, CsvEncoding, CsvState, CsvStrategy, load_records};
+use crate::sockets::waveform_pattern::{SampleFormat, WaveShape, WaveformState, WaveformStrategy};
+use crate::sock::decorators::{ChecksumAlgo, JitterDistribution, LatencyHistogram};
+use crate::error;
+use crate::sock::params::{parse_params, validate_params};
 use hex;
-use log::debug;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use tracing::debug;
 use serde::Deserialize;
 use serde_hex::{SerHex, StrictPfx};
 use std::cell::RefCell;
-use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
-use std::process;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::{any::Any, thread, time::Duration};
 
-#[derive(Deserialize, Debug, schemars::JsonSchema)]
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
 #[serde(tag = "type")]
 pub enum TestGenTypes {
     /// Constant data production
@@ -66,19 +77,379 @@ pub enum TestGenTypes {
     /// Data from file pattern
     #[serde(rename = "file")]
     File {
-        /// Path to file with test pattern
-        path: PathBuf
+        /// Path to file with test pattern (any binary content)
+        path: PathBuf,
+        /// Offset of the pattern in the file
+        #[serde(default)]
+        offset: u64,
+        /// Length of the pattern (up to the end of the file, if it is not set)
+        length: Option<u64>,
+        /// Produce the pattern in a loop, `false` produces it once
+        #[serde(default = "default_repeat")]
+        repeat: bool,
+    },
+    /// Pseudo-random pattern, every iteration continues the sequence
+    #[serde(rename = "random")]
+    Random {
+        /// Seed of the generator, the same seed gives the same data
+        /// (random, if it is not set)
+        seed: Option<u64>,
+        /// Length of one iteration pattern
+        size: usize,
+        /// Lowest byte value in hex
+        #[serde(with = "SerHex::<StrictPfx>", default)]
+        #[schemars(with = "String")]
+        min: u8,
+        /// Highest byte value in hex
+        #[serde(with = "SerHex::<StrictPfx>", default = "default_random_max")]
+        #[schemars(with = "String")]
+        max: u8,
+    },
+    /// Records of the CSV file (e.g. recorded telemetry), one record is
+    /// produced every iteration
+    #[serde(rename = "csv")]
+    Csv {
+        /// Path to the CSV file
+        path: PathBuf,
+        /// Delimiter of the fields, quoting isn't supported
+        #[serde(default = "default_delimiter")]
+        delimiter: char,
+        /// The first line is the header with the column names
+        #[serde(default)]
+        header: bool,
+        /// Columns of the record in order, indices from 0 or header names
+        /// (all columns, if it is not set)
+        columns: Option<Vec<CsvColumn>>,
+        /// Encoding of the record (text line with `,` separator by default)
+        #[serde(default)]
+        encoding: CsvEncoding,
+        /// Produce the records in a loop, `false` produces them once
+        #[serde(default = "default_repeat")]
+        repeat: bool,
+    },
+    /// Sampled waveform, the phase continues over the iterations
+    #[serde(rename = "waveform")]
+    Waveform {
+        /// Shape of the wave
+        shape: WaveShape,
+        /// Frequency of the wave in Hz
+        frequency: f64,
+        /// Amplitude as the part of the full scale (0..=1)
+        #[serde(default = "default_amplitude")]
+        amplitude: f64,
+        /// Samples per second
+        sample_rate: u32,
+        /// Format of the sample (i16 by default)
+        #[serde(default)]
+        format: SampleFormat,
+        /// Byte order of 16-bit samples (big-endian by default)
+        #[serde(default)]
+        little_endian: bool,
+        /// Number of samples of one iteration
+        samples: usize,
+    },
+    /// Frame assembled from the template parts, the counter, timestamp and
+    /// CRC fields are filled every iteration
+    #[serde(rename = "template")]
+    Template {
+        /// Parts of the frame in order
+        parts: Vec<TemplatePart>,
+    },
+    /// Sequence of the sub-patterns, the whole playlist is one iteration
+    #[serde(rename = "playlist")]
+    Playlist {
+        /// Entries produced one by one
+        entries: Vec<PlaylistEntry>,
+    },
+    /// Pattern of every iteration is the result of the Rhai script (blob,
+    /// string or array of bytes), `iter` (iteration number) and `time` (unix
+    /// time in seconds) are the script variables. Requires polysock built
+    /// with the `rhai` feature
+    #[serde(rename = "script")]
+    Script {
+        /// Script source
+        code: Option<String>,
+        /// Path to the script file, if `code` is not set
+        path: Option<PathBuf>,
+    },
+}
+
+/// Sub-pattern of the playlist.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct PlaylistEntry {
+    /// Test pattern of the entry
+    pat: TestGenTypes,
+    /// Number of the sub-pattern iterations
+    #[serde(default = "default_entry_repeat")]
+    repeat: u64,
+    /// Pause before the next entry in microseconds
+    #[serde(default)]
+    gap: u64,
+}
+
+impl PlaylistEntry {
+    /// Generator of all iterations of the entry.
+    fn generator(&self) -> std::io::Result<SimpleTestGen> {
+        SimpleTestGen::from_config(TestGenConfig::new(self.pat.clone(), 0, Some(self.repeat)))
+    }
+}
+
+/// Field of the template frame.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TemplatePart {
+    /// Constant bytes
+    Hex {
+        /// Hex string data (for example "7e01")
+        #[serde(with = "hex::serde")]
+        #[schemars(with = "String")]
+        data: Vec<u8>,
     },
+    /// Constant text
+    Text {
+        /// String in text format
+        data: String,
+    },
+    /// u32 counter incremented every iteration (wraps around)
+    Counter {
+        /// Value of the first iteration
+        #[serde(default)]
+        start: u32,
+        /// Byte order (big-endian by default)
+        #[serde(default)]
+        little_endian: bool,
+    },
+    /// Unix time of the iteration, u32 seconds or u64 milliseconds
+    Timestamp {
+        /// Milliseconds instead of seconds
+        #[serde(default)]
+        millis: bool,
+        /// Byte order (big-endian by default)
+        #[serde(default)]
+        little_endian: bool,
+    },
+    /// CRC of the preceding bytes of the frame
+    Crc {
+        /// CRC algorithm, the same as of the checksum decorator
+        algo: ChecksumAlgo,
+        /// Offset of the first covered byte, e.g. to skip the preamble
+        #[serde(default)]
+        from: usize,
+        /// Byte order (big-endian by default)
+        #[serde(default)]
+        little_endian: bool,
+    },
+}
+
+impl TemplatePart {
+    fn len(&self) -> usize {
+        match self {
+            Self::Hex { data } => data.len(),
+            Self::Text { data } => data.len(),
+            Self::Counter { .. } => 4,
+            Self::Timestamp { millis, .. } => if *millis { 8 } else { 4 },
+            Self::Crc { algo, .. } => algo.len(),
+        }
+    }
+}
+
+/// Frame of the template pattern and the number of the next iteration.
+struct TemplateState {
+    parts: Vec<TemplatePart>,
+    frame: Vec<u8>,
+    iter: u32,
+}
+
+impl TemplateState {
+    fn new(parts: &[TemplatePart]) -> std::io::Result<Self> {
+        let mut offset = 0;
+        for part in parts {
+            if let TemplatePart::Crc { from, .. } = part
+                && *from > offset
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Template CRC at offset {offset} can't cover bytes from {from}"),
+                ));
+            }
+            offset += part.len();
+        }
+        Ok(Self { parts: parts.to_vec(), frame: Vec::with_capacity(offset), iter: 0 })
+    }
+    /// Length of the frame, it's the same every iteration.
+    fn frame_len(&self) -> usize {
+        self.parts.iter().map(TemplatePart::len).sum()
+    }
+    /// Fills the fields of the next iteration.
+    fn next_frame(&mut self) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.frame.clear();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Hex { data } => self.frame.extend(data),
+                TemplatePart::Text { data } => self.frame.extend(data.as_bytes()),
+                TemplatePart::Counter { start, little_endian } => {
+                    let value = start.wrapping_add(self.iter);
+                    self.frame.extend(ordered_bytes(value.to_be_bytes(), *little_endian));
+                }
+                TemplatePart::Timestamp { millis: false, little_endian } => {
+                    let secs = time.as_secs() as u32;
+                    self.frame.extend(ordered_bytes(secs.to_be_bytes(), *little_endian));
+                }
+                TemplatePart::Timestamp { millis: true, little_endian } => {
+                    let millis = time.as_millis() as u64;
+                    self.frame.extend(ordered_bytes(millis.to_be_bytes(), *little_endian));
+                }
+                TemplatePart::Crc { algo, from, little_endian } => {
+                    let crc = algo.checksum(&self.frame[*from..], *little_endian);
+                    self.frame.extend(crc);
+                }
+            }
+        }
+        self.iter = self.iter.wrapping_add(1);
+    }
+}
+
+/// Converts the value to bytes of the byte order.
+fn ordered_bytes<const N: usize>(be: [u8; N], little_endian: bool) -> [u8; N] {
+    let mut bytes = be;
+    if little_endian {
+        bytes.reverse();
+    }
+    bytes
+}
+
+fn default_amplitude() -> f64 {
+    1.0
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_entry_repeat() -> u64 {
+    1
+}
+
+fn default_random_max() -> u8 {
+    0xff
+}
+
+fn default_repeat() -> bool {
+    true
 }
 
 #[derive(Deserialize, Debug, schemars::JsonSchema)]
 pub struct TestGenConfig {
     /// Test pattern type selection
     pat: TestGenTypes,
-    /// Data prosuction cycle in microseconds
+    /// Data prosuction cycle in microseconds (the cycle of bursts of the
+    /// burst rate profile)
+    #[serde(default)]
     cycle: u64,
     /// Iteration number constrain (optional)
     iter_num: Option<u64>,
+    /// Rate profile changing the production cycle over time (optional)
+    rate: Option<RateProfile>,
+    /// Random deviation of the production cycle (optional)
+    cycle_jitter: Option<CycleJitter>,
+    /// Written data is matched against the generated one, when the far end
+    /// echoes the traffic back. Round-trip times and bit errors are reported
+    /// when the socket is closed
+    #[serde(default)]
+    echo: bool,
+}
+
+/// Jitter of the production cycle, e.g. `{ "jitter": 2000, "distribution":
+/// "normal", "seed": 1 }`. The pause is cut at zero.
+#[derive(Deserialize, Debug, Clone, Copy, schemars::JsonSchema)]
+pub struct CycleJitter {
+    /// Max (uniform) or standard (normal) deviation of the cycle in microseconds
+    jitter: u64,
+    #[serde(default)]
+    distribution: JitterDistribution,
+    /// Seed of the jitter generator, the same seed gives the same cycles
+    /// (random, if it is not set)
+    seed: Option<u64>,
+}
+
+impl CycleJitter {
+    /// Returns the pause with the random deviation.
+    fn apply(&self, pause: Duration, rng: &mut StdRng) -> Duration {
+        let deviation = self.distribution.sample(rng, self.jitter as f64);
+        Duration::from_secs_f64((pause.as_secs_f64() + deviation / 1e6).max(0.0))
+    }
+}
+
+impl TestGenConfig {
+    pub(crate) fn new(pat: TestGenTypes, cycle: u64, iter_num: Option<u64>) -> Self {
+        Self { pat, cycle, iter_num, rate: None, cycle_jitter: None, echo: false }
+    }
+    /// Returns the pause before the iteration, which starts `elapsed` after
+    /// the first one.
+    fn pause(&self, elapsed: Duration) -> Duration {
+        let cycle = Duration::from_micros(self.cycle);
+        match self.rate {
+            None => cycle,
+            Some(RateProfile::Ramp { start, end, duration }) => {
+                let progress = if duration == 0 {
+                    1.0
+                } else {
+                    (elapsed.as_secs_f64() * 1e3 / duration as f64).min(1.0)
+                };
+                let cycle = start as f64 + (end as f64 - start as f64) * progress;
+                Duration::from_secs_f64(cycle / 1e6)
+            }
+            Some(RateProfile::Burst { burst, pause }) => {
+                let period = Duration::from_millis(burst + pause);
+                let at = Duration::from_nanos((elapsed.as_nanos() % period.as_nanos()) as u64);
+                if at < Duration::from_millis(burst) {
+                    cycle
+                } else {
+                    // Production is resumed with the next burst
+                    period - at + cycle
+                }
+            }
+        }
+    }
+    /// The shortest production cycle in microseconds.
+    fn min_cycle(&self) -> u64 {
+        match self.rate {
+            Some(RateProfile::Ramp { start, end, .. }) => start.min(end),
+            _ => self.cycle,
+        }
+    }
+    fn check(&self) -> std::io::Result<()> {
+        if let Some(RateProfile::Burst { burst: 0, .. }) = self.rate {
+            return Err(Error::new(ErrorKind::InvalidInput, "Rate burst must not be 0"));
+        }
+        Ok(())
+    }
+}
+
+/// Production rate changing over time, the time is counted from the first
+/// iteration.
+#[derive(Deserialize, Debug, Clone, Copy, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RateProfile {
+    /// Cycle changes linearly from `start` to `end` during `duration`, then
+    /// stays at `end`
+    Ramp {
+        /// Initial production cycle in microseconds
+        start: u64,
+        /// Final production cycle in microseconds
+        end: u64,
+        /// Ramp duration in milliseconds
+        duration: u64,
+    },
+    /// Patterns are produced every `cycle` during `burst`, nothing is produced
+    /// during the following `pause`
+    Burst {
+        /// Burst duration in milliseconds
+        burst: u64,
+        /// Pause duration in milliseconds
+        pause: u64,
+    },
 }
 
 #[derive(Default)]
@@ -87,18 +458,94 @@ pub struct TestGenPrivate {
     pattern_size: usize,
     max_iter: Option<u64>,
     curr_iter: u64,
+    /// Time of the first iteration
+    started: Option<Instant>,
+    /// Generator of the cycle jitter
+    jitter_rng: Option<StdRng>,
+    /// Generated data waiting for the echo
+    echo: Option<EchoTracker>,
     pattern_priv: Option<Box<dyn Any + Send>>,
 }
 
-fn get_curr_size(pattern_size: usize, req_size: usize, pos: usize) -> usize {
+/// Max number of the generated bytes waiting for the echo, older ones are lost.
+const MAX_ECHO_PENDING: usize = 1 << 20;
+
+/// Matches the echoed data with the generated chunks. The echo is a byte
+/// stream, round trip of the chunk is measured when its last byte is
+/// echoed.
+#[derive(Default)]
+struct EchoTracker {
+    /// Generated chunks with their times, the echoed length of the first one
+    pending: VecDeque<(Vec<u8>, Instant)>,
+    offset: usize,
+    pending_len: usize,
+    histogram: LatencyHistogram,
+    bits: u64,
+    bit_errors: u64,
+    lost: u64,
+    unexpected: u64,
+}
+
+impl EchoTracker {
+    fn sent(&mut self, chunk: &[u8], now: Instant) {
+        self.pending.push_back((chunk.to_vec(), now));
+        self.pending_len += chunk.len();
+        while self.pending_len > MAX_ECHO_PENDING
+            && let Some((chunk, _)) = self.pending.pop_front()
+        {
+            self.lost += (chunk.len() - self.offset) as u64;
+            self.pending_len -= chunk.len();
+            self.offset = 0;
+        }
+    }
+    fn echoed(&mut self, mut data: &[u8], now: Instant) {
+        while let Some((chunk, sent)) = self.pending.front()
+            && !data.is_empty()
+        {
+            let len = (chunk.len() - self.offset).min(data.len());
+            let expected = &chunk[self.offset..self.offset + len];
+            let errors: u32 = expected.iter().zip(data).map(|(a, b)| (a ^ b).count_ones()).sum();
+            self.bit_errors += errors as u64;
+            self.bits += len as u64 * 8;
+            self.offset += len;
+            data = &data[len..];
+            if self.offset == chunk.len() {
+                self.histogram.add(now - *sent);
+                self.pending_len -= chunk.len();
+                self.pending.pop_front();
+                self.offset = 0;
+            }
+        }
+        self.unexpected += data.len() as u64;
+    }
+}
+
+impl fmt::Display for EchoTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ber = if self.bits > 0 { self.bit_errors as f64 / self.bits as f64 } else { 0.0 };
+        write!(
+            f,
+            "{} of {} bits errored (BER {ber:.2e}), {} bytes lost, {} bytes unexpected, {}",
+            self.bit_errors,
+            self.bits,
+            self.lost + (self.pending_len - self.offset) as u64,
+            self.unexpected,
+            self.histogram
+        )
+    }
+}
+
+fn get_curr_size(pattern_size: usize, req_size: usize, pos: usize) -> std::io::Result<usize> {
     // return data size according to requested
     // transaction size, data pattern size and
     // current position
-    if pattern_size - pos < req_size {
-        pattern_size - pos
-    } else {
-        req_size
-    }
+    let left = pattern_size.checked_sub(pos).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Pattern position {pos} is past the end of {pattern_size} bytes pattern"),
+        )
+    })?;
+    Ok(left.min(req_size))
 }
 
 fn update_pos(p: &mut TestGenPrivate, req: usize, ret: usize) {
@@ -279,6 +726,26 @@ impl TestPatternStrategy for HexStringStrategy {
     }
 }
 
+/// Reads `length` bytes of the file from `offset` (up to the end of the
+/// file, if `length` is not set).
+fn read_pattern_file(path: &Path, offset: u64, length: Option<u64>) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let end = length
+        .map_or(Some(size), |length| offset.checked_add(length))
+        .filter(|end| offset <= size && *end <= size)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Pattern range is out of {} of {size} bytes", path.display()),
+            )
+        })?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    file.take(end - offset).read_to_end(&mut data)?;
+    Ok(data)
+}
+
 struct FileStrategy;
 impl TestPatternStrategy for FileStrategy {
     fn read(
@@ -289,7 +756,7 @@ impl TestPatternStrategy for FileStrategy {
             real_size: usize,
             pos: usize,
         ) -> std::io::Result<usize> {
-        let ret = if let Some(data) = p.as_ref().unwrap().downcast_ref::<String>() {
+        let ret = if let Some(data) = p.as_ref().unwrap().downcast_ref::<Vec<u8>>() {
             unsafe {
                 ptr::copy_nonoverlapping(data.as_ptr().wrapping_add(pos), buf.as_mut_ptr(), real_size);
             }
@@ -301,100 +768,177 @@ impl TestPatternStrategy for FileStrategy {
     }
 }
 
-pub trait TestPatternStrategy {
+struct RandomStrategy;
+impl TestPatternStrategy for RandomStrategy {
     fn read(
         &self,
         cfg: &(dyn Any + Send),
         p: &mut Option<Box<dyn Any + Send>>,
         buf: &mut [u8],
         real_size: usize,
-        pos: usize,
-    ) -> std::io::Result<usize>;
-    fn reset_priv(&self, _p: &mut Option<Box<dyn Any + Send>>) {}
+        _: usize,
+    ) -> std::io::Result<usize> {
+        let ret = if let Some(TestGenTypes::Random { min, max, .. }) = cfg.downcast_ref()
+            && let Some(rng) = p.as_mut().unwrap().downcast_mut::<StdRng>()
+        {
+            for el in buf[..real_size].iter_mut() {
+                *el = rng.random_range(*min..=*max);
+            }
+            real_size
+        } else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        Ok(ret)
+    }
 }
 
-make_simple_sock!(SimpleTestGen {
-    config: TestGenConfig,
-    pat_cfg: Box<dyn Any + Send>,
-    p: RefCell<TestGenPrivate>,
-    reader: Box<dyn TestPatternStrategy + Send>,
-}, "test-gen");
+/// Entry of the playlist being produced.
+struct PlaylistState {
+    idx: usize,
+    current: Option<SimpleTestGen>,
+}
 
-impl SimpleSock for SimpleTestGen {
-    fn read(&self, data: &mut [u8], sz: usize) -> std::io::Result<usize> {
-        let mut p = self.p.borrow_mut();
-        // Sleep only if pattern starts
-        if p.pos == 0 {
-            thread::sleep(Duration::from_micros(self.config.cycle));
-        }
-        // Get real size, according to pattern size, current position of
-        // pattern producing & requested size
-        let real_size = get_curr_size(p.pattern_size, sz, p.pos);
-        let pos = p.pos;
-        let ret = self.reader
-            .read(self.pat_cfg.as_ref(), &mut p.pattern_priv, data, real_size, pos)?;
-        // Update position of pattern producing
-        update_pos(&mut p, sz, real_size);
-        // End of pattern block
-        if p.pos == 0 {
-            // Check if iteration constrains were configured
-            if let Some(max_iter) = p.max_iter {
-                p.curr_iter += 1;
-                if p.curr_iter > max_iter {
-                    println!("Max iteration limit is reached ({max_iter} iterations)");
-                    process::exit(0);
+struct PlaylistStrategy;
+impl TestPatternStrategy for PlaylistStrategy {
+    fn read(
+        &self,
+        cfg: &(dyn Any + Send),
+        p: &mut Option<Box<dyn Any + Send>>,
+        buf: &mut [u8],
+        real_size: usize,
+        _: usize,
+    ) -> std::io::Result<usize> {
+        let ret = if let Some(TestGenTypes::Playlist { entries }) = cfg.downcast_ref()
+            && let Some(state) = p.as_mut().unwrap().downcast_mut::<PlaylistState>()
+        {
+            let mut filled = 0;
+            while filled < real_size {
+                let Some(entry) = entries.get(state.idx) else {
+                    return Err(Error::from(ErrorKind::InvalidData));
+                };
+                let current = match &mut state.current {
+                    Some(current) => current,
+                    None => state.current.insert(entry.generator()?),
+                };
+                match current.read(&mut buf[filled..], real_size - filled) {
+                    Ok(read) => filled += read,
+                    // All iterations of the entry are produced
+                    Err(e) if error::is_eof(&e) => {
+                        thread::sleep(Duration::from_micros(entry.gap));
+                        state.idx += 1;
+                        state.current = None;
+                    }
+                    Err(e) => return Err(e),
                 }
             }
-            // Reset private strategy state, if implemented
-            self.reader.reset_priv(&mut p.pattern_priv);
-        }
+            real_size
+        } else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
         Ok(ret)
     }
-    fn write(&self, _: &[u8], _: usize) -> std::io::Result<()> {
-        debug!("Socket test-gen unsupports write operation! Skipping...");
-        Ok(())
+    fn reset_priv(&self, p: &mut Option<Box<dyn Any + Send>>) {
+        if let Some(state) = p.as_mut().unwrap().downcast_mut::<PlaylistState>() {
+            state.idx = 0;
+            state.current = None;
+        }
     }
 }
 
-impl SockBlockCtl for SimpleTestGen {}
-
-struct TestGenDoc;
-impl SockDocViewer for TestGenDoc {
-    fn get_full_scheme(&self) -> String {
-        let schema = schemars::schema_for!(TestGenConfig);
-        serde_json::to_string_pretty(&schema).unwrap()
+struct TemplateStrategy;
+impl TestPatternStrategy for TemplateStrategy {
+    fn read(
+        &self,
+        _: &(dyn Any + Send),
+        p: &mut Option<Box<dyn Any + Send>>,
+        buf: &mut [u8],
+        real_size: usize,
+        pos: usize,
+    ) -> std::io::Result<usize> {
+        let Some(state) = p.as_ref().unwrap().downcast_ref::<TemplateState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        buf[..real_size].copy_from_slice(&state.frame[pos..pos + real_size]);
+        Ok(real_size)
     }
-    fn get_examples(&self) -> String {
-        let inc_cfg = "{ \"pat\": { \"type\": \"inc\", \"data\": \"0xf0\", \"size\": 100 }, \"cycle\": 10000 }";
-        let hex_str_cfg = "{ \"pat\": { \"type\": \"hex_str\", \"data\": \"1122334455aaddff\" }, \"cycle\": 10000, \"iter_num\": 10 }";
-        format!(
-            "{}: {}\n{}: {}",
-            "Incremantal traffic generation", inc_cfg,
-            "Hex string traffic generation (only 10 iterations)", hex_str_cfg
-        )
+    fn start_iteration(&self, p: &mut Option<Box<dyn Any + Send>>) -> std::io::Result<Option<usize>> {
+        let Some(state) = p.as_mut().unwrap().downcast_mut::<TemplateState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        state.next_frame();
+        Ok(None)
     }
 }
 
-pub struct TestGenFactory;
+/// Strategy of the script pattern, its state and size of the first iteration.
+type ScriptPattern = (Box<dyn TestPatternStrategy + Send>, Box<dyn Any + Send>, usize);
 
-impl TestGenFactory {
-    pub fn new() -> Self {
-        Self
-    }
+#[cfg(feature = "rhai")]
+fn script_pattern(code: Option<&str>, path: Option<&Path>) -> std::io::Result<ScriptPattern> {
+    let code = match (code, path) {
+        (Some(code), _) => code.to_string(),
+        (None, Some(path)) => std::fs::read_to_string(path)?,
+        (None, None) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Script pattern requires code or path",
+            ));
+        }
+    };
+    let state = super::script_pattern::ScriptState::new(&code)?;
+    let size = state.frame_len();
+    Ok((Box::new(super::script_pattern::ScriptStrategy), Box::new(state), size))
 }
 
-impl SocketFactory for TestGenFactory {
-    fn create_sock(
+#[cfg(not(feature = "rhai"))]
+fn script_pattern(_: Option<&str>, _: Option<&Path>) -> std::io::Result<ScriptPattern> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "Script pattern requires polysock built with the `rhai` feature",
+    ))
+}
+
+pub trait TestPatternStrategy {
+    fn read(
         &self,
-        params: crate::sock::SocketParams,
-    ) -> std::io::Result<Box<dyn ComplexSock>> {
-        // Deserialize to TestGenConfig
-        let testgen_cfg: TestGenConfig = serde_json::from_str(params.as_str()).map_err(|e| {
-            eprintln!("{e}");
-            Error::new(ErrorKind::InvalidInput, "Invalid test-gen configuration")
-        })?;
+        cfg: &(dyn Any + Send),
+        p: &mut Option<Box<dyn Any + Send>>,
+        buf: &mut [u8],
+        real_size: usize,
+        pos: usize,
+    ) -> std::io::Result<usize>;
+    fn reset_priv(&self, _p: &mut Option<Box<dyn Any + Send>>) {}
+    /// Prepares the next iteration, returns its size, if the size of the
+    /// pattern varies.
+    fn start_iteration(&self, _p: &mut Option<Box<dyn Any + Send>>) -> std::io::Result<Option<usize>> {
+        Ok(None)
+    }
+}
+
+make_simple_sock!(SimpleTestGen {
+    config: TestGenConfig,
+    pat_cfg: Box<dyn Any + Send>,
+    p: RefCell<TestGenPrivate>,
+    reader: Box<dyn TestPatternStrategy + Send>,
+}, "test-gen"; info: {
+    /// One pattern is produced every cycle
+    fn source_rate(&self) -> Option<f64> {
+        let pattern_size = self.p.borrow().pattern_size as f64;
+        let cycle = self.config.min_cycle();
+        (cycle > 0).then(|| pattern_size * 1e6 / cycle as f64)
+    }
+});
 
+impl SimpleTestGen {
+    /// Creates the generator of the configured pattern.
+    pub(crate) fn from_config(testgen_cfg: TestGenConfig) -> std::io::Result<Self> {
+        testgen_cfg.check()?;
         let mut p: TestGenPrivate = TestGenPrivate { max_iter: testgen_cfg.iter_num, ..Default::default() };
+        p.echo = testgen_cfg.echo.then(EchoTracker::default);
+        p.jitter_rng = testgen_cfg.cycle_jitter.map(|jitter| match jitter.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => rand::make_rng(),
+        });
         let (cb, pat_cfg, p) = match &testgen_cfg.pat {
             TestGenTypes::Static { data, size } => {
                 p.pattern_size = *size;
@@ -455,28 +999,274 @@ impl SocketFactory for TestGenFactory {
                     RefCell::new(p),
                 )
             }
-            TestGenTypes::File { path } => {
-                let data = std::fs::read_to_string(path)?;
+            TestGenTypes::File { path, offset, length, repeat } => {
+                let data = read_pattern_file(path, *offset, *length)?;
                 p.pattern_size = data.len();
                 p.pattern_priv = Some(Box::new(data));
+                if !repeat {
+                    p.max_iter = Some(p.max_iter.map_or(1, |max_iter| max_iter.min(1)));
+                }
                 (
                     Box::new(FileStrategy) as Box<dyn TestPatternStrategy + Send>,
-                    Box::new(TestGenTypes::File { path: path.clone() }),
+                    Box::new(TestGenTypes::File {
+                        path: path.clone(),
+                        offset: *offset,
+                        length: *length,
+                        repeat: *repeat,
+                    }),
+                    RefCell::new(p),
+                )
+            }
+            TestGenTypes::Random { seed, size, min, max } => {
+                if min > max {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Random pattern min must not be greater than max",
+                    ));
+                }
+                let rng: StdRng = match seed {
+                    Some(seed) => StdRng::seed_from_u64(*seed),
+                    None => rand::make_rng(),
+                };
+                p.pattern_priv = Some(Box::new(rng));
+                p.pattern_size = *size;
+                (
+                    Box::new(RandomStrategy) as Box<dyn TestPatternStrategy + Send>,
+                    Box::new(TestGenTypes::Random {
+                        seed: *seed,
+                        size: *size,
+                        min: *min,
+                        max: *max,
+                    }),
+                    RefCell::new(p),
+                )
+            }
+            TestGenTypes::Script { code, path } => {
+                let (strategy, state, size) = script_pattern(code.as_deref(), path.as_deref())?;
+                p.pattern_priv = Some(state);
+                p.pattern_size = size;
+                (
+                    strategy,
+                    Box::new(TestGenTypes::Script { code: code.clone(), path: path.clone() }),
+                    RefCell::new(p),
+                )
+            }
+            TestGenTypes::Csv { path, delimiter, header, columns, encoding, repeat } => {
+                let records =
+                    load_records(path, *delimiter, *header, columns.as_deref(), encoding)?;
+                p.pattern_size = records[0].len();
+                if !repeat {
+                    let count = records.len() as u64;
+                    p.max_iter = Some(p.max_iter.map_or(count, |max_iter| max_iter.min(count)));
+                }
+                p.pattern_priv = Some(Box::new(CsvState::new(records)));
+                (
+                    Box::new(CsvStrategy) as Box<dyn TestPatternStrategy + Send>,
+                    Box::new(TestGenTypes::Csv {
+                        path: path.clone(),
+                        delimiter: *delimiter,
+                        header: *header,
+                        columns: columns.clone(),
+                        encoding: encoding.clone(),
+                        repeat: *repeat,
+                    }),
+                    RefCell::new(p),
+                )
+            }
+            TestGenTypes::Waveform {
+                shape,
+                frequency,
+                amplitude,
+                sample_rate,
+                format,
+                little_endian,
+                samples,
+            } => {
+                let state = WaveformState::new(
+                    *shape,
+                    *frequency,
+                    *amplitude,
+                    *sample_rate,
+                    *format,
+                    *little_endian,
+                    *samples,
+                )?;
+                p.pattern_size = samples * format.len();
+                p.pattern_priv = Some(Box::new(state));
+                (
+                    Box::new(WaveformStrategy) as Box<dyn TestPatternStrategy + Send>,
+                    Box::new(TestGenTypes::Waveform {
+                        shape: *shape,
+                        frequency: *frequency,
+                        amplitude: *amplitude,
+                        sample_rate: *sample_rate,
+                        format: *format,
+                        little_endian: *little_endian,
+                        samples: *samples,
+                    }),
+                    RefCell::new(p),
+                )
+            }
+            TestGenTypes::Template { parts } => {
+                let state = TemplateState::new(parts)?;
+                p.pattern_size = state.frame_len();
+                p.pattern_priv = Some(Box::new(state));
+                (
+                    Box::new(TemplateStrategy) as Box<dyn TestPatternStrategy + Send>,
+                    Box::new(TestGenTypes::Template { parts: parts.clone() }),
+                    RefCell::new(p),
+                )
+            }
+            TestGenTypes::Playlist { entries } => {
+                let mut size = 0;
+                for entry in entries {
+                    size += entry.generator()?.pattern_size() * entry.repeat as usize;
+                }
+                p.pattern_size = size;
+                p.pattern_priv = Some(Box::new(PlaylistState { idx: 0, current: None }));
+                (
+                    Box::new(PlaylistStrategy) as Box<dyn TestPatternStrategy + Send>,
+                    Box::new(TestGenTypes::Playlist { entries: entries.clone() }),
                     RefCell::new(p),
                 )
             }
         };
 
-        Ok(Box::new(SimpleTestGen::new(testgen_cfg, pat_cfg, p, cb)))
+        // Empty pattern would produce nothing forever, script patterns
+        // may produce empty iterations
+        if p.borrow().pattern_size == 0 && !matches!(testgen_cfg.pat, TestGenTypes::Script { .. }) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Test pattern is empty"));
+        }
+
+        Ok(SimpleTestGen::new(testgen_cfg, pat_cfg, p, cb))
+    }
+    /// Length of one iteration of the pattern.
+    pub(crate) fn pattern_size(&self) -> usize {
+        self.p.borrow().pattern_size
+    }
+}
+
+impl SimpleSock for SimpleTestGen {
+    fn read(&self, data: &mut [u8], sz: usize) -> std::io::Result<usize> {
+        check_buffer_size(sz, data.len())?;
+        // Zero-sized read neither produces data nor moves the pattern
+        if sz == 0 {
+            return Ok(0);
+        }
+        let mut p = self.p.borrow_mut();
+        // The binding is finished, when all iterations are produced
+        if p.max_iter.is_some_and(|max_iter| p.curr_iter >= max_iter) {
+            return Err(error::Error::Eof.into());
+        }
+        // Sleep only if pattern starts
+        if p.pos == 0 {
+            let elapsed = p.started.get_or_insert_with(Instant::now).elapsed();
+            let mut pause = self.config.pause(elapsed);
+            if let Some(jitter) = self.config.cycle_jitter
+                && let Some(rng) = &mut p.jitter_rng
+            {
+                pause = jitter.apply(pause, rng);
+            }
+            thread::sleep(pause);
+            if let Some(size) = self.reader.start_iteration(&mut p.pattern_priv)? {
+                p.pattern_size = size;
+            }
+        }
+        // Get real size, according to pattern size, current position of
+        // pattern producing & requested size
+        let real_size = get_curr_size(p.pattern_size, sz, p.pos)?;
+        let pos = p.pos;
+        let ret = self.reader
+            .read(self.pat_cfg.as_ref(), &mut p.pattern_priv, data, real_size, pos)?;
+        if let Some(echo) = &mut p.echo {
+            echo.sent(&data[..ret], Instant::now());
+        }
+        // Update position of pattern producing
+        update_pos(&mut p, sz, real_size);
+        // End of pattern block
+        if p.pos == 0 {
+            // Check if iteration constrains were configured
+            if let Some(max_iter) = p.max_iter {
+                p.curr_iter += 1;
+                if p.curr_iter == max_iter {
+                    tracing::info!("Max iteration limit is reached ({max_iter} iterations)");
+                }
+            }
+            // Reset private strategy state, if implemented
+            self.reader.reset_priv(&mut p.pattern_priv);
+        }
+        Ok(ret)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> std::io::Result<usize> {
+        check_buffer_size(sz, data.len())?;
+        match &mut self.p.borrow_mut().echo {
+            Some(echo) => echo.echoed(&data[..sz], Instant::now()),
+            None => debug!("Socket test-gen unsupports write operation! Skipping..."),
+        }
+        Ok(sz)
+    }
+    fn close(&mut self) {
+        if let Some(echo) = &self.p.borrow().echo {
+            tracing::info!("Echo of test-gen: {echo}");
+        }
+    }
+}
+
+impl SockBlockCtl for SimpleTestGen {}
+
+impl SockPoll for SimpleTestGen {}
+
+struct TestGenDoc;
+impl SockDocViewer for TestGenDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(TestGenConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let inc_cfg = "{ \"pat\": { \"type\": \"inc\", \"data\": \"0xf0\", \"size\": 100 }, \"cycle\": 10000 }";
+        let hex_str_cfg = "{ \"pat\": { \"type\": \"hex_str\", \"data\": \"1122334455aaddff\" }, \"cycle\": 10000, \"iter_num\": 10 }";
+        let random_cfg = "{ \"pat\": { \"type\": \"random\", \"seed\": 42, \"size\": 1024, \"min\": \"0x20\", \"max\": \"0x7e\" }, \"cycle\": 1000 }";
+        let template_cfg = "{ \"pat\": { \"type\": \"template\", \"parts\": [ { \"type\": \"hex\", \"data\": \"7e\" }, { \"type\": \"counter\" }, { \"type\": \"timestamp\" }, { \"type\": \"crc\", \"algo\": \"crc32\" } ] }, \"cycle\": 10000 }";
+        format!(
+            "{}: {}\n{}: {}\n{}: {}\n{}: {}",
+            "Incremantal traffic generation", inc_cfg,
+            "Hex string traffic generation (only 10 iterations)", hex_str_cfg,
+            "Reproducible random printable characters", random_cfg,
+            "Sequence numbered frames with timestamp and CRC-32", template_cfg
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct TestGenFactory;
+
+impl TestGenFactory {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl SocketFactory for TestGenFactory {
+    fn create_sock(
+        &self,
+        params: crate::sock::SocketParams,
+    ) -> std::io::Result<Box<dyn ComplexSock>> {
+        // Deserialize to TestGenConfig
+        let testgen_cfg: TestGenConfig = parse_params("test-gen", &params)?;
+        Ok(Box::new(SimpleTestGen::from_config(testgen_cfg)?))
     }
     fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
         Box::new(TestGenDoc)
     }
+    fn validate_params(&self, params: &SocketParams) -> std::io::Result<()> {
+        Ok(validate_params::<TestGenConfig>("test-gen", params)?)
+    }
 }
 
 mod tests {
     #![allow(unused_imports)]
 
+    use super::*;
     use crate::{sock::SocketFactory, sockets::testgen::{TestGenConfig, TestGenFactory}};
 
     #[test]
@@ -490,4 +1280,234 @@ mod tests {
     fn test_doc_params() {
         println!("{}", TestGenFactory::new().create_doc_viewer().get_full_scheme());
     }
+    #[test]
+    fn zero_and_oversized_reads() {
+        let cfg = "{ \"pat\": { \"type\": \"hex_str\", \"data\": \"0011223344\" }, \"cycle\": 0 }";
+        let sock = TestGenFactory::new().create_sock(cfg.parse().unwrap()).unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(sock.read(&mut buf, 0).unwrap(), 0);
+        assert_eq!(sock.read(&mut buf, 8).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(sock.read(&mut buf, 4).unwrap(), 4);
+        assert_eq!(buf, [0x00, 0x11, 0x22, 0x33]);
+        let empty = "{ \"pat\": { \"type\": \"text_str\", \"data\": \"\" }, \"cycle\": 0 }";
+        assert!(TestGenFactory::new().create_sock(empty.parse().unwrap()).is_err());
+    }
+    #[test]
+    fn iteration_limit() {
+        let cfg = "{ \"pat\": { \"type\": \"text_str\", \"data\": \"ping\" }, \"cycle\": 0, \"iter_num\": 2 }";
+        let sock = TestGenFactory::new().create_sock(cfg.parse().unwrap()).unwrap();
+        let mut buf = [0u8; 8];
+        for _ in 0..2 {
+            assert_eq!(sock.read(&mut buf, 8).unwrap(), 4);
+            assert_eq!(&buf[..4], b"ping");
+        }
+        // The process isn't exited, the socket reports the end of data
+        let err = sock.read(&mut buf, 8).unwrap_err();
+        assert!(error::is_eof(&err));
+        assert!(error::is_eof(&sock.read(&mut buf, 8).unwrap_err()));
+    }
+    #[test]
+    fn binary_file_pattern() {
+        let path = std::env::temp_dir().join(format!("testgen-{}.bin", std::process::id()));
+        std::fs::write(&path, [0x00, 0xff, 0xc3, 0x28, 0x80, 0x01]).unwrap();
+        let create = |extra: &str| {
+            let cfg = format!(
+                "{{ \"pat\": {{ \"type\": \"file\", \"path\": {path:?}{extra} }}, \"cycle\": 0 }}"
+            );
+            TestGenFactory::new().create_sock(cfg.parse().unwrap())
+        };
+        let mut buf = [0u8; 8];
+        // Invalid UTF-8 is produced as is
+        let sock = create("").unwrap();
+        assert_eq!(sock.read(&mut buf, 8).unwrap(), 6);
+        assert_eq!(buf[..6], [0x00, 0xff, 0xc3, 0x28, 0x80, 0x01]);
+
+        let sock = create(", \"offset\": 1, \"length\": 3, \"repeat\": false").unwrap();
+        assert_eq!(sock.read(&mut buf, 8).unwrap(), 3);
+        assert_eq!(buf[..3], [0xff, 0xc3, 0x28]);
+        assert!(error::is_eof(&sock.read(&mut buf, 8).unwrap_err()));
+
+        assert!(create(", \"offset\": 4, \"length\": 3").is_err());
+        assert!(create(", \"offset\": 7").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+    #[test]
+    fn playlist_pattern() {
+        let cfg = r#"{ "pat": { "type": "playlist", "entries": [
+            { "pat": { "type": "hex_str", "data": "aa55" } },
+            { "pat": { "type": "inc", "data": "0x01", "size": 3 }, "repeat": 3, "gap": 20000 },
+            { "pat": { "type": "text_str", "data": "end" } } ] }, "cycle": 0, "iter_num": 2 }"#;
+        let sock = TestGenFactory::new().create_sock(cfg.parse().unwrap()).unwrap();
+        let mut buf = [0u8; 5];
+        let mut out: Vec<u8> = Vec::new();
+        let start = std::time::Instant::now();
+        loop {
+            match sock.read(&mut buf, 5) {
+                Ok(read) => out.extend(&buf[..read]),
+                Err(e) if error::is_eof(&e) => break,
+                Err(e) => panic!("{e}"),
+            }
+        }
+        let iteration = b"\xaa\x55\x01\x01\x01\x02\x02\x02\x03\x03\x03end";
+        assert_eq!(out, [&iteration[..], &iteration[..]].concat());
+        // Gap of the inc entry is waited in every iteration
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+    #[test]
+    fn rate_profiles() {
+        let pat = "\"pat\": { \"type\": \"text_str\", \"data\": \"ping\" }";
+        let config = |rate: &str| -> TestGenConfig {
+            serde_json::from_str(&format!("{{ {pat}, \"cycle\": 1000, \"rate\": {rate} }}")).unwrap()
+        };
+        let ms = Duration::from_millis;
+        let ramp = config("{ \"type\": \"ramp\", \"start\": 10000, \"end\": 0, \"duration\": 100 }");
+        assert_eq!(ramp.pause(ms(0)), ms(10));
+        assert_eq!(ramp.pause(ms(50)), ms(5));
+        assert_eq!(ramp.pause(ms(200)), ms(0));
+        let burst = config("{ \"type\": \"burst\", \"burst\": 10, \"pause\": 30 }");
+        assert_eq!(burst.pause(ms(5)), ms(1));
+        // The rest of the pause is waited before the next burst
+        assert_eq!(burst.pause(ms(15)), ms(26));
+        assert_eq!(burst.pause(ms(45)), ms(1));
+
+        let cfg = format!("{{ {pat}, \"rate\": {{ \"type\": \"burst\", \"burst\": 0, \"pause\": 5 }} }}");
+        assert!(TestGenFactory::new().create_sock(cfg.parse().unwrap()).is_err());
+        // Cycle is optional with the ramp
+        let cfg = format!("{{ {pat}, \"rate\": {{ \"type\": \"ramp\", \"start\": 0, \"end\": 0, \"duration\": 0 }} }}");
+        let sock = TestGenFactory::new().create_sock(cfg.parse().unwrap()).unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(sock.read(&mut buf, 8).unwrap(), 4);
+    }
+    #[test]
+    fn cycle_jitter() {
+        let pauses = |jitter: &str| -> Vec<Duration> {
+            let jitter: CycleJitter = serde_json::from_str(jitter).unwrap();
+            let mut rng = StdRng::seed_from_u64(jitter.seed.unwrap());
+            (0..100).map(|_| jitter.apply(Duration::from_millis(10), &mut rng)).collect()
+        };
+        let uniform = pauses(r#"{ "jitter": 2000, "seed": 3 }"#);
+        assert_eq!(uniform, pauses(r#"{ "jitter": 2000, "seed": 3 }"#));
+        let ms = Duration::from_millis;
+        assert!(uniform.iter().all(|p| p.abs_diff(ms(10)) <= ms(2)));
+        assert!(uniform.iter().any(|p| *p != uniform[0]));
+        // Normal deviations over the pause are cut at zero
+        let normal = pauses(r#"{ "jitter": 20000, "distribution": "gaussian", "seed": 3 }"#);
+        assert!(normal.contains(&Duration::ZERO));
+        assert!(normal.iter().any(|p| *p > ms(20)));
+    }
+    #[test]
+    fn echo_statistics() {
+        let cfg = r#"{ "pat": { "type": "hex_str", "data": "00ff" }, "cycle": 0, "echo": true }"#;
+        let sock = SimpleTestGen::from_config(serde_json::from_str(cfg).unwrap()).unwrap();
+        let mut buf = [0u8; 4];
+        for _ in 0..3 {
+            assert_eq!(sock.read(&mut buf, 4).unwrap(), 2);
+        }
+        // Echo is split differently and one bit is flipped
+        assert_eq!(sock.write(&[0x00], 1).unwrap(), 1);
+        assert_eq!(sock.write(&[0xff, 0x01, 0xff], 3).unwrap(), 3);
+        let p = sock.p.borrow();
+        let echo = p.echo.as_ref().unwrap();
+        let report = echo.to_string();
+        // The last chunk waits for the echo
+        assert!(report.starts_with(
+            "1 of 32 bits errored (BER 3.12e-2), 2 bytes lost, 0 bytes unexpected, 2 samples"
+        ));
+        drop(p);
+
+        let mut echo = EchoTracker::default();
+        let now = Instant::now();
+        echo.sent(&[0u8; MAX_ECHO_PENDING], now);
+        echo.sent(&[1, 2], now);
+        echo.echoed(&[1, 2, 3], now);
+        assert_eq!((echo.lost, echo.unexpected, echo.bit_errors), (MAX_ECHO_PENDING as u64, 1, 0));
+    }
+    #[test]
+    fn template_pattern() {
+        let cfg = r#"{ "pat": { "type": "template", "parts": [
+            { "type": "hex", "data": "7e" },
+            { "type": "counter", "start": 4294967295 },
+            { "type": "timestamp", "little_endian": true },
+            { "type": "text", "data": "ok" },
+            { "type": "crc", "algo": "crc16_modbus", "from": 1, "little_endian": true }
+        ] }, "cycle": 0, "iter_num": 2 }"#;
+        let sock = TestGenFactory::new().create_sock(cfg.parse().unwrap()).unwrap();
+        let mut buf = [0u8; 32];
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        for counter in [[0xff; 4], [0x00; 4]] {
+            assert_eq!(sock.read(&mut buf, 32).unwrap(), 13);
+            assert_eq!(buf[..5], [[0x7e].as_slice(), &counter].concat());
+            let time = u32::from_le_bytes(buf[5..9].try_into().unwrap());
+            assert!(time.abs_diff(now) <= 1);
+            assert_eq!(&buf[9..11], b"ok");
+            let crc = ChecksumAlgo::Crc16Modbus.checksum(&buf[1..11], true);
+            assert_eq!(buf[11..13], crc);
+        }
+        assert!(error::is_eof(&sock.read(&mut buf, 32).unwrap_err()));
+
+        // CRC can't cover the following bytes
+        let cfg = r#"{ "pat": { "type": "template", "parts": [
+            { "type": "crc", "algo": "crc32", "from": 1 }, { "type": "hex", "data": "00" }
+        ] }, "cycle": 0 }"#;
+        assert!(TestGenFactory::new().create_sock(cfg.parse().unwrap()).is_err());
+    }
+    #[test]
+    fn random_pattern() {
+        let cfg = "{ \"pat\": { \"type\": \"random\", \"seed\": 7, \"size\": 64, \"min\": \"0x30\", \"max\": \"0x39\" }, \"cycle\": 0 }";
+        let read = || {
+            let sock = TestGenFactory::new().create_sock(cfg.parse().unwrap()).unwrap();
+            let mut data = vec![0u8; 192];
+            assert_eq!(sock.read(&mut data, 128).unwrap(), 64);
+            assert_eq!(sock.read(&mut data[64..], 128).unwrap(), 64);
+            data.truncate(128);
+            data
+        };
+        let data = read();
+        // The same seed gives the same data, iterations differ
+        assert_eq!(data, read());
+        assert_ne!(data[..64], data[64..]);
+        assert!(data.iter().all(u8::is_ascii_digit));
+        let inverted = "{ \"pat\": { \"type\": \"random\", \"size\": 8, \"min\": \"0x40\", \"max\": \"0x30\" }, \"cycle\": 0 }";
+        assert!(TestGenFactory::new().create_sock(inverted.parse().unwrap()).is_err());
+    }
+
+    #[cfg(test)]
+    proptest::proptest! {
+        #[test]
+        fn curr_size_fits_pattern(
+            pattern_size in 0..64usize,
+            req in 0..128usize,
+            pos in 0..128usize,
+        ) {
+            match get_curr_size(pattern_size, req, pos) {
+                Ok(size) => {
+                    assert!(size <= req && pos + size <= pattern_size);
+                    // Data is cut only by the pattern end
+                    assert!(size == req || pos + size == pattern_size);
+                }
+                Err(_) => assert!(pos > pattern_size),
+            }
+        }
+        #[test]
+        fn pattern_survives_any_read_sizes(
+            pattern in proptest::collection::vec(proptest::num::u8::ANY, 1..32),
+            sizes in proptest::collection::vec(0..48usize, 1..32),
+        ) {
+            let cfg = format!(
+                "{{ \"pat\": {{ \"type\": \"hex_str\", \"data\": \"{}\" }}, \"cycle\": 0 }}",
+                hex::encode(&pattern)
+            );
+            let sock = TestGenFactory::new().create_sock(cfg.parse().unwrap()).unwrap();
+            let mut out: Vec<u8> = Vec::new();
+            let mut buf = [0u8; 32];
+            for sz in sizes {
+                match sock.read(&mut buf, sz) {
+                    Ok(read) => out.extend(&buf[..read]),
+                    Err(e) => assert!(sz > buf.len() && e.kind() == ErrorKind::InvalidInput),
+                }
+            }
+            let expected: Vec<u8> = pattern.iter().cycle().take(out.len()).copied().collect();
+            assert_eq!(out, expected);
+        }
+    }
 }
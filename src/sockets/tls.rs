@@ -0,0 +1,178 @@
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::sign::CertifiedKey;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Max time of the TLS handshake, the connection is dropped after it.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Crypto provider of the TLS endpoints.
+pub fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn pem_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("Reading {} failed: {e}", path.display()),
+    )
+}
+
+/// Protocol names of the ALPN extension.
+pub fn alpn_protocols(alpn: &[String]) -> Vec<Vec<u8>> {
+    alpn.iter().map(|proto| proto.as_bytes().to_vec()).collect()
+}
+
+/// Loads the certificate chain (PEM) and its private key (PEM).
+pub fn load_certified_key(cert: &Path, key: &Path) -> io::Result<Arc<CertifiedKey>> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .map_err(|e| pem_error(cert, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| pem_error(cert, e))?;
+    if certs.is_empty() {
+        return Err(pem_error(cert, "no certificates"));
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|e| pem_error(key, e))?;
+    let key = provider()
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    Ok(Arc::new(CertifiedKey::new(certs, key)))
+}
+
+/// Loads the trusted root certificates (PEM).
+pub fn load_roots(path: &Path) -> io::Result<rustls::RootCertStore> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(path).map_err(|e| pem_error(path, e))? {
+        roots
+            .add(cert.map_err(|e| pem_error(path, e))?)
+            .map_err(|e| pem_error(path, e))?;
+    }
+    Ok(roots)
+}
+
+/// Result of reading the TLS stream.
+pub enum Received {
+    Data(usize),
+    /// No data is available without blocking
+    Nothing,
+    /// Peer closed the connection
+    Closed,
+}
+
+/// TLS connection over the TCP stream. Written data is buffered by the
+/// connection and sent as the socket accepts it, so the stream can be
+/// nonblocking.
+pub struct TlsStream {
+    conn: rustls::Connection,
+    sock: TcpStream,
+}
+
+impl TlsStream {
+    /// Completes the handshake on the connected stream. Blocking mode of
+    /// the stream is set after it.
+    pub fn handshake(
+        conn: impl Into<rustls::Connection>,
+        sock: TcpStream,
+        blocking: bool,
+    ) -> io::Result<Self> {
+        let mut stream = Self {
+            conn: conn.into(),
+            sock,
+        };
+        stream.sock.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.sock.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        stream.sock.set_read_timeout(None)?;
+        stream.sock.set_write_timeout(None)?;
+        stream.conn.set_buffer_limit(None);
+        stream.sock.set_nonblocking(!blocking)?;
+        Ok(stream)
+    }
+    /// Sets timeouts of blocking read and write operations.
+    pub fn set_timeout(&self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        self.tcp().set_read_timeout(read)?;
+        self.tcp().set_write_timeout(write)
+    }
+    pub fn tcp(&self) -> &TcpStream {
+        &self.sock
+    }
+    /// Server name requested by the client (server side only).
+    pub fn server_name(&self) -> Option<&str> {
+        match &self.conn {
+            rustls::Connection::Server(conn) => conn.server_name(),
+            rustls::Connection::Client(_) => None,
+        }
+    }
+    /// Application protocol negotiated by ALPN.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.conn.alpn_protocol()
+    }
+    pub fn recv(&mut self, data: &mut [u8]) -> io::Result<Received> {
+        loop {
+            match self.conn.reader().read(data) {
+                Ok(0) => return Ok(Received::Closed),
+                Ok(sz) => return Ok(Received::Data(sz)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+            match self.conn.read_tls(&mut self.sock) {
+                Ok(0) => return Ok(Received::Closed),
+                Ok(_) => {
+                    let res = self.conn.process_new_packets();
+                    // Alerts and key updates are sent back anyway
+                    self.flush()?;
+                    res.map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(Received::Nothing),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    /// Whether previously sent data isn't accepted by the socket yet.
+    pub fn is_pending(&self) -> bool {
+        self.conn.wants_write()
+    }
+    /// Buffers the data and sends as much as the socket accepts. Returns
+    /// number of accepted bytes: all data, or 0 while the previously sent
+    /// data is still pending.
+    pub fn send(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.flush()?;
+        if self.is_pending() {
+            return Ok(0);
+        }
+        self.conn.writer().write_all(data)?;
+        self.flush()?;
+        Ok(data.len())
+    }
+    /// Sends the buffered data, the rest is kept for the next call.
+    pub fn flush(&mut self) -> io::Result<()> {
+        while self.conn.wants_write() {
+            match self.conn.write_tls(&mut self.sock) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+    /// Sends close notification to the peer and shuts down the write side,
+    /// the data of the peer still can be received.
+    pub fn shutdown_write(&mut self) -> io::Result<()> {
+        self.conn.send_close_notify();
+        self.flush()?;
+        self.sock.shutdown(std::net::Shutdown::Write)
+    }
+    /// Sends close notification to the peer.
+    pub fn close(&mut self) {
+        self.conn.send_close_notify();
+        let _ = self.flush();
+        let _ = self.sock.shutdown(std::net::Shutdown::Both);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error;
+use crate::serde_helpers;
+use crate::sock::make_simple_sock;
+use crate::sock::params::{parse_params, validate_params};
+use crate::sock::{
+    ComplexSock, RawHandle, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll, SocketFactory,
+    SocketParams, poll,
+};
+use crate::sockets::sockopt;
+use crate::sockets::tls::{self, Received, TlsStream};
+use rustls::pki_types::ServerName;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configuration for TLS client.
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct TlsClientConfig {
+    /// Destination host IP address to connect
+    ip_dst: IpAddr,
+    #[serde(
+        default = "serde_helpers::default_port",
+    )]
+    /// Destination port of host TLS server to connect
+    port_dst: u16,
+    /// Network interface to bind socket (SO_BINDTODEVICE, Linux only)
+    interface: Option<String>,
+    /// Server name (SNI) to request and verify, the destination IP address if not set
+    server_name: Option<String>,
+    /// Trusted root certificates (PEM), the well-known web roots if not set
+    ca: Option<PathBuf>,
+    /// Application protocols offered by ALPN in the order of preference
+    #[serde(default)]
+    alpn: Vec<String>,
+}
+
+impl TlsClientConfig {
+    fn client_config(&self) -> std::io::Result<Arc<rustls::ClientConfig>> {
+        let roots = match &self.ca {
+            Some(ca) => tls::load_roots(ca)?,
+            None => rustls::RootCertStore::from_iter(
+                webpki_roots::TLS_SERVER_ROOTS.iter().cloned(),
+            ),
+        };
+        let mut config = rustls::ClientConfig::builder_with_provider(tls::provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = tls::alpn_protocols(&self.alpn);
+        Ok(Arc::new(config))
+    }
+    fn server_name(&self) -> std::io::Result<ServerName<'static>> {
+        match &self.server_name {
+            Some(name) => ServerName::try_from(name.clone())
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e)),
+            None => Ok(ServerName::IpAddress(self.ip_dst.into())),
+        }
+    }
+}
+
+make_simple_sock!(SimpleTlsClient {
+    config: TlsClientConfig,
+    stream: RefCell<Option<TlsStream>>,
+    is_blocking: bool,
+    timeouts: (Option<Duration>, Option<Duration>),
+}, "tls-client");
+
+impl SimpleSock for SimpleTlsClient {
+    fn open(&mut self) -> std::io::Result<()> {
+        let conn = rustls::ClientConnection::new(
+            self.config.client_config()?,
+            self.config.server_name()?,
+        )
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        let sock = sockopt::tcp_connect(
+            SocketAddr::new(self.config.ip_dst, self.config.port_dst),
+            self.config.interface.as_deref(),
+            false,
+            None,
+        )?;
+        let stream = TlsStream::handshake(conn, sock, self.is_blocking)?;
+        stream.set_timeout(self.timeouts.0, self.timeouts.1)?;
+        self.stream = RefCell::new(Some(stream));
+        Ok(())
+    }
+    fn close(&mut self) {
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            stream.close();
+        }
+    }
+    fn read(&self, data: &mut [u8], sz: usize) -> std::io::Result<usize> {
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            return match stream.recv(data[..sz].as_mut())? {
+                Received::Data(count) => Ok(count),
+                // Peer has sent close notification, no data will come
+                Received::Closed if sz > 0 => Err(error::Error::Eof.into()),
+                Received::Nothing | Received::Closed => Ok(0),
+            };
+        }
+        Err(Error::from(ErrorKind::NotConnected))
+    }
+    fn write(&self, data: &[u8], sz: usize) -> std::io::Result<usize> {
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            return match stream.send(data[..sz].as_ref()) {
+                // Blocking send is pending only if the timeout is expired
+                Ok(0) if self.is_blocking => Err(Error::from(ErrorKind::TimedOut)),
+                count => count,
+            };
+        }
+        Err(Error::from(ErrorKind::NotConnected))
+    }
+}
+
+impl SockBlockCtl for SimpleTlsClient {
+    fn set_block(&mut self, is_blocking: bool) -> std::io::Result<()> {
+        self.is_blocking = is_blocking;
+        Ok(())
+    }
+    // Applied to the stream when it is connected
+    fn set_timeout(
+        &mut self,
+        read: Option<Duration>,
+        write: Option<Duration>,
+    ) -> std::io::Result<()> {
+        self.timeouts = (read, write);
+        if let Some(stream) = self.stream.borrow().as_ref() {
+            stream.set_timeout(read, write)?;
+        }
+        Ok(())
+    }
+    fn shutdown_write(&self) -> std::io::Result<()> {
+        match self.stream.borrow_mut().as_mut() {
+            Some(stream) => stream.shutdown_write(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl SockPoll for SimpleTlsClient {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        self.stream
+            .borrow()
+            .as_ref()
+            .map(|s| vec![poll::raw_handle(s.tcp())])
+    }
+}
+
+struct TlsClientDoc;
+impl SockDocViewer for TlsClientDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(TlsClientConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let example = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8443, \
+            \"server_name\": \"localhost\", \"ca\": \"ca.pem\" }";
+        let example_sni = "{ \"ip_dst\": \"192.168.1.10\", \"port_dst\": 8443, \
+            \"server_name\": \"gps.local\", \"ca\": \"ca.pem\" }";
+        format!(
+            "{}: {}\n{}: {}",
+            "Connection verified by private CA", example,
+            "Connection to routed server name", example_sni,
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct TlsClientFactory;
+
+impl TlsClientFactory {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl SocketFactory for TlsClientFactory {
+    fn create_sock(&self, params: SocketParams) -> std::io::Result<Box<dyn ComplexSock>> {
+        let tls_config: TlsClientConfig = parse_params("tls-client", &params)?;
+
+        // Blocking by default
+        Ok(Box::new(SimpleTlsClient::new(
+            tls_config,
+            RefCell::new(None),
+            true,
+            (None, None),
+        )))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
+        Box::new(TlsClientDoc)
+    }
+    fn validate_params(&self, params: &SocketParams) -> std::io::Result<()> {
+        Ok(validate_params::<TlsClientConfig>("tls-client", params)?)
+    }
+}
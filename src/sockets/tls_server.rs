@@ -0,0 +1,645 @@
+use crate::error;
+use crate::serde_helpers;
+use crate::sock::make_simple_sock;
+use crate::sock::params::{parse_params, validate_params};
+use crate::sock::{
+    ComplexSock, HashAlgo, HashDecoratorFactory, MonitorConfig, MonitorDecoratorFactory,
+    POLL_TIMEOUT, READ_CHUNK_SIZE, RawHandle, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll,
+    SocketFactory, SocketParams, SummaryDecoratorFactory, TraceCanonicalDecoratorFactory,
+    TraceInfoDecoratorFactory, TraceRawDecoratorFactory, TraceSide, poll,
+};
+use crate::sockets::sockopt;
+use crate::sockets::tls::{self, Received, TlsStream};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{self, Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Decorator of the route backend, the same as the trace options of the
+/// oneliner.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RouteDecorator {
+    Info,
+    Raw,
+    Canon,
+    Hash { algo: HashAlgo },
+    /// Aggregate line every `interval` seconds
+    Summary { interval: u64 },
+    Monitor(MonitorConfig),
+}
+
+impl RouteDecorator {
+    fn decorate(&self, factory: Box<dyn SocketFactory>) -> Box<dyn SocketFactory> {
+        // Backend is the destination of the client data
+        let side = TraceSide::To;
+        match self {
+            Self::Info => TraceInfoDecoratorFactory::new(factory, side),
+            Self::Raw => TraceRawDecoratorFactory::new(factory, side),
+            Self::Canon => TraceCanonicalDecoratorFactory::new(factory, side),
+            Self::Hash { algo } => HashDecoratorFactory::new(factory, *algo),
+            Self::Summary { interval } => {
+                SummaryDecoratorFactory::new(factory, Duration::from_secs(*interval))
+            }
+            Self::Monitor(config) => MonitorDecoratorFactory::new(factory, config.clone()),
+        }
+    }
+}
+
+/// Backend socket of the connections with the server name and the
+/// negotiated protocol. Criteria which aren't set match any connection.
+#[derive(Deserialize, Debug, Clone, schemars::JsonSchema)]
+pub struct TlsRoute {
+    /// Server name (SNI) requested by the client
+    sni: Option<String>,
+    /// Application protocol negotiated by ALPN
+    alpn: Option<String>,
+    /// Certificate chain of the name (PEM), the server one if not set
+    cert: Option<PathBuf>,
+    /// Private key of the name certificate (PEM)
+    key: Option<PathBuf>,
+    /// Socket type of the backend
+    #[serde(rename = "type")]
+    ty: String,
+    /// Backend socket parameters
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    params: serde_json::Value,
+    /// Decorators of the backend, applied in the order of the list
+    #[serde(default)]
+    decorators: Vec<RouteDecorator>,
+}
+
+impl TlsRoute {
+    fn matches(&self, tls: &TlsStream) -> bool {
+        let sni = self.sni.as_deref();
+        let alpn = self.alpn.as_ref().map(|alpn| alpn.as_bytes());
+        sni.is_none_or(|sni| tls.server_name() == Some(sni))
+            && alpn.is_none_or(|alpn| tls.alpn_protocol() == Some(alpn))
+    }
+    fn name(&self) -> String {
+        match (&self.sni, &self.alpn) {
+            (Some(sni), Some(alpn)) => format!("{sni} ({alpn})"),
+            (Some(name), None) | (None, Some(name)) => name.clone(),
+            (None, None) => "any".to_string(),
+        }
+    }
+}
+
+/// Configuration for TLS server.
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct TlsServerConfig {
+    /// Local IP address constrain of TLS server
+    #[serde(default = "serde_helpers::default_ip_local")]
+    ip_local: IpAddr,
+    /// Local port of TLS server
+    port_local: u16,
+    /// Network interface to bind server (SO_BINDTODEVICE, Linux only)
+    interface: Option<String>,
+    /// Certificate chain of the server (PEM)
+    cert: PathBuf,
+    /// Private key of the server certificate (PEM)
+    key: PathBuf,
+    /// Application protocols offered by ALPN in the order of preference
+    #[serde(default)]
+    alpn: Vec<String>,
+    /// Connections matching a route are bridged to its backend socket, the
+    /// other ones are the clients of the binding. The first matching route
+    /// is used
+    #[serde(default)]
+    routes: Vec<TlsRoute>,
+}
+
+/// Creates the factory of the socket type, e.g. the backend of the route.
+pub type FactoryLookup = fn(&str) -> Option<Box<dyn SocketFactory>>;
+
+/// Max time to wait for a new connection before the running flag is rechecked.
+const ACCEPT_POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Certificate of the requested server name, the server one otherwise.
+#[derive(Debug)]
+struct SniResolver {
+    server: Arc<CertifiedKey>,
+    names: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let name = hello.server_name().and_then(|name| self.names.get(name));
+        Some(name.unwrap_or(&self.server).clone())
+    }
+}
+
+impl TlsServerConfig {
+    fn server_config(&self) -> io::Result<Arc<rustls::ServerConfig>> {
+        let mut names = HashMap::new();
+        for route in &self.routes {
+            match (&route.sni, &route.cert, &route.key) {
+                (Some(sni), Some(cert), Some(key)) => {
+                    names.insert(sni.clone(), tls::load_certified_key(cert, key)?);
+                }
+                (_, None, None) => {}
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Route {} must set sni, cert and key together", route.name()),
+                    ));
+                }
+            }
+        }
+        let resolver = SniResolver {
+            server: tls::load_certified_key(&self.cert, &self.key)?,
+            names,
+        };
+        let mut config = rustls::ServerConfig::builder_with_provider(tls::provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(resolver));
+        config.alpn_protocols = tls::alpn_protocols(&self.alpn);
+        Ok(Arc::new(config))
+    }
+}
+
+type Clients = Arc<Mutex<Vec<(TlsStream, SocketAddr)>>>;
+/// Read and write timeouts applied to every client connection.
+type ClientTimeouts = (Option<Duration>, Option<Duration>);
+
+/// State shared by the listener and connection threads.
+#[derive(Clone)]
+struct Acceptor {
+    config: Arc<rustls::ServerConfig>,
+    routes: Arc<Vec<TlsRoute>>,
+    lookup: FactoryLookup,
+    clients: Clients,
+    blocking: Arc<AtomicBool>,
+    timeouts: Arc<Mutex<ClientTimeouts>>,
+    is_running: Arc<AtomicBool>,
+}
+
+impl Acceptor {
+    /// Completes the handshake and passes the connection to the binding
+    /// or to the backend of its route.
+    fn connect(&self, stream: TcpStream, addr: SocketAddr) -> io::Result<()> {
+        let conn = rustls::ServerConnection::new(self.config.clone())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let tls = TlsStream::handshake(conn, stream, true)?;
+        match self.routes.iter().find(|route| route.matches(&tls)) {
+            Some(route) => {
+                let backend = self.open_backend(route)?;
+                tracing::trace!("Client {addr} is routed to {}", route.name());
+                self.relay(tls, backend)
+            }
+            None => {
+                tls.tcp().set_nonblocking(!self.blocking.load(Ordering::Relaxed))?;
+                let (read, write) = *self.timeouts.lock().unwrap();
+                tls.set_timeout(read, write)?;
+                self.clients.lock().unwrap().push((tls, addr));
+                Ok(())
+            }
+        }
+    }
+    fn open_backend(&self, route: &TlsRoute) -> io::Result<Box<dyn ComplexSock>> {
+        let factory = (self.lookup)(&route.ty).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Socket type {} of {} route isn't found", route.ty, route.name()),
+            )
+        })?;
+        let factory = route
+            .decorators
+            .iter()
+            .fold(factory, |factory, decorator| decorator.decorate(factory));
+        let params = SocketParams::new(route.params.clone());
+        let mut backend = factory.create_sock_blockctl(params, false)?;
+        backend.open()?;
+        Ok(backend)
+    }
+    /// Passes data between the client and the backend until any of them is
+    /// closed or the server is stopped.
+    fn relay(&self, mut tls: TlsStream, mut backend: Box<dyn ComplexSock>) -> io::Result<()> {
+        tls.tcp().set_nonblocking(true)?;
+        let mut buf = vec![0u8; READ_CHUNK_SIZE];
+        // Data of the client which isn't accepted by the backend yet
+        let mut pending = Vec::new();
+        let res = (|| {
+            while self.is_running.load(Ordering::Relaxed) {
+                let mut idle = true;
+                if pending.is_empty() {
+                    match tls.recv(&mut buf)? {
+                        Received::Data(sz) => pending.extend_from_slice(&buf[..sz]),
+                        Received::Nothing => {}
+                        Received::Closed => break,
+                    }
+                }
+                if !pending.is_empty() {
+                    let written = backend.write(&pending, pending.len())?;
+                    pending.drain(..written);
+                    idle &= written == 0;
+                }
+                // Backend isn't read, until the client accepts sent data
+                tls.flush()?;
+                if !tls.is_pending() {
+                    let sz = backend.read(&mut buf, READ_CHUNK_SIZE)?;
+                    if sz > 0 {
+                        tls.send(&buf[..sz])?;
+                        idle = false;
+                    }
+                }
+                if !idle {
+                    continue;
+                }
+                if pending.is_empty() && !tls.is_pending() {
+                    let handles = backend.poll_handles().map(|mut handles| {
+                        handles.push(poll::raw_handle(tls.tcp()));
+                        handles
+                    });
+                    poll::wait_or_sleep(handles, POLL_TIMEOUT)?;
+                } else {
+                    // Peer isn't ready, the remainder is retried later
+                    thread::sleep(poll::FALLBACK_SLEEP);
+                }
+            }
+            Ok(())
+        })();
+        tls.close();
+        backend.close();
+        res
+    }
+}
+
+make_simple_sock!(TlsServer {
+    config: TlsServerConfig,
+    lookup: FactoryLookup,
+    clients: Clients,
+    blocking: Arc<AtomicBool>,
+    timeouts: Arc<Mutex<ClientTimeouts>>,
+    is_running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<io::Result<()>>>,
+}, "tls-server", self, {
+    let mut descr = format!("{}{}", self.get_type_name(), self.get_id());
+    let clients = self.clients.lock().unwrap();
+    if !clients.is_empty() {
+        descr.push_str(", connected clients:");
+        for (tls, addr) in clients.iter() {
+            descr.push_str(format!("\nClient {addr}").as_str());
+            if let Some(name) = tls.server_name() {
+                descr.push_str(format!(" ({name})").as_str());
+            }
+            if let Some(alpn) = tls.alpn_protocol() {
+                descr.push_str(format!(" [{}]", String::from_utf8_lossy(alpn)).as_str());
+            }
+        }
+    }
+    descr
+});
+
+impl SimpleSock for TlsServer {
+    fn open(&mut self) -> io::Result<()> {
+        let cfg = &self.config;
+        let acceptor = Acceptor {
+            config: cfg.server_config()?,
+            routes: Arc::new(cfg.routes.clone()),
+            lookup: self.lookup,
+            clients: self.clients.clone(),
+            blocking: self.blocking.clone(),
+            timeouts: self.timeouts.clone(),
+            is_running: self.is_running.clone(),
+        };
+        let listener = sockopt::tcp_listen(
+            SocketAddr::new(cfg.ip_local, cfg.port_local),
+            cfg.interface.as_deref(),
+            None,
+            None,
+        )?;
+        listener.set_nonblocking(true)?;
+        self.is_running.store(true, Ordering::Relaxed);
+        let listener_handle = poll::raw_handle(&listener);
+
+        self.handle = Some(thread::spawn(move || -> io::Result<()> {
+            // Connection threads are joined when the server is stopped
+            let mut relays: Vec<JoinHandle<()>> = Vec::new();
+            while acceptor.is_running.load(Ordering::Relaxed) {
+                let Ok((stream, addr)) = listener.accept() else {
+                    // Wait for the next connection, but wake up
+                    // periodically to check the running flag
+                    poll::wait_readable(&[listener_handle], ACCEPT_POLL_TIMEOUT)?;
+                    continue;
+                };
+                stream.set_nonblocking(false)?;
+                // Slow handshake or routed connection doesn't stop accepting
+                let connection = acceptor.clone();
+                let handle = thread::spawn(move || {
+                    if let Err(e) = connection.connect(stream, addr) {
+                        tracing::info!("TLS client {addr} is dropped: {e}");
+                    }
+                });
+                relays.retain(|relay| !relay.is_finished());
+                relays.push(handle);
+            }
+            for relay in relays {
+                let _ = relay.join();
+            }
+            Ok(())
+        }));
+        Ok(())
+    }
+    fn close(&mut self) {
+        self.is_running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            // Wait when listener and connection threads are finished
+            let _ = handle.join();
+            let mut clients = self.clients.lock().unwrap();
+            for (tls, _) in clients.iter_mut() {
+                tls.close();
+            }
+            clients.clear();
+        }
+    }
+    fn read(&self, data: &mut [u8], sz: usize) -> io::Result<usize> {
+        let mut clients = self.clients.lock().unwrap();
+        let mut total: usize = 0;
+        let sz = sz.min(data.len());
+        // Closed and failed connections are removed
+        clients.retain_mut(|(tls, addr)| {
+            if total >= sz {
+                return true;
+            }
+            match tls.recv(&mut data[total..sz]) {
+                Ok(Received::Data(len)) => {
+                    total += len;
+                    true
+                }
+                Ok(Received::Nothing) => true,
+                Ok(Received::Closed) => false,
+                Err(e) => {
+                    tracing::trace!("Client {addr} is dropped: {e}");
+                    false
+                }
+            }
+        });
+        Ok(total)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> io::Result<usize> {
+        let mut clients = self.clients.lock().unwrap();
+
+        // Data is broadcasted, so it is considered written even if some
+        // clients failed to receive it
+        for (tls, addr) in clients.iter_mut() {
+            if matches!(tls.send(&data[..sz]), Ok(len) if len > 0) {
+                tracing::trace!("Data sent to {}", addr);
+            }
+        }
+        Ok(sz)
+    }
+}
+
+impl SockBlockCtl for TlsServer {
+    fn set_block(&mut self, is_blocking: bool) -> io::Result<()> {
+        self.blocking.store(is_blocking, Ordering::Relaxed);
+        Ok(())
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        *self.timeouts.lock().unwrap() = (read, write);
+        for (tls, _) in self.clients.lock().unwrap().iter() {
+            tls.set_timeout(read, write)?;
+        }
+        Ok(())
+    }
+}
+
+impl SockPoll for TlsServer {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        let clients = self.clients.lock().unwrap();
+        Some(
+            clients
+                .iter()
+                .map(|(tls, _)| poll::raw_handle(tls.tcp()))
+                .collect(),
+        )
+    }
+}
+
+struct TlsServerDoc;
+impl SockDocViewer for TlsServerDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(TlsServerConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let example =
+            "{ \"port_local\": 8443, \"cert\": \"server.pem\", \"key\": \"server.key\" }";
+        let example_routes = "{ \"port_local\": 8443, \"cert\": \"server.pem\", \
+            \"key\": \"server.key\", \
+            \"routes\": [ { \"sni\": \"gps.local\", \"type\": \"udp\", \
+            \"params\": { \"ip_dst\": \"10.0.0.7\", \"port_dst\": 5150 } } ] }";
+        let example_alpn = "{ \"port_local\": 8443, \"cert\": \"server.pem\", \
+            \"key\": \"server.key\", \"alpn\": [ \"nmea\", \"ubx\" ], \
+            \"routes\": [ { \"alpn\": \"ubx\", \"type\": \"tcp-client\", \
+            \"params\": { \"ip_dst\": \"10.0.0.7\", \"port_dst\": 2101 }, \
+            \"decorators\": [ { \"type\": \"raw\" } ] } ] }";
+        format!(
+            "{}: {}\n{}: {}\n{}: {}",
+            "Server terminating TLS", example,
+            "Connections to gps.local bridged to UDP device", example_routes,
+            "Traced ubx connections bridged to TCP device, nmea ones bound", example_alpn,
+        )
+    }
+}
+
+pub struct TlsServerFactory {
+    lookup: FactoryLookup,
+}
+
+impl TlsServerFactory {
+    /// Backends of the routes are created by the `lookup`.
+    pub fn new(lookup: FactoryLookup) -> Self {
+        Self { lookup }
+    }
+}
+
+impl SocketFactory for TlsServerFactory {
+    fn create_sock(&self, params: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
+        let config: TlsServerConfig = parse_params("tls-server", &params)?;
+
+        // Blocking by default
+        Ok(Box::new(TlsServer::new(
+            config,
+            self.lookup,
+            Arc::new(Mutex::new(Vec::new())),
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(Mutex::new((None, None))),
+            Arc::new(AtomicBool::new(true)),
+            None,
+        )))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
+        Box::new(TlsServerDoc)
+    }
+    fn validate_params(&self, params: &SocketParams) -> io::Result<()> {
+        validate_params::<TlsServerConfig>("tls-server", params)?;
+        let config: TlsServerConfig = parse_params("tls-server", params)?;
+        for (i, route) in config.routes.iter().enumerate() {
+            let params = SocketParams::new(route.params.clone());
+            (self.lookup)(&route.ty)
+                .ok_or_else(|| error::Error::UnknownType(route.ty.clone()).into())
+                .and_then(|factory| factory.validate_params(&params))
+                .map_err(|e: io::Error| error::Error::InvalidValue {
+                    sock: "tls-server".to_string(),
+                    field: format!("routes[{i}]"),
+                    message: e.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sockets::{tls_client::TlsClientFactory, udp::SocketFactoryUDP};
+    use crate::test_helpers::*;
+    use std::time::Instant;
+
+    fn udp_lookup(ty: &str) -> Option<Box<dyn SocketFactory>> {
+        (ty == "udp").then(|| Box::new(SocketFactoryUDP::new()) as Box<dyn SocketFactory>)
+    }
+
+    /// Reads from the socket until the data is received or time is out.
+    fn read_exact(sock: &dyn ComplexSock, len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 64];
+        let started = Instant::now();
+        while data.len() < len && started.elapsed() < Duration::from_secs(5) {
+            let sz = sock.read(&mut buf, 64).unwrap();
+            data.extend_from_slice(&buf[..sz]);
+            thread::sleep(Duration::from_millis(1));
+        }
+        data
+    }
+
+    fn client(
+        port: u16,
+        server_name: &str,
+        alpn: &[&str],
+        ca: &std::path::Path,
+    ) -> Box<dyn ComplexSock> {
+        let params = serde_json::json!({
+            "ip_dst": "127.0.0.1",
+            "port_dst": port,
+            "server_name": server_name,
+            "ca": ca,
+            "alpn": alpn,
+        });
+        let mut client = TlsClientFactory::new()
+            .create_sock_blockctl(params.into(), false)
+            .unwrap();
+        client.open().unwrap();
+        client
+    }
+
+    #[test]
+    fn test_tls_loopback() {
+        let (ca, cert, key) = test_pki("tls-loopback").unwrap();
+        let params = serde_json::json!({ "port_local": 8121, "cert": cert, "key": key });
+        let mut server = TlsServerFactory::new(udp_lookup)
+            .create_sock_blockctl(params.into(), false)
+            .unwrap();
+        server.open().unwrap();
+
+        let mut client = client(8121, "localhost", &[], &ca);
+        client.write(b"Hello", 5).unwrap();
+        assert_eq!(read_exact(server.as_ref(), 5), b"Hello");
+        server.write(b"World", 5).unwrap();
+        assert_eq!(read_exact(client.as_ref(), 5), b"World");
+        client.close();
+        server.close();
+    }
+
+    #[test]
+    fn test_tls_sni_route() {
+        let (ca, cert, key) = test_pki("tls-sni-route").unwrap();
+        let params = serde_json::json!({
+            "port_local": 8122,
+            "cert": cert,
+            "key": key,
+            "routes": [{
+                "sni": "gps.local",
+                "type": "udp",
+                "params": { "ip_dst": "127.0.0.1", "port_dst": 8124, "port_local": 8123 },
+            }],
+        });
+        let mut server = TlsServerFactory::new(udp_lookup)
+            .create_sock_blockctl(params.into(), false)
+            .unwrap();
+        server.open().unwrap();
+        let device = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8123, \"port_local\": 8124 }";
+        let mut device = SocketFactoryUDP::new()
+            .create_sock_blockctl(device.parse().unwrap(), false)
+            .unwrap();
+        device.open().unwrap();
+
+        // Routed connection bypasses the binding clients
+        let mut client = client(8122, "gps.local", &[], &ca);
+        client.write(b"ping", 4).unwrap();
+        assert_eq!(read_exact(device.as_ref(), 4), b"ping");
+        device.write(b"pong", 4).unwrap();
+        assert_eq!(read_exact(client.as_ref(), 4), b"pong");
+        let mut buf = [0u8; 16];
+        assert_eq!(server.read(&mut buf, 16).unwrap(), 0);
+        client.close();
+        device.close();
+        server.close();
+    }
+
+    #[test]
+    fn test_tls_alpn_dispatch() {
+        let (ca, cert, key) = test_pki("tls-alpn-dispatch").unwrap();
+        let params = serde_json::json!({
+            "port_local": 8125,
+            "cert": cert,
+            "key": key,
+            "alpn": ["nmea", "ubx"],
+            "routes": [{
+                "alpn": "ubx",
+                "type": "udp",
+                "params": { "ip_dst": "127.0.0.1", "port_dst": 8127, "port_local": 8126 },
+                "decorators": [{ "type": "info" }, { "type": "hash", "algo": "crc32" }],
+            }],
+        });
+        let mut server = TlsServerFactory::new(udp_lookup)
+            .create_sock_blockctl(params.into(), false)
+            .unwrap();
+        server.open().unwrap();
+        let device = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8126, \"port_local\": 8127 }";
+        let mut device = SocketFactoryUDP::new()
+            .create_sock_blockctl(device.parse().unwrap(), false)
+            .unwrap();
+        device.open().unwrap();
+
+        // Protocol of the route is dispatched to its backend
+        let mut ubx = client(8125, "localhost", &["ubx"], &ca);
+        ubx.write(b"ubx", 3).unwrap();
+        assert_eq!(read_exact(device.as_ref(), 3), b"ubx");
+        device.write(b"ack", 3).unwrap();
+        assert_eq!(read_exact(ubx.as_ref(), 3), b"ack");
+
+        // Other protocols are the clients of the binding
+        let mut nmea = client(8125, "localhost", &["nmea"], &ca);
+        nmea.write(b"$GPGGA", 6).unwrap();
+        assert_eq!(read_exact(server.as_ref(), 6), b"$GPGGA");
+        assert!(server.get_description().contains("[nmea]"));
+        ubx.close();
+        nmea.close();
+        device.close();
+        server.close();
+    }
+}
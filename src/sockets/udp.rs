@@ -1,8 +1,14 @@
 use crate::serde_helpers;
-use crate::sock::{ComplexSock, SimpleSock, SockBlockCtl, SocketFactory, SocketParams, make_simple_sock, SockDocViewer};
+use crate::sockets::sockopt;
+use crate::sock::{ComplexSock, Semantics, SimpleSock, SockBlockCtl, SocketFactory, SocketParams, make_simple_sock, SockDocViewer};
+use crate::sock::params::{parse_params, validate_params};
+use crate::sock::{poll, RawHandle, SockPoll};
 use serde::Deserialize;
-use std::io::{self, Error, ErrorKind};
-use std::net::{IpAddr, UdpSocket};
+use std::io::{self, ErrorKind};
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 use schemars::JsonSchema;
 
 /// Configuration for UDP socket.
@@ -23,18 +29,77 @@ pub struct UdpConfig {
     )]
     /// Port of the desired host
     port_dst: u16,
+    /// Network interface to bind socket (SO_BINDTODEVICE, Linux only)
+    interface: Option<String>,
+    /// Capture kernel receive timestamps of datagrams (SO_TIMESTAMPING, Linux only)
+    #[serde(default)]
+    rx_timestamps: bool,
 }
 
 make_simple_sock!(SimpleUDP {
-    _config: UdpConfig,
-    socket: UdpSocket,
+    config: UdpConfig,
+    // Bound when the socket is opened, the port is released on close
+    socket: Option<UdpSocket>,
+    is_blocking: bool,
+    timeouts: (Option<Duration>, Option<Duration>),
     dst_addr: Option<String>,
-}, "udp");
+    last_rx_time: Mutex<Option<SystemTime>>,
+    // Senders of the read datagrams, recorded in echo mode only
+    rx_peers: Option<Mutex<VecDeque<SocketAddr>>>,
+}, "udp"; info: {
+    fn rx_timestamp(&self) -> Option<SystemTime> {
+        *self.last_rx_time.lock().unwrap()
+    }
+    fn semantics(&self) -> Semantics {
+        Semantics::Message
+    }
+});
+
+impl SimpleUDP {
+    fn socket(&self) -> io::Result<&UdpSocket> {
+        self.socket
+            .as_ref()
+            .ok_or_else(|| io::Error::from(ErrorKind::NotConnected))
+    }
+    fn recv(&self, data: &mut [u8]) -> io::Result<usize> {
+        let socket = self.socket()?;
+        // Sender is needed to reply, timestamps aren't captured then
+        if let Some(rx_peers) = &self.rx_peers {
+            let (sz, addr) = socket.recv_from(data)?;
+            rx_peers.lock().unwrap().push_back(addr);
+            return Ok(sz);
+        }
+        if !self.config.rx_timestamps {
+            return socket.recv(data);
+        }
+        let (sz, ts) = sockopt::recv_with_timestamp(socket, data)?;
+        *self.last_rx_time.lock().unwrap() = ts;
+        Ok(sz)
+    }
+}
 
 impl SimpleSock for SimpleUDP {
+    fn open(&mut self) -> io::Result<()> {
+        let cfg = &self.config;
+        let socket = sockopt::udp_bind(
+            SocketAddr::new(cfg.ip_local, cfg.port_local),
+            cfg.interface.as_deref(),
+        )?;
+        if cfg.rx_timestamps {
+            sockopt::enable_rx_timestamping(&socket)?;
+        }
+        socket.set_read_timeout(self.timeouts.0)?;
+        socket.set_write_timeout(self.timeouts.1)?;
+        socket.set_nonblocking(!self.is_blocking)?;
+        self.socket = Some(socket);
+        Ok(())
+    }
+    fn close(&mut self) {
+        self.socket = None;
+    }
     fn read(&self, data: &mut [u8], _sz: usize) -> io::Result<usize> {
         // In kind of empty socket we want Ok(0) to return
-        match self.socket.recv(data) {
+        match self.recv(data) {
             Err(err) => {
                 if err.kind() == ErrorKind::WouldBlock {
                     return Ok(0);
@@ -45,26 +110,68 @@ impl SimpleSock for SimpleUDP {
         }
     }
 
-    fn write(&self, data: &[u8], sz: usize) -> io::Result<()> {
+    fn write(&self, data: &[u8], sz: usize) -> io::Result<usize> {
+        if let Some(rx_peers) = self.rx_peers.as_ref().filter(|_| sz > 0) {
+            let mut rx_peers = rx_peers.lock().unwrap();
+            if let Some(addr) = rx_peers.front() {
+                // Sender is replied with one datagram, it's kept for retry
+                // if the socket isn't ready
+                return match self.socket()?.send_to(&data[..sz], addr) {
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(0),
+                    res => {
+                        rx_peers.pop_front();
+                        res
+                    }
+                };
+            }
+        }
         if sz > 0 {
             if let Some(dst_addr) = &self.dst_addr {
-                self.socket.send_to(&data[..sz], dst_addr)?;
+                // Datagram is sent entirely or not sent at all
+                return match self.socket()?.send_to(&data[..sz], dst_addr) {
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(0),
+                    res => res,
+                };
             } else {
                 return Err(io::Error::from(ErrorKind::InvalidFilename));
             }
         }
-        Ok(())
+        Ok(0)
     }
 }
 
+// Applied to the socket when it is opened
 impl SockBlockCtl for SimpleUDP {
     fn set_block(&mut self, is_block: bool) -> io::Result<()> {
-        // Invert the operation
-        self.socket.set_nonblocking(!is_block)
+        self.is_blocking = is_block;
+        match &self.socket {
+            // Invert the operation
+            Some(socket) => socket.set_nonblocking(!is_block),
+            None => Ok(()),
+        }
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        self.timeouts = (read, write);
+        if let Some(socket) = &self.socket {
+            socket.set_read_timeout(read)?;
+            socket.set_write_timeout(write)?;
+        }
+        Ok(())
+    }
+    fn set_echo(&mut self, echo: bool) -> io::Result<()> {
+        self.rx_peers = echo.then(|| Mutex::new(VecDeque::new()));
+        Ok(())
+    }
+}
+
+impl SockPoll for SimpleUDP {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        self.socket.as_ref().map(|socket| vec![poll::raw_handle(socket)])
     }
 }
 
 /// UDP socket factory implementing the SocketFactory trait.
+#[derive(Default)]
 pub struct SocketFactoryUDP;
 
 impl SocketFactoryUDP {
@@ -83,10 +190,14 @@ impl SockDocViewer for UdpDoc {
     fn get_examples(&self) -> String {
         let example_dst = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 1234 }";
         let example_src = "{ \"port_local\": 1234 }";
+        let example_iface = "{ \"port_local\": 1234, \"interface\": \"eth0\" }";
+        let example_ts = "{ \"port_local\": 1234, \"rx_timestamps\": true }";
         format!(
-            "{}: {}\n{}: {}",
+            "{}: {}\n{}: {}\n{}: {}\n{}: {}",
             "Transmitter configuration", example_dst,
-            "Receiver configuration", example_src
+            "Receiver configuration", example_src,
+            "Receiver bound to network interface", example_iface,
+            "Receiver with kernel timestamps", example_ts
         )
     }
 }
@@ -94,22 +205,28 @@ impl SockDocViewer for UdpDoc {
 impl SocketFactory for SocketFactoryUDP {
     fn create_sock(&self, params: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
         // Deserialize to UdpConfig
-        let udp_config: UdpConfig = serde_json::from_str(params.as_str()).map_err(|e| {
-            eprintln!("{e}");
-            Error::new(ErrorKind::InvalidInput, "Invalid UDP configuration")
-        })?;
-
-        // Bind and connect the socket
-        let socket = UdpSocket::bind(format!("{}:{}", udp_config.ip_local, udp_config.port_local))?;
+        let udp_config: UdpConfig = parse_params("udp", &params)?;
         let dst_addr = udp_config
             .ip_dst
             .map(|ip_dst| format!("{}:{}", ip_dst, udp_config.port_dst));
 
-        Ok(Box::new(SimpleUDP::new(udp_config, socket, dst_addr)))
+        // Blocking by default, the socket is bound when opened
+        Ok(Box::new(SimpleUDP::new(
+            udp_config,
+            None,
+            true,
+            (None, None),
+            dst_addr,
+            Mutex::new(None),
+            None,
+        )))
     }
     fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
         Box::new(UdpDoc)
     }
+    fn validate_params(&self, params: &SocketParams) -> io::Result<()> {
+        Ok(validate_params::<UdpConfig>("udp", params)?)
+    }
 }
 
 mod tests {
@@ -122,21 +239,102 @@ mod tests {
     fn test_udp_socket_echo_loopback() {
         let factory = SocketFactoryUDP::new();
         let sender_params =
-            "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8080, \"port_local\": 8081}".to_string();
+            "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8080, \"port_local\": 8081}".parse().unwrap();
         let receiver_params = 
-            "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8081, \"port_local\": 8080}".to_string();
+            "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8081, \"port_local\": 8080}".parse().unwrap();
         let snd_data = "Hello".as_bytes().to_vec();
 
         assert!(if let Err(e) =
             echo_loopback_test(&factory, sender_params, receiver_params, snd_data)
         {
-            eprintln!("{e}");
+            tracing::error!("{e}");
             false
         } else {
             true
         })
     }
     #[test]
+    #[cfg(target_os = "linux")]
+    fn test_udp_rx_timestamps() {
+        let factory = SocketFactoryUDP::new();
+        let mut receiver = factory
+            .create_sock_blockctl(
+                "{ \"ip_local\": \"127.0.0.1\", \"port_local\": 8082, \"rx_timestamps\": true }"
+                    .parse()
+                    .unwrap(),
+                true,
+            )
+            .unwrap();
+        receiver.open().unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut buf = [0u8; 16];
+        // Kernel enables timestamping asynchronously, so the first
+        // datagrams may come without the timestamp
+        let mut rx_time = None;
+        for _ in 0..10 {
+            sender.send_to(b"ping", "127.0.0.1:8082").unwrap();
+            assert_eq!(receiver.read(&mut buf, 16).unwrap(), 4);
+            rx_time = receiver.rx_timestamp();
+            if rx_time.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Software timestamp is taken by the kernel just before the read
+        let age = rx_time.unwrap().elapsed().unwrap();
+        assert!(age < Duration::from_secs(1));
+    }
+    #[test]
+    fn test_udp_echo_replies_to_sender() {
+        let factory = SocketFactoryUDP::new();
+        let mut echo = factory
+            .create_sock_blockctl(
+                "{ \"ip_local\": \"127.0.0.1\", \"port_local\": 8083 }"
+                    .parse()
+                    .unwrap(),
+                true,
+            )
+            .unwrap();
+        echo.set_echo(true).unwrap();
+        echo.open().unwrap();
+        let first = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").unwrap();
+        first.send_to(b"first", "127.0.0.1:8083").unwrap();
+        second.send_to(b"second", "127.0.0.1:8083").unwrap();
+        let mut buf = [0u8; 16];
+        for _ in 0..2 {
+            let sz = echo.read(&mut buf, 16).unwrap();
+            assert_eq!(echo.write(&buf, sz).unwrap(), sz);
+        }
+        assert_eq!(first.recv(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"first");
+        assert_eq!(second.recv(&mut buf).unwrap(), 6);
+        assert_eq!(&buf[..6], b"second");
+    }
+    #[test]
+    fn test_udp_lifecycle() {
+        let factory = SocketFactoryUDP::new();
+        let params: SocketParams = "{ \"ip_local\": \"127.0.0.1\", \"port_local\": 8084 }"
+            .parse()
+            .unwrap();
+        // Port is bound on open only
+        let mut sock = factory.create_sock_blockctl(params.clone(), false).unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(sock.read(&mut buf, 16).unwrap_err().kind(), ErrorKind::NotConnected);
+        assert!(sock.poll_handles().is_none());
+        sock.open().unwrap();
+        // Nonblocking mode set before open is applied
+        assert_eq!(sock.read(&mut buf, 16).unwrap(), 0);
+        let mut other = factory.create_sock(params).unwrap();
+        assert_eq!(other.open().unwrap_err().kind(), ErrorKind::AddrInUse);
+
+        // Port is released on close and can be bound again
+        sock.close();
+        other.open().unwrap();
+        other.close();
+        sock.open().unwrap();
+    }
+    #[test]
     fn test_doc_params() {
         println!("{}", SocketFactoryUDP::new().create_doc_viewer().get_full_scheme());
     }
@@ -0,0 +1,196 @@
+use super::testgen::TestPatternStrategy;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::any::Any;
+use std::f64::consts::TAU;
+use std::io::{Error, ErrorKind, Result};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WaveShape {
+    Sine,
+    /// Rising from the lowest to the highest value
+    Sawtooth,
+    /// The highest value in the first half of the period
+    Square,
+}
+
+impl WaveShape {
+    /// Value in -1.0..=1.0 at the phase in 0.0..1.0.
+    fn value(&self, phase: f64) -> f64 {
+        match self {
+            Self::Sine => (TAU * phase).sin(),
+            Self::Sawtooth => 2.0 * phase - 1.0,
+            Self::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+        }
+    }
+}
+
+/// Format of the sample, unsigned samples are offset by the half of the
+/// full scale.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleFormat {
+    U8,
+    I8,
+    U16,
+    #[default]
+    I16,
+}
+
+impl SampleFormat {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+        }
+    }
+    /// Appends the sample of the value in -1.0..=1.0.
+    fn encode(&self, value: f64, little_endian: bool, out: &mut Vec<u8>) {
+        let sample = match self {
+            Self::U8 => (value * 127.0).round() as i32 + 128,
+            Self::I8 => (value * 127.0).round() as i32,
+            Self::U16 => (value * 32767.0).round() as i32 + 32768,
+            Self::I16 => (value * 32767.0).round() as i32,
+        };
+        // The low bytes are the sample in two's complement
+        let bytes = sample.to_be_bytes();
+        let bytes = &bytes[4 - self.len()..];
+        if little_endian {
+            out.extend(bytes.iter().rev());
+        } else {
+            out.extend(bytes);
+        }
+    }
+}
+
+/// Waveform parameters and the samples of the current iteration. The phase
+/// continues over the iterations.
+pub struct WaveformState {
+    shape: WaveShape,
+    /// Periods per sample
+    step: f64,
+    amplitude: f64,
+    format: SampleFormat,
+    little_endian: bool,
+    samples: usize,
+    /// Number of the next sample
+    next: u64,
+    frame: Vec<u8>,
+}
+
+impl WaveformState {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        shape: WaveShape,
+        frequency: f64,
+        amplitude: f64,
+        sample_rate: u32,
+        format: SampleFormat,
+        little_endian: bool,
+        samples: usize,
+    ) -> Result<Self> {
+        let valid_frequency = frequency.is_finite() && frequency >= 0.0;
+        if sample_rate == 0 || !valid_frequency || !(0.0..=1.0).contains(&amplitude) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Waveform requires sample rate > 0, frequency >= 0 and amplitude in 0..=1",
+            ));
+        }
+        Ok(Self {
+            shape,
+            step: frequency / sample_rate as f64,
+            amplitude,
+            format,
+            little_endian,
+            samples,
+            next: 0,
+            frame: Vec::with_capacity(samples * format.len()),
+        })
+    }
+    fn next_frame(&mut self) {
+        self.frame.clear();
+        for n in self.next..self.next + self.samples as u64 {
+            let phase = (n as f64 * self.step).fract();
+            let value = self.shape.value(phase) * self.amplitude;
+            self.format.encode(value, self.little_endian, &mut self.frame);
+        }
+        self.next += self.samples as u64;
+    }
+}
+
+pub struct WaveformStrategy;
+impl TestPatternStrategy for WaveformStrategy {
+    fn read(
+        &self,
+        _: &(dyn Any + Send),
+        p: &mut Option<Box<dyn Any + Send>>,
+        buf: &mut [u8],
+        real_size: usize,
+        pos: usize,
+    ) -> Result<usize> {
+        let Some(state) = p.as_ref().unwrap().downcast_ref::<WaveformState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        buf[..real_size].copy_from_slice(&state.frame[pos..pos + real_size]);
+        Ok(real_size)
+    }
+    fn start_iteration(&self, p: &mut Option<Box<dyn Any + Send>>) -> Result<Option<usize>> {
+        let Some(state) = p.as_mut().unwrap().downcast_mut::<WaveformState>() else {
+            return Err(Error::from(ErrorKind::InvalidData));
+        };
+        state.next_frame();
+        Ok(None)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+
+    use super::*;
+    use crate::sock::SocketFactory;
+    use crate::sockets::testgen::TestGenFactory;
+
+    #[test]
+    fn waveform_samples() {
+        // Two iterations
+        let read = |pat: serde_json::Value| {
+            let cfg = serde_json::json!({ "pat": pat, "cycle": 0 });
+            let sock = TestGenFactory::new().create_sock(cfg.into()).unwrap();
+            let mut buf = [0u8; 16];
+            let mut out = Vec::new();
+            for _ in 0..2 {
+                let read = sock.read(&mut buf, 16).unwrap();
+                out.extend(&buf[..read]);
+            }
+            out
+        };
+        // Quarter of the period per sample, the phase continues in the next iteration
+        let sine = serde_json::json!({
+            "type": "waveform", "shape": "sine", "frequency": 250.0, "sample_rate": 1000,
+            "samples": 2, "little_endian": true
+        });
+        let samples: Vec<i16> = read(sine)
+            .chunks(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]))
+            .collect();
+        assert_eq!(samples, [0, 32767, 0, -32767]);
+
+        let square = serde_json::json!({
+            "type": "waveform", "shape": "square", "frequency": 1.0, "sample_rate": 4,
+            "samples": 4, "format": "u8", "amplitude": 0.5
+        });
+        assert_eq!(read(square), [192, 192, 64, 64, 192, 192, 64, 64]);
+        let sawtooth = serde_json::json!({
+            "type": "waveform", "shape": "sawtooth", "frequency": 1.0, "sample_rate": 2,
+            "samples": 2, "format": "u16"
+        });
+        assert_eq!(read(sawtooth), [0x00, 0x01, 0x80, 0x00, 0x00, 0x01, 0x80, 0x00]);
+
+        let invalid = serde_json::json!({
+            "pat": { "type": "waveform", "shape": "sine", "frequency": 1.0, "sample_rate": 0,
+                "samples": 8 }, "cycle": 0
+        });
+        assert!(TestGenFactory::new().create_sock(invalid.into()).is_err());
+    }
+}
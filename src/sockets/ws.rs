@@ -0,0 +1,114 @@
+use std::io::{self, Error, ErrorKind};
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+use tungstenite::handshake::HandshakeRole;
+use tungstenite::{HandshakeError, Message, WebSocket};
+
+/// Max time of the WebSocket handshake, the connection is dropped after it.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Header of the subprotocols requested by the client and accepted by the
+/// server.
+pub const SUBPROTOCOL_HEADER: &str = "Sec-WebSocket-Protocol";
+
+pub fn io_error(e: tungstenite::Error) -> Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            Error::from(ErrorKind::NotConnected)
+        }
+        e => Error::new(ErrorKind::InvalidData, e),
+    }
+}
+
+pub fn handshake_error<R: HandshakeRole>(e: HandshakeError<R>) -> Error {
+    match e {
+        // Handshake is interrupted by the timeout of the stream
+        HandshakeError::Interrupted(_) => Error::from(ErrorKind::TimedOut),
+        HandshakeError::Failure(e) => io_error(e),
+    }
+}
+
+/// Sets the handshake timeouts of the stream.
+pub fn set_handshake_timeout(sock: &TcpStream) -> io::Result<()> {
+    sock.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    sock.set_write_timeout(Some(HANDSHAKE_TIMEOUT))
+}
+
+/// WebSocket connection over the TCP stream. Every written chunk is sent as
+/// one binary message, payloads of the received messages are read as the
+/// stream.
+pub struct WsStream {
+    ws: WebSocket<TcpStream>,
+    /// Payload of the received message which isn't read yet
+    pending: Vec<u8>,
+}
+
+impl WsStream {
+    /// Wraps the connection after the handshake. Timeouts of the handshake
+    /// are reset and blocking mode of the stream is set.
+    pub fn new(ws: WebSocket<TcpStream>, blocking: bool) -> io::Result<Self> {
+        let sock = ws.get_ref();
+        sock.set_read_timeout(None)?;
+        sock.set_write_timeout(None)?;
+        sock.set_nonblocking(!blocking)?;
+        Ok(Self {
+            ws,
+            pending: Vec::new(),
+        })
+    }
+    /// Sets timeouts of blocking read and write operations.
+    pub fn set_timeout(&self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        self.tcp().set_read_timeout(read)?;
+        self.tcp().set_write_timeout(write)
+    }
+    pub fn tcp(&self) -> &TcpStream {
+        self.ws.get_ref()
+    }
+    /// Reads the payload of the data messages, the part which doesn't fit
+    /// is kept for the next call. Returns `None`, if the peer closed the
+    /// connection.
+    pub fn recv(&mut self, data: &mut [u8]) -> io::Result<Option<usize>> {
+        while self.pending.is_empty() {
+            match self.ws.read() {
+                Ok(Message::Binary(payload)) => self.pending = payload,
+                Ok(Message::Text(text)) => self.pending = text.into_bytes(),
+                // Pongs and close replies are sent by the protocol
+                Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_)) => {}
+                Ok(Message::Close(_))
+                | Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(None);
+                }
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {
+                    return Ok(Some(0));
+                }
+                Err(e) => return Err(io_error(e)),
+            }
+        }
+        let len = data.len().min(self.pending.len());
+        data[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        Ok(Some(len))
+    }
+    /// Sends the data as one binary message. Returns number of accepted
+    /// bytes: all data, or 0 while the previous message is still pending.
+    pub fn send(&mut self, data: &[u8]) -> io::Result<usize> {
+        match self.ws.flush() {
+            Ok(()) => {}
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => return Ok(0),
+            Err(e) => return Err(io_error(e)),
+        }
+        match self.ws.send(Message::binary(data)) {
+            // Message is queued and flushed by the next call
+            Ok(()) => Ok(data.len()),
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(data.len()),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+    /// Sends close frame to the peer.
+    pub fn close(&mut self) {
+        let _ = self.ws.close(None);
+        let _ = self.ws.flush();
+        let _ = self.tcp().shutdown(Shutdown::Both);
+    }
+}
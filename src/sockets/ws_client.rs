@@ -0,0 +1,204 @@
+use crate::error;
+use crate::serde_helpers;
+use crate::sock::make_simple_sock;
+use crate::sock::params::{parse_params, validate_params};
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll,
+    SocketFactory, SocketParams, poll,
+};
+use crate::sockets::sockopt;
+use crate::sockets::ws::{self, WsStream};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::{HeaderName, HeaderValue, Request};
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+/// Configuration for WebSocket client.
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct WsClientConfig {
+    /// Destination host IP address to connect
+    ip_dst: IpAddr,
+    #[serde(
+        default = "serde_helpers::default_port",
+    )]
+    /// Destination port of host WebSocket server to connect
+    port_dst: u16,
+    /// Network interface to bind socket (SO_BINDTODEVICE, Linux only)
+    interface: Option<String>,
+    /// Request path with the optional query, e.g. `/feed?id=7`
+    #[serde(default = "default_path")]
+    path: String,
+    /// Subprotocols requested in the order of preference, the server must
+    /// accept one of them
+    #[serde(default)]
+    subprotocols: Vec<String>,
+    /// Additional request headers, e.g. `Authorization` with the token
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+impl WsClientConfig {
+    fn request(&self) -> std::io::Result<Request<()>> {
+        let invalid =
+            |e: &dyn std::fmt::Display| Error::new(ErrorKind::InvalidInput, e.to_string());
+        let addr = SocketAddr::new(self.ip_dst, self.port_dst);
+        let mut request = format!("ws://{addr}{}", self.path)
+            .into_client_request()
+            .map_err(|e| invalid(&e))?;
+        let headers = request.headers_mut();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::try_from(name).map_err(|e| invalid(&e))?,
+                HeaderValue::try_from(value).map_err(|e| invalid(&e))?,
+            );
+        }
+        if !self.subprotocols.is_empty() {
+            // Names are compared with the response untrimmed
+            let protocols = self.subprotocols.join(",");
+            headers.insert(
+                ws::SUBPROTOCOL_HEADER,
+                HeaderValue::try_from(protocols).map_err(|e| invalid(&e))?,
+            );
+        }
+        Ok(request)
+    }
+}
+
+make_simple_sock!(SimpleWsClient {
+    config: WsClientConfig,
+    stream: RefCell<Option<WsStream>>,
+    is_blocking: bool,
+    timeouts: (Option<Duration>, Option<Duration>),
+}, "ws-client"; info: {
+    fn semantics(&self) -> Semantics {
+        Semantics::Message
+    }
+});
+
+impl SimpleSock for SimpleWsClient {
+    fn open(&mut self) -> std::io::Result<()> {
+        let request = self.config.request()?;
+        let sock = sockopt::tcp_connect(
+            SocketAddr::new(self.config.ip_dst, self.config.port_dst),
+            self.config.interface.as_deref(),
+            false,
+            None,
+        )?;
+        ws::set_handshake_timeout(&sock)?;
+        // Response is validated by the handshake, e.g. the subprotocol
+        let (socket, _) = tungstenite::client(request, sock).map_err(ws::handshake_error)?;
+        let stream = WsStream::new(socket, self.is_blocking)?;
+        stream.set_timeout(self.timeouts.0, self.timeouts.1)?;
+        self.stream = RefCell::new(Some(stream));
+        Ok(())
+    }
+    fn close(&mut self) {
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            stream.close();
+        }
+    }
+    fn read(&self, data: &mut [u8], sz: usize) -> std::io::Result<usize> {
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            return match stream.recv(data[..sz].as_mut())? {
+                Some(count) => Ok(count),
+                // Peer has closed the connection, no data will come
+                None => Err(error::Error::Eof.into()),
+            };
+        }
+        Err(Error::from(ErrorKind::NotConnected))
+    }
+    fn write(&self, data: &[u8], sz: usize) -> std::io::Result<usize> {
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            return match stream.send(data[..sz].as_ref()) {
+                // Blocking send is pending only if the timeout is expired
+                Ok(0) if self.is_blocking => Err(Error::from(ErrorKind::TimedOut)),
+                count => count,
+            };
+        }
+        Err(Error::from(ErrorKind::NotConnected))
+    }
+}
+
+impl SockBlockCtl for SimpleWsClient {
+    fn set_block(&mut self, is_blocking: bool) -> std::io::Result<()> {
+        self.is_blocking = is_blocking;
+        Ok(())
+    }
+    // Applied to the stream when it is connected
+    fn set_timeout(
+        &mut self,
+        read: Option<Duration>,
+        write: Option<Duration>,
+    ) -> std::io::Result<()> {
+        self.timeouts = (read, write);
+        if let Some(stream) = self.stream.borrow().as_ref() {
+            stream.set_timeout(read, write)?;
+        }
+        Ok(())
+    }
+}
+
+impl SockPoll for SimpleWsClient {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        self.stream
+            .borrow()
+            .as_ref()
+            .map(|s| vec![poll::raw_handle(s.tcp())])
+    }
+}
+
+struct WsClientDoc;
+impl SockDocViewer for WsClientDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(WsClientConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let example = "{ \"ip_dst\": \"127.0.0.1\", \"port_dst\": 8080, \"path\": \"/feed\" }";
+        let example_auth = "{ \"ip_dst\": \"192.168.1.10\", \"port_dst\": 8080, \
+            \"path\": \"/feed\", \"subprotocols\": [ \"nmea.v2\", \"nmea.v1\" ], \
+            \"headers\": { \"Authorization\": \"Bearer 7f3a\" } }";
+        format!(
+            "{}: {}\n{}: {}",
+            "Connection to the feed", example,
+            "Authorized connection with subprotocol", example_auth,
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct WsClientFactory;
+
+impl WsClientFactory {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl SocketFactory for WsClientFactory {
+    fn create_sock(&self, params: SocketParams) -> std::io::Result<Box<dyn ComplexSock>> {
+        let ws_config: WsClientConfig = parse_params("ws-client", &params)?;
+
+        // Blocking by default
+        Ok(Box::new(SimpleWsClient::new(
+            ws_config,
+            RefCell::new(None),
+            true,
+            (None, None),
+        )))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
+        Box::new(WsClientDoc)
+    }
+    fn validate_params(&self, params: &SocketParams) -> std::io::Result<()> {
+        Ok(validate_params::<WsClientConfig>("ws-client", params)?)
+    }
+}
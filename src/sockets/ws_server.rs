@@ -0,0 +1,407 @@
+use crate::serde_helpers;
+use crate::sock::make_simple_sock;
+use crate::sock::params::{parse_params, validate_params};
+use crate::sock::{
+    ComplexSock, RawHandle, Semantics, SimpleSock, SockBlockCtl, SockDocViewer, SockPoll,
+    SocketFactory, SocketParams, poll,
+};
+use crate::sockets::sockopt;
+use crate::sockets::ws::{self, WsStream};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::{HeaderValue, StatusCode};
+
+/// Configuration for WebSocket server.
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct WsServerConfig {
+    /// Local IP address constrain of WebSocket server
+    #[serde(default = "serde_helpers::default_ip_local")]
+    ip_local: IpAddr,
+    /// Local port of WebSocket server
+    port_local: u16,
+    /// Network interface to bind server (SO_BINDTODEVICE, Linux only)
+    interface: Option<String>,
+    /// Path the clients must request, any path is accepted if not set
+    path: Option<String>,
+    /// Supported subprotocols in the order of preference. If set, the
+    /// client must request one of them
+    #[serde(default)]
+    subprotocols: Vec<String>,
+    /// Headers the client must send with the exact values, e.g.
+    /// `Authorization` with the token
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+/// Status and reason of the rejected request.
+type Rejection = (StatusCode, String);
+
+impl WsServerConfig {
+    /// Checks the request of the client and selects the subprotocol.
+    fn validate(&self, request: &Request) -> Result<Option<&str>, Rejection> {
+        let path = request.uri().path();
+        if self.path.as_ref().is_some_and(|expected| expected != path) {
+            return Err((StatusCode::NOT_FOUND, format!("Path {path} isn't found")));
+        }
+        for (name, expected) in &self.headers {
+            let value = request.headers().get(name).and_then(|v| v.to_str().ok());
+            if value != Some(expected.as_str()) {
+                return Err((
+                    StatusCode::UNAUTHORIZED,
+                    format!("Header {name} is missing or invalid"),
+                ));
+            }
+        }
+        if self.subprotocols.is_empty() {
+            return Ok(None);
+        }
+        let requested: Vec<&str> = request
+            .headers()
+            .get_all(ws::SUBPROTOCOL_HEADER)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(',').map(str::trim))
+            .collect();
+        match self
+            .subprotocols
+            .iter()
+            .find(|protocol| requested.contains(&protocol.as_str()))
+        {
+            Some(protocol) => Ok(Some(protocol)),
+            None => Err((
+                StatusCode::BAD_REQUEST,
+                format!("None of subprotocols {} is requested", self.subprotocols.join(", ")),
+            )),
+        }
+    }
+}
+
+type Clients = Arc<Mutex<Vec<(WsStream, SocketAddr)>>>;
+/// Read and write timeouts applied to every client connection.
+type ClientTimeouts = (Option<Duration>, Option<Duration>);
+
+/// Max time to wait for a new connection before the running flag is rechecked.
+const ACCEPT_POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+make_simple_sock!(WsServer {
+    config: Arc<WsServerConfig>,
+    clients: Clients,
+    blocking: Arc<AtomicBool>,
+    timeouts: Arc<Mutex<ClientTimeouts>>,
+    is_running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<io::Result<()>>>,
+    handshakes: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}, "ws-server", self, {
+    let mut descr = format!("{}{}", self.get_type_name(), self.get_id());
+    let clients = self.clients.lock().unwrap();
+    if !clients.is_empty() {
+        descr.push_str(", connected clients:");
+        for (_, addr) in clients.iter() {
+            descr.push_str(format!("\nClient {addr}").as_str());
+        }
+    }
+    descr
+}; info: {
+    fn semantics(&self) -> Semantics {
+        Semantics::Message
+    }
+});
+
+/// Completes the handshake of the client, rejected clients are dropped.
+// Error response of the handshake callback is defined by tungstenite
+#[allow(clippy::result_large_err)]
+fn accept(
+    config: &WsServerConfig,
+    stream: TcpStream,
+    blocking: bool,
+    (read, write): ClientTimeouts,
+) -> io::Result<WsStream> {
+    stream.set_nonblocking(false)?;
+    ws::set_handshake_timeout(&stream)?;
+    let callback = |request: &Request, mut response: Response| match config.validate(request) {
+        Ok(protocol) => {
+            if let Some(protocol) = protocol {
+                // Protocol names are checked by the client request already
+                let protocol = HeaderValue::from_str(protocol).unwrap();
+                response.headers_mut().insert(ws::SUBPROTOCOL_HEADER, protocol);
+            }
+            Ok(response)
+        }
+        Err((status, reason)) => {
+            let mut response = ErrorResponse::new(Some(reason));
+            *response.status_mut() = status;
+            Err(response)
+        }
+    };
+    let socket = tungstenite::accept_hdr(stream, callback).map_err(ws::handshake_error)?;
+    let ws = WsStream::new(socket, blocking)?;
+    ws.set_timeout(read, write)?;
+    Ok(ws)
+}
+
+impl SimpleSock for WsServer {
+    fn open(&mut self) -> io::Result<()> {
+        let cfg = &self.config;
+        let listener = sockopt::tcp_listen(
+            SocketAddr::new(cfg.ip_local, cfg.port_local),
+            cfg.interface.as_deref(),
+            None,
+            None,
+        )?;
+        listener.set_nonblocking(true)?;
+        self.is_running.store(true, Ordering::Relaxed);
+        let r = self.is_running.clone();
+        let config = self.config.clone();
+        let clients = self.clients.clone();
+        let b = self.blocking.clone();
+        let t = self.timeouts.clone();
+        let handshakes = self.handshakes.clone();
+        let listener_handle = poll::raw_handle(&listener);
+
+        self.handle = Some(thread::spawn(move || -> io::Result<()> {
+            while r.load(Ordering::Relaxed) {
+                let Ok((stream, addr)) = listener.accept() else {
+                    // Wait for the next connection, but wake up
+                    // periodically to check the running flag
+                    poll::wait_readable(&[listener_handle], ACCEPT_POLL_TIMEOUT)?;
+                    continue;
+                };
+                // Slow handshake doesn't stop accepting
+                let (config, clients) = (config.clone(), clients.clone());
+                let (blocking, timeouts) = (b.load(Ordering::Relaxed), *t.lock().unwrap());
+                let handle = thread::spawn(move || {
+                    match accept(&config, stream, blocking, timeouts) {
+                        Ok(ws) => clients.lock().unwrap().push((ws, addr)),
+                        Err(e) => tracing::info!("WebSocket client {addr} is rejected: {e}"),
+                    }
+                });
+                let mut handshakes = handshakes.lock().unwrap();
+                handshakes.retain(|handshake| !handshake.is_finished());
+                handshakes.push(handle);
+            }
+            Ok(())
+        }));
+        Ok(())
+    }
+    fn close(&mut self) {
+        self.is_running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            // Wait when listener thread is finished
+            let _ = handle.join();
+            for handshake in self.handshakes.lock().unwrap().drain(..) {
+                let _ = handshake.join();
+            }
+            let mut clients = self.clients.lock().unwrap();
+            for (ws, _) in clients.iter_mut() {
+                ws.close();
+            }
+            clients.clear();
+        }
+    }
+    fn read(&self, data: &mut [u8], sz: usize) -> io::Result<usize> {
+        let mut clients = self.clients.lock().unwrap();
+        let mut total: usize = 0;
+        let sz = sz.min(data.len());
+        // Closed and failed connections are removed
+        clients.retain_mut(|(ws, addr)| {
+            if total >= sz {
+                return true;
+            }
+            match ws.recv(&mut data[total..sz]) {
+                Ok(Some(len)) => {
+                    total += len;
+                    true
+                }
+                Ok(None) => false,
+                Err(e) => {
+                    tracing::trace!("Client {addr} is dropped: {e}");
+                    false
+                }
+            }
+        });
+        Ok(total)
+    }
+    fn write(&self, data: &[u8], sz: usize) -> io::Result<usize> {
+        let mut clients = self.clients.lock().unwrap();
+
+        // Data is broadcasted, so it is considered written even if some
+        // clients failed to receive it
+        for (ws, addr) in clients.iter_mut() {
+            if matches!(ws.send(&data[..sz]), Ok(len) if len > 0) {
+                tracing::trace!("Data sent to {}", addr);
+            }
+        }
+        Ok(sz)
+    }
+}
+
+impl SockBlockCtl for WsServer {
+    fn set_block(&mut self, is_blocking: bool) -> io::Result<()> {
+        self.blocking.store(is_blocking, Ordering::Relaxed);
+        Ok(())
+    }
+    fn set_timeout(&mut self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        *self.timeouts.lock().unwrap() = (read, write);
+        for (ws, _) in self.clients.lock().unwrap().iter() {
+            ws.set_timeout(read, write)?;
+        }
+        Ok(())
+    }
+}
+
+impl SockPoll for WsServer {
+    fn poll_handles(&self) -> Option<Vec<RawHandle>> {
+        let clients = self.clients.lock().unwrap();
+        Some(
+            clients
+                .iter()
+                .map(|(ws, _)| poll::raw_handle(ws.tcp()))
+                .collect(),
+        )
+    }
+}
+
+struct WsServerDoc;
+impl SockDocViewer for WsServerDoc {
+    fn get_full_scheme(&self) -> String {
+        let schema = schemars::schema_for!(WsServerConfig);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+    fn get_examples(&self) -> String {
+        let example = "{ \"port_local\": 8080 }";
+        let example_auth = "{ \"port_local\": 8080, \"path\": \"/feed\", \
+            \"subprotocols\": [ \"nmea.v2\" ], \
+            \"headers\": { \"Authorization\": \"Bearer 7f3a\" } }";
+        format!(
+            "{}: {}\n{}: {}",
+            "Server accepting any client", example,
+            "Server accepting authorized clients of the feed", example_auth,
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct WsServerFactory;
+
+impl WsServerFactory {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl SocketFactory for WsServerFactory {
+    fn create_sock(&self, params: SocketParams) -> io::Result<Box<dyn ComplexSock>> {
+        let config: WsServerConfig = parse_params("ws-server", &params)?;
+
+        // Blocking by default
+        Ok(Box::new(WsServer::new(
+            Arc::new(config),
+            Arc::new(Mutex::new(Vec::new())),
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(Mutex::new((None, None))),
+            Arc::new(AtomicBool::new(true)),
+            None,
+            Arc::new(Mutex::new(Vec::new())),
+        )))
+    }
+    fn create_doc_viewer(&self) -> Box<dyn SockDocViewer> {
+        Box::new(WsServerDoc)
+    }
+    fn validate_params(&self, params: &SocketParams) -> io::Result<()> {
+        Ok(validate_params::<WsServerConfig>("ws-server", params)?)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use super::*;
+    use crate::sockets::ws_client::WsClientFactory;
+    use std::time::Instant;
+
+    /// Reads from the socket until the data is received or time is out.
+    fn read_exact(sock: &dyn ComplexSock, len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4];
+        let started = Instant::now();
+        while data.len() < len && started.elapsed() < Duration::from_secs(5) {
+            let sz = sock.read(&mut buf, 4).unwrap();
+            data.extend_from_slice(&buf[..sz]);
+            thread::sleep(Duration::from_millis(1));
+        }
+        data
+    }
+
+    fn server(port: u16) -> Box<dyn ComplexSock> {
+        let params = serde_json::json!({
+            "port_local": port,
+            "path": "/feed",
+            "subprotocols": ["nmea.v2", "nmea.v1"],
+            "headers": { "Authorization": "Bearer 7f3a" },
+        });
+        let mut server = WsServerFactory::new()
+            .create_sock_blockctl(params.into(), false)
+            .unwrap();
+        server.open().unwrap();
+        server
+    }
+
+    fn client(params: serde_json::Value) -> io::Result<Box<dyn ComplexSock>> {
+        let mut client = WsClientFactory::new().create_sock_blockctl(params.into(), false)?;
+        client.open()?;
+        Ok(client)
+    }
+
+    #[test]
+    fn test_ws_authorized_client() {
+        let mut server = server(8128);
+        let mut client = client(serde_json::json!({
+            "ip_dst": "127.0.0.1",
+            "port_dst": 8128,
+            "path": "/feed",
+            "subprotocols": ["nmea.v1", "nmea.v2"],
+            "headers": { "authorization": "Bearer 7f3a" },
+        }))
+        .unwrap();
+
+        // Message longer than the read buffer is read in parts
+        client.write(b"$GPGGA,1", 8).unwrap();
+        assert_eq!(read_exact(server.as_ref(), 8), b"$GPGGA,1");
+        server.write(b"ack", 3).unwrap();
+        assert_eq!(read_exact(client.as_ref(), 3), b"ack");
+        client.close();
+        server.close();
+    }
+
+    #[test]
+    fn test_ws_rejected_clients() {
+        let mut server = server(8129);
+        let valid = serde_json::json!({
+            "ip_dst": "127.0.0.1",
+            "port_dst": 8129,
+            "path": "/feed",
+            "subprotocols": ["nmea.v1"],
+            "headers": { "Authorization": "Bearer 7f3a" },
+        });
+        let rejected = |key: &str, value: serde_json::Value| {
+            let mut params = valid.clone();
+            params[key] = value;
+            client(params).is_err()
+        };
+        assert!(rejected("path", "/".into()));
+        assert!(rejected("headers", serde_json::json!({ "Authorization": "Bearer 0000" })));
+        assert!(rejected("subprotocols", serde_json::json!(["nmea.v3"])));
+        assert!(rejected("subprotocols", serde_json::json!([])));
+        assert!(!server.get_description().contains("Client"));
+        let mut client = client(valid).unwrap();
+        client.close();
+        server.close();
+    }
+}